@@ -0,0 +1,45 @@
+use funboy_core::{
+    FunboyError,
+    output_style::{OutputStyle, StyledDisplay},
+};
+use serde_json::json;
+
+/// Exit code for mistakes the caller can fix such as invalid names or malformed files
+pub const USER_ERROR_EXIT_CODE: u8 = 2;
+/// Exit code for failures outside of the caller's control such as an unreachable database
+pub const INFRASTRUCTURE_ERROR_EXIT_CODE: u8 = 1;
+
+#[derive(Debug)]
+pub enum CliError {
+    Funboy(FunboyError),
+    User(String),
+    Infrastructure(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Funboy(FunboyError::UserInput(_)) | CliError::User(_) => USER_ERROR_EXIT_CODE,
+            CliError::Funboy(_) | CliError::Infrastructure(_) => INFRASTRUCTURE_ERROR_EXIT_CODE,
+        }
+    }
+
+    pub fn to_styled_string(&self, style: OutputStyle) -> String {
+        match (self, style) {
+            (CliError::Funboy(e), _) => e.to_styled_string(style),
+            (CliError::User(message), OutputStyle::Json) => {
+                json!({ "error": "user_input", "message": message }).to_string()
+            }
+            (CliError::Infrastructure(message), OutputStyle::Json) => {
+                json!({ "error": "infrastructure", "message": message }).to_string()
+            }
+            (CliError::User(message) | CliError::Infrastructure(message), _) => message.clone(),
+        }
+    }
+}
+
+impl From<FunboyError> for CliError {
+    fn from(value: FunboyError) -> Self {
+        CliError::Funboy(value)
+    }
+}
@@ -1,5 +1,871 @@
-use funboy_core;
+use std::{ops::ControlFlow, path::PathBuf, process::ExitCode, sync::Arc, time::Duration};
 
-fn main() {
-    println!("Hello, World!");
+use clap::{Args, Parser, Subcommand};
+use dotenvy::dotenv;
+use fsl_interpreter::FslInterpreter;
+use funboy_core::{
+    BulkOutcome, CodeValidation, Funboy,
+    output_style::OutputStyle,
+    substitute_kind::SubKind,
+    template_database::{
+        DB_URL_SCHEMES, DbPool, DbPoolOptions, Limit, OrderBy, SortOrder, SubstituteReceipt,
+        Template, TemplateDatabase, TemplateFilter, is_supported_db_url, maintenance::Backfill,
+    },
+    template_export::{ImportOptions, TemplateExport},
+};
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+
+use crate::cli_error::{CliError, USER_ERROR_EXIT_CODE};
+
+mod cli_error;
+
+/// Headless administration of funboy templates
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Database to connect to, read from DATABASE_URL or a .env file when not given
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: String,
+
+    /// Print structured JSON instead of plain text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generates text from input resolving templates and embedded code
+    Generate { input: String },
+    /// Manages templates
+    #[command(subcommand)]
+    Templates(TemplatesCommand),
+    /// Manages the substitutes of a template
+    #[command(subcommand)]
+    Subs(SubsCommand),
+    /// Writes every template and its substitutes as JSON
+    Export {
+        /// File to write to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Adds the templates and substitutes of a file written by export
+    Import {
+        file: PathBuf,
+        /// Keep the exported substitute ids, only allowed into templates without substitutes
+        #[arg(long)]
+        preserve_ids: bool,
+        /// Preserve ids even into templates that already have substitutes
+        #[arg(long, requires = "preserve_ids")]
+        force: bool,
+    },
+    /// Reports templates and substitutes that will fail to generate, exits with 2 when any are found
+    Lint,
+    /// Previews migrations and runs backfills of rows that predate a column
+    #[command(subcommand)]
+    Maintenance(MaintenanceCommand),
+}
+
+#[derive(Subcommand)]
+enum MaintenanceCommand {
+    /// Lists the migrations the next start would apply and unfinished backfills without changing anything
+    Status,
+    /// Runs a backfill in batches, an interrupted run continues where it stopped
+    Backfill {
+        name: String,
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+        /// Stop after this many batches, run again to continue
+        #[arg(long)]
+        max_batches: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplatesCommand {
+    List {
+        #[arg(long)]
+        search: Option<String>,
+    },
+    Add {
+        name: String,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    Delete {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Shows the description, settings and examples of a template
+    Info {
+        name: String,
+    },
+    /// Samples random substitutes of a template and compares how often each came up with how
+    /// often it should have
+    Audit {
+        name: String,
+        #[arg(long, default_value_t = 10000)]
+        draws: usize,
+        /// How many of the substitutes furthest from their expected draws are listed
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Sets the description of a template, leave it out to remove the description
+    Describe {
+        name: String,
+        description: Option<String>,
+    },
+    /// Lists the templates matching a filter
+    Select(FilterArgs),
+    /// Deletes the templates matching a filter, run without --confirm first to get a token
+    DeleteMatching {
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[arg(long)]
+        confirm: Option<String>,
+    },
+    /// Archives the templates matching a filter, run without --confirm first to get a token
+    ArchiveMatching {
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[arg(long)]
+        confirm: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    /// Template name glob where * matches anything and ? a single character
+    #[arg(long)]
+    name: Option<String>,
+    #[arg(long)]
+    min_subs: Option<i64>,
+    #[arg(long)]
+    max_subs: Option<i64>,
+    /// Only templates created more than this many days ago
+    #[arg(long)]
+    older_than_days: Option<u64>,
+    #[arg(long)]
+    archived: Option<bool>,
+}
+
+impl FilterArgs {
+    fn into_filter(self) -> TemplateFilter {
+        TemplateFilter {
+            name_glob: self.name,
+            min_substitutes: self.min_subs,
+            max_substitutes: self.max_subs,
+            created_before: self.older_than_days.map(TemplateFilter::days_ago),
+            archived: self.archived,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum SubsCommand {
+    List {
+        #[arg(long)]
+        template: String,
+        #[arg(long)]
+        search: Option<String>,
+        /// Only list substitutes of this kind, one of prose, code or mixed
+        #[arg(long)]
+        kind: Option<String>,
+    },
+    Add {
+        #[arg(long)]
+        template: String,
+        #[arg(required = true)]
+        substitutes: Vec<String>,
+        /// Leave out substitutes whose embedded code fails to parse instead of warning
+        #[arg(long)]
+        strict: bool,
+    },
+    Delete {
+        #[arg(long)]
+        template: String,
+        #[arg(required = true)]
+        substitutes: Vec<String>,
+    },
+}
+
+/// What a command printed in both output styles
+struct Output {
+    text: String,
+    json: Value,
+    exit_code: u8,
+}
+
+impl Output {
+    fn new(text: String, json: Value) -> Self {
+        Self {
+            text,
+            json,
+            exit_code: 0,
+        }
+    }
+}
+
+fn substitute_receipt_json(receipt: &SubstituteReceipt) -> Value {
+    json!({
+        "updated": receipt.updated.iter().map(|sub| json!({ "id": sub.id, "name": sub.name })).collect::<Vec<_>>(),
+        "ignored": receipt.ignored.iter().map(|entry| json!({
+            "name": entry.name,
+            "reason": entry.reason.kind(),
+        })).collect::<Vec<_>>(),
+        "warnings": receipt.warnings.iter().map(|warning| json!({
+            "substitute": warning.substitute,
+            "message": warning.error.to_string(),
+        })).collect::<Vec<_>>(),
+        "refused": receipt.refused.as_ref().map(|refused| json!({
+            "substitutes": refused.substitutes,
+            "count": refused.count,
+            "cap": refused.cap,
+        })),
+    })
+}
+
+fn substitute_receipt_text(receipt: &SubstituteReceipt, updated: &str) -> String {
+    let mut lines = Vec::new();
+    if !receipt.updated.is_empty() {
+        lines.push(format!(
+            "{}: {}",
+            updated,
+            receipt.updated_to_styled_string(OutputStyle::Plain)
+        ));
+    }
+    if !receipt.ignored.is_empty() {
+        lines.push(format!(
+            "ignored: {}",
+            receipt.ignored_to_styled_string(OutputStyle::Plain)
+        ));
+    }
+    for warning in &receipt.warnings {
+        lines.push(format!(
+            "warning: \"{}\" {}",
+            warning.substitute, warning.error
+        ));
+    }
+    if let Some(refused) = &receipt.refused {
+        lines.push(format!(
+            "refused: {} (template holds {} of its {} substitute cap)",
+            OutputStyle::Plain.list(refused.substitutes.iter().map(|sub| sub.as_str())),
+            refused.count,
+            refused.cap
+        ));
+    }
+    lines.join("\n")
+}
+
+fn templates_json(templates: &[Template]) -> Value {
+    json!(
+        templates
+            .iter()
+            .map(|template| json!({ "id": template.id, "name": template.name }))
+            .collect::<Vec<_>>()
+    )
+}
+
+fn templates_text(templates: &[Template]) -> String {
+    templates
+        .iter()
+        .map(|template| template.name.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn bulk_output(outcome: BulkOutcome, verb: &str, command: &str) -> Output {
+    match outcome {
+        BulkOutcome::Preview(preview) => Output::new(
+            format!(
+                "{}\n{} templates would be {}, run again with --confirm {} to {} them",
+                templates_text(&preview.templates),
+                preview.templates.len(),
+                verb,
+                preview.token,
+                command
+            ),
+            json!({
+                "applied": false,
+                "templates": templates_json(&preview.templates),
+                "token": preview.token,
+            }),
+        ),
+        BulkOutcome::Applied(templates) => Output::new(
+            format!("{} {} templates", verb, templates.len()),
+            json!({ "applied": true, "templates": templates_json(&templates) }),
+        ),
+    }
+}
+
+async fn connect_pool(database_url: &str) -> Result<DbPool, CliError> {
+    if !is_supported_db_url(database_url) {
+        return Err(CliError::User(format!(
+            "database url must start with one of {:?} for this build",
+            DB_URL_SCHEMES
+        )));
+    }
+
+    let pool = DbPoolOptions::new()
+        .acquire_timeout(Duration::from_secs(5))
+        .connect(database_url)
+        .await
+        .map_err(|e| CliError::Infrastructure(format!("failed to connect to database: {}", e)))?;
+    Ok(pool)
+}
+
+async fn migrate(pool: &DbPool) -> Result<(), CliError> {
+    TemplateDatabase::migrate(pool)
+        .await
+        .map_err(|e| CliError::Infrastructure(format!("migration failed: {}", e)))
+}
+
+async fn connect(database_url: &str) -> Result<Funboy, CliError> {
+    let pool = connect_pool(database_url).await?;
+    migrate(&pool).await?;
+    Ok(Funboy::new(TemplateDatabase::new(Arc::new(pool))))
+}
+
+fn maintenance_error(e: sqlx::Error) -> CliError {
+    CliError::Infrastructure(format!("maintenance failed: {}", e))
+}
+
+/// Runs without migrating so status can preview what the next start would apply
+async fn run_maintenance(
+    database_url: &str,
+    command: MaintenanceCommand,
+) -> Result<Output, CliError> {
+    let pool = connect_pool(database_url).await?;
+    match command {
+        MaintenanceCommand::Status => {
+            let migrations = TemplateDatabase::pending_migrations(&pool)
+                .await
+                .map_err(maintenance_error)?;
+            let mut text: Vec<String> = migrations
+                .iter()
+                .map(|migration| {
+                    format!(
+                        "pending migration {} {}\n{}",
+                        migration.version,
+                        migration.description,
+                        migration.sql.trim()
+                    )
+                })
+                .collect();
+
+            // backfill progress is kept in a table a pending migration may still create
+            let backfills = if migrations.is_empty() {
+                TemplateDatabase::new(Arc::new(pool))
+                    .pending_backfills()
+                    .await
+                    .map_err(maintenance_error)?
+            } else {
+                text.push("backfills are listed once migrations are applied".to_string());
+                Vec::new()
+            };
+            text.extend(backfills.iter().map(|task| {
+                format!(
+                    "pending backfill {}: {} ({} rows left)",
+                    task.backfill.name(),
+                    task.backfill.description(),
+                    task.remaining
+                )
+            }));
+            if text.is_empty() {
+                text.push("nothing pending".to_string());
+            }
+
+            Ok(Output::new(
+                text.join("\n"),
+                json!({
+                    "migrations": migrations.iter().map(|migration| json!({
+                        "version": migration.version,
+                        "description": migration.description,
+                        "sql": migration.sql,
+                    })).collect::<Vec<_>>(),
+                    "backfills": backfills.iter().map(|task| json!({
+                        "name": task.backfill.name(),
+                        "description": task.backfill.description(),
+                        "last_id": task.last_id,
+                        "remaining": task.remaining,
+                    })).collect::<Vec<_>>(),
+                }),
+            ))
+        }
+        MaintenanceCommand::Backfill {
+            name,
+            batch_size,
+            max_batches,
+        } => {
+            let Some(backfill) = Backfill::parse(&name) else {
+                return Err(CliError::User(format!(
+                    "unknown backfill {}, expected one of {:?}",
+                    name,
+                    Backfill::ALL
+                        .iter()
+                        .map(|backfill| backfill.name())
+                        .collect::<Vec<_>>()
+                )));
+            };
+            migrate(&pool).await?;
+
+            let db = TemplateDatabase::new(Arc::new(pool));
+            let mut batches = 0;
+            let progress = db
+                .run_backfill(backfill, batch_size, |progress| {
+                    batches += 1;
+                    eprintln!(
+                        "{}: processed {}, {} rows left",
+                        backfill.name(),
+                        progress.processed,
+                        progress.remaining
+                    );
+                    if max_batches.is_some_and(|max_batches| batches >= max_batches) {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                })
+                .await
+                .map_err(maintenance_error)?;
+
+            let text = if progress.completed {
+                format!(
+                    "{} completed, processed {} rows",
+                    backfill.name(),
+                    progress.processed
+                )
+            } else {
+                format!(
+                    "{} stopped after {} rows with {} left, run again to continue",
+                    backfill.name(),
+                    progress.processed,
+                    progress.remaining
+                )
+            };
+            Ok(Output::new(
+                text,
+                json!({
+                    "name": backfill.name(),
+                    "processed": progress.processed,
+                    "remaining": progress.remaining,
+                    "completed": progress.completed,
+                }),
+            ))
+        }
+    }
+}
+
+async fn run_templates(funboy: &Funboy, command: TemplatesCommand) -> Result<Output, CliError> {
+    match command {
+        TemplatesCommand::List { search } => {
+            let templates = funboy
+                .get_templates(
+                    search.as_deref(),
+                    OrderBy::Name(SortOrder::Ascending),
+                    Limit::None,
+                )
+                .await?;
+            Ok(Output::new(
+                templates_text(&templates),
+                templates_json(&templates),
+            ))
+        }
+        TemplatesCommand::Select(filter) => {
+            let templates = funboy.select_templates(&filter.into_filter()).await?;
+            Ok(Output::new(
+                templates_text(&templates),
+                templates_json(&templates),
+            ))
+        }
+        TemplatesCommand::DeleteMatching { filter, confirm } => {
+            let outcome = funboy
+                .delete_templates_matching(&filter.into_filter(), confirm.as_deref())
+                .await?;
+            Ok(bulk_output(outcome, "deleted", "delete"))
+        }
+        TemplatesCommand::ArchiveMatching { filter, confirm } => {
+            let outcome = funboy
+                .archive_templates_matching(&filter.into_filter(), confirm.as_deref())
+                .await?;
+            Ok(bulk_output(outcome, "archived", "archive"))
+        }
+        TemplatesCommand::Add { name } => match funboy.create_template(&name).await? {
+            Some(template) => Ok(Output::new(
+                format!("created {}", template.name),
+                json!({ "created": true, "id": template.id, "name": template.name }),
+            )),
+            None => Ok(Output::new(
+                format!("{} already exists", name),
+                json!({ "created": false, "name": name }),
+            )),
+        },
+        TemplatesCommand::Rename { from, to } => match funboy.rename_template(&from, &to).await? {
+            Some(template) => Ok(Output::new(
+                format!("renamed {} to {}", from, template.name),
+                json!({ "renamed": true, "from": from, "to": template.name }),
+            )),
+            None => Err(CliError::User(format!("template {} does not exist", from))),
+        },
+        TemplatesCommand::Info { name } => {
+            let info = funboy.get_template_info(&name).await?;
+            let mut text = vec![
+                info.template.name.clone(),
+                format!(
+                    "description: {}",
+                    info.description.as_deref().unwrap_or("none")
+                ),
+                format!(
+                    "substitutes: {} ({} disabled)",
+                    info.substitutes, info.disabled_substitutes
+                ),
+                format!(
+                    "cap: {}{}",
+                    info.substitute_cap,
+                    if info.custom_cap { "" } else { " (default)" }
+                ),
+                format!("created at: {}", info.created_at),
+                format!("archived: {}", info.archived),
+            ];
+            text.extend(
+                info.examples
+                    .iter()
+                    .map(|example| format!("example: {}", example)),
+            );
+            Ok(Output::new(
+                text.join("\n"),
+                json!({
+                    "id": info.template.id,
+                    "name": info.template.name,
+                    "description": info.description,
+                    "substitutes": info.substitutes,
+                    "disabled_substitutes": info.disabled_substitutes,
+                    "substitute_cap": info.substitute_cap,
+                    "custom_cap": info.custom_cap,
+                    "created_at": info.created_at,
+                    "archived": info.archived,
+                    "examples": info.examples,
+                }),
+            ))
+        }
+        TemplatesCommand::Audit { name, draws, top } => {
+            let report = funboy.sample_distribution(&name, draws).await?;
+            let deviations = report.top_deviations(top);
+            let mut text = vec![
+                format!(
+                    "{} draws from {} of {} substitutes",
+                    report.draws,
+                    report.page_len,
+                    report.substitutes.len()
+                ),
+                format!(
+                    "chi-square: {:.2} over {} degrees of freedom (score {:.2})",
+                    report.chi_square(),
+                    report.degrees_of_freedom(),
+                    report.deviation_score()
+                ),
+            ];
+            text.extend(deviations.iter().map(|sub| {
+                format!(
+                    "{}: {} draws, {:.4} observed, {:.4} expected, {:+.2} deviation",
+                    sub.substitute.name,
+                    sub.draws,
+                    sub.observed_probability(report.draws),
+                    sub.expected_probability,
+                    sub.deviation(report.draws)
+                )
+            }));
+            Ok(Output::new(
+                text.join("\n"),
+                json!({
+                    "template": report.template,
+                    "draws": report.draws,
+                    "page_len": report.page_len,
+                    "chi_square": report.chi_square(),
+                    "degrees_of_freedom": report.degrees_of_freedom(),
+                    "deviation_score": report.deviation_score(),
+                    "substitutes": report.substitutes.iter().map(|sub| json!({
+                        "id": sub.substitute.id,
+                        "name": sub.substitute.name,
+                        "enabled": sub.substitute.enabled,
+                        "draws": sub.draws,
+                        "expected_probability": sub.expected_probability,
+                    })).collect::<Vec<_>>(),
+                }),
+            ))
+        }
+        TemplatesCommand::Describe { name, description } => {
+            funboy
+                .set_template_description(&name, description.as_deref())
+                .await?;
+            let description = funboy.get_template_description(&name).await?;
+            Ok(Output::new(
+                match &description {
+                    Some(_) => format!("set the description of {}", name),
+                    None => format!("removed the description of {}", name),
+                },
+                json!({ "name": name, "description": description }),
+            ))
+        }
+        TemplatesCommand::Delete { names } => {
+            let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+            let receipt = funboy.delete_templates(&names).await?;
+            let mut text = Vec::new();
+            if !receipt.updated.is_empty() {
+                text.push(format!(
+                    "deleted: {}",
+                    receipt.updated_to_styled_string(OutputStyle::Plain)
+                ));
+            }
+            if !receipt.ignored.is_empty() {
+                text.push(format!(
+                    "not found: {}",
+                    receipt.ignored_to_styled_string(OutputStyle::Plain)
+                ));
+            }
+            Ok(Output::new(
+                text.join("\n"),
+                json!({
+                    "deleted": receipt.updated.iter().map(|template| &template.name).collect::<Vec<_>>(),
+                    "ignored": receipt.ignored,
+                }),
+            ))
+        }
+    }
+}
+
+async fn run_subs(funboy: &Funboy, command: SubsCommand) -> Result<Output, CliError> {
+    match command {
+        SubsCommand::List {
+            template,
+            search,
+            kind,
+        } => {
+            let kind = match kind {
+                Some(kind) => Some(SubKind::parse(&kind).ok_or_else(|| {
+                    CliError::User(format!(
+                        "unknown kind {}, expected one of {:?}",
+                        kind,
+                        SubKind::ALL
+                            .iter()
+                            .map(|kind| kind.as_str())
+                            .collect::<Vec<_>>()
+                    ))
+                })?),
+                None => None,
+            };
+            let subs = funboy
+                .get_substitutes(
+                    &template,
+                    search.as_deref(),
+                    kind,
+                    OrderBy::Default,
+                    Limit::None,
+                )
+                .await?;
+            Ok(Output::new(
+                subs.iter()
+                    .map(|sub| sub.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                json!(
+                    subs.iter()
+                        .map(|sub| json!({
+                            "id": sub.id,
+                            "name": sub.name,
+                            "enabled": sub.enabled,
+                            "kind": sub.kind,
+                        }))
+                        .collect::<Vec<_>>()
+                ),
+            ))
+        }
+        SubsCommand::Add {
+            template,
+            substitutes,
+            strict,
+        } => {
+            let validation = if strict {
+                CodeValidation::Strict
+            } else {
+                CodeValidation::Warn
+            };
+            let substitutes: Vec<&str> = substitutes.iter().map(|sub| sub.as_str()).collect();
+            let receipt = funboy
+                .add_substitutes_with_validation(&template, &substitutes, validation)
+                .await?;
+            Ok(Output::new(
+                substitute_receipt_text(&receipt, "added"),
+                substitute_receipt_json(&receipt),
+            ))
+        }
+        SubsCommand::Delete {
+            template,
+            substitutes,
+        } => {
+            let substitutes: Vec<&str> = substitutes.iter().map(|sub| sub.as_str()).collect();
+            let receipt = funboy.delete_substitutes(&template, &substitutes).await?;
+            Ok(Output::new(
+                substitute_receipt_text(&receipt, "deleted"),
+                substitute_receipt_json(&receipt),
+            ))
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<Output, CliError> {
+    let command = match cli.command {
+        Command::Maintenance(command) => {
+            return run_maintenance(&cli.database_url, command).await;
+        }
+        command => command,
+    };
+    let funboy = connect(&cli.database_url).await?;
+
+    match command {
+        Command::Generate { input } => {
+            let output = funboy
+                .generate(&input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await?
+                .text;
+            funboy.flush_usage().await?;
+            funboy.flush_selection_state().await?;
+            Ok(Output::new(
+                output.clone(),
+                json!({ "input": input, "output": output }),
+            ))
+        }
+        Command::Templates(command) => run_templates(&funboy, command).await,
+        Command::Subs(command) => run_subs(&funboy, command).await,
+        Command::Export { output } => {
+            let export = funboy.export_templates().await?;
+            let json = json!(export);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json.to_string()).map_err(|e| {
+                        CliError::Infrastructure(format!(
+                            "failed to write {}: {}",
+                            path.display(),
+                            e
+                        ))
+                    })?;
+                    Ok(Output::new(
+                        format!(
+                            "exported {} templates to {}",
+                            export.templates.len(),
+                            path.display()
+                        ),
+                        json!({ "templates": export.templates.len(), "path": path }),
+                    ))
+                }
+                None => Ok(Output::new(json.to_string(), json)),
+            }
+        }
+        Command::Import {
+            file,
+            preserve_ids,
+            force,
+        } => {
+            let contents = std::fs::read_to_string(&file)
+                .map_err(|e| CliError::User(format!("failed to read {}: {}", file.display(), e)))?;
+            let export: TemplateExport = serde_json::from_str(&contents).map_err(|e| {
+                CliError::User(format!("{} is not a funboy export: {}", file.display(), e))
+            })?;
+
+            let options = ImportOptions {
+                preserve_ids,
+                force,
+            };
+            let report = funboy.import_templates(&export, options).await?;
+            let mut text = vec![format!(
+                "imported {} templates, added {} substitutes, ignored {}, refused {}",
+                report.templates, report.added, report.ignored, report.refused
+            )];
+            text.extend(
+                report.warnings.iter().map(|warning| {
+                    format!("warning: \"{}\" {}", warning.substitute, warning.error)
+                }),
+            );
+            text.extend(report.reassigned.iter().map(|reassigned| {
+                format!(
+                    "reassigned: \"{}\" id {} was taken, now {}",
+                    reassigned.substitute, reassigned.requested, reassigned.assigned
+                )
+            }));
+            Ok(Output::new(
+                text.join("\n"),
+                json!({
+                    "templates": report.templates,
+                    "added": report.added,
+                    "ignored": report.ignored,
+                    "refused": report.refused,
+                    "warnings": report.warnings.iter().map(|warning| json!({
+                        "substitute": warning.substitute,
+                        "message": warning.error.to_string(),
+                    })).collect::<Vec<_>>(),
+                    "reassigned": report.reassigned.iter().map(|reassigned| json!({
+                        "substitute": reassigned.substitute,
+                        "requested": reassigned.requested,
+                        "assigned": reassigned.assigned,
+                    })).collect::<Vec<_>>(),
+                }),
+            ))
+        }
+        Command::Lint => {
+            let issues = funboy.lint().await?;
+            let mut output = Output::new(
+                issues
+                    .iter()
+                    .map(|issue| issue.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                json!(
+                    issues
+                        .iter()
+                        .map(|issue| json!({
+                            "kind": issue.kind(),
+                            "template": issue.template(),
+                            "message": issue.to_string(),
+                        }))
+                        .collect::<Vec<_>>()
+                ),
+            );
+            if !issues.is_empty() {
+                output.exit_code = USER_ERROR_EXIT_CODE;
+            }
+            Ok(output)
+        }
+        Command::Maintenance(_) => unreachable!("maintenance runs before connecting"),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+    let cli = Cli::parse();
+    let style = if cli.json {
+        OutputStyle::Json
+    } else {
+        OutputStyle::Plain
+    };
+
+    match run(cli).await {
+        Ok(output) => {
+            match style {
+                OutputStyle::Json => println!("{}", output.json),
+                _ if output.text.is_empty() => {}
+                _ => println!("{}", output.text),
+            }
+            ExitCode::from(output.exit_code)
+        }
+        Err(e) => {
+            eprintln!("{}", e.to_styled_string(style));
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""Compares the latest criterion results against the committed baseline.
+
+Run `cargo bench -p funboy-core --bench generation_pipeline` first, then:
+
+    benches/check_baseline.py            fails when a benchmark is slower than allowed
+    benches/check_baseline.py --update   records the latest results as the new baseline
+
+A benchmark regresses when its mean exceeds the baseline by more than the tolerance in
+baseline.json. Benchmarks without a recorded baseline are reported but never fail.
+"""
+
+import argparse
+import json
+import sys
+from pathlib import Path
+
+BENCHES_DIR = Path(__file__).resolve().parent
+BASELINE_PATH = BENCHES_DIR / "baseline.json"
+CRITERION_DIR = BENCHES_DIR.parent.parent / "target" / "criterion"
+
+
+def latest_mean_ns(criterion_dir, benchmark):
+    estimates = criterion_dir / benchmark / "new" / "estimates.json"
+    if not estimates.exists():
+        return None
+    with open(estimates) as f:
+        return json.load(f)["mean"]["point_estimate"]
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--update", action="store_true", help="record the latest results")
+    parser.add_argument("--tolerance", type=float, help="override the tolerance in baseline.json")
+    parser.add_argument("--criterion-dir", type=Path, default=CRITERION_DIR)
+    args = parser.parse_args()
+
+    with open(BASELINE_PATH) as f:
+        baseline = json.load(f)
+    tolerance = args.tolerance if args.tolerance is not None else baseline["tolerance"]
+
+    regressions = []
+    for benchmark, baseline_ns in baseline["mean_ns"].items():
+        latest_ns = latest_mean_ns(args.criterion_dir, benchmark)
+        if latest_ns is None:
+            print(f"{benchmark}: no results, run the benchmarks first")
+            continue
+
+        if args.update:
+            baseline["mean_ns"][benchmark] = round(latest_ns)
+            print(f"{benchmark}: recorded {latest_ns:.0f}ns")
+        elif baseline_ns is None:
+            print(f"{benchmark}: {latest_ns:.0f}ns, no baseline recorded")
+        else:
+            change = latest_ns / baseline_ns - 1
+            print(f"{benchmark}: {latest_ns:.0f}ns ({change:+.1%} against {baseline_ns}ns)")
+            if change > tolerance:
+                regressions.append(benchmark)
+
+    if args.update:
+        with open(BASELINE_PATH, "w") as f:
+            json.dump(baseline, f, indent=2)
+            f.write("\n")
+        return 0
+
+    if regressions:
+        print(f"regressed by more than {tolerance:.0%}: {', '.join(regressions)}")
+        return 1
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,86 @@
+//! How long a trivial generation waits while a busy script runs on the same worker
+//!
+//! Run with `cargo bench -p funboy-core`. The `inline` case interprets the busy script on the
+//! worker itself so the trivial generation waits for it to finish, `blocking` moves it to a
+//! blocking thread with [`Funboy::with_blocking_threshold`] so the trivial generation only
+//! waits for its own work.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use fsl_interpreter::FslInterpreter;
+use funboy_core::{
+    Funboy,
+    template_database::{DEBUG_DB_URL, DbPool, TemplateDatabase},
+};
+use tokio::{runtime::Runtime, sync::Mutex};
+
+const BUSY_INPUT: &str = "{repeat(20000, print(\"busy\"))}";
+const TRIVIAL_INPUT: &str = "{print(\"hi\")}";
+
+/// A single worker so the busy script and the trivial generation compete for it
+fn single_worker_runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// Neither input uses templates so the database is never reached
+fn funboy() -> Funboy {
+    let pool = DbPool::connect_lazy(DEBUG_DB_URL).unwrap();
+    Funboy::new(TemplateDatabase::new(Arc::new(pool)))
+}
+
+fn new_interpreter() -> Arc<Mutex<FslInterpreter>> {
+    Arc::new(Mutex::new(FslInterpreter::new()))
+}
+
+/// Time from starting the trivial generation until it finishes with the busy one in flight
+fn trivial_latency(runtime: &Runtime, funboy: Arc<Funboy>) -> Duration {
+    runtime.block_on(async {
+        let busy = {
+            let funboy = funboy.clone();
+            tokio::spawn(async move { funboy.generate(BUSY_INPUT, new_interpreter()).await })
+        };
+        tokio::task::yield_now().await;
+
+        let start = Instant::now();
+        let trivial =
+            tokio::spawn(async move { funboy.generate(TRIVIAL_INPUT, new_interpreter()).await });
+        trivial.await.unwrap().unwrap();
+        let elapsed = start.elapsed();
+
+        busy.await.unwrap().unwrap();
+        elapsed
+    })
+}
+
+fn generation_latency(c: &mut Criterion) {
+    let runtime = single_worker_runtime();
+    let inline = Arc::new(funboy());
+    let blocking = Arc::new(funboy().with_blocking_threshold(0));
+
+    let mut group = c.benchmark_group("trivial_generation_beside_busy_script");
+    group.bench_function("inline", |b| {
+        b.iter_custom(|iters| {
+            (0..iters)
+                .map(|_| trivial_latency(&runtime, inline.clone()))
+                .sum()
+        })
+    });
+    group.bench_function("blocking", |b| {
+        b.iter_custom(|iters| {
+            (0..iters)
+                .map(|_| trivial_latency(&runtime, blocking.clone()))
+                .sum()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, generation_latency);
+criterion_main!(benches);
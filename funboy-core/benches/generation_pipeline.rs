@@ -0,0 +1,136 @@
+//! Throughput of each stage of the generation pipeline
+//!
+//! Run with `cargo bench -p funboy-core --bench generation_pipeline`, then compare against the
+//! committed baseline with `benches/check_baseline.py`. Substitution runs against a
+//! [`TemplateMap`] so only the substitutor is measured, `generate` runs against the debug
+//! database which is in memory when the `sqlite` feature is enabled.
+use std::sync::Arc;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use fsl_interpreter::FslInterpreter;
+use funboy_core::{
+    Funboy,
+    template_database::{DEBUG_DB_URL, DbPoolOptions, TemplateDatabase},
+    template_substitutor::{TemplateMap, TemplateSubstitutor},
+};
+use tokio::{runtime::Runtime, sync::Mutex};
+
+const TEMPLATE_REFERENCES: usize = 100;
+const INPUT_BYTES: usize = 10 * 1024;
+const NESTING_LEVELS: usize = 5;
+const GENERATE_TEMPLATE: &str = "bench_noun";
+const GENERATE_SUBSTITUTES: usize = 1000;
+
+const FSL_INPUT: &str = concat!(
+    "The {store(0, n) while(not(eq(n, 10)), print(n, \" \"), store(add(1, n), n))} count. ",
+    "{store(\"apple\", \"pear\", \"plum\", fruits) print(upper(index(1, fruits)))} is shouted. ",
+    "{if_then_else(gt(random_range(1, 10), 5), print(\"high\"), print(\"low\"))} roll, ",
+    "{repeat(20, print(capitalize(\"word \")))}",
+    "{print(concat(\"a\", \"b\", \"c\"), mul(4, 4), sub(10, 3))} and ",
+    "{print(length(\"length of this text\"))} characters.",
+);
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// About 10KB of prose with 100 references spread across ten templates
+fn single_pass_input() -> (String, TemplateMap) {
+    let filler_len = INPUT_BYTES / TEMPLATE_REFERENCES;
+    let filler = "lorem ipsum dolor sit amet ".repeat(filler_len / 27 + 1);
+    let mut input = String::with_capacity(INPUT_BYTES + TEMPLATE_REFERENCES * 8);
+    for i in 0..TEMPLATE_REFERENCES {
+        input.push_str(&filler[..filler_len - 8]);
+        input.push_str(&format!("^noun_{} ", i % 10));
+    }
+    let templates = (0..10).map(|i| (format!("noun_{}", i), format!("thing {}", i)));
+    (input, templates.collect())
+}
+
+/// Each level refers to the next one twice so every level is resolved on its own pass
+fn nested_templates() -> TemplateMap {
+    let mut templates = TemplateMap::new();
+    for level in 0..NESTING_LEVELS - 1 {
+        templates.insert(
+            format!("level_{}", level),
+            format!("({} ^level_{} ^level_{})", level, level + 1, level + 1),
+        );
+    }
+    templates.insert(format!("level_{}", NESTING_LEVELS - 1), "leaf");
+    templates
+}
+
+/// Fills a fresh copy of the generate template with distinct substitutes
+async fn seeded_funboy() -> Funboy {
+    // A single connection keeps every query on the same in memory sqlite database
+    let pool = DbPoolOptions::new()
+        .max_connections(1)
+        .connect(DEBUG_DB_URL)
+        .await
+        .unwrap();
+    TemplateDatabase::migrate(&pool).await.unwrap();
+
+    let funboy = Funboy::new(TemplateDatabase::new(Arc::new(pool)));
+    funboy.delete_templates(&[GENERATE_TEMPLATE]).await.ok();
+    let substitutes: Vec<String> = (0..GENERATE_SUBSTITUTES)
+        .map(|i| format!("substitute number {}", i))
+        .collect();
+    let substitutes: Vec<&str> = substitutes.iter().map(String::as_str).collect();
+    funboy
+        .add_substitutes(GENERATE_TEMPLATE, &substitutes)
+        .await
+        .unwrap();
+    funboy
+}
+
+fn substitution(c: &mut Criterion) {
+    let runtime = runtime();
+    let substitutor = runtime.block_on(TemplateSubstitutor::default());
+
+    let (input, templates) = single_pass_input();
+    c.bench_function("substitute_single_pass", |b| {
+        b.iter(|| runtime.block_on(substitutor.substitute(&input, &templates.mapper())))
+    });
+
+    let templates = nested_templates();
+    let input = "^level_0 ".repeat(10);
+    c.bench_function("substitute_recursively_nested", |b| {
+        b.iter(|| {
+            runtime.block_on(substitutor.substitute_recursively(input.clone(), templates.mapper()))
+        })
+    });
+}
+
+fn interpretation(c: &mut Criterion) {
+    let runtime = runtime();
+    c.bench_function("interpret_embedded_code", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                FslInterpreter::new()
+                    .interpret_embedded_code(FSL_INPUT)
+                    .await
+                    .unwrap()
+            })
+        })
+    });
+}
+
+fn generation(c: &mut Criterion) {
+    let runtime = runtime();
+    let funboy = runtime.block_on(seeded_funboy());
+    let input = format!("I found ^{} and ^{}.", GENERATE_TEMPLATE, GENERATE_TEMPLATE);
+    c.bench_function("generate_from_store", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let interpreter = Arc::new(Mutex::new(FslInterpreter::new()));
+                funboy.generate(&input, interpreter).await.unwrap()
+            })
+        })
+    });
+}
+
+criterion_group!(benches, substitution, interpretation, generation);
+criterion_main!(benches);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS examples (
+	id BIGSERIAL PRIMARY KEY,
+	template_id BIGINT NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+	text TEXT NOT NULL CHECK (length(text) <= 500),
+	position INT NOT NULL CHECK (position >= 0 AND position < 3),
+	UNIQUE(template_id, position)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS prompt_presets (
+	id BIGSERIAL PRIMARY KEY,
+	name TEXT NOT NULL CHECK (length(name) <= 100),
+	owner_id BIGINT NOT NULL,
+	body TEXT NOT NULL CHECK (length(body) <= 4000),
+	wrap_prompt BOOLEAN NOT NULL,
+	UNIQUE(name, owner_id)
+);
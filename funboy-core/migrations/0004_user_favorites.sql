@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS user_favorites (
+	id BIGSERIAL PRIMARY KEY,
+	user_id BIGINT NOT NULL,
+	template_id BIGINT NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+	UNIQUE(user_id, template_id)
+);
@@ -0,0 +1,3 @@
+-- Unix seconds so filters bind the same integer type on every backend
+ALTER TABLE templates ADD COLUMN created_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM NOW())::BIGINT);
+ALTER TABLE templates ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Disabled substitutes stay listed but are never picked when generating
+ALTER TABLE substitutes ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT TRUE;
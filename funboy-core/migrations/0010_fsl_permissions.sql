@@ -0,0 +1,6 @@
+-- Roles allowed to use FSL commands that send messages, a guild without rows allows everyone
+CREATE TABLE IF NOT EXISTS fsl_allowed_roles (
+	guild_id BIGINT NOT NULL,
+	role_id BIGINT NOT NULL,
+	PRIMARY KEY (guild_id, role_id)
+);
@@ -0,0 +1,6 @@
+-- Channels generation is limited to, a guild without rows allows every channel
+CREATE TABLE IF NOT EXISTS generate_channels (
+	guild_id BIGINT NOT NULL,
+	channel_id BIGINT NOT NULL,
+	PRIMARY KEY (guild_id, channel_id)
+);
@@ -0,0 +1,10 @@
+-- Phrases that make the bot reply in a guild with a generation of template_input
+CREATE TABLE IF NOT EXISTS triggers (
+	id BIGSERIAL PRIMARY KEY,
+	guild_id BIGINT NOT NULL,
+	pattern TEXT NOT NULL CHECK (length(pattern) <= 200),
+	match_mode TEXT NOT NULL,
+	template_input TEXT NOT NULL CHECK (length(template_input) <= 4000),
+	cooldown_secs BIGINT NOT NULL,
+	UNIQUE(guild_id, match_mode, pattern)
+);
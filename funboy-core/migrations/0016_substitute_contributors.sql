@@ -0,0 +1,6 @@
+-- Who added each substitute and from which guild, both are NULL for substitutes added before they
+-- were recorded or from outside of Discord
+ALTER TABLE substitutes ADD COLUMN created_by BIGINT;
+ALTER TABLE substitutes ADD COLUMN created_in_guild BIGINT;
+ALTER TABLE substitutes ADD COLUMN created_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM NOW())::BIGINT);
+CREATE INDEX IF NOT EXISTS substitutes_created_by ON substitutes (created_by);
@@ -0,0 +1,4 @@
+-- Whether each substitute is prose, code or mixed, classified when it is written. Rows added
+-- before this migration are classified by the substitute_kind backfill
+ALTER TABLE substitutes ADD COLUMN kind TEXT NOT NULL DEFAULT 'prose';
+CREATE INDEX IF NOT EXISTS substitutes_template_kind ON substitutes (template_id, kind);
@@ -0,0 +1,2 @@
+-- Notes from curators on why a template exists or how its substitutes should be written
+ALTER TABLE templates ADD COLUMN description TEXT CHECK (length(description) <= 1000);
@@ -0,0 +1,6 @@
+-- How generation picks substitutes of a template, NULL picks them uniformly at random
+ALTER TABLE template_settings ADD COLUMN selection_strategy TEXT;
+-- Substitute round robin picked last, written from memory periodically
+ALTER TABLE template_settings ADD COLUMN round_robin_cursor BIGINT;
+-- Unix milliseconds least recently used last picked a substitute, written from memory periodically
+ALTER TABLE substitutes ADD COLUMN last_used_at BIGINT;
@@ -0,0 +1,4 @@
+-- Label put on Ollama responses in a guild while label_ai_output is on, NULL text uses the default
+ALTER TABLE guild_settings ADD COLUMN label_ai_output BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE guild_settings ADD COLUMN ai_label TEXT;
+ALTER TABLE guild_settings ADD COLUMN ai_label_placement TEXT;
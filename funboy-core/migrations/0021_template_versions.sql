@@ -0,0 +1,35 @@
+-- Version of each template, public pages build their ETags from it so every process sharing the
+-- database sees the same ones. Changes only clear the version of their template, templates without
+-- one are stamped from a sequence when versions are next read so a batch of changes costs a single
+-- bump however many rows it touches
+CREATE SEQUENCE IF NOT EXISTS template_versions;
+ALTER TABLE templates ADD COLUMN version BIGINT;
+CREATE INDEX IF NOT EXISTS templates_unversioned ON templates (id) WHERE version IS NULL;
+
+CREATE OR REPLACE FUNCTION clear_template_version() RETURNS TRIGGER AS $$
+BEGIN
+	NEW.version := NULL;
+	RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER templates_version BEFORE UPDATE OF name, archived, description ON templates
+FOR EACH ROW EXECUTE FUNCTION clear_template_version();
+
+-- Templates already cleared by an earlier row of the batch are left alone
+CREATE OR REPLACE FUNCTION clear_substitute_template_version() RETURNS TRIGGER AS $$
+BEGIN
+	IF TG_OP <> 'INSERT' THEN
+		UPDATE templates SET version = NULL WHERE id = OLD.template_id AND version IS NOT NULL;
+	END IF;
+	IF TG_OP <> 'DELETE' THEN
+		UPDATE templates SET version = NULL WHERE id = NEW.template_id AND version IS NOT NULL;
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- last_used_at changes on every generation and isn't shown anywhere public so it doesn't count
+CREATE TRIGGER substitutes_template_version
+AFTER INSERT OR DELETE OR UPDATE OF name, kind, enabled, template_id ON substitutes
+FOR EACH ROW EXECUTE FUNCTION clear_substitute_template_version();
@@ -0,0 +1,11 @@
+CREATE TABLE templates (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	name TEXT NOT NULL UNIQUE CHECK (name <> '' AND name NOT GLOB '*[^a-z0-9_]*' AND length(name) <= 255)
+);
+
+CREATE TABLE IF NOT EXISTS substitutes (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	name TEXT NOT NULL CHECK (length(name) <= 16000),
+	template_id INTEGER NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+	UNIQUE(name, template_id)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS examples (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	template_id INTEGER NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+	text TEXT NOT NULL CHECK (length(text) <= 500),
+	position INTEGER NOT NULL CHECK (position >= 0 AND position < 3),
+	UNIQUE(template_id, position)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS prompt_presets (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	name TEXT NOT NULL CHECK (length(name) <= 100),
+	owner_id INTEGER NOT NULL,
+	body TEXT NOT NULL CHECK (length(body) <= 4000),
+	wrap_prompt BOOLEAN NOT NULL,
+	UNIQUE(name, owner_id)
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS user_favorites (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	user_id INTEGER NOT NULL,
+	template_id INTEGER NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+	UNIQUE(user_id, template_id)
+);
@@ -0,0 +1,11 @@
+-- SQLite can't add a column with a non-constant default so new rows are stamped by a trigger
+ALTER TABLE templates ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE templates ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE templates SET created_at = CAST(strftime('%s', 'now') AS INTEGER);
+
+CREATE TRIGGER IF NOT EXISTS templates_created_at AFTER INSERT ON templates
+WHEN NEW.created_at = 0
+BEGIN
+	UPDATE templates SET created_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE id = NEW.id;
+END;
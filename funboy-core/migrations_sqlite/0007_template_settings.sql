@@ -0,0 +1,5 @@
+-- Per template overrides, a missing row or NULL column falls back to the bot wide default
+CREATE TABLE IF NOT EXISTS template_settings (
+	template_id INTEGER PRIMARY KEY REFERENCES templates(id) ON DELETE CASCADE,
+	max_substitutes INTEGER
+);
@@ -0,0 +1,16 @@
+-- Tracks played in each guild, started_at is in unix seconds
+CREATE TABLE IF NOT EXISTS playback_events (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	guild_id INTEGER NOT NULL,
+	track_name TEXT NOT NULL,
+	track_url TEXT,
+	requested_by INTEGER NOT NULL,
+	started_at INTEGER NOT NULL,
+	completed BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE INDEX IF NOT EXISTS playback_events_guild_started ON playback_events (guild_id, started_at);
+
+CREATE TABLE IF NOT EXISTS guild_settings (
+	guild_id INTEGER PRIMARY KEY,
+	announcement_channel_id INTEGER
+);
@@ -0,0 +1,9 @@
+-- How often substitutes of each template were picked per day, day counts days since the unix epoch
+-- and guild_id is NULL for generations outside of a guild
+CREATE TABLE IF NOT EXISTS template_usage_daily (
+	template_id INTEGER NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+	guild_id INTEGER,
+	day INTEGER NOT NULL,
+	count INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS template_usage_daily_guild_day ON template_usage_daily (guild_id, day);
@@ -0,0 +1,8 @@
+-- Guild shortcuts /generate expands when its whole input is an alias name
+CREATE TABLE IF NOT EXISTS aliases (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	guild_id INTEGER NOT NULL,
+	alias_name TEXT NOT NULL CHECK (length(alias_name) <= 255),
+	input_text TEXT NOT NULL CHECK (length(input_text) <= 4000),
+	UNIQUE(guild_id, alias_name)
+);
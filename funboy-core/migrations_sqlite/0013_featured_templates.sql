@@ -0,0 +1,15 @@
+-- Channels a template of the day is posted in, last_posted_day counts days since the unix epoch
+CREATE TABLE IF NOT EXISTS featured_channels (
+	guild_id INTEGER PRIMARY KEY,
+	channel_id INTEGER NOT NULL,
+	strategy TEXT NOT NULL,
+	last_posted_day INTEGER
+);
+
+-- Templates featured in each guild so recent ones aren't picked again
+CREATE TABLE IF NOT EXISTS featured_history (
+	guild_id INTEGER NOT NULL,
+	template_id INTEGER NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+	day INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS featured_history_guild_template ON featured_history (guild_id, template_id);
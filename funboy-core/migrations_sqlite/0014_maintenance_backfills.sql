@@ -0,0 +1,6 @@
+-- Progress of each backfill, last_id is the highest id already processed so runs can resume
+CREATE TABLE IF NOT EXISTS maintenance_backfills (
+	name TEXT PRIMARY KEY,
+	last_id INTEGER NOT NULL,
+	completed BOOLEAN NOT NULL DEFAULT FALSE
+);
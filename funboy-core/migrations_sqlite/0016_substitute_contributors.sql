@@ -0,0 +1,15 @@
+-- Who added each substitute and from which guild, both are NULL for substitutes added before they
+-- were recorded or from outside of Discord
+ALTER TABLE substitutes ADD COLUMN created_by INTEGER;
+ALTER TABLE substitutes ADD COLUMN created_in_guild INTEGER;
+-- SQLite can't add a column with a non-constant default so new rows are stamped by a trigger
+ALTER TABLE substitutes ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS substitutes_created_by ON substitutes (created_by);
+
+UPDATE substitutes SET created_at = CAST(strftime('%s', 'now') AS INTEGER);
+
+CREATE TRIGGER IF NOT EXISTS substitutes_created_at AFTER INSERT ON substitutes
+WHEN NEW.created_at = 0
+BEGIN
+	UPDATE substitutes SET created_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE id = NEW.id;
+END;
@@ -0,0 +1,37 @@
+-- Version of each template, public pages build their ETags from it so every process sharing the
+-- database sees the same ones. Changes only clear the version of their template, templates without
+-- one are stamped from the counter when versions are next read so a batch of changes costs a single
+-- bump however many rows it touches
+CREATE TABLE IF NOT EXISTS template_version (
+	id INTEGER PRIMARY KEY CHECK (id = 1),
+	latest INTEGER NOT NULL
+);
+INSERT INTO template_version (id, latest) VALUES (1, 0);
+
+ALTER TABLE templates ADD COLUMN version INTEGER;
+CREATE INDEX IF NOT EXISTS templates_unversioned ON templates (id) WHERE version IS NULL;
+
+CREATE TRIGGER IF NOT EXISTS templates_version
+AFTER UPDATE OF name, archived, description ON templates
+BEGIN
+	UPDATE templates SET version = NULL WHERE id = NEW.id;
+END;
+
+-- Templates already cleared by an earlier row of the batch are left alone
+CREATE TRIGGER IF NOT EXISTS substitutes_template_version_insert AFTER INSERT ON substitutes
+BEGIN
+	UPDATE templates SET version = NULL WHERE id = NEW.template_id AND version IS NOT NULL;
+END;
+
+-- last_used_at changes on every generation and isn't shown anywhere public so it doesn't count
+CREATE TRIGGER IF NOT EXISTS substitutes_template_version_update
+AFTER UPDATE OF name, kind, enabled, template_id ON substitutes
+BEGIN
+	UPDATE templates SET version = NULL
+	WHERE id IN (OLD.template_id, NEW.template_id) AND version IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS substitutes_template_version_delete AFTER DELETE ON substitutes
+BEGIN
+	UPDATE templates SET version = NULL WHERE id = OLD.template_id AND version IS NOT NULL;
+END;
@@ -0,0 +1,149 @@
+//! Labels guilds can put on responses from Ollama to mark them as AI-generated
+
+use std::fmt::Display;
+
+pub const DEFAULT_AI_LABEL: &str = "🤖 AI-generated";
+
+/// Which side of a response its label goes on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LabelPlacement {
+    /// A line above the response, posted with its first message
+    #[default]
+    Before,
+    /// A line below the response, posted with its last message
+    After,
+}
+
+impl LabelPlacement {
+    pub const ALL: [LabelPlacement; 2] = [LabelPlacement::Before, LabelPlacement::After];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LabelPlacement::Before => "before",
+            LabelPlacement::After => "after",
+        }
+    }
+
+    /// Reads a placement stored with [`LabelPlacement::as_str`]
+    pub fn parse(placement: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == placement)
+    }
+}
+
+impl Display for LabelPlacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AiLabel {
+    pub text: String,
+    pub placement: LabelPlacement,
+}
+
+impl Default for AiLabel {
+    fn default() -> Self {
+        Self {
+            text: DEFAULT_AI_LABEL.to_string(),
+            placement: LabelPlacement::default(),
+        }
+    }
+}
+
+impl AiLabel {
+    /// Puts the label on its own line on its side of response
+    ///
+    /// Apply it to the finished response, after anything that trims or cuts the text the model
+    /// returned, so the label never takes the place of generated text. Splitting the result into
+    /// messages keeps the label in the first message, or the last when placed after
+    pub fn apply(&self, response: &str) -> String {
+        match self.placement {
+            LabelPlacement::Before => format!("{}\n{}", self.text, response),
+            LabelPlacement::After => format!("{}\n{}", response, self.text),
+        }
+    }
+}
+
+/// Response with label applied when there is one
+pub fn label_response(response: &str, label: Option<&AiLabel>) -> String {
+    match label {
+        Some(label) => label.apply(response),
+        None => response.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod ai_label_test {
+    use crate::message_format::{DISCORD_CHARACTER_LIMIT, split_message};
+
+    use super::*;
+
+    fn long_response() -> String {
+        "word ".repeat(DISCORD_CHARACTER_LIMIT / 2)
+    }
+
+    #[test]
+    fn placements_round_trip() {
+        for placement in LabelPlacement::ALL {
+            assert_eq!(LabelPlacement::parse(placement.as_str()), Some(placement));
+        }
+        assert_eq!(LabelPlacement::parse("middle"), None);
+    }
+
+    #[test]
+    fn single_message_responses() {
+        let before = AiLabel::default();
+        assert_eq!(
+            label_response("hello", Some(&before)),
+            "🤖 AI-generated\nhello"
+        );
+
+        let after = AiLabel {
+            text: "[bot]".to_string(),
+            placement: LabelPlacement::After,
+        };
+        assert_eq!(label_response("hello", Some(&after)), "hello\n[bot]");
+        assert_eq!(label_response("hello", None), "hello");
+    }
+
+    #[test]
+    fn label_stays_in_one_message_when_split() {
+        let response = long_response();
+
+        let labelled = label_response(&response, Some(&AiLabel::default()));
+        let messages = split_message(&labelled);
+        assert!(messages.len() > 1);
+        assert!(messages[0].starts_with(DEFAULT_AI_LABEL));
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|message| message.contains(DEFAULT_AI_LABEL))
+                .count(),
+            1
+        );
+
+        let after = AiLabel {
+            placement: LabelPlacement::After,
+            ..AiLabel::default()
+        };
+        let labelled = label_response(&response, Some(&after));
+        let messages = split_message(&labelled);
+        assert!(messages.last().unwrap().ends_with(DEFAULT_AI_LABEL));
+        assert!(!messages[0].contains(DEFAULT_AI_LABEL));
+    }
+
+    #[test]
+    fn label_does_not_replace_trimmed_text() {
+        // A response cut down to its limit keeps every character it was cut to
+        let response: String = long_response().chars().take(100).collect();
+        let labelled = label_response(&response, Some(&AiLabel::default()));
+        assert!(labelled.ends_with(&response));
+        assert_eq!(
+            labelled.chars().count(),
+            response.chars().count() + DEFAULT_AI_LABEL.chars().count() + 1
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use rand::random_range;
+
+use crate::user_facing_error::UserFacingError;
+
+/// A number taken by the checked arithmetic commands, any Float makes the result a Float
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    /// Reads text written the way the interpreter writes numbers
+    ///
+    /// Text such as NaN or inf is not a number, only Float values can hold those
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        match text.parse::<i64>() {
+            Ok(int) => Some(Number::Int(int)),
+            Err(_) => text
+                .parse::<f64>()
+                .ok()
+                .filter(|float| float.is_finite())
+                .map(Number::Float),
+        }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(int) => int as f64,
+            Number::Float(float) => float,
+        }
+    }
+}
+
+/// A value compared by [`equals`], anything that isn't a number is compared as text
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Number(Number),
+    Text(String),
+}
+
+/// Folds numbers from left to right with int_op while all of them are Int and float_op once
+/// any is a Float
+///
+/// A zero divisor is rejected for both Int and Float before float_op can turn it into infinity
+/// or NaN, and so is any Float that isn't finite going in or coming out. An Int result that
+/// doesn't fit, such as i64::MIN / -1, is an overflow
+fn fold_checked(
+    command: &str,
+    numbers: &[Number],
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Number, UserFacingError> {
+    let not_finite = || UserFacingError::NumberNotFinite {
+        command: command.to_string(),
+    };
+    let overflow = || UserFacingError::IntegerOverflow {
+        command: command.to_string(),
+    };
+    let Some((first, rest)) = numbers.split_first() else {
+        return Err(not_finite());
+    };
+
+    let mut result = *first;
+    for number in rest {
+        if number.as_f64() == 0.0 {
+            return Err(UserFacingError::DivisionByZero {
+                command: command.to_string(),
+            });
+        }
+        result = match (result, *number) {
+            (Number::Int(lhs), Number::Int(rhs)) => {
+                Number::Int(int_op(lhs, rhs).ok_or_else(overflow)?)
+            }
+            (lhs, rhs) => {
+                let (lhs, rhs) = (lhs.as_f64(), rhs.as_f64());
+                if !lhs.is_finite() || !rhs.is_finite() {
+                    return Err(not_finite());
+                }
+                let result = float_op(lhs, rhs);
+                if !result.is_finite() {
+                    return Err(not_finite());
+                }
+                Number::Float(result)
+            }
+        };
+    }
+    Ok(result)
+}
+
+/// Divides numbers from left to right
+pub fn divide(command: &str, numbers: &[Number]) -> Result<Number, UserFacingError> {
+    fold_checked(command, numbers, i64::checked_div, |lhs, rhs| lhs / rhs)
+}
+
+/// The remainder of dividing numbers from left to right
+pub fn modulo(command: &str, numbers: &[Number]) -> Result<Number, UserFacingError> {
+    fold_checked(command, numbers, i64::checked_rem, |lhs, rhs| lhs % rhs)
+}
+
+/// Whether two values are equal, an Int and a Float are equal when they hold the same number
+///
+/// NaN is never equal to anything so comparing it is rejected rather than always false
+pub fn equals(lhs: &Operand, rhs: &Operand) -> Result<bool, UserFacingError> {
+    match (lhs, rhs) {
+        (Operand::Number(lhs), Operand::Number(rhs)) => {
+            if lhs.as_f64().is_nan() || rhs.as_f64().is_nan() {
+                return Err(UserFacingError::ComparedNan);
+            }
+            match (lhs, rhs) {
+                (Number::Int(lhs), Number::Int(rhs)) => Ok(lhs == rhs),
+                (lhs, rhs) => Ok(lhs.as_f64() == rhs.as_f64()),
+            }
+        }
+        (Operand::Text(lhs), Operand::Text(rhs)) => Ok(lhs == rhs),
+        _ => Ok(false),
+    }
+}
+
+/// A random number from min up to but not including max, equal bounds return min
+///
+/// Reversed bounds and Float bounds whose span isn't finite are rejected since sampling them
+/// panics
+pub fn random_in_range(min: Number, max: Number) -> Result<Number, UserFacingError> {
+    match (min, max) {
+        (Number::Int(min), Number::Int(max)) => match min.cmp(&max) {
+            std::cmp::Ordering::Greater => Err(UserFacingError::MinNotLessThanMax),
+            std::cmp::Ordering::Equal => Ok(Number::Int(min)),
+            std::cmp::Ordering::Less => Ok(Number::Int(random_range(min..max))),
+        },
+        (min, max) => {
+            let (min, max) = (min.as_f64(), max.as_f64());
+            if !(max - min).is_finite() {
+                Err(UserFacingError::RangeNotFinite)
+            } else if min > max {
+                Err(UserFacingError::MinNotLessThanMax)
+            } else if min == max {
+                Ok(Number::Float(min))
+            } else {
+                Ok(Number::Float(random_range(min..max)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod checked_math_test {
+    use super::*;
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(Number::parse("-3"), Some(Number::Int(-3)));
+        assert_eq!(Number::parse("2.5"), Some(Number::Float(2.5)));
+        assert_eq!(Number::parse("two"), None);
+        assert_eq!(Number::parse("NaN"), None);
+        assert_eq!(Number::parse("inf"), None);
+        assert_eq!(Number::parse("-infinity"), None);
+    }
+
+    #[test]
+    fn divides_like_the_builtin() {
+        assert_eq!(
+            divide(
+                "div",
+                &[Number::Float(10.0), Number::Float(2.0), Number::Float(2.0)]
+            ),
+            Ok(Number::Float(2.5))
+        );
+        assert_eq!(
+            divide("div", &[Number::Int(7), Number::Int(2)]),
+            Ok(Number::Int(3))
+        );
+        assert_eq!(
+            modulo("mod", &[Number::Int(2), Number::Int(4)]),
+            Ok(Number::Int(2))
+        );
+        assert_eq!(
+            modulo("mod", &[Number::Float(5.5), Number::Int(2)]),
+            Ok(Number::Float(1.5))
+        );
+    }
+
+    #[test]
+    fn float_division_by_zero_is_rejected() {
+        for numbers in [
+            [Number::Float(1.0), Number::Float(0.0)],
+            [Number::Float(1.0), Number::Float(-0.0)],
+            [Number::Int(1), Number::Float(0.0)],
+            [Number::Int(1), Number::Int(0)],
+        ] {
+            assert_eq!(
+                divide("div", &numbers),
+                Err(UserFacingError::DivisionByZero {
+                    command: "div".to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn modulo_by_zero_is_rejected() {
+        for numbers in [
+            [Number::Float(5.5), Number::Float(0.0)],
+            [Number::Int(5), Number::Int(0)],
+        ] {
+            assert_eq!(
+                modulo("mod", &numbers),
+                Err(UserFacingError::DivisionByZero {
+                    command: "mod".to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn non_finite_results_are_rejected() {
+        let not_finite = Err(UserFacingError::NumberNotFinite {
+            command: "div".to_string(),
+        });
+        assert_eq!(
+            divide("div", &[Number::Float(f64::MAX), Number::Float(0.5)]),
+            not_finite
+        );
+        assert_eq!(
+            divide("div", &[Number::Float(f64::INFINITY), Number::Int(2)]),
+            not_finite
+        );
+    }
+
+    #[test]
+    fn integer_overflow_is_rejected() {
+        let numbers = [Number::Int(i64::MIN), Number::Int(-1)];
+        assert_eq!(
+            divide("div", &numbers),
+            Err(UserFacingError::IntegerOverflow {
+                command: "div".to_string()
+            })
+        );
+        assert_eq!(
+            modulo("mod", &numbers),
+            Err(UserFacingError::IntegerOverflow {
+                command: "mod".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn nan_comparisons_are_rejected() {
+        let nan = Operand::Number(Number::Float(f64::NAN));
+        assert_eq!(
+            equals(&nan, &Operand::Number(Number::Int(1))),
+            Err(UserFacingError::ComparedNan)
+        );
+        assert_eq!(equals(&nan, &nan), Err(UserFacingError::ComparedNan));
+    }
+
+    #[test]
+    fn compares_values() {
+        let int = Operand::Number(Number::Int(1));
+        let float = Operand::Number(Number::Float(1.0));
+        let text = Operand::Text("1".to_string());
+        assert_eq!(equals(&int, &float), Ok(true));
+        assert_eq!(equals(&int, &Operand::Number(Number::Int(2))), Ok(false));
+        assert_eq!(equals(&text, &Operand::Text("1".to_string())), Ok(true));
+        assert_eq!(equals(&int, &text), Ok(false));
+    }
+
+    #[test]
+    fn reversed_random_range_bounds_are_rejected() {
+        assert_eq!(
+            random_in_range(Number::Int(6), Number::Int(1)),
+            Err(UserFacingError::MinNotLessThanMax)
+        );
+        assert_eq!(
+            random_in_range(Number::Float(6.0), Number::Int(1)),
+            Err(UserFacingError::MinNotLessThanMax)
+        );
+        assert_eq!(
+            random_in_range(Number::Float(f64::NEG_INFINITY), Number::Float(1.0)),
+            Err(UserFacingError::RangeNotFinite)
+        );
+    }
+
+    #[test]
+    fn random_range_stays_in_bounds() {
+        assert_eq!(
+            random_in_range(Number::Int(3), Number::Int(3)),
+            Ok(Number::Int(3))
+        );
+        assert_eq!(
+            random_in_range(Number::Float(2.5), Number::Float(2.5)),
+            Ok(Number::Float(2.5))
+        );
+        for _ in 0..100 {
+            let Ok(Number::Int(int)) = random_in_range(Number::Int(1), Number::Int(4)) else {
+                panic!("Int bounds should give an Int");
+            };
+            assert!((1..4).contains(&int));
+            let Ok(Number::Float(float)) = random_in_range(Number::Int(1), Number::Float(4.0))
+            else {
+                panic!("a Float bound should give a Float");
+            };
+            assert!((1.0..4.0).contains(&float));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::{fmt::Display, str::FromStr};
+
+use rand::random_range;
+
+use crate::user_facing_error::UserFacingError;
+
+pub const MAX_DICE: u32 = 100;
+pub const MAX_SIDES: u64 = 1_000_000;
+
+/// Dice written as `NdM` such as `3d6`, the count can be left out to roll one die as in `d20`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u64,
+}
+
+impl FromStr for Dice {
+    type Err = UserFacingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || UserFacingError::DiceInvalid {
+            input: s.to_string(),
+        };
+
+        let lowercase = s.trim().to_lowercase();
+        let (count, sides) = lowercase.split_once('d').ok_or_else(invalid)?;
+
+        let count = if count.is_empty() {
+            1
+        } else {
+            count.parse::<u32>().map_err(|_| invalid())?
+        };
+        let sides = sides.parse::<u64>().map_err(|_| invalid())?;
+
+        if !(1..=MAX_DICE).contains(&count) || !(1..=MAX_SIDES).contains(&sides) {
+            return Err(invalid());
+        }
+
+        Ok(Self { count, sides })
+    }
+}
+
+impl Dice {
+    pub fn roll(&self) -> DiceRoll {
+        DiceRoll {
+            rolls: (0..self.count)
+                .map(|_| random_range(1..=self.sides))
+                .collect(),
+        }
+    }
+}
+
+/// The face each die landed on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiceRoll {
+    pub rolls: Vec<u64>,
+}
+
+impl DiceRoll {
+    pub fn total(&self) -> u64 {
+        self.rolls.iter().sum()
+    }
+}
+
+impl Display for DiceRoll {
+    /// Shows the total followed by each roll when more than one die was rolled
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.rolls.len() == 1 {
+            write!(f, "{}", self.total())
+        } else {
+            let rolls: Vec<String> = self.rolls.iter().map(|roll| roll.to_string()).collect();
+            write!(f, "{} ({})", self.total(), rolls.join(" + "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod dice_test {
+    use super::*;
+
+    #[test]
+    fn parses_dice() {
+        assert_eq!("3d6".parse(), Ok(Dice { count: 3, sides: 6 }));
+        assert_eq!(
+            "d20".parse(),
+            Ok(Dice {
+                count: 1,
+                sides: 20
+            })
+        );
+        assert_eq!(" 2D8 ".parse(), Ok(Dice { count: 2, sides: 8 }));
+    }
+
+    #[test]
+    fn rejects_invalid_dice() {
+        for input in [
+            "", "d", "6", "3d", "0d6", "3d0", "-1d6", "3d6+1", "ad6", "101d6",
+        ] {
+            assert!(
+                matches!(
+                    input.parse::<Dice>(),
+                    Err(UserFacingError::DiceInvalid { .. })
+                ),
+                "{} should not parse",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let dice = Dice { count: 3, sides: 6 };
+        for _ in 0..100 {
+            let roll = dice.roll();
+            assert_eq!(roll.rolls.len(), 3);
+            assert!(roll.rolls.iter().all(|roll| (1..=6).contains(roll)));
+            assert!((3..=18).contains(&roll.total()));
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(DiceRoll { rolls: vec![4] }.to_string(), "4");
+        assert_eq!(
+            DiceRoll {
+                rolls: vec![1, 5, 3]
+            }
+            .to_string(),
+            "9 (1 + 5 + 3)"
+        );
+    }
+}
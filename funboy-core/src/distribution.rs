@@ -0,0 +1,188 @@
+//! Sampling the substitutes random selection picks so its distribution can be checked against the
+//! probability each substitute is expected to have
+
+use rand::random_range;
+
+use crate::template_database::{KeySize, Substitute};
+
+/// Picks the substitute generation uses from a page of enabled substitutes
+pub fn pick_substitute(subs: &[Substitute]) -> Option<&Substitute> {
+    if subs.is_empty() {
+        None
+    } else {
+        subs.get(random_range(0..subs.len()))
+    }
+}
+
+/// The probability each of subs has to be picked, every enabled substitute is equally likely and
+/// disabled substitutes are never picked
+pub fn expected_probabilities(subs: &[Substitute]) -> Vec<f64> {
+    let enabled = subs.iter().filter(|sub| sub.enabled).count();
+    subs.iter()
+        .map(|sub| {
+            if sub.enabled {
+                1.0 / enabled as f64
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// How often a substitute was drawn against how often it should have been
+#[derive(Debug, Clone)]
+pub struct SubstituteDraws {
+    pub substitute: Substitute,
+    pub draws: usize,
+    pub expected_probability: f64,
+}
+
+impl SubstituteDraws {
+    pub fn observed_probability(&self, total_draws: usize) -> f64 {
+        if total_draws == 0 {
+            0.0
+        } else {
+            self.draws as f64 / total_draws as f64
+        }
+    }
+
+    /// The difference between observed and expected draws in standard deviations of a uniform
+    /// count, infinite when a substitute that should never be drawn was
+    pub fn deviation(&self, total_draws: usize) -> f64 {
+        let expected = self.expected_probability * total_draws as f64;
+        if expected == 0.0 {
+            if self.draws == 0 { 0.0 } else { f64::INFINITY }
+        } else {
+            (self.draws as f64 - expected) / expected.sqrt()
+        }
+    }
+}
+
+/// Draws sampled from a template alongside what each substitute was expected to get
+#[derive(Debug, Clone)]
+pub struct DistributionReport {
+    pub template: String,
+    pub draws: usize,
+    /// Substitutes in the page draws were picked from, smaller than the enabled substitutes when
+    /// template has more than a page of them
+    pub page_len: usize,
+    pub substitutes: Vec<SubstituteDraws>,
+}
+
+impl DistributionReport {
+    /// Counts how often each of subs appears in drawn
+    pub fn new(template: &str, subs: Vec<Substitute>, page_len: usize, drawn: &[KeySize]) -> Self {
+        let probabilities = expected_probabilities(&subs);
+        let substitutes = subs
+            .into_iter()
+            .zip(probabilities)
+            .map(|(substitute, expected_probability)| SubstituteDraws {
+                draws: drawn.iter().filter(|id| **id == substitute.id).count(),
+                substitute,
+                expected_probability,
+            })
+            .collect();
+        Self {
+            template: template.to_string(),
+            draws: drawn.len(),
+            page_len,
+            substitutes,
+        }
+    }
+
+    /// Pearson's chi-square statistic over the substitutes that can be drawn
+    pub fn chi_square(&self) -> f64 {
+        self.substitutes
+            .iter()
+            .filter(|sub| sub.expected_probability > 0.0)
+            .map(|sub| sub.deviation(self.draws).powi(2))
+            .sum()
+    }
+
+    /// One less than the substitutes that can be drawn
+    pub fn degrees_of_freedom(&self) -> usize {
+        self.substitutes
+            .iter()
+            .filter(|sub| sub.expected_probability > 0.0)
+            .count()
+            .saturating_sub(1)
+    }
+
+    /// Chi-square divided by its degrees of freedom, close to 1 for a fair selection
+    pub fn deviation_score(&self) -> f64 {
+        match self.degrees_of_freedom() {
+            0 => 0.0,
+            degrees => self.chi_square() / degrees as f64,
+        }
+    }
+
+    /// The count substitutes furthest from their expected draws, furthest first
+    pub fn top_deviations(&self, count: usize) -> Vec<&SubstituteDraws> {
+        let mut substitutes: Vec<&SubstituteDraws> = self.substitutes.iter().collect();
+        substitutes.sort_by(|a, b| {
+            b.deviation(self.draws)
+                .abs()
+                .total_cmp(&a.deviation(self.draws).abs())
+        });
+        substitutes.truncate(count);
+        substitutes
+    }
+}
+
+#[cfg(test)]
+mod distribution_test {
+    use super::*;
+
+    fn sub(id: i64, enabled: bool) -> Substitute {
+        Substitute {
+            id: id as _,
+            name: format!("sub{}", id),
+            template_id: 1,
+            enabled,
+            kind: "prose".to_string(),
+        }
+    }
+
+    #[test]
+    fn enabled_substitutes_are_equally_likely() {
+        let probabilities = expected_probabilities(&[sub(1, true), sub(2, false), sub(3, true)]);
+        assert_eq!(probabilities, vec![0.5, 0.0, 0.5]);
+        assert!(expected_probabilities(&[sub(1, false)]) == vec![0.0]);
+    }
+
+    #[test]
+    fn report_counts_and_deviations() {
+        let report = DistributionReport::new(
+            "fruit",
+            vec![sub(1, true), sub(2, true), sub(3, false)],
+            2,
+            &[1, 1, 1, 2],
+        );
+        assert_eq!(report.draws, 4);
+        assert_eq!(report.substitutes[0].draws, 3);
+        assert_eq!(report.substitutes[1].draws, 1);
+        assert_eq!(report.substitutes[2].draws, 0);
+        assert_eq!(report.substitutes[2].expected_probability, 0.0);
+        assert_eq!(
+            report.substitutes[0].observed_probability(report.draws),
+            0.75
+        );
+
+        // Both enabled substitutes expect 2 draws, (3 - 2)² / 2 + (1 - 2)² / 2
+        assert!((report.chi_square() - 1.0).abs() < 1e-9);
+        assert_eq!(report.degrees_of_freedom(), 1);
+        assert!((report.deviation_score() - 1.0).abs() < 1e-9);
+
+        let top = report.top_deviations(2);
+        assert_eq!(top.len(), 2);
+        assert!(top.iter().all(|sub| sub.substitute.enabled));
+    }
+
+    #[test]
+    fn drawing_a_disabled_substitute_is_infinitely_off() {
+        let report =
+            DistributionReport::new("fruit", vec![sub(1, true), sub(2, false)], 2, &[1, 2]);
+        assert_eq!(report.top_deviations(1)[0].substitute.id, 2);
+        assert!(report.top_deviations(1)[0].deviation(2).is_infinite());
+    }
+}
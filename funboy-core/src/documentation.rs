@@ -0,0 +1,51 @@
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+const FSL_DOCUMENTATION: &str = include_str!("../fsl_documentation.json");
+
+static DOCUMENTATION: OnceLock<CommandDocumentation> = OnceLock::new();
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandEntry {
+    pub name: String,
+    pub argument_count: String,
+    pub argument_types: String,
+    pub return_type: String,
+    pub description: String,
+    pub examples: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandDocumentation {
+    pub commands: Vec<CommandEntry>,
+}
+
+impl CommandDocumentation {
+    pub fn command_names(&self) -> impl Iterator<Item = &str> {
+        self.commands.iter().map(|command| command.name.as_str())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandEntry> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+}
+
+/// Documentation for every FSL command available to generations
+pub fn get_command_documentation() -> &'static CommandDocumentation {
+    DOCUMENTATION.get_or_init(|| {
+        serde_json::from_str(FSL_DOCUMENTATION).expect("fsl documentation should be valid json")
+    })
+}
+
+#[cfg(test)]
+mod documentation_test {
+    use super::*;
+
+    #[test]
+    fn documentation_parses() {
+        let documentation = get_command_documentation();
+        assert!(documentation.get("print").is_some());
+        assert!(documentation.command_names().all(|name| !name.is_empty()));
+    }
+}
@@ -0,0 +1,70 @@
+//! Edit distances for suggesting what a mistyped name was meant to be
+
+/// Names this close to what was typed are close enough to be a typo of it
+pub const MAX_FUZZY_DISTANCE: usize = 2;
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// The Levenshtein distance between a and b counted in characters
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_ch != *b_ch);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// How far a name can be from input to still be suggested, a third of its length but never less
+/// than a fuzzy match would allow
+pub fn max_suggestion_distance(input: &str) -> usize {
+    (input.chars().count() / 3).max(MAX_FUZZY_DISTANCE)
+}
+
+/// Up to [`MAX_SUGGESTIONS`] of names closest to name, names further than
+/// [`max_suggestion_distance`] are too different to be what was meant
+pub fn similar_names<'a>(name: &str, names: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let max_distance = max_suggestion_distance(name);
+    let mut similar: Vec<(usize, &str)> = names
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    similar.sort();
+    similar.dedup();
+
+    similar
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod edit_distance_test {
+    use super::*;
+
+    #[test]
+    fn counts_edits() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("numbr", "number"), 1);
+        assert_eq!(edit_distance("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn suggests_closest_names_first() {
+        let names = ["other", "numbers", "number", "number"];
+        assert_eq!(similar_names("numbr", names), ["number", "numbers"]);
+        assert!(similar_names("zzzzzzzz", names).is_empty());
+
+        let names = ["ab", "ac", "ad", "ae"];
+        assert_eq!(similar_names("a", names).len(), MAX_SUGGESTIONS);
+    }
+}
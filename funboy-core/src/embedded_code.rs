@@ -0,0 +1,801 @@
+use std::{borrow::Cow, fmt::Display, ops::Range};
+
+pub const CODE_BLOCK_OPEN: char = '{';
+pub const CODE_BLOCK_CLOSE: char = '}';
+const STRING_DELIMITER: char = '"';
+const STRING_ESCAPE: char = '\\';
+const ARGS_OPEN: char = '(';
+const ARGS_CLOSE: char = ')';
+const ARG_SEPARATOR: char = ',';
+pub const STATEMENT_SEPARATOR: char = ';';
+
+/// Deepest nesting of command calls allowed inside a single code block
+///
+/// Checked before code reaches the interpreter since its evaluation recurses once per nested
+/// command and has no depth limit of its own
+pub const DEFAULT_MAX_EXPRESSION_DEPTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeBlockError {
+    Unclosed { offset: usize },
+}
+
+impl Display for CodeBlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeBlockError::Unclosed { offset } => {
+                write!(f, "code block opened at offset {} is never closed", offset)
+            }
+        }
+    }
+}
+
+/// Finds the byte ranges of every top level `{}` code block in input including the braces
+///
+/// Braces inside of quoted strings within a block are ignored, a quote escaped with a backslash
+/// doesn't close its string
+pub fn find_code_blocks(input: &str) -> Result<Vec<Range<usize>>, CodeBlockError> {
+    let mut blocks = Vec::new();
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, ch) in input.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if ch == STRING_ESCAPE {
+                escaped = true;
+            } else if ch == STRING_DELIMITER {
+                in_string = false;
+            }
+        } else if depth > 0 && ch == STRING_DELIMITER {
+            in_string = true;
+        } else if ch == CODE_BLOCK_OPEN {
+            if depth == 0 {
+                start = i;
+            }
+            depth += 1;
+        } else if ch == CODE_BLOCK_CLOSE && depth > 0 {
+            depth -= 1;
+            if depth == 0 {
+                blocks.push(start..i + ch.len_utf8());
+            }
+        }
+    }
+
+    if depth > 0 {
+        Err(CodeBlockError::Unclosed { offset: start })
+    } else {
+        Ok(blocks)
+    }
+}
+
+/// What a [`Token`] of code is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A command name or var name, anything up to the next character that means something on
+    /// its own
+    Word,
+    /// A word that is a numeric literal such as `5`, `-2.5` or `1e3`
+    Number,
+    /// A quoted string including its quotes, a quote escaped with a backslash doesn't close it
+    String,
+    ArgsOpen,
+    ArgsClose,
+    ArgSeparator,
+    StatementSeparator,
+    Whitespace,
+}
+
+/// A piece of code and its byte range in the code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+fn ends_word(ch: char) -> bool {
+    ch.is_whitespace()
+        || matches!(
+            ch,
+            STRING_DELIMITER | ARGS_OPEN | ARGS_CLOSE | ARG_SEPARATOR | STATEMENT_SEPARATOR
+        )
+}
+
+/// Splits the code of a block into tokens, every pass over code reads it through these so
+/// they agree on where strings start and end
+///
+/// A string left open runs to the end of code
+pub fn tokenize(code: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = code.char_indices().peekable();
+
+    while let Some((start, ch)) = chars.next() {
+        let kind = match ch {
+            STRING_DELIMITER => {
+                let mut escaped = false;
+                for (_, ch) in chars.by_ref() {
+                    if escaped {
+                        escaped = false;
+                    } else if ch == STRING_ESCAPE {
+                        escaped = true;
+                    } else if ch == STRING_DELIMITER {
+                        break;
+                    }
+                }
+                TokenKind::String
+            }
+            ARGS_OPEN => TokenKind::ArgsOpen,
+            ARGS_CLOSE => TokenKind::ArgsClose,
+            ARG_SEPARATOR => TokenKind::ArgSeparator,
+            STATEMENT_SEPARATOR => TokenKind::StatementSeparator,
+            ch if ch.is_whitespace() => {
+                while chars.next_if(|(_, ch)| ch.is_whitespace()).is_some() {}
+                TokenKind::Whitespace
+            }
+            _ => {
+                while chars.next_if(|(_, ch)| !ends_word(*ch)).is_some() {}
+                let end = chars.peek().map_or(code.len(), |(i, _)| *i);
+                if is_numeric_literal(&code[start..end]) {
+                    TokenKind::Number
+                } else {
+                    TokenKind::Word
+                }
+            }
+        };
+        let end = chars.peek().map_or(code.len(), |(i, _)| *i);
+        tokens.push(Token {
+            kind,
+            span: start..end,
+        });
+    }
+
+    tokens
+}
+
+/// Whether word is an optionally negative integer or float, with an optional exponent
+fn is_numeric_literal(word: &str) -> bool {
+    let unsigned = word.strip_prefix('-').unwrap_or(word);
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (unsigned, None),
+    };
+    let is_digits = |text: &str| !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit());
+    let valid_mantissa = match mantissa.split_once('.') {
+        Some((whole, fraction)) => is_digits(whole) && is_digits(fraction),
+        None => is_digits(mantissa),
+    };
+    valid_mantissa
+        && exponent
+            .is_none_or(|exponent| is_digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)))
+}
+
+/// The name token of the command whose arguments the token at open opens
+fn command_name_token(tokens: &[Token], open: usize) -> Option<&Token> {
+    tokens[..open]
+        .iter()
+        .rev()
+        .find(|token| token.kind != TokenKind::Whitespace)
+        .filter(|token| token.kind == TokenKind::Word)
+}
+
+/// Name of the command whose arguments the token at open opens, empty when no name comes
+/// before it
+fn command_name<'a>(code: &'a str, tokens: &[Token], open: usize) -> &'a str {
+    command_name_token(tokens, open).map_or("", |token| &code[token.span.clone()])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionDepthError {
+    pub command: String,
+    pub limit: usize,
+}
+
+impl Display for ExpressionDepthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "maximum expression nesting depth of {} exceeded inside of `{}`",
+            self.limit, self.command
+        )
+    }
+}
+
+/// Fails if command calls in code are nested deeper than limit naming the outermost command
+///
+/// Parentheses inside of quoted strings are ignored
+pub fn check_expression_depth(code: &str, limit: usize) -> Result<(), ExpressionDepthError> {
+    let tokens = tokenize(code);
+    let mut depth: usize = 0;
+    let mut outermost = "";
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::ArgsOpen => {
+                if depth == 0 {
+                    outermost = command_name(code, &tokens, i);
+                }
+                depth += 1;
+                if depth > limit {
+                    return Err(ExpressionDepthError {
+                        command: outermost.to_string(),
+                        limit,
+                    });
+                }
+            }
+            TokenKind::ArgsClose => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces `;` between top level commands in code with spaces for the interpreter
+///
+/// Commands may still be separated by whitespace alone or follow each other directly as they
+/// could before separators existed. Semicolons inside of arguments and quoted strings are left
+/// untouched and code without top level semicolons is returned unchanged.
+pub fn separate_statements(code: &str) -> Cow<'_, str> {
+    let mut depth: usize = 0;
+    let mut separators: Vec<Range<usize>> = Vec::new();
+
+    for token in tokenize(code) {
+        match token.kind {
+            TokenKind::ArgsOpen => depth += 1,
+            TokenKind::ArgsClose => depth = depth.saturating_sub(1),
+            TokenKind::StatementSeparator if depth == 0 => separators.push(token.span),
+            _ => {}
+        }
+    }
+
+    if separators.is_empty() {
+        return Cow::Borrowed(code);
+    }
+
+    let mut output = code.to_string();
+    for span in separators {
+        output.replace_range(span, " ");
+    }
+    Cow::Owned(output)
+}
+
+/// Rewrites a numeric literal the interpreter can't read into an equivalent it can
+///
+/// Scientific notation becomes a plain float and a leading minus becomes a subtraction from
+/// zero, literals the interpreter already reads or that don't fit its numbers return None
+fn expand_numeric_literal(literal: &str) -> Option<String> {
+    let (negative, unsigned) = match literal.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, literal),
+    };
+    let is_float = unsigned.contains(['.', 'e', 'E']);
+    if !negative && !unsigned.contains(['e', 'E']) {
+        return None;
+    }
+
+    let value = if unsigned.contains(['e', 'E']) {
+        let value: f64 = unsigned
+            .parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())?;
+        let value = value.to_string();
+        if value.contains('.') {
+            value
+        } else {
+            value + ".0"
+        }
+    } else if is_float || unsigned.parse::<i64>().is_ok() {
+        unsigned.to_string()
+    } else if unsigned.parse::<u64>().ok() == Some(i64::MIN.unsigned_abs()) {
+        // The smallest integer has no positive counterpart to subtract from zero
+        return Some(format!("sub(sub(0, {}), 1)", i64::MAX));
+    } else {
+        return None;
+    };
+
+    if negative {
+        Some(format!("sub(0, {})", value))
+    } else {
+        Some(value)
+    }
+}
+
+/// Rewrites negative and scientific notation numeric arguments in code for the interpreter
+///
+/// Only an argument made up entirely of a [`TokenKind::Number`] is rewritten, a minus followed
+/// by whitespace is not a number and is left for the interpreter to reject. Code without such
+/// literals is returned unchanged.
+pub fn expand_numeric_literals(code: &str) -> Cow<'_, str> {
+    let tokens: Vec<Token> = tokenize(code)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Whitespace)
+        .collect();
+    let mut expansions: Vec<(Range<usize>, String)> = Vec::new();
+
+    for window in tokens.windows(3) {
+        let [before, literal, after] = window else {
+            continue;
+        };
+        let is_argument = matches!(before.kind, TokenKind::ArgsOpen | TokenKind::ArgSeparator)
+            && matches!(after.kind, TokenKind::ArgSeparator | TokenKind::ArgsClose);
+        if literal.kind != TokenKind::Number || !is_argument {
+            continue;
+        }
+        if let Some(expanded) = expand_numeric_literal(&code[literal.span.clone()]) {
+            expansions.push((literal.span.clone(), expanded));
+        }
+    }
+
+    if expansions.is_empty() {
+        return Cow::Borrowed(code);
+    }
+
+    let mut output = code.to_string();
+    for (range, expanded) in expansions.into_iter().rev() {
+        output.replace_range(range, &expanded);
+    }
+    Cow::Owned(output)
+}
+
+/// The top level commands of code as written, each from its name to its closing parenthesis
+///
+/// A command left unclosed runs to the end of code
+pub fn top_level_commands(code: &str) -> Vec<&str> {
+    let tokens = tokenize(code);
+    let mut depth: usize = 0;
+    let mut start = 0;
+    let mut commands = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::ArgsOpen => {
+                if depth == 0 {
+                    start = command_name_token(&tokens, i).unwrap_or(token).span.start;
+                }
+                depth += 1;
+            }
+            TokenKind::ArgsClose if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    commands.push(&code[start..token.span.end]);
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        commands.push(&code[start..]);
+    }
+
+    commands
+}
+
+/// A syntax problem found in embedded code without running it, offsets are bytes into the
+/// checked text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeSyntaxError {
+    Block(CodeBlockError),
+    UnclosedArgs { command: String, offset: usize },
+    UnexpectedArgsClose { offset: usize },
+}
+
+impl Display for CodeSyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeSyntaxError::Block(e) => write!(f, "{}", e),
+            CodeSyntaxError::UnclosedArgs { command, offset } => write!(
+                f,
+                "arguments of command {} opened at offset {} are never closed",
+                command, offset
+            ),
+            CodeSyntaxError::UnexpectedArgsClose { offset } => {
+                write!(f, "unexpected '{}' at offset {}", ARGS_CLOSE, offset)
+            }
+        }
+    }
+}
+
+/// Checks that command arguments in the code of a block are balanced
+///
+/// Strings are always closed within a block found by [`find_code_blocks`] so only parentheses
+/// are tracked. Reported offsets are shifted by base so they point into the text code was taken from
+fn check_code_syntax(code: &str, base: usize) -> Result<(), CodeSyntaxError> {
+    let tokens = tokenize(code);
+    let mut open_args: Vec<(usize, &str)> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::ArgsOpen => {
+                open_args.push((token.span.start, command_name(code, &tokens, i)))
+            }
+            TokenKind::ArgsClose if open_args.pop().is_none() => {
+                return Err(CodeSyntaxError::UnexpectedArgsClose {
+                    offset: base + token.span.start,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((offset, command)) = open_args.into_iter().next() {
+        return Err(CodeSyntaxError::UnclosedArgs {
+            command: command.to_string(),
+            offset: base + offset,
+        });
+    }
+
+    Ok(())
+}
+
+/// Checks the syntax of every code block in input without interpreting any of them
+///
+/// Blocks are found with the same scanner generation uses so anything rejected here would
+/// also fail once the text is generated
+pub fn check_embedded_code(input: &str) -> Result<(), CodeSyntaxError> {
+    let blocks = find_code_blocks(input).map_err(CodeSyntaxError::Block)?;
+    for block in blocks {
+        let base = block.start + CODE_BLOCK_OPEN.len_utf8();
+        check_code_syntax(block_contents(input, block), base)?;
+    }
+    Ok(())
+}
+
+/// Finds every call to command within the code blocks of input, each as its trimmed top level
+/// arguments
+///
+/// Text whose blocks fail to scan has no calls since it would fail to generate anyway
+pub fn find_command_calls<'a>(input: &'a str, command: &str) -> Vec<Vec<&'a str>> {
+    find_calls(input, &[command])
+        .into_iter()
+        .map(|(_, args)| args)
+        .collect()
+}
+
+/// An open call with where its current argument starts, args are only kept for the commands
+/// looked for
+type OpenCall<'a> = (usize, Option<(&'a str, Vec<&'a str>)>);
+
+/// Finds every call to any of commands within the code blocks of input alongside the command
+/// called, in the order the calls are evaluated so arguments come before the call taking them
+pub fn find_calls<'a>(input: &'a str, commands: &[&str]) -> Vec<(&'a str, Vec<&'a str>)> {
+    let Ok(blocks) = find_code_blocks(input) else {
+        return Vec::new();
+    };
+
+    let mut calls = Vec::new();
+    for block in blocks {
+        let code = block_contents(input, block);
+        let tokens = tokenize(code);
+        let mut open_calls: Vec<OpenCall> = Vec::new();
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token.kind {
+                TokenKind::ArgsOpen => {
+                    let name = command_name(code, &tokens, i);
+                    let args = commands.contains(&name).then(|| (name, Vec::new()));
+                    open_calls.push((token.span.end, args));
+                }
+                TokenKind::ArgSeparator => {
+                    if let Some((arg_start, call)) = open_calls.last_mut() {
+                        if let Some((_, args)) = call {
+                            args.push(code[*arg_start..token.span.start].trim());
+                        }
+                        *arg_start = token.span.end;
+                    }
+                }
+                TokenKind::ArgsClose => {
+                    if let Some((arg_start, Some((name, mut args)))) = open_calls.pop() {
+                        let last = code[arg_start..token.span.start].trim();
+                        if !last.is_empty() || !args.is_empty() {
+                            args.push(last);
+                        }
+                        calls.push((name, args));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    calls
+}
+
+/// Returns the code inside of a block range found by [`find_code_blocks`] without its braces
+pub fn block_contents(input: &str, block: Range<usize>) -> &str {
+    &input[block.start + CODE_BLOCK_OPEN.len_utf8()..block.end - CODE_BLOCK_CLOSE.len_utf8()]
+}
+
+#[cfg(test)]
+mod embedded_code_test {
+    use super::*;
+
+    #[test]
+    fn finds_top_level_blocks() {
+        let input = "a {print(\"b\")} c {repeat(2, print(\"{d}\"))} e";
+        let blocks = find_code_blocks(input).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(block_contents(input, blocks[0].clone()), "print(\"b\")");
+        assert_eq!(
+            block_contents(input, blocks[1].clone()),
+            "repeat(2, print(\"{d}\"))"
+        );
+    }
+
+    #[test]
+    fn text_without_blocks() {
+        assert!(find_code_blocks("no code } here").unwrap().is_empty());
+    }
+
+    fn nested_concat(depth: usize) -> String {
+        let mut code = "\"(\"".to_string();
+        for _ in 0..depth {
+            code = format!("concat(\"a\", {})", code);
+        }
+        format!("print({})", code)
+    }
+
+    #[test]
+    fn expression_depth_at_limit() {
+        let limit = DEFAULT_MAX_EXPRESSION_DEPTH;
+        assert!(check_expression_depth(&nested_concat(limit - 1), limit).is_ok());
+    }
+
+    #[test]
+    fn escaped_quotes_stay_in_strings() {
+        let code = "print(\"a\\\"(\", concat(\"\\\\\", x))";
+        assert_eq!(
+            tokenize(code)
+                .iter()
+                .map(|token| &code[token.span.clone()])
+                .collect::<Vec<_>>(),
+            vec![
+                "print",
+                "(",
+                "\"a\\\"(\"",
+                ",",
+                " ",
+                "concat",
+                "(",
+                "\"\\\\\"",
+                ",",
+                " ",
+                "x",
+                ")",
+                ")"
+            ]
+        );
+        assert!(check_expression_depth(code, 2).is_ok());
+        assert_eq!(
+            find_code_blocks("{print(\"\\\"}\")} b").unwrap(),
+            vec![0..14]
+        );
+    }
+
+    #[test]
+    fn expression_depth_over_limit() {
+        let limit = DEFAULT_MAX_EXPRESSION_DEPTH;
+        assert_eq!(
+            check_expression_depth(&nested_concat(limit), limit),
+            Err(ExpressionDepthError {
+                command: "print".to_string(),
+                limit,
+            })
+        );
+    }
+
+    #[test]
+    fn semicolons_separate_statements() {
+        assert_eq!(
+            separate_statements("store(\"a\", x); print(x);"),
+            "store(\"a\", x)  print(x) "
+        );
+        assert_eq!(
+            separate_statements("store(\"a\", x);print(x)"),
+            "store(\"a\", x) print(x)"
+        );
+    }
+
+    #[test]
+    fn semicolons_inside_arguments_are_kept() {
+        for code in [
+            "print(\"a; b\") print(concat(\";\", x))",
+            "print(\"a\\\"; b\")",
+        ] {
+            assert!(matches!(separate_statements(code), Cow::Borrowed(kept) if kept == code));
+        }
+    }
+
+    #[test]
+    fn abutting_commands_are_kept() {
+        for code in ["store(\"a\", x)print(x)", "print(\")\")repeat(2, print(x))"] {
+            assert!(matches!(separate_statements(code), Cow::Borrowed(kept) if kept == code));
+        }
+        assert_eq!(
+            separate_statements("print(a)print(b); print(c)"),
+            "print(a)print(b)  print(c)"
+        );
+    }
+
+    #[test]
+    fn negative_literals_become_subtractions() {
+        assert_eq!(
+            expand_numeric_literals("print(add(-1,-2))"),
+            "print(add(sub(0, 1),sub(0, 2)))"
+        );
+        assert_eq!(
+            expand_numeric_literals("print(mul( -2.5 , 2))"),
+            "print(mul( sub(0, 2.5) , 2))"
+        );
+    }
+
+    #[test]
+    fn smallest_integer_is_not_overflowed() {
+        assert_eq!(
+            expand_numeric_literals("print(-9223372036854775808)"),
+            "print(sub(sub(0, 9223372036854775807), 1))"
+        );
+        assert_eq!(
+            expand_numeric_literals("print(-9223372036854775807)"),
+            "print(sub(0, 9223372036854775807))"
+        );
+    }
+
+    #[test]
+    fn scientific_notation_becomes_floats() {
+        assert_eq!(expand_numeric_literals("print(1e3)"), "print(1000.0)");
+        assert_eq!(expand_numeric_literals("print(2.5E-2)"), "print(0.025)");
+        assert_eq!(
+            expand_numeric_literals("print(add(-2.5e-2, 1e+1))"),
+            "print(add(sub(0, 0.025), 10.0))"
+        );
+    }
+
+    #[test]
+    fn other_arguments_are_kept() {
+        for code in [
+            "print(add(1, 2.5))",
+            "print(sub(5, 3))",
+            "print(add(- 1, 2))",
+            "print(\"-1\", \"1e3\")",
+            "print(concat(\"a\", -x))",
+            "print(1e, 1e-, -, e3, 1.e3, 1e999)",
+            "print(-9223372036854775809)",
+            "print(\"a\\\",-1\", x)",
+            "print(\"a\\\"\", \"(-1)\")",
+        ] {
+            assert!(
+                matches!(expand_numeric_literals(code), Cow::Borrowed(kept) if kept == code),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn numbers_are_tokenized() {
+        let code = "add(-2.5e-2, x1, \"3\")";
+        let kinds: Vec<(TokenKind, &str)> = tokenize(code)
+            .into_iter()
+            .filter(|token| token.kind != TokenKind::Whitespace)
+            .map(|token| (token.kind, &code[token.span]))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (TokenKind::Word, "add"),
+                (TokenKind::ArgsOpen, "("),
+                (TokenKind::Number, "-2.5e-2"),
+                (TokenKind::ArgSeparator, ","),
+                (TokenKind::Word, "x1"),
+                (TokenKind::ArgSeparator, ","),
+                (TokenKind::String, "\"3\""),
+                (TokenKind::ArgsClose, ")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_top_level_commands() {
+        assert_eq!(
+            top_level_commands("print(add(-1, 2)); store(\"a)\", x)print(a) (b) print("),
+            [
+                "print(add(-1, 2))",
+                "store(\"a)\", x)",
+                "print(a)",
+                "(b)",
+                "print("
+            ]
+        );
+        assert!(top_level_commands("  ").is_empty());
+    }
+
+    /// Every documented example must reach the interpreter exactly as before,
+    /// apart from top level semicolons becoming spaces
+    #[test]
+    fn documentation_examples_are_unchanged() {
+        for command in &crate::documentation::get_command_documentation().commands {
+            for example in &command.examples {
+                let input = example.split(" = ").next().unwrap();
+                for block in find_code_blocks(input).unwrap() {
+                    let code = block_contents(input, block);
+                    let separated = separate_statements(code);
+                    if code.contains(STATEMENT_SEPARATOR) {
+                        assert_eq!(separated, code.replace(STATEMENT_SEPARATOR, " "));
+                    } else {
+                        assert!(matches!(separated, Cow::Borrowed(_)), "{}", example);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unclosed_block() {
+        assert_eq!(
+            find_code_blocks("ok {print(\"fine\")} {repeat(5, print("),
+            Err(CodeBlockError::Unclosed { offset: 19 })
+        );
+    }
+
+    #[test]
+    fn valid_embedded_code() {
+        assert!(check_embedded_code("plain text").is_ok());
+        assert!(check_embedded_code("a {repeat(2, print(\"(\"))} b {print(x); print(y)}").is_ok());
+        assert!(check_embedded_code("ab {print(x)print(y)}").is_ok());
+    }
+
+    #[test]
+    fn invalid_embedded_code() {
+        assert_eq!(
+            check_embedded_code("a {repeat(5, print(\"x\")}"),
+            Err(CodeSyntaxError::UnclosedArgs {
+                command: "repeat".to_string(),
+                offset: 9,
+            })
+        );
+        assert_eq!(
+            check_embedded_code("{print(\"x\"))}"),
+            Err(CodeSyntaxError::UnexpectedArgsClose { offset: 11 })
+        );
+        assert_eq!(
+            check_embedded_code("{print(\"x\")"),
+            Err(CodeSyntaxError::Block(CodeBlockError::Unclosed {
+                offset: 0
+            }))
+        );
+    }
+
+    #[test]
+    fn finds_command_calls() {
+        let input = concat!(
+            "store(1, prose) {store(\"a, b\", x) print(store(add(1, 2), y))} ",
+            "{restore(1, z) store() store(0, 1, 2, list)}"
+        );
+        assert_eq!(
+            find_command_calls(input, "store"),
+            vec![
+                vec!["\"a, b\"", "x"],
+                vec!["add(1, 2)", "y"],
+                vec![],
+                vec!["0", "1", "2", "list"],
+            ]
+        );
+        assert!(find_command_calls("{store(1, x)", "store").is_empty());
+    }
+
+    #[test]
+    fn finds_calls_in_evaluation_order() {
+        let input = "{store(clone(a), b) print(clone())} {store(1)}";
+        assert_eq!(
+            find_calls(input, &["store", "clone"]),
+            vec![
+                ("clone", vec!["a"]),
+                ("store", vec!["clone(a)", "b"]),
+                ("clone", vec![]),
+                ("store", vec!["1"]),
+            ]
+        );
+    }
+}
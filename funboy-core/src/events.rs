@@ -0,0 +1,247 @@
+use std::{
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    time::Duration,
+};
+
+use tokio::sync::broadcast;
+
+use crate::{
+    FunboyError, GenerationOutput, template_substitutor::ExpansionError,
+    user_facing_error::UserFacingError,
+};
+
+/// How many events a subscriber may fall behind before it misses the oldest ones
+const EVENT_CAPACITY: usize = 64;
+
+/// Which expansion limit a generation ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BudgetKind {
+    TotalExpansions,
+    TemplateExpansions,
+    Registers,
+    RegisterLength,
+}
+
+impl BudgetKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetKind::TotalExpansions => "total_expansions",
+            BudgetKind::TemplateExpansions => "template_expansions",
+            BudgetKind::Registers => "registers",
+            BudgetKind::RegisterLength => "register_length",
+        }
+    }
+}
+
+impl From<&ExpansionError> for BudgetKind {
+    fn from(value: &ExpansionError) -> Self {
+        match value {
+            ExpansionError::TotalLimitReached { .. } => BudgetKind::TotalExpansions,
+            ExpansionError::TemplateLimitReached { .. } => BudgetKind::TemplateExpansions,
+            ExpansionError::RegisterLimitReached { .. } => BudgetKind::Registers,
+            ExpansionError::RegisterTooLong { .. } => BudgetKind::RegisterLength,
+        }
+    }
+}
+
+/// Something operators may want to be alerted about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunboyEvent {
+    /// A generation took longer than [`EventThresholds::slow_generation`]
+    ///
+    /// The input is hashed so alerts can tell repeated inputs apart without repeating them
+    SlowGeneration {
+        input_hash: u64,
+        duration: Duration,
+        passes: u8,
+    },
+    /// Templates were still nested when a generation reached its maximum depth
+    DepthLimitHit { templates: Vec<String> },
+    /// A generation was stopped by one of its expansion limits
+    BudgetExceeded { kind: BudgetKind },
+}
+
+impl FunboyEvent {
+    /// Name shared by every event of the same variant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FunboyEvent::SlowGeneration { .. } => "slow_generation",
+            FunboyEvent::DepthLimitHit { .. } => "depth_limit_hit",
+            FunboyEvent::BudgetExceeded { .. } => "budget_exceeded",
+        }
+    }
+}
+
+impl Display for FunboyEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FunboyEvent::SlowGeneration {
+                input_hash,
+                duration,
+                passes,
+            } => write!(
+                f,
+                "generation {:016x} took {:.1}s over {} passes",
+                input_hash,
+                duration.as_secs_f64(),
+                passes
+            ),
+            FunboyEvent::DepthLimitHit { templates } => write!(
+                f,
+                "generation reached its maximum depth with `{}` still nested",
+                templates.join("`, `")
+            ),
+            FunboyEvent::BudgetExceeded { kind } => {
+                write!(f, "generation exceeded its {} budget", kind.as_str())
+            }
+        }
+    }
+}
+
+/// Soft limits on generations that emit a [`FunboyEvent`] when crossed without failing them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventThresholds {
+    pub slow_generation: Duration,
+}
+
+impl Default for EventThresholds {
+    fn default() -> Self {
+        Self {
+            slow_generation: Duration::from_secs(5),
+        }
+    }
+}
+
+impl EventThresholds {
+    /// The events a generation of input that took duration and ended with result crosses
+    pub fn evaluate(
+        &self,
+        input: &str,
+        duration: Duration,
+        result: &Result<GenerationOutput, FunboyError>,
+    ) -> Vec<FunboyEvent> {
+        let mut events = Vec::new();
+        match result {
+            Ok(output) => {
+                if duration > self.slow_generation {
+                    events.push(FunboyEvent::SlowGeneration {
+                        input_hash: input_hash(input),
+                        duration,
+                        passes: output.passes,
+                    });
+                }
+                for warning in &output.warnings {
+                    if let UserFacingError::NestingTooDeep { templates, .. } = warning {
+                        events.push(FunboyEvent::DepthLimitHit {
+                            templates: templates.clone(),
+                        });
+                    }
+                }
+            }
+            Err(FunboyError::UserInput(UserFacingError::NestingTooDeep { templates, .. })) => {
+                events.push(FunboyEvent::DepthLimitHit {
+                    templates: templates.clone(),
+                });
+            }
+            Err(FunboyError::UserInput(UserFacingError::GenerationTooLarge(e))) => {
+                events.push(FunboyEvent::BudgetExceeded { kind: e.into() });
+            }
+            Err(_) => {}
+        }
+        events
+    }
+}
+
+fn input_hash(input: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Sends [`FunboyEvent`]s to every subscriber, clones share their subscribers
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<FunboyEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> broadcast::Receiver<FunboyEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Events emitted without subscribers are dropped
+    pub fn emit(&self, event: FunboyEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+#[cfg(test)]
+mod events_test {
+    use super::*;
+
+    fn output(passes: u8, warnings: Vec<UserFacingError>) -> Result<GenerationOutput, FunboyError> {
+        let mut output = GenerationOutput::new("text".to_string(), passes);
+        output.warnings = warnings;
+        Ok(output)
+    }
+
+    #[test]
+    fn only_crossed_thresholds_emit_events() {
+        let thresholds = EventThresholds::default();
+        let fast = Duration::from_millis(200);
+        let slow = Duration::from_secs(6);
+
+        assert!(thresholds.evaluate("^noun", fast, &output(1, vec![])) == vec![]);
+        assert!(
+            thresholds.evaluate("^noun", slow, &output(3, vec![]))
+                == vec![FunboyEvent::SlowGeneration {
+                    input_hash: input_hash("^noun"),
+                    duration: slow,
+                    passes: 3,
+                }]
+        );
+
+        let nesting = UserFacingError::NestingTooDeep {
+            depth: 32,
+            templates: vec!["deep".to_string()],
+        };
+        let depth_hit = FunboyEvent::DepthLimitHit {
+            templates: vec!["deep".to_string()],
+        };
+        assert!(
+            thresholds.evaluate("^deep", fast, &output(1, vec![nesting.clone()]))
+                == vec![depth_hit.clone()]
+        );
+        assert!(
+            thresholds.evaluate("^deep", fast, &Err(FunboyError::UserInput(nesting)))
+                == vec![depth_hit]
+        );
+
+        let too_large =
+            UserFacingError::GenerationTooLarge(ExpansionError::RegisterLimitReached { limit: 10 });
+        assert!(
+            thresholds.evaluate("+noun-1+", slow, &Err(FunboyError::UserInput(too_large)))
+                == vec![FunboyEvent::BudgetExceeded {
+                    kind: BudgetKind::Registers
+                }]
+        );
+        assert!(
+            thresholds
+                .evaluate(
+                    "^noun",
+                    slow,
+                    &Err(FunboyError::Database("down".to_string()))
+                )
+                .is_empty()
+        );
+    }
+}
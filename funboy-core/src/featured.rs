@@ -0,0 +1,214 @@
+use std::fmt::Display;
+
+use rand::Rng;
+
+use crate::template_database::FeaturedCandidate;
+
+/// Days a featured template is skipped for before it can be featured again in the same guild
+pub const REPEAT_WINDOW_DAYS: i64 = 7;
+/// Days of usage counted by [`FeaturedStrategy::MostUsed`] including today
+pub const USAGE_WINDOW_DAYS: i64 = 30;
+
+/// How the template of the day of a guild is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeaturedStrategy {
+    Random,
+    /// Templates never featured first, then the one featured longest ago
+    LeastRecentlyFeatured,
+    /// The template used the most in the guild over the last [`USAGE_WINDOW_DAYS`]
+    MostUsed,
+}
+
+impl FeaturedStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeaturedStrategy::Random => "random",
+            FeaturedStrategy::LeastRecentlyFeatured => "least_recently_featured",
+            FeaturedStrategy::MostUsed => "most_used",
+        }
+    }
+
+    /// Reads a strategy stored with [`FeaturedStrategy::as_str`]
+    pub fn parse(strategy: &str) -> Option<Self> {
+        [
+            FeaturedStrategy::Random,
+            FeaturedStrategy::LeastRecentlyFeatured,
+            FeaturedStrategy::MostUsed,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == strategy)
+    }
+}
+
+impl Display for FeaturedStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether candidate was featured within [`REPEAT_WINDOW_DAYS`] before today
+fn featured_recently(candidate: &FeaturedCandidate, today: i64) -> bool {
+    candidate
+        .last_featured_day
+        .is_some_and(|day| today - day < REPEAT_WINDOW_DAYS)
+}
+
+/// Picks the template to feature today with strategy
+///
+/// Templates featured recently are skipped unless every candidate was, so guilds with only a
+/// few templates still get one each day. Ties are broken by the lowest id.
+pub fn select_featured<'a, R: Rng + ?Sized>(
+    candidates: &'a [FeaturedCandidate],
+    strategy: FeaturedStrategy,
+    today: i64,
+    rng: &mut R,
+) -> Option<&'a FeaturedCandidate> {
+    let fresh: Vec<&FeaturedCandidate> = candidates
+        .iter()
+        .filter(|candidate| !featured_recently(candidate, today))
+        .collect();
+    let pool = if fresh.is_empty() {
+        candidates.iter().collect()
+    } else {
+        fresh
+    };
+
+    match strategy {
+        FeaturedStrategy::Random => {
+            if pool.is_empty() {
+                None
+            } else {
+                Some(pool[rng.random_range(0..pool.len())])
+            }
+        }
+        FeaturedStrategy::LeastRecentlyFeatured => pool
+            .into_iter()
+            .min_by_key(|candidate| (candidate.last_featured_day, candidate.id)),
+        FeaturedStrategy::MostUsed => pool
+            .into_iter()
+            .min_by_key(|candidate| (-candidate.uses, candidate.id)),
+    }
+}
+
+#[cfg(test)]
+mod featured_test {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::template_database::KeySize;
+
+    const TODAY: i64 = 100;
+
+    fn candidate(id: KeySize, last_featured_day: Option<i64>, uses: i64) -> FeaturedCandidate {
+        FeaturedCandidate {
+            id,
+            name: format!("template_{}", id),
+            substitute_count: 1,
+            last_featured_day,
+            uses,
+        }
+    }
+
+    fn selected_id(
+        candidates: &[FeaturedCandidate],
+        strategy: FeaturedStrategy,
+        seed: u64,
+    ) -> Option<KeySize> {
+        select_featured(
+            candidates,
+            strategy,
+            TODAY,
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .map(|candidate| candidate.id)
+    }
+
+    #[test]
+    fn strategies_round_trip() {
+        for strategy in [
+            FeaturedStrategy::Random,
+            FeaturedStrategy::LeastRecentlyFeatured,
+            FeaturedStrategy::MostUsed,
+        ] {
+            assert_eq!(FeaturedStrategy::parse(strategy.as_str()), Some(strategy));
+        }
+        assert_eq!(FeaturedStrategy::parse("newest"), None);
+    }
+
+    #[test]
+    fn nothing_is_selected_without_candidates() {
+        assert_eq!(selected_id(&[], FeaturedStrategy::Random, 0), None);
+        assert_eq!(
+            selected_id(&[], FeaturedStrategy::LeastRecentlyFeatured, 0),
+            None
+        );
+        assert_eq!(selected_id(&[], FeaturedStrategy::MostUsed, 0), None);
+    }
+
+    #[test]
+    fn random_is_deterministic_for_a_seed_and_skips_recent() {
+        let candidates = [
+            candidate(1, Some(TODAY - 1), 0),
+            candidate(2, None, 0),
+            candidate(3, Some(TODAY - REPEAT_WINDOW_DAYS), 0),
+            candidate(4, Some(TODAY - 3), 0),
+        ];
+        for seed in 0..50 {
+            let selected = selected_id(&candidates, FeaturedStrategy::Random, seed);
+            assert!(selected == Some(2) || selected == Some(3), "{:?}", selected);
+            assert_eq!(
+                selected,
+                selected_id(&candidates, FeaturedStrategy::Random, seed)
+            );
+        }
+    }
+
+    #[test]
+    fn least_recently_featured_prefers_never_featured() {
+        let candidates = [
+            candidate(1, Some(TODAY - 30), 0),
+            candidate(2, Some(TODAY - 20), 0),
+            candidate(3, None, 0),
+            candidate(4, None, 0),
+        ];
+        assert_eq!(
+            selected_id(&candidates, FeaturedStrategy::LeastRecentlyFeatured, 0),
+            Some(3)
+        );
+        assert_eq!(
+            selected_id(&candidates[..2], FeaturedStrategy::LeastRecentlyFeatured, 0),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn most_used_skips_recent_winners() {
+        let candidates = [
+            candidate(1, Some(TODAY - 1), 90),
+            candidate(2, None, 40),
+            candidate(3, Some(TODAY - 10), 40),
+            candidate(4, None, 5),
+        ];
+        assert_eq!(
+            selected_id(&candidates, FeaturedStrategy::MostUsed, 0),
+            Some(2)
+        );
+        assert_eq!(
+            selected_id(&candidates[..1], FeaturedStrategy::MostUsed, 0),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn every_recent_candidate_falls_back_to_all() {
+        let candidates = [
+            candidate(1, Some(TODAY), 0),
+            candidate(2, Some(TODAY - 2), 0),
+        ];
+        assert_eq!(
+            selected_id(&candidates, FeaturedStrategy::LeastRecentlyFeatured, 0),
+            Some(2)
+        );
+        assert!(selected_id(&candidates, FeaturedStrategy::Random, 0).is_some());
+    }
+}
@@ -0,0 +1,306 @@
+/// Word beginnings spelled with a vowel but pronounced with a consonant sound
+pub const CONSONANT_SOUND_PREFIXES: &[&str] = &[
+    "eu", "ewe", "ouija", "ubiq", "ufo", "ukulele", "unanim", "unicorn", "unif", "union", "uniq",
+    "unison", "unit", "univers", "uran", "urin", "usa", "use", "usu", "uten", "util", "utop",
+];
+
+/// Words pronounced with a consonant sound only when they stand alone, "onerous" and "onion" still
+/// start with a vowel sound
+pub const CONSONANT_SOUND_WORDS: &[&str] = &["once", "one"];
+
+/// Word beginnings spelled with a consonant but pronounced with a vowel sound
+pub const VOWEL_SOUND_PREFIXES: &[&str] = &["heir", "honest", "honor", "honour", "hour"];
+
+const VOWELS: &[char] = &['a', 'e', 'i', 'o', 'u'];
+
+/// Picks the indefinite article for noun based on the sound it most likely starts with
+pub fn a_or_an(noun: &str) -> &'static str {
+    let noun = noun.trim().to_lowercase();
+
+    let starts_with_any = |prefixes: &[&str]| prefixes.iter().any(|p| noun.starts_with(p));
+    let first_word = noun
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .next()
+        .unwrap_or_default();
+
+    let vowel_sound = if starts_with_any(CONSONANT_SOUND_PREFIXES)
+        || CONSONANT_SOUND_WORDS.contains(&first_word)
+    {
+        false
+    } else if starts_with_any(VOWEL_SOUND_PREFIXES) {
+        true
+    } else {
+        match noun.chars().next() {
+            Some('8') => true,
+            Some(ch) => VOWELS.contains(&ch) || noun == "11" || noun == "18",
+            None => false,
+        }
+    };
+
+    if vowel_sound { "an" } else { "a" }
+}
+
+/// Picks the singular or plural form of a word for count
+///
+/// Without a plural form the singular form with an `s` appended is used
+pub fn plural(count: f64, singular: &str, plural_form: Option<&str>) -> String {
+    if count == 1.0 {
+        singular.to_string()
+    } else {
+        match plural_form {
+            Some(plural_form) => plural_form.to_string(),
+            None => format!("{}s", singular),
+        }
+    }
+}
+
+/// Formats n as an english ordinal such as 1st, 2nd, 3rd, or 11th
+pub fn ordinal(n: i64) -> String {
+    let last_two = n.unsigned_abs() % 100;
+    let suffix = match (last_two % 10, last_two) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Words left lowercase inside of headlines unless they start or end them
+pub const HEADLINE_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "so", "the",
+    "to", "up", "yet",
+];
+
+/// Uppercases the first character of text leaving the rest unchanged
+///
+/// Works on characters rather than bytes so text starting with a multi-byte character is safe
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Capitalizes every word of text keeping the whitespace between them
+///
+/// Headline style leaves [`HEADLINE_STOP_WORDS`] lowercase unless they are the first or last word
+pub fn title_case(text: &str, headline: bool) -> String {
+    let word_count = text.split_whitespace().count();
+    let mut title = String::with_capacity(text.len());
+    let mut word_index = 0;
+
+    for piece in split_keeping_whitespace(text) {
+        if piece.starts_with(char::is_whitespace) {
+            title.push_str(piece);
+            continue;
+        }
+
+        let is_edge = word_index == 0 || word_index == word_count - 1;
+        let lowercase = piece.to_lowercase();
+        if headline && !is_edge && HEADLINE_STOP_WORDS.contains(&lowercase.as_str()) {
+            title.push_str(&lowercase);
+        } else {
+            title.push_str(&capitalize(piece));
+        }
+        word_index += 1;
+    }
+
+    title
+}
+
+/// Splits text into runs of whitespace and runs of everything else
+fn split_keeping_whitespace(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut in_whitespace = None;
+
+    for (i, ch) in text.char_indices() {
+        let is_whitespace = ch.is_whitespace();
+        if in_whitespace.is_some_and(|in_whitespace| in_whitespace != is_whitespace) {
+            pieces.push(&text[start..i]);
+            start = i;
+        }
+        in_whitespace = Some(is_whitespace);
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+
+    pieces
+}
+
+/// Splits text into lowercase words at separators and at case changes such as `fooBar`
+fn identifier_words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (i, &ch) in chars.iter().enumerate() {
+        if !ch.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        if ch.is_uppercase() && !word.is_empty() {
+            let previous = chars[i - 1];
+            let next_is_lowercase = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            // fooBar and foo2Bar split before the uppercase letter, HTTPServer splits before the S
+            if previous.is_lowercase()
+                || previous.is_numeric()
+                || (previous.is_uppercase() && next_is_lowercase)
+            {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.extend(ch.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Converts text to snake_case splitting words at separators and case changes
+pub fn snake_case(text: &str) -> String {
+    identifier_words(text).join("_")
+}
+
+/// Converts text to camelCase splitting words at separators and case changes
+pub fn camel_case(text: &str) -> String {
+    identifier_words(text)
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            if i == 0 {
+                word.clone()
+            } else {
+                capitalize(word)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod grammar_test {
+    use super::*;
+
+    #[test]
+    fn articles() {
+        assert_eq!(a_or_an("apple"), "an");
+        assert_eq!(a_or_an("banana"), "a");
+        assert_eq!(a_or_an("8 ball"), "an");
+        assert_eq!(a_or_an(""), "a");
+    }
+
+    #[test]
+    fn article_exceptions() {
+        for word in ["hour", "honest", "heir", "honorable"] {
+            assert_eq!(a_or_an(word), "an", "{}", word);
+        }
+        for word in ["university", "unicorn", "user", "european", "one", "ufo"] {
+            assert_eq!(a_or_an(word), "a", "{}", word);
+        }
+        for word in ["one-off", "once upon a time", "one way street"] {
+            assert_eq!(a_or_an(word), "a", "{}", word);
+        }
+        for word in ["umbrella", "onerous", "onion"] {
+            assert_eq!(a_or_an(word), "an", "{}", word);
+        }
+    }
+
+    #[test]
+    fn articles_ignore_case() {
+        assert_eq!(a_or_an("APPLE"), "an");
+        assert_eq!(a_or_an("Hour"), "an");
+        assert_eq!(a_or_an("UNIVERSITY"), "a");
+        assert_eq!(a_or_an("Banana"), "a");
+    }
+
+    #[test]
+    fn plurals() {
+        assert_eq!(plural(1.0, "item", None), "item");
+        assert_eq!(plural(0.0, "item", None), "items");
+        assert_eq!(plural(2.0, "item", None), "items");
+        assert_eq!(plural(2.0, "mouse", Some("mice")), "mice");
+        assert_eq!(plural(1.0, "mouse", Some("mice")), "mouse");
+    }
+
+    #[test]
+    fn ordinals() {
+        assert_eq!(ordinal(1), "1st");
+        assert_eq!(ordinal(2), "2nd");
+        assert_eq!(ordinal(3), "3rd");
+        assert_eq!(ordinal(4), "4th");
+        assert_eq!(ordinal(0), "0th");
+        assert_eq!(ordinal(21), "21st");
+        assert_eq!(ordinal(102), "102nd");
+        assert_eq!(ordinal(-3), "-3rd");
+    }
+
+    #[test]
+    fn ordinal_teens() {
+        assert_eq!(ordinal(11), "11th");
+        assert_eq!(ordinal(12), "12th");
+        assert_eq!(ordinal(13), "13th");
+        assert_eq!(ordinal(111), "111th");
+        assert_eq!(ordinal(112), "112th");
+        assert_eq!(ordinal(113), "113th");
+    }
+
+    #[test]
+    fn capitalize_multi_byte() {
+        assert_eq!(capitalize("émile"), "Émile");
+        assert_eq!(capitalize("ß"), "SS");
+        assert_eq!(capitalize("日本"), "日本");
+        assert_eq!(capitalize("hello world"), "Hello world");
+        assert_eq!(capitalize(""), "");
+    }
+
+    #[test]
+    fn title_cases() {
+        assert_eq!(
+            title_case("the lord of the rings", false),
+            "The Lord Of The Rings"
+        );
+        assert_eq!(
+            title_case("the lord of the rings", true),
+            "The Lord of the Rings"
+        );
+        assert_eq!(title_case("what is it for", true), "What Is It For");
+        assert_eq!(
+            title_case("  émile  and NASA\tgo ", true),
+            "  Émile  and NASA\tGo "
+        );
+        assert_eq!(title_case("", true), "");
+    }
+
+    #[test]
+    fn snake_cases() {
+        assert_eq!(snake_case("Hello World"), "hello_world");
+        assert_eq!(
+            snake_case("hello-world_fooBar baz"),
+            "hello_world_foo_bar_baz"
+        );
+        assert_eq!(
+            snake_case("HTTPServer error2Code"),
+            "http_server_error2_code"
+        );
+        assert_eq!(snake_case("  Émile Zola!  "), "émile_zola");
+        assert_eq!(snake_case("--"), "");
+    }
+
+    #[test]
+    fn camel_cases() {
+        assert_eq!(camel_case("Hello World"), "helloWorld");
+        assert_eq!(camel_case("hello-world_fooBar baz"), "helloWorldFooBarBaz");
+        assert_eq!(camel_case("HTTP_SERVER"), "httpServer");
+        assert_eq!(camel_case("émile zola"), "émileZola");
+        assert_eq!(camel_case(""), "");
+    }
+}
@@ -0,0 +1,380 @@
+//! Path lookups and edits of JSON text behind the json_get, json_set and to_json commands
+//!
+//! Paths are keys separated by dots with array indices in brackets, `a.b[0]`, keys with dots or
+//! brackets in them are quoted in brackets, `["a.b"]`. An empty path is the whole document
+
+use std::fmt::Display;
+
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, "{:?}", key),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    InvalidPath {
+        path: String,
+        reason: String,
+    },
+    InvalidJson {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// Nothing is at path, segment is the first part of it that wasn't found
+    Missing {
+        path: String,
+        segment: String,
+    },
+    TooLong {
+        length: usize,
+        limit: usize,
+    },
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::InvalidPath { path, reason } => {
+                write!(f, "invalid JSON path \"{}\": {}", path, reason)
+            }
+            JsonError::InvalidJson {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "invalid JSON at line {} column {}: {}",
+                line, column, message
+            ),
+            JsonError::Missing { path, segment } => {
+                write!(
+                    f,
+                    "nothing at JSON path \"{}\", {} not found",
+                    path, segment
+                )
+            }
+            JsonError::TooLong { length, limit } => write!(
+                f,
+                "JSON is {} characters long, the limit is {}",
+                length, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Splits path into its keys and indices
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>, JsonError> {
+    let invalid = |reason: &str| JsonError::InvalidPath {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let mut segments = Vec::new();
+    let mut chars = path.trim().chars().peekable();
+    // A key is expected at the start and after every dot
+    let mut expect_key = true;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '[' => {
+                chars.next();
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    let mut key = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\\') => match chars.next() {
+                                Some(escaped) => key.push(escaped),
+                                None => return Err(invalid("unterminated quoted key")),
+                            },
+                            Some('"') => break,
+                            Some(c) => key.push(c),
+                            None => return Err(invalid("unterminated quoted key")),
+                        }
+                    }
+                    if chars.next() != Some(']') {
+                        return Err(invalid("expected ] after quoted key"));
+                    }
+                    segments.push(PathSegment::Key(key));
+                } else {
+                    let mut index = String::new();
+                    loop {
+                        match chars.next() {
+                            Some(']') => break,
+                            Some(c) => index.push(c),
+                            None => return Err(invalid("unterminated index")),
+                        }
+                    }
+                    let index = index
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| invalid("indices must be whole numbers of at least 0"))?;
+                    segments.push(PathSegment::Index(index));
+                }
+                expect_key = false;
+            }
+            '.' => {
+                chars.next();
+                if expect_key {
+                    return Err(invalid("empty key"));
+                }
+                expect_key = true;
+            }
+            ']' => return Err(invalid("unmatched ]")),
+            _ => {
+                if !expect_key {
+                    return Err(invalid("expected . or [ between keys"));
+                }
+                let mut key = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '.' || c == '[' || c == ']' {
+                        break;
+                    }
+                    key.push(c);
+                    chars.next();
+                }
+                segments.push(PathSegment::Key(key));
+                expect_key = false;
+            }
+        }
+    }
+    if expect_key && !segments.is_empty() {
+        return Err(invalid("path ends with a dot"));
+    }
+    Ok(segments)
+}
+
+fn check_length(text: &str, limit: usize) -> Result<(), JsonError> {
+    let length = text.chars().count();
+    if length > limit {
+        Err(JsonError::TooLong { length, limit })
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses text as JSON unless it is longer than limit
+pub fn parse(text: &str, limit: usize) -> Result<Value, JsonError> {
+    check_length(text, limit)?;
+    serde_json::from_str(text).map_err(|e| JsonError::InvalidJson {
+        line: e.line(),
+        column: e.column(),
+        message: e.to_string(),
+    })
+}
+
+/// Writes json as compact text unless it is longer than limit
+pub fn to_text(json: &Value, limit: usize) -> Result<String, JsonError> {
+    let text = json.to_string();
+    check_length(&text, limit)?;
+    Ok(text)
+}
+
+/// Reads text as JSON when it is a JSON value and as a string otherwise
+///
+/// Lets `json_set` keep numbers and booleans while plain words don't have to be quoted
+pub fn parse_value(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+fn missing(path: &[PathSegment], segment: &PathSegment) -> JsonError {
+    JsonError::Missing {
+        path: path_to_string(path),
+        segment: segment.to_string(),
+    }
+}
+
+fn path_to_string(path: &[PathSegment]) -> String {
+    let mut text = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key)
+                if !key.is_empty() && !key.contains(['.', '[', ']', '"', '\\']) =>
+            {
+                if !text.is_empty() {
+                    text.push('.');
+                }
+                text.push_str(key);
+            }
+            PathSegment::Key(key) => {
+                text.push_str(&format!("[{}]", serde_json::to_string(key).unwrap()))
+            }
+            PathSegment::Index(index) => text.push_str(&format!("[{}]", index)),
+        }
+    }
+    text
+}
+
+/// The value at path in json
+pub fn get<'a>(json: &'a Value, path: &[PathSegment]) -> Result<&'a Value, JsonError> {
+    let mut current = json;
+    for segment in path {
+        let next = match (segment, current) {
+            (PathSegment::Key(key), Value::Object(object)) => object.get(key),
+            (PathSegment::Index(index), Value::Array(array)) => array.get(*index),
+            _ => None,
+        };
+        current = next.ok_or_else(|| missing(path, segment))?;
+    }
+    Ok(current)
+}
+
+/// Sets path in json to value
+///
+/// Missing keys are added as objects on the way, an index may be one past the end of an array to
+/// append to it
+pub fn set(json: &mut Value, path: &[PathSegment], value: Value) -> Result<(), JsonError> {
+    let mut current = json;
+    for (i, segment) in path.iter().enumerate() {
+        let last = i == path.len() - 1;
+        let placeholder = || {
+            if last {
+                Value::Null
+            } else {
+                Value::Object(Map::new())
+            }
+        };
+        current = match (segment, current) {
+            (PathSegment::Key(key), Value::Object(object)) => {
+                object.entry(key.clone()).or_insert_with(placeholder)
+            }
+            (PathSegment::Index(index), Value::Array(array)) if *index <= array.len() => {
+                if *index == array.len() {
+                    array.push(placeholder());
+                }
+                &mut array[*index]
+            }
+            _ => return Err(missing(path, segment)),
+        };
+    }
+    *current = value;
+    Ok(())
+}
+
+#[cfg(test)]
+mod json_path_test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn path(path: &str) -> Vec<PathSegment> {
+        parse_path(path).unwrap()
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(path(""), vec![]);
+        assert_eq!(
+            path("a.b[2][0].c"),
+            vec![
+                PathSegment::Key("a".into()),
+                PathSegment::Key("b".into()),
+                PathSegment::Index(2),
+                PathSegment::Index(0),
+                PathSegment::Key("c".into()),
+            ]
+        );
+        assert_eq!(
+            path("[0][\"x.y\"].z"),
+            vec![
+                PathSegment::Index(0),
+                PathSegment::Key("x.y".into()),
+                PathSegment::Key("z".into()),
+            ]
+        );
+        for invalid in [
+            "a..b", "a.", ".a", "a[-1]", "a[x]", "a[0", "a]", "a[0]b", "[\"a",
+        ] {
+            assert!(
+                matches!(parse_path(invalid), Err(JsonError::InvalidPath { .. })),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn nested_lookups() {
+        let json = json!({"user": {"name": "Ann", "tags": ["a", {"id": 7}]}, "x.y": true});
+        assert_eq!(get(&json, &path("user.name")), Ok(&json!("Ann")));
+        assert_eq!(get(&json, &path("user.tags[1].id")), Ok(&json!(7)));
+        assert_eq!(get(&json, &path("[\"x.y\"]")), Ok(&json!(true)));
+        assert_eq!(get(&json, &path("")), Ok(&json));
+
+        assert_eq!(
+            get(&json, &path("user.tags[5]")),
+            Err(JsonError::Missing {
+                path: "user.tags[5]".into(),
+                segment: "[5]".into(),
+            })
+        );
+        assert!(get(&json, &path("user.name.first")).is_err());
+        assert!(get(&json, &path("user[0]")).is_err());
+    }
+
+    #[test]
+    fn sets_keep_types() {
+        let mut json = json!({"a": {"list": [1, 2]}});
+        set(&mut json, &path("a.list[0]"), parse_value("1.5")).unwrap();
+        set(&mut json, &path("a.list[2]"), parse_value("true")).unwrap();
+        set(&mut json, &path("a.name"), parse_value("Ann")).unwrap();
+        set(&mut json, &path("a.quoted"), parse_value("\"5\"")).unwrap();
+        set(&mut json, &path("b.c.d"), parse_value("null")).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "a": {"list": [1.5, 2, true], "name": "Ann", "quoted": "5"},
+                "b": {"c": {"d": null}}
+            })
+        );
+
+        assert!(set(&mut json, &path("a.list[9]"), json!(1)).is_err());
+        assert!(set(&mut json, &path("a.name.first"), json!(1)).is_err());
+
+        set(&mut json, &path(""), json!([])).unwrap();
+        assert_eq!(json, json!([]));
+    }
+
+    #[test]
+    fn invalid_json_has_position() {
+        assert!(matches!(
+            parse("{\n  \"a\": 1,\n  oops\n}", 100),
+            Err(JsonError::InvalidJson {
+                line: 3,
+                column: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn size_cap() {
+        assert!(parse("[1, 2]", 6).is_ok());
+        assert_eq!(
+            parse("[1, 2]", 5),
+            Err(JsonError::TooLong {
+                length: 6,
+                limit: 5
+            })
+        );
+        assert_eq!(to_text(&json!([1, 2]), 5), Ok("[1,2]".to_string()));
+        assert!(matches!(
+            to_text(&json!([1, 2, 3]), 5),
+            Err(JsonError::TooLong { .. })
+        ));
+    }
+}
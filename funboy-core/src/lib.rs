@@ -1,105 +1,575 @@
 use std::{
+    borrow::Cow,
     collections::{HashMap, HashSet},
     fmt::Debug,
+    future::{Future, ready},
     hash::{DefaultHasher, Hash, Hasher},
+    pin::Pin,
     str::FromStr,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use async_recursion::async_recursion;
 use fsl_interpreter::{
     FslInterpreter, InterpreterData,
-    commands::{INDEX_TYPES, TEXT_TYPES, WHOLE_NUMBER_TYPES},
+    commands::{INDEX_TYPES, NUMERIC_TYPES, TEXT_TYPES, WHOLE_NUMBER_TYPES},
     types::{
         command::{ArgPos, ArgRule, Command, CommandError, Executor, UserCommand},
         value::Value,
     },
 };
 use moka::future::{Cache, CacheBuilder};
+#[cfg(feature = "ollama")]
 use ollama_rs::{generation::completion::GenerationResponse, models::ModelInfo};
 use rand::{Rng, distr::uniform::SampleUniform, random_range};
 use regex::Regex;
 use tokio::sync::Mutex;
 
 use crate::{
-    ollama::{OllamaGenerator, OllamaSettings},
+    ai_label::{AiLabel, LabelPlacement},
+    checked_math::{Number, Operand},
+    dice::{Dice, DiceRoll},
+    distribution::{DistributionReport, pick_substitute},
+    documentation::{CommandDocumentation, get_command_documentation},
+    embedded_code::{
+        CODE_BLOCK_OPEN, DEFAULT_MAX_EXPRESSION_DEPTH, block_contents, check_embedded_code,
+        check_expression_depth, expand_numeric_literals, find_code_blocks, find_command_calls,
+        separate_statements, top_level_commands,
+    },
+    events::{EventBus, EventThresholds, FunboyEvent},
+    featured::{FeaturedStrategy, USAGE_WINDOW_DAYS, select_featured},
+    grammar::{a_or_an, camel_case, capitalize, ordinal, plural, snake_case, title_case},
+    lint::{LintIssue, PreviewWarning},
+    output_style::{OutputStyle, StyledDisplay},
+    page_cache::{CachedTemplate, PageCache},
+    public_browse::{PublicPage, etag},
+    selection::{Selection, SelectionState, SelectionStates, SelectionStrategy},
+    self_test::{SelfTestReport, SelfTestSpec, run_case},
+    source_search::{FUZZY_THRESHOLD, MatchQuality, fragments, normalize, trigram_similarity},
+    substitute_kind::SubKind,
     template_database::{
-        KeySize, Limit, OrderBy, Substitute, SubstituteReceipt, Template, TemplateDatabase,
-        TemplateReceipt,
+        Alias, CloneReport, Contribution, Contributor, Example, Favorite, FavoriteInsert,
+        FeaturedCandidate, FeaturedChannel, IgnoreReason, IgnoredEntry, KeySize, Limit,
+        NewSubstitute, OrderBy, PlaybackEvent, PublicTemplate, ReferenceChange, RewriteScope,
+        SortOrder, Substitute, SubstituteReceipt, SubstituteWarning, Template,
+        TemplateContribution, TemplateDatabase, TemplateFilter, TemplateReceipt, TemplateUsage,
+        Trigger,
+    },
+    template_export::{
+        EXPORT_VERSION, ExportedSubstitute, ExportedTemplate, ImportOptions, ImportReport,
+        TemplateExport,
+    },
+    template_substitutor::{
+        DelimiterError, DelimiterRegistry, ExpansionCounter, ExpansionLimits, TemplateDelimiter,
+        TemplateSubstitutor, VALID_TEMPLATE_CHARS,
+    },
+    template_usage::{UsageAccumulator, day_of},
+    triggers::{CompiledTriggers, MAX_TRIGGER_COOLDOWN_SECS, TriggerMatchMode, TriggerMatcher},
+    user_facing_error::{TemplateNameReason, UserFacingError},
+};
+#[cfg(feature = "ollama")]
+use crate::{
+    ollama::{
+        OllamaGenerator, OllamaRequest, OllamaSettings, PROMPT_PLACEHOLDER, PresetMode,
+        PresetScope, apply_preset, resolve_preset,
     },
-    template_substitutor::{TemplateDelimiter, TemplateSubstitutor, VALID_TEMPLATE_CHARS},
+    template_database::PromptPreset,
 };
 
+pub mod ai_label;
+pub mod checked_math;
+pub mod dice;
+pub mod distribution;
+pub mod documentation;
+pub mod edit_distance;
+pub mod embedded_code;
+pub mod events;
+pub mod featured;
+pub mod grammar;
+pub mod json_path;
+pub mod lint;
+pub mod message_format;
+#[cfg(feature = "ollama")]
 pub mod ollama;
+pub mod output_style;
+pub mod page_cache;
+pub mod public_browse;
+pub mod quote_filter;
+pub mod selection;
+pub mod self_test;
+pub mod source_search;
+pub mod substitute_kind;
 pub mod template_database;
+pub mod template_export;
 pub mod template_substitutor;
+pub mod template_usage;
+pub mod triggers;
+pub mod user_facing_error;
 
 #[derive(Debug, Clone)]
 pub enum FunboyError {
     Interpreter(String),
     Ollama(String),
     Database(String),
-    UserInput(String),
+    UserInput(UserFacingError),
 }
 
-impl ToString for FunboyError {
-    fn to_string(&self) -> String {
+impl FunboyError {
+    /// Short name of the variant, used in logs and JSON errors
+    pub fn kind(&self) -> &'static str {
         match self {
-            FunboyError::Interpreter(e) => {
-                format!("FSL interpreter error:\n{}", e)
+            FunboyError::Interpreter(_) => "interpreter",
+            FunboyError::Ollama(_) => "ollama",
+            FunboyError::Database(_) => "database",
+            FunboyError::UserInput(_) => "user_input",
+        }
+    }
+}
+
+impl StyledDisplay for FunboyError {
+    fn to_styled_string(&self, style: OutputStyle) -> String {
+        match (self, style) {
+            (FunboyError::UserInput(e), OutputStyle::Json) => e.to_styled_string(style),
+            (FunboyError::Interpreter(e), _) => {
+                style.error(self.kind(), format!("FSL interpreter error:\n{}", e))
             }
-            FunboyError::Ollama(e) => {
-                format!("Ollama error:\n{}", e)
+            (FunboyError::Ollama(e), _) => {
+                style.error(self.kind(), format!("Ollama error:\n{}", e))
             }
-            FunboyError::Database(e) => {
-                format!("Database error:\n{}", e)
+            (FunboyError::Database(e), _) => {
+                style.error(self.kind(), format!("Database error:\n{}", e))
             }
-            FunboyError::UserInput(e) => {
-                format!("User input error:\n{}", e)
+            (FunboyError::UserInput(e), _) => {
+                format!("User input error:\n{}", e.to_styled_string(style))
             }
         }
     }
 }
 
+impl ToString for FunboyError {
+    fn to_string(&self) -> String {
+        self.to_styled_string(OutputStyle::default())
+    }
+}
+
 impl From<sqlx::Error> for FunboyError {
     fn from(value: sqlx::Error) -> Self {
-        eprintln!("{}", value);
+        tracing::error!(error = %value, "database error");
         FunboyError::Database(value.to_string())
     }
 }
 
+/// Logs a failed call with the variant of its error, user input errors are expected so they
+/// only show up at debug level
+fn log_error(error: &FunboyError) {
+    let message = error.to_styled_string(OutputStyle::Plain);
+    match error {
+        FunboyError::UserInput(e) => {
+            tracing::debug!(
+                error = error.kind(),
+                reason = e.kind(),
+                message,
+                "call failed"
+            )
+        }
+        _ => tracing::warn!(error = error.kind(), message, "call failed"),
+    }
+}
+
+/// What a bulk template operation does once confirmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BulkOperation {
+    Delete,
+    Archive,
+}
+
+/// The templates a bulk operation would change and the token that confirms exactly that change
+#[derive(Debug, Clone)]
+pub struct BulkPreview {
+    pub templates: Vec<Template>,
+    pub token: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum BulkOutcome {
+    /// Nothing was changed, pass the token back to apply the operation
+    Preview(BulkPreview),
+    Applied(Vec<Template>),
+}
+
+/// Derives a token from everything a confirmation vouches for
+///
+/// Tokens hold no state so they stay valid across processes, the CLI previews and confirms in
+/// separate runs. Any template matching or no longer matching the filter changes the token.
+fn bulk_token(operation: BulkOperation, filter: &TemplateFilter, templates: &[Template]) -> String {
+    let mut hasher = DefaultHasher::new();
+    operation.hash(&mut hasher);
+    filter.hash(&mut hasher);
+    for template in templates {
+        template.id.hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// How substitutes with embedded code that fails to parse are added
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodeValidation {
+    /// Add them anyway and list them in the receipt warnings
+    #[default]
+    Warn,
+    /// Leave them out and list them in the receipt warnings
+    Strict,
+}
+
+/// The value a single top level command produced while interpreting embedded code
+#[derive(Debug, Clone)]
+pub struct CommandValue {
+    pub command: String,
+    pub value: String,
+}
+
+/// How a generation treats templates still nested when [`GenerateOptions::max_depth`] is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthMode {
+    /// Fail with [`UserFacingError::NestingTooDeep`]
+    Strict,
+    /// Keep the unresolved templates in the output and list the problem in its warnings
+    #[default]
+    Lenient,
+}
+
+/// Options for a single call to [`Funboy::generate_with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerateOptions {
+    /// How many passes over the input may run, which is also how deeply templates may nest
+    /// within a single pass
+    pub max_depth: u8,
+    pub depth_mode: DepthMode,
+}
+
+impl GenerateOptions {
+    pub const DEFAULT_MAX_DEPTH: u8 = 32;
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            depth_mode: DepthMode::default(),
+        }
+    }
+}
+
+/// The text a generation produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationOutput {
+    pub text: String,
+    /// Nothing is visible in text, like when every branch of a condition was false
+    pub is_effectively_empty: bool,
+    /// How many passes over the input ran before it was complete
+    pub passes: u8,
+    /// Problems that did not stop the generation, such as templates left unresolved in
+    /// [`DepthMode::Lenient`]
+    pub warnings: Vec<UserFacingError>,
+}
+
+impl GenerationOutput {
+    pub fn new(text: String, passes: u8) -> Self {
+        Self {
+            is_effectively_empty: is_effectively_empty(&text),
+            text,
+            passes,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Whether text is empty once whitespace and zero-width characters are trimmed
+pub fn is_effectively_empty(text: &str) -> bool {
+    text.chars().all(|c| {
+        c.is_whitespace()
+            || matches!(
+                c,
+                '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}'
+            )
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugOutput {
+    pub output: String,
+    pub log: Vec<CommandValue>,
+}
+
+type CommandLog = Arc<Mutex<Vec<CommandValue>>>;
+type SharedExpansionCounter = Arc<Mutex<ExpansionCounter>>;
+
+fn value_to_log_string(value: &Value) -> String {
+    match value {
+        Value::None => "None".to_string(),
+        Value::Text(text) => format!("\"{}\"", text),
+        Value::Int(int) => int.to_string(),
+        Value::Float(float) => float.to_string(),
+        Value::Bool(bool) => bool.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// The substitutes renaming a template would rewrite
+#[derive(Debug, Clone)]
+pub struct RenamePreview {
+    pub from: String,
+    pub to: String,
+    pub changes: Vec<ReferenceChange>,
+}
+
+impl RenamePreview {
+    /// Groups changes by the delimiter that caused them skipping delimiters without changes
+    pub fn by_delimiter(&self) -> Vec<(TemplateDelimiter, Vec<&ReferenceChange>)> {
+        let mut delimiters: Vec<TemplateDelimiter> = Vec::new();
+        for change in &self.changes {
+            if !delimiters.contains(&change.delimiter) {
+                delimiters.push(change.delimiter);
+            }
+        }
+
+        delimiters
+            .iter()
+            .map(|delimiter| {
+                let changes = self
+                    .changes
+                    .iter()
+                    .filter(|change| change.delimiter == *delimiter)
+                    .collect::<Vec<_>>();
+                (*delimiter, changes)
+            })
+            .filter(|(_, changes)| !changes.is_empty())
+            .collect()
+    }
+}
+
+/// How rewriting references changed a single substitute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewrittenSubstitute {
+    pub substitute_id: KeySize,
+    pub template: String,
+    pub before: String,
+    pub after: String,
+    /// References to the old template before the rewrite
+    pub old_references: usize,
+    /// References to the new template after the rewrite
+    pub new_references: usize,
+}
+
+/// The substitutes [`Funboy::rewrite_references`] rewrote or would rewrite when previewed
+#[derive(Debug, Clone)]
+pub struct RewriteReport {
+    pub from: String,
+    pub to: String,
+    pub scope: RewriteScope,
+    pub substitutes: Vec<RewrittenSubstitute>,
+}
+
+impl RewriteReport {
+    /// Templates with rewritten substitutes in the order they were first rewritten
+    pub fn templates(&self) -> Vec<&str> {
+        let mut templates: Vec<&str> = Vec::new();
+        for sub in &self.substitutes {
+            if !templates.contains(&sub.template.as_str()) {
+                templates.push(&sub.template);
+            }
+        }
+        templates
+    }
+}
+
+/// The pinned examples of a template alongside a fresh generation of it
+#[derive(Debug, Clone)]
+pub struct TemplatePreview {
+    pub template: String,
+    pub examples: Vec<String>,
+    pub generated: String,
+}
+
+/// What curators know about a template, assembled by [`Funboy::get_template_info`]
+#[derive(Debug, Clone)]
+pub struct TemplateInfo {
+    pub template: Template,
+    pub description: Option<String>,
+    /// Unix seconds
+    pub created_at: i64,
+    pub archived: bool,
+    pub substitutes: i64,
+    pub disabled_substitutes: i64,
+    pub substitute_cap: i64,
+    /// Whether substitute_cap was set for this template rather than being the default
+    pub custom_cap: bool,
+    pub examples: Vec<String>,
+}
+
+/// What a substitute would generate if it were picked from a template, see
+/// [`Funboy::preview_substitute`]
+#[derive(Debug, Clone)]
+pub struct PreviewResult {
+    pub template: String,
+    pub substitute: String,
+    /// None when generating failed, the reason is in warnings
+    pub generated: Option<String>,
+    pub warnings: Vec<PreviewWarning>,
+}
+
+/// Picks a substitute for a template that has none in the database, None leaves it unresolved
+pub type FallbackResolver =
+    Arc<dyn Fn(&str) -> Pin<Box<dyn Future<Output = Option<String>> + Send>> + Send + Sync>;
+
+#[derive(Clone)]
+struct Fallback(FallbackResolver);
+
+impl Debug for Fallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FallbackResolver")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Funboy {
     template_db: TemplateDatabase,
+    #[cfg(feature = "ollama")]
     ollama_model: Arc<Mutex<Option<String>>>,
+    #[cfg(feature = "ollama")]
     ollama_generator: OllamaGenerator,
     valid_template_regex: Regex,
-    random_sub_cache: Arc<Cache<String, Vec<Substitute>>>,
+    random_sub_cache: Arc<PageCache>,
+    selection: Arc<SelectionStates>,
+    trigger_cache: Arc<Cache<KeySize, Arc<CompiledTriggers>>>,
+    reserved_template_names: Arc<HashSet<String>>,
+    expansion_limits: ExpansionLimits,
+    max_expression_depth: usize,
+    max_substitutes: i64,
+    blocking_threshold: Option<usize>,
+    fallback_resolver: Option<Fallback>,
+    usage: Arc<UsageAccumulator>,
+    usage_guild: Option<KeySize>,
+    contributor: Option<Contributor>,
+    delimiters: DelimiterRegistry,
+    events: EventBus,
+    event_thresholds: EventThresholds,
 }
 
 impl Funboy {
     pub fn new(template_db: TemplateDatabase) -> Self {
         Self {
             template_db,
+            #[cfg(feature = "ollama")]
             ollama_generator: OllamaGenerator::default(),
+            #[cfg(feature = "ollama")]
             ollama_model: Arc::new(Mutex::new(None)),
             valid_template_regex: Regex::new(&format!("^[{}]+$", VALID_TEMPLATE_CHARS)).unwrap(),
-            random_sub_cache: Arc::new(
-                CacheBuilder::new(20)
-                    .time_to_live(Duration::from_secs(60))
+            random_sub_cache: Arc::new(PageCache::new(20, Duration::from_secs(60))),
+            selection: Arc::new(SelectionStates::default()),
+            trigger_cache: Arc::new(
+                CacheBuilder::new(1000)
+                    .time_to_idle(Duration::from_secs(60 * 10))
                     .build(),
             ),
+            reserved_template_names: Arc::new(reserved_template_names(get_command_documentation())),
+            expansion_limits: ExpansionLimits::default(),
+            max_expression_depth: DEFAULT_MAX_EXPRESSION_DEPTH,
+            max_substitutes: Funboy::DEFAULT_MAX_SUBSTITUTES,
+            blocking_threshold: None,
+            fallback_resolver: None,
+            usage: Arc::new(UsageAccumulator::default()),
+            usage_guild: None,
+            contributor: None,
+            delimiters: DelimiterRegistry::default(),
+            events: EventBus::default(),
+            event_thresholds: EventThresholds::default(),
         }
     }
 
-    pub async fn get_ollama_model(&self) -> Option<String> {
-        self.ollama_model.lock().await.clone()
+    /// Overrides how many templates a single generation may resolve
+    pub fn with_expansion_limits(mut self, expansion_limits: ExpansionLimits) -> Self {
+        self.expansion_limits = expansion_limits;
+        self
     }
 
-    pub async fn set_ollama_model(&self, new_model: Option<String>) {
-        let mut model = self.ollama_model.lock().await;
-        *model = new_model;
+    /// Overrides how deeply command calls may be nested inside of a code block
+    pub fn with_max_expression_depth(mut self, max_expression_depth: usize) -> Self {
+        self.max_expression_depth = max_expression_depth;
+        self
+    }
+
+    /// Interprets code longer than threshold bytes on a blocking thread
+    ///
+    /// Interpreting is CPU bound between commands so large scripts would otherwise stall every
+    /// other task on the same worker. Small inputs stay inline since moving to another thread
+    /// costs more than they take to run.
+    pub fn with_blocking_threshold(mut self, threshold: usize) -> Self {
+        self.blocking_threshold = Some(threshold);
+        self
+    }
+
+    /// Overrides when generations emit events such as [`FunboyEvent::SlowGeneration`]
+    pub fn with_event_thresholds(mut self, event_thresholds: EventThresholds) -> Self {
+        self.event_thresholds = event_thresholds;
+        self
+    }
+
+    /// Receives the events of generations crossing the [`EventThresholds`], clones share events
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<FunboyEvent> {
+        self.events.subscribe()
+    }
+
+    /// Consults resolver for templates without substitutes in the database
+    ///
+    /// Resolved substitutes are neither stored nor cached so they can differ per generation,
+    /// clone the Funboy to use a resolver for a single generation. Reserve the names it resolves
+    /// with [`Funboy::with_reserved_names`] so they cannot be created as templates.
+    pub fn set_fallback_resolver(&mut self, resolver: FallbackResolver) {
+        self.fallback_resolver = Some(Fallback(resolver));
+    }
+
+    /// Counts template uses toward guild_id, None counts them as outside of a guild
+    ///
+    /// Clones share their pending uses so like [`Funboy::set_fallback_resolver`] this is meant
+    /// for a clone made for a single generation
+    pub fn set_usage_guild(&mut self, guild_id: Option<u64>) {
+        self.usage_guild = guild_id.map(|guild_id| guild_id as KeySize);
+    }
+
+    /// Credits substitutes added with [`Funboy::add_substitutes`] to user_id in guild_id
+    ///
+    /// Meant for a clone made for a single command like [`Funboy::set_usage_guild`]
+    pub fn set_contributor(&mut self, user_id: u64, guild_id: Option<u64>) {
+        self.contributor = Some(Contributor {
+            user_id: user_id as KeySize,
+            guild_id: guild_id.map(|guild_id| guild_id as KeySize),
+        });
+    }
+
+    /// Overrides how many substitutes a template without its own cap may hold
+    pub fn with_max_substitutes(mut self, max_substitutes: i64) -> Self {
+        self.max_substitutes = max_substitutes;
+        self
+    }
+
+    /// Reserves additional command names registered by consumers so templates cannot shadow them
+    pub fn with_reserved_names(mut self, names: &[&str]) -> Self {
+        let mut reserved_template_names = self.reserved_template_names.as_ref().clone();
+        reserved_template_names.extend(names.iter().map(|name| name.to_string()));
+        self.reserved_template_names = Arc::new(reserved_template_names);
+        self
+    }
+
+    /// Recognizes templates written with delimiter in addition to `^` such as `%noun%`
+    pub fn with_delimiter(mut self, delimiter: TemplateDelimiter) -> Result<Self, DelimiterError> {
+        self.delimiters.register(delimiter)?;
+        Ok(self)
+    }
+
+    pub fn is_reserved_name(&self, name: &str) -> bool {
+        self.reserved_template_names.contains(name)
     }
 
     fn gen_rand_num_inclusive<T: SampleUniform + PartialOrd>(min: T, max: T) -> T {
@@ -116,11 +586,11 @@ impl Funboy {
         min: &str,
         max: &str,
         inclusive: bool,
-    ) -> Result<String, &'static str> {
+    ) -> Result<String, UserFacingError> {
         match (min.parse(), max.parse()) {
             (Ok(min), Ok(max)) => {
                 if min >= max {
-                    Err("min must be less than max")
+                    Err(UserFacingError::MinNotLessThanMax)
                 } else {
                     if inclusive {
                         Ok(Self::gen_rand_num_inclusive::<T>(min, max).to_string())
@@ -129,31 +599,45 @@ impl Funboy {
                     }
                 }
             }
-            _ => Err("min and max values must be a number"),
+            _ => Err(UserFacingError::RangeNotNumeric),
         }
     }
 
     pub fn random_number(min: &str, max: &str, inclusive: bool) -> Result<String, FunboyError> {
         if min.contains('.') || max.contains('.') {
+            // Sampling panics when a bound is NaN or infinite or the span between them overflows
+            if let (Ok(min), Ok(max)) = (min.parse::<f64>(), max.parse::<f64>())
+                && !(max - min).is_finite()
+            {
+                return Err(FunboyError::UserInput(UserFacingError::RangeNotFinite));
+            }
             match Self::gen_rand_num_from_str::<f64>(min, max, inclusive) {
                 Ok(result) => Ok(result),
 
-                Err(e) => Err(FunboyError::UserInput(e.to_string())),
+                Err(e) => Err(FunboyError::UserInput(e)),
             }
         } else {
             match Self::gen_rand_num_from_str::<i64>(min, max, inclusive) {
                 Ok(result) => Ok(result),
 
-                Err(e) => Err(FunboyError::UserInput(e.to_string())),
+                Err(e) => Err(FunboyError::UserInput(e)),
             }
         }
     }
 
+    /// Rolls dice written like `d20` or `3d6`
+    pub fn roll_dice(dice: &str) -> Result<DiceRoll, FunboyError> {
+        match dice.parse::<Dice>() {
+            Ok(dice) => Ok(dice.roll()),
+            Err(e) => Err(FunboyError::UserInput(e)),
+        }
+    }
+
     pub fn random_entry<'b>(list: &[&'b str]) -> Result<&'b str, FunboyError> {
         if list.len() < 2 {
-            Err(FunboyError::UserInput(
-                "list must contain at least two entries".to_string(),
-            ))
+            Err(FunboyError::UserInput(UserFacingError::ListTooShort {
+                min: 2,
+            }))
         } else {
             let output = list[Self::gen_rand_num_inclusive(0, list.len() - 1)];
             Ok(output)
@@ -162,39 +646,225 @@ impl Funboy {
 
     pub const MAX_TEMPLATE_LENGTH: usize = 255;
     fn validate_template_name(&self, template: &str) -> Result<(), FunboyError> {
-        if template.is_empty() {
-            return Err(FunboyError::UserInput(
-                "template cannot be empty".to_string(),
-            ));
-        } else if template.chars().nth(0).is_some_and(|ch| ch.is_numeric()) {
-            return Err(FunboyError::UserInput(
-                "first character of template cannot be a number".to_string(),
-            ));
-        } else if !self.valid_template_regex.is_match(template) {
-            return Err(FunboyError::UserInput(
-                "template must be lowercase containing only characters a-z, 0-9, and _".to_string(),
-            ));
-        } else if template.len() > Funboy::MAX_TEMPLATE_LENGTH {
+        match self.template_name_reason(template) {
+            Some(reason) => Err(FunboyError::UserInput(
+                UserFacingError::TemplateNameInvalid { reason },
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Why name can't be used as a template name, None when it can
+    fn template_name_reason(&self, name: &str) -> Option<TemplateNameReason> {
+        if name.is_empty() {
+            Some(TemplateNameReason::Empty)
+        } else if name.chars().nth(0).is_some_and(|ch| ch.is_numeric()) {
+            Some(TemplateNameReason::StartsWithNumber)
+        } else if !self.valid_template_regex.is_match(name) {
+            Some(TemplateNameReason::InvalidCharacters)
+        } else if name.len() > Funboy::MAX_TEMPLATE_LENGTH {
+            Some(TemplateNameReason::TooLong {
+                limit: Funboy::MAX_TEMPLATE_LENGTH,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Vars starting with this are set from the context of a generation and cannot be stored by
+    /// embedded code, see [`Funboy::generate_with_vars`]
+    pub const RESERVED_VAR_PREFIX: &str = "ctx_";
+
+    /// Checks name can be saved by a user, which also keeps it clear of reserved vars
+    pub fn validate_user_variable_name(name: &str) -> Result<(), UserFacingError> {
+        Self::validate_variable_name(name)?;
+        if name.starts_with(Self::RESERVED_VAR_PREFIX) {
+            return Err(UserFacingError::VariableReserved {
+                name: name.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks name can be stored and read back in embedded code as an identifier
+    pub fn validate_variable_name(name: &str) -> Result<(), UserFacingError> {
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_lowercase() || ch == '_')
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(UserFacingError::VariableNameInvalid {
+                name: name.to_string(),
+            })
+        }
+    }
+
+    pub const MAX_SUBSTITUTE_LENGTH: usize = 16000;
+    /// Keeps random picks and listings fast, see [`Funboy::set_substitute_cap`] for exceptions
+    pub const DEFAULT_MAX_SUBSTITUTES: i64 = 10_000;
+    /// Why substitute would be left out of a batch before reaching the database
+    fn substitute_ignore_reason(substitute: &str) -> Option<IgnoreReason> {
+        let len = substitute.chars().count();
+        if substitute.trim().is_empty() {
+            Some(IgnoreReason::Empty)
+        } else if len > Funboy::MAX_SUBSTITUTE_LENGTH {
+            Some(IgnoreReason::TooLong {
+                len,
+                max: Funboy::MAX_SUBSTITUTE_LENGTH,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn validate_substitute(substitute: &str) -> Result<(), FunboyError> {
+        let length = substitute.chars().count();
+        if length > Funboy::MAX_SUBSTITUTE_LENGTH {
+            return Err(FunboyError::UserInput(UserFacingError::SubstituteTooLong {
+                length,
+                limit: Funboy::MAX_SUBSTITUTE_LENGTH,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Validates a template name that is about to be created or renamed to
+    ///
+    /// Existing templates are only checked with [`Funboy::validate_template_name`] so templates
+    /// that predate a reservation can still be read, renamed, and deleted
+    fn validate_new_template_name(&self, template: &str) -> Result<(), FunboyError> {
+        self.validate_template_name(template)?;
+        if self.is_reserved_name(template) {
             return Err(FunboyError::UserInput(
-                "template must be less than 255 characters long".to_string(),
+                UserFacingError::TemplateNameReserved {
+                    name: template.to_string(),
+                },
             ));
         }
         Ok(())
     }
 
+    /// Validates the name of a template substitutes are about to be added to
+    ///
+    /// A reserved name is only rejected when the template doesn't exist yet and would be created,
+    /// so templates that predate a reservation can still be added to
+    async fn validate_template_name_to_add_to(&self, template: &str) -> Result<(), FunboyError> {
+        if self.is_reserved_name(template) && self.template_db.template_exists(template).await? {
+            self.validate_template_name(template)
+        } else {
+            self.validate_new_template_name(template)
+        }
+    }
+
+    /// Adds substitutes listing any whose embedded code fails to parse in the receipt warnings
     pub async fn add_substitutes<'a>(
         &self,
         template: &str,
         substitutes: &[&'a str],
     ) -> Result<SubstituteReceipt, FunboyError> {
-        self.validate_template_name(template)?;
+        self.add_substitutes_with_validation(template, substitutes, CodeValidation::Warn)
+            .await
+    }
 
-        let receipt = self.template_db.create_substitutes(template, substitutes);
-        let receipt = receipt.await?;
+    /// Adds substitutes after parsing the embedded code of each one without running it
+    #[tracing::instrument(level = "debug", skip(self, substitutes), fields(count = substitutes.len()))]
+    pub async fn add_substitutes_with_validation<'a>(
+        &self,
+        template: &str,
+        substitutes: &[&'a str],
+        validation: CodeValidation,
+    ) -> Result<SubstituteReceipt, FunboyError> {
+        let substitutes: Vec<NewSubstitute> = substitutes
+            .iter()
+            .map(|substitute| NewSubstitute::new(substitute).with_contributor(self.contributor))
+            .collect();
+        self.add_new_substitutes(template, &substitutes, validation)
+            .await
+    }
+
+    async fn add_new_substitutes(
+        &self,
+        template: &str,
+        substitutes: &[NewSubstitute<'_>],
+        validation: CodeValidation,
+    ) -> Result<SubstituteReceipt, FunboyError> {
+        self.validate_template_name_to_add_to(template).await?;
+
+        let mut accepted = Vec::with_capacity(substitutes.len());
+        let mut ignored = Vec::new();
+        let mut warnings = Vec::new();
+        for new_substitute in substitutes {
+            let substitute = new_substitute.name;
+            if let Some(reason) = Self::substitute_ignore_reason(substitute) {
+                ignored.push(IgnoredEntry::new(substitute, reason));
+                continue;
+            }
+            match check_embedded_code(substitute) {
+                Ok(()) => accepted.push(*new_substitute),
+                Err(error) => {
+                    match validation {
+                        CodeValidation::Warn => accepted.push(*new_substitute),
+                        CodeValidation::Strict => ignored.push(IgnoredEntry::new(
+                            substitute,
+                            IgnoreReason::ValidationFailed {
+                                detail: error.to_string(),
+                            },
+                        )),
+                    }
+                    warnings.push(SubstituteWarning {
+                        substitute: substitute.to_string(),
+                        error,
+                    });
+                }
+            }
+        }
+
+        let accepted_names: Vec<&str> = accepted.iter().map(|sub| sub.name).collect();
+        let mut receipt = if accepted.is_empty() {
+            SubstituteReceipt::new()
+        } else if self.substitutes_exist(template, &accepted_names).await? {
+            let mut receipt = SubstituteReceipt::new();
+            receipt.ignored = accepted_names
+                .iter()
+                .map(|sub| IgnoredEntry::new(sub, IgnoreReason::Duplicate))
+                .collect();
+            receipt
+        } else {
+            let cap = self.get_substitute_cap(template).await?;
+            let receipt = self
+                .template_db
+                .create_substitutes_with_ids_up_to(template, &accepted, cap);
+            receipt.await?
+        };
+        ignored.append(&mut receipt.ignored);
+        receipt.ignored = ignored;
+        receipt.warnings = warnings;
         self.random_sub_cache.invalidate(template).await;
         Ok(receipt)
     }
 
+    /// Whether every substitute is already in template, so adding them would only be ignored
+    async fn substitutes_exist(
+        &self,
+        template: &str,
+        substitutes: &[&str],
+    ) -> Result<bool, FunboyError> {
+        let existing = self
+            .template_db
+            .read_existing_substitute_names(template, substitutes)
+            .await?;
+        Ok(substitutes
+            .iter()
+            .all(|substitute| existing.contains(*substitute)))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, substitutes), fields(count = substitutes.len()))]
     pub async fn delete_substitutes<'a>(
         &self,
         template: &str,
@@ -206,7 +876,12 @@ impl Funboy {
             .template_db
             .delete_substitutes_by_name(template, substitutes);
         let receipt = receipt.await?;
-        self.random_sub_cache.invalidate(template).await;
+        match receipt.updated.as_slice() {
+            [deleted] if substitutes.len() == 1 => {
+                self.remove_cached_substitute(template, deleted.id).await
+            }
+            _ => self.random_sub_cache.invalidate(template).await,
+        }
         Ok(receipt)
     }
 
@@ -219,18 +894,73 @@ impl Funboy {
         for sub in &receipt.updated {
             let template = self.template_db.read_template_by_id(sub.template_id);
             let template = template.await?.expect("sub must be inside template");
-            self.random_sub_cache.invalidate(&template.name).await;
+            if ids.len() == 1 {
+                self.remove_cached_substitute(&template.name, sub.id).await;
+            } else {
+                self.random_sub_cache.invalidate(&template.name).await;
+            }
         }
         Ok(receipt)
     }
 
+    /// How many substitutes template may hold, its own cap if set and the default otherwise
+    pub async fn get_substitute_cap(&self, template: &str) -> Result<i64, FunboyError> {
+        self.validate_template_name(template)?;
+
+        let cap = self.template_db.read_substitute_cap(template);
+        Ok(cap.await?.unwrap_or(self.max_substitutes))
+    }
+
+    /// Gives template its own substitute cap, None goes back to the default
+    ///
+    /// Substitutes already past a lowered cap are kept, only adding more is refused
+    pub async fn set_substitute_cap(
+        &self,
+        template: &str,
+        cap: Option<i64>,
+    ) -> Result<(), FunboyError> {
+        self.validate_template_name(template)?;
+
+        if self.template_db.set_substitute_cap(template, cap).await? {
+            Ok(())
+        } else {
+            Err(FunboyError::UserInput(
+                self.template_not_found(template).await?,
+            ))
+        }
+    }
+
+    /// Fails if adding count more substitutes would take template past its cap
+    async fn check_substitute_cap(&self, template: &str, adding: i64) -> Result<(), FunboyError> {
+        let cap = self.get_substitute_cap(template).await?;
+        let count = self.template_db.count_substitutes(template).await?;
+        if adding > 0 && count.saturating_add(adding) > cap {
+            return Err(FunboyError::UserInput(
+                UserFacingError::SubstituteCapExceeded {
+                    template: template.to_string(),
+                    count,
+                    adding,
+                    cap,
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copies every substitute missing from to_template, nothing is copied if they would not fit
     pub async fn copy_substitutes(
         &self,
         from_template: &str,
         to_template: &str,
     ) -> Result<Vec<Substitute>, FunboyError> {
         self.validate_template_name(from_template)?;
-        self.validate_template_name(to_template)?;
+        self.validate_template_name_to_add_to(to_template).await?;
+
+        let adding = self
+            .template_db
+            .count_substitutes_missing_from(from_template, to_template);
+        self.check_substitute_cap(to_template, adding.await?)
+            .await?;
 
         let subs = self
             .template_db
@@ -240,6 +970,60 @@ impl Funboy {
         Ok(subs)
     }
 
+    /// Duplicates source and all of its substitutes under new_name
+    pub async fn clone_template(
+        &self,
+        source: &str,
+        new_name: &str,
+    ) -> Result<CloneReport, FunboyError> {
+        self.validate_template_name(source)?;
+        self.validate_new_template_name(new_name)?;
+
+        if !self.template_db.template_exists(source).await? {
+            return Err(FunboyError::UserInput(
+                self.template_not_found(source).await?,
+            ));
+        }
+
+        if self.template_db.template_exists(new_name).await? {
+            return Err(FunboyError::UserInput(UserFacingError::TemplateExists {
+                name: new_name.to_string(),
+            }));
+        }
+        let adding = self.template_db.count_substitutes(source).await?;
+        self.check_substitute_cap(new_name, adding).await?;
+
+        let report = self.template_db.clone_template(source, new_name);
+        match report.await? {
+            Some(report) => {
+                self.random_sub_cache.invalidate(new_name).await;
+                Ok(report)
+            }
+            None => Err(FunboyError::UserInput(UserFacingError::TemplateExists {
+                name: new_name.to_string(),
+            })),
+        }
+    }
+
+    /// The not found error for template suggesting up to three templates with similar names
+    async fn template_not_found(&self, template: &str) -> Result<UserFacingError, FunboyError> {
+        let suggestions = self
+            .get_templates(
+                Some(template),
+                OrderBy::NameIgnoreCase(SortOrder::Ascending),
+                Limit::Count(3),
+            )
+            .await?
+            .into_iter()
+            .map(|template| template.name)
+            .collect();
+        Ok(UserFacingError::TemplateNotFound {
+            name: template.to_string(),
+            suggestions,
+        })
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, old, new))]
     pub async fn replace_substitute(
         &self,
         template: &str,
@@ -248,20 +1032,68 @@ impl Funboy {
     ) -> Result<Option<Substitute>, FunboyError> {
         self.validate_template_name(template)?;
 
+        if !self.template_db.template_exists(template).await? {
+            return Err(FunboyError::UserInput(
+                self.template_not_found(template).await?,
+            ));
+        }
+        if !self.template_db.substitute_exists(template, old).await? {
+            return Err(FunboyError::UserInput(
+                UserFacingError::SubstituteNotFound {
+                    template: template.to_string(),
+                    name: old.to_string(),
+                },
+            ));
+        }
+        if old != new && self.template_db.substitute_exists(template, new).await? {
+            return Err(FunboyError::UserInput(UserFacingError::SubstituteExists {
+                template: template.to_string(),
+                name: new.to_string(),
+            }));
+        }
+
         let sub = self
             .template_db
             .update_substitute_by_name(template, old, new);
         let sub = sub.await?;
-        self.random_sub_cache.invalidate(template).await;
+        if let Some(sub) = sub.as_ref() {
+            self.update_cached_substitute(template, sub).await;
+        }
         Ok(sub)
     }
 
-    pub async fn replace_substitute_by_id(
+    pub async fn get_substitute_by_id(
         &self,
         id: KeySize,
-        new: &str,
     ) -> Result<Option<Substitute>, FunboyError> {
-        let sub = self.template_db.update_substitute_by_id(id, new);
+        let sub = self.template_db.read_substitute_by_id(id);
+        Ok(sub.await?)
+    }
+
+    pub async fn replace_substitute_by_id(
+        &self,
+        id: KeySize,
+        new: &str,
+    ) -> Result<Option<Substitute>, FunboyError> {
+        Self::validate_substitute(new)?;
+
+        let sub = self.template_db.update_substitute_by_id(id, new);
+        let sub = sub.await?;
+        if let Some(sub) = sub.as_ref() {
+            let template = self.template_db.read_template_by_id(sub.template_id);
+            let template = template.await?.expect("sub must be inside template");
+            self.update_cached_substitute(&template.name, sub).await;
+        }
+        Ok(sub)
+    }
+
+    /// Disabled substitutes stay in their template but are never picked when generating
+    pub async fn set_substitute_enabled(
+        &self,
+        id: KeySize,
+        enabled: bool,
+    ) -> Result<Option<Substitute>, FunboyError> {
+        let sub = self.template_db.set_substitute_enabled(id, enabled);
         let sub = sub.await?;
         if let Some(sub) = sub.as_ref() {
             let template = self.template_db.read_template_by_id(sub.template_id);
@@ -271,6 +1103,24 @@ impl Funboy {
         Ok(sub)
     }
 
+    /// Enables or disables every substitute of template containing search_term, returning how
+    /// many changed
+    pub async fn set_substitutes_enabled_matching(
+        &self,
+        template: &str,
+        search_term: &str,
+        enabled: bool,
+    ) -> Result<u64, FunboyError> {
+        self.validate_template_name(template)?;
+
+        let changed =
+            self.template_db
+                .set_substitutes_enabled_matching(template, search_term, enabled);
+        let changed = changed.await?;
+        self.random_sub_cache.invalidate(template).await;
+        Ok(changed)
+    }
+
     pub async fn delete_template(&self, template: &str) -> Result<Option<Template>, FunboyError> {
         self.validate_template_name(template)?;
 
@@ -280,6 +1130,7 @@ impl Funboy {
         Ok(template)
     }
 
+    #[tracing::instrument(level = "debug", skip(self))]
     pub async fn delete_templates(
         &self,
         templates: &[&str],
@@ -294,557 +1145,6599 @@ impl Funboy {
         Ok(receipt)
     }
 
+    #[tracing::instrument(level = "debug", skip(self))]
     pub async fn rename_template(
         &self,
         from: &str,
         to: &str,
     ) -> Result<Option<Template>, FunboyError> {
-        self.validate_template_name(from)?;
-        self.validate_template_name(to)?;
+        self.check_rename(from, to).await?;
 
-        let template = self.template_db.update_template_by_name(from, to);
+        let template =
+            self.template_db
+                .update_template_by_name(from, to, &self.delimiters.renamed());
         let template = template.await?;
         self.random_sub_cache.invalidate_all();
         Ok(template)
     }
 
-    pub async fn get_templates(
-        &self,
-        search_term: Option<&str>,
-        order: OrderBy,
-        limit: Limit,
-    ) -> Result<Vec<Template>, FunboyError> {
-        let templates = self.template_db.read_templates(search_term, order, limit);
-        let templates = templates.await?;
-        Ok(templates)
-    }
+    /// Lists the substitutes [`Funboy::rename_template`] would rewrite without changing anything
+    pub async fn preview_rename(&self, from: &str, to: &str) -> Result<RenamePreview, FunboyError> {
+        self.check_rename(from, to).await?;
 
-    pub async fn get_substitutes(
-        &self,
-        template: &str,
-        search_term: Option<&str>,
-        order: OrderBy,
-        limit: Limit,
-    ) -> Result<Vec<Substitute>, FunboyError> {
-        self.validate_template_name(template)?;
-        let subs =
+        let changes =
             self.template_db
-                .read_substitutes_from_template(template, search_term, order, limit);
-        let subs = subs.await?;
-        Ok(subs)
+                .preview_template_rename(from, to, &self.delimiters.renamed());
+        let changes = changes.await?;
+        Ok(RenamePreview {
+            from: from.to_string(),
+            to: to.to_string(),
+            changes,
+        })
     }
 
-    async fn get_random_substitute(&self, template: &str) -> Result<Substitute, FunboyError> {
-        self.validate_template_name(template)?;
+    /// Rejects renaming a missing template or renaming onto an existing one
+    async fn check_rename(&self, from: &str, to: &str) -> Result<(), FunboyError> {
+        self.validate_template_name(from)?;
+        self.validate_new_template_name(to)?;
 
-        match self.random_sub_cache.get(template).await {
-            Some(subs) => {
-                let sub = subs
-                    .get(random_range(0..subs.len()))
-                    .expect("subs should be present in cache if match was found");
-                Ok(sub.clone())
-            }
-            None => {
-                let subs = self.get_substitutes(template, None, OrderBy::Random, Limit::Count(200));
-                let subs = subs.await?;
-
-                if !subs.is_empty() {
-                    let rnd_range = random_range(0..subs.len());
-                    let sub = subs
-                        .get(rnd_range)
-                        .cloned()
-                        .expect("subs cannot be empty due to explicit check");
-                    self.random_sub_cache
-                        .insert(template.to_string(), subs)
-                        .await;
-                    Ok(sub)
-                } else {
-                    Err(FunboyError::Database(format!(
-                        "No substitutes were present in template \"{}\"",
-                        template
-                    )))
-                }
-            }
+        if !self.template_db.template_exists(from).await? {
+            return Err(FunboyError::UserInput(self.template_not_found(from).await?));
+        }
+        if from != to && self.template_db.template_exists(to).await? {
+            return Err(FunboyError::UserInput(UserFacingError::TemplateExists {
+                name: to.to_string(),
+            }));
         }
+        Ok(())
     }
 
-    /// Resolves templates and interprets embeded code in input with a single pass
-    async fn interpret_input(
+    /// Rewrites references to from in substitutes within scope the way [`Funboy::rename_template`]
+    /// would without renaming any template
+    ///
+    /// References edited by hand can be fixed up afterwards or moved ahead of a later rename
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub async fn rewrite_references(
         &self,
-        input: String,
-        interpreter: Arc<Mutex<FslInterpreter>>,
-    ) -> Result<String, FunboyError> {
-        let mut substituted_text = self
-            .substitute_register_templates(input, interpreter.clone())
-            .await?;
+        from: &str,
+        to: &str,
+        scope: RewriteScope,
+    ) -> Result<RewriteReport, FunboyError> {
+        self.check_rewrite(from, to, &scope).await?;
 
-        substituted_text = TemplateSubstitutor::new(TemplateDelimiter::Caret)
-            .await
-            .substitute_recursively(substituted_text, |template: String| async move {
-                match self.get_random_substitute(&template).await {
-                    Ok(sub) => Some(sub.name.to_string()),
-                    Err(_) => None,
-                }
-            })
-            .await;
+        let changes = self.template_db.rewrite_template_references(
+            from,
+            to,
+            &self.delimiters.renamed(),
+            &scope,
+        );
+        let changes = changes.await?;
+        let report = self.rewrite_report(from, to, scope, changes).await;
+        for template in report.templates() {
+            self.random_sub_cache.invalidate(template).await;
+        }
+        Ok(report)
+    }
 
-        let mut interpreter = interpreter.lock().await;
-        let interpreter_result = interpreter.interpret_embedded_code(&substituted_text).await;
+    /// Lists the substitutes [`Funboy::rewrite_references`] would rewrite without changing anything
+    pub async fn preview_rewrite_references(
+        &self,
+        from: &str,
+        to: &str,
+        scope: RewriteScope,
+    ) -> Result<RewriteReport, FunboyError> {
+        self.check_rewrite(from, to, &scope).await?;
 
-        match interpreter_result {
-            Ok(interpreted_text) => Ok(interpreted_text),
-            Err(e) => Err(FunboyError::Interpreter(e.to_string())),
-        }
+        let changes = self.template_db.preview_reference_rewrite(
+            from,
+            to,
+            &self.delimiters.renamed(),
+            &scope,
+        );
+        let changes = changes.await?;
+        Ok(self.rewrite_report(from, to, scope, changes).await)
     }
 
-    #[async_recursion]
-    async fn substitute_register_templates(
+    /// Rejects invalid names and scopes naming a missing template
+    async fn check_rewrite(
         &self,
-        input: String,
-        interpreter: Arc<Mutex<FslInterpreter>>,
-    ) -> Result<String, FunboyError> {
-        let sub_map: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
-        let funboy_error: Arc<Mutex<Option<FunboyError>>> = Arc::new(Mutex::new(None));
-        let output = TemplateSubstitutor::new(TemplateDelimiter::PlusRegister)
-            .await
-            .substitute_recursively(input, |template: String| {
-                let sub_map = sub_map.clone();
-                let interpreter = interpreter.clone();
-                let funboy_error = funboy_error.clone();
+        from: &str,
+        to: &str,
+        scope: &RewriteScope,
+    ) -> Result<(), FunboyError> {
+        self.validate_template_name(from)?;
+        self.validate_new_template_name(to)?;
 
-                async move {
-                    let mut sub_map = sub_map.lock().await;
-                    let result = sub_map.get(&template);
-                    if let Some(value) = result {
-                        Some(value.clone())
-                    } else {
-                        let split = template.split('-').collect::<Vec<&str>>();
-                        let template_before_dash = split.get(0).unwrap_or(&"");
-                        match self.get_random_substitute(&template_before_dash).await {
-                            Ok(sub) => {
-                                let sub = match self.generate(&sub.name, interpreter).await {
-                                    Ok(interpreted_sub) => interpreted_sub,
-                                    Err(e) => {
-                                        let _ = funboy_error.lock().await.insert(e);
-                                        return None;
-                                    }
-                                };
-                                sub_map.insert(template.to_string(), sub.clone());
-                                return Some(sub);
-                            }
-                            Err(_) => None,
-                        }
-                    }
-                }
-            })
-            .await;
-        let err = funboy_error.lock().await.take();
-        match err {
-            Some(e) => return Err(e),
-            None => return Ok(output),
+        if let RewriteScope::Template(template) = scope {
+            self.validate_template_name(template)?;
+            if !self.template_db.template_exists(template).await? {
+                return Err(FunboyError::UserInput(
+                    self.template_not_found(template).await?,
+                ));
+            }
         }
+        Ok(())
     }
 
-    /* PROFILE CODE
-        let before = SystemTime::now();
+    /// Merges the changes of each substitute into its text before and after every delimiter
+    async fn rewrite_report(
+        &self,
+        from: &str,
+        to: &str,
+        scope: RewriteScope,
+        changes: Vec<ReferenceChange>,
+    ) -> RewriteReport {
+        let mut substitutes: Vec<RewrittenSubstitute> = Vec::new();
+        for change in changes {
+            match substitutes
+                .iter_mut()
+                .find(|sub| sub.substitute_id == change.substitute_id)
+            {
+                Some(sub) => sub.after = change.after,
+                None => substitutes.push(RewrittenSubstitute {
+                    substitute_id: change.substitute_id,
+                    template: change.template,
+                    before: change.before,
+                    after: change.after,
+                    old_references: 0,
+                    new_references: 0,
+                }),
+            }
+        }
 
-        let after = SystemTime::now();
+        let substitutor = TemplateSubstitutor::with_delimiters(&self.delimiters.renamed()).await;
+        for sub in &mut substitutes {
+            sub.old_references = substitutor.count_references(&sub.before, from);
+            sub.new_references = substitutor.count_references(&sub.after, to);
+        }
 
-        let time = after.duration_since(before).unwrap();
-        unsafe {
-            static mut INTERP_TIME: Duration = Duration::new(0, 0);
-            INTERP_TIME += time;
-            dbg!(INTERP_TIME);
+        RewriteReport {
+            from: from.to_string(),
+            to: to.to_string(),
+            scope,
+            substitutes,
         }
-    */
-    /// Resolves templates and fsl code until output is complete or depth limit is reached
-    pub async fn generate(
-        &self,
-        input: &str,
-        interpreter: Arc<Mutex<FslInterpreter>>,
-    ) -> Result<String, FunboyError> {
-        let mut output = input.to_string();
-        let mut prev_hashes = HashSet::new();
+    }
 
-        let mut modified_interpreter = interpreter.lock().await;
-        let funboy = Arc::new(self.clone());
-        modified_interpreter.add_command(
-            GET_SUB,
-            GET_SUB_RULES,
-            create_get_sub_command(funboy.clone()),
-        );
-        modified_interpreter.add_command(ASK_AI, ASK_AI_RULES, create_ask_ai_command(funboy));
-        drop(modified_interpreter);
+    pub const MAX_EXAMPLES: usize = 3;
+    pub const MAX_EXAMPLE_LENGTH: usize = 500;
 
-        const MAX_GENERATIONS: u8 = 255;
-        for _ in 0..MAX_GENERATIONS {
-            let mut hasher = DefaultHasher::new();
-            output.hash(&mut hasher);
-            let hash = hasher.finish();
+    /// Replaces the pinned examples of an existing template, an empty list removes them
+    pub async fn set_template_examples(
+        &self,
+        template: &str,
+        examples: &[&str],
+    ) -> Result<Vec<Example>, FunboyError> {
+        self.validate_template_name(template)?;
 
-            if !prev_hashes.insert(hash) {
-                break;
-            } else {
-                output = self.interpret_input(output, interpreter.clone()).await?;
+        if examples.len() > Funboy::MAX_EXAMPLES {
+            return Err(FunboyError::UserInput(UserFacingError::TooManyExamples {
+                limit: Funboy::MAX_EXAMPLES,
+            }));
+        }
+        for example in examples {
+            let length = example.chars().count();
+            if length > Funboy::MAX_EXAMPLE_LENGTH {
+                return Err(FunboyError::UserInput(UserFacingError::ExampleTooLong {
+                    length,
+                    limit: Funboy::MAX_EXAMPLE_LENGTH,
+                }));
             }
         }
 
-        Ok(output)
+        let examples = self.template_db.replace_examples(template, examples);
+        match examples.await? {
+            Some(examples) => Ok(examples),
+            None => Err(FunboyError::UserInput(UserFacingError::TemplateNotFound {
+                name: template.to_string(),
+                suggestions: Vec::new(),
+            })),
+        }
     }
 
-    pub async fn get_ollama_models(&self) -> Result<Vec<String>, FunboyError> {
-        let models = self.ollama_generator.get_models().await;
-        match models {
-            Ok(models) => Ok(models.iter().map(|m| m.name.to_string()).collect()),
-            Err(e) => Err(FunboyError::Ollama(e.to_string())),
-        }
+    pub async fn get_template_examples(&self, template: &str) -> Result<Vec<Example>, FunboyError> {
+        self.validate_template_name(template)?;
+
+        let examples = self.template_db.read_examples(template);
+        let examples = examples.await?;
+        Ok(examples)
     }
 
-    pub async fn get_ollama_model_info(&self, model: String) -> Result<ModelInfo, FunboyError> {
-        match self.ollama_generator.get_model_info(model).await {
-            Ok(info) => Ok(info),
-            Err(e) => Err(FunboyError::Ollama(e.to_string())),
+    pub const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+    /// Sets the description of an existing template, None or a blank description removes it
+    pub async fn set_template_description(
+        &self,
+        template: &str,
+        description: Option<&str>,
+    ) -> Result<(), FunboyError> {
+        self.validate_template_name(template)?;
+
+        let description = description
+            .map(str::trim)
+            .filter(|description| !description.is_empty());
+        if let Some(description) = description {
+            let length = description.chars().count();
+            if length > Funboy::MAX_DESCRIPTION_LENGTH {
+                return Err(FunboyError::UserInput(
+                    UserFacingError::DescriptionTooLong {
+                        length,
+                        limit: Funboy::MAX_DESCRIPTION_LENGTH,
+                    },
+                ));
+            }
+        }
+
+        let set = self
+            .template_db
+            .set_template_description(template, description);
+        if set.await? {
+            Ok(())
+        } else {
+            Err(FunboyError::UserInput(
+                self.template_not_found(template).await?,
+            ))
         }
     }
 
-    pub async fn generate_ollama(
+    /// The description of template, None when it has none
+    pub async fn get_template_description(
         &self,
-        model: Option<String>,
-        ollama_settings: &OllamaSettings,
-        prompt: &str,
+        template: &str,
+    ) -> Result<Option<String>, FunboyError> {
+        self.validate_template_name(template)?;
+
+        let description = self.template_db.read_template_description(template);
+        Ok(description.await?)
+    }
+
+    /// Gathers the description, flags, substitute counts, settings and examples of a template
+    pub async fn get_template_info(&self, template: &str) -> Result<TemplateInfo, FunboyError> {
+        self.validate_template_name(template)?;
+
+        let (Some(found), Some(details)) = (
+            self.template_db.read_template_by_name(template).await?,
+            self.template_db.read_template_details(template).await?,
+        ) else {
+            return Err(FunboyError::UserInput(
+                self.template_not_found(template).await?,
+            ));
+        };
+
+        let cap = self.template_db.read_substitute_cap(template).await?;
+        let examples = self
+            .template_db
+            .read_examples(template)
+            .await?
+            .into_iter()
+            .map(|example| example.text)
+            .collect();
+
+        Ok(TemplateInfo {
+            template: found,
+            description: details.description,
+            created_at: details.created_at,
+            archived: details.archived,
+            substitutes: self.template_db.count_substitutes(template).await?,
+            disabled_substitutes: self
+                .template_db
+                .count_disabled_substitutes(template)
+                .await?,
+            substitute_cap: cap.unwrap_or(self.max_substitutes),
+            custom_cap: cap.is_some(),
+            examples,
+        })
+    }
+
+    /// Collects the pinned examples of a template and generates it once more
+    pub async fn preview_template(
+        &self,
+        template: &str,
         interpreter: Arc<Mutex<FslInterpreter>>,
-    ) -> Result<GenerationResponse, FunboyError> {
-        let prompt = self.generate(prompt, interpreter).await?;
-        match self
-            .ollama_generator
-            .generate(&prompt, ollama_settings, model)
-            .await
+    ) -> Result<TemplatePreview, FunboyError> {
+        self.validate_template_name(template)?;
+
+        if self
+            .template_db
+            .read_template_by_name(template)
+            .await?
+            .is_none()
         {
-            Ok(output) => Ok(output),
-            Err(e) => Err(FunboyError::Ollama(e.to_string())),
+            return Err(FunboyError::UserInput(UserFacingError::TemplateNotFound {
+                name: template.to_string(),
+                suggestions: Vec::new(),
+            }));
         }
+
+        let examples = self
+            .get_template_examples(template)
+            .await?
+            .into_iter()
+            .map(|example| example.text)
+            .collect();
+
+        let input = format!("{}{}", TemplateDelimiter::CARET.to_char(), template);
+        let generated = self.generate(&input, interpreter).await?.text;
+
+        Ok(TemplatePreview {
+            template: template.to_string(),
+            examples,
+            generated,
+        })
     }
-}
 
-const GET_SUB: &str = "get_sub";
-const GET_SUB_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), TEXT_TYPES)];
-fn create_get_sub_command(funboy: Arc<Funboy>) -> Executor {
-    let get_sub_command = {
-        move |command: Command, data: Arc<InterpreterData>| {
-            let funboy = funboy.clone();
-            async move {
-                let mut args = command.take_args();
-                let template = args.pop_front().unwrap().as_text(data).await?;
-                let regex = TemplateDelimiter::BackTick.to_regex().await;
-                if regex.is_match(&template) {
-                    let template = template.trim_matches('`');
-                    let sub = funboy.get_random_substitute(template).await;
-                    match sub {
-                        Ok(sub) => Ok(Value::Text(sub.name)),
-                        Err(e) => Err(CommandError::Custom(e.to_string())),
-                    }
-                } else {
-                    return Err(CommandError::Custom(format!(
-                        "template name must be preceeded by a single ` (backtick)\nThis ensures if the template is renamed this {} will not be invalid",
-                        GET_SUB
-                    )));
-                }
+    /// Generates content as if it had been picked from template and reports anything that
+    /// would go wrong adding it
+    ///
+    /// Nothing is written, not even the uses of the templates content refers to
+    pub async fn preview_substitute(
+        &self,
+        template: &str,
+        content: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+    ) -> Result<PreviewResult, FunboyError> {
+        self.validate_template_name_to_add_to(template).await?;
+
+        let mut warnings = Vec::new();
+        if let Some(reason) = Self::substitute_ignore_reason(content) {
+            warnings.push(PreviewWarning::Ignored(reason));
+        } else if self.template_db.template_exists(template).await? {
+            if self
+                .template_db
+                .substitute_exists(template, content)
+                .await?
+            {
+                warnings.push(PreviewWarning::Ignored(IgnoreReason::Duplicate));
+            } else if self.template_db.count_substitutes(template).await?
+                >= self.get_substitute_cap(template).await?
+            {
+                warnings.push(PreviewWarning::Ignored(IgnoreReason::CapExceeded));
+            }
+        }
+        if let Err(error) = check_embedded_code(content) {
+            warnings.push(PreviewWarning::InvalidCode(error));
+        }
+        for reference in self.find_template_references(content).await {
+            if !self.template_db.template_exists(&reference).await?
+                && self.resolve_fallback(&reference).await.is_none()
+            {
+                warnings.push(PreviewWarning::UnknownReference(reference));
             }
         }
-    };
-    Some(Arc::new(get_sub_command))
-}
 
-const ASK_AI: &str = "ask_ai";
-const ASK_AI_RULES: &[ArgRule] = &[
-    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
-    ArgRule::new(ArgPos::Index(1), WHOLE_NUMBER_TYPES),
-];
-const MAX_WORD_LIMIT: i64 = 500;
-fn create_ask_ai_command(funboy: Arc<Funboy>) -> Executor {
-    let get_sub_command = {
-        move |command: Command, data: Arc<InterpreterData>| {
-            let funboy = funboy.clone();
-            async move {
-                let mut args = command.take_args();
-                let prompt = args.pop_front().unwrap().as_text(data.clone()).await?;
+        // Uses are only counted for real generations so the preview records into its own, and
+        // moves through round robin and least recently used cycles of its own
+        let preview = Self {
+            usage: Arc::new(UsageAccumulator::default()),
+            selection: Arc::new(SelectionStates::default()),
+            ..self.clone()
+        };
+        let expansions = preview.new_expansion_counter();
+        if let Err(e) = expansions.lock().await.record(template) {
+            return Err(FunboyError::UserInput(e.into()));
+        }
+        let generated = match preview
+            .generate_with_log(
+                content,
+                interpreter,
+                None,
+                expansions,
+                GenerateOptions::default(),
+            )
+            .await
+        {
+            Ok(generated) => Some(generated.text),
+            Err(e) => {
+                warnings.push(PreviewWarning::GenerationFailed(e.to_string()));
+                None
+            }
+        };
 
-                let word_limit = args.pop_front().unwrap().as_int(data).await?;
-                if word_limit <= 0 {
-                    return Err(CommandError::Custom(
-                        "word limit must be greater than zero".to_string(),
-                    ));
-                } else if word_limit > MAX_WORD_LIMIT {
-                    return Err(CommandError::Custom(format!(
-                        "word limit cannot be greater than {}",
-                        MAX_WORD_LIMIT
-                    )));
+        Ok(PreviewResult {
+            template: template.to_string(),
+            substitute: content.to_string(),
+            generated,
+            warnings,
+        })
+    }
+
+    /// Names of the templates input refers to in the order they first appear, registers such
+    /// as `+noun-1+` refer to the template before the dash
+    async fn find_template_references(&self, input: &str) -> Vec<String> {
+        let mut delimiters = self.delimiters.substituted();
+        delimiters.push(TemplateDelimiter::PLUS_REGISTER);
+
+        let references = std::sync::Mutex::new(Vec::new());
+        TemplateSubstitutor::with_delimiters(&delimiters)
+            .await
+            .substitute(input, &|template: String| {
+                let name = template.split('-').next().unwrap_or_default().to_string();
+                let mut references = references.lock().unwrap();
+                if !name.is_empty() && !references.contains(&name) {
+                    references.push(name);
                 }
+                ready(None)
+            })
+            .await;
+        references.into_inner().unwrap()
+    }
 
-                let mut ollama_settings = OllamaSettings::default();
-                ollama_settings.set_output_limit(word_limit as u16);
-                let ollama_generator = OllamaGenerator::default();
-                let model = funboy.get_ollama_model().await;
+    /// Lists existing templates whose names collide with reserved command names
+    ///
+    /// Conflicting templates are only reported and never renamed automatically
+    pub async fn check_reserved_conflicts(&self) -> Result<Vec<Template>, FunboyError> {
+        let templates =
+            self.template_db
+                .read_templates(None, OrderBy::Name(SortOrder::Ascending), Limit::None);
+        let templates = templates.await?;
+        Ok(templates
+            .into_iter()
+            .filter(|template| self.is_reserved_name(&template.name))
+            .collect())
+    }
 
-                let response = ollama_generator
-                    .generate(&prompt, &ollama_settings, model)
-                    .await;
-                match response {
-                    Ok(response) => {
-                        let response = response.response;
-                        Ok(Value::Text(response))
-                    }
-                    Err(e) => Err(CommandError::Custom(e.to_string())),
+    pub async fn get_templates(
+        &self,
+        search_term: Option<&str>,
+        order: OrderBy,
+        limit: Limit,
+    ) -> Result<Vec<Template>, FunboyError> {
+        let templates = self.template_db.read_templates(search_term, order, limit);
+        let templates = templates.await?;
+        Ok(templates)
+    }
+
+    /// Substitutes of template containing search_term, only the ones of kind if it is given
+    pub async fn get_substitutes(
+        &self,
+        template: &str,
+        search_term: Option<&str>,
+        kind: Option<SubKind>,
+        order: OrderBy,
+        limit: Limit,
+    ) -> Result<Vec<Substitute>, FunboyError> {
+        self.validate_template_name(template)?;
+        let subs = self.template_db.read_substitutes_from_template(
+            template,
+            search_term,
+            kind,
+            order,
+            limit,
+        );
+        let subs = subs.await?;
+        Ok(subs)
+    }
+
+    /// Substitutes of any template that could have produced text, best matches first
+    ///
+    /// Substitutes are matched against the whole text, against runs of its words so the parts
+    /// of a generation drawing on several templates are found, and by trigram similarity
+    pub async fn find_source_of(
+        &self,
+        text: &str,
+        limit: usize,
+    ) -> Result<Vec<(Template, Substitute, MatchQuality)>, FunboyError> {
+        const CANDIDATES_PER_PASS: i64 = 100;
+
+        let normalized = normalize(text);
+        if normalized.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut matches: HashMap<KeySize, (Substitute, MatchQuality)> = HashMap::new();
+        let mut add = |sub: Substitute, quality: MatchQuality| match matches.get(&sub.id) {
+            Some((_, best)) if best.score() >= quality.score() => {}
+            _ => {
+                matches.insert(sub.id, (sub, quality));
+            }
+        };
+
+        let mut names = vec![normalized.clone()];
+        names.extend(fragments(&normalized));
+        let named = self
+            .template_db
+            .read_substitutes_named_any(&names, CANDIDATES_PER_PASS);
+        for sub in named.await? {
+            let name = normalize(&sub.name);
+            let quality = if name == normalized {
+                MatchQuality::Exact
+            } else {
+                MatchQuality::Fragment {
+                    words: name.split(' ').count(),
                 }
+            };
+            add(sub, quality);
+        }
+
+        let containing = self
+            .template_db
+            .read_substitutes_containing(&normalized, CANDIDATES_PER_PASS);
+        for sub in containing.await? {
+            let name = normalize(&sub.name);
+            if name == normalized {
+                add(sub, MatchQuality::Exact);
+            } else if name.contains(&normalized) {
+                add(sub, MatchQuality::Contains);
             }
         }
-    };
-    Some(Arc::new(get_sub_command))
-}
 
-#[cfg(test)]
-mod core {
-    use super::*;
-    use sqlx::PgPool;
-    use std::panic;
-    use template_database::test::create_debug_db;
+        let similar = self
+            .template_db
+            .read_similar_substitutes(&normalized, CANDIDATES_PER_PASS);
+        for sub in similar.await? {
+            let similarity = trigram_similarity(&normalize(&sub.name), &normalized);
+            if similarity >= FUZZY_THRESHOLD {
+                add(sub, MatchQuality::Fuzzy { similarity });
+            }
+        }
 
-    #[tokio::test]
-    async fn random_number_produces_int_in_range() {
-        for _ in 0..100 {
-            let result = Funboy::random_number("1", "6", true)
-                .unwrap()
-                .parse::<i64>()
-                .unwrap();
-            assert!((1..=6).contains(&result), "output outside of range");
+        let mut matches: Vec<(Substitute, MatchQuality)> = matches.into_values().collect();
+        matches.sort_by(|(a, a_quality), (b, b_quality)| {
+            b_quality
+                .score()
+                .total_cmp(&a_quality.score())
+                .then(a.id.cmp(&b.id))
+        });
+        matches.truncate(limit);
+
+        let mut templates: HashMap<KeySize, Template> = HashMap::new();
+        let mut sources = Vec::with_capacity(matches.len());
+        for (sub, quality) in matches {
+            let template = match templates.get(&sub.template_id) {
+                Some(template) => template.clone(),
+                None => {
+                    let template = self.template_db.read_template_by_id(sub.template_id);
+                    let template = template.await?.expect("sub must be inside template");
+                    templates.insert(template.id, template.clone());
+                    template
+                }
+            };
+            sources.push((template, sub, quality));
         }
+        Ok(sources)
     }
 
-    #[tokio::test]
-    async fn random_number_produces_float() {
-        for _ in 0..100 {
-            let result = Funboy::random_number("1.0", "6.0", true)
-                .unwrap()
-                .parse::<f64>()
-                .unwrap();
-            assert!((1.0..=6.0).contains(&result), "output outside of range");
+    /// Creates a template without any substitutes, returns None if it already exists
+    pub async fn create_template(&self, template: &str) -> Result<Option<Template>, FunboyError> {
+        self.validate_new_template_name(template)?;
+
+        let created = self.template_db.create_template(template).await?;
+        if created.is_some() {
+            // Moves the public listing on to a new version
+            self.random_sub_cache.invalidate(template).await;
         }
+        Ok(created)
     }
 
-    #[tokio::test]
-    async fn random_number_fails_when_min_greater_than_max() {
-        match Funboy::random_number("6", "1", true) {
-            Ok(_) => {
-                panic!("Value should not be Ok");
+    pub async fn select_templates(
+        &self,
+        filter: &TemplateFilter,
+    ) -> Result<Vec<Template>, FunboyError> {
+        let templates = self.template_db.read_templates_matching(filter);
+        Ok(templates.await?)
+    }
+
+    /// Deletes every template matching filter in two steps
+    ///
+    /// Without a token nothing is deleted and a preview with the token to confirm it is returned
+    pub async fn delete_templates_matching(
+        &self,
+        filter: &TemplateFilter,
+        token: Option<&str>,
+    ) -> Result<BulkOutcome, FunboyError> {
+        self.run_bulk_operation(BulkOperation::Delete, filter, token)
+            .await
+    }
+
+    /// Archives every template matching filter in two steps like [`Funboy::delete_templates_matching`]
+    pub async fn archive_templates_matching(
+        &self,
+        filter: &TemplateFilter,
+        token: Option<&str>,
+    ) -> Result<BulkOutcome, FunboyError> {
+        self.run_bulk_operation(BulkOperation::Archive, filter, token)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, token))]
+    async fn run_bulk_operation(
+        &self,
+        operation: BulkOperation,
+        filter: &TemplateFilter,
+        token: Option<&str>,
+    ) -> Result<BulkOutcome, FunboyError> {
+        if filter.is_empty() {
+            return Err(FunboyError::UserInput(UserFacingError::BulkFilterEmpty));
+        }
+
+        let templates = self.select_templates(filter).await?;
+        let expected = bulk_token(operation, filter, &templates);
+
+        let Some(token) = token else {
+            return Ok(BulkOutcome::Preview(BulkPreview {
+                templates,
+                token: expected,
+            }));
+        };
+        if token != expected {
+            return Err(FunboyError::UserInput(UserFacingError::BulkTokenMismatch));
+        }
+
+        let ids: Vec<KeySize> = templates.iter().map(|template| template.id).collect();
+        let applied = match operation {
+            BulkOperation::Delete => {
+                let deleted = self.template_db.delete_templates_by_id(&ids).await?;
+                self.random_sub_cache.invalidate_all();
+                deleted
             }
-            Err(e) => {
-                assert!(
-                    matches!(e, FunboyError::UserInput(_)),
-                    "error was not UserInput variant"
-                );
+            BulkOperation::Archive => {
+                let archived = self.template_db.archive_templates_by_id(&ids).await?;
+                for template in &archived {
+                    self.random_sub_cache.invalidate(&template.name).await;
+                }
+                archived
             }
+        };
+        Ok(BulkOutcome::Applied(applied))
+    }
+
+    pub const PUBLIC_PAGE_SIZE: usize = 100;
+
+    /// Templates that can be browsed publicly, see [`public_browse::render_template_list`]
+    pub async fn public_templates(&self) -> Result<Vec<PublicTemplate>, FunboyError> {
+        let templates = self.template_db.read_public_templates();
+        Ok(templates.await?)
+    }
+
+    /// A page of the enabled substitutes of template starting at 1
+    ///
+    /// None when template is archived, doesn't exist or has no such page
+    pub async fn public_template_page(
+        &self,
+        template: &str,
+        page: usize,
+    ) -> Result<Option<PublicPage>, FunboyError> {
+        if self.validate_template_name(template).is_err() || page == 0 {
+            return Ok(None);
+        }
+        match self.template_db.read_template_details(template).await? {
+            Some(details) if !details.archived => {}
+            _ => return Ok(None),
+        }
+
+        let count = self.template_db.count_enabled_substitutes(template).await? as usize;
+        let page_count = PublicPage::page_count(count, Self::PUBLIC_PAGE_SIZE);
+        if page > page_count {
+            return Ok(None);
         }
+
+        let substitutes = self
+            .template_db
+            .read_public_substitute_page(
+                template,
+                Self::PUBLIC_PAGE_SIZE as i64,
+                ((page - 1) * Self::PUBLIC_PAGE_SIZE) as i64,
+            )
+            .await?;
+        Ok(Some(PublicPage {
+            template: template.to_string(),
+            substitutes,
+            page,
+            page_count,
+            page_size: Self::PUBLIC_PAGE_SIZE,
+        }))
     }
 
-    #[tokio::test]
-    async fn random_number_fails_when_min_equal_to_max() {
-        match Funboy::random_number("6", "6", true) {
-            Ok(_) => {
-                panic!("Value should not be Ok");
+    /// ETag of the public listing, it changes whenever any template does
+    pub async fn public_listing_etag(&self) -> Result<String, FunboyError> {
+        let (version, count) = self.template_db.read_public_listing_version().await?;
+        Ok(etag(count as u64, version as u64, 0))
+    }
+
+    /// ETag of a page of template, it changes whenever template does
+    pub async fn public_template_etag(
+        &self,
+        template: &str,
+        page: usize,
+    ) -> Result<String, FunboyError> {
+        let version = self.template_db.read_template_version(template).await?;
+        Ok(etag(0, version.unwrap_or(0) as u64, page))
+    }
+
+    /// Collects every template and its substitutes ordered by template name
+    pub async fn export_templates(&self) -> Result<TemplateExport, FunboyError> {
+        let templates =
+            self.template_db
+                .read_templates(None, OrderBy::Name(SortOrder::Ascending), Limit::None);
+        let templates = templates.await?;
+
+        let mut exported = Vec::with_capacity(templates.len());
+        for template in templates {
+            let substitutes = self.template_db.read_substitutes_from_template(
+                &template.name,
+                None,
+                None,
+                OrderBy::Default,
+                Limit::None,
+            );
+            let substitutes = substitutes
+                .await?
+                .into_iter()
+                .map(|sub| ExportedSubstitute {
+                    id: Some(sub.id),
+                    name: sub.name,
+                    enabled: sub.enabled,
+                });
+            exported.push(ExportedTemplate {
+                name: template.name,
+                substitutes: substitutes.collect(),
+            });
+        }
+
+        Ok(TemplateExport::new(exported))
+    }
+
+    /// Adds the templates and substitutes of an export, substitutes already present are kept
+    ///
+    /// Preserving ids is refused for templates that already have substitutes unless forced so
+    /// exported references aren't mixed with existing ones by accident
+    pub async fn import_templates(
+        &self,
+        export: &TemplateExport,
+        options: ImportOptions,
+    ) -> Result<ImportReport, FunboyError> {
+        if export.version != EXPORT_VERSION {
+            return Err(FunboyError::UserInput(
+                UserFacingError::ExportVersionUnsupported {
+                    version: export.version,
+                    supported: EXPORT_VERSION,
+                },
+            ));
+        }
+
+        if options.preserve_ids && !options.force {
+            for template in &export.templates {
+                if !template.substitutes.is_empty()
+                    && self.template_db.count_substitutes(&template.name).await? > 0
+                {
+                    return Err(FunboyError::UserInput(
+                        UserFacingError::PreserveIdsTemplateNotEmpty {
+                            template: template.name.clone(),
+                        },
+                    ));
+                }
             }
-            Err(e) => {
-                assert!(
-                    matches!(e, FunboyError::UserInput(_)),
-                    "error was not UserInput variant"
-                );
+        }
+
+        let mut report = ImportReport::default();
+        for template in &export.templates {
+            if template.substitutes.is_empty() {
+                self.create_template(&template.name).await?;
+            } else {
+                let substitutes: Vec<NewSubstitute> = template
+                    .substitutes
+                    .iter()
+                    .map(|sub| match sub.id {
+                        Some(id) if options.preserve_ids => NewSubstitute::with_id(id, &sub.name),
+                        _ => NewSubstitute::new(&sub.name),
+                    })
+                    .collect();
+                let receipt = self
+                    .add_new_substitutes(&template.name, &substitutes, CodeValidation::Warn)
+                    .await?;
+                let disabled: HashSet<&str> = template
+                    .substitutes
+                    .iter()
+                    .filter(|sub| !sub.enabled)
+                    .map(|sub| sub.name.as_str())
+                    .collect();
+                for sub in &receipt.updated {
+                    if disabled.contains(sub.name.as_str()) {
+                        self.template_db
+                            .set_substitute_enabled(sub.id, false)
+                            .await?;
+                    }
+                }
+                if !disabled.is_empty() {
+                    self.random_sub_cache.invalidate(&template.name).await;
+                }
+                report.reassigned.extend(receipt.reassigned);
+                report.added += receipt.updated.len();
+                report.ignored += receipt
+                    .ignored
+                    .iter()
+                    .filter(|entry| entry.reason != IgnoreReason::CapExceeded)
+                    .count();
+                report.warnings.extend(receipt.warnings);
+                if let Some(refused) = receipt.refused {
+                    report.refused += refused.substitutes.len();
+                }
             }
+            report.templates += 1;
         }
+        Ok(report)
     }
 
-    #[tokio::test]
-    async fn random_entry_returns_correct_output() {
-        let result = Funboy::random_entry(&["one", "two", "three", "four"]).unwrap();
+    /// Finds templates with reserved names and substitutes whose embedded code fails to parse
+    pub async fn lint(&self) -> Result<Vec<LintIssue>, FunboyError> {
+        let export = self.export_templates().await?;
 
-        if !(&["one", "two", "three", "four"].contains(&result)) {
-            panic!("array should contain result");
+        let mut issues = Vec::new();
+        for template in export.templates {
+            if self.is_reserved_name(&template.name) {
+                issues.push(LintIssue::ReservedName {
+                    template: template.name.clone(),
+                });
+            }
+            for substitute in template.substitutes {
+                if let Err(error) = check_embedded_code(&substitute.name) {
+                    issues.push(LintIssue::InvalidCode {
+                        template: template.name.clone(),
+                        warning: SubstituteWarning {
+                            substitute: substitute.name,
+                            error,
+                        },
+                    });
+                }
+            }
         }
+        Ok(issues)
     }
 
-    #[tokio::test]
-    async fn random_entry_fails_with_less_than_two_entries() {
-        match Funboy::random_entry(&["one"]) {
-            Ok(_) => {
-                panic!("Value should not be Ok");
+    /// Swaps the cached copy of sub for its new value so a replace doesn't refetch template
+    async fn update_cached_substitute(&self, template: &str, sub: &Substitute) {
+        self.random_sub_cache
+            .patch(template, |subs| {
+                if let Some(cached) = subs.iter_mut().find(|cached| cached.id == sub.id) {
+                    *cached = sub.clone();
+                }
+            })
+            .await;
+    }
+
+    /// Drops a deleted substitute from the cached substitutes of template
+    ///
+    /// The entry is removed once empty so the next generation can report the template as empty
+    async fn remove_cached_substitute(&self, template: &str, id: KeySize) {
+        self.random_sub_cache
+            .patch(template, |subs| subs.retain(|cached| cached.id != id))
+            .await;
+    }
+
+    /// The version of every template changed since the cache was last cleared alongside its cached
+    /// page, a page older than its template is refetched on its next read
+    pub fn cache_debug(&self) -> Vec<CachedTemplate> {
+        self.random_sub_cache.debug()
+    }
+
+    async fn get_random_substitute(&self, template: &str) -> Result<Substitute, FunboyError> {
+        self.validate_template_name(template)?;
+
+        if let Some(sub) = self.select_by_strategy(template).await? {
+            self.record_usage(sub.template_id);
+            return Ok(sub);
+        }
+
+        match self.random_sub_cache.get(template).await {
+            Some(subs) => {
+                let sub = pick_substitute(&subs)
+                    .expect("subs should be present in cache if match was found");
+                self.record_usage(sub.template_id);
+                Ok(sub.clone())
             }
-            Err(e) => {
-                assert!(
-                    matches!(e, FunboyError::UserInput(_)),
-                    "error was not UserInput variant"
+            None => {
+                let version = self.random_sub_cache.version(template);
+                let subs = self.read_substitute_page(template).await?;
+
+                if let Some(sub) = pick_substitute(&subs).cloned() {
+                    self.random_sub_cache.insert(template, version, subs).await;
+                    self.record_usage(sub.template_id);
+                    Ok(sub)
+                } else if let Some(sub) = self.resolve_fallback(template).await {
+                    Ok(sub)
+                } else {
+                    Err(FunboyError::Database(format!(
+                        "No substitutes were present in template \"{}\"",
+                        template
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Picks the next substitute of template when it has a round robin or least recently used
+    /// strategy, None when it picks at random, has no enabled substitutes or does not exist
+    ///
+    /// Strategies pick from every enabled substitute rather than a page so each one gets its turn,
+    /// they are read again once the page cache version of template moves on
+    async fn select_by_strategy(&self, template: &str) -> Result<Option<Substitute>, FunboyError> {
+        let version = self.random_sub_cache.version(template);
+        match self.selection.pick(template, version, unix_now_millis()) {
+            Selection::Picked(sub) => return Ok(Some(sub)),
+            Selection::Random => return Ok(None),
+            Selection::Stale => {}
+        }
+
+        let Some((strategy, cursor)) = self.template_db.read_selection_settings(template).await?
+        else {
+            return Ok(None);
+        };
+        let strategy = strategy
+            .as_deref()
+            .and_then(SelectionStrategy::parse)
+            .unwrap_or_default();
+        let (subs, stored) = match strategy {
+            SelectionStrategy::Random => (Vec::new(), SelectionState::default()),
+            _ => {
+                let subs = self.template_db.read_enabled_substitutes_from_template(
+                    template,
+                    OrderBy::Id(SortOrder::Ascending),
+                    Limit::None,
                 );
+                let last_used = self.template_db.read_last_used(template);
+                (
+                    subs.await?,
+                    SelectionState::new(cursor, last_used.await?.into_iter().collect()),
+                )
             }
+        };
+        self.selection
+            .load(template, version, strategy, subs, stored);
+
+        match self.selection.pick(template, version, unix_now_millis()) {
+            Selection::Picked(sub) => Ok(Some(sub)),
+            _ => Ok(None),
+        }
+    }
+
+    /// How generation picks substitutes of template
+    pub async fn get_template_strategy(
+        &self,
+        template: &str,
+    ) -> Result<SelectionStrategy, FunboyError> {
+        self.validate_template_name(template)?;
+
+        match self.template_db.read_selection_settings(template).await? {
+            Some((strategy, _)) => Ok(strategy
+                .as_deref()
+                .and_then(SelectionStrategy::parse)
+                .unwrap_or_default()),
+            None => Err(FunboyError::UserInput(
+                self.template_not_found(template).await?,
+            )),
         }
     }
 
-    async fn get_pool() -> PgPool {
-        PgPool::connect(template_database::DEBUG_DB_URL)
+    /// Changes how generation picks substitutes of template
+    ///
+    /// A round robin cycle or least recently used history already in progress carries on when the
+    /// strategy is switched back to it
+    pub async fn set_template_strategy(
+        &self,
+        template: &str,
+        strategy: SelectionStrategy,
+    ) -> Result<(), FunboyError> {
+        self.validate_template_name(template)?;
+
+        let stored = match strategy {
+            SelectionStrategy::Random => None,
+            _ => Some(strategy.as_str()),
+        };
+        if self
+            .template_db
+            .set_selection_strategy(template, stored)
+            .await?
+        {
+            self.random_sub_cache.invalidate(template).await;
+            Ok(())
+        } else {
+            Err(FunboyError::UserInput(
+                self.template_not_found(template).await?,
+            ))
+        }
+    }
+
+    /// Writes round robin cursors and least recently used picks made since the last flush
+    /// returning how many templates were written
+    ///
+    /// Both change on every pick so they are only kept in memory in between, a template whose
+    /// write fails is written with the next flush
+    pub async fn flush_selection_state(&self) -> Result<usize, FunboyError> {
+        let mut written = 0;
+        let mut failed = None;
+        for (template, changes) in self.selection.take_changes() {
+            let write = self.template_db.write_selection_state(
+                &template,
+                changes.cursor,
+                &changes.last_used,
+            );
+            match write.await {
+                Ok(()) => written += 1,
+                Err(e) => {
+                    self.selection.restore(&template, &changes);
+                    failed = Some(e);
+                }
+            }
+        }
+        match failed {
+            Some(e) => Err(e.into()),
+            None => Ok(written),
+        }
+    }
+
+    /// Random substitutes are picked from a page of this many read at once
+    const SUBSTITUTE_PAGE_SIZE: i64 = 200;
+
+    pub const MAX_SAMPLE_DRAWS: usize = 100_000;
+
+    /// Draws from template the way generation picks a random substitute and compares how often
+    /// each substitute came up with how often it should have
+    ///
+    /// Draws are picked from a page read like a cache miss reads one, without caching it, counting
+    /// a use or emitting events
+    pub async fn sample_distribution(
+        &self,
+        template: &str,
+        draws: usize,
+    ) -> Result<DistributionReport, FunboyError> {
+        self.validate_template_name(template)?;
+        if draws == 0 || draws > Self::MAX_SAMPLE_DRAWS {
+            return Err(FunboyError::UserInput(
+                UserFacingError::SampleDrawsInvalid {
+                    draws,
+                    limit: Self::MAX_SAMPLE_DRAWS,
+                },
+            ));
+        }
+        if !self.template_db.template_exists(template).await? {
+            return Err(FunboyError::UserInput(
+                self.template_not_found(template).await?,
+            ));
+        }
+
+        let subs = self
+            .template_db
+            .read_substitutes_from_template(template, None, None, OrderBy::Default, Limit::None)
+            .await?;
+        let page = self.read_substitute_page(template).await?;
+        let drawn: Vec<KeySize> = (0..draws)
+            .filter_map(|_| pick_substitute(&page).map(|sub| sub.id))
+            .collect();
+        Ok(DistributionReport::new(template, subs, page.len(), &drawn))
+    }
+
+    async fn read_substitute_page(&self, template: &str) -> Result<Vec<Substitute>, FunboyError> {
+        let subs = self.template_db.read_enabled_substitutes_from_template(
+            template,
+            OrderBy::Random,
+            Limit::Count(Self::SUBSTITUTE_PAGE_SIZE),
+        );
+        Ok(subs.await?)
+    }
+
+    /// The cached page of template, read and cached when it is not cached yet
+    async fn cached_substitute_page(&self, template: &str) -> Result<Vec<Substitute>, FunboyError> {
+        if let Some(subs) = self.random_sub_cache.get(template).await {
+            return Ok(subs);
+        }
+        let version = self.random_sub_cache.version(template);
+        let subs = self.read_substitute_page(template).await?;
+        if !subs.is_empty() {
+            self.random_sub_cache
+                .insert(template, version, subs.clone())
+                .await;
+        }
+        Ok(subs)
+    }
+
+    /// Picks a random substitute of template, or one of fallbacks when template is missing or has
+    /// no enabled substitutes
+    pub async fn get_substitute_or(
+        &self,
+        template: &str,
+        fallbacks: &[String],
+    ) -> Result<String, FunboyError> {
+        self.validate_template_name(template)?;
+        if !fallbacks.is_empty() && self.cached_substitute_page(template).await?.is_empty() {
+            return Ok(fallbacks[random_range(0..fallbacks.len())].clone());
+        }
+        Ok(self.get_random_substitute(template).await?.name)
+    }
+
+    /// Picks uniformly from the cached page of template together with extras
+    ///
+    /// Only extras are picked from when template is missing or has no enabled substitutes
+    pub async fn get_substitute_merged(
+        &self,
+        template: &str,
+        extras: &[String],
+    ) -> Result<String, FunboyError> {
+        self.validate_template_name(template)?;
+        let subs = self.cached_substitute_page(template).await?;
+        if subs.is_empty() && extras.is_empty() {
+            return Ok(self.get_random_substitute(template).await?.name);
+        }
+
+        let index = random_range(0..subs.len() + extras.len());
+        match subs.get(index) {
+            Some(sub) => {
+                self.record_usage(sub.template_id);
+                Ok(sub.name.clone())
+            }
+            None => Ok(extras[index - subs.len()].clone()),
+        }
+    }
+
+    /// Picks a random substitute like [`Funboy::get_random_substitute`] but keeps each page in
+    /// pages so every register of a template in one pass reads at most one page
+    async fn get_register_substitute(
+        &self,
+        template: &str,
+        pages: &mut HashMap<String, Vec<Substitute>>,
+        expansions: &SharedExpansionCounter,
+    ) -> Result<Substitute, FunboyError> {
+        self.validate_template_name(template)?;
+
+        if !pages.contains_key(template) {
+            let page = match self.random_sub_cache.get(template).await {
+                Some(page) => page,
+                None => {
+                    expansions.lock().await.record_page_fetch();
+                    let version = self.random_sub_cache.version(template);
+                    let page = self.read_substitute_page(template).await?;
+                    if !page.is_empty() {
+                        self.random_sub_cache
+                            .insert(template, version, page.clone())
+                            .await;
+                    }
+                    page
+                }
+            };
+            pages.insert(template.to_string(), page);
+        }
+
+        let page = &pages[template];
+        if page.is_empty() {
+            return match self.resolve_fallback(template).await {
+                Some(sub) => Ok(sub),
+                None => Err(FunboyError::Database(format!(
+                    "No substitutes were present in template \"{}\"",
+                    template
+                ))),
+            };
+        }
+        let sub = page[random_range(0..page.len())].clone();
+        self.record_usage(sub.template_id);
+        Ok(sub)
+    }
+
+    fn record_usage(&self, template_id: KeySize) {
+        self.usage
+            .record(template_id, self.usage_guild, day_of(unix_now()));
+    }
+
+    /// Substitutes from the fallback resolver are not stored so their ids are 0
+    async fn resolve_fallback(&self, template: &str) -> Option<Substitute> {
+        let Fallback(resolver) = self.fallback_resolver.as_ref()?;
+        let name = resolver(template).await?;
+        Some(Substitute {
+            id: 0,
+            kind: SubKind::classify(&name).as_str().to_string(),
+            name,
+            template_id: 0,
+            enabled: true,
+        })
+    }
+
+    /// Rejects code blocks nested too deeply before they reach the interpreter
+    ///
+    /// Malformed blocks are left for the interpreter to report
+    fn check_expression_depths(&self, input: &str) -> Result<(), FunboyError> {
+        let Ok(blocks) = find_code_blocks(input) else {
+            return Ok(());
+        };
+
+        for block in blocks {
+            if let Err(e) =
+                check_expression_depth(block_contents(input, block), self.max_expression_depth)
+            {
+                return Err(FunboyError::Interpreter(e.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects code storing into a reserved var unless it is the seed of
+    /// [`Funboy::generate_with_vars`], which can only ever read each seeded value once
+    fn check_reserved_stores(input: &str) -> Result<(), FunboyError> {
+        for args in find_command_calls(input, "store") {
+            let Some(name) = args.last().map(|name| name.trim_matches('"')) else {
+                continue;
+            };
+            let seeded = args.len() == 2 && args[0].starts_with(&format!("{}(", SEEDED_VAR));
+            if name.starts_with(Self::RESERVED_VAR_PREFIX) && !seeded {
+                return Err(FunboyError::UserInput(UserFacingError::VariableReserved {
+                    name: name.to_string(),
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces top level statement separators in every code block with whitespace
+    ///
+    /// Malformed blocks are left for the interpreter to report
+    fn separate_block_statements(input: &str) -> Cow<'_, str> {
+        let Ok(blocks) = find_code_blocks(input) else {
+            return Cow::Borrowed(input);
+        };
+
+        let mut output = Cow::Borrowed(input);
+        for block in blocks {
+            let start = block.start + CODE_BLOCK_OPEN.len_utf8();
+            if let Cow::Owned(code) = separate_statements(block_contents(input, block)) {
+                output
+                    .to_mut()
+                    .replace_range(start..start + code.len(), &code);
+            }
+        }
+        output
+    }
+
+    /// Rewrites negative and scientific notation numeric literals in every code block into forms
+    /// the interpreter reads
+    ///
+    /// Blocks are rewritten from last to first since an expansion shifts everything after it
+    fn expand_block_numeric_literals(input: &str) -> Cow<'_, str> {
+        let Ok(blocks) = find_code_blocks(input) else {
+            return Cow::Borrowed(input);
+        };
+
+        let mut output = Cow::Borrowed(input);
+        for block in blocks.into_iter().rev() {
+            let start = block.start + CODE_BLOCK_OPEN.len_utf8();
+            let code = block_contents(input, block);
+            if let Cow::Owned(expanded) = expand_numeric_literals(code) {
+                output
+                    .to_mut()
+                    .replace_range(start..start + code.len(), &expanded);
+            }
+        }
+        output
+    }
+
+    async fn interpret_embedded_code(
+        interpreter: Arc<Mutex<FslInterpreter>>,
+        input: String,
+    ) -> Result<String, FunboyError> {
+        let interpreter_result = interpreter
+            .lock()
+            .await
+            .interpret_embedded_code(&input)
+            .await;
+
+        match interpreter_result {
+            Ok(interpreted_text) => Ok(interpreted_text),
+            Err(e) => Err(FunboyError::Interpreter(e.to_string())),
+        }
+    }
+
+    /// Interprets each embedded code block separately recording the value of every top level command
+    ///
+    /// Commands are logged as they are written in source, the input before it was rewritten for
+    /// the interpreter, unless its blocks no longer line up with the values the interpreter logs
+    async fn interpret_embedded_code_logged(
+        interpreter: Arc<Mutex<FslInterpreter>>,
+        input: String,
+        source: String,
+        log: CommandLog,
+    ) -> Result<String, FunboyError> {
+        let blocks = match find_code_blocks(&input) {
+            Ok(blocks) => blocks,
+            Err(e) => return Err(FunboyError::Interpreter(e.to_string())),
+        };
+        let source_blocks = find_code_blocks(&source).unwrap_or_default();
+
+        let mut interpreter = interpreter.lock().await;
+        let mut output = String::new();
+        let mut end = 0;
+        for (i, block) in blocks.into_iter().enumerate() {
+            output.push_str(&input[end..block.start]);
+            end = block.end;
+
+            match interpreter
+                .interpret_and_log(block_contents(&input, block))
+                .await
+            {
+                Ok((interpreted_text, values)) => {
+                    output.push_str(&interpreted_text);
+                    let written = source_blocks
+                        .get(i)
+                        .map(|block| top_level_commands(block_contents(&source, block.clone())))
+                        .filter(|written| written.len() == values.len());
+                    log.lock().await.extend(values.iter().enumerate().map(
+                        |(j, (command, value))| CommandValue {
+                            command: written.as_ref().map_or_else(
+                                || command.to_string(),
+                                |written| written[j].to_string(),
+                            ),
+                            value: value_to_log_string(value),
+                        },
+                    ));
+                }
+                Err(e) => return Err(FunboyError::Interpreter(e.to_string())),
+            }
+        }
+        output.push_str(&input[end..]);
+
+        Ok(output)
+    }
+
+    /// Resolves templates and interprets embeded code in input with a single pass
+    async fn interpret_input(
+        &self,
+        input: String,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+        log: Option<CommandLog>,
+        expansions: SharedExpansionCounter,
+        options: GenerateOptions,
+    ) -> Result<String, FunboyError> {
+        let substituted_text = self
+            .substitute_register_templates(input, interpreter.clone(), expansions.clone(), options)
+            .await?;
+
+        let funboy_error: Arc<Mutex<Option<FunboyError>>> = Arc::new(Mutex::new(None));
+        let (substituted_text, depth_reached) =
+            TemplateSubstitutor::with_delimiters(&self.delimiters.substituted())
+                .await
+                .with_depth_limit(options.max_depth.into())
+                .substitute_within_depth(substituted_text, |template: String| {
+                    let expansions = expansions.clone();
+                    let funboy_error = funboy_error.clone();
+
+                    async move {
+                        match self.get_random_substitute(&template).await {
+                            Ok(sub) => {
+                                if let Err(e) = expansions.lock().await.record(&template) {
+                                    let _ = funboy_error
+                                        .lock()
+                                        .await
+                                        .get_or_insert(FunboyError::UserInput(e.into()));
+                                    return None;
+                                }
+                                Some(sub.name.to_string())
+                            }
+                            Err(_) => None,
+                        }
+                    }
+                })
+                .await;
+        if let Some(e) = funboy_error.lock().await.take() {
+            return Err(e);
+        }
+        if depth_reached {
+            expansions.lock().await.record_depth_reached();
+        }
+
+        self.check_expression_depths(&substituted_text)?;
+        Self::check_reserved_stores(&substituted_text)?;
+        let code = Self::separate_block_statements(&substituted_text).into_owned();
+        let code = Self::expand_block_numeric_literals(&code).into_owned();
+
+        // Logged and unlogged generations run on the same thread so they behave the same
+        let blocking = self
+            .blocking_threshold
+            .is_some_and(|threshold| code.len() > threshold);
+        let interpret = move || async move {
+            match log {
+                Some(log) => {
+                    Self::interpret_embedded_code_logged(interpreter, code, substituted_text, log)
+                        .await
+                }
+                None => Self::interpret_embedded_code(interpreter, code).await,
+            }
+        };
+
+        if blocking {
+            Self::interpret_on_blocking_thread(interpret).await
+        } else {
+            interpret().await
+        }
+    }
+
+    /// Runs interpret on a blocking thread, commands still run on the runtime through its handle
+    async fn interpret_on_blocking_thread<F, Fut>(interpret: F) -> Result<String, FunboyError>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<String, FunboyError>>,
+    {
+        let handle = tokio::runtime::Handle::current();
+        let result = tokio::task::spawn_blocking(move || handle.block_on(interpret())).await;
+
+        match result {
+            Ok(result) => result,
+            Err(e) => Err(FunboyError::Interpreter(e.to_string())),
+        }
+    }
+
+    #[async_recursion]
+    async fn substitute_register_templates(
+        &self,
+        input: String,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+        expansions: SharedExpansionCounter,
+        options: GenerateOptions,
+    ) -> Result<String, FunboyError> {
+        let sub_map: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
+        let pages: Arc<Mutex<HashMap<String, Vec<Substitute>>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let funboy_error: Arc<Mutex<Option<FunboyError>>> = Arc::new(Mutex::new(None));
+        let (output, depth_reached) = TemplateSubstitutor::new(TemplateDelimiter::PLUS_REGISTER)
+            .await
+            .with_depth_limit(options.max_depth.into())
+            .substitute_within_depth(input, |template: String| {
+                let sub_map = sub_map.clone();
+                let pages = pages.clone();
+                let interpreter = interpreter.clone();
+                let funboy_error = funboy_error.clone();
+                let expansions = expansions.clone();
+
+                async move {
+                    let mut sub_map = sub_map.lock().await;
+                    if let Some(value) = sub_map.get(&template) {
+                        return Some(value.clone());
+                    }
+
+                    let template_before_dash = template.split('-').next().unwrap_or_default();
+                    let sub = self
+                        .get_register_substitute(
+                            template_before_dash,
+                            &mut *pages.lock().await,
+                            &expansions,
+                        )
+                        .await
+                        .ok()?;
+
+                    let counted = {
+                        let mut expansions = expansions.lock().await;
+                        expansions
+                            .record_register()
+                            .and_then(|_| expansions.record(template_before_dash))
+                    };
+                    if let Err(e) = counted {
+                        let _ = funboy_error
+                            .lock()
+                            .await
+                            .insert(FunboyError::UserInput(e.into()));
+                        return None;
+                    }
+
+                    let sub = match self
+                        .generate_with_log(
+                            &sub.name,
+                            interpreter,
+                            None,
+                            expansions.clone(),
+                            options,
+                        )
+                        .await
+                    {
+                        Ok(interpreted_sub) => interpreted_sub.text,
+                        Err(e) => {
+                            let _ = funboy_error.lock().await.insert(e);
+                            return None;
+                        }
+                    };
+                    let checked = expansions
+                        .lock()
+                        .await
+                        .check_register_length(&template, sub.len());
+                    if let Err(e) = checked {
+                        let _ = funboy_error
+                            .lock()
+                            .await
+                            .insert(FunboyError::UserInput(e.into()));
+                        return None;
+                    }
+
+                    sub_map.insert(template.to_string(), sub.clone());
+                    Some(sub)
+                }
+            })
+            .await;
+
+        if depth_reached {
+            expansions.lock().await.record_depth_reached();
+        }
+
+        let registers = sub_map.lock().await.len();
+        if registers > 0 {
+            let expansions = expansions.lock().await;
+            tracing::debug!(
+                registers,
+                generation_registers = expansions.registers(),
+                page_fetches = expansions.page_fetches(),
+                "substituted registers"
+            );
+        }
+
+        let err = funboy_error.lock().await.take();
+        match err {
+            Some(e) => return Err(e),
+            None => return Ok(output),
+        }
+    }
+
+    /* PROFILE CODE
+        let before = SystemTime::now();
+
+        let after = SystemTime::now();
+
+        let time = after.duration_since(before).unwrap();
+        unsafe {
+            static mut INTERP_TIME: Duration = Duration::new(0, 0);
+            INTERP_TIME += time;
+            dbg!(INTERP_TIME);
+        }
+    */
+    /// Resolves templates and fsl code until output is complete or depth limit is reached
+    #[tracing::instrument(level = "debug", skip_all, fields(input_len = input.len()))]
+    pub async fn generate(
+        &self,
+        input: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+    ) -> Result<GenerationOutput, FunboyError> {
+        self.generate_with_options(input, interpreter, GenerateOptions::default())
+            .await
+    }
+
+    /// Generates like [`Funboy::generate`] with options for this call only
+    #[tracing::instrument(level = "debug", skip_all, fields(input_len = input.len(), max_depth = options.max_depth))]
+    pub async fn generate_with_options(
+        &self,
+        input: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+        options: GenerateOptions,
+    ) -> Result<GenerationOutput, FunboyError> {
+        let started = Instant::now();
+        let result = self
+            .generate_with_log(
+                input,
+                interpreter,
+                None,
+                self.new_expansion_counter(),
+                options,
+            )
+            .await;
+
+        for event in self
+            .event_thresholds
+            .evaluate(input, started.elapsed(), &result)
+        {
+            self.events.emit(event);
+        }
+        result.inspect_err(log_error)
+    }
+
+    /// Generates every case of spec on a fresh interpreter, each within its own timeout
+    pub async fn self_test(&self, spec: &SelfTestSpec) -> SelfTestReport {
+        let started = Instant::now();
+        let mut results = Vec::new();
+        for case in spec.cases() {
+            let generation = async {
+                self.generate(&case.input, Arc::new(Mutex::new(FslInterpreter::new())))
+                    .await
+                    .map(|output| output.text)
+            };
+            results.push(run_case(&case, spec.timeout(&case), generation).await);
+        }
+        SelfTestReport {
+            results,
+            duration: started.elapsed(),
+        }
+    }
+
+    /// Generates like [`Funboy::generate`] with vars already stored before input is interpreted
+    ///
+    /// Embedded code reads each var with `clone(name)`, values are stored as text without being
+    /// parsed so they can contain anything. Names starting with [`Funboy::RESERVED_VAR_PREFIX`]
+    /// can be seeded here but never stored by the input itself.
+    #[tracing::instrument(level = "debug", skip_all, fields(input_len = input.len(), vars = vars.len()))]
+    pub async fn generate_with_vars(
+        &self,
+        input: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+        vars: &[(&str, &str)],
+    ) -> Result<GenerationOutput, FunboyError> {
+        if vars.is_empty() {
+            return self.generate(input, interpreter).await;
+        }
+
+        for (name, _) in vars {
+            Self::validate_variable_name(name).map_err(FunboyError::UserInput)?;
+        }
+
+        let values = vars
+            .iter()
+            .map(|(_, value)| Some(value.to_string()))
+            .collect();
+        interpreter.lock().await.add_command(
+            SEEDED_VAR,
+            SEEDED_VAR_RULES,
+            create_seeded_var_command(Arc::new(std::sync::Mutex::new(values))),
+        );
+
+        let seed = vars
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| format!("store({}({}), {})", SEEDED_VAR, i, name))
+            .collect::<Vec<_>>()
+            .join("; ");
+        self.generate(&format!("{{{}}}{}", seed, input), interpreter)
+            .await
+    }
+
+    /// Generates like [`Funboy::generate`] while recording the value of every top level command
+    /// in every embedded code block of each pass
+    #[tracing::instrument(level = "debug", skip_all, fields(input_len = input.len()))]
+    pub async fn debug_generate(
+        &self,
+        input: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+    ) -> Result<DebugOutput, FunboyError> {
+        let log: CommandLog = Arc::new(Mutex::new(Vec::new()));
+        let output = self
+            .generate_with_log(
+                input,
+                interpreter,
+                Some(log.clone()),
+                self.new_expansion_counter(),
+                GenerateOptions::default(),
+            )
+            .await
+            .inspect_err(log_error)?
+            .text;
+        let log = log.lock().await.clone();
+        Ok(DebugOutput { output, log })
+    }
+
+    fn new_expansion_counter(&self) -> SharedExpansionCounter {
+        Arc::new(Mutex::new(ExpansionCounter::new(self.expansion_limits)))
+    }
+
+    /// Expansions are shared with nested generations of register templates so their
+    /// resolutions count towards the same limits, stored vars are shared since they store into
+    /// the same interpreter
+    ///
+    /// Generation stops once a pass leaves output unchanged without resolving any template.
+    /// Output repeating an earlier pass is not enough to stop since random substitutes can
+    /// return to the same text and still make progress later, templates that only ever
+    /// resolve back to themselves are stopped by the expansion limits instead.
+    async fn generate_with_log(
+        &self,
+        input: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+        log: Option<CommandLog>,
+        expansions: SharedExpansionCounter,
+        options: GenerateOptions,
+    ) -> Result<GenerationOutput, FunboyError> {
+        let mut output = input.to_string();
+
+        let mut modified_interpreter = interpreter.lock().await;
+        let funboy = Arc::new(self.clone());
+        modified_interpreter.add_command(
+            GET_SUB,
+            GET_SUB_RULES,
+            create_get_sub_command(funboy.clone()),
+        );
+        modified_interpreter.add_command(
+            GET_SUB_OR,
+            GET_SUB_INLINE_RULES,
+            create_get_sub_or_command(funboy.clone()),
+        );
+        modified_interpreter.add_command(
+            GET_SUB_MERGED,
+            GET_SUB_INLINE_RULES,
+            create_get_sub_merged_command(funboy.clone()),
+        );
+        #[cfg(feature = "ollama")]
+        modified_interpreter.add_command(ASK_AI, ASK_AI_RULES, create_ask_ai_command(funboy));
+        modified_interpreter.add_command(A_OR_AN, A_OR_AN_RULES, create_a_or_an_command());
+        modified_interpreter.add_command(PLURAL, PLURAL_RULES, create_plural_command());
+        modified_interpreter.add_command(ORDINAL, ORDINAL_RULES, create_ordinal_command());
+        modified_interpreter.add_command(TITLE_CASE, TITLE_CASE_RULES, create_title_case_command());
+        modified_interpreter.add_command(SNAKE_CASE, SNAKE_CASE_RULES, create_snake_case_command());
+        modified_interpreter.add_command(CAMEL_CASE, CAMEL_CASE_RULES, create_camel_case_command());
+        modified_interpreter.add_command(CAPITALIZE, CAPITALIZE_RULES, create_capitalize_command());
+        modified_interpreter.add_command(DIV, CHECKED_MATH_RULES, create_div_command());
+        modified_interpreter.add_command(MOD, CHECKED_MATH_RULES, create_mod_command());
+        modified_interpreter.add_command(EQ, EQ_RULES, create_eq_command());
+        modified_interpreter.add_command(
+            RANDOM_RANGE,
+            RANDOM_RANGE_RULES,
+            create_random_range_command(),
+        );
+        modified_interpreter.add_command(JSON_GET, JSON_GET_RULES, create_json_get_command());
+        modified_interpreter.add_command(JSON_SET, JSON_SET_RULES, create_json_set_command());
+        modified_interpreter.add_command(TO_JSON, TO_JSON_RULES, create_to_json_command());
+        modified_interpreter.add_command(VARS, VARS_RULES, create_vars_command());
+        modified_interpreter.add_command(CLONE, CLONE_RULES, create_clone_command());
+        modified_interpreter.add_command(HAS_STORED, HAS_STORED_RULES, create_has_stored_command());
+        drop(modified_interpreter);
+
+        let max_depth = options.max_depth.max(1);
+        let mut passes = 0;
+        let mut complete = false;
+        for _ in 0..max_depth {
+            passes += 1;
+            let resolved_before = expansions.lock().await.total();
+            let next = self
+                .interpret_input(
+                    output.clone(),
+                    interpreter.clone(),
+                    log.clone(),
+                    expansions.clone(),
+                    options,
+                )
+                .await?;
+            let resolved = expansions.lock().await.total() - resolved_before;
+
+            let unchanged = next == output;
+            output = next;
+            if unchanged && resolved == 0 {
+                complete = true;
+                break;
+            }
+            if expansions.lock().await.depth_reached() {
+                break;
+            }
+        }
+
+        let mut output = GenerationOutput::new(output, passes);
+        if !complete || expansions.lock().await.depth_reached() {
+            let templates = self.unresolved_templates(&output.text).await?;
+            if !templates.is_empty() {
+                let error = UserFacingError::NestingTooDeep {
+                    depth: max_depth,
+                    templates,
+                };
+                match options.depth_mode {
+                    DepthMode::Strict => return Err(FunboyError::UserInput(error)),
+                    DepthMode::Lenient => output.warnings.push(error),
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Templates referenced in text that exist, so they were left over by a depth limit rather
+    /// than because there was nothing to resolve them with
+    async fn unresolved_templates(&self, text: &str) -> Result<Vec<String>, FunboyError> {
+        let mut delimiters = self.delimiters.substituted();
+        delimiters.push(TemplateDelimiter::PLUS_REGISTER);
+        let names = TemplateSubstitutor::with_delimiters(&delimiters)
+            .await
+            .template_names(text);
+
+        let mut templates: Vec<String> = Vec::new();
+        for name in names {
+            // registers are named after their template followed by a dash and a number
+            let name = name.split('-').next().unwrap_or_default();
+            if !templates.iter().any(|template| template == name)
+                && self.template_db.template_exists(name).await?
+            {
+                templates.push(name.to_string());
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Favorites are capped so they fit in a single Discord select menu
+    pub const MAX_FAVORITES: usize = 25;
+
+    /// Marks a template as a favorite of a user
+    ///
+    /// Returns None if the template already was a favorite
+    pub async fn add_favorite(
+        &self,
+        user_id: u64,
+        template: &str,
+    ) -> Result<Option<Favorite>, FunboyError> {
+        self.validate_template_name(template)?;
+
+        let favorite = self.template_db.create_favorite(
+            user_id as KeySize,
+            template,
+            Funboy::MAX_FAVORITES as i64,
+        );
+        match favorite.await? {
+            FavoriteInsert::Added(favorite) => Ok(Some(favorite)),
+            FavoriteInsert::AlreadyFavorite => Ok(None),
+            FavoriteInsert::TemplateNotFound => {
+                Err(FunboyError::UserInput(UserFacingError::TemplateNotFound {
+                    name: template.to_string(),
+                    suggestions: Vec::new(),
+                }))
+            }
+            FavoriteInsert::LimitReached => {
+                Err(FunboyError::UserInput(UserFacingError::TooManyFavorites {
+                    limit: Funboy::MAX_FAVORITES,
+                }))
+            }
+        }
+    }
+
+    /// Returns whether the template was a favorite of the user
+    pub async fn remove_favorite(&self, user_id: u64, template: &str) -> Result<bool, FunboyError> {
+        let removed = self
+            .template_db
+            .delete_favorite(user_id as KeySize, template);
+        Ok(removed.await?)
+    }
+
+    /// Lists the favorite templates of a user ordered by name
+    pub async fn list_favorites(&self, user_id: u64) -> Result<Vec<Favorite>, FunboyError> {
+        let favorites = self.template_db.read_favorites(user_id as KeySize);
+        Ok(favorites.await?)
+    }
+
+    /// Playback events older than this are removed by [`Funboy::prune_playback_history`]
+    pub const PLAYBACK_RETENTION_DAYS: u64 = 30;
+
+    /// Records that a track started playing now
+    pub async fn record_playback(
+        &self,
+        guild_id: u64,
+        track_name: &str,
+        track_url: Option<&str>,
+        requested_by: u64,
+    ) -> Result<PlaybackEvent, FunboyError> {
+        let event = self.template_db.create_playback_event(
+            guild_id as KeySize,
+            track_name,
+            track_url,
+            requested_by as KeySize,
+            unix_now(),
+        );
+        Ok(event.await?)
+    }
+
+    /// Marks a recorded track as played to the end
+    pub async fn complete_playback(&self, event_id: KeySize) -> Result<(), FunboyError> {
+        self.template_db.complete_playback_event(event_id).await?;
+        Ok(())
+    }
+
+    /// The tracks most recently played in a guild, newest first
+    pub async fn get_playback_history(
+        &self,
+        guild_id: u64,
+        limit: usize,
+    ) -> Result<Vec<PlaybackEvent>, FunboyError> {
+        let events = self
+            .template_db
+            .read_playback_events(guild_id as KeySize, limit as i64);
+        Ok(events.await?)
+    }
+
+    /// Deletes playback events older than [`Funboy::PLAYBACK_RETENTION_DAYS`]
+    pub async fn prune_playback_history(&self) -> Result<u64, FunboyError> {
+        const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+        let cutoff = unix_now() - Funboy::PLAYBACK_RETENTION_DAYS as i64 * SECONDS_PER_DAY;
+        let deleted = self.template_db.delete_playback_events_before(cutoff);
+        Ok(deleted.await?)
+    }
+
+    /// The channel now playing announcements of a guild go to, None when they are off
+    pub async fn get_announcement_channel(
+        &self,
+        guild_id: u64,
+    ) -> Result<Option<u64>, FunboyError> {
+        let channel_id = self
+            .template_db
+            .read_announcement_channel(guild_id as KeySize);
+        Ok(channel_id.await?.map(|id| id as u64))
+    }
+
+    pub async fn set_announcement_channel(
+        &self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+    ) -> Result<(), FunboyError> {
+        let set = self
+            .template_db
+            .set_announcement_channel(guild_id as KeySize, channel_id.map(|id| id as KeySize));
+        Ok(set.await?)
+    }
+
+    pub const MAX_AI_LABEL_LENGTH: usize = 100;
+
+    /// The label Ollama responses in a guild are posted with, None when they aren't labelled
+    pub async fn get_ai_label(&self, guild_id: u64) -> Result<Option<AiLabel>, FunboyError> {
+        let label = self.template_db.read_ai_label(guild_id as KeySize);
+        let Some((true, text, placement)) = label.await? else {
+            return Ok(None);
+        };
+        let default = AiLabel::default();
+        Ok(Some(AiLabel {
+            text: text.unwrap_or(default.text),
+            placement: placement
+                .as_deref()
+                .and_then(LabelPlacement::parse)
+                .unwrap_or(default.placement),
+        }))
+    }
+
+    /// Turns labelling Ollama responses in a guild on or off, None or a blank text uses
+    /// [`ai_label::DEFAULT_AI_LABEL`]
+    pub async fn set_ai_label(
+        &self,
+        guild_id: u64,
+        enabled: bool,
+        text: Option<&str>,
+        placement: LabelPlacement,
+    ) -> Result<(), FunboyError> {
+        let text = text.map(str::trim).filter(|text| !text.is_empty());
+        if let Some(text) = text {
+            let length = text.chars().count();
+            if length > Funboy::MAX_AI_LABEL_LENGTH {
+                return Err(FunboyError::UserInput(UserFacingError::AiLabelTooLong {
+                    length,
+                    limit: Funboy::MAX_AI_LABEL_LENGTH,
+                }));
+            }
+        }
+
+        let set =
+            self.template_db
+                .set_ai_label(guild_id as KeySize, enabled, text, placement.as_str());
+        Ok(set.await?)
+    }
+
+    /// Roles allowed to use FSL commands that send messages, empty when everyone may use them
+    pub async fn get_fsl_allowed_roles(&self, guild_id: u64) -> Result<Vec<u64>, FunboyError> {
+        let roles = self.template_db.read_fsl_allowed_roles(guild_id as KeySize);
+        Ok(roles.await?.into_iter().map(|role| role as u64).collect())
+    }
+
+    /// Allows or disallows a role, returns whether anything changed
+    pub async fn set_fsl_role_allowed(
+        &self,
+        guild_id: u64,
+        role_id: u64,
+        allowed: bool,
+    ) -> Result<bool, FunboyError> {
+        let changed = if allowed {
+            self.template_db
+                .create_fsl_allowed_role(guild_id as KeySize, role_id as KeySize)
+                .await?
+        } else {
+            self.template_db
+                .delete_fsl_allowed_role(guild_id as KeySize, role_id as KeySize)
+                .await?
+        };
+        Ok(changed)
+    }
+
+    /// Channels generation is limited to, empty when every channel is allowed
+    pub async fn get_generate_channels(&self, guild_id: u64) -> Result<Vec<u64>, FunboyError> {
+        let channels = self.template_db.read_generate_channels(guild_id as KeySize);
+        Ok(channels
+            .await?
+            .into_iter()
+            .map(|channel| channel as u64)
+            .collect())
+    }
+
+    /// Allows or disallows generating in a channel, returns whether anything changed
+    pub async fn set_generate_channel_allowed(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        allowed: bool,
+    ) -> Result<bool, FunboyError> {
+        let changed = if allowed {
+            self.template_db
+                .create_generate_channel(guild_id as KeySize, channel_id as KeySize)
+                .await?
+        } else {
+            self.template_db
+                .delete_generate_channel(guild_id as KeySize, channel_id as KeySize)
+                .await?
+        };
+        Ok(changed)
+    }
+
+    pub const MAX_ALIASES: usize = 100;
+    pub const MAX_ALIAS_INPUT_LENGTH: usize = 4000;
+
+    /// Saves an alias of a guild replacing the input of any alias with the same name
+    pub async fn set_alias(
+        &self,
+        guild_id: u64,
+        name: &str,
+        input: &str,
+    ) -> Result<Alias, FunboyError> {
+        if let Some(reason) = self.template_name_reason(name) {
+            return Err(FunboyError::UserInput(UserFacingError::AliasNameInvalid {
+                reason,
+            }));
+        }
+        let length = input.chars().count();
+        if input.trim().is_empty() || length > Funboy::MAX_ALIAS_INPUT_LENGTH {
+            return Err(FunboyError::UserInput(UserFacingError::AliasInputInvalid {
+                length,
+                limit: Funboy::MAX_ALIAS_INPUT_LENGTH,
+            }));
+        }
+
+        let alias = self.template_db.upsert_alias(
+            guild_id as KeySize,
+            name,
+            input,
+            Funboy::MAX_ALIASES as i64,
+        );
+        match alias.await? {
+            Some(alias) => Ok(alias),
+            None => Err(FunboyError::UserInput(UserFacingError::TooManyAliases {
+                limit: Funboy::MAX_ALIASES,
+            })),
+        }
+    }
+
+    /// Returns whether the guild had an alias named name
+    pub async fn remove_alias(&self, guild_id: u64, name: &str) -> Result<bool, FunboyError> {
+        let removed = self.template_db.delete_alias(guild_id as KeySize, name);
+        Ok(removed.await?)
+    }
+
+    /// Lists the aliases of a guild ordered by name
+    pub async fn get_aliases(&self, guild_id: u64) -> Result<Vec<Alias>, FunboyError> {
+        let aliases = self.template_db.read_aliases(guild_id as KeySize);
+        Ok(aliases.await?)
+    }
+
+    /// The input of the alias named by the whole of input, or input itself when there is none
+    ///
+    /// The alias input is returned as is so an alias naming another alias expands only once
+    pub async fn expand_alias(&self, guild_id: u64, input: &str) -> Result<String, FunboyError> {
+        let name = input.trim();
+        if self.template_name_reason(name).is_some() {
+            return Ok(input.to_string());
+        }
+
+        let alias = self.template_db.read_alias(guild_id as KeySize, name);
+        match alias.await? {
+            Some(alias) => Ok(alias.input_text),
+            None => Ok(input.to_string()),
+        }
+    }
+
+    pub const MAX_TRIGGERS: usize = 25;
+    pub const MAX_TRIGGER_INPUT_LENGTH: usize = 4000;
+
+    /// Saves a trigger of a guild replacing the input and cooldown of one with the same pattern
+    /// and mode, cooldowns longer than [`MAX_TRIGGER_COOLDOWN_SECS`] are shortened to it
+    pub async fn set_trigger(
+        &self,
+        guild_id: u64,
+        pattern: &str,
+        mode: TriggerMatchMode,
+        template_input: &str,
+        cooldown_secs: u64,
+    ) -> Result<Trigger, FunboyError> {
+        TriggerMatcher::compile(pattern, mode)?;
+        let pattern = match mode {
+            TriggerMatchMode::Exact => pattern.trim(),
+            TriggerMatchMode::Contains | TriggerMatchMode::Regex => pattern,
+        };
+        let length = template_input.chars().count();
+        if template_input.trim().is_empty() || length > Funboy::MAX_TRIGGER_INPUT_LENGTH {
+            return Err(FunboyError::UserInput(
+                UserFacingError::TriggerInputInvalid {
+                    length,
+                    limit: Funboy::MAX_TRIGGER_INPUT_LENGTH,
+                },
+            ));
+        }
+
+        let trigger = self.template_db.upsert_trigger(
+            guild_id as KeySize,
+            pattern,
+            mode.as_str(),
+            template_input,
+            cooldown_secs.min(MAX_TRIGGER_COOLDOWN_SECS) as i64,
+            Funboy::MAX_TRIGGERS as i64,
+        );
+        let trigger = trigger.await?;
+        self.trigger_cache.invalidate(&(guild_id as KeySize)).await;
+        match trigger {
+            Some(trigger) => Ok(trigger),
+            None => Err(FunboyError::UserInput(UserFacingError::TooManyTriggers {
+                limit: Funboy::MAX_TRIGGERS,
+            })),
+        }
+    }
+
+    /// Returns whether the guild had a trigger with the id
+    pub async fn remove_trigger(&self, guild_id: u64, id: KeySize) -> Result<bool, FunboyError> {
+        let removed = self.template_db.delete_trigger(guild_id as KeySize, id);
+        let removed = removed.await?;
+        self.trigger_cache.invalidate(&(guild_id as KeySize)).await;
+        Ok(removed)
+    }
+
+    /// Lists the triggers of a guild in the order they are matched
+    pub async fn get_triggers(&self, guild_id: u64) -> Result<Vec<Trigger>, FunboyError> {
+        let triggers = self.template_db.read_triggers(guild_id as KeySize);
+        Ok(triggers.await?)
+    }
+
+    /// The first trigger of a guild matching content
+    ///
+    /// Patterns are compiled once per guild and kept until a trigger of the guild changes
+    pub async fn match_trigger(
+        &self,
+        guild_id: u64,
+        content: &str,
+    ) -> Result<Option<Trigger>, FunboyError> {
+        let guild_id = guild_id as KeySize;
+        let compiled = match self.trigger_cache.get(&guild_id).await {
+            Some(compiled) => compiled,
+            None => {
+                let triggers = self.template_db.read_triggers(guild_id).await?;
+                let compiled = Arc::new(CompiledTriggers::compile(triggers));
+                self.trigger_cache.insert(guild_id, compiled.clone()).await;
+                compiled
+            }
+        };
+        Ok(compiled.find_match(content).cloned())
+    }
+
+    /// Writes the template uses counted since the last flush returning how many rows were written
+    ///
+    /// Uses are kept for the next flush when writing them fails
+    pub async fn flush_usage(&self) -> Result<usize, FunboyError> {
+        let counts = self.usage.drain();
+        if counts.is_empty() {
+            return Ok(0);
+        }
+
+        if let Err(e) = self.template_db.add_template_usage(&counts).await {
+            self.usage.restore(&counts);
+            return Err(e.into());
+        }
+        Ok(counts.len())
+    }
+
+    /// The most used templates of a guild over the last days including today
+    ///
+    /// Uses that have not been flushed yet with [`Funboy::flush_usage`] are not included
+    pub async fn get_template_leaderboard(
+        &self,
+        guild_id: Option<u64>,
+        days: u64,
+        limit: usize,
+    ) -> Result<Vec<TemplateUsage>, FunboyError> {
+        let today = day_of(unix_now());
+        let first_day = today - days.saturating_sub(1) as i64;
+        let usage = self.template_db.read_top_templates(
+            guild_id.map(|guild_id| guild_id as KeySize),
+            first_day,
+            today,
+            limit as i64,
+        );
+        Ok(usage.await?)
+    }
+
+    /// The users who added the most substitutes from a guild, or from anywhere when guild_id is
+    /// None, optionally only counting template and substitutes added in the last days
+    pub async fn get_contribution_stats(
+        &self,
+        guild_id: Option<u64>,
+        template: Option<&str>,
+        days: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Contribution>, FunboyError> {
+        if let Some(template) = template {
+            self.validate_template_name(template)?;
+        }
+        const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+        let since = days.map(|days| unix_now() - days as i64 * SECONDS_PER_DAY);
+        let stats = self.template_db.contribution_stats(
+            guild_id.map(|guild_id| guild_id as KeySize),
+            template,
+            since,
+            limit as i64,
+        );
+        Ok(stats.await?)
+    }
+
+    /// How many substitutes user_id added to each template from a guild, or from anywhere when
+    /// guild_id is None
+    pub async fn get_user_contributions(
+        &self,
+        user_id: u64,
+        guild_id: Option<u64>,
+    ) -> Result<Vec<TemplateContribution>, FunboyError> {
+        let contributions = self.template_db.read_user_contributions(
+            user_id as KeySize,
+            guild_id.map(|guild_id| guild_id as KeySize),
+        );
+        Ok(contributions.await?)
+    }
+
+    /// Picks the template of the day of a guild and records it so it isn't picked again within
+    /// [`featured::REPEAT_WINDOW_DAYS`]
+    ///
+    /// Returns None when no unarchived template has an enabled substitute
+    pub async fn pick_featured_template(
+        &self,
+        guild_id: u64,
+        strategy: FeaturedStrategy,
+    ) -> Result<Option<FeaturedCandidate>, FunboyError> {
+        let today = day_of(unix_now());
+        let candidates = self
+            .template_db
+            .read_featured_candidates(guild_id as KeySize, today - (USAGE_WINDOW_DAYS - 1))
+            .await?;
+        let Some(featured) =
+            select_featured(&candidates, strategy, today, &mut rand::rng()).cloned()
+        else {
+            return Ok(None);
+        };
+
+        self.template_db
+            .create_featured_history(guild_id as KeySize, featured.id, today)
+            .await?;
+        Ok(Some(featured))
+    }
+
+    /// Posts the template of the day of a guild in channel_id, replacing any earlier channel
+    pub async fn set_featured_channel(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        strategy: FeaturedStrategy,
+    ) -> Result<(), FunboyError> {
+        let set = self.template_db.upsert_featured_channel(
+            guild_id as KeySize,
+            channel_id as KeySize,
+            strategy.as_str(),
+        );
+        Ok(set.await?)
+    }
+
+    /// Stops posting the template of the day, returns whether the guild had a featured channel
+    pub async fn remove_featured_channel(&self, guild_id: u64) -> Result<bool, FunboyError> {
+        let deleted = self
+            .template_db
+            .delete_featured_channel(guild_id as KeySize);
+        Ok(deleted.await?)
+    }
+
+    /// Featured channels that have not been posted in on day, see [`template_usage::day_of`]
+    pub async fn get_featured_channels_due(
+        &self,
+        day: i64,
+    ) -> Result<Vec<FeaturedChannel>, FunboyError> {
+        let channels = self.template_db.read_featured_channels_due(day);
+        Ok(channels.await?)
+    }
+
+    pub async fn mark_featured_posted(&self, guild_id: u64, day: i64) -> Result<(), FunboyError> {
+        let updated = self
+            .template_db
+            .update_featured_posted_day(guild_id as KeySize, day);
+        Ok(updated.await?)
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn unix_now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+#[cfg(feature = "ollama")]
+impl Funboy {
+    pub async fn get_ollama_model(&self) -> Option<String> {
+        self.ollama_model.lock().await.clone()
+    }
+
+    pub async fn set_ollama_model(&self, new_model: Option<String>) {
+        let mut model = self.ollama_model.lock().await;
+        *model = new_model;
+    }
+
+    pub async fn get_ollama_models(&self) -> Result<Vec<String>, FunboyError> {
+        let models = self.ollama_generator.get_models().await;
+        match models {
+            Ok(models) => Ok(models.iter().map(|m| m.name.to_string()).collect()),
+            Err(e) => Err(FunboyError::Ollama(e.to_string())),
+        }
+    }
+
+    pub async fn get_ollama_model_info(&self, model: String) -> Result<ModelInfo, FunboyError> {
+        match self.ollama_generator.get_model_info(model).await {
+            Ok(info) => Ok(info),
+            Err(e) => Err(FunboyError::Ollama(e.to_string())),
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, ollama_settings, prompt, interpreter))]
+    pub async fn generate_ollama(
+        &self,
+        model: Option<String>,
+        ollama_settings: &OllamaSettings,
+        prompt: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+    ) -> Result<GenerationResponse, FunboyError> {
+        let request = self
+            .prepare_ollama_request(prompt, ollama_settings, None, "", interpreter)
+            .await?;
+        match self
+            .ollama_generator
+            .generate(&request.prompt, &request.settings, model)
+            .await
+        {
+            Ok(output) => Ok(output),
+            Err(e) => Err(FunboyError::Ollama(e.to_string())).inspect_err(log_error),
+        }
+    }
+
+    /// Generates prompt and applies preset to it for Ollama
+    ///
+    /// Only prompt is generated, the system prompt and template of settings and the body of
+    /// preset are passed on as raw text so templates and FSL stored in them never run
+    pub async fn prepare_ollama_request(
+        &self,
+        prompt: &str,
+        ollama_settings: &OllamaSettings,
+        preset: Option<&PromptPreset>,
+        username: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+    ) -> Result<OllamaRequest, FunboyError> {
+        let generated_prompt = self.generate(prompt, interpreter).await?.text;
+        let (prompt, settings) = match preset {
+            Some(preset) => apply_preset(preset, &generated_prompt, username, ollama_settings),
+            None => (generated_prompt.clone(), ollama_settings.clone()),
+        };
+        Ok(OllamaRequest {
+            generated_prompt,
+            prompt,
+            settings,
+        })
+    }
+
+    pub const MAX_PRESET_NAME_LENGTH: usize = 100;
+    pub const MAX_PRESET_BODY_LENGTH: usize = 4000;
+
+    /// Saves a prompt preset replacing any preset with the same name in scope
+    pub async fn save_prompt_preset(
+        &self,
+        name: &str,
+        scope: PresetScope,
+        body: &str,
+        mode: PresetMode,
+    ) -> Result<PromptPreset, FunboyError> {
+        if name.trim().is_empty() || name.chars().count() > Funboy::MAX_PRESET_NAME_LENGTH {
+            return Err(FunboyError::UserInput(UserFacingError::PresetNameInvalid {
+                limit: Funboy::MAX_PRESET_NAME_LENGTH,
+            }));
+        }
+
+        let length = body.chars().count();
+        if length > Funboy::MAX_PRESET_BODY_LENGTH {
+            return Err(FunboyError::UserInput(UserFacingError::PresetBodyTooLong {
+                length,
+                limit: Funboy::MAX_PRESET_BODY_LENGTH,
+            }));
+        }
+
+        if mode == PresetMode::Wrap && !body.contains(PROMPT_PLACEHOLDER) {
+            return Err(FunboyError::UserInput(
+                UserFacingError::PresetMissingPlaceholder {
+                    placeholder: PROMPT_PLACEHOLDER.to_string(),
+                },
+            ));
+        }
+
+        let preset = self.template_db.upsert_prompt_preset(
+            name,
+            scope.owner_id(),
+            body,
+            mode == PresetMode::Wrap,
+        );
+        let preset = preset.await?;
+        Ok(preset)
+    }
+
+    /// Lists the presets a user can use, their own and global presets
+    pub async fn get_prompt_presets(&self, user_id: u64) -> Result<Vec<PromptPreset>, FunboyError> {
+        let owner_ids = [
+            PresetScope::User(user_id).owner_id(),
+            PresetScope::Global.owner_id(),
+        ];
+        let presets = self.template_db.read_prompt_presets(&owner_ids);
+        let presets = presets.await?;
+        Ok(presets)
+    }
+
+    /// Finds the preset named name a user sees, their own before a global one
+    pub async fn get_prompt_preset(
+        &self,
+        name: &str,
+        user_id: u64,
+    ) -> Result<Option<PromptPreset>, FunboyError> {
+        let owner_ids = [
+            PresetScope::User(user_id).owner_id(),
+            PresetScope::Global.owner_id(),
+        ];
+        let presets = self
+            .template_db
+            .read_prompt_presets_by_name(name, &owner_ids);
+        let presets = presets.await?;
+        Ok(resolve_preset(presets, user_id))
+    }
+
+    pub async fn delete_prompt_preset(
+        &self,
+        name: &str,
+        scope: PresetScope,
+    ) -> Result<Option<PromptPreset>, FunboyError> {
+        let preset = self
+            .template_db
+            .delete_prompt_preset(name, scope.owner_id());
+        let preset = preset.await?;
+        Ok(preset)
+    }
+
+    /// Generates like [`Funboy::generate_ollama`] with the current model after applying the preset
+    /// a user sees as preset_name
+    pub async fn generate_ollama_with_preset(
+        &self,
+        preset_name: &str,
+        user_id: u64,
+        username: &str,
+        ollama_settings: &OllamaSettings,
+        prompt: &str,
+        interpreter: Arc<Mutex<FslInterpreter>>,
+    ) -> Result<GenerationResponse, FunboyError> {
+        let Some(preset) = self.get_prompt_preset(preset_name, user_id).await? else {
+            return Err(FunboyError::UserInput(UserFacingError::PresetNotFound {
+                name: preset_name.to_string(),
+            }));
+        };
+
+        let request = self
+            .prepare_ollama_request(
+                prompt,
+                ollama_settings,
+                Some(&preset),
+                username,
+                interpreter,
+            )
+            .await?;
+        let model = self.get_ollama_model().await;
+        match self
+            .ollama_generator
+            .generate(&request.prompt, &request.settings, model)
+            .await
+        {
+            Ok(output) => Ok(output),
+            Err(e) => Err(FunboyError::Ollama(e.to_string())),
+        }
+    }
+}
+
+/// Commands Funboy registers on every interpreter it generates with
+pub const FUNBOY_COMMAND_NAMES: &[&str] = &[
+    GET_SUB,
+    GET_SUB_OR,
+    GET_SUB_MERGED,
+    ASK_AI,
+    A_OR_AN,
+    PLURAL,
+    ORDINAL,
+    TITLE_CASE,
+    SNAKE_CASE,
+    CAMEL_CASE,
+    JSON_GET,
+    JSON_SET,
+    TO_JSON,
+    VARS,
+    HAS_STORED,
+    SEEDED_VAR,
+];
+
+/// Names templates cannot use since they would shadow an FSL command
+pub fn reserved_template_names(documentation: &CommandDocumentation) -> HashSet<String> {
+    documentation
+        .command_names()
+        .chain(FUNBOY_COMMAND_NAMES.iter().copied())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+const GET_SUB: &str = "get_sub";
+const GET_SUB_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), TEXT_TYPES)];
+fn create_get_sub_command(funboy: Arc<Funboy>) -> Executor {
+    let get_sub_command = {
+        move |command: Command, data: Arc<InterpreterData>| {
+            let funboy = funboy.clone();
+            async move {
+                let mut args = command.take_args();
+                let template = args.pop_front().unwrap().as_text(data).await?;
+                let template = backtick_template(GET_SUB, &template).await?;
+                let sub = funboy.get_random_substitute(template).await;
+                match sub {
+                    Ok(sub) => Ok(Value::Text(sub.name)),
+                    Err(e) => Err(CommandError::Custom(e.to_string())),
+                }
+            }
+        }
+    };
+    Some(Arc::new(get_sub_command))
+}
+
+/// The name in a template given to command in backticks
+///
+/// Backticks are required so renaming the template also rewrites the argument
+async fn backtick_template<'a>(command: &str, template: &'a str) -> Result<&'a str, CommandError> {
+    let regex = TemplateDelimiter::BACKTICK.to_regex().await;
+    if regex.is_match(template) {
+        Ok(template.trim_matches('`'))
+    } else {
+        Err(CommandError::Custom(format!(
+            "template name must be preceeded by a single ` (backtick)\nThis ensures if the template is renamed this {} will not be invalid",
+            command
+        )))
+    }
+}
+
+/// Rules of commands taking a template followed by substitutes written inline
+const GET_SUB_INLINE_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(1), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(2), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(3), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(4), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(5), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(6), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(7), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(8), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(9), TEXT_TYPES),
+];
+
+/// Takes the template and inline substitutes of a command using [`GET_SUB_INLINE_RULES`]
+async fn take_inline_substitutes(
+    name: &str,
+    command: Command,
+    data: Arc<InterpreterData>,
+) -> Result<(String, Vec<String>), CommandError> {
+    let mut args = command.take_args();
+    let template = args.pop_front().unwrap().as_text(data.clone()).await?;
+    let template = backtick_template(name, &template).await?.to_string();
+    let mut inline = Vec::with_capacity(args.len());
+    for arg in args {
+        inline.push(arg.as_text(data.clone()).await?);
+    }
+    Ok((template, inline))
+}
+
+const GET_SUB_OR: &str = "get_sub_or";
+fn create_get_sub_or_command(funboy: Arc<Funboy>) -> Executor {
+    let get_sub_or_command = {
+        move |command: Command, data: Arc<InterpreterData>| {
+            let funboy = funboy.clone();
+            async move {
+                let (template, fallbacks) =
+                    take_inline_substitutes(GET_SUB_OR, command, data).await?;
+                match funboy.get_substitute_or(&template, &fallbacks).await {
+                    Ok(sub) => Ok(Value::Text(sub)),
+                    Err(e) => Err(CommandError::Custom(e.to_string())),
+                }
+            }
+        }
+    };
+    Some(Arc::new(get_sub_or_command))
+}
+
+const GET_SUB_MERGED: &str = "get_sub_merged";
+fn create_get_sub_merged_command(funboy: Arc<Funboy>) -> Executor {
+    let get_sub_merged_command = {
+        move |command: Command, data: Arc<InterpreterData>| {
+            let funboy = funboy.clone();
+            async move {
+                let (template, extras) =
+                    take_inline_substitutes(GET_SUB_MERGED, command, data).await?;
+                match funboy.get_substitute_merged(&template, &extras).await {
+                    Ok(sub) => Ok(Value::Text(sub)),
+                    Err(e) => Err(CommandError::Custom(e.to_string())),
+                }
+            }
+        }
+    };
+    Some(Arc::new(get_sub_merged_command))
+}
+
+/// Returns the value of a var passed to [`Funboy::generate_with_vars`] by its position
+///
+/// Each value can only be taken once so the seed is the only code able to store reserved vars
+const SEEDED_VAR: &str = "seeded_var";
+const SEEDED_VAR_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), WHOLE_NUMBER_TYPES)];
+fn create_seeded_var_command(values: Arc<std::sync::Mutex<Vec<Option<String>>>>) -> Executor {
+    let seeded_var_command = {
+        move |command: Command, data: Arc<InterpreterData>| {
+            let values = values.clone();
+            async move {
+                let mut args = command.take_args();
+                let index = args.pop_front().unwrap().as_int(data).await?;
+                let value = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| values.lock().unwrap().get_mut(index).and_then(Option::take));
+                match value {
+                    Some(value) => Ok(Value::Text(value)),
+                    None => Err(CommandError::Custom(format!(
+                        "no var was seeded at position {}",
+                        index
+                    ))),
+                }
+            }
+        }
+    };
+    Some(Arc::new(seeded_var_command))
+}
+
+const ASK_AI: &str = "ask_ai";
+#[cfg(feature = "ollama")]
+const ASK_AI_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(1), WHOLE_NUMBER_TYPES),
+];
+#[cfg(feature = "ollama")]
+const MAX_WORD_LIMIT: i64 = 500;
+#[cfg(feature = "ollama")]
+fn create_ask_ai_command(funboy: Arc<Funboy>) -> Executor {
+    let get_sub_command = {
+        move |command: Command, data: Arc<InterpreterData>| {
+            let funboy = funboy.clone();
+            async move {
+                let mut args = command.take_args();
+                let prompt = args.pop_front().unwrap().as_text(data.clone()).await?;
+
+                let word_limit = args.pop_front().unwrap().as_int(data).await?;
+                if word_limit <= 0 {
+                    return Err(CommandError::Custom(
+                        "word limit must be greater than zero".to_string(),
+                    ));
+                } else if word_limit > MAX_WORD_LIMIT {
+                    return Err(CommandError::Custom(format!(
+                        "word limit cannot be greater than {}",
+                        MAX_WORD_LIMIT
+                    )));
+                }
+
+                let mut ollama_settings = OllamaSettings::default();
+                ollama_settings.set_output_limit(word_limit as u16);
+                let ollama_generator = OllamaGenerator::default();
+                let model = funboy.get_ollama_model().await;
+
+                let response = ollama_generator
+                    .generate(&prompt, &ollama_settings, model)
+                    .await;
+                match response {
+                    Ok(response) => {
+                        let response = response.response;
+                        Ok(Value::Text(response))
+                    }
+                    Err(e) => Err(CommandError::Custom(e.to_string())),
+                }
+            }
+        }
+    };
+    Some(Arc::new(get_sub_command))
+}
+
+const A_OR_AN: &str = "a_or_an";
+const A_OR_AN_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), TEXT_TYPES)];
+fn create_a_or_an_command() -> Executor {
+    let a_or_an_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let noun = args.pop_front().unwrap().as_text(data).await?;
+            Ok(Value::Text(a_or_an(&noun).to_string()))
+        }
+    };
+    Some(Arc::new(a_or_an_command))
+}
+
+const PLURAL: &str = "plural";
+const PLURAL_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), NUMERIC_TYPES),
+    ArgRule::new(ArgPos::Index(1), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(2), TEXT_TYPES),
+];
+fn create_plural_command() -> Executor {
+    let plural_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let count = args.pop_front().unwrap().as_float(data.clone()).await?;
+            let singular = args.pop_front().unwrap().as_text(data.clone()).await?;
+            let plural_form = match args.pop_front() {
+                Some(plural_form) => Some(plural_form.as_text(data).await?),
+                None => None,
+            };
+            Ok(Value::Text(plural(
+                count,
+                &singular,
+                plural_form.as_deref(),
+            )))
+        }
+    };
+    Some(Arc::new(plural_command))
+}
+
+const ORDINAL: &str = "ordinal";
+const ORDINAL_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), WHOLE_NUMBER_TYPES)];
+fn create_ordinal_command() -> Executor {
+    let ordinal_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let n = args.pop_front().unwrap().as_int(data).await?;
+            Ok(Value::Text(ordinal(n)))
+        }
+    };
+    Some(Arc::new(ordinal_command))
+}
+
+const TITLE_CASE: &str = "title_case";
+const TITLE_CASE_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(1), TEXT_TYPES),
+];
+fn create_title_case_command() -> Executor {
+    let title_case_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let text = args.pop_front().unwrap().as_text(data.clone()).await?;
+            let headline = match args.pop_front() {
+                Some(headline) => headline.as_text(data).await? == "true",
+                None => false,
+            };
+            Ok(Value::Text(title_case(&text, headline)))
+        }
+    };
+    Some(Arc::new(title_case_command))
+}
+
+const SNAKE_CASE: &str = "snake_case";
+const SNAKE_CASE_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), TEXT_TYPES)];
+fn create_snake_case_command() -> Executor {
+    let snake_case_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let text = args.pop_front().unwrap().as_text(data).await?;
+            Ok(Value::Text(snake_case(&text)))
+        }
+    };
+    Some(Arc::new(snake_case_command))
+}
+
+const CAMEL_CASE: &str = "camel_case";
+const CAMEL_CASE_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), TEXT_TYPES)];
+fn create_camel_case_command() -> Executor {
+    let camel_case_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let text = args.pop_front().unwrap().as_text(data).await?;
+            Ok(Value::Text(camel_case(&text)))
+        }
+    };
+    Some(Arc::new(camel_case_command))
+}
+
+/// Replaces the builtin capitalize which slices the first byte and panics on multi-byte characters
+const CAPITALIZE: &str = "capitalize";
+const CAPITALIZE_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), TEXT_TYPES)];
+fn create_capitalize_command() -> Executor {
+    let capitalize_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let text = args.pop_front().unwrap().as_text(data).await?;
+            Ok(Value::Text(capitalize(&text)))
+        }
+    };
+    Some(Arc::new(capitalize_command))
+}
+
+/// Reads a numeric argument keeping whether it is an Int or a Float
+async fn take_number(arg: Value, data: Arc<InterpreterData>) -> Result<Number, CommandError> {
+    match arg {
+        Value::Int(int) => Ok(Number::Int(int)),
+        Value::Float(float) => Ok(Number::Float(float)),
+        arg => {
+            let text = arg.as_text(data).await?;
+            Number::parse(&text)
+                .ok_or_else(|| CommandError::Custom(format!("{} is not a number", text)))
+        }
+    }
+}
+
+/// Reads an argument of [`EQ`], numbers are compared by value and everything else as text
+async fn take_operand(arg: Value, data: Arc<InterpreterData>) -> Result<Operand, CommandError> {
+    match arg {
+        Value::Int(int) => Ok(Operand::Number(Number::Int(int))),
+        Value::Float(float) => Ok(Operand::Number(Number::Float(float))),
+        Value::Text(text) => Ok(Operand::Text(text)),
+        Value::Bool(bool) => Ok(Operand::Text(bool.to_string())),
+        arg => {
+            let text = arg.as_text(data).await?;
+            Ok(Number::parse(&text).map_or(Operand::Text(text), Operand::Number))
+        }
+    }
+}
+
+fn number_to_value(number: Number) -> Value {
+    match number {
+        Number::Int(int) => Value::Int(int),
+        Number::Float(float) => Value::Float(float),
+    }
+}
+
+fn checked_math_error(error: UserFacingError) -> CommandError {
+    CommandError::Custom(error.to_string())
+}
+
+/// Rules of the checked arithmetic commands taking two or more numbers, like the builtins any
+/// number of arguments may follow and [`take_number`] rejects those that aren't numbers
+const CHECKED_MATH_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), NUMERIC_TYPES),
+    ArgRule::new(ArgPos::Index(1), NUMERIC_TYPES),
+];
+
+async fn take_numbers(
+    command: Command,
+    data: Arc<InterpreterData>,
+) -> Result<Vec<Number>, CommandError> {
+    let args = command.take_args();
+    let mut numbers = Vec::with_capacity(args.len());
+    for arg in args {
+        numbers.push(take_number(arg, data.clone()).await?);
+    }
+    Ok(numbers)
+}
+
+/// Replaces the builtin div which lets a zero divisor turn Floats into inf and NaN
+const DIV: &str = "div";
+fn create_div_command() -> Executor {
+    let div_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let numbers = take_numbers(command, data).await?;
+            let quotient = checked_math::divide(DIV, &numbers).map_err(checked_math_error)?;
+            Ok(number_to_value(quotient))
+        }
+    };
+    Some(Arc::new(div_command))
+}
+
+/// Replaces the builtin mod which lets a zero divisor turn Floats into NaN
+const MOD: &str = "mod";
+fn create_mod_command() -> Executor {
+    let mod_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let numbers = take_numbers(command, data).await?;
+            let remainder = checked_math::modulo(MOD, &numbers).map_err(checked_math_error)?;
+            Ok(number_to_value(remainder))
+        }
+    };
+    Some(Arc::new(mod_command))
+}
+
+/// Replaces the builtin eq which is silently false whenever NaN is compared
+const EQ: &str = "eq";
+const EQ_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(1), TEXT_TYPES),
+];
+fn create_eq_command() -> Executor {
+    let eq_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let lhs = take_operand(args.pop_front().unwrap(), data.clone()).await?;
+            let rhs = take_operand(args.pop_front().unwrap(), data).await?;
+            let equal = checked_math::equals(&lhs, &rhs).map_err(checked_math_error)?;
+            Ok(Value::Bool(equal))
+        }
+    };
+    Some(Arc::new(eq_command))
+}
+
+/// Replaces the builtin random_range which panics on reversed or equal bounds
+const RANDOM_RANGE: &str = "random_range";
+const RANDOM_RANGE_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), NUMERIC_TYPES),
+    ArgRule::new(ArgPos::Index(1), NUMERIC_TYPES),
+];
+fn create_random_range_command() -> Executor {
+    let random_range_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let min = take_number(args.pop_front().unwrap(), data.clone()).await?;
+            let max = take_number(args.pop_front().unwrap(), data).await?;
+            let number = checked_math::random_in_range(min, max).map_err(checked_math_error)?;
+            Ok(number_to_value(number))
+        }
+    };
+    Some(Arc::new(random_range_command))
+}
+
+/// Longest JSON text the json commands read or write
+pub const MAX_JSON_LENGTH: usize = Funboy::MAX_SUBSTITUTE_LENGTH;
+
+fn json_error(error: json_path::JsonError) -> CommandError {
+    CommandError::Custom(error.to_string())
+}
+
+/// Converts JSON to the closest interpreter value, objects and arrays stay JSON text
+fn json_to_value(json: &serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => Value::None,
+        serde_json::Value::Bool(bool) => Value::Bool(*bool),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(int) => Value::Int(int),
+            None => Value::Float(number.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(text) => Value::Text(text.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::None => serde_json::Value::Null,
+        Value::Text(text) => serde_json::Value::String(text.clone()),
+        Value::Int(int) => serde_json::Value::from(*int),
+        Value::Float(float) => serde_json::Value::from(*float),
+        Value::Bool(bool) => serde_json::Value::Bool(*bool),
+        Value::List(items) => items.iter().map(value_to_json).collect(),
+        other => serde_json::Value::String(format!("{:?}", other)),
+    }
+}
+
+const JSON_GET: &str = "json_get";
+const JSON_GET_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(1), TEXT_TYPES),
+];
+fn create_json_get_command() -> Executor {
+    let json_get_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let path = args.pop_front().unwrap().as_text(data.clone()).await?;
+            let text = args.pop_front().unwrap().as_text(data).await?;
+            let path = json_path::parse_path(&path).map_err(json_error)?;
+            let json = json_path::parse(&text, MAX_JSON_LENGTH).map_err(json_error)?;
+            let value = json_path::get(&json, &path).map_err(json_error)?;
+            Ok(json_to_value(value))
+        }
+    };
+    Some(Arc::new(json_get_command))
+}
+
+const JSON_SET: &str = "json_set";
+const JSON_SET_RULES: &[ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(1), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(2), TEXT_TYPES),
+];
+fn create_json_set_command() -> Executor {
+    let json_set_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let path = args.pop_front().unwrap().as_text(data.clone()).await?;
+            let value = args.pop_front().unwrap().as_text(data.clone()).await?;
+            let text = args.pop_front().unwrap().as_text(data).await?;
+            let path = json_path::parse_path(&path).map_err(json_error)?;
+            let mut json = json_path::parse(&text, MAX_JSON_LENGTH).map_err(json_error)?;
+            json_path::set(&mut json, &path, json_path::parse_value(&value)).map_err(json_error)?;
+            Ok(Value::Text(
+                json_path::to_text(&json, MAX_JSON_LENGTH).map_err(json_error)?,
+            ))
+        }
+    };
+    Some(Arc::new(json_set_command))
+}
+
+/// FSL has no maps so only lists are serialized
+const TO_JSON: &str = "to_json";
+const TO_JSON_RULES: &[ArgRule] = &[ArgRule::new(ArgPos::Index(0), INDEX_TYPES)];
+fn create_to_json_command() -> Executor {
+    let to_json_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let list = args.pop_front().unwrap().as_list(data).await?;
+            let json = list.iter().map(value_to_json).collect();
+            Ok(Value::Text(
+                json_path::to_text(&json, MAX_JSON_LENGTH).map_err(json_error)?,
+            ))
+        }
+    };
+    Some(Arc::new(to_json_command))
+}
+
+/// Name of the type of value as the command documentation writes it
+fn value_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::None => "None",
+        Value::Text(_) => "Text",
+        Value::Int(_) => "Int",
+        Value::Float(_) => "Float",
+        Value::Bool(_) => "Bool",
+        Value::List(_) => "List",
+        _ => "Command",
+    }
+}
+
+/// Lists the vars defined in the interpreter's var map when it runs with the type of each,
+/// sorted by name so the output doesn't depend on the order of the map
+const VARS: &str = "vars";
+const VARS_RULES: &[ArgRule] = &[];
+fn create_vars_command() -> Executor {
+    let vars_command = {
+        move |_command: Command, data: Arc<InterpreterData>| async move {
+            let mut vars = data.vars.entries().await;
+            vars.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let vars: Vec<String> = vars
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value_type_name(value)))
+                .collect();
+            Ok(Value::Text(vars.join(", ")))
+        }
+    };
+    Some(Arc::new(vars_command))
+}
+
+/// The name of the var an argument of clone() refers to
+fn var_name(arg: &Value) -> Result<String, CommandError> {
+    match arg {
+        Value::Identifier(name) | Value::Text(name) => Ok(name.clone()),
+        other => Err(CommandError::Custom(format!(
+            "{} is not the name of a var",
+            value_to_log_string(other)
+        ))),
+    }
+}
+
+/// Replaces the interpreter's clone so reading something that was never stored fails where it
+/// is read instead of handing None to the next command
+///
+/// Both the copy buffer and the var map are read when the command runs, so a store only counts
+/// once it has run
+const CLONE: &str = "clone";
+const CLONE_RULES: &[ArgRule] = &[];
+fn create_clone_command() -> Executor {
+    let clone_command = {
+        move |command: Command, data: Arc<InterpreterData>| async move {
+            let mut args = command.take_args();
+            let Some(arg) = args.pop_front() else {
+                return data.copy_buffer().await.ok_or_else(|| {
+                    CommandError::Custom(UserFacingError::NothingStored.to_string())
+                });
+            };
+
+            let name = var_name(&arg)?;
+            if let Some(value) = data.vars.get_value(&name).await {
+                return Ok(value);
+            }
+            let vars = data.vars.entries().await;
+            let suggestions =
+                edit_distance::similar_names(&name, vars.iter().map(|(name, _)| name.as_str()))
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect();
+            Err(CommandError::Custom(
+                UserFacingError::VariableNotFound { name, suggestions }.to_string(),
+            ))
+        }
+    };
+    Some(Arc::new(clone_command))
+}
+
+/// Whether a value was stored without a name for clone() to read by the time it runs
+const HAS_STORED: &str = "has_stored";
+const HAS_STORED_RULES: &[ArgRule] = &[];
+fn create_has_stored_command() -> Executor {
+    let has_stored_command = {
+        move |_command: Command, data: Arc<InterpreterData>| async move {
+            Ok(Value::Bool(data.copy_buffer().await.is_some()))
+        }
+    };
+    Some(Arc::new(has_stored_command))
+}
+
+#[cfg(test)]
+mod core {
+    use super::*;
+    use embedded_code::{CodeBlockError, CodeSyntaxError};
+    use std::panic;
+    use template_database::{
+        DbPool,
+        test::{connect_debug_pool, create_debug_db},
+    };
+    use template_substitutor::ExpansionError;
+
+    #[tokio::test]
+    async fn random_number_produces_int_in_range() {
+        for _ in 0..100 {
+            let result = Funboy::random_number("1", "6", true)
+                .unwrap()
+                .parse::<i64>()
+                .unwrap();
+            assert!((1..=6).contains(&result), "output outside of range");
+        }
+    }
+
+    #[tokio::test]
+    async fn random_number_produces_float() {
+        for _ in 0..100 {
+            let result = Funboy::random_number("1.0", "6.0", true)
+                .unwrap()
+                .parse::<f64>()
+                .unwrap();
+            assert!((1.0..=6.0).contains(&result), "output outside of range");
+        }
+    }
+
+    #[tokio::test]
+    async fn random_number_fails_when_min_greater_than_max() {
+        match Funboy::random_number("6", "1", true) {
+            Ok(_) => {
+                panic!("Value should not be Ok");
+            }
+            Err(e) => {
+                assert!(
+                    matches!(e, FunboyError::UserInput(_)),
+                    "error was not UserInput variant"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn random_number_fails_when_min_equal_to_max() {
+        match Funboy::random_number("6", "6", true) {
+            Ok(_) => {
+                panic!("Value should not be Ok");
+            }
+            Err(e) => {
+                assert!(
+                    matches!(e, FunboyError::UserInput(_)),
+                    "error was not UserInput variant"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn random_number_float_bounds_are_checked() {
+        for (min, max) in [("6.0", "1.0"), ("1.0", "1.0")] {
+            assert!(
+                Funboy::random_number(min, max, true).is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::MinNotLessThanMax)
+                )),
+                "{} {}",
+                min,
+                max
+            );
+        }
+
+        for (min, max) in [
+            ("1.0", "inf"),
+            ("-inf", "1.0"),
+            ("NaN", "1.0"),
+            ("1.0", "NaN"),
+            ("-1.7e308", "1.7e308"),
+        ] {
+            for inclusive in [true, false] {
+                assert!(
+                    Funboy::random_number(min, max, inclusive).is_err_and(|e| matches!(
+                        e,
+                        FunboyError::UserInput(UserFacingError::RangeNotFinite)
+                    )),
+                    "{} {}",
+                    min,
+                    max
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn random_entry_returns_correct_output() {
+        let result = Funboy::random_entry(&["one", "two", "three", "four"]).unwrap();
+
+        if !(&["one", "two", "three", "four"].contains(&result)) {
+            panic!("array should contain result");
+        }
+    }
+
+    #[tokio::test]
+    async fn random_entry_fails_with_less_than_two_entries() {
+        match Funboy::random_entry(&["one"]) {
+            Ok(_) => {
+                panic!("Value should not be Ok");
+            }
+            Err(e) => {
+                assert!(
+                    matches!(e, FunboyError::UserInput(_)),
+                    "error was not UserInput variant"
+                );
+            }
+        }
+    }
+
+    async fn get_pool() -> DbPool {
+        connect_debug_pool().await
+    }
+
+    async fn get_funboy(pool: DbPool) -> Funboy {
+        let db = create_debug_db(pool).await.unwrap();
+        Funboy::new(db)
+    }
+
+    #[tokio::test]
+    async fn generate_templates() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let output = funboy
+            .generate("^sentence", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+
+        assert!(output == "^sentence");
+        println!("OUTPUT: {}", output);
+
+        funboy
+            .add_substitutes(
+                "sentence",
+                &["A ^gtadj brown ^gtnoun ^gtverb^ed over the lazy dog."],
+            )
+            .await
+            .unwrap();
+
+        funboy.add_substitutes("gtadj", &["quick"]).await.unwrap();
+        funboy.add_substitutes("gtnoun", &["fox"]).await.unwrap();
+        funboy.add_substitutes("gtverb", &["jump"]).await.unwrap();
+
+        let output = funboy
+            .generate("^sentence", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+
+        println!("OUTPUT: {}", output);
+        assert!(output == "A quick brown fox jumped over the lazy dog.");
+    }
+
+    const CODE_SUBSTITUTES: [&str; 3] = [
+        "{print(\"fine\")}",
+        "{repeat(5, print(\"x\")}",
+        "{repeat(5, print(",
+    ];
+
+    fn warned_substitutes(receipt: &SubstituteReceipt) -> Vec<&str> {
+        receipt
+            .warnings
+            .iter()
+            .map(|warning| warning.substitute.as_str())
+            .collect()
+    }
+
+    fn ignored_kinds(receipt: &SubstituteReceipt) -> Vec<(&str, &str)> {
+        receipt
+            .ignored
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.reason.kind()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn invalid_code_warns_when_added() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy
+            .add_substitutes("code", &CODE_SUBSTITUTES)
+            .await
+            .unwrap();
+
+        assert!(receipt.updated.len() == 3);
+        assert!(warned_substitutes(&receipt) == CODE_SUBSTITUTES[1..]);
+        assert!(matches!(
+            receipt.warnings[0].error,
+            CodeSyntaxError::UnclosedArgs { offset: 7, .. }
+        ));
+        assert!(matches!(
+            receipt.warnings[1].error,
+            CodeSyntaxError::Block(CodeBlockError::Unclosed { offset: 0 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_code_is_rejected_when_strict() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy
+            .add_substitutes_with_validation("code", &CODE_SUBSTITUTES, CodeValidation::Strict)
+            .await
+            .unwrap();
+
+        assert!(receipt.updated.len() == 1);
+        assert!(receipt.updated[0].name == CODE_SUBSTITUTES[0]);
+        assert!(warned_substitutes(&receipt) == CODE_SUBSTITUTES[1..]);
+        assert!(receipt.ignored.len() == CODE_SUBSTITUTES.len() - 1);
+        for (entry, warning) in receipt.ignored.iter().zip(&receipt.warnings) {
+            assert!(entry.name == warning.substitute);
+            assert!(
+                entry.reason
+                    == IgnoreReason::ValidationFailed {
+                        detail: warning.error.to_string()
+                    }
+            );
+        }
+
+        let substitutes = funboy
+            .get_substitutes("code", None, None, OrderBy::Default, Limit::None)
+            .await
+            .unwrap();
+        assert!(substitutes.len() == 1);
+    }
+
+    #[tokio::test]
+    async fn custom_delimiter_generates_and_renames() {
+        let pool = get_pool().await;
+        let percent = TemplateDelimiter::new('%', Some('%')).unwrap();
+        let funboy = get_funboy(pool).await.with_delimiter(percent).unwrap();
+
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        funboy.add_substitutes("adj", &["quick"]).await.unwrap();
+        funboy
+            .add_substitutes("sentence", &["the %adj% ^noun and %noun%"])
+            .await
+            .unwrap();
+
+        let output = funboy
+            .generate("%sentence", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "the quick fox and fox");
+
+        let preview = funboy.preview_rename("noun", "animal").await.unwrap();
+        let delimiters: Vec<char> = preview
+            .by_delimiter()
+            .iter()
+            .map(|(delimiter, _)| delimiter.to_char())
+            .collect();
+        assert!(delimiters == ['^', '%']);
+
+        funboy.rename_template("noun", "animal").await.unwrap();
+        let substitutes = funboy
+            .get_substitutes("sentence", None, None, OrderBy::Default, Limit::None)
+            .await
+            .unwrap();
+        assert!(substitutes[0].name == "the %adj% ^animal and %animal%");
+    }
+
+    #[tokio::test]
+    async fn generate_with_vars_can_clone_seeded_vars() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let output = funboy
+            .generate_with_vars(
+                "{print(clone(name))} said {print(clone(quote))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+                &[("name", "Jane"), ("quote", "\"hi\", then left)")],
+            )
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "Jane said \"hi\", then left)");
+    }
+
+    #[tokio::test]
+    async fn vars_are_listed_by_name_with_their_types() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let entry = get_command_documentation().get(VARS).unwrap();
+        for example in &entry.examples {
+            let (input, expected) = example.split_once(" = ").unwrap();
+            let output = funboy
+                .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await
+                .unwrap()
+                .text;
+            assert_eq!(output, expected.trim(), "{}", example);
+        }
+
+        let input = "{store(1, zebra) store(\"a\", apple) store(2.5, zebra) print(vars())}";
+        let first = funboy
+            .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(first, "apple: Text, zebra: Float");
+        for _ in 0..5 {
+            let output = funboy
+                .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await
+                .unwrap()
+                .text;
+            assert_eq!(output, first);
+        }
+
+        // Only stores that have run are listed
+        let output = funboy
+            .generate(
+                "{print(vars()) store(1, x) repeat(0, store(2, never)) print(\"|\", vars())}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(output, "|x: Int");
+
+        let output = funboy
+            .generate_with_vars(
+                "{store(1, 2, middle) print(vars())}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+                &[("zed", "z"), ("ant", "a")],
+            )
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(output, "ant: Text, middle: List, zed: Text");
+    }
+
+    #[tokio::test]
+    async fn clone_of_nothing_stored_is_rejected() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let cases = [
+            (
+                "{store(1, x) print(clone())}",
+                UserFacingError::NothingStored.to_string(),
+            ),
+            (
+                "{store(1, number) store(2, numbers) store(3, other) print(clone(numbr))}",
+                UserFacingError::VariableNotFound {
+                    name: "numbr".to_string(),
+                    suggestions: vec!["number".to_string(), "numbers".to_string()],
+                }
+                .to_string(),
+            ),
+            // Reading a var before the store that defines it runs is as empty as never storing it
+            (
+                "{print(clone(x)) store(1, x)}",
+                UserFacingError::VariableNotFound {
+                    name: "x".to_string(),
+                    suggestions: Vec::new(),
+                }
+                .to_string(),
+            ),
+            (
+                "{repeat(0, store(1)) print(clone())}",
+                UserFacingError::NothingStored.to_string(),
+            ),
+        ];
+        for (input, message) in cases {
+            let result = funboy
+                .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await;
+            assert!(
+                result.is_err_and(
+                    |e| matches!(e, FunboyError::Interpreter(error) if error.contains(&message))
+                ),
+                "{}",
+                input
+            );
+        }
+
+        let output = funboy
+            .generate(
+                "{repeat(2, store(\"a\")) store(1, x) print(clone(), clone(x))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(output, "a1");
+    }
+
+    #[tokio::test]
+    async fn has_stored_reports_the_copy_buffer() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let entry = get_command_documentation().get(HAS_STORED).unwrap();
+        for example in &entry.examples {
+            let (input, expected) = example.split_once(" = ").unwrap();
+            let output = funboy
+                .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await
+                .unwrap()
+                .text;
+            assert_eq!(output, expected.trim(), "{}", example);
+        }
+
+        // Only a store that has run fills the copy buffer
+        let output = funboy
+            .generate(
+                "{print(has_stored()) store(1, x) print(has_stored()) store(1) print(has_stored())}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(output, "falsefalsetrue");
+    }
+
+    #[tokio::test]
+    async fn featured_templates_are_not_repeated_within_window() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        assert!(
+            funboy
+                .pick_featured_template(1, FeaturedStrategy::Random)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        for template in ["animal", "color", "verb"] {
+            funboy.add_substitutes(template, &["word"]).await.unwrap();
+        }
+        // each strategy features in its own guild so their histories don't mix
+        for (guild_id, strategy) in [
+            (1, FeaturedStrategy::Random),
+            (2, FeaturedStrategy::LeastRecentlyFeatured),
+            (3, FeaturedStrategy::MostUsed),
+        ] {
+            let mut featured = Vec::new();
+            for _ in 0..3 {
+                let template = funboy
+                    .pick_featured_template(guild_id, strategy)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert!(template.substitute_count == 1);
+                featured.push(template.name);
+            }
+            featured.sort();
+            assert!(featured == ["animal", "color", "verb"], "{}", strategy);
+
+            // once every template is recent one is still picked
+            assert!(
+                funboy
+                    .pick_featured_template(guild_id, strategy)
+                    .await
+                    .unwrap()
+                    .is_some()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn reserved_vars_can_be_seeded_but_not_stored() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        let vars = [("ctx_author", "Jane")];
+
+        let output = funboy
+            .generate_with_vars(
+                "hi {print(clone(ctx_author))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+                &vars,
+            )
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "hi Jane");
+
+        for input in [
+            "{store(\"Bob\", ctx_author) print(clone(ctx_author))}",
+            "{store(1, 2, \"ctx_list\")}",
+            "{store(seeded_var(0), ctx_author)}",
+        ] {
+            assert!(
+                funboy
+                    .generate_with_vars(input, Arc::new(Mutex::new(FslInterpreter::new())), &vars)
+                    .await
+                    .is_err(),
+                "{} should be rejected",
+                input
+            );
+        }
+        assert!(
+            funboy
+                .generate(
+                    "{store(\"Bob\", ctx_author)}",
+                    Arc::new(Mutex::new(FslInterpreter::new())),
+                )
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::VariableReserved { .. })
+                ))
+        );
+        assert!(
+            Funboy::validate_user_variable_name("ctx_author")
+                .is_err_and(|e| matches!(e, UserFacingError::VariableReserved { .. }))
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_with_vars_rejects_invalid_names() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        for name in ["", "1st", "Name", "a b", "x) print(y"] {
+            assert!(
+                funboy
+                    .generate_with_vars(
+                        "text",
+                        Arc::new(Mutex::new(FslInterpreter::new())),
+                        &[(name, "value")],
+                    )
+                    .await
+                    .is_err_and(|e| matches!(
+                        e,
+                        FunboyError::UserInput(UserFacingError::VariableNameInvalid { .. })
+                    )),
+                "{} should be rejected",
+                name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn colliding_delimiters_are_rejected() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let backtick = TemplateDelimiter::new('`', None).unwrap();
+        assert!(
+            funboy
+                .clone()
+                .with_delimiter(backtick)
+                .is_err_and(|e| e == DelimiterError::AlreadyRegistered { delimiter: '`' })
+        );
+
+        let percent = TemplateDelimiter::new('%', Some('%')).unwrap();
+        let funboy = funboy.with_delimiter(percent).unwrap();
+        assert!(funboy.with_delimiter(percent).is_err());
+    }
+
+    #[tokio::test]
+    async fn bulk_delete_needs_matching_token() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy.add_substitutes("temp_one", &["a"]).await.unwrap();
+        funboy.create_template("temp_two").await.unwrap();
+        funboy.add_substitutes("keep", &["a"]).await.unwrap();
+
+        let filter = TemplateFilter {
+            name_glob: Some("temp_*".to_string()),
+            ..Default::default()
+        };
+
+        let BulkOutcome::Preview(preview) = funboy
+            .delete_templates_matching(&filter, None)
+            .await
+            .unwrap()
+        else {
+            panic!("first call must only preview");
+        };
+        assert!(preview.templates.len() == 2);
+        assert!(funboy.select_templates(&filter).await.unwrap().len() == 2);
+
+        // a token for one operation can't confirm another
+        assert!(
+            funboy
+                .archive_templates_matching(&filter, Some(&preview.token))
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::BulkTokenMismatch)
+                ))
+        );
+
+        // the token is stale once another template starts matching
+        funboy.create_template("temp_three").await.unwrap();
+        assert!(
+            funboy
+                .delete_templates_matching(&filter, Some(&preview.token))
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::BulkTokenMismatch)
+                ))
+        );
+
+        let BulkOutcome::Preview(preview) = funboy
+            .delete_templates_matching(&filter, None)
+            .await
+            .unwrap()
+        else {
+            panic!("first call must only preview");
+        };
+        let BulkOutcome::Applied(deleted) = funboy
+            .delete_templates_matching(&filter, Some(&preview.token))
+            .await
+            .unwrap()
+        else {
+            panic!("confirmed call must apply");
+        };
+        assert!(deleted.len() == 3);
+        assert!(funboy.select_templates(&filter).await.unwrap().is_empty());
+        assert!(
+            funboy
+                .get_templates(None, OrderBy::Default, Limit::None)
+                .await
+                .unwrap()
+                .len()
+                == 1
+        );
+    }
+
+    #[tokio::test]
+    async fn bulk_operations_need_a_filter() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        assert!(
+            funboy
+                .archive_templates_matching(&TemplateFilter::default(), None)
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::BulkFilterEmpty)
+                ))
+        );
+    }
+
+    #[tokio::test]
+    async fn public_browse_hides_archived_and_disabled() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool.clone()).await;
+        // Shares the database but none of funboy's in-process caches, like another process would
+        let other = Funboy::new(TemplateDatabase::new(Arc::new(pool)));
+
+        let noun = funboy
+            .add_substitutes("noun", &["fox", "<dog>", "cat"])
+            .await
+            .unwrap();
+        funboy
+            .set_substitute_enabled(noun.updated[2].id, false)
+            .await
+            .unwrap();
+        funboy.add_substitutes("secret", &["hidden"]).await.unwrap();
+        funboy.create_template("empty").await.unwrap();
+
+        let listing_etag = funboy.public_listing_etag().await.unwrap();
+        let filter = TemplateFilter {
+            name_glob: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let BulkOutcome::Preview(preview) = funboy
+            .archive_templates_matching(&filter, None)
+            .await
+            .unwrap()
+        else {
+            panic!("first call must only preview");
+        };
+        funboy
+            .archive_templates_matching(&filter, Some(&preview.token))
+            .await
+            .unwrap();
+        let current_etag = funboy.public_listing_etag().await.unwrap();
+        assert!(current_etag != listing_etag);
+        assert!(other.public_listing_etag().await.unwrap() == current_etag);
+
+        let templates = funboy.public_templates().await.unwrap();
+        assert!(
+            templates
+                == vec![
+                    PublicTemplate {
+                        name: "empty".to_string(),
+                        substitute_count: 0
+                    },
+                    PublicTemplate {
+                        name: "noun".to_string(),
+                        substitute_count: 2
+                    },
+                ]
+        );
+        let html = public_browse::render_template_list(&templates);
+        assert!(html.contains("/public/templates/noun"));
+        assert!(!html.contains("secret"));
+
+        let page = funboy
+            .public_template_page("noun", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(page.substitutes == vec!["fox", "<dog>"]);
+        assert!(page.page_count == 1);
+        let html = public_browse::render_template_page(&page);
+        assert!(html.contains("<li>&lt;dog&gt;</li>"));
+        assert!(!html.contains("cat"));
+
+        assert!(
+            funboy
+                .public_template_page("noun", 0)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            funboy
+                .public_template_page("noun", 2)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            funboy
+                .public_template_page("secret", 1)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            funboy
+                .public_template_page("missing", 1)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            funboy
+                .public_template_page("../noun", 1)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let empty = funboy
+            .public_template_page("empty", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(empty.substitutes.is_empty());
+
+        // A full page spills the rest onto the next one
+        let many: Vec<String> = (0..Funboy::PUBLIC_PAGE_SIZE)
+            .map(|i| format!("word{}", i))
+            .collect();
+        let many: Vec<&str> = many.iter().map(String::as_str).collect();
+        let page_etag = funboy.public_template_etag("noun", 2).await.unwrap();
+        let version = funboy.template_db.read_template_version("noun").await;
+        funboy.add_substitutes("noun", &many).await.unwrap();
+        let current_etag = funboy.public_template_etag("noun", 2).await.unwrap();
+        assert!(current_etag != page_etag);
+        // The whole batch is one bump
+        let current_version = funboy.template_db.read_template_version("noun").await;
+        assert!(current_version.unwrap().unwrap() == version.unwrap().unwrap() + 1);
+        assert!(other.public_template_etag("noun", 2).await.unwrap() == current_etag);
+
+        let first = funboy
+            .public_template_page("noun", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        let second = funboy
+            .public_template_page("noun", 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(first.page_count == 2);
+        assert!(first.substitutes.len() == Funboy::PUBLIC_PAGE_SIZE);
+        assert!(first.substitutes[..2] == ["fox", "<dog>"]);
+        assert!(
+            second.substitutes
+                == vec![
+                    format!("word{}", Funboy::PUBLIC_PAGE_SIZE - 2),
+                    format!("word{}", Funboy::PUBLIC_PAGE_SIZE - 1)
+                ]
+        );
+    }
+
+    #[tokio::test]
+    async fn export_import_round_trip() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy
+            .add_substitutes("noun", &["fox", "dog"])
+            .await
+            .unwrap();
+        funboy.add_substitutes("adj", &["quick"]).await.unwrap();
+        funboy.create_template("empty").await.unwrap();
+
+        let export = funboy.export_templates().await.unwrap();
+        let names = |export: &TemplateExport| -> Vec<(String, Vec<String>)> {
+            export
+                .templates
+                .iter()
+                .map(|template| {
+                    let substitutes = template.substitutes.iter().map(|sub| sub.name.clone());
+                    (template.name.clone(), substitutes.collect())
+                })
+                .collect()
+        };
+        assert!(
+            names(&export)
+                == vec![
+                    ("adj".to_string(), vec!["quick".to_string()]),
+                    ("empty".to_string(), Vec::new()),
+                    (
+                        "noun".to_string(),
+                        vec!["fox".to_string(), "dog".to_string()]
+                    ),
+                ]
+        );
+        assert!(
+            export
+                .templates
+                .iter()
+                .flat_map(|template| &template.substitutes)
+                .all(|sub| sub.id.is_some())
+        );
+
+        funboy.delete_templates(&["noun", "empty"]).await.unwrap();
+        let report = funboy
+            .import_templates(&export, ImportOptions::default())
+            .await
+            .unwrap();
+        assert!(report.templates == 3);
+        assert!(report.added == 2);
+        assert!(report.ignored == 1);
+        assert!(names(&funboy.export_templates().await.unwrap()) == names(&export));
+
+        let mut future_export = export.clone();
+        future_export.version = EXPORT_VERSION + 1;
+        assert!(
+            funboy
+                .import_templates(&future_export, ImportOptions::default())
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::ExportVersionUnsupported { .. })
+                ))
+        );
+    }
+
+    #[tokio::test]
+    async fn import_preserves_ids() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy
+            .add_substitutes("noun", &["fox", "dog"])
+            .await
+            .unwrap();
+        funboy
+            .set_substitutes_enabled_matching("noun", "dog", false)
+            .await
+            .unwrap();
+        let export = funboy.export_templates().await.unwrap();
+        assert!(
+            export.templates[0]
+                .substitutes
+                .iter()
+                .any(|sub| sub.name == "dog" && !sub.enabled)
+        );
+        funboy.delete_templates(&["noun"]).await.unwrap();
+
+        let preserve = ImportOptions {
+            preserve_ids: true,
+            force: false,
+        };
+        let report = funboy.import_templates(&export, preserve).await.unwrap();
+        assert!(report.added == 2);
+        assert!(report.reassigned.is_empty());
+        assert!(funboy.export_templates().await.unwrap() == export);
+
+        assert!(
+            funboy
+                .import_templates(&export, preserve)
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::PreserveIdsTemplateNotEmpty { .. })
+                ))
+        );
+
+        let mut renamed = export.clone();
+        renamed.templates[0].name = "animal".to_string();
+        let fox_id = renamed.templates[0].substitutes[0].id.unwrap();
+        let report = funboy
+            .import_templates(
+                &renamed,
+                ImportOptions {
+                    preserve_ids: true,
+                    force: true,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(report.added == 2);
+        assert!(report.reassigned.len() == 2);
+        assert!(report.reassigned[0].substitute == "fox");
+        assert!(report.reassigned[0].requested == fox_id);
+        assert!(report.reassigned[0].assigned != fox_id);
+    }
+
+    #[tokio::test]
+    async fn lint_finds_invalid_code_and_reserved_names() {
+        let pool = get_pool().await;
+        let db = create_debug_db(pool).await.unwrap();
+        db.create_substitutes("print", &["fine"]).await.unwrap();
+        let funboy = Funboy::new(db).with_reserved_names(&["print"]);
+
+        funboy
+            .add_substitutes("code", &CODE_SUBSTITUTES)
+            .await
+            .unwrap();
+
+        let issues = funboy.lint().await.unwrap();
+        let kinds: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|issue| (issue.template(), issue.kind()))
+            .collect();
+        assert!(
+            kinds
+                == vec![
+                    ("code", "invalid_code"),
+                    ("code", "invalid_code"),
+                    ("print", "reserved_name"),
+                ]
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_copied_template() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy
+            .add_substitutes(
+                "noun",
+                &["fox", "bear", "lion", "tiger", "bat", "giraffe", "zebra"],
+            )
+            .await
+            .unwrap();
+
+        let output = funboy
+            .generate(
+                &format!(
+                    "{0}noun {0}noun {0}noun {0}noun {0}noun",
+                    TemplateDelimiter::PLUS.to_char()
+                ),
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+
+        let mut subs = output.split_whitespace();
+        let first_sub = subs.nth(0).unwrap();
+        for sub in subs {
+            dbg!(sub);
+            assert!(sub == first_sub);
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_copied_template_registers() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy
+            .add_substitutes(
+                "noun",
+                &["fox", "bear", "lion", "tiger", "bat", "giraffe", "zebra"],
+            )
+            .await
+            .unwrap();
+
+        let output = funboy
+            .generate(&format!(
+                "{0}noun-1 {0}noun-1 {0}noun-2 {0}noun-2 {0}noun-2 {0}noun-999 {0}noun-999 {0}noun-999{0}noun-999{0}",
+                TemplateDelimiter::PLUS.to_char()),
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+
+        dbg!(&output);
+
+        let subs = output.split_whitespace().collect::<Vec<&str>>();
+        assert!(subs[0] == subs[1]);
+        assert!(subs[2] == subs[3]);
+        assert!(subs[3] == subs[4]);
+        assert!(subs[5] == subs[6]);
+        assert!(subs[6] != subs[7]);
+    }
+
+    #[tokio::test]
+    async fn generate_code() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let output = funboy
+            .generate(
+                "{repeat(5, print(\"again\"))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+
+        println!("OUTPUT: {}", output);
+        assert!(output == "againagainagainagainagain");
+    }
+
+    #[tokio::test]
+    async fn grammar_command_examples() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        let documentation = get_command_documentation();
+
+        for name in [
+            A_OR_AN, PLURAL, ORDINAL, TITLE_CASE, SNAKE_CASE, CAMEL_CASE, JSON_GET, JSON_SET,
+            TO_JSON,
+        ] {
+            let entry = documentation.get(name).unwrap();
+            for example in &entry.examples {
+                let (input, expected) = example.split_once(" = ").unwrap();
+                let output = funboy
+                    .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                    .await
+                    .unwrap()
+                    .text;
+                assert_eq!(output, expected.trim(), "{}", example);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn built_in_self_test_passes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        let spec = SelfTestSpec::default();
+
+        // Without the health template only the cases that don't read it pass
+        let report = funboy.self_test(&spec).await;
+        assert!(!report.is_healthy());
+        let failed: Vec<&str> = report
+            .failures()
+            .map(|result| result.name.as_str())
+            .collect();
+        assert!(
+            failed == ["template", "register", "get_sub"],
+            "{}",
+            report.summary()
+        );
+
+        funboy
+            .add_substitutes(&spec.health_template, &["ok", "fine"])
+            .await
+            .unwrap();
+        let report = funboy.self_test(&spec).await;
+        assert!(report.is_healthy(), "{}", report.summary());
+        assert!(report.passed() == 5);
+    }
+
+    #[tokio::test]
+    async fn debug_generate_logs_command_values() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let debug_output = funboy
+            .debug_generate(
+                "{add(1, 2) store(\"a\", x) concat(\"b\", clone(x))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+
+        let log = debug_output.log;
+        assert!(log.len() == 3, "{:?}", log);
+        assert!(log[0].command.starts_with("add"));
+        assert!(log[0].value == "3");
+        assert!(log[1].command.starts_with("store"));
+        assert!(log[1].value == "None");
+        assert!(log[2].command.starts_with("concat"));
+        assert!(log[2].value == "\"ba\"");
+    }
+
+    #[tokio::test]
+    async fn json_commands_keep_types() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let debug_output = funboy
+            .debug_generate(
+                "{store(json_set(\"a.b\", \"[1, 2.5]\", \"{}\"), doc) \
+                json_get(\"a.b[0]\", clone(doc)) json_get(\"a.b[1]\", clone(doc)) \
+                json_get(\"a.c\", json_set(\"a.c\", \"true\", clone(doc))) \
+                json_get(\"a.d\", json_set(\"a.d\", \"5 apples\", clone(doc)))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+
+        let values: Vec<&str> = debug_output
+            .log
+            .iter()
+            .filter(|entry| entry.command.starts_with("json_get"))
+            .map(|entry| entry.value.as_str())
+            .collect();
+        assert!(
+            values == ["1", "2.5", "true", "\"5 apples\""],
+            "{:?}",
+            values
+        );
+
+        let missing = funboy
+            .generate(
+                "{json_get(\"a.b[2]\", \"[]\")}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await;
+        assert!(matches!(missing, Err(FunboyError::Interpreter(_))));
+    }
+
+    #[tokio::test]
+    async fn json_commands_reject_invalid_and_oversized_json() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let invalid = funboy
+            .generate(
+                "{json_get(\"a\", \"[1,, 2]\")}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await;
+        match invalid {
+            Err(FunboyError::Interpreter(e)) => assert!(e.contains("line 1 column 4"), "{}", e),
+            other => panic!("expected an interpreter error, got {:?}", other),
+        }
+
+        let too_long = format!("[{}1]", "1, ".repeat(MAX_JSON_LENGTH / 3));
+        let oversized = funboy
+            .generate(
+                &format!("{{json_set(\"[0]\", \"2\", \"{}\")}}", too_long),
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await;
+        match oversized {
+            Err(FunboyError::Interpreter(e)) => assert!(e.contains("the limit is"), "{}", e),
+            other => panic!("expected an interpreter error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_continues_past_repeated_output() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        // half of the time `^coin` generates itself again, so across many generations early
+        // passes are all but certain to repeat the input before reaching "heads"
+        funboy
+            .add_substitutes("coin", &["{print(\"^\", \"coin\")}", "heads"])
+            .await
+            .unwrap();
+
+        for _ in 0..20 {
+            let output = funboy
+                .generate("^coin", Arc::new(Mutex::new(FslInterpreter::new())))
+                .await
+                .unwrap()
+                .text;
+            assert!(output == "heads");
+        }
+    }
+
+    #[test]
+    fn effectively_empty_output() {
+        for text in ["", "   ", "\n\n", "\u{200B}", " \u{200D}\n\u{FEFF}\t"] {
+            assert!(is_effectively_empty(text), "{:?}", text);
+        }
+        for text in ["a", "\u{200B}a\u{200B}", "."] {
+            assert!(!is_effectively_empty(text), "{:?}", text);
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_reports_effectively_empty_output() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let output = funboy
+            .generate(
+                "{if_then(false, print(\"hidden\"))}\n\n",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(output.is_effectively_empty);
+        assert!(output.text == "\n\n");
+        assert!(output.passes >= 1);
+
+        let output = funboy
+            .generate(
+                "{if_then(true, print(\"shown\"))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(!output.is_effectively_empty && output.text == "shown");
+    }
+
+    /// Adds templates prefix_1 through prefix_length each substituting the next one
+    async fn add_chain(funboy: &Funboy, prefix: &str, length: usize) {
+        for i in 1..length {
+            funboy
+                .add_substitutes(
+                    &format!("{}_{}", prefix, i),
+                    &[&format!("^{}_{}", prefix, i + 1)],
+                )
+                .await
+                .unwrap();
+        }
+        funboy
+            .add_substitutes(&format!("{}_{}", prefix, length), &["end"])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn nesting_depth_names_unresolved_templates() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        add_chain(&funboy, "deep", 40).await;
+        add_chain(&funboy, "also", 40).await;
+        add_chain(&funboy, "shallow", 5).await;
+
+        let strict = GenerateOptions {
+            max_depth: 32,
+            depth_mode: DepthMode::Strict,
+        };
+        let result = funboy
+            .generate_with_options(
+                "^deep_1 ^shallow_1 ^also_1 ^missing",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+                strict,
+            )
+            .await;
+        // the first pass resolves the chains 33 templates deep
+        assert!(result.is_err_and(|e| matches!(
+            e,
+            FunboyError::UserInput(UserFacingError::NestingTooDeep { depth: 32, templates })
+                if templates == vec!["deep_34", "also_34"]
+        )));
+
+        let output = funboy
+            .generate(
+                "^deep_1 ^shallow_1",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(output.text == "^deep_34 end");
+        assert!(
+            output.warnings
+                == vec![UserFacingError::NestingTooDeep {
+                    depth: GenerateOptions::DEFAULT_MAX_DEPTH,
+                    templates: vec!["deep_34".to_string()],
+                }]
+        );
+
+        let output = funboy
+            .generate_with_options(
+                "^deep_1 ^missing",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+                GenerateOptions {
+                    max_depth: 64,
+                    ..strict
+                },
+            )
+            .await
+            .unwrap();
+        assert!(output.text == "end ^missing" && output.warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn generations_emit_threshold_events() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool)
+            .await
+            .with_event_thresholds(EventThresholds {
+                slow_generation: Duration::ZERO,
+            });
+        add_chain(&funboy, "deep", 40).await;
+        let mut events = funboy.subscribe_events();
+
+        funboy
+            .generate("^deep_1", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        assert!(matches!(
+            events.try_recv(),
+            Ok(FunboyEvent::SlowGeneration { passes: 1, .. })
+        ));
+        assert!(
+            events.try_recv().ok()
+                == Some(FunboyEvent::DepthLimitHit {
+                    templates: vec!["deep_34".to_string()]
+                })
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn get_sub_or_falls_back_when_template_is_empty() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy.add_substitutes("pet", &["cat"]).await.unwrap();
+        funboy.add_substitutes("hidden", &["mole"]).await.unwrap();
+        funboy
+            .set_substitutes_enabled_matching("hidden", "", false)
+            .await
+            .unwrap();
+
+        for (template, expected) in [
+            ("pet", vec!["cat"]),
+            ("hidden", vec!["bird", "fish"]),
+            ("missing", vec!["bird", "fish"]),
+        ] {
+            let input = format!(
+                "{{print(get_sub_or(\"`{}`\", \"bird\", \"fish\"))}}",
+                template
+            );
+            for _ in 0..10 {
+                let output = funboy
+                    .generate(&input, Arc::new(Mutex::new(FslInterpreter::new())))
+                    .await
+                    .unwrap()
+                    .text;
+                assert!(
+                    expected.contains(&output.as_str()),
+                    "{} {}",
+                    template,
+                    output
+                );
+            }
+        }
+
+        assert!(
+            funboy
+                .generate(
+                    "{print(get_sub_or(\"missing\", \"bird\"))}",
+                    Arc::new(Mutex::new(FslInterpreter::new()))
+                )
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn get_sub_merged_picks_uniformly_from_both_pools() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy.add_substitutes("pet", &["cat"]).await.unwrap();
+
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for _ in 0..300 {
+            let sub = funboy
+                .get_substitute_merged("pet", &["bird".to_string(), "fish".to_string()])
+                .await
+                .unwrap();
+            *counts.entry(sub).or_default() += 1;
+        }
+        // each of the three is expected 100 times
+        assert!(counts.len() == 3, "{:?}", counts);
+        assert!(counts.values().all(|count| *count > 50), "{:?}", counts);
+
+        for _ in 0..10 {
+            let output = funboy
+                .generate(
+                    "{print(get_sub_merged(\"`missing`\", \"bird\", \"fish\"))}",
+                    Arc::new(Mutex::new(FslInterpreter::new())),
+                )
+                .await
+                .unwrap()
+                .text;
+            assert!(output == "bird" || output == "fish", "{}", output);
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_with_statement_separators() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let separated = funboy
+            .generate(
+                "{store(\"a;\", x); print(x);} b",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        let unseparated = funboy
+            .generate(
+                "{store(\"a;\", x) print(x)} b",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert!(separated == unseparated);
+        assert!(separated == "a; b");
+
+        // Commands written one after another without anything between them still run
+        let abutting = funboy
+            .generate(
+                "{store(\"a\", x)print(x)print(x)}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert!(abutting == "aa");
+    }
+
+    #[tokio::test]
+    async fn generate_with_numeric_literals() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let entry = get_command_documentation().get("add").unwrap();
+        for example in &entry.examples {
+            let (input, expected) = example.split_once(" = ").unwrap();
+            let output = funboy
+                .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await
+                .unwrap()
+                .text;
+            assert_eq!(output, expected.trim(), "{}", example);
+        }
+
+        let output = funboy
+            .generate(
+                "{print(mul(-2.5e-2, 4))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(output, "-0.1");
+
+        let output = funboy
+            .generate(
+                "{print(-9223372036854775808)}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(output, "-9223372036854775808");
+    }
+
+    #[tokio::test]
+    async fn debug_generate_logs_commands_as_written() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let debug_output = funboy
+            .debug_generate(
+                "{add(-1, 1e3); print(\"a\\\",-1\")}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+
+        let log = debug_output.log;
+        assert!(log.len() == 2, "{:?}", log);
+        assert_eq!(log[0].command, "add(-1, 1e3)");
+        assert_eq!(log[1].command, "print(\"a\\\",-1\")");
+    }
+
+    #[tokio::test]
+    async fn checked_math_commands() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        let documentation = get_command_documentation();
+
+        for name in [DIV, MOD, EQ] {
+            let entry = documentation.get(name).unwrap();
+            for example in &entry.examples {
+                let (input, expected) = example.split_once(" = ").unwrap();
+                let output = funboy
+                    .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                    .await
+                    .unwrap()
+                    .text;
+                assert_eq!(output, expected.trim(), "{}", example);
+            }
+        }
+
+        for (input, message) in [
+            ("{print(div(1.0, 0.0))}", "cannot divide by zero"),
+            ("{print(div(1, 0))}", "cannot divide by zero"),
+            ("{print(mod(5.5, 0.0))}", "cannot divide by zero"),
+            (
+                "{print(div(1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0))}",
+                "cannot divide by zero",
+            ),
+            ("{print(random_range(6, 1))}", "min must be less than max"),
+            (
+                "{print(random_range(6.5, 1.5))}",
+                "min must be less than max",
+            ),
+        ] {
+            let result = funboy
+                .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await;
+            assert!(
+                result.is_err_and(
+                    |e| matches!(e, FunboyError::Interpreter(error) if error.contains(message))
+                ),
+                "{}",
+                input
+            );
+        }
+
+        // Arguments past the tenth are still divided and text is never read as NaN
+        for (input, expected) in [
+            ("{print(div(4096, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2))}", "2"),
+            ("{store(\"NaN\", nan) print(eq(nan, \"NaN\"))}", "true"),
+        ] {
+            let output = funboy
+                .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+                .await
+                .unwrap()
+                .text;
+            assert_eq!(output, expected, "{}", input);
+        }
+
+        let output = funboy
+            .generate(
+                "{print(random_range(3, 3))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert_eq!(output, "3");
+    }
+
+    #[tokio::test]
+    async fn generate_stops_self_amplifying_template() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool)
+            .await
+            .with_expansion_limits(ExpansionLimits {
+                max_total: 2000,
+                max_per_template: 50,
+                ..Default::default()
+            });
+
+        funboy
+            .add_substitutes("amplify", &["^amplify ^amplify"])
+            .await
+            .unwrap();
+
+        let result = funboy
+            .generate("^amplify", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await;
+
+        assert!(result.is_err_and(
+            |e| matches!(e, FunboyError::UserInput(UserFacingError::GenerationTooLarge(
+                ExpansionError::TemplateLimitReached { template, .. }
+            )) if template == "amplify")
+        ));
+    }
+
+    #[tokio::test]
+    async fn generate_caps_registers() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool)
+            .await
+            .with_expansion_limits(ExpansionLimits {
+                max_registers: 3,
+                max_register_length: 4,
+                ..Default::default()
+            });
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        funboy.add_substitutes("long", &["giraffe"]).await.unwrap();
+
+        let output = funboy
+            .generate(
+                "+noun-1 +noun-2 +noun-1 +noun-3",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "fox fox fox fox");
+
+        let result = funboy
+            .generate(
+                "+noun-1 +noun-2 +noun-3 +noun-4",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await;
+        assert!(result.is_err_and(|e| matches!(
+            e,
+            FunboyError::UserInput(UserFacingError::GenerationTooLarge(
+                ExpansionError::RegisterLimitReached { limit: 3 }
+            ))
+        )));
+
+        let result = funboy
+            .generate("+long-1", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await;
+        assert!(result.is_err_and(|e| matches!(
+            e,
+            FunboyError::UserInput(UserFacingError::GenerationTooLarge(
+                ExpansionError::RegisterTooLong {
+                    length: 7,
+                    limit: 4,
+                    ..
+                }
+            ))
+        )));
+    }
+
+    #[tokio::test]
+    async fn registers_of_one_template_fetch_one_page() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy
+            .add_substitutes("noun", &["fox", "dog"])
+            .await
+            .unwrap();
+        funboy.random_sub_cache.invalidate_all();
+
+        let input = (1..=20)
+            .map(|i| format!("+noun-{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let expansions = funboy.new_expansion_counter();
+        let output = funboy
+            .substitute_register_templates(
+                input,
+                Arc::new(Mutex::new(FslInterpreter::new())),
+                expansions.clone(),
+                GenerateOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(output.split(' ').all(|word| word == "fox" || word == "dog"));
+        let expansions = expansions.lock().await;
+        assert!(expansions.registers() == 20);
+        assert!(expansions.page_fetches() == 1);
+    }
+
+    #[tokio::test]
+    async fn add_substitutes_ignores_long_substitutes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let longest = "a".repeat(Funboy::MAX_SUBSTITUTE_LENGTH);
+        let too_long = "a".repeat(Funboy::MAX_SUBSTITUTE_LENGTH + 1);
+
+        let receipt = funboy
+            .add_substitutes("long", &[&too_long, &longest])
+            .await
+            .unwrap();
+        assert!(receipt.updated.len() == 1);
+        assert!(receipt.updated[0].name == longest);
+        assert!(
+            receipt.ignored
+                == [IgnoredEntry::new(
+                    &too_long,
+                    IgnoreReason::TooLong {
+                        len: Funboy::MAX_SUBSTITUTE_LENGTH + 1,
+                        max: Funboy::MAX_SUBSTITUTE_LENGTH,
+                    }
+                )]
+        );
+    }
+
+    #[tokio::test]
+    async fn add_substitutes_ignores_empty_substitutes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy
+            .add_substitutes("noun", &["", "fox", " \t"])
+            .await
+            .unwrap();
+        assert!(receipt.updated_to_string() == "fox");
+        assert!(ignored_kinds(&receipt) == [("", "empty"), (" \t", "empty")]);
+
+        let receipt = funboy.add_substitutes("noun", &[" "]).await.unwrap();
+        assert!(receipt.updated.is_empty());
+        assert!(ignored_kinds(&receipt) == [(" ", "empty")]);
+    }
+
+    #[tokio::test]
+    async fn ignored_substitutes_are_grouped_by_reason() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await.with_max_substitutes(2);
+
+        let too_long = "a".repeat(Funboy::MAX_SUBSTITUTE_LENGTH + 1);
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        let receipt = funboy
+            .add_substitutes("noun", &["fox", "", &too_long, "fox", "dog", "cat", "owl"])
+            .await
+            .unwrap();
+
+        assert!(receipt.updated_to_string() == "dog");
+        assert!(receipt.ignored_summary() == "1 empty, 1 too long, 2 duplicates, 2 over the cap");
+        assert!(
+            receipt
+                .ignored_to_styled_string(OutputStyle::Plain)
+                .ends_with("; duplicate: fox, fox; over the cap: cat, owl")
+        );
+    }
+
+    #[tokio::test]
+    async fn add_substitutes_ignores_existing() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy
+            .add_substitutes("noun", &["fox", "dog"])
+            .await
+            .unwrap();
+        assert!(receipt.updated_to_string() == "fox, dog");
+        assert!(receipt.ignored.is_empty());
+
+        // Every substitute exists so nothing is written
+        let receipt = funboy
+            .add_substitutes("noun", &["dog", "fox", "dog"])
+            .await
+            .unwrap();
+        assert!(receipt.updated.is_empty());
+        assert!(
+            ignored_kinds(&receipt)
+                == [
+                    ("dog", "duplicate"),
+                    ("fox", "duplicate"),
+                    ("dog", "duplicate")
+                ]
+        );
+
+        let receipt = funboy
+            .add_substitutes("noun", &["cat", "fox", "cat"])
+            .await
+            .unwrap();
+        assert!(receipt.updated_to_string() == "cat");
+        assert!(ignored_kinds(&receipt) == [("fox", "duplicate"), ("cat", "duplicate")]);
+    }
+
+    #[tokio::test]
+    async fn add_substitutes_refuses_past_cap() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await.with_max_substitutes(3);
+
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        let receipt = funboy
+            .add_substitutes("noun", &["fox", "dog", "cat", "owl", "bat"])
+            .await
+            .unwrap();
+
+        assert!(receipt.updated_to_string() == "dog, cat");
+        assert!(
+            ignored_kinds(&receipt)
+                == [
+                    ("fox", "duplicate"),
+                    ("owl", "cap_exceeded"),
+                    ("bat", "cap_exceeded")
+                ]
+        );
+        let refused = receipt.refused.unwrap();
+        assert!(refused.substitutes == ["owl", "bat"]);
+        assert!(refused.count == 3);
+        assert!(refused.cap == 3);
+
+        let receipt = funboy.add_substitutes("noun", &["eel"]).await.unwrap();
+        assert!(receipt.updated.is_empty());
+        assert!(
+            receipt
+                .refused
+                .is_some_and(|refused| refused.substitutes == ["eel"])
+        );
+    }
+
+    #[tokio::test]
+    async fn template_cap_overrides_default() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await.with_max_substitutes(1);
+
+        assert!(
+            funboy
+                .set_substitute_cap("noun", Some(3))
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { .. })
+                ))
+        );
+
+        funboy.create_template("noun").await.unwrap();
+        funboy.set_substitute_cap("noun", Some(3)).await.unwrap();
+        assert!(funboy.get_substitute_cap("noun").await.unwrap() == 3);
+        assert!(funboy.get_substitute_cap("verb").await.unwrap() == 1);
+
+        let receipt = funboy
+            .add_substitutes("noun", &["fox", "dog", "cat"])
+            .await
+            .unwrap();
+        assert!(receipt.updated.len() == 3);
+        assert!(receipt.refused.is_none());
+
+        funboy.set_substitute_cap("noun", None).await.unwrap();
+        assert!(funboy.get_substitute_cap("noun").await.unwrap() == 1);
+    }
+
+    async fn draw_names(funboy: &Funboy, template: &str, count: usize) -> Vec<String> {
+        let mut names = Vec::new();
+        for _ in 0..count {
+            names.push(funboy.get_random_substitute(template).await.unwrap().name);
+        }
+        names
+    }
+
+    #[tokio::test]
+    async fn round_robin_covers_every_substitute_once_per_cycle() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool.clone()).await;
+
+        let receipt = funboy
+            .add_substitutes("quiz", &["a", "b", "c", "d"])
+            .await
+            .unwrap();
+        assert!(
+            funboy
+                .set_template_strategy("missing", SelectionStrategy::RoundRobin)
+                .await
+                .is_err()
+        );
+        funboy
+            .set_template_strategy("quiz", SelectionStrategy::RoundRobin)
+            .await
+            .unwrap();
+        assert!(
+            funboy.get_template_strategy("quiz").await.unwrap() == SelectionStrategy::RoundRobin
+        );
+
+        let mut cycle = draw_names(&funboy, "quiz", 4).await;
+        cycle.sort();
+        assert!(cycle == vec!["a", "b", "c", "d"]);
+
+        // Disabled substitutes are skipped without restarting the cycle
+        let b = receipt.updated.iter().find(|sub| sub.name == "b").unwrap();
+        funboy.set_substitute_enabled(b.id, false).await.unwrap();
+        assert!(draw_names(&funboy, "quiz", 3).await == vec!["a", "c", "d"]);
+        assert!(draw_names(&funboy, "quiz", 1).await == vec!["a"]);
+
+        // The cursor survives a restart once flushed
+        assert!(funboy.flush_selection_state().await.unwrap() == 1);
+        assert!(funboy.flush_selection_state().await.unwrap() == 0);
+        let restarted = Funboy::new(TemplateDatabase::new(Arc::new(pool)));
+        assert!(draw_names(&restarted, "quiz", 2).await == vec!["c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_prefers_stalest_substitutes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool.clone()).await;
+
+        funboy
+            .add_substitutes("quiz", &["a", "b", "c"])
+            .await
+            .unwrap();
+        funboy
+            .set_template_strategy("quiz", SelectionStrategy::LeastRecentlyUsed)
+            .await
+            .unwrap();
+
+        let first = draw_names(&funboy, "quiz", 3).await;
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert!(sorted == vec!["a", "b", "c"]);
+        assert!(draw_names(&funboy, "quiz", 3).await == first);
+
+        // A substitute added later has never been used so it goes first
+        funboy.add_substitutes("quiz", &["e"]).await.unwrap();
+        assert!(draw_names(&funboy, "quiz", 1).await == vec!["e"]);
+        funboy.flush_selection_state().await.unwrap();
+
+        let restarted = Funboy::new(TemplateDatabase::new(Arc::new(pool)));
+        assert!(draw_names(&restarted, "quiz", 3).await == first);
+
+        // Going back to random drops the strategy without touching other settings
+        restarted
+            .set_template_strategy("quiz", SelectionStrategy::Random)
+            .await
+            .unwrap();
+        assert!(
+            restarted.get_template_strategy("quiz").await.unwrap() == SelectionStrategy::Random
+        );
+        assert!(draw_names(&restarted, "quiz", 10).await.len() == 10);
+    }
+
+    #[tokio::test]
+    async fn copy_substitutes_respects_cap() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await.with_max_substitutes(3);
+
+        funboy
+            .add_substitutes("noun", &["fox", "dog", "cat"])
+            .await
+            .unwrap();
+        funboy
+            .add_substitutes("animal", &["fox", "owl"])
+            .await
+            .unwrap();
+
+        assert!(
+            funboy
+                .copy_substitutes("noun", "animal")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::SubstituteCapExceeded {
+                        count: 2,
+                        adding: 2,
+                        cap: 3,
+                        ..
+                    })
+                ))
+        );
+        assert!(
+            funboy
+                .template_db
+                .count_substitutes("animal")
+                .await
+                .unwrap()
+                == 2
+        );
+
+        funboy.set_substitute_cap("animal", Some(4)).await.unwrap();
+        assert!(
+            funboy
+                .copy_substitutes("noun", "animal")
+                .await
+                .unwrap()
+                .len()
+                == 2
+        );
+    }
+
+    #[tokio::test]
+    async fn replace_updates_cached_substitutes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy.add_substitutes("snack", &["chips"]).await.unwrap();
+        let chips = receipt.updated[0].id;
+        funboy.get_random_substitute("snack").await.unwrap();
+
+        // Renaming behind the cache's back shows generations are still served from the cache
+        funboy
+            .replace_substitute("snack", "chips", "pretzels")
+            .await
+            .unwrap();
+        funboy
+            .template_db
+            .update_substitute_by_name("snack", "pretzels", "crackers")
+            .await
+            .unwrap();
+        assert!(funboy.get_random_substitute("snack").await.unwrap().name == "pretzels");
+
+        funboy
+            .replace_substitute_by_id(chips, "popcorn")
+            .await
+            .unwrap();
+        funboy
+            .template_db
+            .update_substitute_by_name("snack", "popcorn", "crackers")
+            .await
+            .unwrap();
+        assert!(funboy.get_random_substitute("snack").await.unwrap().name == "popcorn");
+    }
+
+    #[tokio::test]
+    async fn single_deletes_update_cached_substitutes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy
+            .add_substitutes("drink", &["tea", "coffee", "water"])
+            .await
+            .unwrap();
+        let coffee = receipt.updated[1].id;
+        funboy.get_random_substitute("drink").await.unwrap();
+
+        funboy.delete_substitutes("drink", &["tea"]).await.unwrap();
+        assert!(
+            funboy
+                .random_sub_cache
+                .get("drink")
+                .await
+                .is_some_and(|subs| subs.len() == 2)
+        );
+        for _ in 0..50 {
+            assert!(funboy.get_random_substitute("drink").await.unwrap().name != "tea");
+        }
+
+        funboy.delete_substitutes_by_id(&[coffee]).await.unwrap();
+        assert!(
+            funboy
+                .random_sub_cache
+                .get("drink")
+                .await
+                .is_some_and(|subs| subs.len() == 1 && subs[0].name == "water")
+        );
+
+        // Bulk operations change which substitutes are cached so they still invalidate
+        funboy.add_substitutes("drink", &["juice"]).await.unwrap();
+        assert!(funboy.random_sub_cache.get("drink").await.is_none());
+
+        funboy.get_random_substitute("drink").await.unwrap();
+        funboy
+            .delete_substitutes("drink", &["water", "juice"])
+            .await
+            .unwrap();
+        assert!(funboy.random_sub_cache.get("drink").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn sample_distribution_expects_enabled_substitutes_only() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy
+            .add_substitutes("weather", &["rain", "sun", "fog", "hail"])
+            .await
+            .unwrap();
+        let hail = receipt.updated[3].id;
+        funboy.set_substitute_enabled(hail, false).await.unwrap();
+
+        let report = funboy.sample_distribution("weather", 3000).await.unwrap();
+        assert!(report.draws == 3000);
+        assert!(report.page_len == 3);
+        assert!(report.substitutes.len() == 4);
+        for sub in &report.substitutes {
+            if sub.substitute.id == hail {
+                assert!(sub.expected_probability == 0.0);
+                assert!(sub.draws == 0);
+            } else {
+                assert!((sub.expected_probability - 1.0 / 3.0).abs() < 1e-9);
+                assert!(sub.draws > 0);
+            }
+        }
+        assert!(report.degrees_of_freedom() == 2);
+        // Sampling doesn't fill the cache generation reads from
+        assert!(funboy.random_sub_cache.get("weather").await.is_none());
+
+        for draws in [0, Funboy::MAX_SAMPLE_DRAWS + 1] {
+            assert!(matches!(
+                funboy.sample_distribution("weather", draws).await,
+                Err(FunboyError::UserInput(
+                    UserFacingError::SampleDrawsInvalid { .. }
+                ))
+            ));
+        }
+        assert!(matches!(
+            funboy.sample_distribution("no_weather", 10).await,
+            Err(FunboyError::UserInput(
+                UserFacingError::TemplateNotFound { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn disabled_substitutes_are_never_generated() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let receipt = funboy
+            .add_substitutes("holiday", &["snowman", "snowflake", "pumpkin"])
+            .await
+            .unwrap();
+        let pumpkin = receipt.updated[2].id;
+
+        // Populate the cache so disabling has to invalidate it
+        funboy.get_random_substitute("holiday").await.unwrap();
+
+        funboy.set_substitute_enabled(pumpkin, false).await.unwrap();
+        for _ in 0..100 {
+            assert!(funboy.get_random_substitute("holiday").await.unwrap().id != pumpkin);
+        }
+
+        assert!(
+            funboy
+                .set_substitutes_enabled_matching("holiday", "snow", false)
+                .await
+                .unwrap()
+                == 2
+        );
+        assert!(funboy.get_random_substitute("holiday").await.is_err());
+
+        funboy.set_substitute_enabled(pumpkin, true).await.unwrap();
+        for _ in 0..20 {
+            assert!(funboy.get_random_substitute("holiday").await.unwrap().id == pumpkin);
+        }
+
+        assert!(
+            funboy
+                .set_substitutes_enabled_matching("holiday", "", true)
+                .await
+                .unwrap()
+                == 2
+        );
+        let mut generated = HashSet::new();
+        for _ in 0..200 {
+            generated.insert(funboy.get_random_substitute("holiday").await.unwrap().name);
+        }
+        assert!(generated.len() == 3);
+    }
+
+    #[tokio::test]
+    async fn template_info_gathers_settings() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await.with_max_substitutes(50);
+
+        assert!(
+            funboy
+                .get_template_info("missing")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { .. })
+                ))
+        );
+
+        // Nothing but substitutes so every optional setting falls back to its default
+        funboy
+            .add_substitutes("adj", &["red", "big"])
+            .await
+            .unwrap();
+        let info = funboy.get_template_info("adj").await.unwrap();
+        assert!(info.template.name == "adj");
+        assert!(info.description == None);
+        assert!(info.created_at > 0);
+        assert!(!info.archived);
+        assert!(info.substitutes == 2);
+        assert!(info.disabled_substitutes == 0);
+        assert!(info.substitute_cap == 50);
+        assert!(!info.custom_cap);
+        assert!(info.examples.is_empty());
+
+        funboy
+            .set_template_description("adj", Some("  adjectives, lowercase, no proper nouns\n"))
+            .await
+            .unwrap();
+        funboy.set_substitute_cap("adj", Some(10)).await.unwrap();
+        funboy
+            .set_template_examples("adj", &["red", "big"])
+            .await
+            .unwrap();
+        let big = funboy
+            .get_substitutes("adj", Some("big"), None, OrderBy::Default, Limit::None)
+            .await
+            .unwrap();
+        funboy
+            .set_substitute_enabled(big[0].id, false)
+            .await
+            .unwrap();
+        funboy
+            .template_db
+            .archive_templates_by_id(&[info.template.id])
+            .await
+            .unwrap();
+
+        let info = funboy.get_template_info("adj").await.unwrap();
+        assert!(info.description.as_deref() == Some("adjectives, lowercase, no proper nouns"));
+        assert!(info.archived);
+        assert!(info.substitutes == 2);
+        assert!(info.disabled_substitutes == 1);
+        assert!(info.substitute_cap == 10);
+        assert!(info.custom_cap);
+        assert!(info.examples == ["red", "big"]);
+    }
+
+    #[tokio::test]
+    async fn template_descriptions_are_validated() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        assert!(
+            funboy
+                .set_template_description("missing", Some("notes"))
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { .. })
+                ))
+        );
+
+        funboy.add_substitutes("adj", &["red"]).await.unwrap();
+        let too_long = "é".repeat(Funboy::MAX_DESCRIPTION_LENGTH + 1);
+        assert!(
+            funboy
+                .set_template_description("adj", Some(&too_long))
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::DescriptionTooLong {
+                        length: 1001,
+                        limit: 1000
+                    })
+                ))
+        );
+
+        let longest = "é".repeat(Funboy::MAX_DESCRIPTION_LENGTH);
+        funboy
+            .set_template_description("adj", Some(&longest))
+            .await
+            .unwrap();
+        assert!(funboy.get_template_description("adj").await.unwrap() == Some(longest));
+
+        funboy
+            .set_template_description("adj", Some(" \n "))
+            .await
+            .unwrap();
+        assert!(funboy.get_template_description("adj").await.unwrap() == None);
+    }
+
+    #[tokio::test]
+    async fn template_examples_are_capped() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        assert!(
+            funboy
+                .set_template_examples("missing", &["example"])
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { .. })
+                ))
+        );
+
+        funboy.add_substitutes("fruit", &["apple"]).await.unwrap();
+
+        assert!(
+            funboy
+                .set_template_examples("fruit", &["1", "2", "3", "4"])
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TooManyExamples { limit: 3 })
+                ))
+        );
+
+        let too_long = "a".repeat(Funboy::MAX_EXAMPLE_LENGTH + 1);
+        assert!(
+            funboy
+                .set_template_examples("fruit", &[&too_long])
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::ExampleTooLong { .. })
+                ))
+        );
+
+        let longest = "a".repeat(Funboy::MAX_EXAMPLE_LENGTH);
+        let examples = funboy
+            .set_template_examples("fruit", &["an apple", &longest, "a pear"])
+            .await
+            .unwrap();
+        assert!(examples.len() == Funboy::MAX_EXAMPLES);
+    }
+
+    #[tokio::test]
+    async fn preview_substitute_writes_nothing() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy.add_substitutes("noun", &["cat"]).await.unwrap();
+
+        let preview = funboy
+            .preview_substitute(
+                "animal",
+                "+noun-1+ and +noun-1+ {print(\"!\")}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(preview.generated.as_deref() == Some("cat and cat !"));
+        assert!(preview.warnings.is_empty());
+
+        assert!(!funboy.template_db.template_exists("animal").await.unwrap());
+        assert!(funboy.template_db.count_substitutes("noun").await.unwrap() == 1);
+        assert!(funboy.flush_usage().await.unwrap() == 0);
+    }
+
+    #[tokio::test]
+    async fn preview_substitute_warnings() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await.with_max_expression_depth(2);
+        funboy.add_substitutes("noun", &["cat"]).await.unwrap();
+
+        let preview = funboy
+            .preview_substitute("noun", "cat", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        assert!(preview.generated.as_deref() == Some("cat"));
+        assert!(preview.warnings == vec![PreviewWarning::Ignored(IgnoreReason::Duplicate)]);
+
+        let preview = funboy
+            .preview_substitute(
+                "noun",
+                "^missing {print(\"dog\"",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(
+            preview
+                .warnings
+                .iter()
+                .any(|warning| matches!(warning, PreviewWarning::InvalidCode(_)))
+        );
+        assert!(
+            preview
+                .warnings
+                .contains(&PreviewWarning::UnknownReference("missing".to_string()))
+        );
+
+        let preview = funboy
+            .preview_substitute(
+                "noun",
+                "{print(upper(lower(\"dog\")))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(preview.generated.is_none());
+        assert!(
+            preview
+                .warnings
+                .iter()
+                .any(|warning| matches!(warning, PreviewWarning::GenerationFailed(_)))
+        );
+
+        assert!(
+            funboy
+                .preview_substitute(
+                    "bad name",
+                    "cat",
+                    Arc::new(Mutex::new(FslInterpreter::new()))
+                )
+                .await
+                .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn preview_template() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        assert!(
+            funboy
+                .preview_template("fruit", Arc::new(Mutex::new(FslInterpreter::new())))
+                .await
+                .is_err()
+        );
+
+        funboy.add_substitutes("fruit", &["apple"]).await.unwrap();
+        funboy
+            .set_template_examples("fruit", &["a ripe apple", "a green apple"])
+            .await
+            .unwrap();
+
+        let preview = funboy
+            .preview_template("fruit", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        assert!(preview.template == "fruit");
+        assert!(preview.examples == ["a ripe apple", "a green apple"]);
+        assert!(preview.generated == "apple");
+
+        funboy.set_template_examples("fruit", &[]).await.unwrap();
+        let preview = funboy
+            .preview_template("fruit", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        assert!(preview.examples.is_empty());
+    }
+
+    #[tokio::test]
+    async fn blocking_interpretation_matches_inline() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy
+            .add_substitutes("noun", &["{print(\"fox\")}"])
+            .await
+            .unwrap();
+        let blocking = funboy.clone().with_blocking_threshold(0);
+
+        let input = "the ^noun says {repeat(3, print(\"hi\"))}";
+        let inline = funboy
+            .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+        let offloaded = blocking
+            .generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+        assert!(inline == "the fox says hihihi");
+        assert!(offloaded == inline);
+
+        // Debug generations are offloaded the same way
+        let inline = funboy
+            .debug_generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        let offloaded = blocking
+            .debug_generate(input, Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        assert!(offloaded.output == inline.output);
+        assert!(offloaded.log.len() == inline.log.len());
+    }
+
+    #[tokio::test]
+    async fn fallback_resolver_fills_missing_templates() {
+        let pool = get_pool().await;
+        let mut funboy = get_funboy(pool).await.with_reserved_names(&["_member"]);
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let resolver_calls = calls.clone();
+        funboy.set_fallback_resolver(Arc::new(move |template: &str| {
+            resolver_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let resolved = (template == "_member").then(|| "Alice".to_string());
+            Box::pin(async move { resolved })
+        }));
+
+        let output = funboy
+            .generate(
+                "^_member loves ^noun",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "Alice loves fox");
+        // noun is in the database so only _member reaches the resolver
+        assert!(calls.load(std::sync::atomic::Ordering::SeqCst) == 1);
+        assert!(funboy.random_sub_cache.get("_member").await.is_none());
+
+        assert!(funboy.get_random_substitute("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fallback_templates_cannot_be_created() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool)
+            .await
+            .with_reserved_names(&["_member", "_emoji"]);
+
+        for name in ["_member", "_emoji"] {
+            assert!(
+                funboy
+                    .add_substitutes(name, &["shadow"])
+                    .await
+                    .is_err_and(|e| matches!(
+                        e,
+                        FunboyError::UserInput(UserFacingError::TemplateNameReserved { .. })
+                    ))
+            );
+        }
+        assert!(funboy.add_substitutes("_members", &["ok"]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn substitutes_are_credited_to_contributors() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy.add_substitutes("noun", &["owl"]).await.unwrap();
+
+        let mut contributing = funboy.clone();
+        contributing.set_contributor(10, Some(1));
+        contributing
+            .add_substitutes("noun", &["fox", "dog"])
+            .await
+            .unwrap();
+        contributing
+            .add_substitutes("verb", &["run"])
+            .await
+            .unwrap();
+
+        let stats = funboy
+            .get_contribution_stats(Some(1), None, Some(1), 10)
+            .await
+            .unwrap();
+        assert!(stats.len() == 1 && stats[0].user_id == 10 && stats[0].substitutes == 3);
+        assert!(
+            funboy
+                .get_contribution_stats(None, Some("noun"), None, 10)
+                .await
+                .unwrap()[0]
+                .substitutes
+                == 2
+        );
+        assert!(
+            funboy
+                .get_contribution_stats(None, Some("bad name"), None, 10)
+                .await
+                .is_err()
+        );
+
+        let mine = funboy.get_user_contributions(10, None).await.unwrap();
+        assert!(mine.len() == 2 && mine[0].name == "noun" && mine[0].substitutes == 2);
+    }
+
+    #[tokio::test]
+    async fn usage_is_flushed_in_batches() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        funboy.add_substitutes("verb", &["jump"]).await.unwrap();
+
+        let mut guild_funboy = funboy.clone();
+        guild_funboy.set_usage_guild(Some(1));
+        for _ in 0..3 {
+            guild_funboy
+                .generate(
+                    "^noun ^verb ^noun",
+                    Arc::new(Mutex::new(FslInterpreter::new())),
+                )
+                .await
+                .unwrap();
+        }
+        // nothing is written until the flush
+        assert!(funboy.usage.pending() == 9);
+        assert!(
+            funboy
+                .get_template_leaderboard(Some(1), 1, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(funboy.flush_usage().await.unwrap() == 2);
+        assert!(funboy.usage.pending() == 0);
+        let leaderboard = funboy
+            .get_template_leaderboard(Some(1), 1, 10)
+            .await
+            .unwrap();
+        assert!(
+            leaderboard
+                == vec![
+                    TemplateUsage {
+                        name: "noun".to_string(),
+                        uses: 6
+                    },
+                    TemplateUsage {
+                        name: "verb".to_string(),
+                        uses: 3
+                    },
+                ]
+        );
+
+        guild_funboy
+            .generate("^verb", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        assert!(funboy.flush_usage().await.unwrap() == 1);
+        assert!(funboy.flush_usage().await.unwrap() == 0);
+        let leaderboard = funboy
+            .get_template_leaderboard(Some(1), 7, 1)
+            .await
+            .unwrap();
+        assert!(leaderboard.len() == 1 && leaderboard[0].uses == 6);
+        let verb = funboy
+            .get_template_leaderboard(Some(1), 7, 2)
+            .await
+            .unwrap();
+        assert!(verb[1].name == "verb" && verb[1].uses == 4);
+    }
+
+    #[tokio::test]
+    async fn usage_outside_of_guilds() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+
+        funboy
+            .generate("^noun", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
+        funboy.flush_usage().await.unwrap();
+
+        let leaderboard = funboy.get_template_leaderboard(None, 1, 10).await.unwrap();
+        assert!(leaderboard.len() == 1 && leaderboard[0].uses == 1);
+        assert!(
+            funboy
+                .get_template_leaderboard(Some(1), 1, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_playback_history() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let retention = Funboy::PLAYBACK_RETENTION_DAYS as i64 * 60 * 60 * 24;
+        funboy
+            .template_db
+            .create_playback_event(1, "old", None, 7, unix_now() - retention - 60)
+            .await
+            .unwrap();
+        funboy.record_playback(1, "new", None, 7).await.unwrap();
+
+        assert!(funboy.prune_playback_history().await.unwrap() == 1);
+        let history = funboy.get_playback_history(1, 20).await.unwrap();
+        assert!(history.len() == 1);
+        assert!(history[0].track_name == "new");
+    }
+
+    #[tokio::test]
+    async fn ai_labels_are_set_per_guild() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        assert!(funboy.get_ai_label(1).await.unwrap().is_none());
+
+        funboy.set_announcement_channel(1, Some(5)).await.unwrap();
+        funboy
+            .set_ai_label(1, true, None, LabelPlacement::Before)
+            .await
+            .unwrap();
+        assert!(funboy.get_ai_label(1).await.unwrap() == Some(AiLabel::default()));
+        assert!(funboy.get_ai_label(2).await.unwrap().is_none());
+        assert!(funboy.get_announcement_channel(1).await.unwrap() == Some(5));
+
+        funboy
+            .set_ai_label(1, true, Some(" [bot] "), LabelPlacement::After)
+            .await
+            .unwrap();
+        assert!(
+            funboy.get_ai_label(1).await.unwrap()
+                == Some(AiLabel {
+                    text: "[bot]".to_string(),
+                    placement: LabelPlacement::After,
+                })
+        );
+
+        let too_long = "a".repeat(Funboy::MAX_AI_LABEL_LENGTH + 1);
+        assert!(
+            funboy
+                .set_ai_label(1, true, Some(&too_long), LabelPlacement::Before)
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::AiLabelTooLong { .. })
+                ))
+        );
+
+        funboy
+            .set_ai_label(1, false, None, LabelPlacement::Before)
+            .await
+            .unwrap();
+        assert!(funboy.get_ai_label(1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn aliases_expand_once() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy
+            .set_alias(1, "joke", "^setup ^punchline")
+            .await
+            .unwrap();
+        funboy.set_alias(1, "again", "joke").await.unwrap();
+
+        assert!(funboy.expand_alias(1, " joke ").await.unwrap() == "^setup ^punchline");
+        assert!(funboy.expand_alias(1, "again").await.unwrap() == "joke");
+        assert!(funboy.expand_alias(2, "joke").await.unwrap() == "joke");
+        assert!(funboy.expand_alias(1, "joke ^noun").await.unwrap() == "joke ^noun");
+
+        assert!(
+            funboy
+                .set_alias(1, "Bad Name", "x")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::AliasNameInvalid { .. })
+                ))
+        );
+        assert!(
+            funboy
+                .set_alias(1, "blank", " ")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::AliasInputInvalid { .. })
+                ))
+        );
+
+        assert!(funboy.remove_alias(1, "joke").await.unwrap());
+        assert!(funboy.expand_alias(1, "joke").await.unwrap() == "joke");
+    }
+
+    #[tokio::test]
+    async fn aliases_are_capped_per_guild() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        for i in 0..=Funboy::MAX_ALIASES {
+            let alias = funboy.set_alias(1, &format!("alias_{}", i), "x").await;
+            if i < Funboy::MAX_ALIASES {
+                assert!(alias.is_ok());
+            } else {
+                assert!(alias.is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TooManyAliases { .. })
+                )));
+            }
+        }
+
+        assert!(funboy.set_alias(1, "alias_0", "replaced").await.is_ok());
+        assert!(funboy.set_alias(2, "alias_0", "x").await.is_ok());
+        assert!(funboy.get_aliases(1).await.unwrap().len() == Funboy::MAX_ALIASES);
+    }
+
+    #[tokio::test]
+    async fn triggers_recompile_after_changes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        let pokemon = funboy
+            .set_trigger(1, "pokemon", TriggerMatchMode::Contains, "^pokemon", 30)
+            .await
+            .unwrap();
+        assert!(pokemon.cooldown_secs == 30);
+        let matched = funboy.match_trigger(1, "who's that Pokemon").await.unwrap();
+        assert!(matched.is_some_and(|trigger| trigger.id == pokemon.id));
+        assert!(funboy.trigger_cache.get(&1).await.is_some());
+        assert!(funboy.match_trigger(2, "pokemon").await.unwrap().is_none());
+
+        // Changing a trigger drops the compiled patterns of its guild only
+        funboy
+            .set_trigger(1, "pokemon", TriggerMatchMode::Contains, "^digimon", 0)
+            .await
+            .unwrap();
+        assert!(funboy.trigger_cache.get(&1).await.is_none());
+        assert!(funboy.trigger_cache.get(&2).await.is_some());
+        let matched = funboy.match_trigger(1, "pokemon").await.unwrap();
+        assert!(matched.is_some_and(|trigger| trigger.template_input == "^digimon"));
+
+        assert!(funboy.remove_trigger(1, pokemon.id).await.unwrap());
+        assert!(funboy.match_trigger(1, "pokemon").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn triggers_are_validated_and_capped() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        assert!(matches!(
+            funboy
+                .set_trigger(1, "(unclosed", TriggerMatchMode::Regex, "x", 0)
+                .await,
+            Err(FunboyError::UserInput(
+                UserFacingError::TriggerPatternInvalid { .. }
+            ))
+        ));
+        assert!(matches!(
+            funboy
+                .set_trigger(1, "hello", TriggerMatchMode::Exact, " ", 0)
+                .await,
+            Err(FunboyError::UserInput(
+                UserFacingError::TriggerInputInvalid { .. }
+            ))
+        ));
+
+        let trigger = funboy
+            .set_trigger(1, " hello ", TriggerMatchMode::Exact, "hi", u64::MAX)
+            .await
+            .unwrap();
+        assert!(trigger.pattern == "hello");
+        assert!(trigger.cooldown_secs == MAX_TRIGGER_COOLDOWN_SECS as i64);
+
+        for i in 1..=Funboy::MAX_TRIGGERS {
+            let trigger = funboy
+                .set_trigger(1, &format!("hello {}", i), TriggerMatchMode::Exact, "hi", 0)
+                .await;
+            if i < Funboy::MAX_TRIGGERS {
+                assert!(trigger.is_ok());
+            } else {
+                assert!(trigger.is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TooManyTriggers { .. })
+                )));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn alias_names_can_match_templates() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        funboy.set_alias(1, "noun", "a ^noun").await.unwrap();
+
+        let expanded = funboy.expand_alias(1, "noun").await.unwrap();
+        let output = funboy
+            .generate(&expanded, Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "a fox");
+
+        // Skipping expansion generates the input as written
+        let output = funboy
+            .generate("noun", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "noun");
+    }
+
+    #[tokio::test]
+    async fn favorites_are_capped() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        for i in 0..=Funboy::MAX_FAVORITES {
+            let template = format!("template_{}", i);
+            funboy.add_substitutes(&template, &["sub"]).await.unwrap();
+            let favorite = funboy.add_favorite(7, &template).await;
+            if i < Funboy::MAX_FAVORITES {
+                assert!(favorite.unwrap().is_some());
+            } else {
+                assert!(favorite.is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TooManyFavorites { .. })
+                )));
+            }
+        }
+
+        assert!(
+            funboy
+                .add_favorite(7, "template_0")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            funboy
+                .add_favorite(7, "missing")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { .. })
+                ))
+        );
+        assert!(funboy.list_favorites(7).await.unwrap().len() == Funboy::MAX_FAVORITES);
+        assert!(funboy.remove_favorite(7, "template_0").await.unwrap());
+        assert!(funboy.list_favorites(8).await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "ollama")]
+    #[tokio::test]
+    async fn prompt_preset_scopes() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy
+            .save_prompt_preset(
+                "pirate",
+                PresetScope::Global,
+                "Talk like a pirate",
+                PresetMode::System,
+            )
+            .await
+            .unwrap();
+        funboy
+            .save_prompt_preset(
+                "pirate",
+                PresetScope::User(7),
+                "Arr {prompt}",
+                PresetMode::Wrap,
+            )
+            .await
+            .unwrap();
+
+        let own = funboy
+            .get_prompt_preset("pirate", 7)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(own.scope() == PresetScope::User(7));
+        assert!(own.mode() == PresetMode::Wrap);
+
+        let global = funboy
+            .get_prompt_preset("pirate", 8)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(global.scope() == PresetScope::Global);
+
+        assert!(funboy.get_prompt_presets(7).await.unwrap().len() == 2);
+        assert!(funboy.get_prompt_presets(8).await.unwrap().len() == 1);
+
+        assert!(
+            funboy
+                .save_prompt_preset(
+                    "wrap",
+                    PresetScope::Global,
+                    "no placeholder",
+                    PresetMode::Wrap
+                )
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::PresetMissingPlaceholder { .. })
+                ))
+        );
+        assert!(
+            funboy
+                .save_prompt_preset("", PresetScope::Global, "body", PresetMode::System)
+                .await
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "ollama")]
+    #[tokio::test]
+    async fn stored_ollama_text_reaches_ollama_verbatim() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+
+        let mut settings = OllamaSettings::default();
+        settings
+            .set_system_prompt("Talk about ^noun {print(\"code\")}")
+            .unwrap();
+        settings.set_template("^noun {{ .Prompt }}").unwrap();
+
+        let request = funboy
+            .prepare_ollama_request(
+                "^noun",
+                &settings,
+                None,
+                "bob",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(request.generated_prompt == "fox");
+        assert!(request.prompt == "fox");
+        assert!(request.settings.system_prompt() == "Talk about ^noun {print(\"code\")}");
+        assert!(request.settings.template() == "^noun {{ .Prompt }}");
+
+        let system = funboy
+            .save_prompt_preset(
+                "system",
+                PresetScope::Global,
+                "^noun for {username}",
+                PresetMode::System,
+            )
+            .await
+            .unwrap();
+        let request = funboy
+            .prepare_ollama_request(
+                "^noun",
+                &settings,
+                Some(&system),
+                "bob",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
             .await
-            .unwrap()
-    }
+            .unwrap();
+        assert!(request.prompt == "fox");
+        assert!(request.settings.system_prompt() == "^noun for bob");
 
-    async fn get_funboy(pool: PgPool) -> Funboy {
-        let db = create_debug_db(pool).await.unwrap();
-        Funboy::new(db)
+        let wrap = funboy
+            .save_prompt_preset(
+                "wrap",
+                PresetScope::Global,
+                "^noun: {prompt}",
+                PresetMode::Wrap,
+            )
+            .await
+            .unwrap();
+        let request = funboy
+            .prepare_ollama_request(
+                "^noun",
+                &settings,
+                Some(&wrap),
+                "bob",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap();
+        assert!(request.generated_prompt == "fox");
+        assert!(request.prompt == "^noun: fox");
     }
 
     #[tokio::test]
-    async fn generate_templates() {
+    async fn copy_substitutes_stales_held_pages() {
         let pool = get_pool().await;
         let funboy = get_funboy(pool).await;
 
-        let output = funboy
-            .generate("^sentence", Arc::new(Mutex::new(FslInterpreter::new())))
-            .await
-            .unwrap();
+        funboy.add_substitutes("fruit", &["apple"]).await.unwrap();
+        funboy.add_substitutes("berry", &["cherry"]).await.unwrap();
 
-        assert!(output == "^sentence");
-        println!("OUTPUT: {}", output);
+        // A generation reads the page of fruit and holds on to it while fruit is copied into
+        let version = funboy.random_sub_cache.version("fruit");
+        funboy.get_random_substitute("fruit").await.unwrap();
+        let held = funboy.random_sub_cache.get("fruit").await.unwrap();
 
-        funboy
-            .add_substitutes(
-                "sentence",
-                &["A ^gtadj brown ^gtnoun ^gtverb^ed over the lazy dog."],
-            )
-            .await
-            .unwrap();
+        funboy.copy_substitutes("berry", "fruit").await.unwrap();
+        funboy.random_sub_cache.insert("fruit", version, held).await;
+        assert!(funboy.random_sub_cache.get("fruit").await.is_none());
 
-        funboy.add_substitutes("gtadj", &["quick"]).await.unwrap();
-        funboy.add_substitutes("gtnoun", &["fox"]).await.unwrap();
-        funboy.add_substitutes("gtverb", &["jump"]).await.unwrap();
+        let mut picked = HashSet::new();
+        for _ in 0..50 {
+            picked.insert(funboy.get_random_substitute("fruit").await.unwrap().name);
+        }
+        assert!(picked.contains("cherry"));
 
-        let output = funboy
-            .generate("^sentence", Arc::new(Mutex::new(FslInterpreter::new())))
-            .await
+        let debug = funboy.cache_debug();
+        let fruit = debug
+            .iter()
+            .find(|cached| cached.template == "fruit")
             .unwrap();
+        assert!(fruit.page_version == Some(fruit.version));
+        assert!(fruit.page_len == 2);
+        assert!(!fruit.is_stale());
 
-        println!("OUTPUT: {}", output);
-        assert!(output == "A quick brown fox jumped over the lazy dog.");
+        // A missing template is never cached but copying into it still bumps its version
+        let fallback = ["fallback".to_string()];
+        assert!(
+            funboy
+                .get_substitute_or("new_fruit", &fallback)
+                .await
+                .unwrap()
+                == "fallback"
+        );
+        let version = funboy.random_sub_cache.version("new_fruit");
+        assert!(
+            funboy
+                .copy_substitutes("fruit", "new_fruit")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(funboy.random_sub_cache.version("new_fruit") > version);
     }
 
     #[tokio::test]
-    async fn generate_copied_template() {
+    async fn clone_template() {
         let pool = get_pool().await;
         let funboy = get_funboy(pool).await;
 
         funboy
-            .add_substitutes(
-                "noun",
-                &["fox", "bear", "lion", "tiger", "bat", "giraffe", "zebra"],
-            )
+            .add_substitutes("fruit", &["apple", "pear"])
             .await
             .unwrap();
+        funboy.add_substitutes("taken", &["blah"]).await.unwrap();
 
-        let output = funboy
-            .generate(
-                &format!(
-                    "{0}noun {0}noun {0}noun {0}noun {0}noun",
-                    TemplateDelimiter::Plus.to_char()
-                ),
-                Arc::new(Mutex::new(FslInterpreter::new())),
-            )
+        // Populate the cache for the source template
+        funboy.get_random_substitute("fruit").await.unwrap();
+
+        assert!(
+            funboy
+                .clone_template("fruit", "taken")
+                .await
+                .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+        );
+        assert!(
+            funboy
+                .clone_template("missing", "anything")
+                .await
+                .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+        );
+
+        let report = funboy.clone_template("fruit", "produce").await.unwrap();
+        assert!(report.template.name == "produce");
+        assert!(report.copied == 2);
+
+        assert!(funboy.random_sub_cache.get("produce").await.is_none());
+        assert!(funboy.random_sub_cache.get("fruit").await.is_some());
+        let fruit = funboy
+            .get_substitutes("fruit", None, None, OrderBy::Default, Limit::None)
             .await
             .unwrap();
+        assert!(fruit.len() == 2);
+    }
 
-        let mut subs = output.split_whitespace();
-        let first_sub = subs.nth(0).unwrap();
-        for sub in subs {
-            dbg!(sub);
-            assert!(sub == first_sub);
-        }
+    #[tokio::test]
+    async fn validate_template_names() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        assert!(funboy.add_substitutes("NoGood", &["blah"]).await.is_err());
+
+        assert!(funboy.add_substitutes("very_good", &["blah"]).await.is_ok());
+
+        assert!(
+            funboy
+                .rename_template("notReal", "notRealEither")
+                .await
+                .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+        );
+
+        assert!(
+            funboy
+                .rename_template("real", "notRealEither")
+                .await
+                .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+        );
+
+        assert!(
+            funboy
+                .rename_template("real", "totally_real_too")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { .. })
+                ))
+        );
     }
 
     #[tokio::test]
-    async fn generate_copied_template_registers() {
+    async fn rename_checks_both_templates() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        funboy.add_substitutes("nouns", &["foxes"]).await.unwrap();
+
+        assert!(
+            funboy
+                .rename_template("nou", "thing")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { suggestions, .. })
+                        if suggestions == ["noun", "nouns"]
+                ))
+        );
+        assert!(
+            funboy
+                .preview_rename("noun", "nouns")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateExists { .. })
+                ))
+        );
+        assert!(
+            funboy
+                .rename_template("noun", "nouns")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateExists { .. })
+                ))
+        );
+        assert!(funboy.template_db.template_exists("noun").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn find_source_ranks_exact_fragment_and_fuzzy_matches() {
         let pool = get_pool().await;
         let funboy = get_funboy(pool).await;
 
+        funboy
+            .add_substitutes("animal", &["wet dog", "cat"])
+            .await
+            .unwrap();
         funboy
             .add_substitutes(
-                "noun",
-                &["fox", "bear", "lion", "tiger", "bat", "giraffe", "zebra"],
+                "insult",
+                &["you smell like a ^animal", "you smell like a wet dog"],
             )
             .await
             .unwrap();
+        funboy
+            .add_substitutes("greeting", &["hello there friend"])
+            .await
+            .unwrap();
 
-        let output = funboy
-            .generate(&format!(
-                "{0}noun-1 {0}noun-1 {0}noun-2 {0}noun-2 {0}noun-2 {0}noun-999 {0}noun-999 {0}noun-999{0}noun-999{0}",
-                TemplateDelimiter::Plus.to_char()),
-                Arc::new(Mutex::new(FslInterpreter::new())),
-            )
+        let sources = funboy
+            .find_source_of("You smell like a\n WET dog", 10)
             .await
             .unwrap();
+        let found: Vec<(&str, &str, MatchQuality)> = sources
+            .iter()
+            .map(|(template, sub, quality)| (template.name.as_str(), sub.name.as_str(), *quality))
+            .collect();
+        assert!(found.len() == 3, "{:?}", found);
+        assert!(found[0] == ("insult", "you smell like a wet dog", MatchQuality::Exact));
+        assert!(found[1] == ("animal", "wet dog", MatchQuality::Fragment { words: 2 }));
+        assert!(matches!(
+            found[2],
+            ("insult", "you smell like a ^animal", MatchQuality::Fuzzy { similarity })
+                if similarity > 0.5
+        ));
 
-        dbg!(&output);
+        let sources = funboy.find_source_of("SMELL like", 10).await.unwrap();
+        assert!(sources.len() >= 2);
+        assert!(
+            sources[..2]
+                .iter()
+                .all(|(_, _, quality)| *quality == MatchQuality::Contains)
+        );
 
-        let subs = output.split_whitespace().collect::<Vec<&str>>();
-        assert!(subs[0] == subs[1]);
-        assert!(subs[2] == subs[3]);
-        assert!(subs[3] == subs[4]);
-        assert!(subs[5] == subs[6]);
-        assert!(subs[6] != subs[7]);
+        assert!(funboy.find_source_of(" \n", 10).await.unwrap().is_empty());
+        assert!(funboy.find_source_of("wet dog", 1).await.unwrap().len() == 1);
     }
 
     #[tokio::test]
-    async fn generate_code() {
+    async fn replace_substitute_checks_before_updating() {
         let pool = get_pool().await;
         let funboy = get_funboy(pool).await;
 
-        let output = funboy
-            .generate(
-                "{repeat(5, print(\"again\"))}",
-                Arc::new(Mutex::new(FslInterpreter::new())),
-            )
+        funboy
+            .add_substitutes("noun", &["fox", "dog"])
             .await
             .unwrap();
 
-        println!("OUTPUT: {}", output);
-        assert!(output == "againagainagainagainagain");
+        assert!(
+            funboy
+                .replace_substitute("verb", "fox", "cat")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::TemplateNotFound { .. })
+                ))
+        );
+        assert!(
+            funboy
+                .replace_substitute("noun", "cat", "cow")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::SubstituteNotFound { .. })
+                ))
+        );
+        assert!(
+            funboy
+                .replace_substitute("noun", "fox", "dog")
+                .await
+                .is_err_and(|e| matches!(
+                    e,
+                    FunboyError::UserInput(UserFacingError::SubstituteExists { .. })
+                ))
+        );
+        assert!(
+            funboy
+                .replace_substitute("noun", "fox", "cat")
+                .await
+                .unwrap()
+                .is_some_and(|sub| sub.name == "cat")
+        );
     }
 
     #[tokio::test]
-    async fn validate_template_names() {
+    async fn rewrite_references_reports_each_substitute() {
         let pool = get_pool().await;
         let funboy = get_funboy(pool).await;
+        funboy.add_substitutes("fruit", &["apple"]).await.unwrap();
+        funboy.add_substitutes("food", &["bread"]).await.unwrap();
+        funboy
+            .add_substitutes("sentence", &["^fruit and `fruit` or ^food"])
+            .await
+            .unwrap();
+        funboy
+            .add_substitutes("story", &["once upon a ^fruit"])
+            .await
+            .unwrap();
 
-        assert!(funboy.add_substitutes("NoGood", &["blah"]).await.is_err());
+        // cache the substitutes of sentence so the rewrite has to invalidate them
+        funboy
+            .generate("^sentence", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap();
 
-        assert!(funboy.add_substitutes("very_good", &["blah"]).await.is_ok());
+        let scope = RewriteScope::Template("sentence".to_string());
+        let preview = funboy
+            .preview_rewrite_references("fruit", "food", scope.clone())
+            .await
+            .unwrap();
+        let report = funboy
+            .rewrite_references("fruit", "food", scope)
+            .await
+            .unwrap();
+        assert!(preview.substitutes == report.substitutes);
+        assert!(report.templates() == vec!["sentence"]);
+        assert!(report.substitutes.len() == 1);
+        let sub = &report.substitutes[0];
+        assert!(sub.before == "^fruit and `fruit` or ^food");
+        assert!(sub.after == "^food and `food` or ^food");
+        assert!(sub.old_references == 2 && sub.new_references == 3);
 
+        let output = funboy
+            .generate("^sentence", Arc::new(Mutex::new(FslInterpreter::new())))
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "bread and `food` or bread");
         assert!(
             funboy
-                .rename_template("notReal", "notRealEither")
+                .get_substitutes("story", None, None, OrderBy::Default, Limit::None)
+                .await
+                .unwrap()[0]
+                .name
+                == "once upon a ^fruit"
+        );
+
+        assert!(
+            funboy
+                .rewrite_references("fruit", "print", RewriteScope::All)
+                .await
+                .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+        );
+        assert!(
+            funboy
+                .rewrite_references(
+                    "fruit",
+                    "food",
+                    RewriteScope::Template("missing".to_string())
+                )
                 .await
                 .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
         );
+    }
 
+    #[tokio::test]
+    async fn reject_reserved_template_names() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await.with_reserved_names(&["say"]);
+
+        for reserved in ["print", GET_SUB, ASK_AI, "say"] {
+            assert!(
+                funboy
+                    .add_substitutes(reserved, &["blah"])
+                    .await
+                    .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+            );
+        }
+
+        funboy.add_substitutes("printer", &["blah"]).await.unwrap();
         assert!(
             funboy
-                .rename_template("real", "notRealEither")
+                .rename_template("printer", "print")
+                .await
+                .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
+        );
+        assert!(
+            funboy
+                .copy_substitutes("printer", "print")
                 .await
                 .is_err_and(|e| matches!(e, FunboyError::UserInput(_)))
         );
+    }
+
+    #[tokio::test]
+    async fn templates_predating_a_reservation_can_be_added_to() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
 
+        funboy.template_db.create_template("print").await.unwrap();
+        funboy.add_substitutes("print", &["blah"]).await.unwrap();
+        funboy.add_substitutes("printer", &["ink"]).await.unwrap();
+        funboy.copy_substitutes("printer", "print").await.unwrap();
+
+        let subs = funboy
+            .get_substitutes("print", None, None, OrderBy::Default, Limit::None)
+            .await
+            .unwrap();
+        let mut subs: Vec<&str> = subs.iter().map(|sub| sub.name.as_str()).collect();
+        subs.sort();
+        assert_eq!(subs, ["blah", "ink"]);
+    }
+
+    #[tokio::test]
+    async fn report_reserved_conflicts() {
+        let pool = get_pool().await;
+        let funboy = get_funboy(pool).await;
+
+        funboy.template_db.create_template("print").await.unwrap();
+        funboy.template_db.create_template("concat").await.unwrap();
+        funboy.add_substitutes("fine", &["blah"]).await.unwrap();
+
+        let conflicts = funboy.check_reserved_conflicts().await.unwrap();
+        let conflicts: Vec<&str> = conflicts.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(conflicts, ["concat", "print"]);
+
+        // Conflicting templates must stay reachable so they can be renamed
         assert!(
             funboy
-                .rename_template("real", "totally_real_too")
+                .rename_template("print", "old_print")
                 .await
                 .unwrap()
-                .is_none()
+                .is_some()
+        );
+        assert!(funboy.check_reserved_conflicts().await.unwrap().len() == 1);
+    }
+
+    #[test]
+    fn reserved_names_follow_documentation() {
+        let mut documentation = get_command_documentation().clone();
+        let mut new_command = documentation.commands[0].clone();
+        new_command.name = "brand_new_command".to_string();
+        documentation.commands.push(new_command);
+
+        let reserved = reserved_template_names(&documentation);
+        assert!(reserved.contains("brand_new_command"));
+        assert!(reserved.contains("print"));
+        assert!(reserved.contains(GET_SUB));
+        assert!(
+            !reserved_template_names(get_command_documentation()).contains("brand_new_command")
         );
     }
 
     // Test is slow so only run it selectively
     // #[tokio::test]
+    #[cfg(feature = "ollama")]
     async fn generate_ollama_response() {
         let pool = get_pool().await;
         let funboy = get_funboy(pool).await;
@@ -867,3 +7760,30 @@ mod core {
         println!("Ollama response: {}", generation_response.response);
     }
 }
+
+/// Run with `cargo test -p funboy-core --no-default-features` to check builds without Ollama
+#[cfg(all(test, not(feature = "ollama")))]
+mod without_ollama_test {
+    use super::*;
+    use template_database::test::{connect_debug_pool, create_debug_db};
+
+    #[tokio::test]
+    async fn generate_without_ollama() {
+        let db = create_debug_db(connect_debug_pool().await).await.unwrap();
+        let funboy = Funboy::new(db);
+
+        funboy.add_substitutes("noun", &["fox"]).await.unwrap();
+        let output = funboy
+            .generate(
+                "the ^noun {print(plural(2, \"cat\"))}",
+                Arc::new(Mutex::new(FslInterpreter::new())),
+            )
+            .await
+            .unwrap()
+            .text;
+        assert!(output == "the fox cats");
+
+        // ask_ai stays reserved so templates work the same once Ollama is enabled
+        assert!(funboy.is_reserved_name(ASK_AI));
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt::Display;
+
+use crate::{
+    embedded_code::CodeSyntaxError,
+    template_database::{IgnoreReason, SubstituteWarning},
+};
+
+/// A stored template or substitute that will cause trouble when generated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintIssue {
+    /// The template shares its name with an interpreter command
+    ReservedName { template: String },
+    /// A substitute of the template has embedded code that fails to parse
+    InvalidCode {
+        template: String,
+        warning: SubstituteWarning,
+    },
+}
+
+impl LintIssue {
+    pub fn template(&self) -> &str {
+        match self {
+            LintIssue::ReservedName { template } => template,
+            LintIssue::InvalidCode { template, .. } => template,
+        }
+    }
+
+    /// A stable identifier for the kind of issue used by structured output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LintIssue::ReservedName { .. } => "reserved_name",
+            LintIssue::InvalidCode { .. } => "invalid_code",
+        }
+    }
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintIssue::ReservedName { template } => write!(
+                f,
+                "template {} conflicts with the command of the same name",
+                template
+            ),
+            LintIssue::InvalidCode { template, warning } => write!(
+                f,
+                "substitute \"{}\" of template {}: {}",
+                warning.substitute, template, warning.error
+            ),
+        }
+    }
+}
+
+/// Trouble found while previewing a substitute before it is added to a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewWarning {
+    /// Adding the substitute would leave it out of the template
+    Ignored(IgnoreReason),
+    /// The embedded code of the substitute fails to parse
+    InvalidCode(CodeSyntaxError),
+    /// No template has this name so the reference would be left as written
+    UnknownReference(String),
+    /// Generating the substitute failed, it would fail the same way once added
+    GenerationFailed(String),
+}
+
+impl PreviewWarning {
+    /// A stable identifier for the kind of warning used by structured output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PreviewWarning::Ignored(_) => "ignored",
+            PreviewWarning::InvalidCode(_) => "invalid_code",
+            PreviewWarning::UnknownReference(_) => "unknown_reference",
+            PreviewWarning::GenerationFailed(_) => "generation_failed",
+        }
+    }
+}
+
+impl Display for PreviewWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreviewWarning::Ignored(IgnoreReason::Duplicate) => {
+                write!(f, "the template already has this substitute")
+            }
+            PreviewWarning::Ignored(IgnoreReason::TooLong { len, max }) => write!(
+                f,
+                "the substitute is {} characters long, the limit is {}",
+                len, max
+            ),
+            PreviewWarning::Ignored(IgnoreReason::Empty) => write!(f, "the substitute is empty"),
+            PreviewWarning::Ignored(IgnoreReason::CapExceeded) => {
+                write!(f, "the template is full")
+            }
+            PreviewWarning::Ignored(reason) => {
+                write!(f, "the substitute would be ignored as {}", reason.kind())
+            }
+            PreviewWarning::InvalidCode(error) => write!(f, "{}", error),
+            PreviewWarning::UnknownReference(template) => write!(
+                f,
+                "template {} doesn't exist so it won't be replaced",
+                template
+            ),
+            PreviewWarning::GenerationFailed(error) => write!(f, "generating failed: {}", error),
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, ops::Range, time::Duration};
 
-use super::quote_filter::QuoteFilter;
+use crate::quote_filter::QuoteFilter;
 
 pub const DISCORD_CHARACTER_LIMIT: usize = 2000;
 pub const DISCORD_PRETTY_WIDTH: usize = 100;
@@ -144,59 +144,61 @@ impl Default for SeperatedListOptions<'_> {
     }
 }
 
+/// Formats items as seperated lists wrapped in markdown that each fit in one message
+///
+/// appended_text follows the closing markdown of the last list or gets a message of its own when
+/// it does not fit there. No message is empty or holds only markdown.
 pub fn format_as_item_seperated_list(
     items: &[&str],
     appended_text: &str,
     options: SeperatedListOptions,
 ) -> Vec<String> {
-    let mut messages: Vec<String> = Vec::new();
-    messages.push(String::with_capacity(DISCORD_CHARACTER_LIMIT));
-    let mut current_msg = 0;
+    let markdown = options.markdown;
+    // room left for items between the opening and closing markdown of a message
+    let budget = DISCORD_CHARACTER_LIMIT - (markdown.len() * 2);
+    let item_limit = budget
+        .saturating_sub(appended_text.len())
+        .saturating_sub(options.item_seperator.len());
 
-    messages[current_msg].push_str(options.markdown);
+    let mut messages: Vec<String> = Vec::new();
+    let mut body = String::with_capacity(budget);
     for (i, item) in items.iter().enumerate() {
         let item = if options.quote_on_whitespace && item.contains(char::is_whitespace) {
             format!("\"{}\"", item)
         } else {
-            format!("{}", item)
+            item.to_string()
         };
 
-        let item = if item.len()
-            > DISCORD_CHARACTER_LIMIT
-                - (options.markdown.len() * 2)
-                - appended_text.len()
-                - options.item_seperator.len()
-        {
-            format!("{}", ellipsize_if_long(&item, DISCORD_PRETTY_WIDTH))
+        let item = if item.len() > item_limit {
+            ellipsize_if_long(&item, DISCORD_PRETTY_WIDTH).into_owned()
         } else {
             item
         };
 
-        let addition_len = messages[current_msg].len() + item.len() + options.markdown.len();
-
         let seperator = if i == items.len() - 1 {
             ""
         } else {
             options.item_seperator
         };
 
-        if addition_len + seperator.len() <= DISCORD_CHARACTER_LIMIT {
-            messages[current_msg].push_str(&format!("{}{}", item, seperator));
-        } else {
-            messages[current_msg].push_str(options.markdown);
-            messages.push(String::with_capacity(DISCORD_CHARACTER_LIMIT));
-            current_msg += 1;
-            messages[current_msg].push_str(&format!("{}{}{}", options.markdown, &item, seperator));
+        if !body.is_empty() && body.len() + item.len() + seperator.len() > budget {
+            messages.push(format!("{}{}{}", markdown, body, markdown));
+            body.clear();
         }
+        body.push_str(&item);
+        body.push_str(seperator);
     }
 
-    if messages[current_msg].len() + options.markdown.len() + " ".len() + appended_text.len()
-        != DISCORD_CHARACTER_LIMIT
-    {
-        messages[current_msg].push_str(options.markdown);
-        messages[current_msg].push_str(&format!(" {}", appended_text));
-    } else {
-        messages.push(appended_text.to_string());
+    let last = (!body.is_empty()).then(|| format!("{}{}{}", markdown, body, markdown));
+    match last {
+        Some(last) if appended_text.is_empty() => messages.push(last),
+        Some(last) if last.len() + " ".len() + appended_text.len() <= DISCORD_CHARACTER_LIMIT => {
+            messages.push(format!("{} {}", last, appended_text));
+        }
+        last => {
+            messages.extend(last);
+            messages.extend(split_message(appended_text).into_iter().map(str::to_string));
+        }
     }
 
     messages
@@ -222,6 +224,103 @@ pub fn format_as_numeric_list(items: &[&str]) -> Vec<String> {
         .collect()
 }
 
+/// Truncates input to at most limit bytes without splitting a character
+pub fn truncate_on_char_boundary(input: &str, limit: usize) -> &str {
+    if input.len() <= limit {
+        return input;
+    }
+    let mut end = limit;
+    while !input.is_char_boundary(end) {
+        end -= 1;
+    }
+    &input[..end]
+}
+
+/// Formats command and value pairs as lines inside a single code block that fits in one message
+///
+/// Lines that do not fit are dropped and replaced with a count of how many were omitted
+pub fn format_as_value_log(entries: &[(&str, &str)]) -> String {
+    const FENCE: &str = "```";
+    const OMITTED_RESERVE: usize = 32;
+    let budget = DISCORD_CHARACTER_LIMIT - (FENCE.len() * 2) - "\n".len() - OMITTED_RESERVE;
+
+    let mut body = String::new();
+    let mut omitted = 0;
+    for (command, value) in entries {
+        let line = format!(
+            "{} => {}\n",
+            ellipsize_if_long(&command.replace(FENCE, "'''"), DISCORD_PRETTY_WIDTH),
+            ellipsize_if_long(&value.replace(FENCE, "'''"), DISCORD_PRETTY_WIDTH)
+        );
+        let line = truncate_on_char_boundary(&line, budget);
+
+        if omitted == 0 && body.len() + line.len() <= budget {
+            body.push_str(line);
+        } else {
+            omitted += 1;
+        }
+    }
+
+    if omitted > 0 {
+        body.push_str(&format!("... {} more\n", omitted));
+    }
+
+    format!("{}\n{}{}", FENCE, body, FENCE)
+}
+
+/// Formats a duration as `m:ss` or `h:mm:ss` using `?:??` when the duration is unknown
+pub fn format_duration(duration: Option<Duration>) -> String {
+    let Some(duration) = duration else {
+        return "?:??".to_string();
+    };
+
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Returns the item range shown on a zero based page and the total number of pages
+///
+/// Pages past the end are clamped to the last page and there is always at least one page
+pub fn page_bounds(total: usize, page: usize, per_page: usize) -> (Range<usize>, usize) {
+    let per_page = per_page.max(1);
+    let page_count = total.div_ceil(per_page).max(1);
+    let page = page.min(page_count - 1);
+    let start = page * per_page;
+    (start..(start + per_page).min(total), page_count)
+}
+
+/// Joins template names into a suggestion line no longer than limit
+///
+/// Names that would not fit are left out entirely rather than cut
+pub fn format_template_suggestions(names: &[&str], limit: usize) -> String {
+    const PREFIX: &str = "recent: ";
+    const SEPERATOR: &str = ", ";
+
+    let mut output = String::new();
+    for name in names {
+        let addition = if output.is_empty() {
+            PREFIX.len() + name.len()
+        } else {
+            SEPERATOR.len() + name.len()
+        };
+        if output.len() + addition > limit {
+            break;
+        }
+        if output.is_empty() {
+            output.push_str(PREFIX);
+        } else {
+            output.push_str(SEPERATOR);
+        }
+        output.push_str(name);
+    }
+    output
+}
+
 const IMAGE_TYPES: [&str; 3] = [".png", ".gif", ".jpg"];
 pub fn extract_image_urls(input: &str) -> Vec<&str> {
     let mut urls = Vec::new();
@@ -239,6 +338,44 @@ pub fn extract_image_urls(input: &str) -> Vec<&str> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn duration_formatting() {
+        assert_eq!(format_duration(Some(Duration::from_secs(0))), "0:00");
+        assert_eq!(format_duration(Some(Duration::from_secs(222))), "3:42");
+        assert_eq!(format_duration(Some(Duration::from_secs(3599))), "59:59");
+        assert_eq!(format_duration(Some(Duration::from_secs(3600))), "1:00:00");
+        assert_eq!(
+            format_duration(Some(Duration::from_secs(37_505))),
+            "10:25:05"
+        );
+        assert_eq!(format_duration(None), "?:??");
+    }
+
+    #[test]
+    fn paging_math() {
+        assert_eq!(page_bounds(0, 0, 5), (0..0, 1));
+        assert_eq!(page_bounds(5, 0, 5), (0..5, 1));
+        assert_eq!(page_bounds(6, 0, 5), (0..5, 2));
+        assert_eq!(page_bounds(6, 1, 5), (5..6, 2));
+        assert_eq!(page_bounds(6, 9, 5), (5..6, 2));
+        assert_eq!(page_bounds(3, 0, 0), (0..1, 3));
+    }
+
+    #[test]
+    fn template_suggestions() {
+        assert_eq!(format_template_suggestions(&[], 100), "");
+        assert_eq!(
+            format_template_suggestions(&["noun", "verb"], 100),
+            "recent: noun, verb"
+        );
+        // "recent: noun, verb" is 18 characters so adj would be cut
+        assert_eq!(
+            format_template_suggestions(&["noun", "verb", "adj"], 20),
+            "recent: noun, verb"
+        );
+        assert_eq!(format_template_suggestions(&["noun"], 4), "");
+    }
+
     const ITEM_SEPERATOR: &str = ", ";
 
     #[test]
@@ -366,10 +503,131 @@ mod tests {
         }
     }
 
+    #[test]
+    fn value_log_fits_in_one_message() {
+        let long_value = "v".repeat(DISCORD_CHARACTER_LIMIT);
+        let entries: Vec<(&str, &str)> = (0..100)
+            .map(|_| ("concat(\"```\", \"é\")", long_value.as_str()))
+            .collect();
+
+        let log = format_as_value_log(&entries);
+        assert!(log.len() <= DISCORD_CHARACTER_LIMIT);
+        assert!(log.starts_with("```\n"));
+        assert!(log.ends_with("```"));
+        assert!(log.matches("```").count() == 2);
+        assert!(log.contains("more"));
+    }
+
+    #[test]
+    fn value_log_keeps_order() {
+        let log = format_as_value_log(&[("add(1, 2)", "3"), ("print(\"a\")", "None")]);
+        assert_eq!(log, "```\nadd(1, 2) => 3\nprint(\"a\") => None\n```");
+    }
+
     const MARKDOWN: &str = "```";
     const NOTIFY_TEXT: &str = "added to `nothing`";
     const LIMIT: usize = 2000 - NOTIFY_TEXT.len() - (MARKDOWN.len() * 2) - ITEM_SEPERATOR.len();
 
+    /// Messages from [`format_as_item_seperated_list`] that were checked to be sendable
+    #[derive(Debug)]
+    struct MessageChunks(Vec<String>);
+
+    impl MessageChunks {
+        /// Fails unless every message fits the limit and holds more than markdown
+        fn new(messages: Vec<String>, markdown: &str) -> Result<Self, String> {
+            for (i, message) in messages.iter().enumerate() {
+                if message.len() > DISCORD_CHARACTER_LIMIT {
+                    return Err(format!("message {} is {} long", i, message.len()));
+                }
+                if message.replace(markdown, "").trim().is_empty() {
+                    return Err(format!("message {} has no content: {:?}", i, message));
+                }
+            }
+            Ok(Self(messages))
+        }
+    }
+
+    #[test]
+    fn seperated_list_edge_cases() {
+        let id_list = SeperatedListOptions::as_id_list();
+        let default = SeperatedListOptions::default();
+        // with default options a message holds 1994 characters between its markdown
+        let cases: [(&str, Vec<String>, &str, SeperatedListOptions, usize); 10] = [
+            ("no items", vec![], "", default, 0),
+            ("only appended", vec![], NOTIFY_TEXT, default, 1),
+            (
+                "first oversized",
+                vec!["s".repeat(3000), "b".to_string()],
+                NOTIFY_TEXT,
+                default,
+                1,
+            ),
+            (
+                "first fills the item limit",
+                vec!["s".repeat(LIMIT), "b".to_string()],
+                NOTIFY_TEXT,
+                default,
+                2,
+            ),
+            (
+                "appended exactly fills",
+                vec!["a".repeat(1000), "b".repeat(973)],
+                NOTIFY_TEXT,
+                default,
+                1,
+            ),
+            (
+                "appended one over",
+                vec!["a".repeat(1000), "b".repeat(974)],
+                NOTIFY_TEXT,
+                default,
+                2,
+            ),
+            (
+                "last exactly fits",
+                vec!["a".repeat(1000), "b".repeat(992)],
+                "",
+                default,
+                1,
+            ),
+            (
+                "last one over",
+                vec!["a".repeat(1000), "b".repeat(993)],
+                "",
+                default,
+                2,
+            ),
+            (
+                "id list exactly fits",
+                vec!["a".repeat(1000), "b".repeat(1000)],
+                "",
+                id_list,
+                1,
+            ),
+            (
+                "id list one over",
+                vec!["a".repeat(1000), "b".repeat(1001)],
+                "",
+                id_list,
+                2,
+            ),
+        ];
+
+        for (name, items, appended_text, options, expected) in cases {
+            let messages = format_as_item_seperated_list(&items.to_ref(), appended_text, options);
+            let chunks = MessageChunks::new(messages, options.markdown)
+                .unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(chunks.0.len(), expected, "{}: {:?}", name, chunks);
+            if !appended_text.is_empty() {
+                assert!(
+                    chunks.0.last().unwrap().ends_with(appended_text),
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
     #[tokio::test]
     async fn format_sub_logs() {
         let mut test_subs = Vec::new();
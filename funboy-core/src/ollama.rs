@@ -1,15 +1,54 @@
+use std::sync::LazyLock;
+
 use ollama_rs::{
     Ollama,
     error::OllamaError,
     generation::completion::{GenerationResponse, request::GenerationRequest},
     models::{LocalModel, ModelInfo, ModelOptions},
 };
+use regex::Regex;
+
+use crate::{
+    template_database::{KeySize, PromptPreset},
+    user_facing_error::UserFacingError,
+};
 
 const DEFAULT_SYSTEM_PROMPT: &str = "";
 const DEFAULT_TEMPLATE: &str = "{{ .Prompt }}";
 const DEFAULT_MAX_PREDICT: u16 = 200;
 const PARAMETER_NOT_SET_TEXT: &str = "Unset";
 pub const MAX_PREDICT: u16 = 2000;
+pub const PROMPT_PLACEHOLDER: &str = "{prompt}";
+pub const USERNAME_PLACEHOLDER: &str = "{username}";
+const GLOBAL_OWNER_ID: KeySize = 0;
+pub const MAX_SYSTEM_PROMPT_LENGTH: usize = 4000;
+pub const MAX_TEMPLATE_LENGTH: usize = 4000;
+
+/// Register references such as `+noun-1` with their name captured
+static REGISTER_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\+([a-z0-9_]+-[0-9]+)\+?").unwrap());
+
+/// Removes the delimiters of register references from text, `+noun-1` becomes `noun-1`
+///
+/// Registers only hold a value within the generation they appear in so a stored reference to one
+/// means nothing but could be picked up if the text ever reached a generation
+pub fn strip_registers(text: &str) -> String {
+    REGISTER_REFERENCE.replace_all(text, "$1").into_owned()
+}
+
+/// Checks text a user stores in their settings is at most limit characters long and strips its
+/// register references
+fn stored_text(setting: &str, text: &str, limit: usize) -> Result<String, UserFacingError> {
+    let length = text.chars().count();
+    if length > limit {
+        return Err(UserFacingError::OllamaSettingTooLong {
+            setting: setting.to_string(),
+            length,
+            limit,
+        });
+    }
+    Ok(strip_registers(text))
+}
 
 #[derive(Copy, Clone)]
 pub struct OllamaParameters {
@@ -61,6 +100,11 @@ impl Default for OllamaParameters {
     }
 }
 
+/// What a user sends Ollama along with their prompts
+///
+/// The system prompt and template are raw text. Only the prompt goes through
+/// [`crate::Funboy::generate`], so templates and FSL in them are sent to Ollama as written and
+/// never run on later generations
 #[derive(Clone)]
 pub struct OllamaSettings {
     system_prompt: String,
@@ -70,16 +114,28 @@ pub struct OllamaSettings {
 }
 
 impl OllamaSettings {
-    pub fn set_system_prompt(&mut self, prompt: &str) {
-        self.system_prompt = prompt.to_string();
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    /// Fails when prompt is longer than [`MAX_SYSTEM_PROMPT_LENGTH`]
+    pub fn set_system_prompt(&mut self, prompt: &str) -> Result<(), UserFacingError> {
+        self.system_prompt = stored_text("system prompt", prompt, MAX_SYSTEM_PROMPT_LENGTH)?;
+        Ok(())
     }
 
     pub fn reset_system_prompt(&mut self) {
         self.system_prompt = DEFAULT_SYSTEM_PROMPT.to_string();
     }
 
-    pub fn set_template(&mut self, template: &str) {
-        self.template = template.to_string();
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Fails when template is longer than [`MAX_TEMPLATE_LENGTH`]
+    pub fn set_template(&mut self, template: &str) -> Result<(), UserFacingError> {
+        self.template = stored_text("template", template, MAX_TEMPLATE_LENGTH)?;
+        Ok(())
     }
 
     pub fn reset_template(&mut self) {
@@ -146,6 +202,103 @@ impl ToString for OllamaSettings {
     }
 }
 
+/// Who a prompt preset belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetScope {
+    Global,
+    User(u64),
+}
+
+impl PresetScope {
+    pub fn owner_id(&self) -> KeySize {
+        match self {
+            PresetScope::Global => GLOBAL_OWNER_ID,
+            PresetScope::User(user_id) => *user_id as KeySize,
+        }
+    }
+
+    pub fn from_owner_id(owner_id: KeySize) -> Self {
+        if owner_id == GLOBAL_OWNER_ID {
+            PresetScope::Global
+        } else {
+            PresetScope::User(owner_id as u64)
+        }
+    }
+}
+
+/// How a rendered preset is combined with the prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetMode {
+    /// The rendered preset replaces the system prompt
+    System,
+    /// The rendered preset replaces the prompt, `{prompt}` marks where the prompt goes
+    Wrap,
+}
+
+impl PromptPreset {
+    pub fn mode(&self) -> PresetMode {
+        if self.wrap_prompt {
+            PresetMode::Wrap
+        } else {
+            PresetMode::System
+        }
+    }
+
+    pub fn scope(&self) -> PresetScope {
+        PresetScope::from_owner_id(self.owner_id)
+    }
+}
+
+/// Fills the `{prompt}` and `{username}` placeholders of a preset body
+pub fn render_preset(body: &str, prompt: &str, username: &str) -> String {
+    body.replace(USERNAME_PLACEHOLDER, username)
+        .replace(PROMPT_PLACEHOLDER, prompt)
+}
+
+/// Picks the preset a user sees out of presets sharing a name
+///
+/// A user's own preset shadows a global preset of the same name
+pub fn resolve_preset(presets: Vec<PromptPreset>, user_id: u64) -> Option<PromptPreset> {
+    let user_owner_id = PresetScope::User(user_id).owner_id();
+    let mut global = None;
+    for preset in presets {
+        if preset.owner_id == user_owner_id {
+            return Some(preset);
+        } else if preset.owner_id == GLOBAL_OWNER_ID {
+            global = Some(preset);
+        }
+    }
+    global
+}
+
+/// Returns the prompt and settings to generate with after applying preset
+pub fn apply_preset(
+    preset: &PromptPreset,
+    prompt: &str,
+    username: &str,
+    settings: &OllamaSettings,
+) -> (String, OllamaSettings) {
+    let rendered = render_preset(&preset.body, prompt, username);
+    let mut settings = settings.clone();
+    match preset.mode() {
+        PresetMode::System => {
+            settings.system_prompt = rendered;
+            (prompt.to_string(), settings)
+        }
+        PresetMode::Wrap => (rendered, settings),
+    }
+}
+
+/// A prompt ready to send to Ollama
+#[derive(Clone)]
+pub struct OllamaRequest {
+    /// The prompt as generated, before a preset wrapped it
+    pub generated_prompt: String,
+    /// What is sent as the prompt
+    pub prompt: String,
+    pub settings: OllamaSettings,
+}
+
 #[derive(Debug, Clone)]
 pub struct OllamaGenerator {
     ollama: Ollama,
@@ -221,3 +374,106 @@ impl Default for OllamaGenerator {
         }
     }
 }
+
+#[cfg(test)]
+mod ollama_test {
+    use super::*;
+
+    fn preset(owner_id: KeySize, body: &str, wrap_prompt: bool) -> PromptPreset {
+        PromptPreset {
+            id: owner_id,
+            name: "preset".to_string(),
+            owner_id,
+            body: body.to_string(),
+            wrap_prompt,
+        }
+    }
+
+    #[test]
+    fn placeholders_are_filled() {
+        assert_eq!(
+            render_preset("{username} asks: {prompt}", "why?", "bob"),
+            "bob asks: why?"
+        );
+        assert_eq!(
+            render_preset("no placeholders", "why?", "bob"),
+            "no placeholders"
+        );
+        // Placeholders inside of the prompt are not filled again
+        assert_eq!(render_preset("{prompt}", "{username}", "bob"), "{username}");
+    }
+
+    #[test]
+    fn user_presets_shadow_global_presets() {
+        let global = preset(GLOBAL_OWNER_ID, "global", false);
+        let own = preset(7, "own", false);
+        let other = preset(8, "other", false);
+
+        let resolved = resolve_preset(vec![global.clone(), own.clone(), other.clone()], 7);
+        assert_eq!(resolved.unwrap().body, "own");
+
+        let resolved = resolve_preset(vec![global.clone(), other.clone()], 7);
+        assert_eq!(resolved.unwrap().body, "global");
+
+        assert!(resolve_preset(vec![other], 7).is_none());
+        assert_eq!(own.scope(), PresetScope::User(7));
+        assert_eq!(global.scope(), PresetScope::Global);
+    }
+
+    #[test]
+    fn system_presets_set_the_system_prompt() {
+        let settings = OllamaSettings::default();
+        let (prompt, settings) = apply_preset(
+            &preset(0, "You are talking to {username}", false),
+            "hello",
+            "bob",
+            &settings,
+        );
+        assert_eq!(prompt, "hello");
+        assert_eq!(settings.system_prompt, "You are talking to bob");
+    }
+
+    #[test]
+    fn stored_text_is_capped_and_loses_registers() {
+        let mut settings = OllamaSettings::default();
+        settings
+            .set_system_prompt("Mention ^noun, +noun-1 and +adj-2+ but not 1 +2 or a+b")
+            .unwrap();
+        assert_eq!(
+            settings.system_prompt(),
+            "Mention ^noun, noun-1 and adj-2 but not 1 +2 or a+b"
+        );
+
+        let too_long = "a".repeat(MAX_SYSTEM_PROMPT_LENGTH + 1);
+        assert_eq!(
+            settings.set_system_prompt(&too_long),
+            Err(UserFacingError::OllamaSettingTooLong {
+                setting: "system prompt".to_string(),
+                length: MAX_SYSTEM_PROMPT_LENGTH + 1,
+                limit: MAX_SYSTEM_PROMPT_LENGTH,
+            })
+        );
+        assert!(settings.system_prompt().starts_with("Mention"));
+
+        settings.set_template("{{ .Prompt }} ^noun").unwrap();
+        assert_eq!(settings.template(), "{{ .Prompt }} ^noun");
+        assert!(
+            settings
+                .set_template(&"a".repeat(MAX_TEMPLATE_LENGTH + 1))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn wrap_presets_wrap_the_prompt() {
+        let settings = OllamaSettings::default();
+        let (prompt, settings) = apply_preset(
+            &preset(0, "Reply in rhyme: {prompt}", true),
+            "hello",
+            "bob",
+            &settings,
+        );
+        assert_eq!(prompt, "Reply in rhyme: hello");
+        assert_eq!(settings.system_prompt, DEFAULT_SYSTEM_PROMPT);
+    }
+}
@@ -0,0 +1,205 @@
+use serde_json::json;
+
+/// How text produced by funboy-core is formatted for the frontend showing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputStyle {
+    /// Text without any markup
+    Plain,
+    /// Discord flavored markdown
+    #[default]
+    Markdown,
+    /// A JSON document
+    Json,
+}
+
+impl OutputStyle {
+    /// Marks text as code, such as a template or command name
+    pub fn code(&self, text: &str) -> String {
+        match self {
+            OutputStyle::Markdown => format!("`{}`", text),
+            OutputStyle::Plain | OutputStyle::Json => text.to_string(),
+        }
+    }
+
+    /// Joins items into a list quoting items that contain whitespace
+    pub fn list<'a>(&self, items: impl IntoIterator<Item = &'a str>) -> String {
+        match self {
+            OutputStyle::Json => json!(items.into_iter().collect::<Vec<_>>()).to_string(),
+            OutputStyle::Plain | OutputStyle::Markdown => items
+                .into_iter()
+                .map(|item| {
+                    if item.contains(char::is_whitespace) {
+                        format!("\"{}\"", item)
+                    } else {
+                        item.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    /// Wraps a message in a JSON error object, other styles return the message unchanged
+    pub(crate) fn error(&self, kind: &str, message: String) -> String {
+        match self {
+            OutputStyle::Json => json!({ "error": kind, "message": message }).to_string(),
+            OutputStyle::Plain | OutputStyle::Markdown => message,
+        }
+    }
+}
+
+/// Values that can be rendered for any [`OutputStyle`]
+pub trait StyledDisplay {
+    fn to_styled_string(&self, style: OutputStyle) -> String;
+}
+
+#[cfg(test)]
+mod output_style_test {
+    use crate::{
+        FunboyError,
+        template_database::{
+            IgnoreReason, IgnoredEntry, Substitute, SubstituteReceipt, Template, TemplateReceipt,
+        },
+        template_substitutor::ExpansionError,
+        user_facing_error::UserFacingError,
+    };
+
+    use super::*;
+
+    fn assert_plain(text: &str) {
+        assert!(!text.contains('`'), "{}", text);
+        assert!(!text.contains('*'), "{}", text);
+    }
+
+    fn assert_json(text: &str) {
+        assert!(
+            serde_json::from_str::<serde_json::Value>(text).is_ok(),
+            "{}",
+            text
+        );
+    }
+
+    fn errors() -> Vec<UserFacingError> {
+        vec![
+            UserFacingError::TemplateNameReserved {
+                name: "print".to_string(),
+            },
+            UserFacingError::TemplateNotFound {
+                name: "noun".to_string(),
+                suggestions: vec!["nouns".to_string(), "pronoun".to_string()],
+            },
+            UserFacingError::TemplateExists {
+                name: "noun".to_string(),
+            },
+            UserFacingError::PresetMissingPlaceholder {
+                placeholder: "{prompt}".to_string(),
+            },
+            UserFacingError::PresetNotFound {
+                name: "pirate".to_string(),
+            },
+            UserFacingError::GenerationTooLarge(ExpansionError::TemplateLimitReached {
+                template: "grow".to_string(),
+                limit: 250,
+            }),
+        ]
+    }
+
+    #[test]
+    fn markdown_is_default() {
+        assert_eq!(OutputStyle::default(), OutputStyle::Markdown);
+        for error in errors() {
+            assert_eq!(
+                error.to_string(),
+                error.to_styled_string(OutputStyle::Markdown)
+            );
+        }
+    }
+
+    #[test]
+    fn user_facing_errors() {
+        for error in errors() {
+            assert_plain(&error.to_styled_string(OutputStyle::Plain));
+            assert_json(&error.to_styled_string(OutputStyle::Json));
+            assert!(error.to_styled_string(OutputStyle::Markdown).contains('`'));
+        }
+    }
+
+    #[test]
+    fn funboy_errors() {
+        let errors = [
+            FunboyError::UserInput(UserFacingError::TemplateExists {
+                name: "noun".to_string(),
+            }),
+            FunboyError::Interpreter("unknown command `foo`".to_string()),
+        ];
+        for error in errors {
+            assert_json(&error.to_styled_string(OutputStyle::Json));
+        }
+
+        let error = FunboyError::UserInput(UserFacingError::TemplateExists {
+            name: "noun".to_string(),
+        });
+        assert_plain(&error.to_styled_string(OutputStyle::Plain));
+        assert_eq!(
+            error.to_string(),
+            error.to_styled_string(OutputStyle::Markdown)
+        );
+    }
+
+    #[test]
+    fn receipts() {
+        let substitute_receipt = SubstituteReceipt {
+            updated: vec![Substitute {
+                id: 1,
+                name: "quick brown fox".to_string(),
+                template_id: 1,
+                enabled: true,
+                kind: "prose".to_string(),
+            }],
+            ignored: vec![
+                IgnoredEntry::new("**bold**", IgnoreReason::Duplicate),
+                IgnoredEntry::new("`code`", IgnoreReason::Empty),
+                IgnoredEntry::new("dog", IgnoreReason::Duplicate),
+            ],
+            warnings: Vec::new(),
+            refused: None,
+            reassigned: Vec::new(),
+        };
+        assert_eq!(
+            substitute_receipt.updated_to_string(),
+            "\"quick brown fox\""
+        );
+        assert_eq!(
+            substitute_receipt.updated_to_styled_string(OutputStyle::Plain),
+            "\"quick brown fox\""
+        );
+        assert_json(&substitute_receipt.updated_to_styled_string(OutputStyle::Json));
+        assert_json(&substitute_receipt.ignored_to_styled_string(OutputStyle::Json));
+        assert_eq!(
+            substitute_receipt.ignored_to_string(),
+            "duplicate: **bold**, dog; empty: `code`"
+        );
+        assert_eq!(
+            substitute_receipt.ignored_to_styled_string(OutputStyle::Json),
+            r#"{"duplicate":["**bold**","dog"],"empty":["`code`"]}"#
+        );
+        assert_eq!(
+            substitute_receipt.ignored_summary(),
+            "2 duplicates, 1 empty"
+        );
+
+        let template_receipt = TemplateReceipt {
+            updated: vec![Template {
+                id: 1,
+                name: "noun".to_string(),
+            }],
+            ignored: vec!["verb".to_string(), "adj".to_string()],
+        };
+        assert_plain(&template_receipt.updated_to_styled_string(OutputStyle::Plain));
+        assert_eq!(template_receipt.ignored_to_string(), "verb, adj");
+        assert_eq!(
+            template_receipt.ignored_to_styled_string(OutputStyle::Json),
+            r#"["verb","adj"]"#
+        );
+    }
+}
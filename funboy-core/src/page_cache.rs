@@ -0,0 +1,358 @@
+//! Pages of substitutes cached per template alongside the version of the template they were read at
+//!
+//! Every mutation bumps the version of the template it touches before its page is dropped, so a page
+//! read before the mutation but cached after it, or a copy of a page held across it, is older than
+//! the template and read as a miss
+
+use std::{
+    collections::HashMap,
+    future::ready,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use moka::{
+    future::{Cache, CacheBuilder},
+    ops::compute::Op,
+};
+
+use crate::template_database::Substitute;
+
+#[derive(Debug, Clone)]
+struct VersionedPage {
+    version: u64,
+    subs: Vec<Substitute>,
+}
+
+#[derive(Debug, Default)]
+struct Versions {
+    /// Last version handed out
+    clock: u64,
+    /// Versions below this are stale for every template
+    floor: u64,
+    templates: HashMap<String, u64>,
+}
+
+impl Versions {
+    fn current(&self, template: &str) -> u64 {
+        self.templates
+            .get(template)
+            .copied()
+            .unwrap_or_default()
+            .max(self.floor)
+    }
+
+    fn bump(&mut self, template: &str) -> u64 {
+        self.clock += 1;
+        self.templates.insert(template.to_string(), self.clock);
+        self.clock
+    }
+}
+
+/// The version of a template and of its cached page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedTemplate {
+    pub template: String,
+    pub version: u64,
+    /// Version the cached page was read at, None when template has no cached page
+    pub page_version: Option<u64>,
+    /// Substitutes in the cached page
+    pub page_len: usize,
+}
+
+impl CachedTemplate {
+    /// Whether the cached page will be refetched the next time it is read
+    pub fn is_stale(&self) -> bool {
+        self.page_version
+            .is_some_and(|page_version| page_version < self.version)
+    }
+}
+
+#[derive(Debug)]
+pub struct PageCache {
+    pages: Cache<String, VersionedPage>,
+    versions: Mutex<Versions>,
+    /// Unix nanoseconds the cache was created at, versions start over with every cache
+    epoch: u64,
+}
+
+impl PageCache {
+    pub fn new(capacity: u64, time_to_live: Duration) -> Self {
+        Self {
+            pages: CacheBuilder::new(capacity)
+                .time_to_live(time_to_live)
+                .build(),
+            versions: Mutex::default(),
+            epoch: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64,
+        }
+    }
+
+    /// Tells this cache's versions apart from those of a cache created before or after it
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// The last version handed out to any template, it moves whenever any template changes
+    pub fn latest_version(&self) -> u64 {
+        self.versions().clock
+    }
+
+    fn versions(&self) -> MutexGuard<'_, Versions> {
+        self.versions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The current version of template, read it before reading a page to insert
+    pub fn version(&self, template: &str) -> u64 {
+        self.versions().current(template)
+    }
+
+    /// The cached page of template unless it is older than template
+    pub async fn get(&self, template: &str) -> Option<Vec<Substitute>> {
+        let page = self.pages.get(template).await?;
+        if page.version < self.version(template) {
+            self.pages.invalidate(template).await;
+            return None;
+        }
+        Some(page.subs)
+    }
+
+    /// Caches subs read when template was at version
+    ///
+    /// A page already older than template isn't cached, one that becomes older before it is
+    /// cached is caught by [`PageCache::get`]
+    pub async fn insert(&self, template: &str, version: u64, subs: Vec<Substitute>) {
+        if version < self.version(template) {
+            return;
+        }
+        self.pages
+            .insert(template.to_string(), VersionedPage { version, subs })
+            .await;
+    }
+
+    /// Bumps template so every page read before now is stale and drops its cached page
+    pub async fn invalidate(&self, template: &str) {
+        self.versions().bump(template);
+        self.pages.invalidate(template).await;
+    }
+
+    /// Makes every page read before now stale and drops all cached pages
+    pub fn invalidate_all(&self) {
+        let mut versions = self.versions();
+        versions.clock += 1;
+        versions.floor = versions.clock;
+        versions.templates.clear();
+        drop(versions);
+        self.pages.invalidate_all();
+    }
+
+    /// Bumps template and applies patch to its cached page so changing a single substitute doesn't
+    /// refetch the page
+    ///
+    /// Only a page that was current before the bump is patched, a stale one is dropped instead as
+    /// is a page patch leaves empty
+    pub async fn patch(&self, template: &str, patch: impl FnOnce(&mut Vec<Substitute>)) {
+        let (previous, version) = {
+            let mut versions = self.versions();
+            (versions.current(template), versions.bump(template))
+        };
+        self.pages
+            .entry_by_ref(template)
+            .and_compute_with(|entry| {
+                let op = match entry.map(|entry| entry.into_value()) {
+                    Some(mut page) if page.version >= previous => {
+                        patch(&mut page.subs);
+                        if page.subs.is_empty() {
+                            Op::Remove
+                        } else {
+                            page.version = version;
+                            Op::Put(page)
+                        }
+                    }
+                    Some(_) => Op::Remove,
+                    None => Op::Nop,
+                };
+                ready(op)
+            })
+            .await;
+    }
+
+    /// Every template with a cached page or a version, ordered by name
+    pub fn debug(&self) -> Vec<CachedTemplate> {
+        let versions = self.versions();
+        let mut templates: HashMap<String, CachedTemplate> = versions
+            .templates
+            .keys()
+            .map(|template| {
+                let cached = CachedTemplate {
+                    template: template.clone(),
+                    version: versions.current(template),
+                    page_version: None,
+                    page_len: 0,
+                };
+                (template.clone(), cached)
+            })
+            .collect();
+        for (template, page) in self.pages.iter() {
+            let cached = templates
+                .entry(template.to_string())
+                .or_insert_with(|| CachedTemplate {
+                    template: template.to_string(),
+                    version: versions.current(&template),
+                    page_version: None,
+                    page_len: 0,
+                });
+            cached.page_version = Some(page.version);
+            cached.page_len = page.subs.len();
+        }
+        drop(versions);
+
+        let mut templates: Vec<CachedTemplate> = templates.into_values().collect();
+        templates.sort_by(|a, b| a.template.cmp(&b.template));
+        templates
+    }
+}
+
+#[cfg(test)]
+mod page_cache_test {
+    use super::*;
+
+    fn page(names: &[&str]) -> Vec<Substitute> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| Substitute {
+                id: id as _,
+                name: name.to_string(),
+                template_id: 1,
+                enabled: true,
+                kind: "prose".to_string(),
+            })
+            .collect()
+    }
+
+    fn cache() -> PageCache {
+        PageCache::new(20, Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn held_page_is_stale_after_mutation() {
+        let cache = cache();
+        let version = cache.version("fruit");
+        cache.insert("fruit", version, page(&["apple"])).await;
+        let held = cache.get("fruit").await.unwrap();
+
+        cache.invalidate("fruit").await;
+        // A task that read the page before the mutation caches it again afterwards
+        cache.insert("fruit", version, held).await;
+        assert!(cache.get("fruit").await.is_none());
+
+        let version = cache.version("fruit");
+        cache
+            .insert("fruit", version, page(&["apple", "pear"]))
+            .await;
+        assert!(cache.get("fruit").await.is_some_and(|subs| subs.len() == 2));
+    }
+
+    #[tokio::test]
+    async fn page_read_across_mutation_is_a_miss() {
+        let cache = cache();
+        let version = cache.version("fruit");
+        cache.invalidate("fruit").await;
+
+        // Skip the check on insert to simulate the mutation landing just after it
+        cache
+            .pages
+            .insert(
+                "fruit".to_string(),
+                VersionedPage {
+                    version,
+                    subs: page(&["apple"]),
+                },
+            )
+            .await;
+        assert!(cache.get("fruit").await.is_none());
+        assert!(cache.pages.get("fruit").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn invalidate_all_stales_every_template() {
+        let cache = cache();
+        let fruit = cache.version("fruit");
+        let veg = cache.version("veg");
+        cache.invalidate("fruit").await;
+        cache.invalidate_all();
+
+        cache.insert("fruit", fruit, page(&["apple"])).await;
+        cache.insert("veg", veg, page(&["leek"])).await;
+        assert!(cache.get("fruit").await.is_none());
+        assert!(cache.get("veg").await.is_none());
+        assert!(cache.debug().is_empty());
+    }
+
+    #[tokio::test]
+    async fn patches_only_apply_to_current_pages() {
+        let cache = cache();
+        let version = cache.version("fruit");
+        cache
+            .insert("fruit", version, page(&["apple", "pear"]))
+            .await;
+
+        cache
+            .patch("fruit", |subs| subs.retain(|sub| sub.name != "pear"))
+            .await;
+        assert!(
+            cache
+                .get("fruit")
+                .await
+                .is_some_and(|subs| subs.len() == 1 && subs[0].name == "apple")
+        );
+
+        let stale = cache.version("fruit");
+        cache.invalidate("fruit").await;
+        cache
+            .pages
+            .insert(
+                "fruit".to_string(),
+                VersionedPage {
+                    version: stale,
+                    subs: page(&["apple"]),
+                },
+            )
+            .await;
+        cache
+            .patch("fruit", |subs| subs[0].name = "fig".into())
+            .await;
+        assert!(cache.pages.get("fruit").await.is_none());
+
+        let version = cache.version("fruit");
+        cache.insert("fruit", version, page(&["apple"])).await;
+        cache.patch("fruit", |subs| subs.clear()).await;
+        assert!(cache.get("fruit").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn debug_lists_versions_and_pages() {
+        let cache = cache();
+        let version = cache.version("fruit");
+        cache
+            .insert("fruit", version, page(&["apple", "pear"]))
+            .await;
+        cache.invalidate("veg").await;
+
+        let debug = cache.debug();
+        assert_eq!(debug.len(), 2);
+        assert_eq!(debug[0].template, "fruit");
+        assert_eq!(debug[0].page_version, Some(0));
+        assert_eq!(debug[0].page_len, 2);
+        assert!(!debug[0].is_stale());
+        assert_eq!(debug[1].template, "veg");
+        assert_eq!(debug[1].version, 1);
+        assert_eq!(debug[1].page_version, None);
+
+        cache.versions().bump("fruit");
+        assert!(cache.debug()[0].is_stale());
+    }
+}
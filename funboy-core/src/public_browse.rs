@@ -0,0 +1,235 @@
+//! Read-only HTML listings of templates and their substitutes for people browsing without the bot
+//!
+//! Archived templates and disabled substitutes are never listed. Pages carry weak ETags built from
+//! the template versions stored in the database so a client can revalidate without the listing
+//! being read again, whichever process sharing the database serves it
+
+use crate::template_database::PublicTemplate;
+
+pub const PUBLIC_TEMPLATES_PATH: &str = "/public/templates";
+
+/// One page of the enabled substitutes of a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicPage {
+    pub template: String,
+    pub substitutes: Vec<String>,
+    /// Starts at 1
+    pub page: usize,
+    pub page_count: usize,
+    pub page_size: usize,
+}
+
+impl PublicPage {
+    /// How many pages count substitutes fill, an empty template still has a page
+    pub fn page_count(count: usize, page_size: usize) -> usize {
+        count.div_ceil(page_size).max(1)
+    }
+}
+
+/// Escapes text so it can go in HTML element content or a quoted attribute
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+fn template_path(template: &str) -> String {
+    format!("{}/{}", PUBLIC_TEMPLATES_PATH, escape_html(template))
+}
+
+fn page_path(template: &str, page: usize) -> String {
+    format!("{}?page={}", template_path(template), page)
+}
+
+/// Lists templates with how many substitutes each has, linking to their pages
+pub fn render_template_list(templates: &[PublicTemplate]) -> String {
+    let mut body = String::from("<h1>Templates</h1>\n");
+    if templates.is_empty() {
+        body.push_str("<p>No templates yet.</p>\n");
+    } else {
+        body.push_str("<ul>\n");
+        for template in templates {
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> ({} {})</li>\n",
+                template_path(&template.name),
+                escape_html(&template.name),
+                template.substitute_count,
+                if template.substitute_count == 1 {
+                    "substitute"
+                } else {
+                    "substitutes"
+                }
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+    document("Templates", &body)
+}
+
+/// Lists a page of substitutes numbered across pages with links to the pages around it
+pub fn render_template_page(page: &PublicPage) -> String {
+    let mut body = format!(
+        "<h1>{}</h1>\n<p><a href=\"{}\">All templates</a></p>\n",
+        escape_html(&page.template),
+        PUBLIC_TEMPLATES_PATH
+    );
+    if page.substitutes.is_empty() {
+        body.push_str("<p>No substitutes yet.</p>\n");
+    } else {
+        body.push_str(&format!(
+            "<ol start=\"{}\">\n",
+            (page.page - 1) * page.page_size + 1
+        ));
+        for sub in &page.substitutes {
+            body.push_str(&format!("<li>{}</li>\n", escape_html(sub)));
+        }
+        body.push_str("</ol>\n");
+    }
+
+    body.push_str("<nav>");
+    if page.page > 1 {
+        body.push_str(&format!(
+            "<a rel=\"prev\" href=\"{}\">Previous</a> ",
+            page_path(&page.template, page.page - 1)
+        ));
+    }
+    body.push_str(&format!("Page {} of {}", page.page, page.page_count));
+    if page.page < page.page_count {
+        body.push_str(&format!(
+            " <a rel=\"next\" href=\"{}\">Next</a>",
+            page_path(&page.template, page.page + 1)
+        ));
+    }
+    body.push_str("</nav>\n");
+
+    document(&page.template, &body)
+}
+
+/// Weak ETag of a page rendered while count templates existed and the newest one was at version
+pub fn etag(count: u64, version: u64, page: usize) -> String {
+    format!("W/\"{:x}-{:x}-{}\"", count, version, page)
+}
+
+/// Whether an If-None-Match header lists etag, so the client's copy is still current
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+#[cfg(test)]
+mod public_browse_test {
+    use crate::template_database::KeySize;
+
+    use super::*;
+
+    fn template(name: &str, substitute_count: KeySize) -> PublicTemplate {
+        PublicTemplate {
+            name: name.to_string(),
+            substitute_count,
+        }
+    }
+
+    fn page(number: usize, page_count: usize) -> PublicPage {
+        PublicPage {
+            template: "noun".to_string(),
+            substitutes: vec!["fox".to_string(), "<b>dog</b>".to_string()],
+            page: number,
+            page_count,
+            page_size: 2,
+        }
+    }
+
+    #[test]
+    fn template_list_links_every_template() {
+        let html = render_template_list(&[template("adj", 1), template("noun", 3)]);
+        assert!(html.contains("<title>Templates</title>"));
+        assert!(html.contains("<li><a href=\"/public/templates/adj\">adj</a> (1 substitute)</li>"));
+        assert!(
+            html.contains("<li><a href=\"/public/templates/noun\">noun</a> (3 substitutes)</li>")
+        );
+
+        assert!(render_template_list(&[]).contains("<p>No templates yet.</p>"));
+    }
+
+    #[test]
+    fn substitutes_are_escaped_and_numbered_across_pages() {
+        let html = render_template_page(&page(2, 3));
+        assert!(html.contains("<h1>noun</h1>"));
+        assert!(html.contains("<ol start=\"3\">"));
+        assert!(html.contains("<li>&lt;b&gt;dog&lt;/b&gt;</li>"));
+        assert!(!html.contains("<b>dog</b>"));
+    }
+
+    #[test]
+    fn pagination_links() {
+        let first = render_template_page(&page(1, 3));
+        assert!(!first.contains("rel=\"prev\""));
+        assert!(first.contains(
+            "<nav>Page 1 of 3 <a rel=\"next\" href=\"/public/templates/noun?page=2\">Next</a></nav>"
+        ));
+
+        let middle = render_template_page(&page(2, 3));
+        assert!(
+            middle.contains("<a rel=\"prev\" href=\"/public/templates/noun?page=1\">Previous</a>")
+        );
+        assert!(middle.contains("<a rel=\"next\" href=\"/public/templates/noun?page=3\">Next</a>"));
+
+        let last = render_template_page(&page(3, 3));
+        assert!(last.contains("rel=\"prev\""));
+        assert!(!last.contains("rel=\"next\""));
+
+        assert_eq!(PublicPage::page_count(0, 100), 1);
+        assert_eq!(PublicPage::page_count(100, 100), 1);
+        assert_eq!(PublicPage::page_count(101, 100), 2);
+    }
+
+    #[test]
+    fn etags_change_with_versions() {
+        let current = etag(7, 3, 1);
+        assert_eq!(current, "W/\"7-3-1\"");
+        assert_ne!(current, etag(7, 4, 1));
+        assert_ne!(current, etag(8, 3, 1));
+        assert_ne!(current, etag(7, 3, 2));
+
+        assert!(etag_matches(&current, &current));
+        assert!(etag_matches("\"7-3-1\"", &current));
+        assert!(etag_matches("W/\"1-1-1\", W/\"7-3-1\"", &current));
+        assert!(etag_matches("*", &current));
+        assert!(!etag_matches(&etag(7, 2, 1), &current));
+    }
+
+    #[test]
+    fn etags_match_plain_tag_lists() {
+        let current = etag(7, 3, 1);
+        assert!(etag_matches("\"1-1-1\", \"7-3-1\"", &current));
+        assert!(etag_matches("\"1-1-1\",\"7-3-1\"", &current));
+        assert!(!etag_matches("\"1-1-1\", \"7-3-2\"", &current));
+        assert!(!etag_matches("\"7-3\"", &current));
+    }
+
+    #[test]
+    fn etags_match_any() {
+        assert!(etag_matches("*", &etag(0, 0, 0)));
+        assert!(etag_matches(" * ", &etag(7, 3, 1)));
+        assert!(etag_matches("\"1-1-1\", *", &etag(7, 3, 1)));
+    }
+}
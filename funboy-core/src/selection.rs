@@ -0,0 +1,365 @@
+//! Per template strategies for picking the next substitute
+//!
+//! Round robin cursors and least recently used stamps change on every pick so they are kept in
+//! memory and written to the database in batches by [`crate::Funboy::flush_selection_state`].
+//! Picks made since the last flush are lost when the bot stops, which at worst repeats part of a
+//! cycle.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{
+    distribution::pick_substitute,
+    template_database::{KeySize, Substitute},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SelectionStrategy {
+    /// Every enabled substitute is equally likely on every pick
+    #[default]
+    Random,
+    /// Enabled substitutes are picked in order of their ids, every one once per cycle
+    RoundRobin,
+    /// The enabled substitute picked longest ago, or never, is picked next
+    LeastRecentlyUsed,
+}
+
+impl SelectionStrategy {
+    pub const ALL: [SelectionStrategy; 3] = [
+        SelectionStrategy::Random,
+        SelectionStrategy::RoundRobin,
+        SelectionStrategy::LeastRecentlyUsed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SelectionStrategy::Random => "random",
+            SelectionStrategy::RoundRobin => "round_robin",
+            SelectionStrategy::LeastRecentlyUsed => "least_recently_used",
+        }
+    }
+
+    /// Reads a strategy stored with [`SelectionStrategy::as_str`]
+    pub fn parse(strategy: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == strategy)
+    }
+}
+
+impl Display for SelectionStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where a template is in its cycle
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectionState {
+    /// Substitute round robin picked last
+    pub cursor: Option<KeySize>,
+    /// When least recently used last picked each substitute in unix milliseconds, stamps only
+    /// increase so picks within the same millisecond are still ordered
+    pub last_used: HashMap<KeySize, i64>,
+    cursor_changed: bool,
+    used_changed: HashSet<KeySize>,
+}
+
+impl SelectionState {
+    pub fn new(cursor: Option<KeySize>, last_used: HashMap<KeySize, i64>) -> Self {
+        Self {
+            cursor,
+            last_used,
+            ..Default::default()
+        }
+    }
+
+    /// Picks the next of subs, which are ordered by id, at now in unix milliseconds
+    pub fn pick<'a>(
+        &mut self,
+        strategy: SelectionStrategy,
+        subs: &'a [Substitute],
+        now: i64,
+    ) -> Option<&'a Substitute> {
+        match strategy {
+            SelectionStrategy::Random => pick_substitute(subs),
+            SelectionStrategy::RoundRobin => {
+                let sub = match self.cursor {
+                    Some(cursor) => subs
+                        .iter()
+                        .find(|sub| sub.id > cursor)
+                        .or_else(|| subs.first()),
+                    None => subs.first(),
+                }?;
+                self.cursor = Some(sub.id);
+                self.cursor_changed = true;
+                Some(sub)
+            }
+            SelectionStrategy::LeastRecentlyUsed => {
+                let sub = subs
+                    .iter()
+                    .min_by_key(|sub| (self.last_used.get(&sub.id).copied(), sub.id))?;
+                let latest = self.last_used.values().max().copied().unwrap_or_default();
+                self.last_used.insert(sub.id, now.max(latest + 1));
+                self.used_changed.insert(sub.id);
+                Some(sub)
+            }
+        }
+    }
+
+    /// The cursor if it changed and the stamps that changed since the last call
+    pub fn take_changes(&mut self) -> Option<SelectionChanges> {
+        if !self.cursor_changed && self.used_changed.is_empty() {
+            return None;
+        }
+        let cursor = std::mem::take(&mut self.cursor_changed).then_some(self.cursor);
+        let last_used = std::mem::take(&mut self.used_changed)
+            .into_iter()
+            .filter_map(|id| self.last_used.get(&id).map(|stamp| (id, *stamp)))
+            .collect();
+        Some(SelectionChanges { cursor, last_used })
+    }
+
+    /// Marks changes that failed to be written as changed again
+    pub fn restore(&mut self, changes: &SelectionChanges) {
+        self.cursor_changed |= changes.cursor.is_some();
+        self.used_changed
+            .extend(changes.last_used.iter().map(|(id, _)| *id));
+    }
+}
+
+/// Selection state waiting to be written to the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionChanges {
+    /// Some when the cursor moved
+    pub cursor: Option<Option<KeySize>>,
+    pub last_used: Vec<(KeySize, i64)>,
+}
+
+/// What [`SelectionStates::pick`] found for a template
+#[derive(Debug, Clone)]
+pub enum Selection {
+    /// The template isn't loaded or changed since it was, load it again
+    Stale,
+    /// Pick the usual way, the template is random or has no enabled substitutes
+    Random,
+    Picked(Substitute),
+}
+
+#[derive(Debug)]
+struct TemplateSelection {
+    /// Version of the template in the page cache when it was loaded
+    version: u64,
+    strategy: SelectionStrategy,
+    subs: Vec<Substitute>,
+    state: SelectionState,
+}
+
+/// The strategy, enabled substitutes and state of each template that was picked from
+#[derive(Debug, Default)]
+pub struct SelectionStates {
+    templates: Mutex<HashMap<String, TemplateSelection>>,
+}
+
+impl SelectionStates {
+    fn templates(&self) -> MutexGuard<'_, HashMap<String, TemplateSelection>> {
+        self.templates
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Picks from template unless it was loaded before version
+    pub fn pick(&self, template: &str, version: u64, now: i64) -> Selection {
+        let mut templates = self.templates();
+        let Some(selection) = templates.get_mut(template) else {
+            return Selection::Stale;
+        };
+        if selection.version < version {
+            return Selection::Stale;
+        }
+        if selection.strategy == SelectionStrategy::Random {
+            return Selection::Random;
+        }
+        match selection
+            .state
+            .pick(selection.strategy, &selection.subs, now)
+        {
+            Some(sub) => Selection::Picked(sub.clone()),
+            None => Selection::Random,
+        }
+    }
+
+    /// Loads template at version
+    ///
+    /// State already in memory is kept over stored since it may not have been flushed yet
+    pub fn load(
+        &self,
+        template: &str,
+        version: u64,
+        strategy: SelectionStrategy,
+        subs: Vec<Substitute>,
+        stored: SelectionState,
+    ) {
+        let mut templates = self.templates();
+        let state = match templates.remove(template) {
+            Some(previous) => previous.state,
+            None => stored,
+        };
+        templates.insert(
+            template.to_string(),
+            TemplateSelection {
+                version,
+                strategy,
+                subs,
+                state,
+            },
+        );
+    }
+
+    /// Takes the changes of every template, see [`SelectionState::take_changes`]
+    pub fn take_changes(&self) -> Vec<(String, SelectionChanges)> {
+        self.templates()
+            .iter_mut()
+            .filter_map(|(template, selection)| {
+                selection
+                    .state
+                    .take_changes()
+                    .map(|changes| (template.clone(), changes))
+            })
+            .collect()
+    }
+
+    pub fn restore(&self, template: &str, changes: &SelectionChanges) {
+        if let Some(selection) = self.templates().get_mut(template) {
+            selection.state.restore(changes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod selection_test {
+    use super::*;
+
+    fn subs(ids: &[KeySize]) -> Vec<Substitute> {
+        ids.iter()
+            .map(|id| Substitute {
+                id: *id,
+                name: format!("sub{}", id),
+                template_id: 1,
+                enabled: true,
+                kind: "prose".to_string(),
+            })
+            .collect()
+    }
+
+    fn picks(
+        state: &mut SelectionState,
+        strategy: SelectionStrategy,
+        subs: &[Substitute],
+        count: usize,
+    ) -> Vec<KeySize> {
+        (0..count)
+            .map(|_| state.pick(strategy, subs, 1000).unwrap().id)
+            .collect()
+    }
+
+    #[test]
+    fn strategies_round_trip() {
+        for strategy in SelectionStrategy::ALL {
+            assert_eq!(SelectionStrategy::parse(strategy.as_str()), Some(strategy));
+        }
+        assert_eq!(SelectionStrategy::parse("weighted"), None);
+    }
+
+    #[test]
+    fn round_robin_cycles_in_id_order() {
+        let subs = subs(&[3, 7, 9]);
+        let mut state = SelectionState::default();
+        assert_eq!(
+            picks(&mut state, SelectionStrategy::RoundRobin, &subs, 7),
+            vec![3, 7, 9, 3, 7, 9, 3]
+        );
+
+        // A cursor on a substitute that was deleted or disabled continues after it
+        let mut state = SelectionState::new(Some(8), HashMap::new());
+        assert_eq!(
+            picks(&mut state, SelectionStrategy::RoundRobin, &subs, 2),
+            vec![9, 3]
+        );
+        assert!(
+            SelectionState::default()
+                .pick(SelectionStrategy::RoundRobin, &[], 0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn least_recently_used_picks_stalest_first() {
+        let subs = subs(&[1, 2, 3, 4]);
+        let mut state = SelectionState::new(None, HashMap::from([(1, 500), (2, 100), (4, 300)]));
+
+        // 3 was never picked, then the oldest stamps go first
+        assert_eq!(
+            picks(&mut state, SelectionStrategy::LeastRecentlyUsed, &subs, 6),
+            vec![3, 2, 4, 1, 3, 2]
+        );
+        // Picks in the same millisecond still get increasing stamps
+        assert_eq!(state.last_used[&3], 1004);
+        assert_eq!(state.last_used[&2], 1005);
+    }
+
+    #[test]
+    fn changes_are_taken_once_and_restored() {
+        let subs = subs(&[1, 2]);
+        let mut state = SelectionState::default();
+        assert!(state.take_changes().is_none());
+
+        state.pick(SelectionStrategy::RoundRobin, &subs, 0);
+        state.pick(SelectionStrategy::LeastRecentlyUsed, &subs, 10);
+        let changes = state.take_changes().unwrap();
+        assert_eq!(changes.cursor, Some(Some(1)));
+        assert_eq!(changes.last_used, vec![(1, 10)]);
+        assert!(state.take_changes().is_none());
+
+        state.restore(&changes);
+        assert_eq!(state.take_changes(), Some(changes));
+    }
+
+    #[test]
+    fn states_reload_when_stale() {
+        let states = SelectionStates::default();
+        assert!(matches!(states.pick("quiz", 0, 0), Selection::Stale));
+
+        states.load(
+            "quiz",
+            1,
+            SelectionStrategy::RoundRobin,
+            subs(&[1, 2]),
+            SelectionState::default(),
+        );
+        assert!(matches!(states.pick("quiz", 2, 0), Selection::Stale));
+        assert!(matches!(states.pick("quiz", 1, 0), Selection::Picked(sub) if sub.id == 1));
+
+        // Reloading keeps the cursor that wasn't flushed over the stored one
+        states.load(
+            "quiz",
+            2,
+            SelectionStrategy::RoundRobin,
+            subs(&[1, 2, 3]),
+            SelectionState::new(None, HashMap::new()),
+        );
+        assert!(matches!(states.pick("quiz", 2, 0), Selection::Picked(sub) if sub.id == 2));
+
+        states.load(
+            "quiz",
+            3,
+            SelectionStrategy::Random,
+            subs(&[1]),
+            SelectionState::default(),
+        );
+        assert!(matches!(states.pick("quiz", 3, 0), Selection::Random));
+    }
+}
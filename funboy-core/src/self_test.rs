@@ -0,0 +1,379 @@
+//! Generations run after a deploy to check templates, registers and FSL commands still work
+//! end to end
+
+use std::{
+    fmt::Display,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::FunboyError;
+
+/// Template the built-in cases generate from, it needs at least one enabled substitute
+pub const DEFAULT_HEALTH_TEMPLATE: &str = "health_check";
+pub const DEFAULT_CASE_TIMEOUT_MS: u64 = 5000;
+
+/// What the output of a case has to be for it to pass
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum Expectation {
+    /// Output is not empty and differs from the input, so something was resolved
+    #[default]
+    Resolved,
+    Exact(String),
+    Contains(String),
+    /// Output differs from the input and split on the separator has identical non-empty parts
+    SameParts(String),
+}
+
+impl Expectation {
+    /// Err describes how output missed the expectation
+    pub fn check(&self, input: &str, output: &str) -> Result<(), String> {
+        match self {
+            Expectation::Resolved if output.trim().is_empty() => {
+                Err("output was empty".to_string())
+            }
+            Expectation::Resolved | Expectation::SameParts(_) if output == input => {
+                Err("output was the same as the input".to_string())
+            }
+            Expectation::Exact(expected) if output != expected => {
+                Err(format!("expected {:?}, got {:?}", expected, output))
+            }
+            Expectation::Contains(expected) if !output.contains(expected.as_str()) => {
+                Err(format!("expected {:?} in {:?}", expected, output))
+            }
+            Expectation::SameParts(separator) => {
+                let mut parts = output.split(separator.as_str());
+                let first = parts.next().unwrap_or_default();
+                if first.is_empty() || parts.any(|part| part != first) {
+                    Err(format!(
+                        "expected identical parts separated by {:?}, got {:?}",
+                        separator, output
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfTestCase {
+    pub name: String,
+    pub input: String,
+    #[serde(default)]
+    pub expect: Expectation,
+    /// Overrides the timeout of the spec for this case
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+impl SelfTestCase {
+    fn new(name: &str, input: String, expect: Expectation) -> Self {
+        Self {
+            name: name.to_string(),
+            input,
+            expect,
+            timeout_ms: None,
+        }
+    }
+}
+
+/// The cases a self test runs, read from JSON such as
+/// `{"health_template": "health_check", "timeout_ms": 5000, "cases": [{"name": "sum", "input": "{print(add(1, 2))}", "expect": {"kind": "exact", "value": "3"}}]}`
+///
+/// Every field is optional, the built-in cases run when cases is left out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfTestSpec {
+    #[serde(default = "default_health_template")]
+    pub health_template: String,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub cases: Option<Vec<SelfTestCase>>,
+}
+
+fn default_health_template() -> String {
+    DEFAULT_HEALTH_TEMPLATE.to_string()
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_CASE_TIMEOUT_MS
+}
+
+impl Default for SelfTestSpec {
+    fn default() -> Self {
+        Self {
+            health_template: default_health_template(),
+            timeout_ms: default_timeout_ms(),
+            cases: None,
+        }
+    }
+}
+
+impl SelfTestSpec {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// The configured cases, or cases covering templates, registers, FSL arithmetic and text
+    /// commands and get_sub against the health template
+    pub fn cases(&self) -> Vec<SelfTestCase> {
+        if let Some(cases) = &self.cases {
+            return cases.clone();
+        }
+        let template = &self.health_template;
+        vec![
+            SelfTestCase::new("template", format!("^{}", template), Expectation::Resolved),
+            SelfTestCase::new(
+                "register",
+                format!("+{0}-1|+{0}-1", template),
+                Expectation::SameParts("|".to_string()),
+            ),
+            SelfTestCase::new(
+                "arithmetic",
+                "{print(add(2, 3))}".to_string(),
+                Expectation::Exact("5".to_string()),
+            ),
+            SelfTestCase::new(
+                "text",
+                "{print(capitalize(concat(\"heal\", \"th\")))}".to_string(),
+                Expectation::Exact("Health".to_string()),
+            ),
+            SelfTestCase::new(
+                "get_sub",
+                format!("{{print(get_sub(\"{}\"))}}", template),
+                Expectation::Resolved,
+            ),
+        ]
+    }
+
+    pub fn timeout(&self, case: &SelfTestCase) -> Duration {
+        Duration::from_millis(case.timeout_ms.unwrap_or(self.timeout_ms))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseOutcome {
+    Passed,
+    /// Generation succeeded but missed its expectation
+    Unexpected(String),
+    Failed(String),
+    TimedOut(Duration),
+}
+
+impl Display for CaseOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaseOutcome::Passed => write!(f, "passed"),
+            CaseOutcome::Unexpected(reason) => write!(f, "unexpected output, {}", reason),
+            CaseOutcome::Failed(error) => write!(f, "failed, {}", error),
+            CaseOutcome::TimedOut(timeout) => {
+                write!(f, "timed out after {}ms", timeout.as_millis())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseResult {
+    pub name: String,
+    pub outcome: CaseOutcome,
+    pub duration: Duration,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.outcome == CaseOutcome::Passed
+    }
+}
+
+/// Runs generation for case, giving up once timeout passes
+pub async fn run_case(
+    case: &SelfTestCase,
+    timeout: Duration,
+    generation: impl Future<Output = Result<String, FunboyError>>,
+) -> CaseResult {
+    let started = Instant::now();
+    let outcome = match tokio::time::timeout(timeout, generation).await {
+        Ok(Ok(output)) => match case.expect.check(&case.input, &output) {
+            Ok(()) => CaseOutcome::Passed,
+            Err(reason) => CaseOutcome::Unexpected(reason),
+        },
+        Ok(Err(e)) => CaseOutcome::Failed(e.to_string().replace('\n', " ")),
+        Err(_) => CaseOutcome::TimedOut(timeout),
+    };
+    CaseResult {
+        name: case.name.clone(),
+        outcome,
+        duration: started.elapsed(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    pub results: Vec<CaseResult>,
+    pub duration: Duration,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|result| result.passed()).count()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.results.iter().filter(|result| !result.passed())
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.results.iter().all(CaseResult::passed)
+    }
+
+    /// A line for the totals followed by a line per case
+    pub fn summary(&self) -> String {
+        let mut lines = vec![format!(
+            "self test {}: {}/{} cases passed in {}ms",
+            if self.is_healthy() {
+                "passed"
+            } else {
+                "failed"
+            },
+            self.passed(),
+            self.results.len(),
+            self.duration.as_millis()
+        )];
+        lines.extend(self.results.iter().map(|result| {
+            format!(
+                "{} {} ({}ms)",
+                result.name,
+                result.outcome,
+                result.duration.as_millis()
+            )
+        }));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod self_test_test {
+    use std::future::{pending, ready};
+
+    use super::*;
+
+    fn case(expect: Expectation) -> SelfTestCase {
+        SelfTestCase::new("case", "^input".to_string(), expect)
+    }
+
+    #[test]
+    fn expectations() {
+        assert!(Expectation::Resolved.check("^a", "apple").is_ok());
+        assert!(Expectation::Resolved.check("^a", "^a").is_err());
+        assert!(Expectation::Resolved.check("^a", " ").is_err());
+        assert!(Expectation::Exact("5".into()).check("", "5").is_ok());
+        assert!(Expectation::Exact("5".into()).check("", "6").is_err());
+        assert!(
+            Expectation::Contains("pp".into())
+                .check("", "apple")
+                .is_ok()
+        );
+        assert!(
+            Expectation::SameParts("|".into())
+                .check("", "fox|fox")
+                .is_ok()
+        );
+        assert!(
+            Expectation::SameParts("|".into())
+                .check("", "fox|dog")
+                .is_err()
+        );
+        assert!(Expectation::SameParts("|".into()).check("", "|").is_err());
+        assert!(
+            Expectation::SameParts("|".into())
+                .check("+a|+a", "+a|+a")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn spec_from_json_falls_back_to_defaults() {
+        let spec = SelfTestSpec::from_json("{}").unwrap();
+        assert_eq!(spec, SelfTestSpec::default());
+        assert_eq!(spec.cases().len(), 5);
+
+        let spec = SelfTestSpec::from_json(
+            r#"{"timeout_ms": 100, "cases": [
+                {"name": "sum", "input": "{print(add(1, 2))}", "expect": {"kind": "exact", "value": "3"}},
+                {"name": "slow", "input": "^slow", "timeout_ms": 900}
+            ]}"#,
+        )
+        .unwrap();
+        let cases = spec.cases();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].expect, Expectation::Exact("3".into()));
+        assert_eq!(cases[1].expect, Expectation::Resolved);
+        assert_eq!(spec.timeout(&cases[0]), Duration::from_millis(100));
+        assert_eq!(spec.timeout(&cases[1]), Duration::from_millis(900));
+    }
+
+    #[tokio::test]
+    async fn cases_time_out_on_their_own() {
+        let timeout = Duration::from_millis(20);
+        let result = run_case(&case(Expectation::Resolved), timeout, pending()).await;
+        assert_eq!(result.outcome, CaseOutcome::TimedOut(timeout));
+        assert!(result.duration >= timeout);
+
+        let result = run_case(
+            &case(Expectation::Resolved),
+            timeout,
+            ready(Ok("apple".to_string())),
+        )
+        .await;
+        assert_eq!(result.outcome, CaseOutcome::Passed);
+    }
+
+    #[tokio::test]
+    async fn report_aggregates_cases() {
+        let timeout = Duration::from_secs(1);
+        let results = vec![
+            run_case(
+                &case(Expectation::Resolved),
+                timeout,
+                ready(Ok("apple".to_string())),
+            )
+            .await,
+            run_case(
+                &case(Expectation::Exact("5".into())),
+                timeout,
+                ready(Ok("6".to_string())),
+            )
+            .await,
+            run_case(
+                &case(Expectation::Resolved),
+                timeout,
+                ready(Err(FunboyError::Database("pool closed".to_string()))),
+            )
+            .await,
+        ];
+        let report = SelfTestReport {
+            results,
+            duration: Duration::from_millis(3),
+        };
+
+        assert!(!report.is_healthy());
+        assert_eq!(report.passed(), 1);
+        assert_eq!(report.failures().count(), 2);
+        assert!(matches!(
+            report.results[1].outcome,
+            CaseOutcome::Unexpected(_)
+        ));
+        assert!(matches!(report.results[2].outcome, CaseOutcome::Failed(_)));
+
+        let summary = report.summary();
+        assert!(summary.starts_with("self test failed: 1/3 cases passed"));
+        assert!(summary.contains("pool closed"), "{}", summary);
+    }
+}
@@ -0,0 +1,180 @@
+//! Tracing generated text back to the substitutes that could have produced it
+//!
+//! Text is compared in a normalized form, lowercase with whitespace collapsed to single spaces,
+//! so formatting added by Discord or by copying a message doesn't hide its source
+
+use std::collections::HashSet;
+
+/// Longest run of words tried against substitutes when decomposing text
+pub const MAX_FRAGMENT_WORDS: usize = 6;
+/// Most fragments a single search tries so long messages stay a single query
+pub const MAX_FRAGMENTS: usize = 300;
+/// Lowest trigram similarity a fuzzy match may have, the default threshold of pg_trgm
+pub const FUZZY_THRESHOLD: f32 = 0.3;
+
+/// How a substitute matched the text searched for, better matches rank first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchQuality {
+    /// The substitute is the whole text
+    Exact,
+    /// The text is part of the substitute
+    Contains,
+    /// The substitute is a run of words of the text, like a part of a multi-template generation
+    Fragment { words: usize },
+    /// The substitute resembles the text with a trigram similarity between 0 and 1
+    Fuzzy { similarity: f32 },
+}
+
+impl MatchQuality {
+    /// Orders matches from best to worst, longer fragments and closer fuzzy matches rank higher
+    pub fn score(&self) -> f32 {
+        match self {
+            MatchQuality::Exact => 4.0,
+            MatchQuality::Contains => 3.0,
+            MatchQuality::Fragment { words } => {
+                2.0 + (*words).min(MAX_FRAGMENT_WORDS) as f32 / (MAX_FRAGMENT_WORDS + 1) as f32
+            }
+            MatchQuality::Fuzzy { similarity } => *similarity,
+        }
+    }
+}
+
+impl std::fmt::Display for MatchQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchQuality::Exact => f.write_str("exact"),
+            MatchQuality::Contains => f.write_str("contains"),
+            MatchQuality::Fragment { words } => write!(f, "fragment of {} words", words),
+            MatchQuality::Fuzzy { similarity } => write!(f, "{:.0}% similar", similarity * 100.0),
+        }
+    }
+}
+
+/// Lowercases text and collapses every run of whitespace into a single space
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Every run of up to [`MAX_FRAGMENT_WORDS`] words of normalized text shorter than all of it,
+/// longest runs first and at most [`MAX_FRAGMENTS`] of them
+pub fn fragments(normalized: &str) -> Vec<String> {
+    let words: Vec<&str> = normalized
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut seen = HashSet::new();
+    let mut fragments = Vec::new();
+
+    for length in (1..words.len().min(MAX_FRAGMENT_WORDS + 1)).rev() {
+        for window in words.windows(length) {
+            let fragment = window.join(" ");
+            if seen.insert(fragment.clone()) {
+                fragments.push(fragment);
+                if fragments.len() == MAX_FRAGMENTS {
+                    return fragments;
+                }
+            }
+        }
+    }
+    fragments
+}
+
+/// Trigram similarity of a and b computed like pg_trgm's `similarity`
+///
+/// Each word is padded with two spaces in front and one behind before it is split into
+/// trigrams, the similarity is the share of trigrams the two have in common
+pub fn trigram_similarity(a: &str, b: &str) -> f32 {
+    let a = trigrams(a);
+    let b = trigrams(b);
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let mut trigrams = HashSet::new();
+    for word in text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let padded: Vec<char> = "  ".chars().chain(word.chars()).chain([' ']).collect();
+        for window in padded.windows(3) {
+            trigrams.insert([window[0], window[1], window[2]]);
+        }
+    }
+    trigrams
+}
+
+#[cfg(test)]
+mod source_search_test {
+    use super::*;
+
+    #[test]
+    fn text_is_normalized() {
+        assert_eq!(
+            normalize("  The Quick\n\tbrown  FOX "),
+            "the quick brown fox"
+        );
+        assert_eq!(normalize(" \n "), "");
+    }
+
+    #[test]
+    fn fragments_are_shorter_runs_of_words() {
+        assert_eq!(
+            fragments("red fox ran"),
+            vec!["red fox", "fox ran", "red", "fox", "ran"]
+        );
+        assert!(fragments("fox").is_empty());
+        assert_eq!(fragments("a b a"), vec!["a b", "b a", "a", "b"]);
+
+        let long = vec!["word"; 1000].join(" ");
+        assert!(fragments(&long).len() <= MAX_FRAGMENTS);
+        let many = (0..1000)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let many = fragments(&many);
+        assert!(many.len() == MAX_FRAGMENTS);
+        assert!(many[0].split(' ').count() == MAX_FRAGMENT_WORDS);
+    }
+
+    #[test]
+    fn trigram_similarity_matches_pg_trgm() {
+        assert_eq!(trigram_similarity("word", "word"), 1.0);
+        assert_eq!(trigram_similarity("word", "xyz"), 0.0);
+        assert_eq!(trigram_similarity("", ""), 0.0);
+        // pg_trgm gives similarity('word', 'two words') = 0.36363637
+        assert!((trigram_similarity("word", "two words") - 4.0 / 11.0).abs() < f32::EPSILON);
+        assert!(trigram_similarity("Hello, World", "hello world") == 1.0);
+    }
+
+    #[test]
+    fn matches_rank_by_quality() {
+        let mut qualities = vec![
+            MatchQuality::Fuzzy { similarity: 0.4 },
+            MatchQuality::Fragment { words: 1 },
+            MatchQuality::Contains,
+            MatchQuality::Fuzzy { similarity: 0.9 },
+            MatchQuality::Fragment { words: 3 },
+            MatchQuality::Exact,
+        ];
+        qualities.sort_by(|a, b| b.score().total_cmp(&a.score()));
+        assert_eq!(
+            qualities,
+            vec![
+                MatchQuality::Exact,
+                MatchQuality::Contains,
+                MatchQuality::Fragment { words: 3 },
+                MatchQuality::Fragment { words: 1 },
+                MatchQuality::Fuzzy { similarity: 0.9 },
+                MatchQuality::Fuzzy { similarity: 0.4 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::Display;
+
+use crate::{embedded_code::find_code_blocks, template_substitutor::DelimiterRegistry};
+
+/// Marks the delimiter or brace after it as text
+const ESCAPE: char = '\\';
+/// Text between a pair of these is shown as written in Discord
+const RAW_FENCE: &str = "```";
+/// Stands in for text that is never scanned, it can't start a block or a template name
+const MASK: char = '-';
+
+/// Whether a substitute is plain text, embedded code or both, stored on every substitute so
+/// templates can be curated by kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubKind {
+    /// Text without code blocks or template references
+    Prose,
+    /// Nothing but code blocks
+    Code,
+    /// Text alongside code blocks or template references
+    Mixed,
+}
+
+impl SubKind {
+    pub const ALL: [SubKind; 3] = [SubKind::Prose, SubKind::Code, SubKind::Mixed];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubKind::Prose => "prose",
+            SubKind::Code => "code",
+            SubKind::Mixed => "mixed",
+        }
+    }
+
+    /// Reads a kind stored with [`SubKind::as_str`]
+    pub fn parse(kind: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == kind)
+    }
+
+    /// Classifies a substitute by scanning it for `{}` blocks and references made with the
+    /// builtin delimiters
+    ///
+    /// Delimiters and braces escaped with a backslash and anything inside of a ```` ``` ```` fence
+    /// are read as text. Templates with registered delimiters are only recognized by their
+    /// builtin ones.
+    pub fn classify(substitute: &str) -> Self {
+        let scanned = mask_text(substitute);
+        let blocks = find_code_blocks(&scanned).unwrap_or_default();
+
+        let mut outside = String::with_capacity(scanned.len());
+        let mut last = 0;
+        for block in &blocks {
+            outside.push_str(&scanned[last..block.start]);
+            outside.push(' ');
+            last = block.end;
+        }
+        outside.push_str(&scanned[last..]);
+
+        if has_reference(&outside) {
+            SubKind::Mixed
+        } else if blocks.is_empty() {
+            SubKind::Prose
+        } else if outside.trim().is_empty() {
+            SubKind::Code
+        } else {
+            SubKind::Mixed
+        }
+    }
+}
+
+impl Display for SubKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Replaces fenced text and escaped characters with [`MASK`] so only live syntax is scanned
+fn mask_text(substitute: &str) -> String {
+    let fenced = substitute.split(RAW_FENCE).count() - 1;
+    // An unclosed fence is shown as written so only pairs are masked
+    let closed = fenced - fenced % 2;
+
+    let mut masked = String::with_capacity(substitute.len());
+    for (i, part) in substitute.split(RAW_FENCE).enumerate() {
+        if i > 0 {
+            masked.push_str(RAW_FENCE);
+        }
+        if i % 2 == 1 && i < closed {
+            masked.extend(part.chars().map(|_| MASK));
+            continue;
+        }
+
+        let mut chars = part.chars();
+        while let Some(c) = chars.next() {
+            if c == ESCAPE {
+                masked.push(MASK);
+                if chars.next().is_some() {
+                    masked.push(MASK);
+                }
+            } else {
+                masked.push(c);
+            }
+        }
+    }
+    masked
+}
+
+/// Whether text refers to a template with any builtin delimiter
+fn has_reference(text: &str) -> bool {
+    let delimiters = DelimiterRegistry::RENAMED_BUILTINS.map(|delimiter| delimiter.to_char());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if delimiters.contains(&c)
+            && chars.peek().is_some_and(|next| {
+                next.is_ascii_lowercase() || next.is_ascii_digit() || *next == '_'
+            })
+        {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod substitute_kind_test {
+    use super::*;
+
+    #[test]
+    fn kinds_round_trip() {
+        for kind in SubKind::ALL {
+            assert_eq!(SubKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(SubKind::parse("poetry"), None);
+    }
+
+    #[test]
+    fn plain_text_is_prose() {
+        assert_eq!(SubKind::classify("quick brown fox"), SubKind::Prose);
+        assert_eq!(SubKind::classify(""), SubKind::Prose);
+        assert_eq!(SubKind::classify("2 + 2 = 4"), SubKind::Prose);
+        assert_eq!(SubKind::classify("a ^ b and ^^"), SubKind::Prose);
+        assert_eq!(SubKind::classify("a { lonely brace"), SubKind::Prose);
+    }
+
+    #[test]
+    fn code_blocks_alone_are_code() {
+        assert_eq!(SubKind::classify("{print(\"fox\")}"), SubKind::Code);
+        assert_eq!(
+            SubKind::classify(" {print(\"a\")} {repeat(2, print(\"}\"))} "),
+            SubKind::Code
+        );
+    }
+
+    #[test]
+    fn code_or_references_with_text_are_mixed() {
+        assert_eq!(SubKind::classify("the ^noun"), SubKind::Mixed);
+        assert_eq!(SubKind::classify("^adj ^noun"), SubKind::Mixed);
+        assert_eq!(SubKind::classify("+noun-1+ met +noun-1+"), SubKind::Mixed);
+        assert_eq!(SubKind::classify("a `noun`"), SubKind::Mixed);
+        assert_eq!(SubKind::classify("hi {print(\"there\")}"), SubKind::Mixed);
+        assert_eq!(SubKind::classify("{print(\"a\")} ^noun"), SubKind::Mixed);
+    }
+
+    #[test]
+    fn escaped_delimiters_are_text() {
+        assert_eq!(SubKind::classify("write \\^noun to use it"), SubKind::Prose);
+        assert_eq!(SubKind::classify("\\{print(\"a\")\\}"), SubKind::Prose);
+        assert_eq!(SubKind::classify("\\\\^noun"), SubKind::Mixed);
+        assert_eq!(SubKind::classify("ends with \\"), SubKind::Prose);
+    }
+
+    #[test]
+    fn raw_fences_are_text() {
+        assert_eq!(
+            SubKind::classify("try ```{print(^noun)}``` yourself"),
+            SubKind::Prose
+        );
+        assert_eq!(SubKind::classify("```a``` ^noun ```b```"), SubKind::Mixed);
+        assert_eq!(
+            SubKind::classify("```{print(\"a\")}```{print(\"b\")}"),
+            SubKind::Mixed
+        );
+        assert_eq!(SubKind::classify("``` ^noun"), SubKind::Mixed);
+    }
+}
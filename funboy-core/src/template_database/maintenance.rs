@@ -0,0 +1,444 @@
+//! Previews of pending migrations and backfills of rows that predate a column
+//!
+//! Backfills run in batches that each commit along with how far they got, so an interrupted run
+//! resumes where it stopped and running a finished backfill again does nothing
+
+use std::ops::ControlFlow;
+
+use sqlx::Error;
+
+use super::{DbConnectionRef, DbPool, KeySize, TemplateDatabase};
+use crate::substitute_kind::SubKind;
+
+/// A migration [`TemplateDatabase::migrate`] would apply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+    pub sql: String,
+}
+
+/// A known backfill of rows added before a column existed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backfill {
+    /// Templates added before created_at was recorded all share the time of that migration,
+    /// each is lowered to the earliest created_at of itself and every later id so older ids
+    /// are never newer
+    TemplateCreatedAt,
+    /// Substitutes added before kinds were recorded are all prose, each is classified with
+    /// [`SubKind::classify`]
+    SubstituteKind,
+}
+
+impl Backfill {
+    pub const ALL: &[Backfill] = &[Backfill::TemplateCreatedAt, Backfill::SubstituteKind];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backfill::TemplateCreatedAt => "template_created_at",
+            Backfill::SubstituteKind => "substitute_kind",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Backfill::TemplateCreatedAt => {
+                "estimate created_at of templates added before it was recorded from id ordering"
+            }
+            Backfill::SubstituteKind => {
+                "classify substitutes added before kinds were recorded as prose, code or mixed"
+            }
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|backfill| backfill.name() == name)
+    }
+
+    /// The table whose rows are walked in id order
+    fn table(&self) -> &'static str {
+        match self {
+            Backfill::TemplateCreatedAt => "templates",
+            Backfill::SubstituteKind => "substitutes",
+        }
+    }
+
+    /// Updates the rows with ids from first through last, running it again must change nothing
+    async fn update(
+        &self,
+        conn: DbConnectionRef<'_>,
+        first: KeySize,
+        last: KeySize,
+    ) -> Result<(), Error> {
+        on_backend!(conn, DbConnectionRef, |conn| {
+            match self {
+                Backfill::TemplateCreatedAt => {
+                    sqlx::query(
+                        "
+                            UPDATE templates SET created_at = (
+                                SELECT MIN(later.created_at) FROM templates later
+                                WHERE later.id >= templates.id
+                            )
+                            WHERE id BETWEEN $1 AND $2
+                        ",
+                    )
+                    .bind(first)
+                    .bind(last)
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                // Classifying needs the scanner so each row is read and written back
+                Backfill::SubstituteKind => {
+                    let substitutes = sqlx::query_as::<_, (KeySize, String, String)>(
+                        "SELECT id, name, kind FROM substitutes WHERE id BETWEEN $1 AND $2",
+                    )
+                    .bind(first)
+                    .bind(last)
+                    .fetch_all(&mut *conn)
+                    .await?;
+
+                    for (id, name, kind) in substitutes {
+                        let classified = SubKind::classify(&name).as_str();
+                        if kind != classified {
+                            sqlx::query("UPDATE substitutes SET kind = $1 WHERE id = $2")
+                                .bind(classified)
+                                .bind(id)
+                                .execute(&mut *conn)
+                                .await?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A backfill that has not finished yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackfillTask {
+    pub backfill: Backfill,
+    /// The highest id already processed, 0 if the backfill never ran
+    pub last_id: KeySize,
+    /// Rows left to process
+    pub remaining: i64,
+}
+
+/// How far a run of a backfill got, reported after every batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackfillProgress {
+    /// Rows processed by this run
+    pub processed: i64,
+    /// Rows left after the last batch, may be off if rows are added during the run
+    pub remaining: i64,
+    pub completed: bool,
+}
+
+impl TemplateDatabase {
+    /// Migrations [`TemplateDatabase::migrate`] would apply to pool without applying them
+    pub async fn pending_migrations(pool: &DbPool) -> Result<Vec<PendingMigration>, Error> {
+        let migrator = pool.backend().migrator();
+        let applied = on_backend!(pool, DbPool, |pool| {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+            )
+            .fetch_all(pool)
+            .await
+        });
+        let applied = match applied {
+            Ok(applied) => applied,
+            // the table only exists once a migration ran
+            Err(Error::Database(_)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(migrator
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(|migration| PendingMigration {
+                version: migration.version,
+                description: migration.description.to_string(),
+                sql: migration.sql.to_string(),
+            })
+            .collect())
+    }
+
+    /// Backfills that have not completed with how many rows each has left
+    pub async fn pending_backfills(&self) -> Result<Vec<BackfillTask>, Error> {
+        let mut tasks = Vec::new();
+        for &backfill in Backfill::ALL {
+            let (last_id, completed) = self.read_backfill_state(backfill).await?;
+            if !completed {
+                tasks.push(BackfillTask {
+                    backfill,
+                    last_id,
+                    remaining: self.count_backfill_remaining(backfill, last_id).await?,
+                });
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// Runs backfill in batches of batch_size rows calling on_batch after each one commits
+    ///
+    /// Returning [`ControlFlow::Break`] from on_batch stops the run, the next run continues
+    /// after the last committed batch. A completed backfill returns without touching any row.
+    pub async fn run_backfill(
+        &self,
+        backfill: Backfill,
+        batch_size: i64,
+        mut on_batch: impl FnMut(&BackfillProgress) -> ControlFlow<()>,
+    ) -> Result<BackfillProgress, Error> {
+        on_backend!(self.pool.as_ref(), DbPool, |pool| {
+            let batch_size = batch_size.max(1);
+            let (mut last_id, completed) = self.read_backfill_state(backfill).await?;
+            let mut progress = BackfillProgress {
+                processed: 0,
+                remaining: 0,
+                completed,
+            };
+            if completed {
+                return Ok(progress);
+            }
+            progress.remaining = self.count_backfill_remaining(backfill, last_id).await?;
+
+            loop {
+                let mut tx = pool.begin().await?;
+                let ids = sqlx::query_scalar::<_, KeySize>(&format!(
+                    "SELECT id FROM {} WHERE id > $1 ORDER BY id LIMIT $2",
+                    backfill.table()
+                ))
+                .bind(last_id)
+                .bind(batch_size)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                if let (Some(&first), Some(&last)) = (ids.first(), ids.last()) {
+                    backfill
+                        .update(DbConnectionRef::from(&mut *tx), first, last)
+                        .await?;
+                    last_id = last;
+                }
+
+                let batch = ids.len() as i64;
+                let completed = batch < batch_size;
+                sqlx::query(
+                    "
+                        INSERT INTO maintenance_backfills (name, last_id, completed) VALUES ($1, $2, $3)
+                        ON CONFLICT (name) DO UPDATE
+                        SET last_id = excluded.last_id, completed = excluded.completed
+                    ",
+                )
+                .bind(backfill.name())
+                .bind(last_id)
+                .bind(completed)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+
+                progress.processed += batch;
+                progress.remaining = if completed {
+                    0
+                } else {
+                    (progress.remaining - batch).max(0)
+                };
+                progress.completed = completed;
+
+                if on_batch(&progress).is_break() || completed {
+                    return Ok(progress);
+                }
+            }
+        })
+    }
+
+    /// The last processed id and whether backfill completed
+    async fn read_backfill_state(&self, backfill: Backfill) -> Result<(KeySize, bool), Error> {
+        on_backend!(self.pool.as_ref(), DbPool, |pool| {
+            let state = sqlx::query_as::<_, (KeySize, bool)>(
+                "SELECT last_id, completed FROM maintenance_backfills WHERE name = $1",
+            )
+            .bind(backfill.name())
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(state.unwrap_or((0, false)))
+        })
+    }
+
+    async fn count_backfill_remaining(
+        &self,
+        backfill: Backfill,
+        last_id: KeySize,
+    ) -> Result<i64, Error> {
+        on_backend!(self.pool.as_ref(), DbPool, |pool| {
+            let remaining = sqlx::query_scalar::<_, i64>(&format!(
+                "SELECT COUNT(*) FROM {} WHERE id > $1",
+                backfill.table()
+            ))
+            .bind(last_id)
+            .fetch_one(pool)
+            .await?;
+
+            Ok(remaining)
+        })
+    }
+}
+
+#[cfg(test)]
+mod maintenance_test {
+    use super::*;
+    use crate::template_database::test::{connect_debug_pool, create_debug_db};
+
+    async fn created_at(db: &TemplateDatabase) -> Vec<i64> {
+        on_backend!(db.pool.as_ref(), DbPool, |pool| {
+            sqlx::query_scalar::<_, i64>("SELECT created_at FROM templates ORDER BY id")
+                .fetch_all(pool)
+                .await
+        })
+        .unwrap()
+    }
+
+    async fn pending_backfill(db: &TemplateDatabase, backfill: Backfill) -> Option<BackfillTask> {
+        db.pending_backfills()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|task| task.backfill == backfill)
+    }
+
+    /// Five templates whose created_at runs [100, 500, 300, 400, 200] in id order
+    async fn out_of_order_templates(db: &TemplateDatabase) {
+        for (name, created_at) in [("a", 100), ("b", 500), ("c", 300), ("d", 400), ("e", 200)] {
+            let template = db.create_template(name).await.unwrap().unwrap();
+            on_backend!(db.pool.as_ref(), DbPool, |pool| {
+                sqlx::query("UPDATE templates SET created_at = $1 WHERE id = $2")
+                    .bind(created_at as i64)
+                    .bind(template.id)
+                    .execute(pool)
+                    .await
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn backfill_names_round_trip() {
+        for backfill in Backfill::ALL {
+            assert_eq!(Backfill::parse(backfill.name()), Some(*backfill));
+        }
+        assert_eq!(Backfill::parse("missing"), None);
+    }
+
+    #[tokio::test]
+    async fn migrated_database_has_no_pending_migrations() {
+        let pool = connect_debug_pool().await;
+        let db = create_debug_db(pool).await.unwrap();
+        assert!(
+            TemplateDatabase::pending_migrations(db.pool.as_ref())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn backfill_resumes_after_interruption() {
+        let pool = connect_debug_pool().await;
+        let db = create_debug_db(pool).await.unwrap();
+        out_of_order_templates(&db).await;
+
+        let interrupted = db
+            .run_backfill(Backfill::TemplateCreatedAt, 2, |_| ControlFlow::Break(()))
+            .await
+            .unwrap();
+        assert!(interrupted.processed == 2 && interrupted.remaining == 3);
+        assert!(!interrupted.completed);
+
+        let pending = pending_backfill(&db, Backfill::TemplateCreatedAt).await;
+        assert!(pending.is_some_and(|task| task.remaining == 3));
+        assert!(created_at(&db).await == vec![100, 200, 300, 400, 200]);
+
+        let mut batches = Vec::new();
+        let resumed = db
+            .run_backfill(Backfill::TemplateCreatedAt, 2, |progress| {
+                batches.push(*progress);
+                ControlFlow::Continue(())
+            })
+            .await
+            .unwrap();
+        assert!(resumed.processed == 3 && resumed.completed);
+        assert!(batches.len() == 2 && batches[0].remaining == 1);
+        assert!(created_at(&db).await == vec![100, 200, 200, 200, 200]);
+        assert!(
+            pending_backfill(&db, Backfill::TemplateCreatedAt)
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn backfill_runs_twice_without_changes() {
+        let pool = connect_debug_pool().await;
+        let db = create_debug_db(pool).await.unwrap();
+        out_of_order_templates(&db).await;
+
+        let first = db
+            .run_backfill(Backfill::TemplateCreatedAt, 10, |_| {
+                ControlFlow::Continue(())
+            })
+            .await
+            .unwrap();
+        assert!(first.processed == 5 && first.completed);
+        let backfilled = created_at(&db).await;
+
+        let mut called = false;
+        let second = db
+            .run_backfill(Backfill::TemplateCreatedAt, 10, |_| {
+                called = true;
+                ControlFlow::Continue(())
+            })
+            .await
+            .unwrap();
+        assert!(second.processed == 0 && second.completed && !called);
+        assert!(created_at(&db).await == backfilled);
+    }
+
+    #[tokio::test]
+    async fn substitute_kinds_are_backfilled() {
+        let pool = connect_debug_pool().await;
+        let db = create_debug_db(pool).await.unwrap();
+        db.create_substitutes("line", &["hello", "{print(\"hi\")}", "hi ^name"])
+            .await
+            .unwrap();
+        // Rows written before the migration kept its default
+        on_backend!(db.pool.as_ref(), DbPool, |pool| {
+            sqlx::query("UPDATE substitutes SET kind = 'prose'")
+                .execute(pool)
+                .await
+        })
+        .unwrap();
+
+        let progress = db
+            .run_backfill(Backfill::SubstituteKind, 2, |_| ControlFlow::Continue(()))
+            .await
+            .unwrap();
+        assert!(progress.processed == 3 && progress.completed);
+
+        let kinds = on_backend!(db.pool.as_ref(), DbPool, |pool| {
+            sqlx::query_scalar::<_, String>("SELECT kind FROM substitutes ORDER BY id")
+                .fetch_all(pool)
+                .await
+        })
+        .unwrap();
+        assert!(kinds == vec!["prose", "code", "mixed"]);
+        assert!(
+            pending_backfill(&db, Backfill::SubstituteKind)
+                .await
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+use crate::template_database::{KeySize, ReassignedId, SubstituteWarning};
+
+/// Version written to exports, imports of any other version are refused
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Every template and its substitutes in a form that can be saved and imported elsewhere
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateExport {
+    pub version: u32,
+    pub templates: Vec<ExportedTemplate>,
+}
+
+impl TemplateExport {
+    pub fn new(templates: Vec<ExportedTemplate>) -> Self {
+        Self {
+            version: EXPORT_VERSION,
+            templates,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedTemplate {
+    pub name: String,
+    pub substitutes: Vec<ExportedSubstitute>,
+}
+
+/// A substitute and the id it had when exported
+///
+/// Exports written before ids were included list substitutes as plain strings, those still
+/// import with no id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SubstituteEntry")]
+pub struct ExportedSubstitute {
+    pub id: Option<KeySize>,
+    pub name: String,
+    /// Exports written before substitutes could be disabled import enabled
+    pub enabled: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubstituteEntry {
+    Name(String),
+    WithId {
+        id: Option<KeySize>,
+        name: String,
+        #[serde(default = "enabled_by_default")]
+        enabled: bool,
+    },
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl From<SubstituteEntry> for ExportedSubstitute {
+    fn from(entry: SubstituteEntry) -> Self {
+        match entry {
+            SubstituteEntry::Name(name) => Self {
+                id: None,
+                name,
+                enabled: true,
+            },
+            SubstituteEntry::WithId { id, name, enabled } => Self { id, name, enabled },
+        }
+    }
+}
+
+/// How [`crate::Funboy::import_templates`] treats the substitutes of an export
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Insert substitutes with their exported ids so saved references to them keep working
+    ///
+    /// Ids already taken are reassigned and listed in the report
+    pub preserve_ids: bool,
+    /// Allow preserving ids into templates that already have substitutes
+    pub force: bool,
+}
+
+/// What importing a [`TemplateExport`] changed
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub templates: usize,
+    pub added: usize,
+    /// Substitutes that were already in their template or failed validation
+    pub ignored: usize,
+    /// Substitutes left out because their template reached its cap
+    pub refused: usize,
+    pub warnings: Vec<SubstituteWarning>,
+    /// Substitutes that couldn't keep their exported id
+    pub reassigned: Vec<ReassignedId>,
+}
+
+#[cfg(test)]
+mod template_export_test {
+    use super::*;
+
+    #[test]
+    fn substitutes_without_ids_still_import() {
+        let template: ExportedTemplate =
+            serde_json::from_str(r#"{"name":"noun","substitutes":["fox",{"id":7,"name":"dog"}]}"#)
+                .unwrap();
+        assert_eq!(
+            template.substitutes,
+            vec![
+                ExportedSubstitute {
+                    id: None,
+                    name: "fox".to_string(),
+                    enabled: true,
+                },
+                ExportedSubstitute {
+                    id: Some(7),
+                    name: "dog".to_string(),
+                    enabled: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_substitutes_round_trip() {
+        let substitute = ExportedSubstitute {
+            id: Some(3),
+            name: "cat".to_string(),
+            enabled: false,
+        };
+        let json = serde_json::to_string(&substitute).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ExportedSubstitute>(&json).unwrap(),
+            substitute
+        );
+    }
+}
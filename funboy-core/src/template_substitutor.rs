@@ -1,76 +1,372 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    future::{Ready, ready},
     hash::{DefaultHasher, Hash, Hasher},
+    sync::{LazyLock, Mutex},
 };
 
 use regex::Regex;
-use strum_macros::EnumIter;
-use tokio::sync::OnceCell;
+
+use crate::output_style::OutputStyle;
 
 pub const VALID_TEMPLATE_CHARS: &str = "a-z0-9_";
 
-#[derive(Debug, Copy, Clone, EnumIter)]
-pub enum TemplateDelimiter {
-    Caret,
-    Plus,
-    PlusRegister,
-    BackTick,
+/// Characters with meaning in embedded code that a delimiter would make ambiguous
+pub const CODE_SYNTAX_CHARS: &str = "{}()\",";
+
+/// Marks where a template starts and optionally the character that may end it
+///
+/// **Example:** with an open and close of `%` both `%noun` and `%noun%` refer to `noun`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TemplateDelimiter {
+    open: char,
+    close: Option<char>,
+    name_chars: &'static str,
 }
 
-static CARET_REGEX: OnceCell<Regex> = OnceCell::const_new();
-static PLUS_REGEX: OnceCell<Regex> = OnceCell::const_new();
-static PLUS_REGISTER_REGEX: OnceCell<Regex> = OnceCell::const_new();
-static BACKTICK_REGEX: OnceCell<Regex> = OnceCell::const_new();
+static DELIMITER_REGEXES: LazyLock<Mutex<HashMap<TemplateDelimiter, Regex>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
 
-impl TemplateDelimiter {
-    pub fn to_char(&self) -> char {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DelimiterError {
+    TemplateChar { delimiter: char },
+    CodeSyntaxChar { delimiter: char },
+    AlreadyRegistered { delimiter: char },
+}
+
+impl Display for DelimiterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TemplateDelimiter::Caret => '^',
-            TemplateDelimiter::Plus => '+',
-            TemplateDelimiter::PlusRegister => '+',
-            TemplateDelimiter::BackTick => '`',
+            DelimiterError::TemplateChar { delimiter } => write!(
+                f,
+                "delimiter {} can be part of a template name, template names may use {}",
+                delimiter, VALID_TEMPLATE_CHARS
+            ),
+            DelimiterError::CodeSyntaxChar { delimiter } => write!(
+                f,
+                "delimiter {} is used by embedded code, avoid any of {}",
+                delimiter, CODE_SYNTAX_CHARS
+            ),
+            DelimiterError::AlreadyRegistered { delimiter } => {
+                write!(f, "delimiter {} is already registered", delimiter)
+            }
         }
     }
+}
 
-    pub fn to_regex_pattern(&self) -> String {
-        match self {
-            TemplateDelimiter::Caret => format!(r"\^[{}]+\^?", VALID_TEMPLATE_CHARS),
-            TemplateDelimiter::BackTick => format!(r"\`[{}]+\`?", VALID_TEMPLATE_CHARS),
-            TemplateDelimiter::Plus => format!(r"\+[{}]+\+?", VALID_TEMPLATE_CHARS),
-            TemplateDelimiter::PlusRegister => format!(r"\+[a-z0-9-_]+\+?"),
+impl TemplateDelimiter {
+    pub const CARET: Self = Self::builtin('^');
+    pub const PLUS: Self = Self::builtin('+');
+    pub const BACKTICK: Self = Self::builtin('`');
+    /// Plus delimiter allowing `-` in names so aliases such as `+name-1` can be registered
+    pub const PLUS_REGISTER: Self = Self {
+        open: '+',
+        close: Some('+'),
+        name_chars: "a-z0-9-_",
+    };
+
+    const fn builtin(delimiter: char) -> Self {
+        Self {
+            open: delimiter,
+            close: Some(delimiter),
+            name_chars: VALID_TEMPLATE_CHARS,
         }
     }
 
-    pub async fn to_regex(&self) -> &'static Regex {
-        match self {
-            TemplateDelimiter::Caret => {
-                CARET_REGEX
-                    .get_or_init(|| async { Regex::new(&self.to_regex_pattern()).unwrap() })
-                    .await
-            }
-            TemplateDelimiter::Plus => {
-                PLUS_REGEX
-                    .get_or_init(|| async { Regex::new(&self.to_regex_pattern()).unwrap() })
-                    .await
+    /// Creates a delimiter rejecting characters that could be read as a template name or as code
+    pub fn new(open: char, close: Option<char>) -> Result<Self, DelimiterError> {
+        for delimiter in std::iter::once(open).chain(close) {
+            if delimiter.is_ascii_lowercase() || delimiter.is_ascii_digit() || delimiter == '_' {
+                return Err(DelimiterError::TemplateChar { delimiter });
             }
-            TemplateDelimiter::PlusRegister => {
-                PLUS_REGISTER_REGEX
-                    .get_or_init(|| async { Regex::new(&self.to_regex_pattern()).unwrap() })
-                    .await
-            }
-            TemplateDelimiter::BackTick => {
-                BACKTICK_REGEX
-                    .get_or_init(|| async { Regex::new(&self.to_regex_pattern()).unwrap() })
-                    .await
+            if CODE_SYNTAX_CHARS.contains(delimiter) {
+                return Err(DelimiterError::CodeSyntaxChar { delimiter });
             }
         }
+
+        Ok(Self {
+            open,
+            close,
+            name_chars: VALID_TEMPLATE_CHARS,
+        })
+    }
+
+    pub fn to_char(&self) -> char {
+        self.open
+    }
+
+    pub fn close(&self) -> Option<char> {
+        self.close
+    }
+
+    pub fn to_regex_pattern(&self) -> String {
+        let open = regex::escape(&self.open.to_string());
+        match self.close {
+            Some(close) => format!(
+                r"{}[{}]+{}?",
+                open,
+                self.name_chars,
+                regex::escape(&close.to_string())
+            ),
+            None => format!(r"{}[{}]+", open, self.name_chars),
+        }
+    }
+
+    pub async fn to_regex(&self) -> Regex {
+        DELIMITER_REGEXES
+            .lock()
+            .unwrap()
+            .entry(*self)
+            .or_insert_with(|| Regex::new(&self.to_regex_pattern()).unwrap())
+            .clone()
+    }
+
+    /// Strips the delimiters from a template matched by this delimiter's regex
+    pub fn template_name<'a>(&self, matched: &'a str) -> &'a str {
+        let name = &matched[self.open.len_utf8()..];
+        match self.close {
+            Some(close) => name.strip_suffix(close).unwrap_or(name),
+            None => name,
+        }
+    }
+}
+
+/// The delimiters recognized by a [`crate::Funboy`] beyond the ones it is built with
+///
+/// Registered delimiters resolve templates like `^` and are rewritten when a template is renamed
+#[derive(Debug, Clone, Default)]
+pub struct DelimiterRegistry {
+    registered: Vec<TemplateDelimiter>,
+}
+
+impl DelimiterRegistry {
+    /// Delimiters whose references are rewritten when a template is renamed in the order they are applied
+    pub const RENAMED_BUILTINS: [TemplateDelimiter; 3] = [
+        TemplateDelimiter::CARET,
+        TemplateDelimiter::BACKTICK,
+        TemplateDelimiter::PLUS,
+    ];
+
+    /// Adds delimiter unless it starts or ends like a delimiter that is already recognized
+    pub fn register(&mut self, delimiter: TemplateDelimiter) -> Result<(), DelimiterError> {
+        let taken: HashSet<char> = self
+            .renamed()
+            .iter()
+            .flat_map(|taken| std::iter::once(taken.open).chain(taken.close))
+            .collect();
+        if let Some(delimiter) = std::iter::once(delimiter.open)
+            .chain(delimiter.close)
+            .find(|c| taken.contains(c))
+        {
+            return Err(DelimiterError::AlreadyRegistered { delimiter });
+        }
+
+        self.registered.push(delimiter);
+        Ok(())
+    }
+
+    pub fn registered(&self) -> &[TemplateDelimiter] {
+        &self.registered
+    }
+
+    /// Delimiters rewritten when a template is renamed in the order they are applied
+    pub fn renamed(&self) -> Vec<TemplateDelimiter> {
+        Self::RENAMED_BUILTINS
+            .iter()
+            .chain(self.registered.iter())
+            .copied()
+            .collect()
+    }
+
+    /// Delimiters replaced with a random substitute during generation
+    pub fn substituted(&self) -> Vec<TemplateDelimiter> {
+        std::iter::once(TemplateDelimiter::CARET)
+            .chain(self.registered.iter().copied())
+            .collect()
+    }
+}
+
+/// Caps on how many templates a single generation may resolve
+#[derive(Debug, Copy, Clone)]
+pub struct ExpansionLimits {
+    pub max_total: u32,
+    pub max_per_template: u32,
+    /// Distinct registers such as `+noun-1` across the generation and its nested generations
+    pub max_registers: u32,
+    /// Length of the generated value a register holds
+    pub max_register_length: usize,
+}
+
+impl Default for ExpansionLimits {
+    fn default() -> Self {
+        Self {
+            max_total: 2000,
+            max_per_template: 250,
+            max_registers: 100,
+            max_register_length: 16000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpansionError {
+    TotalLimitReached {
+        limit: u32,
+    },
+    TemplateLimitReached {
+        template: String,
+        limit: u32,
+    },
+    RegisterLimitReached {
+        limit: u32,
+    },
+    RegisterTooLong {
+        register: String,
+        length: usize,
+        limit: usize,
+    },
+}
+
+impl ExpansionError {
+    pub(crate) fn message(&self, style: OutputStyle) -> String {
+        match self {
+            ExpansionError::TotalLimitReached { limit } => format!(
+                "generation resolved more than {} templates, try reducing how many templates each substitute references",
+                limit
+            ),
+            ExpansionError::TemplateLimitReached { template, limit } => format!(
+                "template {} was resolved more than {} times in one generation, check whether its substitutes reference it again",
+                style.code(template),
+                limit
+            ),
+            ExpansionError::RegisterLimitReached { limit } => format!(
+                "generation used more than {} different registers, try reusing registers such as +noun-1 instead of numbering new ones",
+                limit
+            ),
+            ExpansionError::RegisterTooLong {
+                register,
+                length,
+                limit,
+            } => format!(
+                "register {} generated {} characters, registers can hold at most {} characters",
+                style.code(register),
+                length,
+                limit
+            ),
+        }
+    }
+}
+
+impl Display for ExpansionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(OutputStyle::default()))
+    }
+}
+
+/// Counts template resolutions, registers and substitute page reads performed during one
+/// generation
+#[derive(Debug, Clone)]
+pub struct ExpansionCounter {
+    limits: ExpansionLimits,
+    total: u32,
+    per_template: HashMap<String, u32>,
+    registers: u32,
+    page_fetches: u32,
+    depth_reached: bool,
+}
+
+impl ExpansionCounter {
+    pub fn new(limits: ExpansionLimits) -> Self {
+        Self {
+            limits,
+            total: 0,
+            per_template: HashMap::new(),
+            registers: 0,
+            page_fetches: 0,
+            depth_reached: false,
+        }
+    }
+
+    /// Records that templates were still nested when a depth limit stopped resolving them
+    pub fn record_depth_reached(&mut self) {
+        self.depth_reached = true;
+    }
+
+    pub fn depth_reached(&self) -> bool {
+        self.depth_reached
+    }
+
+    /// Records a new distinct register failing once the register limit would be exceeded
+    pub fn record_register(&mut self) -> Result<(), ExpansionError> {
+        if self.registers >= self.limits.max_registers {
+            return Err(ExpansionError::RegisterLimitReached {
+                limit: self.limits.max_registers,
+            });
+        }
+        self.registers += 1;
+        Ok(())
+    }
+
+    /// Fails if a register generated a value longer than the limit
+    pub fn check_register_length(
+        &self,
+        register: &str,
+        length: usize,
+    ) -> Result<(), ExpansionError> {
+        if length > self.limits.max_register_length {
+            return Err(ExpansionError::RegisterTooLong {
+                register: register.to_string(),
+                length,
+                limit: self.limits.max_register_length,
+            });
+        }
+        Ok(())
+    }
+
+    /// Records a page of substitutes read from the database
+    pub fn record_page_fetch(&mut self) {
+        self.page_fetches += 1;
+    }
+
+    /// Records a resolution of template failing once either limit would be exceeded
+    pub fn record(&mut self, template: &str) -> Result<(), ExpansionError> {
+        if self.total >= self.limits.max_total {
+            return Err(ExpansionError::TotalLimitReached {
+                limit: self.limits.max_total,
+            });
+        }
+
+        let count = self.per_template.entry(template.to_string()).or_insert(0);
+        if *count >= self.limits.max_per_template {
+            return Err(ExpansionError::TemplateLimitReached {
+                template: template.to_string(),
+                limit: self.limits.max_per_template,
+            });
+        }
+
+        *count += 1;
+        self.total += 1;
+        Ok(())
+    }
+
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn registers(&self) -> u32 {
+        self.registers
+    }
+
+    pub fn page_fetches(&self) -> u32 {
+        self.page_fetches
     }
 }
 
 #[derive(Debug)]
 pub struct TemplateSubstitutor {
-    delimiter: TemplateDelimiter,
-    regex: &'static Regex,
+    delimiters: Vec<TemplateDelimiter>,
+    regex: Regex,
     depth_limit: u16,
 }
 
@@ -78,14 +374,49 @@ impl TemplateSubstitutor {
     pub async fn new(delimiter: TemplateDelimiter) -> Self {
         let regex = delimiter.to_regex().await;
         Self {
-            delimiter,
+            delimiters: vec![delimiter],
             regex,
             depth_limit: 255,
         }
     }
 
+    /// Resolves templates written with any of delimiters in the same pass
+    ///
+    /// Delimiters are told apart by their open character so each one must start differently
+    pub async fn with_delimiters(delimiters: &[TemplateDelimiter]) -> Self {
+        if let [delimiter] = delimiters {
+            return Self::new(*delimiter).await;
+        }
+
+        let pattern = delimiters
+            .iter()
+            .map(|delimiter| format!("(?:{})", delimiter.to_regex_pattern()))
+            .collect::<Vec<_>>()
+            .join("|");
+        Self {
+            delimiters: delimiters.to_vec(),
+            regex: Regex::new(&pattern).unwrap(),
+            depth_limit: 255,
+        }
+    }
+
     pub async fn default() -> Self {
-        Self::new(TemplateDelimiter::Caret).await
+        Self::new(TemplateDelimiter::CARET).await
+    }
+
+    /// Overrides how many passes [`TemplateSubstitutor::substitute_recursively`] makes after
+    /// the first one
+    pub fn with_depth_limit(mut self, depth_limit: u16) -> Self {
+        self.depth_limit = depth_limit;
+        self
+    }
+
+    fn delimiter_of(&self, matched: &str) -> TemplateDelimiter {
+        *self
+            .delimiters
+            .iter()
+            .find(|delimiter| matched.starts_with(delimiter.to_char()))
+            .unwrap_or(&self.delimiters[0])
     }
 }
 
@@ -96,12 +427,14 @@ impl TemplateSubstitutor {
         for template in self.regex.find_iter(&input[i..]) {
             output.push_str(&input[i..template.start()]);
             let matched = template.as_str();
-            let template_name = matched[1..].trim_end_matches(self.delimiter.to_char());
+            let delimiter = self.delimiter_of(matched);
+            let template_name = delimiter.template_name(matched);
 
             if old_name == template_name {
-                output.push(self.delimiter.to_char());
+                let open_len = delimiter.to_char().len_utf8();
+                output.push(delimiter.to_char());
                 output.push_str(new_name);
-                output.push_str(&matched[template_name.len() + 1..]);
+                output.push_str(&matched[template_name.len() + open_len..]);
             } else {
                 output.push_str(matched);
             }
@@ -112,40 +445,45 @@ impl TemplateSubstitutor {
         output
     }
 
+    /// How many times input references template with any of the delimiters
+    pub fn count_references(&self, input: &str, template: &str) -> usize {
+        self.regex
+            .find_iter(input)
+            .filter(|matched| {
+                self.delimiter_of(matched.as_str())
+                    .template_name(matched.as_str())
+                    == template
+            })
+            .count()
+    }
+
     /// Resolves templates with a single pass over input
+    ///
+    /// Substitutes are inserted literally, unresolved templates are left as they were written
     pub async fn substitute<F, Fut>(&self, input: &str, template_mapper: &F) -> String
     where
         F: Fn(String) -> Fut,
         Fut: Future<Output = Option<String>>,
     {
         let mut output = String::new();
-        let mut start = 0;
         let mut end = 0;
-        for template in self.regex.find_iter(&input[start..]) {
+        for template in self.regex.find_iter(input) {
+            output.push_str(&input[end..template.start()]);
+
+            let matched = template.as_str();
             let sub = template_mapper(
-                template.as_str()[1..]
-                    .trim_end_matches(self.delimiter.to_char())
+                self.delimiter_of(matched)
+                    .template_name(matched)
                     .to_string(),
             )
             .await;
 
             match sub {
-                Some(sub) => {
-                    end = template.end();
-
-                    let segment = self.regex.replace(&input[start..end], &sub).into_owned();
-
-                    start = template.end();
-
-                    output.push_str(&segment);
-                }
-                None => {
-                    output.push_str(&input[end..template.start()]);
-                    start = template.end();
-                    output.push_str(template.as_str());
-                    end = template.end();
-                }
+                Some(sub) => output.push_str(&sub),
+                None => output.push_str(matched),
             }
+
+            end = template.end();
         }
         output.push_str(&input[end..]);
         output
@@ -153,6 +491,23 @@ impl TemplateSubstitutor {
 
     /// Recursively resolves templates until none are present or depth limit or infinte cycle is reached
     pub async fn substitute_recursively<F, Fut>(&self, input: String, template_mapper: F) -> String
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Option<String>>,
+    {
+        self.substitute_within_depth(input, template_mapper).await.0
+    }
+
+    /// Resolves templates like [`TemplateSubstitutor::substitute_recursively`] and also returns
+    /// whether the depth limit stopped it while templates were still present
+    ///
+    /// Templates that are present because they could not be resolved stop it earlier by
+    /// repeating the output, so they are only left over at the limit when nesting continues
+    pub async fn substitute_within_depth<F, Fut>(
+        &self,
+        input: String,
+        template_mapper: F,
+    ) -> (String, bool)
     where
         F: Fn(String) -> Fut,
         Fut: Future<Output = Option<String>>,
@@ -167,51 +522,122 @@ impl TemplateSubstitutor {
             let hash = hasher.finish();
 
             if !previous_hashes.insert(hash) {
-                break;
+                return (output, false);
             } else {
                 output = self.substitute(&output, &template_mapper).await;
             }
         }
 
-        output
+        let depth_reached = self.regex.is_match(&output);
+        (output, depth_reached)
+    }
+
+    /// Names of the templates referenced in input, each listed once in the order they appear
+    pub fn template_names(&self, input: &str) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for template in self.regex.find_iter(input) {
+            let matched = template.as_str();
+            let name = self.delimiter_of(matched).template_name(matched);
+            if !names.iter().any(|existing| existing == name) {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+}
+
+/// Templates held in memory for resolving input without a database
+///
+/// Each template resolves to a single substitute so output is deterministic, which suits tests
+/// and benchmarks of the substitutor
+#[derive(Debug, Clone, Default)]
+pub struct TemplateMap {
+    templates: HashMap<String, String>,
+}
+
+impl TemplateMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, template: impl Into<String>, substitute: impl Into<String>) {
+        self.templates.insert(template.into(), substitute.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// A template mapper for [`TemplateSubstitutor::substitute`] that never awaits
+    pub fn mapper(&self) -> impl Fn(String) -> Ready<Option<String>> + '_ {
+        |template| ready(self.templates.get(&template).cloned())
+    }
+}
+
+impl<T: Into<String>, S: Into<String>> FromIterator<(T, S)> for TemplateMap {
+    fn from_iter<I: IntoIterator<Item = (T, S)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (template, substitute) in iter {
+            map.insert(template, substitute);
+        }
+        map
     }
 }
 
 #[cfg(test)]
 mod template_substitutor_test {
-    use std::{collections::HashMap, sync::Arc};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
 
     use super::*;
 
     #[tokio::test]
     async fn nested_templates() {
-        let mut template_map = HashMap::new();
-        template_map.insert("adj", "quick");
-        template_map.insert("color_adj", "brown");
-        template_map.insert("color", "^color_adj");
-        template_map.insert("noun", "fox");
-        template_map.insert("verb", "jump");
-        template_map.insert(
-            "sentence",
-            "The ^adj ^color_adj ^noun ^verb^ed over the lazy dog.",
-        );
-        let template_map = Arc::new(template_map);
+        let template_map = TemplateMap::from_iter([
+            ("adj", "quick"),
+            ("color_adj", "brown"),
+            ("color", "^color_adj"),
+            ("noun", "fox"),
+            ("verb", "jump"),
+            (
+                "sentence",
+                "The ^adj ^color_adj ^noun ^verb^ed over the lazy dog.",
+            ),
+        ]);
         let template_substitutor = TemplateSubstitutor::default().await;
         let output = template_substitutor
-            .substitute_recursively("^sentence".to_string(), |template| {
-                let template_map = template_map.clone();
-                async move {
-                    match template_map.get(template.as_str()) {
-                        Some(sub) => Some(sub.to_string()),
-                        None => None,
-                    }
-                }
-            })
+            .substitute_recursively("^sentence".to_string(), template_map.mapper())
             .await;
         assert!(output == "The quick brown fox jumped over the lazy dog.");
         println!("OUTPUT: {}", output);
     }
 
+    #[tokio::test]
+    async fn depth_limit_is_reported_with_templates_left() {
+        let template_map: TemplateMap = (1..10)
+            .map(|i| (format!("t{}", i), format!("^t{}", i + 1)))
+            .collect();
+        let template_substitutor = TemplateSubstitutor::default().await.with_depth_limit(3);
+
+        let (output, depth_reached) = template_substitutor
+            .substitute_within_depth("^t1 ^missing".to_string(), template_map.mapper())
+            .await;
+        assert!(output == "^t5 ^missing" && depth_reached);
+        assert!(template_substitutor.template_names(&output) == vec!["t5", "missing"]);
+
+        // t10 can't be resolved so the output repeats before the limit
+        let (output, depth_reached) = template_substitutor
+            .substitute_within_depth("^t8 ^missing".to_string(), template_map.mapper())
+            .await;
+        assert!(output == "^t10 ^missing" && !depth_reached);
+    }
+
     #[tokio::test]
     async fn close_templates() {
         let mut template_map = HashMap::new();
@@ -282,4 +708,326 @@ mod template_substitutor_test {
             .await;
         println!("OUTPUT: {}", output);
     }
+
+    async fn substitute_with_limits(
+        template_map: HashMap<&'static str, &'static str>,
+        input: &str,
+        limits: ExpansionLimits,
+    ) -> (String, Option<ExpansionError>) {
+        let template_map = Arc::new(template_map);
+        let counter = Arc::new(Mutex::new(ExpansionCounter::new(limits)));
+        let error = Arc::new(Mutex::new(None));
+        let template_substitutor = TemplateSubstitutor::default().await;
+        let output = template_substitutor
+            .substitute_recursively(input.to_string(), |template| {
+                let template_map = template_map.clone();
+                let counter = counter.clone();
+                let error = error.clone();
+                async move {
+                    let sub = template_map.get(template.as_str())?;
+                    if let Err(e) = counter.lock().unwrap().record(&template) {
+                        error.lock().unwrap().get_or_insert(e);
+                        return None;
+                    }
+                    Some(sub.to_string())
+                }
+            })
+            .await;
+        let error = error.lock().unwrap().take();
+        (output, error)
+    }
+
+    #[tokio::test]
+    async fn fan_out_trips_total_limit() {
+        let mut template_map = HashMap::new();
+        template_map.insert("a", "^b ^b ^b ^b ^b ^b ^b ^b ^b ^b");
+        template_map.insert("b", "^c ^c ^c ^c ^c ^c ^c ^c ^c ^c");
+        template_map.insert("c", "^d ^d ^d ^d ^d ^d ^d ^d ^d ^d");
+        template_map.insert("d", "leaf");
+        let limits = ExpansionLimits {
+            max_total: 100,
+            max_per_template: 1000,
+            ..Default::default()
+        };
+
+        let (output, error) = substitute_with_limits(template_map, "^a", limits).await;
+        assert_eq!(
+            error,
+            Some(ExpansionError::TotalLimitReached { limit: 100 })
+        );
+        assert!(output.len() < 10_000);
+    }
+
+    #[tokio::test]
+    async fn self_amplifying_trips_template_limit() {
+        let mut template_map = HashMap::new();
+        template_map.insert("grow", "^grow ^grow");
+        let limits = ExpansionLimits::default();
+
+        let (output, error) = substitute_with_limits(template_map, "^grow", limits).await;
+        assert_eq!(
+            error,
+            Some(ExpansionError::TemplateLimitReached {
+                template: "grow".to_string(),
+                limit: limits.max_per_template,
+            })
+        );
+        assert!(output.len() < 10_000);
+    }
+
+    #[tokio::test]
+    async fn substitutes_are_inserted_literally() {
+        let mut template_map = HashMap::new();
+        template_map.insert("price", "$0 and $1 or ${name} \\1");
+        let template_map = Arc::new(template_map);
+        let template_substitutor = TemplateSubstitutor::default().await;
+        let output = template_substitutor
+            .substitute_recursively("costs ^price^!".to_string(), |template| {
+                let template_map = template_map.clone();
+                async move {
+                    template_map
+                        .get(template.as_str())
+                        .map(|sub| sub.to_string())
+                }
+            })
+            .await;
+        assert_eq!(output, "costs $0 and $1 or ${name} \\1!");
+    }
+
+    #[tokio::test]
+    async fn custom_delimiter_substitutes_alongside_caret() {
+        let percent = TemplateDelimiter::new('%', Some('%')).unwrap();
+        let template_substitutor =
+            TemplateSubstitutor::with_delimiters(&[TemplateDelimiter::CARET, percent]).await;
+        let output = template_substitutor
+            .substitute_recursively("%greeting%, ^name!".to_string(), |template| async move {
+                match template.as_str() {
+                    "greeting" => Some("hello %name".to_string()),
+                    "name" => Some("world".to_string()),
+                    _ => None,
+                }
+            })
+            .await;
+        assert_eq!(output, "hello world, world!");
+    }
+
+    #[tokio::test]
+    async fn custom_delimiter_renames() {
+        let percent = TemplateDelimiter::new('%', Some('%')).unwrap();
+        let template_substitutor = TemplateSubstitutor::new(percent).await;
+        let output = template_substitutor
+            .rename_template("%noun% %noun %nouns ^noun 100%", "noun", "thing")
+            .await;
+        assert_eq!(output, "%thing% %thing %nouns ^noun 100%");
+    }
+
+    #[test]
+    fn delimiters_must_not_collide() {
+        for delimiter in ['a', 'z', '0', '_', '{', '}', '(', ')', '"', ','] {
+            assert!(
+                TemplateDelimiter::new(delimiter, None).is_err(),
+                "{}",
+                delimiter
+            );
+            assert!(
+                TemplateDelimiter::new('%', Some(delimiter)).is_err(),
+                "{}",
+                delimiter
+            );
+        }
+        assert_eq!(
+            TemplateDelimiter::new('{', None),
+            Err(DelimiterError::CodeSyntaxChar { delimiter: '{' })
+        );
+
+        let mut registry = DelimiterRegistry::default();
+        for delimiter in ['^', '`', '+'] {
+            assert_eq!(
+                registry.register(TemplateDelimiter::new(delimiter, None).unwrap()),
+                Err(DelimiterError::AlreadyRegistered { delimiter })
+            );
+        }
+        let percent = TemplateDelimiter::new('%', Some('%')).unwrap();
+        assert!(registry.register(percent).is_ok());
+        assert!(
+            registry
+                .register(TemplateDelimiter::new('$', Some('%')).unwrap())
+                .is_err()
+        );
+        assert_eq!(registry.substituted(), [TemplateDelimiter::CARET, percent]);
+    }
+
+    #[test]
+    fn counter_tracks_resolutions() {
+        let mut counter = ExpansionCounter::new(ExpansionLimits {
+            max_total: 3,
+            max_per_template: 2,
+            ..Default::default()
+        });
+        assert!(counter.record("a").is_ok());
+        assert!(counter.record("a").is_ok());
+        assert!(counter.record("a").is_err());
+        assert!(counter.record("b").is_ok());
+        assert!(counter.record("c").is_err());
+        assert_eq!(counter.total(), 3);
+    }
+
+    #[test]
+    fn counter_caps_registers() {
+        let mut counter = ExpansionCounter::new(ExpansionLimits {
+            max_registers: 2,
+            max_register_length: 5,
+            ..Default::default()
+        });
+        assert!(counter.record_register().is_ok());
+        assert!(counter.record_register().is_ok());
+        assert_eq!(
+            counter.record_register(),
+            Err(ExpansionError::RegisterLimitReached { limit: 2 })
+        );
+        assert_eq!(counter.registers(), 2);
+
+        assert!(counter.check_register_length("noun-1", 5).is_ok());
+        assert_eq!(
+            counter.check_register_length("noun-1", 6),
+            Err(ExpansionError::RegisterTooLong {
+                register: "noun-1".to_string(),
+                length: 6,
+                limit: 5
+            })
+        );
+    }
+
+    mod properties {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use proptest::prelude::*;
+
+        use super::*;
+
+        /// Text that can never be read as part of a template, including regex replacement syntax
+        const TEXT_PATTERN: &str = "[A-Z \\.,!$\\\\{}()é-]{0,6}";
+        const NAME_PATTERN: &str = "[abc_][abc0-9_]{0,2}";
+        const DEPTH_LIMIT: u16 = 8;
+
+        #[derive(Debug, Clone)]
+        enum Segment {
+            Text(String),
+            Template { name: String, closed: bool },
+        }
+
+        fn segment() -> impl Strategy<Value = Segment> {
+            prop_oneof![
+                TEXT_PATTERN.prop_map(Segment::Text),
+                (NAME_PATTERN, any::<bool>())
+                    .prop_map(|(name, closed)| Segment::Template { name, closed }),
+            ]
+        }
+
+        fn mapping() -> impl Strategy<Value = HashMap<String, String>> {
+            prop::collection::hash_map(NAME_PATTERN, TEXT_PATTERN, 0..6)
+        }
+
+        /// Writes segments out as input alongside the output a single pass should produce
+        ///
+        /// Templates directly followed by another template are written closed so every segment is
+        /// read back exactly as it was generated
+        fn render(segments: &[Segment], mapping: &HashMap<String, String>) -> (String, String) {
+            let mut input = String::new();
+            let mut expected = String::new();
+            for (i, segment) in segments.iter().enumerate() {
+                match segment {
+                    Segment::Text(text) => {
+                        input.push_str(text);
+                        expected.push_str(text);
+                    }
+                    Segment::Template { name, closed } => {
+                        let next_is_template = segments[i + 1..]
+                            .iter()
+                            .find(|next| !matches!(next, Segment::Text(text) if text.is_empty()))
+                            .is_some_and(|next| matches!(next, Segment::Template { .. }));
+                        let written = if *closed || next_is_template {
+                            format!("^{}^", name)
+                        } else {
+                            format!("^{}", name)
+                        };
+                        input.push_str(&written);
+                        match mapping.get(name) {
+                            Some(sub) => expected.push_str(sub),
+                            None => expected.push_str(&written),
+                        }
+                    }
+                }
+            }
+            (input, expected)
+        }
+
+        fn substitute(input: &str, mapping: &HashMap<String, String>) -> String {
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                let template_substitutor = TemplateSubstitutor::default().await;
+                template_substitutor
+                    .substitute(input, &|template: String| async move {
+                        mapping.get(&template).cloned()
+                    })
+                    .await
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn single_pass_matches_model(
+                segments in prop::collection::vec(segment(), 0..12),
+                mapping in mapping(),
+            ) {
+                let (input, expected) = render(&segments, &mapping);
+                let output = substitute(&input, &mapping);
+                prop_assert_eq!(&output, &expected);
+
+                let regex = Regex::new(&TemplateDelimiter::CARET.to_regex_pattern()).unwrap();
+                for template in regex.find_iter(&output) {
+                    let name = template.as_str()[1..].trim_end_matches('^');
+                    prop_assert!(!mapping.contains_key(name));
+                }
+            }
+
+            #[test]
+            fn recursion_stops_at_depth_limit(
+                mapping in prop::collection::hash_map(
+                    NAME_PATTERN,
+                    (TEXT_PATTERN, prop::option::of(NAME_PATTERN)),
+                    1..6,
+                ),
+                names in prop::collection::vec(NAME_PATTERN, 1..6),
+            ) {
+                // every substitute references at most one template so a pass never has more
+                // templates to resolve than the input started with
+                let mapping: HashMap<String, String> = mapping
+                    .into_iter()
+                    .map(|(name, (text, reference))| match reference {
+                        Some(reference) => (name, format!("{}^{}^", text, reference)),
+                        None => (name, text),
+                    })
+                    .collect();
+                let input: String = names.iter().map(|name| format!("^{}^ ", name)).collect();
+                let calls = AtomicUsize::new(0);
+
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    let template_substitutor = TemplateSubstitutor {
+                        depth_limit: DEPTH_LIMIT,
+                        ..TemplateSubstitutor::default().await
+                    };
+                    template_substitutor
+                        .substitute_recursively(input, |template: String| {
+                            calls.fetch_add(1, Ordering::Relaxed);
+                            let sub = mapping.get(&template).cloned();
+                            async move { sub }
+                        })
+                        .await
+                });
+
+                let max_passes = DEPTH_LIMIT as usize + 1;
+                prop_assert!(calls.load(Ordering::Relaxed) <= names.len() * max_passes);
+            }
+        }
+    }
 }
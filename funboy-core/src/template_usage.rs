@@ -0,0 +1,173 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::template_database::{KeySize, UsageCount};
+
+const SHARD_COUNT: usize = 16;
+const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+/// The day unix_seconds falls on counted in days since the unix epoch
+pub fn day_of(unix_seconds: i64) -> i64 {
+    unix_seconds.div_euclid(SECONDS_PER_DAY)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct UsageKey {
+    template_id: KeySize,
+    guild_id: Option<KeySize>,
+    day: i64,
+}
+
+/// Counts template uses in memory until they are flushed to the database
+///
+/// Keys are spread across shards so concurrent generations rarely wait on the same lock
+#[derive(Debug)]
+pub struct UsageAccumulator {
+    shards: Vec<Mutex<HashMap<UsageKey, i64>>>,
+}
+
+impl Default for UsageAccumulator {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARD_COUNT).map(|_| Mutex::default()).collect(),
+        }
+    }
+}
+
+impl UsageAccumulator {
+    fn shard(&self, key: &UsageKey) -> &Mutex<HashMap<UsageKey, i64>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    fn add(&self, key: UsageKey, count: i64) {
+        let mut shard = self
+            .shard(&key)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *shard.entry(key).or_default() += count;
+    }
+
+    /// Counts one use of a template on day
+    pub fn record(&self, template_id: KeySize, guild_id: Option<KeySize>, day: i64) {
+        let key = UsageKey {
+            template_id,
+            guild_id,
+            day,
+        };
+        self.add(key, 1);
+    }
+
+    /// Takes every pending count leaving the accumulator empty
+    pub fn drain(&self) -> Vec<UsageCount> {
+        let mut counts = Vec::new();
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+            counts.extend(shard.drain().map(|(key, count)| UsageCount {
+                template_id: key.template_id,
+                guild_id: key.guild_id,
+                day: key.day,
+                count,
+            }));
+        }
+        counts
+    }
+
+    /// Puts drained counts back so a failed flush can be retried
+    pub fn restore(&self, counts: &[UsageCount]) {
+        for usage in counts {
+            let key = UsageKey {
+                template_id: usage.template_id,
+                guild_id: usage.guild_id,
+                day: usage.day,
+            };
+            self.add(key, usage.count);
+        }
+    }
+
+    /// How many uses are waiting to be flushed
+    pub fn pending(&self) -> i64 {
+        self.shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+                shard.values().sum::<i64>()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod template_usage_test {
+    use super::*;
+
+    #[test]
+    fn days_start_at_midnight_utc() {
+        assert_eq!(day_of(0), 0);
+        assert_eq!(day_of(SECONDS_PER_DAY - 1), 0);
+        assert_eq!(day_of(SECONDS_PER_DAY), 1);
+        assert_eq!(day_of(-1), -1);
+    }
+
+    #[test]
+    fn uses_are_summed_per_key() {
+        let usage = UsageAccumulator::default();
+        for _ in 0..3 {
+            usage.record(1, Some(10), 5);
+        }
+        usage.record(1, None, 5);
+        usage.record(2, Some(10), 6);
+        assert_eq!(usage.pending(), 5);
+
+        let mut counts = usage.drain();
+        counts.sort_by_key(|usage| (usage.template_id, usage.guild_id, usage.day));
+        assert_eq!(
+            counts,
+            vec![
+                UsageCount {
+                    template_id: 1,
+                    guild_id: None,
+                    day: 5,
+                    count: 1
+                },
+                UsageCount {
+                    template_id: 1,
+                    guild_id: Some(10),
+                    day: 5,
+                    count: 3
+                },
+                UsageCount {
+                    template_id: 2,
+                    guild_id: Some(10),
+                    day: 6,
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(usage.pending(), 0);
+        assert!(usage.drain().is_empty());
+    }
+
+    #[test]
+    fn restored_counts_merge_with_new_uses() {
+        let usage = UsageAccumulator::default();
+        usage.record(1, Some(10), 5);
+        let counts = usage.drain();
+
+        usage.record(1, Some(10), 5);
+        usage.restore(&counts);
+        assert_eq!(
+            usage.drain(),
+            vec![UsageCount {
+                template_id: 1,
+                guild_id: Some(10),
+                day: 5,
+                count: 2
+            }]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    template_database::{KeySize, Trigger},
+    user_facing_error::UserFacingError,
+};
+
+pub const MAX_TRIGGER_PATTERN_LENGTH: usize = 200;
+pub const MAX_TRIGGER_COOLDOWN_SECS: u64 = 60 * 60 * 24;
+/// Bytes a compiled trigger regex may take so patterns like `a{1000}{1000}` are refused
+pub const TRIGGER_REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// How deeply the groups and repetitions of a trigger regex may nest
+pub const TRIGGER_REGEX_NEST_LIMIT: u32 = 10;
+
+/// How the pattern of a trigger is compared with messages, always ignoring case
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMatchMode {
+    /// The whole message is the pattern ignoring surrounding whitespace
+    Exact,
+    /// The pattern is found anywhere in the message
+    Contains,
+    /// The pattern is a regex found anywhere in the message
+    Regex,
+}
+
+impl TriggerMatchMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TriggerMatchMode::Exact => "exact",
+            TriggerMatchMode::Contains => "contains",
+            TriggerMatchMode::Regex => "regex",
+        }
+    }
+
+    /// Reads a mode stored with [`TriggerMatchMode::as_str`]
+    pub fn parse(mode: &str) -> Option<Self> {
+        [
+            TriggerMatchMode::Exact,
+            TriggerMatchMode::Contains,
+            TriggerMatchMode::Regex,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == mode)
+    }
+}
+
+impl Display for TriggerMatchMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A trigger pattern compiled for its match mode
+#[derive(Debug, Clone)]
+pub enum TriggerMatcher {
+    Exact(String),
+    Contains(String),
+    Regex(Regex),
+}
+
+impl TriggerMatcher {
+    pub fn compile(pattern: &str, mode: TriggerMatchMode) -> Result<Self, UserFacingError> {
+        if pattern.trim().is_empty() || pattern.chars().count() > MAX_TRIGGER_PATTERN_LENGTH {
+            return Err(UserFacingError::TriggerPatternInvalid {
+                reason: format!(
+                    "patterns must be between 1 and {} characters long",
+                    MAX_TRIGGER_PATTERN_LENGTH
+                ),
+            });
+        }
+
+        match mode {
+            TriggerMatchMode::Exact => Ok(TriggerMatcher::Exact(pattern.trim().to_lowercase())),
+            TriggerMatchMode::Contains => Ok(TriggerMatcher::Contains(pattern.to_lowercase())),
+            TriggerMatchMode::Regex => RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .size_limit(TRIGGER_REGEX_SIZE_LIMIT)
+                .dfa_size_limit(TRIGGER_REGEX_SIZE_LIMIT)
+                .nest_limit(TRIGGER_REGEX_NEST_LIMIT)
+                .build()
+                .map(TriggerMatcher::Regex)
+                .map_err(|e| UserFacingError::TriggerPatternInvalid {
+                    reason: match e {
+                        regex::Error::CompiledTooBig(_) => "the regex is too complex".to_string(),
+                        _ => "it is not a valid regex".to_string(),
+                    },
+                }),
+        }
+    }
+
+    pub fn is_match(&self, content: &str) -> bool {
+        match self {
+            TriggerMatcher::Exact(pattern) => content.trim().to_lowercase() == *pattern,
+            TriggerMatcher::Contains(pattern) => content.to_lowercase().contains(pattern.as_str()),
+            TriggerMatcher::Regex(regex) => regex.is_match(content),
+        }
+    }
+}
+
+/// The triggers of a guild with their patterns compiled once
+#[derive(Debug, Clone, Default)]
+pub struct CompiledTriggers {
+    triggers: Vec<(Trigger, TriggerMatcher)>,
+}
+
+impl CompiledTriggers {
+    /// Triggers whose stored mode or pattern no longer compiles are left out
+    pub fn compile(triggers: Vec<Trigger>) -> Self {
+        let triggers = triggers
+            .into_iter()
+            .filter_map(|trigger| {
+                let mode = TriggerMatchMode::parse(&trigger.match_mode)?;
+                match TriggerMatcher::compile(&trigger.pattern, mode) {
+                    Ok(matcher) => Some((trigger, matcher)),
+                    Err(e) => {
+                        tracing::warn!(error = %e, trigger_id = trigger.id, "skipping trigger");
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self { triggers }
+    }
+
+    /// The first trigger matching content in the order they were compiled
+    pub fn find_match(&self, content: &str) -> Option<&Trigger> {
+        self.triggers
+            .iter()
+            .find(|(_, matcher)| matcher.is_match(content))
+            .map(|(trigger, _)| trigger)
+    }
+}
+
+/// When each trigger last fired so it stays quiet for its cooldown
+#[derive(Debug, Default)]
+pub struct TriggerCooldowns {
+    fired: HashMap<KeySize, Instant>,
+}
+
+impl TriggerCooldowns {
+    /// Records trigger as fired at now unless it already fired within its cooldown
+    pub fn try_fire(&mut self, trigger: &Trigger, now: Instant) -> bool {
+        let cooldown = Duration::from_secs(trigger.cooldown_secs.max(0) as u64);
+        if let Some(fired) = self.fired.get(&trigger.id)
+            && now.saturating_duration_since(*fired) < cooldown
+        {
+            return false;
+        }
+
+        // no cooldown is longer than a day so older entries can't hold anything back
+        let longest = Duration::from_secs(MAX_TRIGGER_COOLDOWN_SECS);
+        self.fired
+            .retain(|_, fired| now.saturating_duration_since(*fired) < longest);
+        self.fired.insert(trigger.id, now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod triggers_test {
+    use super::*;
+
+    fn trigger(id: KeySize, pattern: &str, mode: TriggerMatchMode, cooldown_secs: i64) -> Trigger {
+        Trigger {
+            id,
+            guild_id: 1,
+            pattern: pattern.to_string(),
+            match_mode: mode.as_str().to_string(),
+            template_input: "^pokemon".to_string(),
+            cooldown_secs,
+        }
+    }
+
+    fn matches(pattern: &str, mode: TriggerMatchMode, content: &str) -> bool {
+        TriggerMatcher::compile(pattern, mode)
+            .unwrap()
+            .is_match(content)
+    }
+
+    #[test]
+    fn modes_round_trip() {
+        for mode in [
+            TriggerMatchMode::Exact,
+            TriggerMatchMode::Contains,
+            TriggerMatchMode::Regex,
+        ] {
+            assert_eq!(TriggerMatchMode::parse(mode.as_str()), Some(mode));
+        }
+        assert_eq!(TriggerMatchMode::parse("glob"), None);
+    }
+
+    #[test]
+    fn match_modes() {
+        let phrase = "Who's that Pokemon";
+        assert!(matches(
+            phrase,
+            TriggerMatchMode::Exact,
+            " who's THAT pokemon "
+        ));
+        assert!(!matches(
+            phrase,
+            TriggerMatchMode::Exact,
+            "who's that pokemon?"
+        ));
+
+        assert!(matches(
+            phrase,
+            TriggerMatchMode::Contains,
+            "ok WHO'S that pokemon?"
+        ));
+        assert!(!matches(
+            phrase,
+            TriggerMatchMode::Contains,
+            "who is that pokemon"
+        ));
+
+        let regex = r"who('s| is) that (pokemon|digimon)\b";
+        assert!(matches(
+            regex,
+            TriggerMatchMode::Regex,
+            "so Who is that Digimon"
+        ));
+        assert!(!matches(
+            regex,
+            TriggerMatchMode::Regex,
+            "who's that pokemons"
+        ));
+    }
+
+    #[test]
+    fn patterns_are_capped() {
+        for (pattern, mode) in [
+            ("", TriggerMatchMode::Contains),
+            ("   ", TriggerMatchMode::Exact),
+            (
+                "a".repeat(MAX_TRIGGER_PATTERN_LENGTH + 1).as_str(),
+                TriggerMatchMode::Contains,
+            ),
+            ("(unclosed", TriggerMatchMode::Regex),
+            ("a{1000}{1000}", TriggerMatchMode::Regex),
+            (
+                format!("{}a{}", "(".repeat(20), ")".repeat(20)).as_str(),
+                TriggerMatchMode::Regex,
+            ),
+        ] {
+            assert!(
+                matches!(
+                    TriggerMatcher::compile(pattern, mode),
+                    Err(UserFacingError::TriggerPatternInvalid { .. })
+                ),
+                "{:?}",
+                pattern
+            );
+        }
+        assert!(
+            TriggerMatcher::compile(
+                &"a".repeat(MAX_TRIGGER_PATTERN_LENGTH),
+                TriggerMatchMode::Regex
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn first_matching_trigger_wins_and_invalid_ones_are_skipped() {
+        let mut broken = trigger(1, "(unclosed", TriggerMatchMode::Regex, 0);
+        broken.match_mode = "glob".to_string();
+        let compiled = CompiledTriggers::compile(vec![
+            broken,
+            trigger(2, "(unclosed", TriggerMatchMode::Regex, 0),
+            trigger(3, "pokemon", TriggerMatchMode::Contains, 0),
+            trigger(4, "that pokemon", TriggerMatchMode::Contains, 0),
+        ]);
+
+        assert_eq!(
+            compiled
+                .find_match("who's that pokemon")
+                .map(|trigger| trigger.id),
+            Some(3)
+        );
+        assert!(compiled.find_match("(unclosed").is_none());
+    }
+
+    #[test]
+    fn cooldowns_are_per_trigger() {
+        let mut cooldowns = TriggerCooldowns::default();
+        let pokemon = trigger(1, "pokemon", TriggerMatchMode::Contains, 30);
+        let digimon = trigger(2, "digimon", TriggerMatchMode::Contains, 30);
+        let spam = trigger(3, "spam", TriggerMatchMode::Contains, 0);
+        let start = Instant::now();
+
+        assert!(cooldowns.try_fire(&pokemon, start));
+        assert!(!cooldowns.try_fire(&pokemon, start + Duration::from_secs(29)));
+        assert!(cooldowns.try_fire(&digimon, start + Duration::from_secs(29)));
+        assert!(cooldowns.try_fire(&pokemon, start + Duration::from_secs(30)));
+
+        assert!(cooldowns.try_fire(&spam, start));
+        assert!(cooldowns.try_fire(&spam, start));
+    }
+}
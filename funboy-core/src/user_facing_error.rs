@@ -0,0 +1,848 @@
+use std::fmt::Display;
+
+use crate::{
+    Funboy,
+    dice::{MAX_DICE, MAX_SIDES},
+    output_style::{OutputStyle, StyledDisplay},
+    template_substitutor::ExpansionError,
+};
+
+const NUMBER_WORDS: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// Why a template name was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateNameReason {
+    Empty,
+    StartsWithNumber,
+    InvalidCharacters,
+    TooLong { limit: usize },
+}
+
+/// A problem with user input that can be shown back to the user
+///
+/// Display produces the default English message, frontends can match on the variant
+/// to phrase the problem differently or to offer a way to fix it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserFacingError {
+    TemplateNameInvalid {
+        reason: TemplateNameReason,
+    },
+    TemplateNameReserved {
+        name: String,
+    },
+    TemplateNotFound {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    TemplateExists {
+        name: String,
+    },
+    SubstituteNotFound {
+        template: String,
+        name: String,
+    },
+    SubstituteExists {
+        template: String,
+        name: String,
+    },
+    SubstituteCapExceeded {
+        template: String,
+        count: i64,
+        adding: i64,
+        cap: i64,
+    },
+    SubstituteTooLong {
+        length: usize,
+        limit: usize,
+    },
+    InvalidId,
+    RangeNotNumeric,
+    MinNotLessThanMax,
+    RangeNotFinite,
+    DivisionByZero {
+        command: String,
+    },
+    NumberNotFinite {
+        command: String,
+    },
+    IntegerOverflow {
+        command: String,
+    },
+    ComparedNan,
+    ListTooShort {
+        min: usize,
+    },
+    DiceInvalid {
+        input: String,
+    },
+    ExportVersionUnsupported {
+        version: u32,
+        supported: u32,
+    },
+    PreserveIdsTemplateNotEmpty {
+        template: String,
+    },
+    BulkFilterEmpty,
+    BulkTokenMismatch,
+    TooManyExamples {
+        limit: usize,
+    },
+    ExampleTooLong {
+        length: usize,
+        limit: usize,
+    },
+    DescriptionTooLong {
+        length: usize,
+        limit: usize,
+    },
+    AiLabelTooLong {
+        length: usize,
+        limit: usize,
+    },
+    PresetNameInvalid {
+        limit: usize,
+    },
+    PresetBodyTooLong {
+        length: usize,
+        limit: usize,
+    },
+    PresetMissingPlaceholder {
+        placeholder: String,
+    },
+    OllamaSettingTooLong {
+        setting: String,
+        length: usize,
+        limit: usize,
+    },
+    PresetNotFound {
+        name: String,
+    },
+    TooManyFavorites {
+        limit: usize,
+    },
+    AliasNameInvalid {
+        reason: TemplateNameReason,
+    },
+    AliasInputInvalid {
+        length: usize,
+        limit: usize,
+    },
+    TooManyAliases {
+        limit: usize,
+    },
+    TriggerPatternInvalid {
+        reason: String,
+    },
+    TriggerInputInvalid {
+        length: usize,
+        limit: usize,
+    },
+    TooManyTriggers {
+        limit: usize,
+    },
+    VariableNameInvalid {
+        name: String,
+    },
+    VariableReserved {
+        name: String,
+    },
+    VariableTooLong {
+        name: String,
+        length: usize,
+        limit: usize,
+    },
+    TooManyVariables {
+        limit: usize,
+    },
+    VariableNotFound {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    NothingStored,
+    SampleDrawsInvalid {
+        draws: usize,
+        limit: usize,
+    },
+    GenerationTooLarge(ExpansionError),
+    NestingTooDeep {
+        depth: u8,
+        templates: Vec<String>,
+    },
+}
+
+impl Display for TemplateNameReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateNameReason::Empty => write!(f, "template cannot be empty"),
+            TemplateNameReason::StartsWithNumber => {
+                write!(f, "first character of template cannot be a number")
+            }
+            TemplateNameReason::InvalidCharacters => write!(
+                f,
+                "template must be lowercase containing only characters a-z, 0-9, and _"
+            ),
+            TemplateNameReason::TooLong { limit } => {
+                write!(f, "template must be less than {} characters long", limit)
+            }
+        }
+    }
+}
+
+impl UserFacingError {
+    /// A stable identifier for the kind of error used by structured output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            UserFacingError::TemplateNameInvalid { .. } => "template_name_invalid",
+            UserFacingError::TemplateNameReserved { .. } => "template_name_reserved",
+            UserFacingError::TemplateNotFound { .. } => "template_not_found",
+            UserFacingError::TemplateExists { .. } => "template_exists",
+            UserFacingError::SubstituteNotFound { .. } => "substitute_not_found",
+            UserFacingError::SubstituteExists { .. } => "substitute_exists",
+            UserFacingError::SubstituteCapExceeded { .. } => "substitute_cap_exceeded",
+            UserFacingError::SubstituteTooLong { .. } => "substitute_too_long",
+            UserFacingError::InvalidId => "invalid_id",
+            UserFacingError::RangeNotNumeric => "range_not_numeric",
+            UserFacingError::MinNotLessThanMax => "min_not_less_than_max",
+            UserFacingError::RangeNotFinite => "range_not_finite",
+            UserFacingError::DivisionByZero { .. } => "division_by_zero",
+            UserFacingError::NumberNotFinite { .. } => "number_not_finite",
+            UserFacingError::IntegerOverflow { .. } => "integer_overflow",
+            UserFacingError::ComparedNan => "compared_nan",
+            UserFacingError::ListTooShort { .. } => "list_too_short",
+            UserFacingError::DiceInvalid { .. } => "dice_invalid",
+            UserFacingError::ExportVersionUnsupported { .. } => "export_version_unsupported",
+            UserFacingError::PreserveIdsTemplateNotEmpty { .. } => {
+                "preserve_ids_template_not_empty"
+            }
+            UserFacingError::BulkFilterEmpty => "bulk_filter_empty",
+            UserFacingError::BulkTokenMismatch => "bulk_token_mismatch",
+            UserFacingError::TooManyExamples { .. } => "too_many_examples",
+            UserFacingError::ExampleTooLong { .. } => "example_too_long",
+            UserFacingError::DescriptionTooLong { .. } => "description_too_long",
+            UserFacingError::AiLabelTooLong { .. } => "ai_label_too_long",
+            UserFacingError::PresetNameInvalid { .. } => "preset_name_invalid",
+            UserFacingError::PresetBodyTooLong { .. } => "preset_body_too_long",
+            UserFacingError::PresetMissingPlaceholder { .. } => "preset_missing_placeholder",
+            UserFacingError::OllamaSettingTooLong { .. } => "ollama_setting_too_long",
+            UserFacingError::PresetNotFound { .. } => "preset_not_found",
+            UserFacingError::TooManyFavorites { .. } => "too_many_favorites",
+            UserFacingError::AliasNameInvalid { .. } => "alias_name_invalid",
+            UserFacingError::AliasInputInvalid { .. } => "alias_input_invalid",
+            UserFacingError::TooManyAliases { .. } => "too_many_aliases",
+            UserFacingError::TriggerPatternInvalid { .. } => "trigger_pattern_invalid",
+            UserFacingError::TriggerInputInvalid { .. } => "trigger_input_invalid",
+            UserFacingError::TooManyTriggers { .. } => "too_many_triggers",
+            UserFacingError::VariableNameInvalid { .. } => "variable_name_invalid",
+            UserFacingError::VariableReserved { .. } => "variable_reserved",
+            UserFacingError::VariableTooLong { .. } => "variable_too_long",
+            UserFacingError::TooManyVariables { .. } => "too_many_variables",
+            UserFacingError::VariableNotFound { .. } => "variable_not_found",
+            UserFacingError::NothingStored => "nothing_stored",
+            UserFacingError::SampleDrawsInvalid { .. } => "sample_draws_invalid",
+            UserFacingError::GenerationTooLarge(_) => "generation_too_large",
+            UserFacingError::NestingTooDeep { .. } => "nesting_too_deep",
+        }
+    }
+
+    fn message(&self, style: OutputStyle) -> String {
+        match self {
+            UserFacingError::TemplateNameInvalid { reason } => reason.to_string(),
+            UserFacingError::TemplateNameReserved { name } => format!(
+                "template {0} conflicts with the command {0}, please choose a different name",
+                style.code(name)
+            ),
+            UserFacingError::TemplateNotFound { name, suggestions } => {
+                let mut message = format!("template {} does not exist", style.code(name));
+                if !suggestions.is_empty() {
+                    let suggestions: Vec<String> =
+                        suggestions.iter().map(|s| style.code(s)).collect();
+                    message.push_str(&format!(", did you mean {}?", suggestions.join(", ")));
+                }
+                message
+            }
+            UserFacingError::TemplateExists { name } => {
+                format!("template {} already exists", style.code(name))
+            }
+            UserFacingError::SubstituteNotFound { template, name } => format!(
+                "substitute {} does not exist in template {}",
+                style.code(name),
+                style.code(template)
+            ),
+            UserFacingError::SubstituteExists { template, name } => format!(
+                "substitute {} already exists in template {}",
+                style.code(name),
+                style.code(template)
+            ),
+            UserFacingError::SubstituteCapExceeded {
+                template,
+                count,
+                adding,
+                cap,
+            } => format!(
+                "template {} can hold at most {} substitutes, it has {} so {} more cannot be added",
+                style.code(template),
+                cap,
+                count,
+                adding
+            ),
+            UserFacingError::SubstituteTooLong { length, limit } => format!(
+                "substitute is {} characters long, substitutes must be at most {} characters long",
+                length, limit
+            ),
+            UserFacingError::InvalidId => "ID must be a valid number.".to_string(),
+            UserFacingError::RangeNotNumeric => "min and max values must be a number".to_string(),
+            UserFacingError::MinNotLessThanMax => "min must be less than max".to_string(),
+            UserFacingError::RangeNotFinite => {
+                "min and max must be finite and the range between them must fit in a number"
+                    .to_string()
+            }
+            UserFacingError::DivisionByZero { command } => {
+                format!("{} cannot divide by zero", style.code(command))
+            }
+            UserFacingError::NumberNotFinite { command } => {
+                format!("{} produced a non-finite value", style.code(command))
+            }
+            UserFacingError::IntegerOverflow { command } => {
+                format!(
+                    "{} produced an integer too large to hold",
+                    style.code(command)
+                )
+            }
+            UserFacingError::ComparedNan => {
+                "cannot compare NaN, it is not equal to anything including itself".to_string()
+            }
+            UserFacingError::ListTooShort { min } => match NUMBER_WORDS.get(*min) {
+                Some(min) => format!("list must contain at least {} entries", min),
+                None => format!("list must contain at least {} entries", min),
+            },
+            UserFacingError::DiceInvalid { input } => format!(
+                "{} is not valid dice, use {} or {} with at most {} dice of at most {} sides",
+                style.code(input),
+                style.code("d20"),
+                style.code("3d6"),
+                MAX_DICE,
+                MAX_SIDES
+            ),
+            UserFacingError::ExportVersionUnsupported { version, supported } => format!(
+                "export is version {}, only version {} can be imported",
+                version, supported
+            ),
+            UserFacingError::PreserveIdsTemplateNotEmpty { template } => format!(
+                "{} already has substitutes, ids can only be preserved into empty templates unless forced",
+                template
+            ),
+            UserFacingError::BulkFilterEmpty => {
+                "a filter is required, bulk operations can't apply to every template".to_string()
+            }
+            UserFacingError::BulkTokenMismatch => {
+                "the matching templates changed since the preview, preview again to get a new token"
+                    .to_string()
+            }
+            UserFacingError::TooManyExamples { limit } => {
+                format!("templates can have at most {} examples", limit)
+            }
+            UserFacingError::ExampleTooLong { length, limit } => format!(
+                "example is {} characters long, examples must be at most {} characters long",
+                length, limit
+            ),
+            UserFacingError::DescriptionTooLong { length, limit } => format!(
+                "description is {} characters long, descriptions must be at most {} characters long",
+                length, limit
+            ),
+            UserFacingError::AiLabelTooLong { length, limit } => format!(
+                "AI label is {} characters long, labels must be at most {} characters long",
+                length, limit
+            ),
+            UserFacingError::PresetNameInvalid { limit } => format!(
+                "preset name cannot be empty and must be at most {} characters long",
+                limit
+            ),
+            UserFacingError::PresetBodyTooLong { length, limit } => format!(
+                "preset is {} characters long, presets must be at most {} characters long",
+                length, limit
+            ),
+            UserFacingError::PresetMissingPlaceholder { placeholder } => format!(
+                "presets that wrap the prompt must contain {} to mark where the prompt goes",
+                style.code(placeholder)
+            ),
+            UserFacingError::OllamaSettingTooLong {
+                setting,
+                length,
+                limit,
+            } => format!(
+                "{} is {} characters long, it must be at most {} characters long",
+                setting, length, limit
+            ),
+            UserFacingError::PresetNotFound { name } => {
+                format!("prompt preset {} does not exist", style.code(name))
+            }
+            UserFacingError::TooManyFavorites { limit } => format!(
+                "you can have at most {} favorite templates, remove one before adding another",
+                limit
+            ),
+            UserFacingError::AliasNameInvalid { reason } => {
+                format!("aliases are named like templates, {}", reason)
+            }
+            UserFacingError::AliasInputInvalid { length, limit } => format!(
+                "alias input is {} characters long, it must be between 1 and {} characters long",
+                length, limit
+            ),
+            UserFacingError::TooManyAliases { limit } => format!(
+                "servers can have at most {} aliases, remove one before adding another",
+                limit
+            ),
+            UserFacingError::TriggerPatternInvalid { reason } => {
+                format!("trigger pattern cannot be used, {}", reason)
+            }
+            UserFacingError::TriggerInputInvalid { length, limit } => format!(
+                "trigger input is {} characters long, it must be between 1 and {} characters long",
+                length, limit
+            ),
+            UserFacingError::TooManyTriggers { limit } => format!(
+                "servers can have at most {} triggers, remove one before adding another",
+                limit
+            ),
+            UserFacingError::VariableNameInvalid { name } => format!(
+                "{} is not a valid variable name, names must be lowercase containing only characters a-z, 0-9, and _ and cannot start with a number",
+                style.code(name)
+            ),
+            UserFacingError::VariableReserved { name } => format!(
+                "{} is reserved, variables starting with {} are set by the bot and cannot be stored",
+                style.code(name),
+                style.code(Funboy::RESERVED_VAR_PREFIX)
+            ),
+            UserFacingError::VariableTooLong {
+                name,
+                length,
+                limit,
+            } => format!(
+                "{} is {} characters long, variables must be at most {} characters long",
+                style.code(name),
+                length,
+                limit
+            ),
+            UserFacingError::TooManyVariables { limit } => format!(
+                "you can have at most {} variables, clear them before saving another",
+                limit
+            ),
+            UserFacingError::VariableNotFound { name, suggestions } => {
+                let mut message = format!("variable {} has not been stored", style.code(name));
+                if !suggestions.is_empty() {
+                    let suggestions: Vec<String> =
+                        suggestions.iter().map(|s| style.code(s)).collect();
+                    message.push_str(&format!(", did you mean {}?", suggestions.join(", ")));
+                }
+                message
+            }
+            UserFacingError::NothingStored => format!(
+                "nothing has been stored yet; call {} first",
+                style.code("store(value)")
+            ),
+            UserFacingError::SampleDrawsInvalid { draws, limit } => format!(
+                "cannot sample {} draws, sample between 1 and {} draws",
+                draws, limit
+            ),
+            UserFacingError::GenerationTooLarge(e) => e.message(style),
+            UserFacingError::NestingTooDeep { depth, templates } => format!(
+                "maximum nesting depth of {} reached, check {} {} for excessive nesting",
+                depth,
+                if templates.len() == 1 {
+                    "template"
+                } else {
+                    "templates"
+                },
+                templates
+                    .iter()
+                    .map(|template| style.code(template))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl StyledDisplay for UserFacingError {
+    fn to_styled_string(&self, style: OutputStyle) -> String {
+        match style {
+            OutputStyle::Json => style.error(self.kind(), self.message(OutputStyle::Plain)),
+            OutputStyle::Plain | OutputStyle::Markdown => self.message(style),
+        }
+    }
+}
+
+impl Display for UserFacingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_styled_string(OutputStyle::default()))
+    }
+}
+
+impl From<ExpansionError> for UserFacingError {
+    fn from(value: ExpansionError) -> Self {
+        UserFacingError::GenerationTooLarge(value)
+    }
+}
+
+#[cfg(test)]
+mod user_facing_error_test {
+    use super::*;
+
+    fn name_error(reason: TemplateNameReason) -> String {
+        UserFacingError::TemplateNameInvalid { reason }.to_string()
+    }
+
+    #[test]
+    fn template_name_messages() {
+        assert_eq!(
+            name_error(TemplateNameReason::Empty),
+            "template cannot be empty"
+        );
+        assert_eq!(
+            name_error(TemplateNameReason::StartsWithNumber),
+            "first character of template cannot be a number"
+        );
+        assert_eq!(
+            name_error(TemplateNameReason::InvalidCharacters),
+            "template must be lowercase containing only characters a-z, 0-9, and _"
+        );
+        assert_eq!(
+            name_error(TemplateNameReason::TooLong { limit: 255 }),
+            "template must be less than 255 characters long"
+        );
+        assert_eq!(
+            UserFacingError::TemplateNameReserved {
+                name: "print".to_string()
+            }
+            .to_string(),
+            "template `print` conflicts with the command `print`, please choose a different name"
+        );
+    }
+
+    #[test]
+    fn template_lookup_messages() {
+        assert_eq!(
+            UserFacingError::TemplateNotFound {
+                name: "nuon".to_string(),
+                suggestions: vec![],
+            }
+            .to_string(),
+            "template `nuon` does not exist"
+        );
+        assert_eq!(
+            UserFacingError::TemplateNotFound {
+                name: "noun".to_string(),
+                suggestions: vec!["nouns".to_string(), "pronoun".to_string()],
+            }
+            .to_string(),
+            "template `noun` does not exist, did you mean `nouns`, `pronoun`?"
+        );
+        assert_eq!(
+            UserFacingError::TemplateExists {
+                name: "noun".to_string()
+            }
+            .to_string(),
+            "template `noun` already exists"
+        );
+        assert_eq!(
+            UserFacingError::SubstituteNotFound {
+                template: "noun".to_string(),
+                name: "fox".to_string()
+            }
+            .to_string(),
+            "substitute `fox` does not exist in template `noun`"
+        );
+        assert_eq!(
+            UserFacingError::SubstituteExists {
+                template: "noun".to_string(),
+                name: "fox".to_string()
+            }
+            .to_string(),
+            "substitute `fox` already exists in template `noun`"
+        );
+        assert_eq!(
+            UserFacingError::SubstituteCapExceeded {
+                template: "noun".to_string(),
+                count: 9,
+                adding: 2,
+                cap: 10
+            }
+            .to_string(),
+            "template `noun` can hold at most 10 substitutes, it has 9 so 2 more cannot be added"
+        );
+    }
+
+    #[test]
+    fn input_messages() {
+        assert_eq!(
+            UserFacingError::SubstituteTooLong {
+                length: 16001,
+                limit: 16000
+            }
+            .to_string(),
+            "substitute is 16001 characters long, substitutes must be at most 16000 characters long"
+        );
+        assert_eq!(
+            UserFacingError::InvalidId.to_string(),
+            "ID must be a valid number."
+        );
+        assert_eq!(
+            UserFacingError::RangeNotNumeric.to_string(),
+            "min and max values must be a number"
+        );
+        assert_eq!(
+            UserFacingError::MinNotLessThanMax.to_string(),
+            "min must be less than max"
+        );
+        assert_eq!(
+            UserFacingError::RangeNotFinite.to_string(),
+            "min and max must be finite and the range between them must fit in a number"
+        );
+        assert_eq!(
+            UserFacingError::DivisionByZero {
+                command: "div".to_string()
+            }
+            .to_string(),
+            "`div` cannot divide by zero"
+        );
+        assert_eq!(
+            UserFacingError::NumberNotFinite {
+                command: "mod".to_string()
+            }
+            .to_string(),
+            "`mod` produced a non-finite value"
+        );
+        assert_eq!(
+            UserFacingError::IntegerOverflow {
+                command: "mod".to_string()
+            }
+            .to_string(),
+            "`mod` produced an integer too large to hold"
+        );
+        assert_eq!(
+            UserFacingError::ComparedNan.to_string(),
+            "cannot compare NaN, it is not equal to anything including itself"
+        );
+        assert_eq!(
+            UserFacingError::ListTooShort { min: 2 }.to_string(),
+            "list must contain at least two entries"
+        );
+        assert_eq!(
+            UserFacingError::DiceInvalid {
+                input: "3d".to_string()
+            }
+            .to_string(),
+            "`3d` is not valid dice, use `d20` or `3d6` with at most 100 dice of at most 1000000 sides"
+        );
+        assert_eq!(
+            UserFacingError::ExportVersionUnsupported {
+                version: 2,
+                supported: 1
+            }
+            .to_string(),
+            "export is version 2, only version 1 can be imported"
+        );
+        assert_eq!(
+            UserFacingError::BulkTokenMismatch.to_string(),
+            "the matching templates changed since the preview, preview again to get a new token"
+        );
+        assert_eq!(
+            UserFacingError::ListTooShort { min: 12 }.to_string(),
+            "list must contain at least 12 entries"
+        );
+    }
+
+    #[test]
+    fn example_messages() {
+        assert_eq!(
+            UserFacingError::TooManyExamples { limit: 3 }.to_string(),
+            "templates can have at most 3 examples"
+        );
+        assert_eq!(
+            UserFacingError::ExampleTooLong {
+                length: 501,
+                limit: 500
+            }
+            .to_string(),
+            "example is 501 characters long, examples must be at most 500 characters long"
+        );
+        assert_eq!(
+            UserFacingError::DescriptionTooLong {
+                length: 1001,
+                limit: 1000
+            }
+            .to_string(),
+            "description is 1001 characters long, descriptions must be at most 1000 characters long"
+        );
+    }
+
+    #[test]
+    fn preset_messages() {
+        assert_eq!(
+            UserFacingError::PresetNameInvalid { limit: 100 }.to_string(),
+            "preset name cannot be empty and must be at most 100 characters long"
+        );
+        assert_eq!(
+            UserFacingError::PresetBodyTooLong {
+                length: 4001,
+                limit: 4000
+            }
+            .to_string(),
+            "preset is 4001 characters long, presets must be at most 4000 characters long"
+        );
+        assert_eq!(
+            UserFacingError::PresetMissingPlaceholder {
+                placeholder: "{prompt}".to_string()
+            }
+            .to_string(),
+            "presets that wrap the prompt must contain `{prompt}` to mark where the prompt goes"
+        );
+        assert_eq!(
+            UserFacingError::PresetNotFound {
+                name: "pirate".to_string()
+            }
+            .to_string(),
+            "prompt preset `pirate` does not exist"
+        );
+    }
+
+    #[test]
+    fn favorite_messages() {
+        assert_eq!(
+            UserFacingError::TooManyFavorites { limit: 25 }.to_string(),
+            "you can have at most 25 favorite templates, remove one before adding another"
+        );
+    }
+
+    #[test]
+    fn alias_messages() {
+        assert_eq!(
+            UserFacingError::AliasNameInvalid {
+                reason: TemplateNameReason::InvalidCharacters
+            }
+            .to_string(),
+            "aliases are named like templates, template must be lowercase containing only characters a-z, 0-9, and _"
+        );
+        assert_eq!(
+            UserFacingError::TooManyAliases { limit: 100 }.to_string(),
+            "servers can have at most 100 aliases, remove one before adding another"
+        );
+    }
+
+    #[test]
+    fn trigger_messages() {
+        assert_eq!(
+            UserFacingError::TriggerPatternInvalid {
+                reason: "it is not a valid regex".to_string()
+            }
+            .to_string(),
+            "trigger pattern cannot be used, it is not a valid regex"
+        );
+        assert_eq!(
+            UserFacingError::TooManyTriggers { limit: 25 }.to_string(),
+            "servers can have at most 25 triggers, remove one before adding another"
+        );
+    }
+
+    #[test]
+    fn nesting_messages() {
+        assert_eq!(
+            UserFacingError::NestingTooDeep {
+                depth: 32,
+                templates: vec!["chain".to_string()]
+            }
+            .to_string(),
+            "maximum nesting depth of 32 reached, check template `chain` for excessive nesting"
+        );
+        assert_eq!(
+            UserFacingError::NestingTooDeep {
+                depth: 8,
+                templates: vec!["a".to_string(), "b".to_string()]
+            }
+            .to_styled_string(OutputStyle::Plain),
+            "maximum nesting depth of 8 reached, check templates a, b for excessive nesting"
+        );
+    }
+
+    #[test]
+    fn variable_messages() {
+        assert_eq!(
+            UserFacingError::VariableReserved {
+                name: "ctx_author".to_string()
+            }
+            .to_string(),
+            "`ctx_author` is reserved, variables starting with `ctx_` are set by the bot and cannot be stored"
+        );
+        assert_eq!(
+            UserFacingError::VariableTooLong {
+                name: "story".to_string(),
+                length: 2001,
+                limit: 2000
+            }
+            .to_string(),
+            "`story` is 2001 characters long, variables must be at most 2000 characters long"
+        );
+        assert_eq!(
+            UserFacingError::TooManyVariables { limit: 20 }.to_string(),
+            "you can have at most 20 variables, clear them before saving another"
+        );
+        assert_eq!(
+            UserFacingError::VariableNotFound {
+                name: "numbr".to_string(),
+                suggestions: vec!["number".to_string()]
+            }
+            .to_string(),
+            "variable `numbr` has not been stored, did you mean `number`?"
+        );
+        assert_eq!(
+            UserFacingError::NothingStored.to_styled_string(OutputStyle::Plain),
+            "nothing has been stored yet; call store(value) first"
+        );
+    }
+
+    #[test]
+    fn sample_draws_message() {
+        assert_eq!(
+            UserFacingError::SampleDrawsInvalid {
+                draws: 200000,
+                limit: 100000
+            }
+            .to_string(),
+            "cannot sample 200000 draws, sample between 1 and 100000 draws"
+        );
+    }
+
+    #[test]
+    fn ollama_setting_message() {
+        assert_eq!(
+            UserFacingError::OllamaSettingTooLong {
+                setting: "system prompt".to_string(),
+                length: 4001,
+                limit: 4000
+            }
+            .to_string(),
+            "system prompt is 4001 characters long, it must be at most 4000 characters long"
+        );
+    }
+
+    #[test]
+    fn ai_label_message() {
+        assert_eq!(
+            UserFacingError::AiLabelTooLong {
+                length: 101,
+                limit: 100
+            }
+            .to_string(),
+            "AI label is 101 characters long, labels must be at most 100 characters long"
+        );
+    }
+
+    #[test]
+    fn generation_messages_match_expansion_errors() {
+        let expansion_error = ExpansionError::TotalLimitReached { limit: 10 };
+        assert_eq!(
+            UserFacingError::from(expansion_error.clone()).to_string(),
+            expansion_error.to_string()
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Scripts a session against the debug database the way the bot would without Discord
+//!
+//! Templates are seeded, generated from and the output is split into messages so regressions
+//! between adding substitutes, generation, prompt assembly and formatting show up in one place
+
+use std::sync::Arc;
+
+use fsl_interpreter::FslInterpreter;
+use funboy_core::{
+    DepthMode, Funboy, FunboyError, GenerateOptions,
+    message_format::{DISCORD_CHARACTER_LIMIT, split_message, split_messages},
+    template_database::{DbPool, TemplateDatabase},
+    user_facing_error::UserFacingError,
+};
+use tokio::sync::Mutex;
+
+#[cfg(not(feature = "sqlite"))]
+async fn connect(_name: &str) -> DbPool {
+    use funboy_core::template_database::DEBUG_DB_URL;
+
+    DbPool::connect(DEBUG_DB_URL).await.unwrap()
+}
+
+/// Each test gets its own in memory database which is dropped once its last connection closes
+#[cfg(feature = "sqlite")]
+async fn connect(name: &str) -> DbPool {
+    use std::str::FromStr;
+
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+    let url = format!("sqlite:file:funboy_{}?mode=memory&cache=shared", name);
+    SqlitePoolOptions::new()
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(SqliteConnectOptions::from_str(&url).unwrap())
+        .await
+        .unwrap()
+        .into()
+}
+
+/// Connects to the debug database with templates left behind by the last run deleted
+async fn funboy_without(name: &str, templates: &[&str]) -> Funboy {
+    let pool = connect(name).await;
+    TemplateDatabase::migrate(&pool).await.unwrap();
+    let funboy = Funboy::new(TemplateDatabase::new(Arc::new(pool)));
+    funboy.delete_templates(templates).await.ok();
+    funboy
+}
+
+async fn story_funboy() -> Funboy {
+    let funboy = funboy_without(
+        "end_to_end_story",
+        &[
+            "e2e_animal",
+            "e2e_color",
+            "e2e_creature",
+            "e2e_story",
+            "e2e_chant",
+        ],
+    )
+    .await;
+
+    funboy
+        .add_substitutes("e2e_animal", &["fox", "owl", "{print(\"hare\")}"])
+        .await
+        .unwrap();
+    funboy
+        .add_substitutes("e2e_color", &["red", "teal"])
+        .await
+        .unwrap();
+    funboy
+        .add_substitutes("e2e_creature", &["^e2e_color ^e2e_animal"])
+        .await
+        .unwrap();
+    funboy
+        .add_substitutes(
+            "e2e_story",
+            &["+e2e_creature-1+ met +e2e_creature-1+ and a {print(get_sub(\"`e2e_animal`\"))}"],
+        )
+        .await
+        .unwrap();
+    funboy
+        .add_substitutes("e2e_chant", &["{repeat(600, print(\"fox \"))}"])
+        .await
+        .unwrap();
+
+    funboy
+}
+
+async fn deep_funboy() -> Funboy {
+    let funboy = funboy_without(
+        "end_to_end_deep",
+        &["e2e_deep_0", "e2e_deep_1", "e2e_deep_2", "e2e_deep_3"],
+    )
+    .await;
+
+    // Each template nests the next so generating the first needs one pass per template
+    for depth in 0..3 {
+        funboy
+            .add_substitutes(
+                &format!("e2e_deep_{}", depth),
+                &[&format!("^e2e_deep_{}", depth + 1)],
+            )
+            .await
+            .unwrap();
+    }
+    funboy
+        .add_substitutes("e2e_deep_3", &["bottom"])
+        .await
+        .unwrap();
+
+    funboy
+}
+
+fn interpreter() -> Arc<Mutex<FslInterpreter>> {
+    Arc::new(Mutex::new(FslInterpreter::new()))
+}
+
+fn assert_resolved(output: &str) {
+    for delimiter in ['^', '`', '+', '{', '}'] {
+        assert!(
+            !output.contains(delimiter),
+            "{:?} left in {}",
+            delimiter,
+            output
+        );
+    }
+}
+
+fn assert_fits_in_messages(output: &str) {
+    let chunks = split_message(output);
+    assert!(!chunks.is_empty());
+    assert!(chunks.concat().len() <= output.len());
+    for chunk in &chunks {
+        assert!(chunk.len() <= DISCORD_CHARACTER_LIMIT);
+    }
+    for message in split_messages(&chunks) {
+        assert!(message.len() <= DISCORD_CHARACTER_LIMIT);
+    }
+}
+
+#[tokio::test]
+async fn scripted_session() {
+    let funboy = story_funboy().await;
+    let animals = ["fox", "owl", "hare"];
+
+    for _ in 0..10 {
+        let output = funboy
+            .generate("^e2e_story", interpreter())
+            .await
+            .unwrap()
+            .text;
+        assert_resolved(&output);
+        assert!(output.len() <= 100, "{}", output);
+
+        // A register repeats the substitute it was first given in the same generation
+        let (first, rest) = output.split_once(" met ").unwrap();
+        let (second, last) = rest.split_once(" and a ").unwrap();
+        assert!(first == second, "{}", output);
+        assert!(animals.contains(&last), "{}", output);
+
+        let (color, animal) = first.split_once(' ').unwrap();
+        assert!(["red", "teal"].contains(&color));
+        assert!(animals.contains(&animal));
+
+        assert_fits_in_messages(&output);
+    }
+
+    // Long generations are split into as many messages as they need
+    let chant = funboy
+        .generate("^e2e_chant", interpreter())
+        .await
+        .unwrap()
+        .text;
+    assert_resolved(&chant);
+    assert!(chant.len() > DISCORD_CHARACTER_LIMIT);
+    assert!(chant.split_whitespace().all(|word| word == "fox"));
+    assert_fits_in_messages(&chant);
+
+    #[cfg(feature = "ollama")]
+    {
+        use funboy_core::{
+            ollama::{OllamaSettings, PresetMode, apply_preset},
+            template_database::PromptPreset,
+        };
+
+        // Prompt assembly is checked without an Ollama server
+        let preset = PromptPreset {
+            id: 0,
+            name: "e2e".to_string(),
+            owner_id: 0,
+            body: "{username} asked for a story about {prompt}".to_string(),
+            wrap_prompt: true,
+        };
+        assert!(preset.mode() == PresetMode::Wrap);
+        let story = funboy
+            .generate("^e2e_creature", interpreter())
+            .await
+            .unwrap()
+            .text;
+        let (prompt, _) = apply_preset(&preset, &story, "jane", &OllamaSettings::default());
+        assert!(prompt == format!("jane asked for a story about {}", story));
+        assert_fits_in_messages(&prompt);
+    }
+}
+
+#[tokio::test]
+async fn depth_modes() {
+    let funboy = deep_funboy().await;
+    let shallow = |depth_mode| GenerateOptions {
+        max_depth: 2,
+        depth_mode,
+    };
+
+    let output = funboy
+        .generate_with_options("^e2e_deep_0", interpreter(), GenerateOptions::default())
+        .await
+        .unwrap();
+    assert!(output.text == "bottom");
+    assert!(output.warnings.is_empty());
+
+    let lenient = funboy
+        .generate_with_options("^e2e_deep_0", interpreter(), shallow(DepthMode::Lenient))
+        .await
+        .unwrap();
+    assert!(lenient.text.starts_with("^e2e_deep_"));
+    assert!(lenient.warnings.len() == 1);
+    assert_fits_in_messages(&lenient.text);
+
+    let strict = funboy
+        .generate_with_options("^e2e_deep_0", interpreter(), shallow(DepthMode::Strict))
+        .await;
+    assert!(matches!(
+        strict,
+        Err(FunboyError::UserInput(
+            UserFacingError::NestingTooDeep { .. }
+        ))
+    ));
+}
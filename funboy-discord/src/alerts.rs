@@ -0,0 +1,79 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use funboy_core::events::FunboyEvent;
+use serenity::all::{ChannelId, Http};
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+
+/// How long an alert of one kind keeps later alerts of the same kind from being posted
+pub const ALERT_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// When an alert of each kind was last posted so operators aren't flooded
+#[derive(Debug, Default)]
+pub struct AlertLimiter {
+    posted: HashMap<&'static str, Instant>,
+}
+
+impl AlertLimiter {
+    /// Records kind as posted at now unless it was already posted within [`ALERT_INTERVAL`]
+    pub fn try_post(&mut self, kind: &'static str, now: Instant) -> bool {
+        if let Some(posted) = self.posted.get(kind)
+            && now.saturating_duration_since(*posted) < ALERT_INTERVAL
+        {
+            return false;
+        }
+        self.posted.insert(kind, now);
+        true
+    }
+}
+
+/// Posts every event received in the audit channel, rate limited per kind, until the bot shuts down
+pub async fn forward_alerts(
+    http: Arc<Http>,
+    channel_id: ChannelId,
+    mut events: Receiver<FunboyEvent>,
+) {
+    let mut limiter = AlertLimiter::default();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "alerts fell behind generation events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        tracing::info!(kind = event.kind(), event = %event, "generation crossed a threshold");
+
+        if !limiter.try_post(event.kind(), Instant::now()) {
+            continue;
+        }
+        if let Err(e) = channel_id.say(&http, format!("Alert: {}", event)).await {
+            tracing::warn!(error = %e, "failed to post alert");
+        }
+    }
+}
+
+#[cfg(test)]
+mod alerts_test {
+    use super::*;
+
+    #[test]
+    fn one_alert_per_kind_per_interval() {
+        let mut limiter = AlertLimiter::default();
+        let start = Instant::now();
+
+        assert!(limiter.try_post("slow_generation", start));
+        assert!(!limiter.try_post("slow_generation", start + Duration::from_secs(60)));
+        assert!(limiter.try_post("depth_limit_hit", start + Duration::from_secs(60)));
+        assert!(!limiter.try_post(
+            "slow_generation",
+            start + ALERT_INTERVAL - Duration::from_secs(1)
+        ));
+        assert!(limiter.try_post("slow_generation", start + ALERT_INTERVAL));
+        assert!(!limiter.try_post("depth_limit_hit", start + ALERT_INTERVAL));
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+
+use serenity::all::{
+    ChannelId, ChannelType, GuildChannel, GuildId, Http, Mentionable, Permissions,
+};
+
+/// Separates a parent channel from a thread name, as in `parent/thread-name`
+pub const THREAD_SEPARATOR: char = '/';
+
+/// The channel a thread was created in
+#[derive(Debug, Clone)]
+pub struct ParentChannel {
+    pub id: ChannelId,
+    pub name: String,
+}
+
+/// A guild channel or thread that messages can be sent to
+#[derive(Debug, Clone)]
+pub struct ChannelEntry {
+    pub id: ChannelId,
+    pub name: String,
+    /// Only set for threads
+    pub parent: Option<ParentChannel>,
+}
+
+impl ChannelEntry {
+    pub fn is_thread(&self) -> bool {
+        self.parent.is_some()
+    }
+
+    /// The channel Discord evaluates permissions against, threads inherit from their parent
+    pub fn permission_channel_id(&self) -> ChannelId {
+        match &self.parent {
+            Some(parent) => parent.id,
+            None => self.id,
+        }
+    }
+
+    fn path(&self) -> String {
+        match &self.parent {
+            Some(parent) => format!("{}{}{}", parent.name, THREAD_SEPARATOR, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+fn is_messageable(kind: ChannelType) -> bool {
+    matches!(
+        kind,
+        ChannelType::Text
+            | ChannelType::News
+            | ChannelType::Voice
+            | ChannelType::PublicThread
+            | ChannelType::PrivateThread
+            | ChannelType::NewsThread
+    )
+}
+
+/// Builds entries for the messageable channels and active threads of a guild
+fn channel_entries(channels: &[GuildChannel], threads: &[GuildChannel]) -> Vec<ChannelEntry> {
+    let names: HashMap<ChannelId, &str> = channels
+        .iter()
+        .map(|channel| (channel.id, channel.name.as_str()))
+        .collect();
+
+    let channel_entries = channels
+        .iter()
+        .filter(|channel| is_messageable(channel.kind))
+        .map(|channel| ChannelEntry {
+            id: channel.id,
+            name: channel.name.clone(),
+            parent: None,
+        });
+
+    let thread_entries = threads.iter().filter_map(|thread| {
+        let parent_id = thread.parent_id?;
+        Some(ChannelEntry {
+            id: thread.id,
+            name: thread.name.clone(),
+            parent: Some(ParentChannel {
+                id: parent_id,
+                name: names.get(&parent_id)?.to_string(),
+            }),
+        })
+    });
+
+    channel_entries.chain(thread_entries).collect()
+}
+
+/// Fetches the messageable channels and active threads of a guild
+pub async fn fetch_channel_entries(
+    http: &Http,
+    guild_id: GuildId,
+) -> Result<Vec<ChannelEntry>, serenity::Error> {
+    let channels: Vec<GuildChannel> = guild_id.channels(http).await?.into_values().collect();
+    let threads = guild_id.get_active_threads(http).await?.threads;
+    Ok(channel_entries(&channels, &threads))
+}
+
+fn find_matches<'a>(
+    name: &str,
+    candidates: impl Iterator<Item = &'a ChannelEntry> + Clone,
+) -> Vec<&'a ChannelEntry> {
+    let exact: Vec<&ChannelEntry> = candidates
+        .clone()
+        .filter(|entry| entry.name == name)
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+
+    let lowercase_name = name.to_lowercase();
+    candidates
+        .filter(|entry| entry.name.to_lowercase() == lowercase_name)
+        .collect()
+}
+
+/// Resolves a channel name, `#name`, a channel mention, or `parent/thread-name` to a channel
+///
+/// Exact names are preferred over case-insensitive ones. When several channels share the
+/// name the error lists each of them so the user can pick one by mention.
+pub fn resolve_channel<'a>(
+    input: &str,
+    channels: &'a [ChannelEntry],
+) -> Result<&'a ChannelEntry, String> {
+    let input = input.trim();
+
+    if let Some(id) = input
+        .strip_prefix("<#")
+        .and_then(|id| id.strip_suffix('>'))
+        .and_then(|id| id.parse::<u64>().ok())
+        .filter(|id| *id != 0)
+    {
+        return channels
+            .iter()
+            .find(|entry| entry.id.get() == id)
+            .ok_or_else(|| format!("no channel {} found", ChannelId::new(id).mention()));
+    }
+
+    let input = input.strip_prefix('#').unwrap_or(input);
+
+    let matches = match input.split_once(THREAD_SEPARATOR) {
+        Some((parent, thread)) => {
+            let parents = find_matches(parent, channels.iter().filter(|entry| !entry.is_thread()));
+            find_matches(
+                thread,
+                channels.iter().filter(|entry| {
+                    entry.parent.as_ref().is_some_and(|thread_parent| {
+                        parents.iter().any(|parent| parent.id == thread_parent.id)
+                    })
+                }),
+            )
+        }
+        None => find_matches(input, channels.iter().filter(|entry| !entry.is_thread())),
+    };
+
+    match matches.as_slice() {
+        [] => Err(format!("no channel named {} found", input)),
+        [entry] => Ok(entry),
+        candidates => {
+            let candidates: Vec<String> = candidates
+                .iter()
+                .map(|entry| format!("{} ({})", entry.path(), entry.id.mention()))
+                .collect();
+            Err(format!(
+                "more than one channel is named {}, use a mention to pick one of {}",
+                input,
+                candidates.join(", ")
+            ))
+        }
+    }
+}
+
+/// The permissions needed to post a message in channel
+pub fn required_send_permissions(channel: &ChannelEntry) -> Permissions {
+    if channel.is_thread() {
+        Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES_IN_THREADS
+    } else {
+        Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES
+    }
+}
+
+/// Checks that permissions allow posting a message in channel
+pub fn check_send_permission(
+    permissions: Permissions,
+    channel: &ChannelEntry,
+) -> Result<(), String> {
+    let missing = required_send_permissions(channel) - permissions;
+    if missing.is_empty() || permissions.administrator() {
+        Ok(())
+    } else {
+        Err(format!(
+            "missing permission to send messages in {} ({})",
+            channel.path(),
+            missing.get_permission_names().join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod channel_resolver_test {
+    use super::*;
+
+    fn channel(id: u64, name: &str) -> ChannelEntry {
+        ChannelEntry {
+            id: ChannelId::new(id),
+            name: name.to_string(),
+            parent: None,
+        }
+    }
+
+    fn thread(id: u64, name: &str, parent: &ChannelEntry) -> ChannelEntry {
+        ChannelEntry {
+            id: ChannelId::new(id),
+            name: name.to_string(),
+            parent: Some(ParentChannel {
+                id: parent.id,
+                name: parent.name.clone(),
+            }),
+        }
+    }
+
+    fn fixture() -> Vec<ChannelEntry> {
+        let general = channel(1, "general");
+        let memes = channel(2, "memes");
+        let games = channel(3, "Games");
+        vec![
+            thread(10, "Weekly Thread", &general),
+            thread(11, "Weekly Thread", &memes),
+            thread(12, "bugs/crashes", &general),
+            general,
+            memes,
+            games,
+            channel(4, "announcements"),
+            channel(5, "announcements"),
+        ]
+    }
+
+    fn resolved_id(input: &str, channels: &[ChannelEntry]) -> Result<u64, String> {
+        resolve_channel(input, channels).map(|entry| entry.id.get())
+    }
+
+    #[test]
+    fn resolves_names_and_mentions() {
+        let channels = fixture();
+        assert_eq!(resolved_id("general", &channels), Ok(1));
+        assert_eq!(resolved_id("#memes", &channels), Ok(2));
+        assert_eq!(resolved_id("<#5>", &channels), Ok(5));
+        assert_eq!(resolved_id("games", &channels), Ok(3));
+    }
+
+    #[test]
+    fn names_do_not_match_threads() {
+        assert!(resolved_id("Weekly Thread", &fixture()).is_err());
+    }
+
+    #[test]
+    fn resolves_threads_by_parent() {
+        let channels = fixture();
+        assert_eq!(resolved_id("general/Weekly Thread", &channels), Ok(10));
+        assert_eq!(resolved_id("memes/weekly thread", &channels), Ok(11));
+        // only the first separator splits the parent from the thread
+        assert_eq!(resolved_id("general/bugs/crashes", &channels), Ok(12));
+        assert!(resolved_id("games/Weekly Thread", &channels).is_err());
+    }
+
+    #[test]
+    fn ambiguous_names_list_candidates() {
+        let error = resolved_id("announcements", &fixture()).unwrap_err();
+        assert!(error.contains("more than one channel"));
+        assert!(error.contains("<#4>"));
+        assert!(error.contains("<#5>"));
+    }
+
+    #[test]
+    fn missing_channels() {
+        assert_eq!(
+            resolved_id("random", &fixture()),
+            Err("no channel named random found".to_string())
+        );
+        assert_eq!(
+            resolved_id("<#99>", &fixture()),
+            Err("no channel <#99> found".to_string())
+        );
+    }
+
+    #[test]
+    fn send_permission_for_channels() {
+        let general = channel(1, "general");
+        assert!(
+            check_send_permission(
+                Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+                &general
+            )
+            .is_ok()
+        );
+        assert!(check_send_permission(Permissions::ADMINISTRATOR, &general).is_ok());
+
+        let error = check_send_permission(Permissions::VIEW_CHANNEL, &general).unwrap_err();
+        assert!(error.contains("general"));
+        assert!(error.contains("Send Messages"));
+    }
+
+    #[test]
+    fn send_permission_for_threads() {
+        let general = channel(1, "general");
+        let weekly = thread(10, "weekly", &general);
+        assert_eq!(weekly.permission_channel_id(), general.id);
+
+        assert!(
+            check_send_permission(
+                Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+                &weekly
+            )
+            .is_err()
+        );
+        assert!(
+            check_send_permission(
+                Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES_IN_THREADS,
+                &weekly
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,636 @@
+//! Extended help for every command shown by `/help_command`
+//!
+//! Each command points `help_text_fn` at the function with its name so this table is the only
+//! place long help is written, doc comments on commands hold just the one line description.
+
+/// Defines [`COMMAND_HELP`] along with a function returning the help of each command
+macro_rules! command_help {
+    ($($(#[$attr:meta])* $command:ident => $help:expr,)*) => {
+        /// Command function names paired with their extended help, checked against the
+        /// registered commands by the tests
+        #[allow(dead_code)]
+        pub const COMMAND_HELP: &[(&str, &str)] =
+            &[$($(#[$attr])* (stringify!($command), $help),)*];
+
+        $(
+            $(#[$attr])*
+            pub fn $command() -> String {
+                $help.to_string()
+            }
+        )*
+    };
+}
+
+command_help! {
+    #[cfg(feature = "ollama")]
+    list_ollama_models => concat!(
+        "The model chosen with `/set_ollama_model` is used by `/generate_ollama`.\n",
+        "\n",
+        "**Example:** `/list_ollama_models`",
+    ),
+    #[cfg(feature = "ollama")]
+    list_ollama_settings => concat!(
+        "Shows the model, parameters, system prompt, template and word limit your `/generate_ollama` prompts use.\n",
+        "\n",
+        "**Example:** `/list_ollama_settings`",
+    ),
+    #[cfg(feature = "ollama")]
+    set_ollama_model => concat!(
+        "The model must be one of the models listed by `/list_ollama_models`.\n",
+        "\n",
+        "**Example:** `/set_ollama_model llama3`",
+    ),
+    #[cfg(feature = "ollama")]
+    set_ollama_parameters => concat!(
+        "Only the parameters given are changed, use `/reset_ollama_parameters` to go back to the defaults.\n",
+        "- `temperature`: higher values give more varied output\n",
+        "- `repeat_penalty`: higher values make repeating words less likely\n",
+        "- `top_k`: how many of the most likely words are considered\n",
+        "- `top_p`: how much of the total likelihood is considered\n",
+        "\n",
+        "**Example:** `/set_ollama_parameters temperature: 1.2 top_k: 60`",
+    ),
+    #[cfg(feature = "ollama")]
+    reset_ollama_parameters => concat!(
+        "Undoes every change made with `/set_ollama_parameters`.\n",
+        "\n",
+        "**Example:** `/reset_ollama_parameters`",
+    ),
+    #[cfg(feature = "ollama")]
+    set_ollama_system_prompt => concat!(
+        "The system prompt tells the model how to respond to every prompt you send with `/generate_ollama`.\n",
+        "It is sent as written, templates and FSL in it are not generated. System prompts are at most 4000 characters long.\n",
+        "\n",
+        "**Example:** `/set_ollama_system_prompt Answer in a single sentence.`",
+    ),
+    #[cfg(feature = "ollama")]
+    reset_ollama_system_prompt => concat!(
+        "Undoes the change made with `/set_ollama_system_prompt`.\n",
+        "\n",
+        "**Example:** `/reset_ollama_system_prompt`",
+    ),
+    #[cfg(feature = "ollama")]
+    set_ollama_template => concat!(
+        "The template controls how the system prompt and prompt are combined before being sent to the model.\n",
+        "It is sent as written, templates and FSL in it are not generated. Templates are at most 4000 characters long.\n",
+        "\n",
+        "**Example:** `/set_ollama_template {{ .System }} {{ .Prompt }}`",
+    ),
+    #[cfg(feature = "ollama")]
+    reset_ollama_template => concat!(
+        "Undoes the change made with `/set_ollama_template`.\n",
+        "\n",
+        "**Example:** `/reset_ollama_template`",
+    ),
+    #[cfg(feature = "ollama")]
+    set_ollama_word_limit => concat!(
+        "The limit can't be raised above the maximum the bot allows.\n",
+        "\n",
+        "**Example:** `/set_ollama_word_limit 200`",
+    ),
+    #[cfg(feature = "ollama")]
+    save_prompt_preset => concat!(
+        "`{prompt}` in the preset is replaced with the prompt and `{username}` with your name.\n",
+        "By default the preset becomes the system prompt, use `wrap_prompt: true` to replace the prompt\n",
+        "with the preset instead.\n",
+        "\n",
+        "**Example:** `/save_prompt_preset pirate \"Answer {username} like a pirate would\"`\n",
+        "\n",
+        "**Example:** `/save_prompt_preset haiku \"Write a haiku about {prompt}\" wrap_prompt: true`\n",
+        "\n",
+        "Presets are private unless saved with `global: true`. Your own presets are used over\n",
+        "global presets with the same name.",
+    ),
+    #[cfg(feature = "ollama")]
+    list_prompt_presets => concat!(
+        "Lists your own presets followed by the global presets, save new ones with `/save_prompt_preset`.\n",
+        "\n",
+        "**Example:** `/list_prompt_presets`",
+    ),
+    #[cfg(feature = "ollama")]
+    generate_ollama => "Use `preset:` to apply a prompt preset saved with `/save_prompt_preset`.",
+    #[cfg(feature = "ollama")]
+    set_ai_label => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "The label is posted on its own line with the first message of a response, or the last when placed after. ",
+        "Labels are at most 100 characters long.\n",
+        "\n",
+        "**Example:** `/set_ai_label True` — labels responses with `🤖 AI-generated`\n",
+        "**Example:** `/set_ai_label True [bot] After` — ends responses with `[bot]`\n",
+        "\n",
+        "Use `/set_ai_label False` to stop labelling responses.",
+    ),
+    random_number => concat!(
+        "The max is included unless inclusive is set to false, so 1 to 6 can roll a 6.\n",
+        "Dice are written like d20 or 3d6 and can't be combined with min and max.",
+    ),
+    random_entry => "Entries are seperated by spaces and multi-word entries can be enclosed in quotes like \"hot dog\"",
+    join_voice => concat!(
+        "You must be in a voice channel for the bot to join it.\n",
+        "\n",
+        "**Example:** `/join_voice`",
+    ),
+    leave_voice => concat!(
+        "Any playing tracks are stopped when the bot leaves.\n",
+        "\n",
+        "**Example:** `/leave_voice`",
+    ),
+    play_track => concat!(
+        "Example usage: **/play_track** url_or_query: **https://www.youtube.com/watch?v=a3mxLL7nX1E**\n",
+        "Example usage: **/play_track** url_or_query: **Back In Black**",
+    ),
+    stop_tracks => concat!(
+        "Stops and removes every track, the bot stays in the voice channel.\n",
+        "\n",
+        "**Example:** `/stop_tracks`",
+    ),
+    list_tracks => concat!(
+        "Tracks are listed with their duration and who added them, use the buttons to change pages.\n",
+        "Playback controls only respond to members in the bot's voice channel.\n",
+        "\n",
+        "**Example:** `/list_tracks sort: Recently Added` — lists the newest tracks first\n",
+        "**Example:** `/list_tracks controls: True` — also shows playback controls for every track",
+    ),
+    track_history => concat!(
+        "Lists the last 20 tracks played in this server with who requested them and when.\n",
+        "Tracks that were stopped or are still playing are marked as not finished.\n",
+        "\n",
+        "**Example:** `/track_history`",
+    ),
+    set_track_announcements => concat!(
+        "Only members with the manage server permission can use this command.\n",
+        "\n",
+        "**Example:** `/set_track_announcements #music` — posts each track in `#music` as it starts\n",
+        "\n",
+        "Leave out the channel to stop announcing tracks.",
+    ),
+    generate => concat!(
+        "## Templates\n",
+        "Templates are any text preceded or optionally followed by a template character.\n",
+        "The character `^` replaces the template with a random substitute.\n",
+        "\n",
+        "**Examples:** `^noun` `^noun^` `^verb^ed` (note: `verb` is the template, `ed` is not)\n",
+        "\n",
+        "**Given templates:**\n",
+        "- `noun`: \"fox\", \"dog\"\n",
+        "- `adj`: \"quick\", \"lazy\"\n",
+        "- `color`: \"brown\"\n",
+        "- `verb`: \"jump\"\n",
+        "\n",
+        "**Example:** `/generate The ^adj ^color ^noun ^verb^ed over the ^adj ^noun`\n",
+        "- Possible output: \"The quick brown fox jumped over the lazy dog\"\n",
+        "- Possible output: \"The lazy brown dog jumped over the quick fox\"\n",
+        "## Template aliases\n",
+        "The character `+` replaces the template with a random substitute **once** — all subsequent uses refer to the same substitute.\n",
+        "\n",
+        "**Examples:** `+name` `+name+` `+name-1` `+name-1+` (aliases defined with `-`)\n",
+        "\n",
+        "**Example:** `/generate +name-1 is female. +name-2 is male. +name-1 is short. +name-2 is tall.`\n",
+        "- Possible output: \"Jane is female. John is male. Jane is short. John is tall.\"\n",
+        "\n",
+        "A generation can use up to 100 different aliases and each can hold up to 16000 characters.\n",
+        "## Guild templates\n",
+        "`^_member` is replaced with the name of a random member of the server and `^_emoji` with a random custom emoji of the server.\n",
+        "## Embedded code\n",
+        "Code between `{}` is executed as FSL (Funboy Scripting Language) code.\n",
+        "\n",
+        "**Example:** `/generate The following text is reversed: {print(reverse(\"reversed\"))}`\n",
+        "- Output: \"The following text is reversed: desrever\"\n",
+        "\n",
+        "Commands in a block can be separated with `;` to make long scripts easier to read.\n",
+        "\n",
+        "**Example:** `/generate {store(\"hello\", h); print(h)}`\n",
+        "- Output: \"hello\"\n",
+        "\n",
+        "For more FSL information, use `/help_fsl`\n",
+        "## Output format\n",
+        "Use `format:` to choose how the output is sent.\n",
+        "- `Plain`: regular messages (default)\n",
+        "- `Embed`: an embed titled with the input, sent as a file when too long for an embed\n",
+        "- `Spoiler`: hidden behind spoiler tags\n",
+        "- `Code Block`: inside of a code block, preserving whitespace\n",
+        "## Session variables\n",
+        "Use `save_as:` to keep the output for an hour, later generations can read it with `clone(name)`.\n",
+        "Names use lowercase letters, digits and `_` and can't start with a digit.\n",
+        "\n",
+        "**Example:** `/generate ^name save_as: hero` then `/generate {clone(hero)} rides off into the sunset`\n",
+        "- Possible output: \"Jane rides off into the sunset\"\n",
+        "\n",
+        "`/session_vars` lists your variables and `/clear_session` deletes them.\n",
+        "## Context variables\n",
+        "Every generation can also read where and by whom it was started, these can't be overwritten or used with `save_as:`.\n",
+        "- `ctx_author`: your display name\n",
+        "- `ctx_author_id`: your user id\n",
+        "- `ctx_channel`: the channel name, empty outside of a server\n",
+        "- `ctx_guild`: the server name, empty outside of a server\n",
+        "- `ctx_time`: when the command was used in RFC 3339 format\n",
+        "\n",
+        "**Example:** `/generate {print(clone(ctx_author))} summons a ^noun`\n",
+        "- Possible output: \"Jane summons a fox\"\n",
+        "## Server aliases\n",
+        "When the whole input is the name of an alias made with `/alias add` its input is generated instead. ",
+        "Set `no_alias: True` to generate the input as written.",
+    ),
+    debug_generate => concat!(
+        "Every top level command of every `{}` block is listed in the order it ran next to its value.\n",
+        "\n",
+        "**Example:** `/debug_generate {store(\"a\", x) concat(\"b\", clone(x))}`\n",
+        "- Log: `store(\"a\", x) => None` `concat(\"b\", clone(x)) => \"ba\"`",
+    ),
+    add_subs => concat!(
+        "Substitutes are space-separated words or quoted phrases. Use quotes for multi-word substitutes.\n",
+        "\n",
+        "**Examples:**\n",
+        "- `/add_subs noun cat dog bird` — adds three single-word substitutes\n",
+        "- `/add_subs noun \"hot dog\" \"cold pizza\"` — adds two multi-word substitutes\n",
+        "\n",
+        "## Single substitute mode\n",
+        "Use `add_as_single_sub: true` for large or complex substitutes, especially those containing quotes.\n",
+        "\n",
+        "**Example:** `/add_subs quote this substitute contains \"a quote\" in it add_as_single_sub: true` - adds a single substitute with quotes inside\n",
+        "\n",
+        "This treats the entire input as a single substitute allowing spaces and quotes inside the substitute.\n",
+        "\n",
+        "## Code validation\n",
+        "Substitutes with embedded code that can't be parsed are added with a warning.\n",
+        "Use `validate_code: true` to leave them out instead.",
+    ),
+    add_message_sub => "Opens a prompt asking which template the message should be added to.",
+    delete_subs => concat!(
+        "Substitutes can be deleted by name or by ID, space-separated.\n",
+        "\n",
+        "## Delete by name\n",
+        "- **Example:** `/delete_subs noun cat dog` — removes \"cat\" and \"dog\" from the `noun` template\n",
+        "- **Example:** `/delete_subs name \"hot dog\"` — removes the \"hot dog\" substitute\n",
+        "\n",
+        "## Delete by ID\n",
+        "- **Example:** `/delete_subs noun 0 2 5 delete_by_id: true` — removes substitutes with IDs: 0, 2, and 5\n",
+        "\n",
+        "This is useful when substitutes are large and difficult to write out fully inside the command.\n",
+        "Note: IDs of substitutes can be obtained by using the `/list_subs` command with the ID list style.\n",
+        "\n",
+        "## Single substitute mode\n",
+        "Use `delete_as_single_sub: true` to treat the entire input as a single substitute name or ID.\n",
+        "Useful for complex substitute names containing spaces or quotes.\n",
+        "\n",
+        "**Example:** `/delete_subs template: sentence subs: This is one substitute containing \"spaces and quotes inside it\" delete_as_single_sub: true`",
+    ),
+    disable_subs => concat!(
+        "Disabled substitutes stay in their template and show up in `/list_subs` marked as disabled, but they are never generated.\n",
+        "Give `ids` to disable substitutes by ID, otherwise every substitute of `template` containing `search_term` is disabled.\n",
+        "\n",
+        "**Example:** `/disable_subs template: joke search_term: santa` — silences every `joke` mentioning santa\n",
+        "**Example:** `/disable_subs ids: 4 9` — disables the substitutes with IDs 4 and 9",
+    ),
+    enable_subs => concat!(
+        "Takes the same options as `/disable_subs`, leaving out `search_term` enables every substitute of the template.\n",
+        "\n",
+        "**Example:** `/enable_subs template: joke search_term: santa`",
+    ),
+    upload_sub => "**Example:** `/upload_sub essay [essay.txt]` — uploads file `essay.txt` and adds it as a single substitute to the `essay`",
+    copy_subs => "**Example:** `/copy_subs food noun` — copies all substitutes from `food` to `noun`",
+    clone_template => "**Example:** `/clone_template noun animal` — creates `animal` with all of the substitutes in `noun`",
+    set_template_examples => concat!(
+        "Examples are space-separated, use quotes for examples containing spaces.\n",
+        "Examples are stored as plain text and are shown by `/preview_template`.\n",
+        "\n",
+        "**Example:** `/set_template_examples insult \"you absolute ^noun\" \"what a ^adj ^noun\"`\n",
+        "\n",
+        "Leave `examples` empty to remove every example from a template.",
+    ),
+    preview_template => "**Example:** `/preview_template noun`",
+    set_template_description => concat!(
+        "Descriptions note why a template exists or how its substitutes should be written, they can be up to 1000 characters long and are shown by `/template_info`.\n",
+        "\n",
+        "**Example:** `/set_template_description adj \"adjectives, lowercase, no proper nouns\"`\n",
+        "\n",
+        "Leave `description` empty to remove it.",
+    ),
+    template_info => concat!(
+        "Shows the description of a template with its substitute count, substitute cap, when it was created, whether it is archived and its examples.\n",
+        "\n",
+        "**Example:** `/template_info adj`",
+    ),
+    test_sub => concat!(
+        "Generates the content as if it had been picked from the template and lists anything that would go wrong, ",
+        "such as code that fails to parse or templates that don't exist. Nothing is added until you press **Add it**.\n",
+        "\n",
+        "**Example:** `/test_sub animal \"a ^color cat\"` — shows what the new substitute of `animal` would generate",
+    ),
+    replace_sub => concat!(
+        "Substitutes can be replaced by name or by ID.\n",
+        "\n",
+        "## Replace by name\n",
+        "- **Example:** `/replace_sub noun cat dog` — replaces the \"cat\" substitute with \"dog\"\n",
+        "- **Example:** `/replace_sub name \"hot dog\" \"cold pizza\"` — replaces \"hot dog\" with \"cold pizza\"\n",
+        "\n",
+        "## Replace by ID\n",
+        "- **Example:** `/replace_sub noun 0 \"new substitute\" replace_by_id: true` — replaces the substitute with id 0\n",
+        "Note: ID's of substitutes can be obtained by using the `/list_subs` command with the ID list style.",
+    ),
+    edit_sub => concat!(
+        "**Example:** `/edit_sub 12` — edits the substitute with id 12\n",
+        "Note: ID's of substitutes can be obtained by using the `/list_subs` command with the ID list style.",
+    ),
+    alias => concat!(
+        "Aliases are shortcuts for inputs used often, `/generate` expands one when its whole input is the alias name.\n",
+        "An alias whose input is the name of another alias is not expanded again.\n",
+        "Aliases are named like templates and each server can have up to 100. Adding and removing them needs the Manage Server permission.\n",
+        "\n",
+        "**Example:** `/alias add joke Why did the ^noun cross the road?` then `/generate joke`\n",
+        "**Example:** `/alias remove joke`\n",
+        "**Example:** `/alias list`",
+    ),
+    set_fsl_permissions => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "Limits `say`, `say_to`, `say_in`, `ask`, `ask_to` and `ask_choice` to administrators and the allowed roles. ",
+        "Everyone else gets a permission error when their scripts use them, the rest of FSL keeps working.\n",
+        "While no roles are allowed everyone can use them.\n",
+        "\n",
+        "**Example:** `/set_fsl_permissions @Trusted` — allows `@Trusted`\n",
+        "**Example:** `/set_fsl_permissions @Trusted allowed: False` — removes `@Trusted`\n",
+        "**Example:** `/set_fsl_permissions` — lists the allowed roles",
+    ),
+    allow_generate_channel => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "Once any channel is allowed, `/generate` and `/generate_ollama` only work in allowed channels and their threads. ",
+        "Scripts can't `say` outside of them either.\n",
+        "While no channels are allowed generating works everywhere.\n",
+        "\n",
+        "**Example:** `/allow_generate_channel #bot-spam` — allows generating in `#bot-spam`",
+    ),
+    disallow_generate_channel => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "Removing the last allowed channel lets generating work everywhere again.\n",
+        "\n",
+        "**Example:** `/disallow_generate_channel #bot-spam` — stops allowing generating in `#bot-spam`",
+    ),
+    list_generate_channels => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "Threads follow the channel they belong to.",
+    ),
+    add_trigger => concat!(
+        "Needs the Manage Server permission.\n",
+        "\n",
+        "When a message matches the pattern the bot replies to it with a generation of the input.\n",
+        "- `Exact`: the whole message is the pattern\n",
+        "- `Contains`: the pattern is anywhere in the message (default)\n",
+        "- `Regex`: the pattern is a regex found anywhere in the message\n",
+        "\n",
+        "Matching ignores case. Messages from bots never set off triggers and replies can't use `say`, `ask` or similar commands.\n",
+        "Each server can have up to 25 triggers. Adding a trigger with the same pattern and mode replaces it.\n",
+        "\n",
+        "**Example:** `/add_trigger who's that pokemon It's ^pokemon!` — replies with a random pokemon\n",
+        "**Example:** `/add_trigger ^(hi|hello)$ ^greeting mode: Regex cooldown: 600` — greets at most every 10 minutes",
+    ),
+    remove_trigger => concat!(
+        "Needs the Manage Server permission.\n",
+        "\n",
+        "**Example:** `/remove_trigger 3` — removes trigger 3 shown by `/list_triggers`",
+    ),
+    list_triggers => concat!(
+        "Needs the Manage Server permission.\n",
+        "\n",
+        "Triggers are listed in the order they are checked, a message only sets off the first one it matches.",
+    ),
+    set_daily_template_channel => concat!(
+        "Needs the Manage Server permission.\n",
+        "\n",
+        "Once a day a template is picked and posted with its substitute count and three samples of it.\n",
+        "- `Random`: any template (default)\n",
+        "- `Least Recently Featured`: templates never featured first, then the one featured longest ago\n",
+        "- `Most Used`: the template used most in this server over the last 30 days\n",
+        "\n",
+        "A template isn't featured again for 7 days unless every template was.\n",
+        "\n",
+        "**Example:** `/set_daily_template_channel #general Most Used` — posts the most used template in `#general` each day\n",
+        "\n",
+        "Leave out the channel to stop posting.",
+    ),
+    set_template_cap => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "Templates hold at most 10000 substitutes unless given their own cap.\n",
+        "\n",
+        "**Example:** `/set_template_cap noun 50000` — lets `noun` hold up to 50000 substitutes\n",
+        "\n",
+        "Leave out the cap to go back to the default.",
+    ),
+    set_template_strategy => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "Random picks any enabled substitute every time. Round robin goes through every enabled ",
+        "substitute once before repeating any, least recently used picks the one that came up longest ago. ",
+        "Disabled substitutes are skipped by every strategy.\n",
+        "\n",
+        "**Example:** `/set_template_strategy quiz_question Round Robin` — asks every `quiz_question` before repeating one",
+    ),
+    favorite_template => "**Example:** `/favorite_template noun` — adds `noun` to your favorites",
+    unfavorite_template => "**Example:** `/unfavorite_template noun` — removes `noun` from your favorites",
+    quick_generate => "Templates can be added to your favorites with `/favorite_template`",
+    delete_templates => concat!(
+        "Template names are space-separated.\n",
+        "\n",
+        "**Example:** `/delete_templates noun verb adjective` — deletes all three templates\n",
+        "\n",
+        "This action cannot be undone.",
+    ),
+    delete_templates_matching => concat!(
+        "Only members with the manage server permission can use this command.\n",
+        "\n",
+        "## Filters\n",
+        "- `name`: glob where `*` matches anything and `?` a single character\n",
+        "- `min_subs` / `max_subs`: bounds on the number of substitutes\n",
+        "- `older_than_days`: only templates created more than this many days ago\n",
+        "- `archived`: only archived or only active templates\n",
+        "\n",
+        "**Example:** `/delete_templates_matching name: test_* max_subs: 0` — deletes empty templates starting with `test_`\n",
+        "\n",
+        "Use `archive: True` to archive the matching templates instead of deleting them.\n",
+        "\n",
+        "Deleting cannot be undone.",
+    ),
+    rename_template => concat!(
+        "**Example:** `/rename_template noun thing` — renames the `noun` template to `thing`\n",
+        "\n",
+        "All substitutes under the previous name will now be under the new name\n",
+        "\n",
+        "## Preview\n",
+        "Use `preview` to list every substitute the rename will rewrite before confirming it.\n",
+        "\n",
+        "**Example:** `/rename_template noun thing preview: True`",
+    ),
+    rewrite_references => concat!(
+        "Only server administrators can use this command.\n",
+        "\n",
+        "Rewrites references to a template in substitutes the way `/rename_template` would, without renaming any template. ",
+        "Use it to fix references after substitutes were edited by hand or to move them ahead of a rename. ",
+        "Every substitute that changes is listed before you confirm.\n",
+        "\n",
+        "**Example:** `/rewrite_references fruit food` — rewrites `^fruit` to `^food` in every substitute\n",
+        "**Example:** `/rewrite_references fruit food in_template: sentence` — only rewrites substitutes of `sentence`",
+    ),
+    list_subs => concat!(
+        "**Example:** `/list_subs noun` — displays all substitutes for the `noun` template\n",
+        "\n",
+        "## Search\n",
+        "Use `search_term` to filter results.\n",
+        "\n",
+        "**Example:** `/list_subs noun search_term: dog` — shows only substitutes containing \"dog\"\n",
+        "\n",
+        "## Kinds\n",
+        "Substitutes are sorted into kinds when they are added or edited:\n",
+        "- `Prose` — plain text\n",
+        "- `Code` — nothing but `{}` code blocks\n",
+        "- `Mixed` — text alongside code blocks or template references\n",
+        "Escaped delimiters such as `\\^noun` and anything inside of a triple backtick fence count as text.\n",
+        "\n",
+        "**Example:** `/list_subs joke kind: Code` — shows only the jokes written as code\n",
+        "\n",
+        "## List styles\n",
+        "- `Default` — standard comma separated format\n",
+        "- `Numeric` — numbered list\n",
+        "- `ID` — shows substitute IDs and kinds\n",
+        "- `File` — uploads text file containing substitutes and their IDs\n",
+        "\n",
+        "**Example:** `/list_subs noun list_style: ID` — displays substitutes with their IDs",
+    ),
+    find_source => concat!(
+        "Needs the Manage Messages permission.\n",
+        "\n",
+        "Candidates are ranked from best to worst:\n",
+        "- `exact` — the substitute is the whole text\n",
+        "- `contains` — the text is part of the substitute\n",
+        "- `fragment` — the substitute is a few words of the text, like one part of a sentence built from several templates\n",
+        "- `similar` — the substitute resembles the text\n",
+        "Case and spacing are ignored.\n",
+        "\n",
+        "**Example:** `/find_source you smell like a wet dog` — lists the substitutes that could have generated the message with their IDs for `/delete_subs`",
+    ),
+    list_templates => concat!(
+        "**Example:** `/list_templates` — displays all templates\n",
+        "\n",
+        "## Search\n",
+        "Use `search_term` to filter results.\n",
+        "\n",
+        "**Example:** `/list_templates search_term: noun` — shows only templates containing \"noun\"\n",
+        "\n",
+        "## List styles\n",
+        "- `Default` — standard format\n",
+        "- `Numeric` — numbered list\n",
+        "- `ID` — shows template IDs\n",
+        "- `File` — uploads text file containing substitutes and their IDs\n",
+        "\n",
+        "**Example:** `/list_templates list_style: ID` — displays templates with their IDs",
+    ),
+    session_vars => "Variables are saved with `/generate save_as:` and expire an hour after the last save.",
+    clear_session => "**Example:** `/clear_session` — forgets every variable saved with `/generate save_as:`",
+    template_leaderboard => concat!(
+        "**Example:** `/template_leaderboard days: 30` — the 10 templates used most in this server over the last 30 days\n",
+        "\n",
+        "Every substitute picked counts as a use of its template. Counts are saved once a minute so the newest uses can take a moment to show up.\n",
+        "In direct messages the leaderboard counts generations made outside of servers.",
+    ),
+    contributors => concat!(
+        "**Example:** `/contributors template: noun days: 30` — the 10 members who added the most substitutes to `noun` in this server over the last 30 days\n",
+        "\n",
+        "Only substitutes added with `/add_subs`, `/upload_sub`, or from a message are credited. Members who left the server are listed by their id.\n",
+        "In direct messages substitutes added from every server are counted.",
+    ),
+    my_contributions => "**Example:** `/my_contributions` — how many substitutes you added to each template in this server",
+    help => concat!(
+        "Commands with extended help are marked with 📖, use `/help_command` to read it.\n",
+        "\n",
+        "**Example:** `/help show_descriptions: True` — lists every command with its description",
+    ),
+    help_command => "**Example:** `/help_command generate` — shows everything `/generate` can do",
+    move_bot_pins => "Example usage: **/move_bot_pins** to_channel: **my-channel**",
+    audit_template => concat!(
+        "Needs the Administrator permission.\n",
+        "\n",
+        "Picks substitutes the way generation does without counting them as used, then lists the substitutes whose draws are furthest from what they should be. Every enabled substitute should come up equally often and disabled ones never.\n",
+        "\n",
+        "**Example:** `/audit_template adj draws: 50000`",
+    ),
+    age => concat!(
+        "Shows how long ago an account was created, defaults to your own account.\n",
+        "\n",
+        "**Example:** `/age user: @funboy`",
+    ),
+    cache_debug => concat!(
+        "Needs the Administrator permission.\n",
+        "\n",
+        "Every change to a template bumps its version. A cached page read at an older version is stale and read again the next time the template is generated from.\n",
+        "Templates are listed once they are cached or changed and the list is emptied whenever the whole cache is cleared.",
+    ),
+    self_test => concat!(
+        "Needs the Administrator permission.\n",
+        "\n",
+        "Generates a set of inputs covering templates, registers, FSL commands and `get_sub`, each with its own timeout, and lists how long each took and why any failed.\n",
+        "The built-in cases read the `health_check` template, which needs at least one substitute. Operators can replace the cases with a JSON file.",
+    ),
+    register => concat!(
+        "Shows buttons to register or unregister the slash commands of the bot, needed after commands are added or changed.\n",
+        "\n",
+        "**Example:** `@funboy register`",
+    ),
+}
+
+#[cfg(test)]
+mod command_help_test {
+    use std::collections::HashSet;
+
+    use crate::registered_commands;
+
+    use super::*;
+
+    #[test]
+    fn every_command_is_documented() {
+        for command in registered_commands() {
+            assert!(
+                command
+                    .description
+                    .as_ref()
+                    .is_some_and(|description| !description.is_empty()),
+                "{} has no description",
+                command.source_code_name
+            );
+
+            let help_text = COMMAND_HELP
+                .iter()
+                .find(|(name, _)| *name == command.source_code_name)
+                .map(|(_, help_text)| *help_text);
+            assert!(
+                help_text.is_some_and(|help_text| !help_text.is_empty()),
+                "{} has no entry in COMMAND_HELP",
+                command.source_code_name
+            );
+            assert_eq!(
+                command.help_text.as_deref(),
+                help_text,
+                "{} must set help_text_fn to its entry",
+                command.source_code_name
+            );
+        }
+    }
+
+    #[test]
+    fn every_entry_belongs_to_a_command() {
+        let commands: HashSet<String> = registered_commands()
+            .into_iter()
+            .map(|command| command.source_code_name)
+            .collect();
+        for (name, _) in COMMAND_HELP {
+            assert!(
+                commands.contains(*name),
+                "{} is not a registered command",
+                name
+            );
+        }
+    }
+}
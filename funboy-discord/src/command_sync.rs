@@ -0,0 +1,456 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde_json::Value;
+use serenity::all::{Command, GuildId, Http};
+
+use crate::{Data, Error, error_reports::ErrorReporter};
+
+/// Where commands are registered when the bot starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// Commands are only registered by running the register prefix command
+    Manual,
+    Global,
+    /// Guild commands update instantly so test guilds see changes without waiting on Discord
+    Guilds(Vec<GuildId>),
+}
+
+impl RegistrationMode {
+    /// Reads `REGISTER_COMMANDS` which is `global`, `guilds` to register in the comma separated
+    /// `TEST_GUILD_IDS` or unset to register manually
+    pub fn from_env() -> Self {
+        match std::env::var("REGISTER_COMMANDS").as_deref() {
+            Ok("global") => RegistrationMode::Global,
+            Ok("guilds") => {
+                let guild_ids = std::env::var("TEST_GUILD_IDS")
+                    .expect("REGISTER_COMMANDS=guilds requires TEST_GUILD_IDS");
+                RegistrationMode::Guilds(
+                    guild_ids
+                        .split(',')
+                        .map(|id| {
+                            GuildId::new(
+                                id.trim()
+                                    .parse()
+                                    .expect("TEST_GUILD_IDS must be comma separated guild ids"),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            Ok("manual") | Err(_) => RegistrationMode::Manual,
+            Ok(other) => panic!(
+                "REGISTER_COMMANDS must be global, guilds or manual, not {}",
+                other
+            ),
+        }
+    }
+}
+
+/// The parts of an option that make up the signature of a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionDescriptor {
+    pub name: String,
+    pub kind: u64,
+    pub description: String,
+    pub required: bool,
+    pub choices: Vec<String>,
+    /// Options of subcommands and subcommand groups
+    pub options: Vec<OptionDescriptor>,
+}
+
+/// The parts of a command compared between the bot and Discord
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandDescriptor {
+    pub name: String,
+    /// Slash commands are 1, context menu commands 2 and 3
+    pub kind: u64,
+    pub description: String,
+    pub options: Vec<OptionDescriptor>,
+}
+
+impl CommandDescriptor {
+    /// Reads a command as Discord represents it, which both command builders and fetched commands
+    /// serialize to
+    pub fn from_json(json: &Value) -> Self {
+        Self {
+            name: string_field(json, "name"),
+            kind: json.get("type").and_then(Value::as_u64).unwrap_or(1),
+            description: string_field(json, "description"),
+            options: options_from_json(json),
+        }
+    }
+}
+
+fn string_field(json: &Value, field: &str) -> String {
+    json.get(field)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn options_from_json(json: &Value) -> Vec<OptionDescriptor> {
+    let Some(options) = json.get("options").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    options
+        .iter()
+        .map(|option| OptionDescriptor {
+            name: string_field(option, "name"),
+            kind: option
+                .get("type")
+                .and_then(Value::as_u64)
+                .unwrap_or_default(),
+            description: string_field(option, "description"),
+            required: option
+                .get("required")
+                .and_then(Value::as_bool)
+                .unwrap_or_default(),
+            choices: option
+                .get("choices")
+                .and_then(Value::as_array)
+                .map(|choices| {
+                    choices
+                        .iter()
+                        .map(|choice| string_field(choice, "name"))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            options: options_from_json(option),
+        })
+        .collect()
+}
+
+/// How a registered command differs from the command the bot runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandDrift {
+    /// The bot has the command but Discord doesn't show it
+    Unregistered(String),
+    /// Discord shows the command but the bot no longer has it
+    Removed(String),
+    Changed {
+        name: String,
+        changes: Vec<String>,
+    },
+}
+
+impl Display for CommandDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandDrift::Unregistered(name) => write!(f, "{} is not registered", name),
+            CommandDrift::Removed(name) => write!(f, "{} is registered but was removed", name),
+            CommandDrift::Changed { name, changes } => {
+                write!(f, "{} changed: {}", name, changes.join(", "))
+            }
+        }
+    }
+}
+
+/// Lists how registered differs from local, ordered by command name
+pub fn diff_commands(
+    local: &[CommandDescriptor],
+    registered: &[CommandDescriptor],
+) -> Vec<CommandDrift> {
+    let key = |command: &CommandDescriptor| (command.name.clone(), command.kind);
+    let registered_by_key: HashMap<_, _> = registered
+        .iter()
+        .map(|command| (key(command), command))
+        .collect();
+    let local_by_key: HashMap<_, _> = local
+        .iter()
+        .map(|command| (key(command), command))
+        .collect();
+
+    let mut drift = Vec::new();
+    for command in local {
+        match registered_by_key.get(&key(command)) {
+            None => drift.push(CommandDrift::Unregistered(command.name.clone())),
+            Some(registered) => {
+                let mut changes = Vec::new();
+                if command.description != registered.description {
+                    changes.push("description".to_string());
+                }
+                diff_options("", &command.options, &registered.options, &mut changes);
+                if !changes.is_empty() {
+                    drift.push(CommandDrift::Changed {
+                        name: command.name.clone(),
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+    for command in registered {
+        if !local_by_key.contains_key(&key(command)) {
+            drift.push(CommandDrift::Removed(command.name.clone()));
+        }
+    }
+
+    drift.sort_by(|a, b| drift_name(a).cmp(drift_name(b)));
+    drift
+}
+
+fn drift_name(drift: &CommandDrift) -> &str {
+    match drift {
+        CommandDrift::Unregistered(name)
+        | CommandDrift::Removed(name)
+        | CommandDrift::Changed { name, .. } => name,
+    }
+}
+
+fn diff_options(
+    prefix: &str,
+    local: &[OptionDescriptor],
+    registered: &[OptionDescriptor],
+    changes: &mut Vec<String>,
+) {
+    for (position, option) in local.iter().enumerate() {
+        let name = format!("{}{}", prefix, option.name);
+        let Some(registered_position) = registered.iter().position(|r| r.name == option.name)
+        else {
+            changes.push(format!("option {} added", name));
+            continue;
+        };
+        let registered_option = &registered[registered_position];
+        if option.kind != registered_option.kind {
+            changes.push(format!("option {} type", name));
+        }
+        if option.required != registered_option.required {
+            changes.push(format!("option {} required", name));
+        }
+        if option.description != registered_option.description {
+            changes.push(format!("option {} description", name));
+        }
+        if option.choices != registered_option.choices {
+            changes.push(format!("option {} choices", name));
+        }
+        if position != registered_position {
+            changes.push(format!("option {} order", name));
+        }
+        diff_options(
+            &format!("{} ", name),
+            &option.options,
+            &registered_option.options,
+            changes,
+        );
+    }
+    for option in registered {
+        if !local.iter().any(|l| l.name == option.name) {
+            changes.push(format!("option {}{} removed", prefix, option.name));
+        }
+    }
+}
+
+/// The descriptors of the commands the bot would register
+pub fn local_descriptors(commands: &[poise::Command<Data, Error>]) -> Vec<CommandDescriptor> {
+    poise::builtins::create_application_commands(commands)
+        .iter()
+        .filter_map(|command| serde_json::to_value(command).ok())
+        .map(|json| CommandDescriptor::from_json(&json))
+        .collect()
+}
+
+fn registered_descriptors(commands: &[Command]) -> Vec<CommandDescriptor> {
+    commands
+        .iter()
+        .filter_map(|command| serde_json::to_value(command).ok())
+        .map(|json| CommandDescriptor::from_json(&json))
+        .collect()
+}
+
+/// Registers commands as mode says then reports where registered commands drifted from them
+///
+/// Guild modes check the test guilds, other modes check global commands
+pub async fn sync_commands(
+    http: &Http,
+    commands: &[poise::Command<Data, Error>],
+    mode: &RegistrationMode,
+    reporter: Option<&ErrorReporter>,
+) {
+    let registration = match mode {
+        RegistrationMode::Manual => Ok(()),
+        RegistrationMode::Global => poise::builtins::register_globally(http, commands).await,
+        RegistrationMode::Guilds(guild_ids) => {
+            let mut result = Ok(());
+            for guild_id in guild_ids {
+                if let Err(e) = poise::builtins::register_in_guild(http, commands, *guild_id).await
+                {
+                    result = Err(e);
+                }
+            }
+            result
+        }
+    };
+    match registration {
+        Ok(()) if *mode != RegistrationMode::Manual => {
+            tracing::info!(mode = ?mode, "registered commands")
+        }
+        Ok(()) => {}
+        Err(e) => tracing::error!(error = %e, "failed to register commands"),
+    }
+
+    let local = local_descriptors(commands);
+    let scopes: Vec<(String, serenity::Result<Vec<Command>>)> = match mode {
+        RegistrationMode::Guilds(guild_ids) => {
+            let mut scopes = Vec::new();
+            for guild_id in guild_ids {
+                scopes.push((
+                    format!("guild {}", guild_id),
+                    guild_id.get_commands(http).await,
+                ));
+            }
+            scopes
+        }
+        _ => vec![(
+            "global".to_string(),
+            Command::get_global_commands(http).await,
+        )],
+    };
+
+    for (scope, registered) in scopes {
+        let registered = match registered {
+            Ok(registered) => registered,
+            Err(e) => {
+                tracing::warn!(error = %e, scope = %scope, "failed to fetch registered commands");
+                continue;
+            }
+        };
+        let drift = diff_commands(&local, &registered_descriptors(&registered));
+        if drift.is_empty() {
+            tracing::info!(scope = %scope, "registered commands are up to date");
+            continue;
+        }
+        for entry in &drift {
+            tracing::warn!(scope = %scope, drift = %entry, "registered command drifted");
+        }
+        if let Some(reporter) = reporter {
+            let lines: Vec<String> = drift.iter().map(|entry| format!("- {}", entry)).collect();
+            let content = format!(
+                "**{} commands drifted from the bot**\n{}",
+                scope,
+                lines.join("\n")
+            );
+            reporter.notify(http, &content).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod command_sync_test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn command(json: Value) -> CommandDescriptor {
+        CommandDescriptor::from_json(&json)
+    }
+
+    fn generate() -> Value {
+        json!({
+            "name": "generate",
+            "type": 1,
+            "description": "Generates text",
+            "options": [
+                {"name": "input", "type": 3, "description": "Text", "required": true},
+                {"name": "depth", "type": 4, "description": "Passes"}
+            ]
+        })
+    }
+
+    #[test]
+    fn descriptors_from_builders_and_fetched_commands_match() {
+        let builder = serenity::all::CreateCommand::new("ping").description("Pings");
+        let local = command(serde_json::to_value(&builder).unwrap());
+        let registered = command(json!({
+            "id": "1",
+            "application_id": "2",
+            "version": "3",
+            "type": 1,
+            "name": "ping",
+            "description": "Pings",
+            "default_member_permissions": null,
+            "options": []
+        }));
+        assert_eq!(local, registered);
+        assert!(diff_commands(&[local], &[registered]).is_empty());
+    }
+
+    #[test]
+    fn added_and_removed_commands() {
+        let local = vec![command(generate()), command(json!({"name": "ping"}))];
+        let registered = vec![
+            command(generate()),
+            command(json!({"name": "old_command"})),
+            // A context menu command with the name of a slash command is its own command
+            command(json!({"name": "ping", "type": 3})),
+        ];
+        assert_eq!(
+            diff_commands(&local, &registered),
+            vec![
+                CommandDrift::Removed("old_command".into()),
+                CommandDrift::Unregistered("ping".into()),
+                CommandDrift::Removed("ping".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn changed_signatures() {
+        let mut changed = generate();
+        changed["description"] = json!("Generates more text");
+        changed["options"][0]["required"] = json!(false);
+        changed["options"][1] =
+            json!({"name": "seed", "type": 4, "description": "Seed", "required": false});
+
+        assert_eq!(
+            diff_commands(&[command(changed)], &[command(generate())]),
+            vec![CommandDrift::Changed {
+                name: "generate".into(),
+                changes: vec![
+                    "description".into(),
+                    "option input required".into(),
+                    "option seed added".into(),
+                    "option depth removed".into(),
+                ],
+            }]
+        );
+
+        let mut retyped = generate();
+        retyped["options"] = json!([
+            {"name": "depth", "type": 10, "description": "Passes"},
+            {"name": "input", "type": 3, "description": "Text", "required": true}
+        ]);
+        assert_eq!(
+            diff_commands(&[command(retyped)], &[command(generate())]),
+            vec![CommandDrift::Changed {
+                name: "generate".into(),
+                changes: vec![
+                    "option depth type".into(),
+                    "option depth order".into(),
+                    "option input order".into(),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn nested_subcommand_options() {
+        let subcommand = |required: bool| {
+            command(json!({
+                "name": "templates",
+                "options": [{
+                    "name": "info",
+                    "type": 1,
+                    "description": "Info",
+                    "options": [{"name": "name", "type": 3, "description": "Name", "required": required}]
+                }]
+            }))
+        };
+        assert_eq!(
+            diff_commands(&[subcommand(true)], &[subcommand(false)]),
+            vec![CommandDrift::Changed {
+                name: "templates".into(),
+                changes: vec!["option info name required".into()],
+            }]
+        );
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "ollama")]
 pub mod ollama;
 pub mod random;
 pub mod sound;
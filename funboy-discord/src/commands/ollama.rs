@@ -1,17 +1,46 @@
-use funboy_core::ollama::{MAX_PREDICT, OllamaSettings};
-use poise::CreateReply;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use funboy_core::{
+    ai_label::{LabelPlacement, label_response},
+    ollama::{
+        MAX_PREDICT, OllamaGenerator, OllamaSettings, PROMPT_PLACEHOLDER, PresetMode, PresetScope,
+        USERNAME_PLACEHOLDER,
+    },
+};
+use poise::{ChoiceParameter, CreateReply};
 use serenity::all::UserId;
+use tokio::sync::Mutex;
 
 use crate::{
-    Context, Error, OllamaUserSettingsMap,
-    interpreter::create_custom_interpreter,
-    io_format::{context_extension::ContextExtension, discord_message_format::ellipsize_if_long},
+    Context, Error,
+    interpreter::create_custom_generation,
+    io_format::{
+        context_extension::ContextExtension,
+        discord_message_format::{StringVecToRef, ellipsize_if_long},
+    },
 };
 
 const ERROR_OLLAMA_UNAVAILABLE: &str = "Error: Ollama service not available.";
 
+pub type OllamaUserSettingsMap = HashMap<UserId, OllamaSettings>;
+
+#[derive(Default)]
+pub struct OllamaData {
+    pub users: Mutex<HashSet<UserId>>,
+    pub generator: Mutex<OllamaGenerator>,
+    pub user_settings: Arc<Mutex<OllamaUserSettingsMap>>,
+}
+
 /// Lists out all the available ollama models
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::list_ollama_models"
+)]
 pub async fn list_ollama_models(ctx: Context<'_>) -> Result<(), Error> {
     let ollama_generator = ctx.data().ollama_data.generator.lock().await;
     let models = ollama_generator.get_models().await;
@@ -49,7 +78,12 @@ fn get_ollama_user_settings_mut<'a>(
 }
 
 /// Lists out the current ollama settings
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::list_ollama_settings"
+)]
 pub async fn list_ollama_settings(ctx: Context<'_>) -> Result<(), Error> {
     let user_id = ctx.author().id;
     let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
@@ -68,7 +102,12 @@ pub async fn list_ollama_settings(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Sets the current ollama model
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::set_ollama_model"
+)]
 pub async fn set_ollama_model(ctx: Context<'_>, model: String) -> Result<(), Error> {
     let ollama_generator = ctx.data().ollama_data.generator.lock().await;
     let models = ollama_generator.get_models().await;
@@ -102,7 +141,12 @@ pub async fn set_ollama_model(ctx: Context<'_>, model: String) -> Result<(), Err
 }
 
 /// Sets the ollama model parameters
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::set_ollama_parameters"
+)]
 pub async fn set_ollama_parameters(
     ctx: Context<'_>,
     temperature: Option<f32>,
@@ -131,7 +175,12 @@ pub async fn set_ollama_parameters(
 }
 
 /// Resets the ollama model parameters to their defaults
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::reset_ollama_parameters"
+)]
 pub async fn reset_ollama_parameters(ctx: Context<'_>) -> Result<(), Error> {
     let user_id = ctx.author().id;
     let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
@@ -143,7 +192,12 @@ pub async fn reset_ollama_parameters(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Sets the system prompt for ollama
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::set_ollama_system_prompt"
+)]
 pub async fn set_ollama_system_prompt(
     ctx: Context<'_>,
     system_prompt: String,
@@ -152,13 +206,20 @@ pub async fn set_ollama_system_prompt(
     let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
     let settings = get_ollama_user_settings_mut(&mut ollama_settings_map, &user_id);
 
-    settings.set_system_prompt(&system_prompt);
-    ctx.say_ephemeral("Ollama system prompt updated.").await?;
+    match settings.set_system_prompt(&system_prompt) {
+        Ok(()) => ctx.say_ephemeral("Ollama system prompt updated.").await?,
+        Err(e) => ctx.say_ephemeral(&e.to_string()).await?,
+    };
     Ok(())
 }
 
 /// Resets the system prompt for ollama to it's default
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::reset_ollama_system_prompt"
+)]
 pub async fn reset_ollama_system_prompt(ctx: Context<'_>) -> Result<(), Error> {
     let user_id = ctx.author().id;
     let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
@@ -170,19 +231,31 @@ pub async fn reset_ollama_system_prompt(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Sets the template for ollama
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::set_ollama_template"
+)]
 pub async fn set_ollama_template(ctx: Context<'_>, template: String) -> Result<(), Error> {
     let user_id = ctx.author().id;
     let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
     let settings = get_ollama_user_settings_mut(&mut ollama_settings_map, &user_id);
 
-    settings.set_template(&template);
-    ctx.say_ephemeral("Ollama system prompt updated.").await?;
+    match settings.set_template(&template) {
+        Ok(()) => ctx.say_ephemeral("Ollama template updated.").await?,
+        Err(e) => ctx.say_ephemeral(&e.to_string()).await?,
+    };
     Ok(())
 }
 
 /// Resets the template for ollama to it's default
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::reset_ollama_template"
+)]
 pub async fn reset_ollama_template(ctx: Context<'_>) -> Result<(), Error> {
     let user_id = ctx.author().id;
     let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
@@ -194,7 +267,12 @@ pub async fn reset_ollama_template(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Sets the maximum amount of words (tokens) ollama can generate per prompt
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::set_ollama_word_limit"
+)]
 pub async fn set_ollama_word_limit(ctx: Context<'_>, limit: u16) -> Result<(), Error> {
     let user_id = ctx.author().id;
     let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
@@ -212,11 +290,213 @@ pub async fn set_ollama_word_limit(ctx: Context<'_>, limit: u16) -> Result<(), E
     Ok(())
 }
 
+/// Saves a named prompt preset
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::save_prompt_preset"
+)]
+pub async fn save_prompt_preset(
+    ctx: Context<'_>,
+    name: String,
+    body: String,
+    wrap_prompt: Option<bool>,
+    global: Option<bool>,
+) -> Result<(), Error> {
+    let mode = if wrap_prompt.unwrap_or(false) {
+        PresetMode::Wrap
+    } else {
+        PresetMode::System
+    };
+    let scope = if global.unwrap_or(false) {
+        PresetScope::Global
+    } else {
+        PresetScope::User(ctx.author().id.get())
+    };
+
+    match ctx
+        .data()
+        .funboy
+        .save_prompt_preset(&name, scope, &body, mode)
+        .await
+    {
+        Ok(preset) => {
+            ctx.say_ephemeral(&format!("Saved prompt preset `{}`", preset.name))
+                .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the prompt presets you can use with `/generate_ollama`
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Ollama",
+    help_text_fn = "crate::command_help::list_prompt_presets"
+)]
+pub async fn list_prompt_presets(ctx: Context<'_>) -> Result<(), Error> {
+    let presets = ctx
+        .data()
+        .funboy
+        .get_prompt_presets(ctx.author().id.get())
+        .await;
+
+    match presets {
+        Ok(presets) if presets.is_empty() => {
+            ctx.say_ephemeral(&format!(
+                "No prompt presets saved. Use `/save_prompt_preset` with `{}` and `{}` placeholders to create one.",
+                PROMPT_PLACEHOLDER, USERNAME_PLACEHOLDER
+            ))
+            .await?;
+        }
+        Ok(presets) => {
+            let entries: Vec<String> = presets
+                .iter()
+                .map(|preset| {
+                    let scope = match preset.scope() {
+                        PresetScope::Global => "global",
+                        PresetScope::User(_) => "yours",
+                    };
+                    let mode = match preset.mode() {
+                        PresetMode::System => "system",
+                        PresetMode::Wrap => "wrap",
+                    };
+                    format!(
+                        "`{}` ({}, {}): {}\n",
+                        preset.name,
+                        scope,
+                        mode,
+                        ellipsize_if_long(&preset.body, 100)
+                    )
+                })
+                .collect();
+            ctx.say_list(&entries.to_ref(), true, None).await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Posts a finished Ollama response, labelled when the guild labels AI output
+///
+/// Every command posting Ollama responses goes through here so they are all labelled alike
+async fn say_ollama_response(ctx: Context<'_>, response: &str) -> Result<(), Error> {
+    let label = match ctx.guild_id() {
+        Some(guild_id) => ctx.data().funboy.get_ai_label(guild_id.get()).await?,
+        None => None,
+    };
+    ctx.say_long(&label_response(response, label.as_ref()), false)
+        .await
+}
+
+/// Which side of a response the AI label goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum LabelPlacementChoice {
+    Before,
+    After,
+}
+
+impl From<LabelPlacementChoice> for LabelPlacement {
+    fn from(value: LabelPlacementChoice) -> Self {
+        match value {
+            LabelPlacementChoice::Before => LabelPlacement::Before,
+            LabelPlacementChoice::After => LabelPlacement::After,
+        }
+    }
+}
+
+/// Labels Ollama responses in this server as AI-generated
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Ollama",
+    help_text_fn = "crate::command_help::set_ai_label"
+)]
+pub async fn set_ai_label(
+    ctx: Context<'_>,
+    #[description = "Whether responses are labelled"] enabled: bool,
+    #[description = "Leave empty for the default label"] label: Option<String>,
+    #[description = "Before by default"] placement: Option<LabelPlacementChoice>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let funboy = &ctx.data().funboy;
+    let result = funboy
+        .set_ai_label(
+            guild_id,
+            enabled,
+            label.as_deref(),
+            placement.map(LabelPlacement::from).unwrap_or_default(),
+        )
+        .await;
+
+    match result {
+        Ok(()) => match funboy.get_ai_label(guild_id).await? {
+            Some(label) => {
+                ctx.say_ephemeral(&format!(
+                    "Ollama responses are now posted with `{}` {} them",
+                    label.text, label.placement
+                ))
+                .await?;
+            }
+            None => {
+                ctx.say_ephemeral("Ollama responses are no longer labelled")
+                    .await?;
+            }
+        },
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    };
+    Ok(())
+}
+
 /// Generates text like the generate command but sends the text as a prompt to ollama
-#[poise::command(slash_command, prefix_command, category = "Ollama")]
-pub async fn generate_ollama(ctx: Context<'_>, prompt: String) -> Result<(), Error> {
+#[poise::command(
+    slash_command,
+    prefix_command,
+    check = "crate::generate_channels::check_generate_channel",
+    category = "Ollama",
+    help_text_fn = "crate::command_help::generate_ollama"
+)]
+pub async fn generate_ollama(
+    ctx: Context<'_>,
+    prompt: String,
+    preset: Option<String>,
+) -> Result<(), Error> {
     let original_message = ctx.say("Generating...").await?;
 
+    let preset = match preset {
+        Some(name) => {
+            match ctx
+                .data()
+                .funboy
+                .get_prompt_preset(&name, ctx.author().id.get())
+                .await
+            {
+                Ok(Some(preset)) => Some(preset),
+                Ok(None) => {
+                    ctx.say_ephemeral(&format!("Error: prompt preset `{}` does not exist.", name))
+                        .await?;
+                    return Ok(());
+                }
+                Err(e) => {
+                    ctx.say_ephemeral(&e.to_string()).await?;
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
+
     let user_id = ctx.author().id;
     let mut users_lock = ctx.data().ollama_data.users.lock().await;
 
@@ -229,39 +509,47 @@ pub async fn generate_ollama(ctx: Context<'_>, prompt: String) -> Result<(), Err
     }
     drop(users_lock);
 
-    let interpreted_prompt = ctx
-        .data()
-        .funboy
-        .generate(&prompt, create_custom_interpreter(&ctx))
+    let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
+    let settings = get_ollama_user_settings_mut(&mut ollama_settings_map, &user_id).clone();
+    drop(ollama_settings_map);
+
+    // Only the prompt is generated, the system prompt, template and preset are sent as written
+    let (funboy, interpreter) = create_custom_generation(&ctx).await;
+    let request = funboy
+        .prepare_ollama_request(
+            &prompt,
+            &settings,
+            preset.as_ref(),
+            ctx.author().display_name(),
+            interpreter,
+        )
         .await;
 
     let result: Result<(), Error> = {
-        match interpreted_prompt {
-            Ok(prompt) => {
+        match request {
+            Ok(request) => {
+                let prompt = &request.generated_prompt;
                 original_message
                     .edit(
                         ctx,
                         CreateReply::default().content(&format!(
                             "Generating prompt: **\"{}\"**",
-                            ellipsize_if_long(&prompt, 200)
+                            ellipsize_if_long(prompt, 200)
                         )),
                     )
                     .await?;
 
-                let user_id = ctx.author().id;
-                let mut ollama_settings_map = ctx.data().ollama_data.user_settings.lock().await;
-                let settings =
-                    get_ollama_user_settings_mut(&mut ollama_settings_map, &user_id).clone();
-                drop(ollama_settings_map);
                 let ollama_generator = ctx.data().ollama_data.generator.lock().await;
                 let model = ctx.data().funboy.get_ollama_model().await;
-                let response = ollama_generator.generate(&prompt, &settings, model).await;
+                let response = ollama_generator
+                    .generate(&request.prompt, &request.settings, model)
+                    .await;
                 match response {
                     Err(e) => {
                         ctx.say_ephemeral(&format!("Error: {}", e)).await?;
                     }
                     Ok(gen_res) => {
-                        ctx.say_long(&format!("{}{}", &prompt, gen_res.response), false)
+                        say_ollama_response(ctx, &format!("{}{}", prompt, gen_res.response))
                             .await?;
                     }
                 }
@@ -280,7 +568,7 @@ pub async fn generate_ollama(ctx: Context<'_>, prompt: String) -> Result<(), Err
     match result {
         Ok(_) => Ok(()),
         Err(e) => {
-            eprintln!("{}", e);
+            tracing::error!(error = %e, "ollama generation failed");
             ctx.say_ephemeral("Error: Ollama generation failed.")
                 .await?;
             Ok(())
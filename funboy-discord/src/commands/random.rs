@@ -6,12 +6,74 @@ use crate::{
         context_extension::ContextExtension,
         discord_message_format::split_by_whitespace_unless_quoted,
     },
+    localization::tr,
 };
 
-/// Generates a random number between or including the min and max provided
-#[poise::command(slash_command, prefix_command, category = "Random")]
-pub async fn random_number(ctx: Context<'_>, min: String, max: String) -> Result<(), Error> {
-    let number = Funboy::random_number(&min, &max, false);
+/// What `random_number` was asked to do
+#[derive(Debug, PartialEq)]
+enum RandomNumberRequest {
+    Range {
+        min: String,
+        max: String,
+        inclusive: bool,
+    },
+    Dice(String),
+}
+
+impl RandomNumberRequest {
+    /// Bounds are inclusive unless told otherwise, dice can't be combined with bounds
+    ///
+    /// Errors are message keys translated with [`tr`]
+    fn from_parameters(
+        min: Option<String>,
+        max: Option<String>,
+        inclusive: Option<bool>,
+        dice: Option<String>,
+    ) -> Result<Self, &'static str> {
+        match (min, max, dice) {
+            (None, None, Some(dice)) => match inclusive {
+                Some(_) => Err("random_inclusive_with_dice"),
+                None => Ok(RandomNumberRequest::Dice(dice)),
+            },
+            (_, _, Some(_)) => Err("random_dice_with_range"),
+            (Some(min), Some(max), None) => Ok(RandomNumberRequest::Range {
+                min,
+                max,
+                inclusive: inclusive.unwrap_or(true),
+            }),
+            _ => Err("random_missing_bounds"),
+        }
+    }
+}
+
+/// Generates a random number between min and max or rolls dice
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Random",
+    help_text_fn = "crate::command_help::random_number"
+)]
+pub async fn random_number(
+    ctx: Context<'_>,
+    min: Option<String>,
+    max: Option<String>,
+    inclusive: Option<bool>,
+    dice: Option<String>,
+) -> Result<(), Error> {
+    let number = match RandomNumberRequest::from_parameters(min, max, inclusive, dice) {
+        Ok(RandomNumberRequest::Range {
+            min,
+            max,
+            inclusive,
+        }) => Funboy::random_number(&min, &max, inclusive),
+        Ok(RandomNumberRequest::Dice(dice)) => {
+            Funboy::roll_dice(&dice).map(|roll| roll.to_string())
+        }
+        Err(key) => {
+            ctx.say_ephemeral(&tr(ctx.locale(), key, &[])).await?;
+            return Ok(());
+        }
+    };
     match number {
         Ok(number) => {
             ctx.say(number).await?;
@@ -24,9 +86,12 @@ pub async fn random_number(ctx: Context<'_>, min: String, max: String) -> Result
 }
 
 /// Randomly selects an item from the list given
-///
-/// Entries are seperated by spaces and multi-word entries can be enclosed in quotes like "hot dog"
-#[poise::command(slash_command, prefix_command, category = "Random")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Random",
+    help_text_fn = "crate::command_help::random_entry"
+)]
 pub async fn random_entry(ctx: Context<'_>, entries: String) -> Result<(), Error> {
     let entries = split_by_whitespace_unless_quoted(&entries);
     let entry = Funboy::random_entry(&entries);
@@ -40,3 +105,77 @@ pub async fn random_entry(ctx: Context<'_>, entries: String) -> Result<(), Error
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod random_test {
+    use super::*;
+
+    fn request(
+        min: Option<&str>,
+        max: Option<&str>,
+        inclusive: Option<bool>,
+        dice: Option<&str>,
+    ) -> Result<RandomNumberRequest, String> {
+        RandomNumberRequest::from_parameters(
+            min.map(str::to_string),
+            max.map(str::to_string),
+            inclusive,
+            dice.map(str::to_string),
+        )
+        .map_err(|key| tr(None, key, &[]))
+    }
+
+    #[test]
+    fn range_is_inclusive_by_default() {
+        assert_eq!(
+            request(Some("1"), Some("6"), None, None),
+            Ok(RandomNumberRequest::Range {
+                min: "1".to_string(),
+                max: "6".to_string(),
+                inclusive: true
+            })
+        );
+        assert_eq!(
+            request(Some("1"), Some("6"), Some(false), None),
+            Ok(RandomNumberRequest::Range {
+                min: "1".to_string(),
+                max: "6".to_string(),
+                inclusive: false
+            })
+        );
+    }
+
+    #[test]
+    fn dice_without_bounds() {
+        assert_eq!(
+            request(None, None, None, Some("3d6")),
+            Ok(RandomNumberRequest::Dice("3d6".to_string()))
+        );
+    }
+
+    #[test]
+    fn conflicting_parameters() {
+        assert_eq!(
+            request(Some("1"), None, None, Some("d20")),
+            Err("use either dice or min and max, not both".to_string())
+        );
+        assert_eq!(
+            request(Some("1"), Some("6"), None, Some("d20")),
+            Err("use either dice or min and max, not both".to_string())
+        );
+        assert_eq!(
+            request(None, None, Some(true), Some("d20")),
+            Err("inclusive only applies to min and max, not dice".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_parameters() {
+        for (min, max) in [(None, None), (Some("1"), None), (None, Some("6"))] {
+            assert_eq!(
+                request(min, max, None, None),
+                Err("provide both min and max, or dice like d20 or 3d6".to_string())
+            );
+        }
+    }
+}
@@ -1,14 +1,28 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     Data, HttpClient,
-    components::{TrackComponent, create_track_button},
-    io_format::context_extension::ContextExtension,
+    components::{
+        NEXT_PAGE_BUTTON_ID, PREVIOUS_PAGE_BUTTON_ID, TrackComponent, create_page_buttons,
+        create_track_button,
+    },
+    io_format::{
+        context_extension::ContextExtension,
+        discord_message_format::{format_as_numeric_list, format_duration, page_bounds},
+    },
+    rate_limiter::{RateLimit, RateLimitResult},
+};
+use funboy_core::{
+    Funboy,
+    template_database::{KeySize, PlaybackEvent},
 };
-use poise::{CreateReply, serenity_prelude::async_trait};
+use poise::{ChoiceParameter, CreateReply, serenity_prelude::async_trait};
 use serenity::all::{
-    CacheHttp, CreateActionRow, CreateInteractionResponse, CreateInteractionResponseMessage,
-    EditInteractionResponse,
+    CacheHttp, ChannelId, ComponentInteraction, CreateActionRow, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse, GuildId, Mentionable, UserId,
 };
 use songbird::{
     CoreEvent, Songbird,
@@ -23,6 +37,11 @@ use uuid::Uuid;
 use crate::{Context, Error, HttpKey};
 
 const TRACK_LIMIT: usize = 10;
+const TRACKS_PER_PAGE: usize = 5;
+const TRACK_PAGE_TIMEOUT_SECS: u64 = 120;
+const TRACK_HISTORY_LIMIT: usize = 20;
+const TRACK_BUTTON_DEBOUNCE: Duration = Duration::from_secs(3);
+const TRACK_BUTTON_ACTION: &str = "track_button";
 
 const NOT_INITIALIZED: &str = "Songbird Voice client placed in at initialisation.";
 const NOT_IN_VOICE_CHANNEL: &str = "Not in a voice channel.";
@@ -31,6 +50,7 @@ const LEFT_CHANNEL_NOTIF: &str = "Left voice channel.";
 const STOP_AUDIO_NOTIF: &str = "Stopping all audio.";
 const NON_EXISTANT_TRACK_ERROR: &str = "Error: Track no longer exists.";
 const NO_TRACKS_PLAYING_NOTIF: &str = "No tracks are currently playing.";
+const NO_TRACK_HISTORY_NOTIF: &str = "No tracks have been played recently.";
 
 pub const PLAY_PAUSE: &str = "Play/Pause";
 pub const STOP: &str = "Stop";
@@ -39,15 +59,34 @@ pub const VOLUME_DOWN: &str = "Volume_Down";
 pub const LOOP: &str = "Loop";
 pub const TRACK_COMMANDS: [&str; 5] = [PLAY_PAUSE, STOP, VOLUME_UP, VOLUME_DOWN, LOOP];
 
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Track {
     name: String,
     handle: TrackHandle,
-    duration: Duration,
+    duration: Option<Duration>,
+    source_url: Option<String>,
+    guild_id: GuildId,
+    added_by: UserId,
+    added_at: Instant,
+    /// The recorded playback event, None if recording it failed
+    playback_id: Option<KeySize>,
 }
 
 impl Track {
+    /// Formats the track as a single line for track listings
+    pub fn get_list_entry(&self) -> String {
+        let name = match &self.source_url {
+            Some(url) => format!("[{}](<{}>)", self.name, url),
+            None => format!("**{}**", self.name),
+        };
+        format!(
+            "{} — {} — added by {}",
+            name,
+            format_duration(self.duration),
+            self.added_by.mention()
+        )
+    }
+
     pub async fn get_description(&self) -> String {
         let track_status;
         if let Ok(status) = self.handle.get_info().await {
@@ -66,6 +105,20 @@ impl Track {
 
 type TrackMap = HashMap<Uuid, Track>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum TrackSort {
+    Name,
+    #[name = "Recently Added"]
+    RecentlyAdded,
+}
+
+pub struct TrackPage<'a> {
+    pub tracks: Vec<&'a Track>,
+    pub page: usize,
+    pub page_count: usize,
+    pub total: usize,
+}
+
 #[derive(Debug)]
 pub struct TrackList {
     track_map: TrackMap,
@@ -82,11 +135,12 @@ impl TrackList {
         self.track_map.insert(track.handle.uuid(), track);
     }
 
-    pub fn remove_track(&mut self, track_id: &Uuid) {
+    pub fn remove_track(&mut self, track_id: &Uuid) -> Option<Track> {
         let track = self.track_map.remove(&track_id);
-        if let Some(track) = track {
+        if let Some(track) = &track {
             let _ = track.handle.stop();
         }
+        track
     }
 
     pub fn get_tracks(&mut self) -> Arc<[&mut Track]> {
@@ -97,6 +151,46 @@ impl TrackList {
         self.track_map.values().len()
     }
 
+    /// How many tracks are queued in guild
+    pub fn get_guild_track_count(&self, guild_id: GuildId) -> usize {
+        self.track_map
+            .values()
+            .filter(|track| track.guild_id == guild_id)
+            .count()
+    }
+
+    /// Returns a zero based page of the tracks queued in guild along with how many there are
+    pub fn page(
+        &self,
+        guild_id: GuildId,
+        page: usize,
+        per_page: usize,
+        sort: TrackSort,
+    ) -> TrackPage<'_> {
+        let mut tracks: Vec<&Track> = self
+            .track_map
+            .values()
+            .filter(|track| track.guild_id == guild_id)
+            .collect();
+
+        match sort {
+            TrackSort::Name => tracks.sort_by_key(|track| track.name.to_lowercase()),
+            TrackSort::RecentlyAdded => {
+                tracks.sort_by_key(|track| std::cmp::Reverse(track.added_at))
+            }
+        }
+
+        let total = tracks.len();
+        let (range, page_count) = page_bounds(total, page, per_page);
+        let page = range.start / per_page.max(1);
+        TrackPage {
+            tracks: tracks.drain(range).collect(),
+            page,
+            page_count,
+            total,
+        }
+    }
+
     pub fn get_track(&mut self, id: &str) -> Option<&mut Track> {
         for (uuid, track) in &mut self.track_map {
             if uuid.to_string().eq(id) {
@@ -117,14 +211,23 @@ impl TrackList {
 #[derive(Debug)]
 pub struct TrackEndHandler {
     track_list: Arc<Mutex<TrackList>>,
+    funboy: Arc<Funboy>,
 }
 
 #[async_trait]
 impl songbird::EventHandler for TrackEndHandler {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track_list) = ctx {
-            for (_, handle) in *track_list {
-                self.track_list.lock().await.remove_track(&handle.uuid());
+            for (state, handle) in *track_list {
+                let track = self.track_list.lock().await.remove_track(&handle.uuid());
+
+                // Stopped and failed tracks also end up here but only finished ones completed
+                if state.playing == PlayMode::End
+                    && let Some(playback_id) = track.and_then(|track| track.playback_id)
+                    && let Err(e) = self.funboy.complete_playback(playback_id).await
+                {
+                    tracing::warn!(error = %e.to_string(), "failed to complete playback event");
+                }
             }
         } else if let EventContext::DriverDisconnect(_) = ctx {
             self.track_list.lock().await.clear();
@@ -141,10 +244,10 @@ impl VoiceEventHandler for TrackErrorNotifier {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track_list) = ctx {
             for (state, handle) in *track_list {
-                println!(
-                    "Track {:?} encountered an error: {:?}.",
-                    handle.uuid(),
-                    state.playing
+                tracing::warn!(
+                    track = %handle.uuid(),
+                    state = ?state.playing,
+                    "track encountered an error"
                 );
             }
         }
@@ -154,7 +257,12 @@ impl VoiceEventHandler for TrackErrorNotifier {
 }
 
 /// Join bot to current voice channel
-#[poise::command(slash_command, prefix_command, category = "Sound")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Sound",
+    help_text_fn = "crate::command_help::join_voice"
+)]
 pub async fn join_voice(ctx: Context<'_>) -> Result<(), Error> {
     let (guild_id, channel_id) = {
         let guild = ctx.guild().unwrap();
@@ -188,6 +296,7 @@ pub async fn join_voice(ctx: Context<'_>) -> Result<(), Error> {
             CoreEvent::DriverDisconnect.into(),
             TrackEndHandler {
                 track_list: ctx.data().track_list.clone(),
+                funboy: ctx.data().funboy.clone(),
             },
         );
 
@@ -198,7 +307,12 @@ pub async fn join_voice(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Disconnect bot from voice channel
-#[poise::command(slash_command, prefix_command, category = "Sound")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Sound",
+    help_text_fn = "crate::command_help::leave_voice"
+)]
 pub async fn leave_voice(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
 
@@ -220,10 +334,12 @@ pub async fn leave_voice(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Play audio track from url or search query
-///
-/// Example usage: **/play_track** url_or_query: **https://www.youtube.com/watch?v=a3mxLL7nX1E**
-/// Example usage: **/play_track** url_or_query: **Back In Black**
-#[poise::command(slash_command, prefix_command, category = "Sound")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Sound",
+    help_text_fn = "crate::command_help::play_track"
+)]
 pub async fn play_track(ctx: Context<'_>, url_or_query: String) -> Result<(), Error> {
     let lock = match ctx.data().track_player_lock.try_lock() {
         Ok(gaurd) => gaurd,
@@ -266,9 +382,9 @@ pub async fn play_track(ctx: Context<'_>, url_or_query: String) -> Result<(), Er
             YoutubeDl::new(http_client, url_or_query.clone()).user_args(ytdl_args)
         };
 
-        let metadata = src.aux_metadata().await?;
+        // A failed probe shouldn't stop the track from playing so its metadata is left unknown
+        let metadata = src.aux_metadata().await.unwrap_or_default();
 
-        let track_duration = metadata.duration.unwrap_or(Duration::from_secs(0));
         let track_name = metadata.title.unwrap_or(url_or_query.clone());
 
         let track_handle = handler.play_input(src.clone().into());
@@ -277,6 +393,7 @@ pub async fn play_track(ctx: Context<'_>, url_or_query: String) -> Result<(), Er
             TrackEvent::End.into(),
             TrackEndHandler {
                 track_list: ctx.data().track_list.clone(),
+                funboy: ctx.data().funboy.clone(),
             },
         );
 
@@ -284,13 +401,47 @@ pub async fn play_track(ctx: Context<'_>, url_or_query: String) -> Result<(), Er
             TrackEvent::Error.into(),
             TrackEndHandler {
                 track_list: ctx.data().track_list.clone(),
+                funboy: ctx.data().funboy.clone(),
             },
         );
 
+        let guild_id = ctx.guild_id().unwrap();
+        let funboy = &ctx.data().funboy;
+        let playback = funboy
+            .record_playback(
+                guild_id.get(),
+                &track_name,
+                metadata.source_url.as_deref(),
+                ctx.author().id.get(),
+            )
+            .await;
+        let playback_id = match playback {
+            Ok(event) => Some(event.id),
+            Err(e) => {
+                tracing::warn!(error = %e.to_string(), "failed to record playback event");
+                None
+            }
+        };
+
+        let announcement_channel = funboy
+            .get_announcement_channel(guild_id.get())
+            .await
+            .unwrap_or_default();
+        if let Some((channel_id, announcement)) =
+            now_playing_announcement(announcement_channel, &track_name, ctx.author().id)
+        {
+            channel_id.say(ctx.http(), announcement).await.ok();
+        }
+
         ctx.data().track_list.lock().await.add_track(Track {
             name: track_name,
             handle: track_handle,
-            duration: track_duration,
+            duration: metadata.duration,
+            source_url: metadata.source_url,
+            guild_id,
+            added_by: ctx.author().id,
+            added_at: Instant::now(),
+            playback_id,
         });
 
         ctx.send(CreateReply::default().content(format!("Playing track **{}**", &url_or_query)))
@@ -307,7 +458,12 @@ pub async fn play_track(ctx: Context<'_>, url_or_query: String) -> Result<(), Er
 }
 
 /// Stop all currently playing audio tracks
-#[poise::command(slash_command, prefix_command, category = "Sound")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Sound",
+    help_text_fn = "crate::command_help::stop_tracks"
+)]
 pub async fn stop_tracks(ctx: Context<'_>) -> Result<(), Error> {
     ctx.data().track_list.lock().await.clear();
 
@@ -360,18 +516,320 @@ pub async fn display_track_controls(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+async fn format_track_page(
+    ctx: Context<'_>,
+    page: usize,
+    sort: TrackSort,
+) -> (String, usize, usize) {
+    let track_list = ctx.data().track_list.lock().await;
+    let track_page = track_list.page(ctx.guild_id().unwrap(), page, TRACKS_PER_PAGE, sort);
+
+    let mut content = format!(
+        "Tracks: **{}** (page {}/{})\n",
+        track_page.total,
+        track_page.page + 1,
+        track_page.page_count
+    );
+    for (i, track) in track_page.tracks.iter().enumerate() {
+        content.push_str(&format!(
+            "{}. {}\n",
+            track_page.page * TRACKS_PER_PAGE + i + 1,
+            track.get_list_entry()
+        ));
+    }
+
+    (content, track_page.page, track_page.page_count)
+}
+
 /// Show currently playing audio tracks
-#[poise::command(slash_command, prefix_command, category = "Sound")]
-pub async fn list_tracks(ctx: Context<'_>) -> Result<(), Error> {
-    display_track_controls(ctx).await?;
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    category = "Sound",
+    help_text_fn = "crate::command_help::list_tracks"
+)]
+pub async fn list_tracks(
+    ctx: Context<'_>,
+    sort: Option<TrackSort>,
+    controls: Option<bool>,
+) -> Result<(), Error> {
+    if controls.unwrap_or(false) {
+        display_track_controls(ctx).await?;
+        return Ok(());
+    }
+
+    let track_count = ctx
+        .data()
+        .track_list
+        .lock()
+        .await
+        .get_guild_track_count(ctx.guild_id().unwrap());
+    if track_count == 0 {
+        ctx.say_ephemeral(NO_TRACKS_PLAYING_NOTIF).await?;
+        return Ok(());
+    }
+
+    let sort = sort.unwrap_or(TrackSort::RecentlyAdded);
+    let (content, mut page, page_count) = format_track_page(ctx, 0, sort).await;
+
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(content)
+                .ephemeral(true)
+                .components(vec![create_page_buttons(page, page_count)]),
+        )
+        .await?;
+
+    while let Some(interaction) = reply
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .timeout(Duration::from_secs(TRACK_PAGE_TIMEOUT_SECS))
+        .await
+    {
+        match interaction.data.custom_id.as_str() {
+            PREVIOUS_PAGE_BUTTON_ID => page = page.saturating_sub(1),
+            NEXT_PAGE_BUTTON_ID => page += 1,
+            _ => continue,
+        }
+
+        let (content, current_page, page_count) = format_track_page(ctx, page, sort).await;
+        page = current_page;
+
+        interaction
+            .create_response(
+                ctx.http(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(vec![create_page_buttons(page, page_count)]),
+                ),
+            )
+            .await?;
+    }
+
     Ok(())
 }
 
+/// The message posted when a track starts, None when the guild has no announcement channel
+fn now_playing_announcement(
+    channel_id: Option<u64>,
+    track_name: &str,
+    requested_by: UserId,
+) -> Option<(ChannelId, String)> {
+    let channel_id = ChannelId::new(channel_id?);
+    Some((
+        channel_id,
+        format!(
+            "Now playing **{}** requested by {}",
+            track_name,
+            requested_by.mention()
+        ),
+    ))
+}
+
+/// Formats a playback event as a single line for the track history
+fn format_playback_event(event: &PlaybackEvent) -> String {
+    let name = match &event.track_url {
+        Some(url) => format!("[{}](<{}>)", event.track_name, url),
+        None => format!("**{}**", event.track_name),
+    };
+    format!(
+        "<t:{}:R> {} — requested by {}{}",
+        event.started_at,
+        name,
+        UserId::new(event.requested_by as u64).mention(),
+        if event.completed {
+            ""
+        } else {
+            " (not finished)"
+        }
+    )
+}
+
+/// Show the tracks most recently played in this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    category = "Sound",
+    help_text_fn = "crate::command_help::track_history"
+)]
+pub async fn track_history(ctx: Context<'_>) -> Result<(), Error> {
+    let events = ctx
+        .data()
+        .funboy
+        .get_playback_history(ctx.guild_id().unwrap().get(), TRACK_HISTORY_LIMIT)
+        .await;
+
+    match events {
+        Ok(events) if events.is_empty() => {
+            ctx.say_ephemeral(NO_TRACK_HISTORY_NOTIF).await?;
+        }
+        Ok(events) => {
+            let entries: Vec<String> = events.iter().map(format_playback_event).collect();
+            let entries: Vec<&str> = entries.iter().map(|entry| entry.as_str()).collect();
+            ctx.say_list(&entries, true, Some(Box::new(format_as_numeric_list)))
+                .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Set the channel where tracks are announced as they start playing
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Sound",
+    help_text_fn = "crate::command_help::set_track_announcements"
+)]
+pub async fn set_track_announcements(
+    ctx: Context<'_>,
+    #[description = "Leave empty to stop announcing tracks"] channel: Option<ChannelId>,
+) -> Result<(), Error> {
+    let result = ctx
+        .data()
+        .funboy
+        .set_announcement_channel(
+            ctx.guild_id().unwrap().get(),
+            channel.map(|channel| channel.get()),
+        )
+        .await;
+
+    let reply = match (result, channel) {
+        (Ok(()), Some(channel)) => format!("Tracks will be announced in {}", channel.mention()),
+        (Ok(()), None) => "Tracks will no longer be announced".to_string(),
+        (Err(e), _) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+
+    Ok(())
+}
+
+/// Why a track button click was ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackClickDenied {
+    NotInVoice,
+    NotInBotChannel,
+    RateLimited,
+    TimedOut,
+    Duplicate,
+}
+
+impl TrackClickDenied {
+    pub fn message(&self) -> &'static str {
+        match self {
+            TrackClickDenied::NotInVoice => "Join a voice channel to use track buttons.",
+            TrackClickDenied::NotInBotChannel => {
+                "Join the voice channel the bot is in to use track buttons."
+            }
+            TrackClickDenied::RateLimited => "You're clicking too fast, slow down a bit.",
+            TrackClickDenied::TimedOut => {
+                "You've clicked too fast too many times, wait a bit before trying again."
+            }
+            TrackClickDenied::Duplicate => "That button was just clicked.",
+        }
+    }
+}
+
+/// Decides whether a track button click is acted on
+///
+/// Clicks are only accepted from users sharing the bot's voice channel, or in any voice channel
+/// while the bot isn't connected. The same button clicked again within
+/// [`TRACK_BUTTON_DEBOUNCE`] is dropped so double clicks don't repeat the action
+#[derive(Debug)]
+pub struct TrackButtonGuard {
+    rate_limit: RateLimit<(UserId, &'static str)>,
+    recent_clicks: HashMap<(String, String), Instant>,
+}
+
+impl TrackButtonGuard {
+    pub fn new(rate_limit: RateLimit<(UserId, &'static str)>) -> Self {
+        Self {
+            rate_limit,
+            recent_clicks: HashMap::new(),
+        }
+    }
+
+    pub fn check(
+        &mut self,
+        user_id: UserId,
+        user_channel: Option<ChannelId>,
+        bot_channel: Option<ChannelId>,
+        track_id: &str,
+        command: &str,
+        now: Instant,
+    ) -> Result<(), TrackClickDenied> {
+        match (user_channel, bot_channel) {
+            (None, _) => return Err(TrackClickDenied::NotInVoice),
+            (Some(user_channel), Some(bot_channel)) if user_channel != bot_channel => {
+                return Err(TrackClickDenied::NotInBotChannel);
+            }
+            _ => {}
+        }
+
+        self.recent_clicks
+            .retain(|_, clicked_at| now.duration_since(*clicked_at) < TRACK_BUTTON_DEBOUNCE);
+        let button = (track_id.to_string(), command.to_string());
+        if self.recent_clicks.contains_key(&button) {
+            return Err(TrackClickDenied::Duplicate);
+        }
+
+        match self.rate_limit.check((user_id, TRACK_BUTTON_ACTION)) {
+            RateLimitResult::MaxLimitsReached => return Err(TrackClickDenied::TimedOut),
+            RateLimitResult::UsesPerIntervalreached => return Err(TrackClickDenied::RateLimited),
+            RateLimitResult::Ok => {}
+        }
+
+        self.recent_clicks.insert(button, now);
+        Ok(())
+    }
+}
+
 pub async fn on_track_button_click(
     ctx: &poise::serenity_prelude::Context,
     track_component: TrackComponent,
     data: &Data,
 ) -> Result<(), Error> {
+    let interaction = track_component.get_interaction();
+    let (user_channel, bot_channel) = interaction
+        .guild_id
+        .and_then(|guild_id| {
+            let guild = ctx.cache.guild(guild_id)?;
+            let channel_of = |user_id: UserId| {
+                guild
+                    .voice_states
+                    .get(&user_id)
+                    .and_then(|voice_state| voice_state.channel_id)
+            };
+            Some((
+                channel_of(interaction.user.id),
+                channel_of(ctx.cache.current_user().id),
+            ))
+        })
+        .unwrap_or_default();
+
+    let allowed = data.track_button_guard.lock().await.check(
+        interaction.user.id,
+        user_channel,
+        bot_channel,
+        track_component.get_track_id(),
+        track_component.get_track_command(),
+        Instant::now(),
+    );
+    if let Err(denied) = allowed {
+        respond_ephemeral(ctx, interaction, denied.message()).await?;
+        return Ok(());
+    }
+
     if let Some(track) = data
         .track_list
         .lock()
@@ -441,23 +899,31 @@ pub async fn on_track_button_click(
                 .await?;
         }
     } else {
-        track_component
-            .get_interaction()
-            .create_response(
-                ctx.http(),
-                CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                        .content(NON_EXISTANT_TRACK_ERROR)
-                        .components(vec![])
-                        .ephemeral(true),
-                ),
-            )
-            .await?;
+        respond_ephemeral(ctx, interaction, NON_EXISTANT_TRACK_ERROR).await?;
     }
 
     Ok(())
 }
 
+async fn respond_ephemeral(
+    ctx: &poise::serenity_prelude::Context,
+    interaction: &ComponentInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    interaction
+        .create_response(
+            ctx.http(),
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![])
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
 async fn get_songbird_manager(ctx: Context<'_>) -> Arc<Songbird> {
     songbird::get(ctx.serenity_context())
         .await
@@ -474,3 +940,150 @@ async fn get_http_client(ctx: Context<'_>) -> HttpClient {
     };
     client
 }
+
+#[cfg(test)]
+mod sound_test {
+    use super::*;
+
+    const USER: UserId = UserId::new(1);
+    const OTHER_USER: UserId = UserId::new(2);
+    const BOT_CHANNEL: ChannelId = ChannelId::new(10);
+    const OTHER_CHANNEL: ChannelId = ChannelId::new(11);
+
+    fn guard() -> TrackButtonGuard {
+        TrackButtonGuard::new(RateLimit::new(100, 60))
+    }
+
+    #[test]
+    fn track_clicks_need_a_voice_channel() {
+        let mut guard = guard();
+        let now = Instant::now();
+
+        assert_eq!(
+            guard.check(USER, None, None, "a", PLAY_PAUSE, now),
+            Err(TrackClickDenied::NotInVoice)
+        );
+        assert_eq!(
+            guard.check(USER, None, Some(BOT_CHANNEL), "a", PLAY_PAUSE, now),
+            Err(TrackClickDenied::NotInVoice)
+        );
+        assert_eq!(
+            guard.check(
+                USER,
+                Some(OTHER_CHANNEL),
+                Some(BOT_CHANNEL),
+                "a",
+                PLAY_PAUSE,
+                now
+            ),
+            Err(TrackClickDenied::NotInBotChannel)
+        );
+        assert_eq!(
+            guard.check(
+                USER,
+                Some(BOT_CHANNEL),
+                Some(BOT_CHANNEL),
+                "a",
+                PLAY_PAUSE,
+                now
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            guard.check(USER, Some(OTHER_CHANNEL), None, "b", PLAY_PAUSE, now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn duplicate_track_clicks_are_debounced() {
+        let mut guard = guard();
+        let now = Instant::now();
+        let channel = Some(BOT_CHANNEL);
+
+        assert_eq!(guard.check(USER, channel, channel, "a", STOP, now), Ok(()));
+        assert_eq!(
+            guard.check(OTHER_USER, channel, channel, "a", STOP, now),
+            Err(TrackClickDenied::Duplicate)
+        );
+        assert_eq!(guard.check(USER, channel, channel, "a", LOOP, now), Ok(()));
+        assert_eq!(guard.check(USER, channel, channel, "b", STOP, now), Ok(()));
+
+        let later = now + TRACK_BUTTON_DEBOUNCE;
+        assert_eq!(
+            guard.check(USER, channel, channel, "a", STOP, later),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn denied_track_clicks_are_not_recorded() {
+        let mut guard = guard();
+        let now = Instant::now();
+
+        assert_eq!(
+            guard.check(USER, None, None, "a", STOP, now),
+            Err(TrackClickDenied::NotInVoice)
+        );
+        assert_eq!(
+            guard.check(USER, Some(BOT_CHANNEL), None, "a", STOP, now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn track_clicks_are_rate_limited_per_user() {
+        let mut guard = TrackButtonGuard::new(RateLimit::new(2, 60).with_timeout(60, 2));
+        let now = Instant::now();
+        let channel = Some(BOT_CHANNEL);
+
+        assert_eq!(guard.check(USER, channel, None, "a", STOP, now), Ok(()));
+        assert_eq!(guard.check(USER, channel, None, "b", STOP, now), Ok(()));
+        assert_eq!(
+            guard.check(USER, channel, None, "c", STOP, now),
+            Err(TrackClickDenied::RateLimited)
+        );
+        assert_eq!(
+            guard.check(USER, channel, None, "c", STOP, now),
+            Err(TrackClickDenied::TimedOut)
+        );
+        assert_eq!(
+            guard.check(OTHER_USER, channel, None, "c", STOP, now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn announcement_needs_a_channel() {
+        assert!(now_playing_announcement(None, "intro", UserId::new(7)).is_none());
+
+        let (channel_id, announcement) =
+            now_playing_announcement(Some(42), "intro", UserId::new(7)).unwrap();
+        assert_eq!(channel_id, ChannelId::new(42));
+        assert_eq!(announcement, "Now playing **intro** requested by <@7>");
+    }
+
+    #[test]
+    fn playback_event_entries() {
+        let mut event = PlaybackEvent {
+            id: 1,
+            guild_id: 2,
+            track_name: "intro".to_string(),
+            track_url: None,
+            requested_by: 7,
+            started_at: 100,
+            completed: true,
+        };
+        assert_eq!(
+            format_playback_event(&event),
+            "<t:100:R> **intro** — requested by <@7>"
+        );
+
+        event.track_url = Some("https://example.com".to_string());
+        event.completed = false;
+        assert_eq!(
+            format_playback_event(&event),
+            "<t:100:R> [intro](<https://example.com>) — requested by <@7> (not finished)"
+        );
+    }
+}
@@ -1,76 +1,234 @@
 use funboy_core::{
-    FunboyError,
-    template_database::{KeySize, Limit, OrderBy, SortOrder},
+    BulkOutcome, CodeValidation, Funboy, FunboyError, GenerationOutput, RenamePreview,
+    RewriteReport,
+    featured::FeaturedStrategy,
+    grammar::plural,
+    selection::SelectionStrategy,
+    substitute_kind::SubKind,
+    template_database::{
+        KeySize, Limit, OrderBy, RefusedSubstitutes, RewriteScope, SortOrder, SubstituteReceipt,
+        TemplateFilter,
+    },
+    triggers::TriggerMatchMode,
+    user_facing_error::UserFacingError,
 };
 use poise::{ChoiceParameter, CreateReply};
-use serenity::all::{Attachment, ComponentInteraction, CreateAttachment};
+use serenity::all::{
+    Attachment, ChannelId, CreateActionRow, CreateAttachment, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    GuildId, Mentionable, Message, RoleId, UserId,
+};
 
 use crate::{
-    Context, Error,
+    Context, Data, Error,
     components::{
-        CANCEL_BUTTON_ID, CONFIRM_BUTTON_ID, create_confirmation_interaction, edit_interaction,
+        AddSubstituteModal, CONFIRM_BUTTON_ID, ConfirmedAction, EditSubstituteModal,
+        MODAL_INPUT_LIMIT, QuickGenerateComponent, create_add_substitute_modal,
+        create_cancel_button, create_confirm_button, create_edit_substitute_modal,
+        create_favorites_menu, edit_interaction, fits_in_modal_input,
+    },
+    contributors::contributor_names,
+    generate_channels::redirect_message,
+    interpreter::{
+        CommandPermissions, InterpreterContext, context_vars, create_custom_generation,
+        create_interpreter,
     },
-    interpreter::create_custom_interpreter,
     io_format::{
-        context_extension::ContextExtension,
+        context_extension::{ContextExtension, MAX_MESSAGE_CHAIN_SIZE, WARN_MESSAGE_SIZE_EXCEEDED},
         discord_message_format::{
             DISCORD_PRETTY_WIDTH, SeperatedListOptions, StringVecToRef, ellipsize_if_long,
-            format_as_item_seperated_list, format_as_numeric_list,
-            split_by_whitespace_unless_quoted,
+            format_as_item_seperated_list, format_as_numeric_list, format_as_value_log,
+            format_template_suggestions, split_by_whitespace_unless_quoted, split_message,
+            truncate_on_char_boundary,
         },
+        generation_format::{GenerateFormat, RenderedGeneration, embed_title, render_generation},
+        text_diff::TextDiff,
     },
+    localization::{tr, tr_count},
 };
 
 /// Generates text by replacing templates with substitutes and interpreting any embedded code
-///
-/// ## Templates
-/// Templates are any text preceded or optionally followed by a template character.
-/// The character `^` replaces the template with a random substitute.
-///
-/// **Examples:** `^noun` `^noun^` `^verb^ed` (note: `verb` is the template, `ed` is not)
-///
-/// **Given templates:**
-/// - `noun`: "fox", "dog"
-/// - `adj`: "quick", "lazy"
-/// - `color`: "brown"
-/// - `verb`: "jump"
-///
-/// **Example:** `/generate The ^adj ^color ^noun ^verb^ed over the ^adj ^noun`
-/// - Possible output: "The quick brown fox jumped over the lazy dog"
-/// - Possible output: "The lazy brown dog jumped over the quick fox"
-/// ## Template aliases
-/// The character `+` replaces the template with a random substitute **once** — all subsequent uses refer to the same substitute.
-///
-/// **Examples:** `+name` `+name+` `+name-1` `+name-1+` (aliases defined with `-`)
-///
-/// **Example:** `/generate +name-1 is female. +name-2 is male. +name-1 is short. +name-2 is tall.`
-/// - Possible output: "Jane is female. John is male. Jane is short. John is tall."
-/// ## Embedded code
-/// Code between `{}` is executed as FSL (Funboy Scripting Language) code.
-///
-/// **Example:** `/generate The following text is reversed: {print(reverse("reversed"))}`
-/// - Output: "The following text is reversed: desrever"
-///
-/// For more FSL information, use `/help_fsl`
-#[poise::command(slash_command, prefix_command, category = "Templates")]
-pub async fn generate(ctx: Context<'_>, input: String) -> Result<(), Error> {
-    let original_message = ctx.say("Generating...").await?;
-
-    let output = ctx
-        .data()
-        .funboy
-        .generate(&input, create_custom_interpreter(&ctx))
-        .await;
+#[poise::command(
+    slash_command,
+    prefix_command,
+    check = "crate::generate_channels::check_generate_channel",
+    category = "Templates",
+    help_text_fn = "crate::command_help::generate"
+)]
+pub async fn generate(
+    ctx: Context<'_>,
+    input: String,
+    format: Option<GenerateFormat>,
+    #[description = "Save the output as a session variable readable with clone(name)"]
+    save_as: Option<String>,
+    #[description = "Generate the input as written even when it is the name of an alias"]
+    no_alias: Option<bool>,
+) -> Result<(), Error> {
+    if let Some(name) = &save_as
+        && let Err(e) = Funboy::validate_user_variable_name(name)
+    {
+        ctx.say_ephemeral(&e.to_string()).await?;
+        return Ok(());
+    }
+
+    let input = match ctx.guild_id() {
+        Some(guild_id) if !no_alias.unwrap_or(false) => {
+            match ctx.data().funboy.expand_alias(guild_id.get(), &input).await {
+                Ok(input) => input,
+                Err(e) => {
+                    ctx.say_ephemeral(&e.to_string()).await?;
+                    return Ok(());
+                }
+            }
+        }
+        _ => input,
+    };
+
+    let format = format.unwrap_or(GenerateFormat::Plain);
+    let original_message = ctx.say(tr(ctx.locale(), "generating", &[])).await?;
+
+    let context_vars = context_vars(&ctx).await;
+    let session_vars = ctx.data().session_vars.get(ctx.author().id).await;
+    let vars: Vec<(&str, &str)> = context_vars
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .chain(
+            session_vars
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+        .collect();
+
+    let (funboy, interpreter) = create_custom_generation(&ctx).await;
+    let output = funboy.generate_with_vars(&input, interpreter, &vars).await;
 
     match output {
         Ok(output) => {
-            if !output.is_empty() {
-                ctx.edit_long(original_message, &output, false).await?;
+            if let Some(name) = &save_as
+                && let Err(e) = ctx
+                    .data()
+                    .session_vars
+                    .save(ctx.author().id, name, &output.text)
+                    .await
+            {
+                ctx.say_ephemeral(&e.to_string()).await?;
+            }
+
+            if !output.is_effectively_empty {
+                match render_generation(&output.text, format) {
+                    RenderedGeneration::Messages(messages) => {
+                        ctx.edit_messages(original_message, &messages.to_ref(), false)
+                            .await?;
+                    }
+                    RenderedGeneration::Embed(description) => {
+                        original_message
+                            .edit(
+                                ctx,
+                                CreateReply::default().content("").embed(
+                                    CreateEmbed::new()
+                                        .title(embed_title(&input))
+                                        .description(description),
+                                ),
+                            )
+                            .await?;
+                    }
+                    RenderedGeneration::Attachment(output) => {
+                        original_message
+                            .edit(
+                                ctx,
+                                CreateReply::default().content(tr(
+                                    ctx.locale(),
+                                    "output_too_long_for_embed",
+                                    &[],
+                                )),
+                            )
+                            .await?;
+                        ctx.send(
+                            CreateReply::default()
+                                .attachment(CreateAttachment::bytes(output, "message.txt")),
+                        )
+                        .await?;
+                    }
+                }
+            } else {
+                original_message
+                    .edit(
+                        ctx,
+                        CreateReply::default().content(tr(
+                            ctx.locale(),
+                            "generation_complete",
+                            &[],
+                        )),
+                    )
+                    .await?;
+                ctx.say_ephemeral(&no_visible_output_message(ctx.locale(), &output))
+                    .await?;
+            }
+
+            for warning in &output.warnings {
+                ctx.say_ephemeral(&tr(
+                    ctx.locale(),
+                    "generation_warning",
+                    &[("warning", warning.to_string().as_str())],
+                ))
+                .await?;
+            }
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// Shown instead of a generation with nothing visible in it, such as when every branch of a
+/// condition was false
+fn no_visible_output_message(locale: Option<&str>, output: &GenerationOutput) -> String {
+    tr_count(locale, "no_visible_output", output.passes.into(), &[])
+}
+
+/// Generates text like `/generate` and shows the value each command in embedded code produced
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::debug_generate"
+)]
+pub async fn debug_generate(ctx: Context<'_>, input: String) -> Result<(), Error> {
+    let original_message = ctx.say(tr(ctx.locale(), "generating", &[])).await?;
+
+    let (funboy, interpreter) = create_custom_generation(&ctx).await;
+    let debug_output = funboy.debug_generate(&input, interpreter).await;
+
+    match debug_output {
+        Ok(debug_output) => {
+            if !debug_output.output.is_empty() {
+                ctx.edit_long(original_message, &debug_output.output, false)
+                    .await?;
             } else {
                 original_message
-                    .edit(ctx, CreateReply::default().content("Generation complete."))
+                    .edit(
+                        ctx,
+                        CreateReply::default().content(tr(
+                            ctx.locale(),
+                            "generation_complete",
+                            &[],
+                        )),
+                    )
                     .await?;
             }
+
+            if debug_output.log.is_empty() {
+                ctx.say_ephemeral(&tr(ctx.locale(), "no_commands_run", &[]))
+                    .await?;
+            } else {
+                let entries: Vec<(&str, &str)> = debug_output
+                    .log
+                    .iter()
+                    .map(|entry| (entry.command.as_str(), entry.value.as_str()))
+                    .collect();
+                ctx.say_ephemeral(&format_as_value_log(&entries)).await?;
+            }
         }
         Err(e) => {
             ctx.say_ephemeral(&e.to_string()).await?;
@@ -79,36 +237,42 @@ pub async fn generate(ctx: Context<'_>, input: String) -> Result<(), Error> {
     Ok(())
 }
 
+/// A clone of funboy crediting the substitutes it adds to user_id
+fn contributing_funboy(funboy: &Funboy, user_id: UserId, guild_id: Option<GuildId>) -> Funboy {
+    let mut funboy = funboy.clone();
+    funboy.set_contributor(user_id.get(), guild_id.map(|guild_id| guild_id.get()));
+    funboy
+}
+
 /// Adds substitutes to a template
-///
-///
-/// Substitutes are space-separated words or quoted phrases. Use quotes for multi-word substitutes.
-///
-/// **Examples:**
-/// - `/add_subs noun cat dog bird` — adds three single-word substitutes
-/// - `/add_subs noun "hot dog" "cold pizza"` — adds two multi-word substitutes
-///
-/// ## Single substitute mode
-/// Use `add_as_single_sub: true` for large or complex substitutes, especially those containing quotes.
-///
-/// **Example:** `/add_subs quote this substitute contains "a quote" in it add_as_single_sub: true` - adds a single substitute with quotes inside
-///
-/// This treats the entire input as a single substitute allowing spaces and quotes inside the substitute.
-#[poise::command(slash_command, prefix_command, category = "Templates")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::add_subs"
+)]
 pub async fn add_subs(
     ctx: Context<'_>,
     template: String,
     subs: String,
     add_as_single_sub: Option<bool>,
+    validate_code: Option<bool>,
 ) -> Result<(), Error> {
     let add_as_single_sub = add_as_single_sub.unwrap_or(false);
+    let validation = if validate_code.unwrap_or(false) {
+        CodeValidation::Strict
+    } else {
+        CodeValidation::Warn
+    };
 
-    let result = if add_as_single_sub {
-        ctx.data().funboy.add_substitutes(&template, &[&subs]).await
+    let subs: Vec<&str> = if add_as_single_sub {
+        vec![subs.as_str()]
     } else {
-        let subs: Vec<&str> = split_by_whitespace_unless_quoted(&subs);
-        ctx.data().funboy.add_substitutes(&template, &subs).await
+        split_by_whitespace_unless_quoted(&subs)
     };
+    let result = contributing_funboy(&ctx.data().funboy, ctx.author().id, ctx.guild_id())
+        .add_substitutes_with_validation(&template, &subs, validation)
+        .await;
 
     match result {
         Ok(sub_record) => {
@@ -131,20 +295,35 @@ pub async fn add_subs(
             }
 
             if sub_record.ignored.len() > 0 {
-                let appended_text = format!("\nalready in `{}`", template);
+                let mut message = format!(
+                    "Not added to `{}`: {}",
+                    template,
+                    sub_record.ignored_summary()
+                );
+                if let Some(refused) = &sub_record.refused {
+                    message.push_str(&format!("\n{}", refused_message(&template, refused)));
+                }
+                ctx.say_ephemeral(&message).await?;
+            }
 
-                ctx.say_list(
-                    &sub_record.ignored.to_ref(),
-                    true,
-                    Some(Box::new(move |items| {
-                        format_as_item_seperated_list(
-                            items,
-                            &appended_text,
-                            SeperatedListOptions::default(),
+            if sub_record.warnings.len() > 0 {
+                let heading = match validation {
+                    CodeValidation::Warn => "Added with code that will fail to generate:",
+                    CodeValidation::Strict => "Not added since their code failed to parse:",
+                };
+                let warnings: Vec<String> = sub_record
+                    .warnings
+                    .iter()
+                    .map(|warning| {
+                        format!(
+                            "`{}` {}",
+                            ellipsize_if_long(&warning.substitute, DISCORD_PRETTY_WIDTH),
+                            warning.error
                         )
-                    })),
-                )
-                .await?;
+                    })
+                    .collect();
+                ctx.say_long(&format!("{}\n{}", heading, warnings.join("\n")), true)
+                    .await?;
             }
         }
         Err(e) => {
@@ -154,27 +333,250 @@ pub async fn add_subs(
     Ok(())
 }
 
+const RECENT_TEMPLATE_COUNT: i64 = 5;
+const MODAL_PLACEHOLDER_LIMIT: usize = 100;
+
+/// Adds the content of a message as a substitute to a template
+#[poise::command(
+    context_menu_command = "Add to template…",
+    category = "Templates",
+    help_text_fn = "crate::command_help::add_message_sub"
+)]
+pub async fn add_message_sub(ctx: Context<'_>, message: Message) -> Result<(), Error> {
+    let Context::Application(app_ctx) = ctx else {
+        return Ok(());
+    };
+
+    let recent_templates = ctx
+        .data()
+        .funboy
+        .get_templates(
+            None,
+            OrderBy::Id(SortOrder::Descending),
+            Limit::Count(RECENT_TEMPLATE_COUNT),
+        )
+        .await
+        .unwrap_or_default();
+    let recent_templates: Vec<&str> = recent_templates
+        .iter()
+        .map(|template| template.name.as_str())
+        .collect();
+
+    let modal = create_add_substitute_modal(
+        message.channel_id,
+        message.id,
+        &format_template_suggestions(&recent_templates, MODAL_PLACEHOLDER_LIMIT),
+    );
+
+    app_ctx
+        .interaction
+        .create_response(ctx, CreateInteractionResponse::Modal(modal))
+        .await?;
+    app_ctx
+        .has_sent_initial_response
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// Tells how many substitutes were left out because template is full
+fn refused_message(template: &str, refused: &RefusedSubstitutes) -> String {
+    format!(
+        "{} substitutes not added, `{}` reached its cap of {} substitutes",
+        refused.substitutes.len(),
+        template,
+        refused.cap
+    )
+}
+
+pub async fn on_add_substitute_modal_submit(
+    ctx: &poise::serenity_prelude::Context,
+    add_substitute_modal: AddSubstituteModal,
+    data: &Data,
+) -> Result<(), Error> {
+    let template = add_substitute_modal.get_template();
+
+    let reply = match add_substitute_modal
+        .get_channel_id()
+        .message(ctx, add_substitute_modal.get_message_id())
+        .await
+    {
+        Ok(message) => {
+            let content = message.content.trim();
+            if content.is_empty() {
+                "message has no text to add".to_string()
+            } else {
+                let interaction = add_substitute_modal.get_interaction();
+                let funboy =
+                    contributing_funboy(&data.funboy, interaction.user.id, interaction.guild_id);
+                match funboy.add_substitutes(template, &[content]).await {
+                    Ok(SubstituteReceipt {
+                        refused: Some(refused),
+                        ..
+                    }) => refused_message(template, &refused),
+                    Ok(sub_record) if sub_record.updated.len() > 0 => {
+                        format!(
+                            "{}\nadded to `{}`",
+                            ellipsize_if_long(content, DISCORD_PRETTY_WIDTH),
+                            template
+                        )
+                    }
+                    Ok(sub_record) => {
+                        format!(
+                            "{}\nnot added to `{}`: {}",
+                            ellipsize_if_long(content, DISCORD_PRETTY_WIDTH),
+                            template,
+                            sub_record.ignored_summary()
+                        )
+                    }
+                    Err(e) => e.to_string(),
+                }
+            }
+        }
+        Err(_) => "message could not be found".to_string(),
+    };
+
+    add_substitute_modal
+        .get_interaction()
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(reply)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Stops substitutes from being generated without deleting them
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::disable_subs"
+)]
+pub async fn disable_subs(
+    ctx: Context<'_>,
+    template: Option<String>,
+    search_term: Option<String>,
+    ids: Option<String>,
+) -> Result<(), Error> {
+    set_subs_enabled(ctx, template, search_term, ids, false).await
+}
+
+/// Lets substitutes disabled with /disable_subs be generated again
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::enable_subs"
+)]
+pub async fn enable_subs(
+    ctx: Context<'_>,
+    template: Option<String>,
+    search_term: Option<String>,
+    ids: Option<String>,
+) -> Result<(), Error> {
+    set_subs_enabled(ctx, template, search_term, ids, true).await
+}
+
+async fn set_subs_enabled(
+    ctx: Context<'_>,
+    template: Option<String>,
+    search_term: Option<String>,
+    ids: Option<String>,
+    enabled: bool,
+) -> Result<(), Error> {
+    let action = if enabled { "Enabled" } else { "Disabled" };
+
+    if let Some(ids) = ids {
+        let ids: Result<Vec<KeySize>, _> = split_by_whitespace_unless_quoted(&ids)
+            .iter()
+            .map(|id| id.parse::<KeySize>())
+            .collect();
+        let Ok(ids) = ids else {
+            ctx.say_ephemeral(&UserFacingError::InvalidId.to_string())
+                .await?;
+            return Ok(());
+        };
+
+        let mut changed = Vec::new();
+        let mut missing = Vec::new();
+        for id in ids {
+            match ctx.data().funboy.set_substitute_enabled(id, enabled).await {
+                Ok(Some(sub)) => changed.push(sub.name),
+                Ok(None) => missing.push(id.to_string()),
+                Err(e) => {
+                    ctx.say_ephemeral(&e.to_string()).await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            let appended_text = format!("\n{}", action.to_lowercase());
+            ctx.say_list(
+                &changed.to_ref(),
+                true,
+                Some(Box::new(move |subs| {
+                    format_as_item_seperated_list(
+                        subs,
+                        &appended_text,
+                        SeperatedListOptions::default(),
+                    )
+                })),
+            )
+            .await?;
+        }
+        if !missing.is_empty() {
+            ctx.say_ephemeral(&format!("No substitutes with IDs {}", missing.join(", ")))
+                .await?;
+        }
+        return Ok(());
+    }
+
+    let Some(template) = template else {
+        ctx.say_ephemeral("Give a template to match substitutes in or a list of IDs.")
+            .await?;
+        return Ok(());
+    };
+    let search_term = search_term.unwrap_or_default();
+
+    match ctx
+        .data()
+        .funboy
+        .set_substitutes_enabled_matching(&template, &search_term, enabled)
+        .await
+    {
+        Ok(changed) => {
+            let matching = if search_term.is_empty() {
+                String::new()
+            } else {
+                format!(" matching `{}`", search_term)
+            };
+            ctx.say(&format!(
+                "{} {} substitutes in `{}`{}",
+                action, changed, template, matching
+            ))
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
 /// Deletes substitutes from a template
-///
-///
-/// Substitutes can be deleted by name or by ID, space-separated.
-///
-/// ## Delete by name
-/// - **Example:** `/delete_subs noun cat dog` — removes "cat" and "dog" from the `noun` template
-/// - **Example:** `/delete_subs name "hot dog"` — removes the "hot dog" substitute
-///
-/// ## Delete by ID
-/// - **Example:** `/delete_subs noun 0 2 5 delete_by_id: true` — removes substitutes with IDs: 0, 2, and 5
-///
-/// This is useful when substitutes are large and difficult to write out fully inside the command.
-/// Note: IDs of substitutes can be obtained by using the `/list_subs` command with the ID list style.
-///
-/// ## Single substitute mode
-/// Use `delete_as_single_sub: true` to treat the entire input as a single substitute name or ID.
-/// Useful for complex substitute names containing spaces or quotes.
-///
-/// **Example:** `/delete_subs template: sentence subs: This is one substitute containing "spaces and quotes inside it" delete_as_single_sub: true`
-#[poise::command(slash_command, prefix_command, category = "Templates")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::delete_subs"
+)]
 pub async fn delete_subs(
     ctx: Context<'_>,
     template: String,
@@ -189,9 +591,7 @@ pub async fn delete_subs(
         if delete_by_id {
             match subs.parse::<KeySize>() {
                 Ok(id) => ctx.data().funboy.delete_substitutes_by_id(&[id]).await,
-                Err(_) => Err(FunboyError::UserInput(
-                    "ID must be a valid number.".to_string(),
-                )),
+                Err(_) => Err(FunboyError::UserInput(UserFacingError::InvalidId)),
             }
         } else {
             ctx.data()
@@ -206,9 +606,7 @@ pub async fn delete_subs(
             let ids: Result<Vec<KeySize>, _> = subs.iter().map(|s| s.parse::<KeySize>()).collect();
             match ids {
                 Ok(ids) => ctx.data().funboy.delete_substitutes_by_id(&ids).await,
-                Err(_) => Err(FunboyError::UserInput(
-                    "Id must be a valid number.".to_string(),
-                )),
+                Err(_) => Err(FunboyError::UserInput(UserFacingError::InvalidId)),
             }
         } else {
             ctx.data().funboy.delete_substitutes(&template, &subs).await
@@ -236,19 +634,11 @@ pub async fn delete_subs(
             }
 
             if sub_record.ignored.len() > 0 {
-                let appended_text = format!("\nnot present in `{}`", template);
-
-                ctx.say_list(
-                    &sub_record.ignored.to_ref(),
-                    true,
-                    Some(Box::new(move |items| {
-                        format_as_item_seperated_list(
-                            items,
-                            &appended_text,
-                            SeperatedListOptions::default(),
-                        )
-                    })),
-                )
+                ctx.say_ephemeral(&format!(
+                    "Not deleted from `{}`: {}",
+                    template,
+                    sub_record.ignored_summary()
+                ))
                 .await?;
             }
         }
@@ -260,9 +650,12 @@ pub async fn delete_subs(
 }
 
 /// Adds a single substitute from a file
-///
-/// **Example:** `/upload_sub essay [essay.txt]` — uploads file `essay.txt` and adds it as a single substitute to the `essay`
-#[poise::command(slash_command, prefix_command, category = "Templates")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::upload_sub"
+)]
 pub async fn upload_sub(
     ctx: Context<'_>,
     template: String,
@@ -271,7 +664,8 @@ pub async fn upload_sub(
     const ALLOWED_TYPES: &[&str] = &["text/plain; charset=utf-8"];
 
     if !ALLOWED_TYPES.contains(&sub_file.content_type.as_deref().unwrap_or("")) {
-        ctx.say_ephemeral("Only text files are allowed.").await?;
+        ctx.say_ephemeral(&tr(ctx.locale(), "only_text_files", &[]))
+            .await?;
         return Ok(());
     }
 
@@ -280,8 +674,26 @@ pub async fn upload_sub(
 
     match sub {
         Ok(sub) => {
-            let result = ctx.data().funboy.add_substitutes(&template, &[&sub]).await;
+            let result = contributing_funboy(&ctx.data().funboy, ctx.author().id, ctx.guild_id())
+                .add_substitutes(&template, &[&sub])
+                .await;
             match result {
+                Ok(SubstituteReceipt {
+                    refused: Some(refused),
+                    ..
+                }) => {
+                    ctx.say_ephemeral(&refused_message(&template, &refused))
+                        .await?;
+                }
+                Ok(sub_record) if sub_record.updated.is_empty() => {
+                    ctx.say_ephemeral(&format!(
+                        "Substitute from file {} not added to `{}`: {}",
+                        ellipsize_if_long(&sub_file.filename, DISCORD_PRETTY_WIDTH),
+                        template,
+                        sub_record.ignored_summary()
+                    ))
+                    .await?;
+                }
                 Ok(_) => {
                     ctx.say_ephemeral(&format!(
                         "Added substitute from file {}",
@@ -295,7 +707,8 @@ pub async fn upload_sub(
             }
         }
         Err(_) => {
-            ctx.say_ephemeral("Text must be valid utf8.").await?;
+            ctx.say_ephemeral(&tr(ctx.locale(), "text_not_utf8", &[]))
+                .await?;
         }
     }
 
@@ -303,9 +716,12 @@ pub async fn upload_sub(
 }
 
 /// Copies all substitutes from one template to another
-///
-/// **Example:** `/copy_subs food noun` — copies all substitutes from `food` to `noun`
-#[poise::command(slash_command, prefix_command, category = "Templates")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::copy_subs"
+)]
 pub async fn copy_subs(
     ctx: Context<'_>,
     from_template: String,
@@ -332,67 +748,70 @@ pub async fn copy_subs(
     Ok(())
 }
 
-/// Replaces a substitute in a template with another value
-///
-/// Substitutes can be replaced by name or by ID.
-///
-/// ## Replace by name
-/// - **Example:** `/replace_sub noun cat dog` — replaces the "cat" substitute with "dog"
-/// - **Example:** `/replace_sub name "hot dog" "cold pizza"` — replaces "hot dog" with "cold pizza"
-///
-/// ## Replace by ID
-/// - **Example:** `/replace_sub noun 0 "new substitute" replace_by_id: true` — replaces the substitute with id 0
-/// Note: ID's of substitutes can be obtained by using the `/list_subs` command with the ID list style.
-#[poise::command(slash_command, prefix_command, category = "Templates")]
-pub async fn replace_sub(
+/// Creates a new template containing a copy of every substitute in an existing template
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::clone_template"
+)]
+pub async fn clone_template(
     ctx: Context<'_>,
     template: String,
-    from: String,
-    to: String,
-    replace_by_id: Option<bool>,
+    new_name: String,
 ) -> Result<(), Error> {
-    let replace_by_id = replace_by_id.unwrap_or(false);
+    let result = ctx.data().funboy.clone_template(&template, &new_name).await;
 
-    let result = if replace_by_id {
-        let id = from.parse::<KeySize>();
-        match id {
-            Ok(id) => ctx.data().funboy.replace_substitute_by_id(id, &to).await,
-            Err(_) => {
-                ctx.say_ephemeral("Id must be a valid number.").await?;
-                return Ok(());
-            }
+    match result {
+        Ok(report) => {
+            ctx.say_ephemeral(&format!(
+                "Cloned `{}` into `{}` with {} substitutes",
+                template, report.template.name, report.copied
+            ))
+            .await?;
+        }
+        Err(FunboyError::UserInput(UserFacingError::TemplateExists { name })) => {
+            ctx.say_ephemeral(&format!(
+                "`{}` already exists, use `/copy_subs {} {}` to add the substitutes to it instead",
+                name, template, name
+            ))
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
         }
-    } else {
-        ctx.data()
-            .funboy
-            .replace_substitute(&template, &from, &to)
-            .await
     };
+    Ok(())
+}
+
+/// Raises or lowers how many substitutes a template may hold
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::set_template_cap"
+)]
+pub async fn set_template_cap(
+    ctx: Context<'_>,
+    template: String,
+    #[description = "Leave empty to go back to the default cap"] cap: Option<u32>,
+) -> Result<(), Error> {
+    let funboy = &ctx.data().funboy;
+    let result = funboy
+        .set_substitute_cap(&template, cap.map(i64::from))
+        .await;
 
     match result {
-        Ok(template) => match template {
-            Some(_) => {
-                ctx.say_long(
-                    &format!(
-                        "Renamed substitute `{}` to `{}`",
-                        ellipsize_if_long(&from, 255),
-                        ellipsize_if_long(&to, 255)
-                    ),
-                    false,
-                )
-                .await?;
-            }
-            None => {
-                ctx.say_long(
-                    &format!(
-                        "Failed to rename substitute `{}`",
-                        ellipsize_if_long(&from, 255)
-                    ),
-                    true,
-                )
-                .await?;
-            }
-        },
+        Ok(()) => {
+            let cap = funboy.get_substitute_cap(&template).await?;
+            ctx.say_ephemeral(&format!(
+                "`{}` can now hold up to {} substitutes",
+                template, cap
+            ))
+            .await?;
+        }
         Err(e) => {
             ctx.say_ephemeral(&e.to_string()).await?;
         }
@@ -400,97 +819,1199 @@ pub async fn replace_sub(
     Ok(())
 }
 
-async fn delete_multiple_templates(
+/// How a template picks its next substitute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum StrategyChoice {
+    Random,
+    #[name = "Round Robin"]
+    RoundRobin,
+    #[name = "Least Recently Used"]
+    LeastRecentlyUsed,
+}
+
+impl From<StrategyChoice> for SelectionStrategy {
+    fn from(value: StrategyChoice) -> Self {
+        match value {
+            StrategyChoice::Random => SelectionStrategy::Random,
+            StrategyChoice::RoundRobin => SelectionStrategy::RoundRobin,
+            StrategyChoice::LeastRecentlyUsed => SelectionStrategy::LeastRecentlyUsed,
+        }
+    }
+}
+
+/// Changes how a template picks its substitutes
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::set_template_strategy"
+)]
+pub async fn set_template_strategy(
     ctx: Context<'_>,
-    templates_to_delete: &[&str],
-    interaction: &ComponentInteraction,
+    template: String,
+    strategy: StrategyChoice,
 ) -> Result<(), Error> {
-    match ctx
+    let result = ctx
         .data()
         .funboy
-        .delete_templates(templates_to_delete)
-        .await
-    {
-        Ok(result) => {
-            if result.updated.len() > 0 {
-                edit_interaction(
-                    ctx,
-                    &interaction,
-                    &format!(
-                        "Deleted templates `{}`",
-                        ellipsize_if_long(&result.updated_to_string(), 1000)
+        .set_template_strategy(&template, strategy.into())
+        .await;
+
+    match result {
+        Ok(()) => {
+            ctx.say_ephemeral(&format!(
+                "`{}` now picks substitutes by {}",
+                template,
+                strategy.name().to_lowercase()
+            ))
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// Restricts the FSL commands that send messages to chosen roles
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::set_fsl_permissions"
+)]
+pub async fn set_fsl_permissions(
+    ctx: Context<'_>,
+    #[description = "Leave empty to list the allowed roles"] role: Option<RoleId>,
+    #[description = "Whether the role may use them, defaults to true"] allowed: Option<bool>,
+) -> Result<(), Error> {
+    let funboy = &ctx.data().funboy;
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    if let Some(role) = role {
+        let allowed = allowed.unwrap_or(true);
+        if let Err(e) = funboy
+            .set_fsl_role_allowed(guild_id, role.get(), allowed)
+            .await
+        {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    }
+
+    let reply = match funboy.get_fsl_allowed_roles(guild_id).await {
+        Ok(roles) if roles.is_empty() => {
+            "Everyone can use FSL commands that send messages.".to_string()
+        }
+        Ok(roles) => {
+            let roles: Vec<String> = roles
+                .into_iter()
+                .map(|role| RoleId::new(role).mention().to_string())
+                .collect();
+            format!(
+                "FSL commands that send messages are limited to administrators and {}",
+                roles.join(", ")
+            )
+        }
+        Err(e) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// Allows generating in a channel, once any channel is allowed the others are not
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::allow_generate_channel"
+)]
+pub async fn allow_generate_channel(ctx: Context<'_>, channel: ChannelId) -> Result<(), Error> {
+    set_generate_channel_allowed(ctx, channel, true).await
+}
+
+/// Stops allowing generation in a channel
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::disallow_generate_channel"
+)]
+pub async fn disallow_generate_channel(ctx: Context<'_>, channel: ChannelId) -> Result<(), Error> {
+    set_generate_channel_allowed(ctx, channel, false).await
+}
+
+async fn set_generate_channel_allowed(
+    ctx: Context<'_>,
+    channel: ChannelId,
+    allowed: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let changed = ctx
+        .data()
+        .funboy
+        .set_generate_channel_allowed(guild_id, channel.get(), allowed)
+        .await;
+
+    let reply = match (changed, allowed) {
+        (Ok(true), true) => format!("Generating is allowed in {}", channel.mention()),
+        (Ok(false), true) => format!("Generating was already allowed in {}", channel.mention()),
+        (Ok(true), false) => format!("Generating is no longer allowed in {}", channel.mention()),
+        (Ok(false), false) => format!("{} was not an allowed channel", channel.mention()),
+        (Err(e), _) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// Lists the channels generating is limited to
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::list_generate_channels"
+)]
+pub async fn list_generate_channels(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let reply = match ctx.data().funboy.get_generate_channels(guild_id).await {
+        Ok(channels) if channels.is_empty() => {
+            "Generating is allowed in every channel.".to_string()
+        }
+        Ok(channels) => {
+            let channels: Vec<ChannelId> = channels.into_iter().map(ChannelId::new).collect();
+            redirect_message(&channels)
+        }
+        Err(e) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// How the pattern of a trigger is compared with messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum TriggerModeChoice {
+    Exact,
+    Contains,
+    Regex,
+}
+
+impl From<TriggerModeChoice> for TriggerMatchMode {
+    fn from(value: TriggerModeChoice) -> Self {
+        match value {
+            TriggerModeChoice::Exact => TriggerMatchMode::Exact,
+            TriggerModeChoice::Contains => TriggerMatchMode::Contains,
+            TriggerModeChoice::Regex => TriggerMatchMode::Regex,
+        }
+    }
+}
+
+const DEFAULT_TRIGGER_COOLDOWN_SECS: u64 = 30;
+
+/// Reply to messages containing a phrase with a generation
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Templates",
+    help_text_fn = "crate::command_help::add_trigger"
+)]
+pub async fn add_trigger(
+    ctx: Context<'_>,
+    #[description = "Phrase that sets off the trigger"] pattern: String,
+    #[description = "What is generated in reply"] input: String,
+    #[description = "Contains by default"] mode: Option<TriggerModeChoice>,
+    #[description = "Seconds before the trigger replies again, 30 by default"]
+    #[max = 86400]
+    cooldown: Option<u64>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let mode = mode.unwrap_or(TriggerModeChoice::Contains).into();
+    let cooldown = cooldown.unwrap_or(DEFAULT_TRIGGER_COOLDOWN_SECS);
+    let reply = match ctx
+        .data()
+        .funboy
+        .set_trigger(guild_id, &pattern, mode, &input, cooldown)
+        .await
+    {
+        Ok(trigger) => format!(
+            "Trigger {} now replies to messages matching `{}` ({}) with `{}`",
+            trigger.id,
+            ellipsize_if_long(&trigger.pattern, DISCORD_PRETTY_WIDTH),
+            trigger.match_mode,
+            ellipsize_if_long(&trigger.template_input, DISCORD_PRETTY_WIDTH)
+        ),
+        Err(e) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// Remove a trigger by the ID shown by /list_triggers
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Templates",
+    help_text_fn = "crate::command_help::remove_trigger"
+)]
+pub async fn remove_trigger(ctx: Context<'_>, id: KeySize) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let reply = match ctx.data().funboy.remove_trigger(guild_id, id).await {
+        Ok(true) => format!("Removed trigger {}", id),
+        Ok(false) => format!("There is no trigger with ID {}", id),
+        Err(e) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// List the triggers of this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Templates",
+    help_text_fn = "crate::command_help::list_triggers"
+)]
+pub async fn list_triggers(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    match ctx.data().funboy.get_triggers(guild_id).await {
+        Ok(triggers) if triggers.is_empty() => {
+            ctx.say_ephemeral("This server has no triggers.").await?;
+        }
+        Ok(triggers) => {
+            let lines: Vec<String> = triggers
+                .iter()
+                .map(|trigger| {
+                    format!(
+                        "{}: `{}` ({}, {}s cooldown) → `{}`",
+                        trigger.id,
+                        ellipsize_if_long(&trigger.pattern, DISCORD_PRETTY_WIDTH),
+                        trigger.match_mode,
+                        trigger.cooldown_secs,
+                        ellipsize_if_long(&trigger.template_input, DISCORD_PRETTY_WIDTH)
+                    )
+                })
+                .collect();
+            ctx.say_long(&lines.join("\n"), true).await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// How the template of the day is picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum FeaturedChoice {
+    Random,
+    #[name = "Least Recently Featured"]
+    LeastRecentlyFeatured,
+    #[name = "Most Used"]
+    MostUsed,
+}
+
+impl From<FeaturedChoice> for FeaturedStrategy {
+    fn from(value: FeaturedChoice) -> Self {
+        match value {
+            FeaturedChoice::Random => FeaturedStrategy::Random,
+            FeaturedChoice::LeastRecentlyFeatured => FeaturedStrategy::LeastRecentlyFeatured,
+            FeaturedChoice::MostUsed => FeaturedStrategy::MostUsed,
+        }
+    }
+}
+
+/// Set the channel a template of the day is posted in
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Templates",
+    help_text_fn = "crate::command_help::set_daily_template_channel"
+)]
+pub async fn set_daily_template_channel(
+    ctx: Context<'_>,
+    #[description = "Leave empty to stop posting a template of the day"] channel: Option<ChannelId>,
+    #[description = "Random by default"] strategy: Option<FeaturedChoice>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let funboy = &ctx.data().funboy;
+
+    let reply = match channel {
+        Some(channel) => {
+            let strategy = strategy.unwrap_or(FeaturedChoice::Random);
+            match funboy
+                .set_featured_channel(guild_id, channel.get(), strategy.into())
+                .await
+            {
+                Ok(()) => format!(
+                    "A template of the day picked by {} will be posted in {}",
+                    strategy.name().to_lowercase(),
+                    channel.mention()
+                ),
+                Err(e) => e.to_string(),
+            }
+        }
+        None => match funboy.remove_featured_channel(guild_id).await {
+            Ok(true) => "A template of the day will no longer be posted".to_string(),
+            Ok(false) => "No channel was set to post a template of the day".to_string(),
+            Err(e) => e.to_string(),
+        },
+    };
+    ctx.say_ephemeral(&reply).await?;
+
+    Ok(())
+}
+
+/// Manages the shortcuts of this server that /generate expands
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands("alias_add", "alias_remove", "alias_list"),
+    subcommand_required,
+    category = "Templates",
+    help_text_fn = "crate::command_help::alias"
+)]
+pub async fn alias(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Adds an alias or replaces the input of an existing one
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "add"
+)]
+pub async fn alias_add(
+    ctx: Context<'_>,
+    #[description = "Named like a template"] name: String,
+    #[description = "What /generate uses when its input is the alias name"] input: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let reply = match ctx.data().funboy.set_alias(guild_id, &name, &input).await {
+        Ok(alias) => format!(
+            "`/generate {}` now generates `{}`",
+            alias.alias_name,
+            ellipsize_if_long(&alias.input_text, DISCORD_PRETTY_WIDTH)
+        ),
+        Err(e) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// Removes an alias
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "remove"
+)]
+pub async fn alias_remove(ctx: Context<'_>, name: String) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let reply = match ctx.data().funboy.remove_alias(guild_id, &name).await {
+        Ok(true) => format!("Removed alias `{}`", name),
+        Ok(false) => format!("There is no alias named `{}`", name),
+        Err(e) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// Lists the aliases of this server
+#[poise::command(slash_command, prefix_command, guild_only, rename = "list")]
+pub async fn alias_list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    match ctx.data().funboy.get_aliases(guild_id).await {
+        Ok(aliases) if aliases.is_empty() => {
+            ctx.say_ephemeral("This server has no aliases.").await?;
+        }
+        Ok(aliases) => {
+            let entries: Vec<String> = aliases
+                .iter()
+                .map(|alias| {
+                    format!(
+                        "`{}` → `{}`",
+                        alias.alias_name,
+                        ellipsize_if_long(&alias.input_text, DISCORD_PRETTY_WIDTH)
+                    )
+                })
+                .collect();
+            ctx.say_list(
+                &entries.to_ref(),
+                true,
+                Some(Box::new(format_as_numeric_list)),
+            )
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+const EMBED_FIELD_LIMIT: usize = 1024;
+
+/// Truncates text with an ellipsis so it fits in an embed field
+pub fn fit_embed_field(text: &str) -> String {
+    if text.len() > EMBED_FIELD_LIMIT {
+        let ellipsis = "...";
+        format!(
+            "{}{}",
+            truncate_on_char_boundary(text, EMBED_FIELD_LIMIT - ellipsis.len()),
+            ellipsis
+        )
+    } else {
+        text.to_string()
+    }
+}
+
+/// Pins up to three example outputs to a template
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::set_template_examples"
+)]
+pub async fn set_template_examples(
+    ctx: Context<'_>,
+    template: String,
+    examples: Option<String>,
+) -> Result<(), Error> {
+    let examples = examples.unwrap_or_default();
+    let examples = split_by_whitespace_unless_quoted(&examples);
+
+    match ctx
+        .data()
+        .funboy
+        .set_template_examples(&template, &examples)
+        .await
+    {
+        Ok(examples) if examples.is_empty() => {
+            ctx.say_ephemeral(&format!("Removed examples from `{}`", template))
+                .await?;
+        }
+        Ok(examples) => {
+            ctx.say_ephemeral(&format!(
+                "Set {} examples for `{}`",
+                examples.len(),
+                template
+            ))
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// Shows the pinned examples of a template next to a fresh generation of it
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::preview_template"
+)]
+pub async fn preview_template(ctx: Context<'_>, template: String) -> Result<(), Error> {
+    let (funboy, interpreter) = create_custom_generation(&ctx).await;
+    let preview = funboy.preview_template(&template, interpreter).await;
+
+    match preview {
+        Ok(preview) => {
+            let mut embed = CreateEmbed::new().title(format!("`{}`", preview.template));
+
+            if preview.examples.is_empty() {
+                embed = embed.field("Examples", "No examples set.", true);
+            }
+            for (i, example) in preview.examples.iter().enumerate() {
+                embed = embed.field(format!("Example {}", i + 1), example, true);
+            }
+
+            let generated = if preview.generated.is_empty() {
+                "Generation was empty.".to_string()
+            } else {
+                fit_embed_field(&preview.generated)
+            };
+            embed = embed.field("Fresh generation", generated, true);
+
+            ctx.send(CreateReply::default().embed(embed)).await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// Notes why a template exists or how its substitutes should be written
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::set_template_description"
+)]
+pub async fn set_template_description(
+    ctx: Context<'_>,
+    template: String,
+    #[description = "Leave empty to remove the description"] description: Option<String>,
+) -> Result<(), Error> {
+    let funboy = &ctx.data().funboy;
+    let reply = match funboy
+        .set_template_description(&template, description.as_deref())
+        .await
+    {
+        Ok(()) => match funboy.get_template_description(&template).await? {
+            Some(_) => format!("Set the description of `{}`", template),
+            None => format!("Removed the description of `{}`", template),
+        },
+        Err(e) => e.to_string(),
+    };
+    ctx.say_ephemeral(&reply).await?;
+    Ok(())
+}
+
+/// Shows the description, settings and examples of a template
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::template_info"
+)]
+pub async fn template_info(ctx: Context<'_>, template: String) -> Result<(), Error> {
+    let info = match ctx.data().funboy.get_template_info(&template).await {
+        Ok(info) => info,
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    let description = match &info.description {
+        Some(description) => fit_embed_field(description),
+        None => "No description set.".to_string(),
+    };
+    let substitutes = if info.disabled_substitutes > 0 {
+        format!(
+            "{} ({} disabled)",
+            info.substitutes, info.disabled_substitutes
+        )
+    } else {
+        info.substitutes.to_string()
+    };
+    let cap = if info.custom_cap {
+        info.substitute_cap.to_string()
+    } else {
+        format!("{} (default)", info.substitute_cap)
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("`{}`", info.template.name))
+        .description(description)
+        .field("Substitutes", substitutes, true)
+        .field("Cap", cap, true)
+        .field("Created", format!("<t:{}:R>", info.created_at), true);
+    if info.archived {
+        embed = embed.field("Archived", "Yes", true);
+    }
+    if info.examples.is_empty() {
+        embed = embed.field("Examples", "No examples set.", false);
+    }
+    for (i, example) in info.examples.iter().enumerate() {
+        embed = embed.field(
+            format!("Example {}", i + 1),
+            fit_embed_field(example),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Deviations listed by audit_template
+const AUDIT_DEVIATION_COUNT: usize = 5;
+
+/// Samples random substitutes of a template to check they come up as often as they should
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::audit_template"
+)]
+pub async fn audit_template(
+    ctx: Context<'_>,
+    template: String,
+    #[description = "Substitutes to draw, 10000 by default"]
+    #[min = 1]
+    #[max = 100000]
+    draws: Option<usize>,
+) -> Result<(), Error> {
+    let draws = draws.unwrap_or(10000);
+    let report = match ctx
+        .data()
+        .funboy
+        .sample_distribution(&template, draws)
+        .await
+    {
+        Ok(report) => report,
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    let mut lines = vec![
+        format!(
+            "Drew `{}` {} times from {} of {} substitutes.",
+            report.template,
+            report.draws,
+            report.page_len,
+            report.substitutes.len()
+        ),
+        format!(
+            "Chi-square {:.2} over {} degrees of freedom, a score of {:.2} where about 1 is fair.",
+            report.chi_square(),
+            report.degrees_of_freedom(),
+            report.deviation_score()
+        ),
+    ];
+    if report.page_len < report.substitutes.len() {
+        lines.push(
+            "Only one page of substitutes is drawn from at a time so the rest show no draws."
+                .to_string(),
+        );
+    }
+    lines.push("\nFurthest from expected:".to_string());
+    lines.extend(
+        report
+            .top_deviations(AUDIT_DEVIATION_COUNT)
+            .iter()
+            .map(|sub| {
+                format!(
+                    "`{}` {}: {} draws, {:.2}% expected, {:+.2}",
+                    sub.substitute.id,
+                    ellipsize_if_long(&sub.substitute.name, DISCORD_PRETTY_WIDTH),
+                    sub.draws,
+                    sub.expected_probability * 100.0,
+                    sub.deviation(report.draws)
+                )
+            }),
+    );
+    ctx.say_long(&lines.join("\n"), true).await?;
+    Ok(())
+}
+
+/// Previews a substitute as if it were picked from a template without adding it
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::test_sub"
+)]
+pub async fn test_sub(ctx: Context<'_>, template: String, content: String) -> Result<(), Error> {
+    let (funboy, interpreter) = create_custom_generation(&ctx).await;
+    let preview = match funboy
+        .preview_substitute(&template, &content, interpreter)
+        .await
+    {
+        Ok(preview) => preview,
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    let generated = match preview.generated.as_deref() {
+        Some("") => "Generation was empty.".to_string(),
+        Some(generated) => fit_embed_field(generated),
+        None => "Generation failed.".to_string(),
+    };
+    let mut embed = CreateEmbed::new()
+        .title(format!("`{}`", preview.template))
+        .field("Substitute", fit_embed_field(&preview.substitute), false)
+        .field("Generated", generated, false);
+    if !preview.warnings.is_empty() {
+        let warnings: Vec<String> = preview
+            .warnings
+            .iter()
+            .map(|warning| format!("- {}", warning))
+            .collect();
+        embed = embed.field("Warnings", fit_embed_field(&warnings.join("\n")), false);
+    }
+
+    let buttons = CreateActionRow::Buttons(vec![
+        create_cancel_button(),
+        create_confirm_button().label("Add it"),
+    ]);
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .embed(embed)
+                .ephemeral(true)
+                .components(vec![buttons]),
+        )
+        .await?;
+    let interaction = reply
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .timeout(std::time::Duration::from_secs(60))
+        .await;
+
+    let Some(interaction) = interaction else {
+        reply
+            .edit(ctx, CreateReply::default().components(vec![]))
+            .await?;
+        return Ok(());
+    };
+    interaction
+        .create_response(ctx.http(), CreateInteractionResponse::Acknowledge)
+        .await?;
+    if interaction.data.custom_id != CONFIRM_BUTTON_ID {
+        return edit_interaction(ctx, &interaction, "Substitute not added.", true).await;
+    }
+
+    let message = match contributing_funboy(&ctx.data().funboy, ctx.author().id, ctx.guild_id())
+        .add_substitutes(&template, &[content.as_str()])
+        .await
+    {
+        Ok(receipt) if !receipt.updated.is_empty() => format!("Added to `{}`.", template),
+        Ok(receipt) => format!("Not added to `{}`: {}", template, receipt.ignored_summary()),
+        Err(e) => e.to_string(),
+    };
+    edit_interaction(ctx, &interaction, &message, true).await
+}
+
+/// Replaces a substitute in a template with another value
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::replace_sub"
+)]
+pub async fn replace_sub(
+    ctx: Context<'_>,
+    template: String,
+    from: String,
+    to: String,
+    replace_by_id: Option<bool>,
+) -> Result<(), Error> {
+    let replace_by_id = replace_by_id.unwrap_or(false);
+
+    let result = if replace_by_id {
+        let id = from.parse::<KeySize>();
+        match id {
+            Ok(id) => ctx.data().funboy.replace_substitute_by_id(id, &to).await,
+            Err(_) => {
+                ctx.say_ephemeral("Id must be a valid number.").await?;
+                return Ok(());
+            }
+        }
+    } else {
+        ctx.data()
+            .funboy
+            .replace_substitute(&template, &from, &to)
+            .await
+    };
+
+    match result {
+        Ok(template) => match template {
+            Some(_) => {
+                ctx.say_long(
+                    &format!(
+                        "Renamed substitute `{}` to `{}`",
+                        ellipsize_if_long(&from, 255),
+                        ellipsize_if_long(&to, 255)
+                    ),
+                    false,
+                )
+                .await?;
+            }
+            None => {
+                ctx.say_long(
+                    &format!(
+                        "Failed to rename substitute `{}`",
+                        ellipsize_if_long(&from, 255)
                     ),
                     true,
                 )
                 .await?;
             }
-            if result.ignored.len() > 0 {
-                edit_interaction(
+        },
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// Opens a prompt to edit a substitute in place
+#[poise::command(
+    slash_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::edit_sub"
+)]
+pub async fn edit_sub(ctx: Context<'_>, id: KeySize) -> Result<(), Error> {
+    let Context::Application(app_ctx) = ctx else {
+        return Ok(());
+    };
+
+    let sub = match ctx.data().funboy.get_substitute_by_id(id).await {
+        Ok(Some(sub)) => sub,
+        Ok(None) => {
+            ctx.say_ephemeral(&format!("No substitute with id {} exists", id))
+                .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    if !fits_in_modal_input(&sub.name) {
+        ctx.say_ephemeral(&format!(
+            "Substitute is too long to edit here, substitutes longer than {} characters can be changed with `/replace_sub` using `replace_by_id: true`",
+            MODAL_INPUT_LIMIT
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    app_ctx
+        .interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Modal(create_edit_substitute_modal(sub.id, &sub.name)),
+        )
+        .await?;
+    app_ctx
+        .has_sent_initial_response
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+
+    Ok(())
+}
+
+pub async fn on_edit_substitute_modal_submit(
+    ctx: &poise::serenity_prelude::Context,
+    edit_substitute_modal: EditSubstituteModal,
+    data: &Data,
+) -> Result<(), Error> {
+    let id = edit_substitute_modal.get_substitute_id();
+    let new = edit_substitute_modal.get_substitute();
+
+    let reply = match data.funboy.get_substitute_by_id(id).await {
+        Ok(Some(old)) => match data.funboy.replace_substitute_by_id(id, new).await {
+            Ok(Some(_)) => format!(
+                "Edited substitute {} ({})",
+                id,
+                TextDiff::new(&old.name, new)
+            ),
+            Ok(None) => format!("Substitute {} no longer exists", id),
+            Err(e) => e.to_string(),
+        },
+        Ok(None) => format!("Substitute {} no longer exists", id),
+        Err(e) => e.to_string(),
+    };
+
+    edit_substitute_modal
+        .get_interaction()
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(reply)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Adds a template to your favorites so it can be picked with `/quick_generate`
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::favorite_template"
+)]
+pub async fn favorite_template(ctx: Context<'_>, template: String) -> Result<(), Error> {
+    match ctx
+        .data()
+        .funboy
+        .add_favorite(ctx.author().id.get(), &template)
+        .await
+    {
+        Ok(Some(favorite)) => {
+            ctx.say_ephemeral(&tr(
+                ctx.locale(),
+                "favorite_added",
+                &[("template", favorite.template_name.as_str())],
+            ))
+            .await?;
+        }
+        Ok(None) => {
+            ctx.say_ephemeral(&tr(
+                ctx.locale(),
+                "favorite_already_added",
+                &[("template", template.as_str())],
+            ))
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes a template from your favorites
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::unfavorite_template"
+)]
+pub async fn unfavorite_template(ctx: Context<'_>, template: String) -> Result<(), Error> {
+    match ctx
+        .data()
+        .funboy
+        .remove_favorite(ctx.author().id.get(), &template)
+        .await
+    {
+        Ok(true) => {
+            ctx.say_ephemeral(&tr(
+                ctx.locale(),
+                "favorite_removed",
+                &[("template", template.as_str())],
+            ))
+            .await?;
+        }
+        Ok(false) => {
+            ctx.say_ephemeral(&tr(
+                ctx.locale(),
+                "favorite_missing",
+                &[("template", template.as_str())],
+            ))
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Picks one of your favorite templates to generate
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::quick_generate"
+)]
+pub async fn quick_generate(ctx: Context<'_>) -> Result<(), Error> {
+    let favorites = match ctx
+        .data()
+        .funboy
+        .list_favorites(ctx.author().id.get())
+        .await
+    {
+        Ok(favorites) => favorites,
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    if favorites.is_empty() {
+        ctx.say_ephemeral(&tr(ctx.locale(), "no_favorites", &[]))
+            .await?;
+        return Ok(());
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(tr(ctx.locale(), "pick_favorite", &[]))
+            .components(vec![create_favorites_menu(&favorites)])
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn on_quick_generate_select(
+    ctx: &poise::serenity_prelude::Context,
+    quick_generate_component: QuickGenerateComponent,
+    data: &Data,
+) -> Result<(), Error> {
+    let interaction = quick_generate_component.get_interaction();
+
+    let favorite = match quick_generate_component.get_template_id() {
+        Some(template_id) => data
+            .funboy
+            .list_favorites(interaction.user.id.get())
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .find(|favorite| favorite.template_id == template_id),
+        None => None,
+    };
+
+    let Some(favorite) = favorite else {
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(tr(
+                            Some(&interaction.locale),
+                            "favorite_no_longer_exists",
+                            &[],
+                        ))
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(tr(
+                        Some(&interaction.locale),
+                        "generating_template",
+                        &[("template", favorite.template_name.as_str())],
+                    ))
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    let permissions = CommandPermissions::resolve(
+        &data.funboy,
+        interaction.guild_id,
+        interaction.member.as_ref(),
+    )
+    .await;
+    let ictx = InterpreterContext::from_component(ctx, interaction, data);
+    let funboy = ictx.funboy.clone();
+    let output = funboy
+        .generate(
+            &format!("^{}", favorite.template_name),
+            create_interpreter(ictx, permissions),
+        )
+        .await;
+
+    let (messages, ephemeral) = match &output {
+        Ok(output) if output.is_effectively_empty => (
+            vec![no_visible_output_message(Some(&interaction.locale), output)],
+            true,
+        ),
+        Ok(output) if output.text.len() > MAX_MESSAGE_CHAIN_SIZE => {
+            (vec![WARN_MESSAGE_SIZE_EXCEEDED.to_string()], true)
+        }
+        Ok(output) => (
+            split_message(&output.text)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            false,
+        ),
+        Err(e) => {
+            interaction
+                .create_followup(
                     ctx,
-                    &interaction,
-                    &format!(
-                        "Templates `{}` do not exist.",
-                        ellipsize_if_long(&result.ignored_to_string(), 1000)
-                    ),
-                    true,
+                    CreateInteractionResponseFollowup::new()
+                        .content(e.to_string())
+                        .ephemeral(true),
                 )
                 .await?;
-            }
-            Ok(())
-        }
-        Err(e) => {
-            edit_interaction(ctx, &interaction, e.to_string().as_str(), true).await?;
-            Ok(())
+            return Ok(());
         }
+    };
+
+    for message in messages {
+        interaction
+            .create_followup(
+                ctx,
+                CreateInteractionResponseFollowup::new()
+                    .content(message)
+                    .ephemeral(ephemeral),
+            )
+            .await?;
     }
+
+    Ok(())
 }
 
-async fn delete_single_template(
-    ctx: Context<'_>,
-    template: &str,
-    interaction: &ComponentInteraction,
-) -> Result<(), Error> {
-    match ctx.data().funboy.delete_template(&template).await {
-        Ok(result) => match result {
-            Some(_) => {
-                edit_interaction(
-                    ctx,
-                    &interaction,
-                    &format!("Deleted template `{}`", ellipsize_if_long(template, 1000)),
-                    true,
-                )
-                .await?;
-                Ok(())
+async fn delete_multiple_templates_message(ctx: Context<'_>, templates: &[&str]) -> String {
+    match ctx.data().funboy.delete_templates(templates).await {
+        Ok(result) => {
+            let mut lines = Vec::new();
+            if result.updated.len() > 0 {
+                lines.push(format!(
+                    "Deleted templates `{}`",
+                    ellipsize_if_long(&result.updated_to_string(), 1000)
+                ));
             }
-            None => {
-                edit_interaction(
-                    ctx,
-                    &interaction,
-                    &format!(
-                        "Template `{}` does not exist.",
-                        ellipsize_if_long(template, 1000)
-                    ),
-                    true,
-                )
-                .await?;
-                Ok(())
+            if result.ignored.len() > 0 {
+                lines.push(format!(
+                    "Templates `{}` do not exist.",
+                    ellipsize_if_long(&result.ignored_to_string(), 1000)
+                ));
             }
-        },
-        Err(e) => {
-            edit_interaction(ctx, &interaction, e.to_string().as_str(), true).await?;
-            Ok(())
+            lines.join("\n")
         }
+        Err(e) => e.to_string(),
+    }
+}
+
+async fn delete_single_template_message(ctx: Context<'_>, template: &str) -> String {
+    match ctx.data().funboy.delete_template(template).await {
+        Ok(Some(_)) => format!("Deleted template `{}`", ellipsize_if_long(template, 1000)),
+        Ok(None) => format!(
+            "Template `{}` does not exist.",
+            ellipsize_if_long(template, 1000)
+        ),
+        Err(e) => e.to_string(),
     }
 }
 
 /// Deletes a template or templates
-///
-/// Template names are space-separated.
-///
-/// **Example:** `/delete_templates noun verb adjective` — deletes all three templates
-///
-/// This action cannot be undone.
-#[poise::command(slash_command, prefix_command, category = "Templates")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::delete_templates"
+)]
 pub async fn delete_templates(ctx: Context<'_>, names: String) -> Result<(), Error> {
     let templates = split_by_whitespace_unless_quoted(&names);
 
@@ -500,76 +2021,298 @@ pub async fn delete_templates(ctx: Context<'_>, names: String) -> Result<(), Err
         if templates.len() > 1 { "their" } else { "it's" }
     );
 
-    match create_confirmation_interaction(ctx, &interaction_text, 30).await? {
-        Some(interaction) => match interaction.data.custom_id.as_str() {
-            CANCEL_BUTTON_ID => {
-                interaction
-                    .create_response(
-                        ctx.http(),
-                        serenity::all::CreateInteractionResponse::Acknowledge,
-                    )
-                    .await?;
+    let Some(confirmation) = ConfirmedAction::new(ctx, interaction_text).ask().await? else {
+        return Ok(());
+    };
+    let message = if !confirmation.confirmed {
+        "Command to remove templates canceled.".to_string()
+    } else if templates.len() > 1 {
+        delete_multiple_templates_message(ctx, &templates).await
+    } else {
+        delete_single_template_message(ctx, &names).await
+    };
+    confirmation.report(&message).await
+}
 
-                edit_interaction(
-                    ctx,
-                    &interaction,
-                    "Command to remove templates canceled.",
-                    true,
-                )
-                .await?;
+/// Deletes or archives every template matching a filter
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Templates",
+    help_text_fn = "crate::command_help::delete_templates_matching"
+)]
+pub async fn delete_templates_matching(
+    ctx: Context<'_>,
+    name: Option<String>,
+    min_subs: Option<i64>,
+    max_subs: Option<i64>,
+    older_than_days: Option<u64>,
+    archived: Option<bool>,
+    archive: Option<bool>,
+) -> Result<(), Error> {
+    let filter = TemplateFilter {
+        name_glob: name,
+        min_substitutes: min_subs,
+        max_substitutes: max_subs,
+        created_before: older_than_days.map(TemplateFilter::days_ago),
+        archived,
+    };
+    let archive = archive.unwrap_or(false);
+    let (verb, past) = if archive {
+        ("archive", "Archived")
+    } else {
+        ("delete", "Deleted")
+    };
 
-                Ok(())
-            }
-            CONFIRM_BUTTON_ID => {
-                interaction
-                    .create_response(
-                        ctx.http(),
-                        serenity::all::CreateInteractionResponse::Acknowledge,
-                    )
-                    .await?;
-                if templates.len() > 1 {
-                    delete_multiple_templates(ctx, &templates, &interaction).await?;
-                } else {
-                    delete_single_template(ctx, &names, &interaction).await?;
-                };
+    let funboy = &ctx.data().funboy;
+    let run = async |token: Option<&str>| {
+        if archive {
+            funboy.archive_templates_matching(&filter, token).await
+        } else {
+            funboy.delete_templates_matching(&filter, token).await
+        }
+    };
+
+    let preview = match run(None).await {
+        Ok(BulkOutcome::Preview(preview)) => preview,
+        Ok(BulkOutcome::Applied(_)) => {
+            panic!("Bulk template operation applied without a token.")
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    if preview.templates.is_empty() {
+        ctx.say_ephemeral("No templates match the filter.").await?;
+        return Ok(());
+    }
+
+    let names: Vec<&str> = preview
+        .templates
+        .iter()
+        .map(|template| template.name.as_str())
+        .collect();
+    let interaction_text = format!(
+        "Are you sure you want to {} {} templates?\n`{}`",
+        verb,
+        names.len(),
+        ellipsize_if_long(&names.join(" "), 1000)
+    );
 
-                Ok(())
+    let Some(confirmation) = ConfirmedAction::new(ctx, interaction_text).ask().await? else {
+        return Ok(());
+    };
+    let message = if !confirmation.confirmed {
+        format!("Command to {} templates canceled.", verb)
+    } else {
+        match run(Some(&preview.token)).await {
+            Ok(BulkOutcome::Applied(templates)) => {
+                format!("{} {} templates.", past, templates.len())
             }
-            _ => {
-                panic!("Incorrect id for remove template confirmation interaction.")
+            Ok(BulkOutcome::Preview(_)) => {
+                panic!("Bulk template operation previewed with a token.")
             }
-        },
-        None => {
-            ctx.say_ephemeral("Timeout: Command to remove template canceled.")
-                .await?;
-            Ok(())
+            Err(e) => e.to_string(),
         }
-    }
+    };
+    confirmation.report(&message).await
 }
 
 /// Renames a template
-///
-/// **Example:** `/rename_template noun thing` — renames the `noun` template to `thing`
-///
-/// All substitutes under the previous name will now be under the new name
-#[poise::command(slash_command, prefix_command, category = "Templates")]
-pub async fn rename_template(ctx: Context<'_>, from: String, to: String) -> Result<(), Error> {
-    match ctx.data().funboy.rename_template(&from, &to).await {
-        Ok(template) => match template {
-            Some(_) => {
-                ctx.say(&format!("Renamed template `{}` to `{}`", from, to))
-                    .await?;
-            }
-            None => {
-                ctx.say(&format!("Failed to rename template `{}`", from,))
-                    .await?;
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::rename_template"
+)]
+pub async fn rename_template(
+    ctx: Context<'_>,
+    from: String,
+    to: String,
+    preview: Option<bool>,
+) -> Result<(), Error> {
+    if !preview.unwrap_or(false) {
+        match ctx.data().funboy.rename_template(&from, &to).await {
+            Ok(template) => match template {
+                Some(_) => {
+                    ctx.say(&format!("Renamed template `{}` to `{}`", from, to))
+                        .await?;
+                }
+                None => {
+                    ctx.say(&format!("Failed to rename template `{}`", from,))
+                        .await?;
+                }
+            },
+            Err(e) => {
+                ctx.say_ephemeral(&e.to_string()).await?;
             }
-        },
+        };
+        return Ok(());
+    }
+
+    let preview = match ctx.data().funboy.preview_rename(&from, &to).await {
+        Ok(preview) => preview,
         Err(e) => {
             ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
         }
     };
-    Ok(())
+
+    if preview.changes.is_empty() {
+        ctx.say_ephemeral("No substitutes reference this template.")
+            .await?;
+    } else {
+        let lines = format_rename_preview(&preview);
+        ctx.say_list(&lines.to_ref(), true, None).await?;
+    }
+
+    let interaction_text = format!("Rename template `{}` to `{}`?", from, to);
+    let confirmation = ConfirmedAction::new(ctx, interaction_text)
+        .timeout(std::time::Duration::from_secs(60))
+        .ask()
+        .await?;
+    let Some(confirmation) = confirmation else {
+        return Ok(());
+    };
+    let message = if confirmation.confirmed {
+        rename_template_message(ctx, &from, &to).await
+    } else {
+        "Command to rename template canceled.".to_string()
+    };
+    confirmation.report(&message).await
+}
+
+async fn rename_template_message(ctx: Context<'_>, from: &str, to: &str) -> String {
+    match ctx.data().funboy.rename_template(from, to).await {
+        Ok(Some(_)) => format!("Renamed template `{}` to `{}`", from, to),
+        Ok(None) => format!("Failed to rename template `{}`", from),
+        Err(e) => e.to_string(),
+    }
+}
+
+fn format_rename_preview(preview: &RenamePreview) -> Vec<String> {
+    let mut lines = vec![format!(
+        "Renaming `{}` to `{}` rewrites {} substitutes\n",
+        preview.from,
+        preview.to,
+        preview.changes.len()
+    )];
+
+    for (delimiter, changes) in preview.by_delimiter() {
+        lines.push(format!("**`{}` references**\n", delimiter.to_char()));
+        for change in changes {
+            lines.push(format!(
+                "`{}` {}: {} → {}\n",
+                change.template,
+                change.substitute_id,
+                ellipsize_if_long(&change.before, DISCORD_PRETTY_WIDTH),
+                ellipsize_if_long(&change.after, DISCORD_PRETTY_WIDTH)
+            ));
+        }
+    }
+
+    lines
+}
+
+/// Rewrites references to a template in substitutes without renaming any template
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Templates",
+    help_text_fn = "crate::command_help::rewrite_references"
+)]
+pub async fn rewrite_references(
+    ctx: Context<'_>,
+    from: String,
+    to: String,
+    #[description = "Only rewrite substitutes of this template"] in_template: Option<String>,
+) -> Result<(), Error> {
+    let scope = match in_template {
+        Some(template) => RewriteScope::Template(template),
+        None => RewriteScope::All,
+    };
+
+    let preview = match ctx
+        .data()
+        .funboy
+        .preview_rewrite_references(&from, &to, scope.clone())
+        .await
+    {
+        Ok(preview) => preview,
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    if preview.substitutes.is_empty() {
+        ctx.say_ephemeral(&format!("No substitutes reference `{}`.", from))
+            .await?;
+        return Ok(());
+    }
+    let lines = format_rewrite_report(&preview);
+    ctx.say_list(&lines.to_ref(), true, None).await?;
+
+    let interaction_text = format!("Rewrite references to `{}` as `{}`?", from, to);
+    let confirmation = ConfirmedAction::new(ctx, interaction_text)
+        .timeout(std::time::Duration::from_secs(60))
+        .ask()
+        .await?;
+    let Some(confirmation) = confirmation else {
+        return Ok(());
+    };
+    let message = if confirmation.confirmed {
+        match ctx
+            .data()
+            .funboy
+            .rewrite_references(&from, &to, scope)
+            .await
+        {
+            Ok(report) => format!(
+                "Rewrote references to `{}` in {} substitutes",
+                from,
+                report.substitutes.len()
+            ),
+            Err(e) => e.to_string(),
+        }
+    } else {
+        "Command to rewrite references canceled.".to_string()
+    };
+    confirmation.report(&message).await
+}
+
+fn format_rewrite_report(report: &RewriteReport) -> Vec<String> {
+    let scope = match &report.scope {
+        RewriteScope::Template(template) => format!(" in `{}`", template),
+        RewriteScope::All => String::new(),
+    };
+    let mut lines = vec![format!(
+        "Rewriting `{}` as `{}`{} changes {} substitutes\n",
+        report.from,
+        report.to,
+        scope,
+        report.substitutes.len()
+    )];
+
+    for sub in &report.substitutes {
+        lines.push(format!(
+            "`{}` {} ({} → {} references): {} → {}\n",
+            sub.template,
+            sub.substitute_id,
+            sub.old_references,
+            sub.new_references,
+            ellipsize_if_long(&sub.before, DISCORD_PRETTY_WIDTH),
+            ellipsize_if_long(&sub.after, DISCORD_PRETTY_WIDTH)
+        ));
+    }
+
+    lines
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
@@ -580,27 +2323,89 @@ pub enum ListStyle {
     File,
 }
 
+/// Which kind of substitutes to list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum SubKindChoice {
+    Prose,
+    Code,
+    Mixed,
+}
+
+impl From<SubKindChoice> for SubKind {
+    fn from(value: SubKindChoice) -> Self {
+        match value {
+            SubKindChoice::Prose => SubKind::Prose,
+            SubKindChoice::Code => SubKind::Code,
+            SubKindChoice::Mixed => SubKind::Mixed,
+        }
+    }
+}
+
+/// Find the substitutes that could have generated some text
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    category = "Templates",
+    help_text_fn = "crate::command_help::find_source"
+)]
+pub async fn find_source(
+    ctx: Context<'_>,
+    #[description = "Generated text to trace back"] text: String,
+    #[description = "Most candidates to show, 10 by default"]
+    #[min = 1]
+    #[max = 25]
+    limit: Option<usize>,
+) -> Result<(), Error> {
+    let limit = limit.unwrap_or(10);
+    match ctx.data().funboy.find_source_of(&text, limit).await {
+        Ok(sources) if sources.is_empty() => {
+            ctx.say_ephemeral("No substitute could have generated that text.")
+                .await?;
+        }
+        Ok(sources) => {
+            let mut lines: Vec<String> = sources
+                .iter()
+                .map(|(template, sub, quality)| {
+                    format!(
+                        "`{}` {}: {} ({})",
+                        sub.id,
+                        template.name,
+                        ellipsize_if_long(&sub.name, DISCORD_PRETTY_WIDTH),
+                        quality
+                    )
+                })
+                .collect();
+            let (template, sub, _) = &sources[0];
+            lines.push(format!(
+                "\nDelete a substitute with `/delete_subs template: {} subs: {} delete_by_id: True`",
+                template.name, sub.id
+            ));
+            ctx.say_long(&lines.join("\n"), true).await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Appended to substitutes that generation skips when listing them
+const DISABLED_MARKER: &str = " (disabled)";
+
 /// Lists all substitutes in a template
-///
-/// **Example:** `/list_subs noun` — displays all substitutes for the `noun` template
-///
-/// ## Search
-/// Use `search_term` to filter results.
-///
-/// **Example:** `/list_subs noun search_term: dog` — shows only substitutes containing "dog"
-///
-/// ## List styles
-/// - `Default` — standard comma separated format
-/// - `Numeric` — numbered list
-/// - `ID` — shows substitute IDs
-/// - `File` — uploads text file containing substitutes and their IDs
-///
-/// **Example:** `/list_subs noun list_style: ID` — displays substitutes with their IDs
-#[poise::command(slash_command, prefix_command, category = "Templates")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::list_subs"
+)]
 pub async fn list_subs(
     ctx: Context<'_>,
     template: String,
     search_term: Option<String>,
+    #[description = "Only list substitutes of this kind"] kind: Option<SubKindChoice>,
     list_style: Option<ListStyle>,
 ) -> Result<(), Error> {
     let result = ctx
@@ -609,6 +2414,7 @@ pub async fn list_subs(
         .get_substitutes(
             &template,
             search_term.as_deref(),
+            kind.map(SubKind::from),
             OrderBy::NameIgnoreCase(SortOrder::Ascending),
             Limit::Count(1000),
         )
@@ -617,8 +2423,12 @@ pub async fn list_subs(
     match result {
         Ok(subs) => {
             if subs.len() == 0 {
-                ctx.say_ephemeral(&format!("No substitutes found in `{}`", template))
-                    .await?;
+                ctx.say_ephemeral(&tr(
+                    ctx.locale(),
+                    "no_substitutes_in_template",
+                    &[("template", template.as_str())],
+                ))
+                .await?;
                 return Ok(());
             }
 
@@ -627,8 +2437,10 @@ pub async fn list_subs(
                     subs.iter()
                         .map(|sub| {
                             format!(
-                                "\nID: {}\n{}{}\n",
+                                "\nID: {} ({}){}\n{}{}\n",
                                 sub.id,
+                                sub.kind,
+                                if sub.enabled { "" } else { DISABLED_MARKER },
                                 if sub.name.len() > DISCORD_PRETTY_WIDTH {
                                     "\n"
                                 } else {
@@ -639,7 +2451,15 @@ pub async fn list_subs(
                         })
                         .collect()
                 } else {
-                    subs.iter().map(|sub| sub.name.clone()).collect()
+                    subs.iter()
+                        .map(|sub| {
+                            if sub.enabled {
+                                sub.name.clone()
+                            } else {
+                                format!("{}{}", sub.name, DISABLED_MARKER)
+                            }
+                        })
+                        .collect()
                 };
 
             let subs = subs.to_ref();
@@ -695,23 +2515,224 @@ pub async fn list_subs(
     Ok(())
 }
 
+/// Lists the names of your session variables
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::session_vars"
+)]
+pub async fn session_vars(ctx: Context<'_>) -> Result<(), Error> {
+    let vars = ctx.data().session_vars.get(ctx.author().id).await;
+    if vars.is_empty() {
+        ctx.say_ephemeral("You have no session variables.").await?;
+        return Ok(());
+    }
+
+    let names: Vec<String> = vars.into_iter().map(|(name, _)| name).collect();
+    ctx.say_ephemeral(&format!("Session variables: {}", names.join(", ")))
+        .await?;
+    Ok(())
+}
+
+/// Deletes all of your session variables
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::clear_session"
+)]
+pub async fn clear_session(ctx: Context<'_>) -> Result<(), Error> {
+    let cleared = ctx.data().session_vars.clear(ctx.author().id).await;
+    ctx.say_ephemeral(&format!("Cleared {} session variable(s).", cleared))
+        .await?;
+    Ok(())
+}
+
+const LEADERBOARD_SIZE: usize = 10;
+const DEFAULT_LEADERBOARD_DAYS: u32 = 7;
+
+/// Shows the templates used most in this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::template_leaderboard"
+)]
+pub async fn template_leaderboard(
+    ctx: Context<'_>,
+    #[description = "How many days back to count, defaults to 7"]
+    #[min = 1]
+    #[max = 365]
+    days: Option<u32>,
+) -> Result<(), Error> {
+    let days = days.unwrap_or(DEFAULT_LEADERBOARD_DAYS).max(1);
+    let leaderboard = ctx
+        .data()
+        .funboy
+        .get_template_leaderboard(
+            ctx.guild_id().map(|guild_id| guild_id.get()),
+            days as u64,
+            LEADERBOARD_SIZE,
+        )
+        .await;
+
+    match leaderboard {
+        Ok(leaderboard) if leaderboard.is_empty() => {
+            ctx.say_ephemeral(&format!(
+                "No templates were used in the last {} {}.",
+                days,
+                plural(days as f64, "day", None)
+            ))
+            .await?;
+        }
+        Ok(leaderboard) => {
+            let entries: Vec<String> = leaderboard
+                .iter()
+                .map(|usage| {
+                    format!(
+                        "`{}` {} {}",
+                        usage.name,
+                        usage.uses,
+                        plural(usage.uses as f64, "use", None)
+                    )
+                })
+                .collect();
+            ctx.say_list(
+                &entries.to_ref(),
+                true,
+                Some(Box::new(format_as_numeric_list)),
+            )
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows who added the most substitutes in this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::contributors"
+)]
+pub async fn contributors(
+    ctx: Context<'_>,
+    #[description = "Only count substitutes of this template"] template: Option<String>,
+    #[description = "Only count substitutes added in this many days, defaults to all time"]
+    #[min = 1]
+    #[max = 3650]
+    days: Option<u32>,
+) -> Result<(), Error> {
+    let contributors = ctx
+        .data()
+        .funboy
+        .get_contribution_stats(
+            ctx.guild_id().map(|guild_id| guild_id.get()),
+            template.as_deref(),
+            days.map(|days| days as u64),
+            LEADERBOARD_SIZE,
+        )
+        .await;
+
+    match contributors {
+        Ok(contributors) if contributors.is_empty() => {
+            ctx.say_ephemeral("No contributions were recorded yet.")
+                .await?;
+        }
+        Ok(contributors) => {
+            let user_ids: Vec<KeySize> = contributors
+                .iter()
+                .map(|contribution| contribution.user_id)
+                .collect();
+            let names = contributor_names(ctx.serenity_context(), ctx.guild_id(), &user_ids).await;
+            let entries: Vec<String> = contributors
+                .iter()
+                .zip(names)
+                .map(|(contribution, name)| {
+                    format!(
+                        "{} {} {}",
+                        name,
+                        contribution.substitutes,
+                        plural(contribution.substitutes as f64, "substitute", None)
+                    )
+                })
+                .collect();
+            ctx.say_list(
+                &entries.to_ref(),
+                true,
+                Some(Box::new(format_as_numeric_list)),
+            )
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows how many substitutes you added to each template
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::my_contributions"
+)]
+pub async fn my_contributions(ctx: Context<'_>) -> Result<(), Error> {
+    let contributions = ctx
+        .data()
+        .funboy
+        .get_user_contributions(
+            ctx.author().id.get(),
+            ctx.guild_id().map(|guild_id| guild_id.get()),
+        )
+        .await;
+
+    match contributions {
+        Ok(contributions) if contributions.is_empty() => {
+            ctx.say_ephemeral("You haven't added any substitutes yet.")
+                .await?;
+        }
+        Ok(contributions) => {
+            let entries: Vec<String> = contributions
+                .iter()
+                .map(|contribution| {
+                    format!(
+                        "`{}` {} {}",
+                        contribution.name,
+                        contribution.substitutes,
+                        plural(contribution.substitutes as f64, "substitute", None)
+                    )
+                })
+                .collect();
+            ctx.say_list(
+                &entries.to_ref(),
+                true,
+                Some(Box::new(format_as_numeric_list)),
+            )
+            .await?;
+        }
+        Err(e) => {
+            ctx.say_ephemeral(&e.to_string()).await?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Lists all templates
-///
-/// **Example:** `/list_templates` — displays all templates
-///
-/// ## Search
-/// Use `search_term` to filter results.
-///
-/// **Example:** `/list_templates search_term: noun` — shows only templates containing "noun"
-///
-/// ## List styles
-/// - `Default` — standard format
-/// - `Numeric` — numbered list
-/// - `ID` — shows template IDs
-/// - `File` — uploads text file containing substitutes and their IDs
-///
-/// **Example:** `/list_templates list_style: ID` — displays templates with their IDs
-#[poise::command(slash_command, prefix_command, category = "Templates")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Templates",
+    help_text_fn = "crate::command_help::list_templates"
+)]
 pub async fn list_templates(
     ctx: Context<'_>,
     search_term: Option<String>,
@@ -729,7 +2750,8 @@ pub async fn list_templates(
     match result {
         Ok(templates) => {
             if templates.len() == 0 {
-                ctx.say_ephemeral(&format!("No templates found.")).await?;
+                ctx.say_ephemeral(&tr(ctx.locale(), "no_templates_found", &[]))
+                    .await?;
                 return Ok(());
             }
 
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use crate::{
     Context, Error,
+    channel_resolver::{fetch_channel_entries, resolve_channel},
     io_format::{
         context_extension::ContextExtension,
         discord_message_format::{DISCORD_CHARACTER_LIMIT, extract_image_urls},
@@ -20,6 +21,9 @@ struct CommandInfo<'a> {
     pub help_text: &'a Option<String>,
 }
 
+/// Shown in `/help` after commands with extended help
+pub const EXTENDED_HELP_MARKER: &str = "📖";
+
 static HELP_MESSAGES: OnceCell<Vec<String>> = OnceCell::const_new();
 static HELP_MESSAGES_WITH_DESCRIPTIONS: OnceCell<Vec<String>> = OnceCell::const_new();
 
@@ -55,7 +59,11 @@ async fn generate_help_messages<'a>(ctx: Context<'_>, show_descriptions: bool) -
         help_message.push_str(&format!("**{}**\n", key));
 
         for value in command_map.get(key).unwrap() {
-            help_message.push_str(&format!("- /{}\n", value.name));
+            if has_extended_help(value.help_text) {
+                help_message.push_str(&format!("- /{} {}\n", value.name, EXTENDED_HELP_MARKER));
+            } else {
+                help_message.push_str(&format!("- /{}\n", value.name));
+            }
 
             if show_descriptions {
                 if let Some(description) = value.description.as_ref() {
@@ -94,7 +102,12 @@ async fn generate_help_messages<'a>(ctx: Context<'_>, show_descriptions: bool) -
 }
 
 /// Lists out all available commands optionally showing their descriptions
-#[poise::command(slash_command, prefix_command, category = "Utility")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Utility",
+    help_text_fn = "crate::command_help::help"
+)]
 pub async fn help(ctx: Context<'_>, show_descriptions: Option<bool>) -> Result<(), Error> {
     let show_descriptions = show_descriptions.unwrap_or(false);
     let help_messages = if show_descriptions {
@@ -111,32 +124,46 @@ pub async fn help(ctx: Context<'_>, show_descriptions: Option<bool>) -> Result<(
         ctx.say_ephemeral(&message).await?;
     }
 
-    ctx.say_ephemeral("Use `/help_command` for more detailed information on a command")
-        .await?;
+    ctx.say_ephemeral(&format!(
+        "Use `/help_command` for more detailed information on commands marked with {}",
+        EXTENDED_HELP_MARKER
+    ))
+    .await?;
 
     Ok(())
 }
 
+fn has_extended_help(help_text: &Option<String>) -> bool {
+    help_text.as_ref().is_some_and(|text| !text.is_empty())
+}
+
+/// The message `/help_command` sends for a command with extended help
+fn format_command_help(name: &str, description: Option<&str>, help_text: &str) -> String {
+    format!(
+        "# {}\n{}\n{}",
+        name,
+        description.unwrap_or(&format!("No description available for {}.", name)),
+        help_text
+    )
+}
+
 /// Get detailed information on an individual command
-#[poise::command(slash_command, prefix_command, category = "Utility")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Utility",
+    help_text_fn = "crate::command_help::help_command"
+)]
 pub async fn help_command(ctx: Context<'_>, command: String) -> Result<(), Error> {
     let commands = &ctx.framework().options().commands;
     match commands.iter().find(|c| c.name == command) {
         Some(command) => {
-            if command
-                .help_text
-                .as_ref()
-                .is_some_and(|text| !text.is_empty())
-            {
+            if has_extended_help(&command.help_text) {
                 ctx.say_long(
-                    &format!(
-                        "# {}\n{}\n{}",
-                        command.name,
-                        command
-                            .description
-                            .as_ref()
-                            .unwrap_or(&format!("No description available for {}.", command.name)),
-                        command.help_text.as_ref().unwrap()
+                    &format_command_help(
+                        &command.name,
+                        command.description.as_deref(),
+                        command.help_text.as_ref().unwrap(),
                     ),
                     true,
                 )
@@ -168,81 +195,86 @@ pub async fn register(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Moves pinned bot messages to the selected channel and creates an embed for them
-///
-/// Example usage: **/move_bot_pins** to_channel: **my-channel**
-#[poise::command(slash_command, prefix_command, category = "Utility")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Utility",
+    help_text_fn = "crate::command_help::move_bot_pins"
+)]
 pub async fn move_bot_pins(ctx: Context<'_>, to_channel: String) -> Result<(), Error> {
-    if let Some(to_id) = get_channel_id(ctx, &to_channel).await? {
-        let pins = ctx.channel_id().pins(ctx.http()).await?;
-        for pin in pins {
-            let bot_user = ctx.http().get_current_user().await?;
-            if pin.author.name == bot_user.name {
-                let mut embed = CreateEmbed::new()
-                    .title(&pin.author.name)
-                    .description(&pin.content)
-                    .url(pin.link());
-
-                let image_urls = extract_image_urls(&pin.content);
-
-                if image_urls.len() == 1 {
-                    embed = embed.image(image_urls[0]);
-                    ctx.defer().await?;
-                    to_id
-                        .send_message(&ctx.http(), CreateMessage::new().embed(embed))
-                        .await?;
-                } else {
+    let to_id = match get_channel_id(ctx, &to_channel).await? {
+        Ok(to_id) => to_id,
+        Err(e) => {
+            ctx.say(format!("Error: {}.", e)).await?;
+            return Ok(());
+        }
+    };
+    let pins = ctx.channel_id().pins(ctx.http()).await?;
+    for pin in pins {
+        let bot_user = ctx.http().get_current_user().await?;
+        if pin.author.name == bot_user.name {
+            let mut embed = CreateEmbed::new()
+                .title(&pin.author.name)
+                .description(&pin.content)
+                .url(pin.link());
+
+            let image_urls = extract_image_urls(&pin.content);
+
+            if image_urls.len() == 1 {
+                embed = embed.image(image_urls[0]);
+                ctx.defer().await?;
+                to_id
+                    .send_message(&ctx.http(), CreateMessage::new().embed(embed))
+                    .await?;
+            } else {
+                ctx.defer().await?;
+                to_id
+                    .send_message(&ctx.http(), CreateMessage::new().embed(embed))
+                    .await?;
+
+                for image_url in extract_image_urls(&pin.content) {
                     ctx.defer().await?;
                     to_id
-                        .send_message(&ctx.http(), CreateMessage::new().embed(embed))
+                        .send_message(
+                            &ctx.http(),
+                            CreateMessage::new().embed(CreateEmbed::new().image(image_url)),
+                        )
                         .await?;
-
-                    for image_url in extract_image_urls(&pin.content) {
-                        ctx.defer().await?;
-                        to_id
-                            .send_message(
-                                &ctx.http(),
-                                CreateMessage::new().embed(CreateEmbed::new().image(image_url)),
-                            )
-                            .await?;
-                    }
                 }
-
-                pin.unpin(ctx.http()).await?;
             }
+
+            pin.unpin(ctx.http()).await?;
         }
-        ctx.defer().await?;
-        ctx.send(CreateReply::default().content(format!(
-            "Succesfully moved pins to channel **{}**.",
-            to_channel
-        )))
-        .await?;
-    } else {
-        ctx.say(format!(
-            "Error: Could not find channel with name **{}**.",
-            to_channel
-        ))
-        .await?;
     }
+    ctx.defer().await?;
+    ctx.send(CreateReply::default().content(format!(
+        "Succesfully moved pins to channel **{}**.",
+        to_channel
+    )))
+    .await?;
     Ok(())
 }
 
-async fn get_channel_id(ctx: Context<'_>, channel_name: &str) -> Result<Option<ChannelId>, Error> {
+async fn get_channel_id(
+    ctx: Context<'_>,
+    channel_name: &str,
+) -> Result<Result<ChannelId, String>, Error> {
     match ctx.guild_id() {
         Some(guild_id) => {
-            let guild = guild_id.to_partial_guild(ctx).await?;
-            for (channel_id, channel) in guild.channels(ctx).await?.iter() {
-                if channel.name() == channel_name {
-                    return Ok(Some(*channel_id));
-                }
-            }
-            Ok(None)
+            let channels = fetch_channel_entries(ctx.http(), guild_id).await?;
+            Ok(resolve_channel(channel_name, &channels).map(|channel| channel.id))
         }
-        None => Ok(None),
+        None => Ok(Err("channels can only be found in a server".to_string())),
     }
 }
 
 /// Display the age of a users account.
-#[poise::command(slash_command, prefix_command, category = "Utility")]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Utility",
+    help_text_fn = "crate::command_help::age"
+)]
 pub async fn age(
     ctx: Context<'_>,
     #[description = "Selected user"] user: Option<serenity::User>,
@@ -252,3 +284,95 @@ pub async fn age(
     ctx.say(response).await?;
     Ok(())
 }
+
+/// Show the version of each cached template page
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Utility",
+    help_text_fn = "crate::command_help::cache_debug"
+)]
+pub async fn cache_debug(ctx: Context<'_>) -> Result<(), Error> {
+    let templates = ctx.data().funboy.cache_debug();
+    if templates.is_empty() {
+        ctx.say_ephemeral("No template has been cached or changed since the cache was cleared.")
+            .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = templates
+        .iter()
+        .map(|cached| match cached.page_version {
+            Some(page_version) => format!(
+                "`{}` v{}: page v{} with {} substitutes{}",
+                cached.template,
+                cached.version,
+                page_version,
+                cached.page_len,
+                if cached.is_stale() { " (stale)" } else { "" }
+            ),
+            None => format!("`{}` v{}: not cached", cached.template, cached.version),
+        })
+        .collect();
+    ctx.say_long(&lines.join("\n"), true).await?;
+    Ok(())
+}
+
+/// Run the self test generations and show which cases failed
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Utility",
+    help_text_fn = "crate::command_help::self_test"
+)]
+pub async fn self_test(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let report = ctx
+        .data()
+        .funboy
+        .self_test(&ctx.data().self_test_spec)
+        .await;
+    ctx.say_long(&format!("```\n{}\n```", report.summary()), true)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod utility_test {
+    use crate::{
+        command_help,
+        io_format::discord_message_format::{DISCORD_CHARACTER_LIMIT, split_message},
+    };
+
+    use super::*;
+
+    #[test]
+    fn long_help_text_is_split_for_say_long() {
+        let help_text = command_help::generate().repeat(3);
+        assert!(help_text.len() > DISCORD_CHARACTER_LIMIT);
+
+        let message = format_command_help("generate", Some("Generates text"), &help_text);
+        let messages = split_message(&message);
+
+        assert!(messages.len() > 1);
+        assert!(messages[0].starts_with("# generate\nGenerates text\n## Templates"));
+        assert!(
+            messages
+                .iter()
+                .all(|message| message.len() <= DISCORD_CHARACTER_LIMIT)
+        );
+        assert_eq!(messages.concat(), message);
+    }
+
+    #[test]
+    fn missing_description_is_noted() {
+        assert_eq!(
+            format_command_help("age", None, "help"),
+            "# age\nNo description available for age.\nhelp"
+        );
+    }
+}
@@ -1,32 +1,214 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use poise::CreateReply;
-use serenity::all::{ComponentInteraction, CreateActionRow, CreateButton, EditInteractionResponse};
+use serenity::all::{
+    ActionRowComponent, ChannelId, ComponentInteraction, ComponentInteractionDataKind,
+    CreateActionRow, CreateButton, CreateInputText, CreateInteractionResponse, CreateModal,
+    CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse,
+    HttpError, InputTextStyle, Mentionable, MessageId, ModalInteraction, Timestamp,
+};
 use uuid::Uuid;
 
-use crate::{Context, Error};
+use funboy_core::template_database::{Favorite, KeySize};
+
+use crate::{
+    Context, Error,
+    io_format::{
+        context_extension::ContextExtension, discord_message_format::truncate_on_char_boundary,
+    },
+};
 
 pub const TRACK_BUTTON_ID: &str = "track";
 pub const CANCEL_BUTTON_ID: &str = "cancel";
 pub const CONFIRM_BUTTON_ID: &str = "confirm";
+pub const PREVIOUS_PAGE_BUTTON_ID: &str = "previous_page";
+pub const NEXT_PAGE_BUTTON_ID: &str = "next_page";
+pub const ADD_SUBSTITUTE_MODAL_ID: &str = "add_sub";
+pub const TEMPLATE_INPUT_ID: &str = "template";
+pub const EDIT_SUBSTITUTE_MODAL_ID: &str = "edit_sub";
+pub const SUBSTITUTE_INPUT_ID: &str = "substitute";
+pub const QUICK_GENERATE_MENU_ID: &str = "quick_generate";
+pub const ASK_CHOICE_BUTTON_ID: &str = "ask_choice";
+pub const STOP_CHOICE_ID: &str = "stop";
+
+/// The most characters Discord allows in a modal text input
+pub const MODAL_INPUT_LIMIT: usize = 4000;
 
 pub enum CustomComponent {
     TrackComponent,
+    QuickGenerate,
     None,
 }
 
 impl CustomComponent {
     pub fn from(component_interaction: &ComponentInteraction) -> Self {
-        if component_interaction
-            .data
-            .custom_id
-            .starts_with(TRACK_BUTTON_ID)
-        {
+        let custom_id = &component_interaction.data.custom_id;
+        if custom_id.starts_with(TRACK_BUTTON_ID) {
             return CustomComponent::TrackComponent;
+        } else if custom_id == QUICK_GENERATE_MENU_ID {
+            return CustomComponent::QuickGenerate;
         } else {
             return CustomComponent::None;
         }
     }
 }
 
+pub enum CustomModal {
+    AddSubstitute,
+    EditSubstitute,
+    None,
+}
+
+impl CustomModal {
+    pub fn from(modal_interaction: &ModalInteraction) -> Self {
+        let custom_id = &modal_interaction.data.custom_id;
+        if custom_id.starts_with(ADD_SUBSTITUTE_MODAL_ID) {
+            return CustomModal::AddSubstitute;
+        } else if custom_id.starts_with(EDIT_SUBSTITUTE_MODAL_ID) {
+            return CustomModal::EditSubstitute;
+        } else {
+            return CustomModal::None;
+        }
+    }
+}
+
+/// A submitted modal asking which template a message should be added to
+pub struct AddSubstituteModal {
+    interaction: ModalInteraction,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    template: String,
+}
+
+impl AddSubstituteModal {
+    pub fn new(modal_interaction: ModalInteraction) -> Self {
+        let mut ids = modal_interaction.data.custom_id.split_whitespace().skip(1);
+
+        let channel_id = ids
+            .next()
+            .and_then(|id| id.parse::<u64>().ok())
+            .map(ChannelId::new)
+            .expect("Add substitute modal id should contain channel id.");
+
+        let message_id = ids
+            .next()
+            .and_then(|id| id.parse::<u64>().ok())
+            .map(MessageId::new)
+            .expect("Add substitute modal id should contain message id.");
+
+        let template = modal_interaction
+            .data
+            .components
+            .iter()
+            .flat_map(|row| row.components.iter())
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) if input.custom_id == TEMPLATE_INPUT_ID => {
+                    input.value.clone()
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        AddSubstituteModal {
+            interaction: modal_interaction,
+            channel_id,
+            message_id,
+            template: template.trim().to_string(),
+        }
+    }
+
+    pub fn get_interaction(&self) -> &ModalInteraction {
+        &self.interaction
+    }
+
+    pub fn get_channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    pub fn get_message_id(&self) -> MessageId {
+        self.message_id
+    }
+
+    pub fn get_template(&self) -> &str {
+        self.template.as_str()
+    }
+}
+
+/// A submitted modal containing the edited text of a substitute
+pub struct EditSubstituteModal {
+    interaction: ModalInteraction,
+    substitute_id: KeySize,
+    substitute: String,
+}
+
+impl EditSubstituteModal {
+    pub fn new(modal_interaction: ModalInteraction) -> Self {
+        let substitute_id = parse_edit_substitute_modal_id(&modal_interaction.data.custom_id)
+            .expect("Edit substitute modal id should contain substitute id.");
+
+        let substitute = modal_interaction
+            .data
+            .components
+            .iter()
+            .flat_map(|row| row.components.iter())
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) if input.custom_id == SUBSTITUTE_INPUT_ID => {
+                    input.value.clone()
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        EditSubstituteModal {
+            interaction: modal_interaction,
+            substitute_id,
+            substitute,
+        }
+    }
+
+    pub fn get_interaction(&self) -> &ModalInteraction {
+        &self.interaction
+    }
+
+    pub fn get_substitute_id(&self) -> KeySize {
+        self.substitute_id
+    }
+
+    pub fn get_substitute(&self) -> &str {
+        self.substitute.as_str()
+    }
+}
+
+/// A favorite template picked from the quick generate menu
+pub struct QuickGenerateComponent {
+    interaction: ComponentInteraction,
+    template_id: Option<KeySize>,
+}
+
+impl QuickGenerateComponent {
+    pub fn new(component_interaction: ComponentInteraction) -> Self {
+        let template_id = match &component_interaction.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values
+                .first()
+                .and_then(|value| value.parse::<KeySize>().ok()),
+            _ => None,
+        };
+
+        QuickGenerateComponent {
+            interaction: component_interaction,
+            template_id,
+        }
+    }
+
+    pub fn get_interaction(&self) -> &ComponentInteraction {
+        &self.interaction
+    }
+
+    pub fn get_template_id(&self) -> Option<KeySize> {
+        self.template_id
+    }
+}
+
 pub struct TrackComponent {
     interaction: ComponentInteraction,
     track_id: String,
@@ -111,27 +293,466 @@ pub fn create_confirm_button() -> CreateButton {
         .label("Confirm")
 }
 
-pub async fn create_confirmation_interaction<'a>(
-    ctx: Context<'a>,
-    interaction_msg: &str,
-    timeout_secs: u64,
-) -> Result<Option<ComponentInteraction>, Error> {
-    let action_row =
-        CreateActionRow::Buttons(vec![create_cancel_button(), create_confirm_button()]);
-
-    let interaction = ctx
-        .send(
-            CreateReply::default()
-                .content(interaction_msg)
-                .ephemeral(true)
-                .components(vec![action_row]),
+/// Creates previous and next buttons disabling whichever direction has no page
+pub fn create_page_buttons(page: usize, page_count: usize) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(PREVIOUS_PAGE_BUTTON_ID)
+            .label("Previous")
+            .disabled(page == 0),
+        CreateButton::new(NEXT_PAGE_BUTTON_ID)
+            .label("Next")
+            .disabled(page + 1 >= page_count),
+    ])
+}
+
+/// Creates a modal asking which template the message should be added to
+pub fn create_add_substitute_modal(
+    channel_id: ChannelId,
+    message_id: MessageId,
+    suggestions: &str,
+) -> CreateModal {
+    let mut template_input =
+        CreateInputText::new(InputTextStyle::Short, "Template", TEMPLATE_INPUT_ID).max_length(255);
+
+    if !suggestions.is_empty() {
+        template_input = template_input.placeholder(suggestions);
+    }
+
+    CreateModal::new(
+        format!("{} {} {}", ADD_SUBSTITUTE_MODAL_ID, channel_id, message_id),
+        "Add to template",
+    )
+    .components(vec![CreateActionRow::InputText(template_input)])
+}
+
+/// The most characters Discord allows in a select menu option label
+const SELECT_MENU_LABEL_LIMIT: usize = 100;
+
+/// Creates a select menu listing favorite templates by name
+///
+/// Options hold template ids since names can be longer than an option value allows
+pub fn create_favorites_menu(favorites: &[Favorite]) -> CreateActionRow {
+    let options = favorites
+        .iter()
+        .map(|favorite| {
+            CreateSelectMenuOption::new(
+                truncate_on_char_boundary(&favorite.template_name, SELECT_MENU_LABEL_LIMIT),
+                favorite.template_id.to_string(),
+            )
+        })
+        .collect();
+
+    CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(
+            QUICK_GENERATE_MENU_ID,
+            CreateSelectMenuKind::String { options },
         )
-        .await?;
-
-    Ok(interaction
-        .message()
-        .await?
-        .await_component_interaction(ctx)
-        .timeout(std::time::Duration::from_secs(timeout_secs))
-        .await)
+        .placeholder("Pick a template to generate"),
+    )
+}
+
+/// Whether text fits in a modal text input and can be edited there
+pub fn fits_in_modal_input(text: &str) -> bool {
+    text.chars().count() <= MODAL_INPUT_LIMIT
+}
+
+fn edit_substitute_modal_id(substitute_id: KeySize) -> String {
+    format!("{} {}", EDIT_SUBSTITUTE_MODAL_ID, substitute_id)
+}
+
+fn parse_edit_substitute_modal_id(custom_id: &str) -> Option<KeySize> {
+    let mut parts = custom_id.split_whitespace();
+    if parts.next() != Some(EDIT_SUBSTITUTE_MODAL_ID) {
+        return None;
+    }
+    parts.next()?.parse::<KeySize>().ok()
+}
+
+/// Creates a modal pre-filled with the current text of a substitute
+///
+/// The substitute must fit in a modal, see [`fits_in_modal_input`]
+pub fn create_edit_substitute_modal(substitute_id: KeySize, substitute: &str) -> CreateModal {
+    let substitute_input =
+        CreateInputText::new(InputTextStyle::Paragraph, "Substitute", SUBSTITUTE_INPUT_ID)
+            .value(substitute)
+            .max_length(MODAL_INPUT_LIMIT as u16);
+
+    CreateModal::new(edit_substitute_modal_id(substitute_id), "Edit substitute")
+        .components(vec![CreateActionRow::InputText(substitute_input)])
+}
+
+/// The most options ask_choice offers, one action row of buttons
+pub const MAX_CHOICES: usize = 5;
+
+/// The most characters Discord allows in a button label
+const BUTTON_LABEL_LIMIT: usize = 80;
+
+/// How an ask_choice question was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChoiceOutcome {
+    Chosen(usize),
+    Stopped,
+    TimedOut,
+}
+
+/// The id prefix shared by the buttons of one ask_choice question
+///
+/// The nonce keeps concurrent questions from picking up each other's clicks
+pub fn ask_choice_id_prefix(channel_id: ChannelId, nonce: Uuid) -> String {
+    format!("{} {} {}", ASK_CHOICE_BUTTON_ID, channel_id, nonce)
+}
+
+fn ask_choice_button_id(prefix: &str, choice: &str) -> String {
+    format!("{} {}", prefix, choice)
+}
+
+/// Reads which button of the question with prefix was clicked
+pub fn parse_ask_choice_button_id(prefix: &str, custom_id: &str) -> Option<ChoiceOutcome> {
+    match custom_id.strip_prefix(prefix)?.strip_prefix(' ')? {
+        STOP_CHOICE_ID => Some(ChoiceOutcome::Stopped),
+        index => index.parse::<usize>().ok().map(ChoiceOutcome::Chosen),
+    }
+}
+
+/// Creates a numbered button for each option followed by a row with a stop button
+///
+/// There must be no more than [`MAX_CHOICES`] options
+pub fn create_choice_buttons(prefix: &str, options: &[String]) -> Vec<CreateActionRow> {
+    let buttons = options
+        .iter()
+        .enumerate()
+        .map(|(index, option)| {
+            CreateButton::new(ask_choice_button_id(prefix, &index.to_string())).label(
+                truncate_on_char_boundary(
+                    &format!("{}. {}", index + 1, option),
+                    BUTTON_LABEL_LIMIT,
+                ),
+            )
+        })
+        .collect();
+
+    vec![
+        CreateActionRow::Buttons(buttons),
+        CreateActionRow::Buttons(vec![
+            CreateButton::new(ask_choice_button_id(prefix, STOP_CHOICE_ID))
+                .style(serenity::all::ButtonStyle::Danger)
+                .label("Stop"),
+        ]),
+    ]
+}
+
+/// How long Discord accepts edits to an interaction response after the interaction is created
+pub const INTERACTION_TOKEN_LIFETIME: Duration = Duration::from_secs(15 * 60);
+/// Leeway so a request sent just before the token expires doesn't arrive just after
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Discord error codes returned for requests made with an expired interaction token
+const UNKNOWN_WEBHOOK: isize = 10015;
+const UNKNOWN_INTERACTION: isize = 10062;
+const INVALID_WEBHOOK_TOKEN: isize = 50027;
+
+/// Whether an interaction token this old should no longer be used
+pub fn token_expired(token_age: Duration) -> bool {
+    token_age + TOKEN_EXPIRY_MARGIN >= INTERACTION_TOKEN_LIFETIME
+}
+
+/// How long a confirmation may wait for an answer before its token can't report the outcome,
+/// None when it already can't
+pub fn confirmation_timeout(requested: Duration, token_age: Duration) -> Option<Duration> {
+    let remaining = INTERACTION_TOKEN_LIFETIME.checked_sub(token_age + TOKEN_EXPIRY_MARGIN)?;
+    Some(requested.min(remaining)).filter(|timeout| !timeout.is_zero())
+}
+
+/// The status and Discord error code of a request Discord rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestFailure {
+    pub status: u16,
+    pub code: isize,
+}
+
+impl RequestFailure {
+    pub fn from_error(error: &serenity::all::Error) -> Option<Self> {
+        match error {
+            serenity::all::Error::Http(HttpError::UnsuccessfulRequest(response)) => Some(Self {
+                status: response.status_code.as_u16(),
+                code: response.error.code,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether the request failed because its interaction token expired
+    pub fn is_expired_token(&self) -> bool {
+        self.status == 401
+            || matches!(
+                self.code,
+                UNKNOWN_WEBHOOK | UNKNOWN_INTERACTION | INVALID_WEBHOOK_TOKEN
+            )
+    }
+}
+
+fn age_of(created_at: Timestamp) -> Duration {
+    let created_at = Duration::from_secs(created_at.unix_timestamp().max(0) as u64);
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .saturating_sub(created_at)
+}
+
+fn create_confirmation_buttons(disabled: bool) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        create_cancel_button().disabled(disabled),
+        create_confirm_button().disabled(disabled),
+    ])
+}
+
+/// Asks the author to confirm a destructive action before it runs
+///
+/// The wait never outlives the command's interaction token and the buttons are disabled once it
+/// times out, so a late click can't run the action without anyone being told about it
+pub struct ConfirmedAction<'a> {
+    ctx: Context<'a>,
+    prompt: String,
+    timeout: Duration,
+}
+
+impl<'a> ConfirmedAction<'a> {
+    pub fn new(ctx: Context<'a>, prompt: impl Into<String>) -> Self {
+        Self {
+            ctx,
+            prompt: prompt.into(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Waits for an answer, None once the wait times out
+    pub async fn ask(self) -> Result<Option<Confirmation<'a>>, Error> {
+        let Some(timeout) = confirmation_timeout(self.timeout, age_of(self.ctx.created_at()))
+        else {
+            self.ctx
+                .say_ephemeral("This command is too old to confirm, run it again.")
+                .await?;
+            return Ok(None);
+        };
+
+        let reply = self
+            .ctx
+            .send(
+                CreateReply::default()
+                    .content(&self.prompt)
+                    .ephemeral(true)
+                    .components(vec![create_confirmation_buttons(false)]),
+            )
+            .await?;
+
+        let interaction = reply
+            .message()
+            .await?
+            .await_component_interaction(self.ctx)
+            .timeout(timeout)
+            .await;
+
+        let Some(interaction) = interaction else {
+            let timed_out = CreateReply::default()
+                .content(format!("{}\nTimed out, nothing was changed.", self.prompt))
+                .components(vec![create_confirmation_buttons(true)]);
+            if let Err(e) = reply.edit(self.ctx, timed_out).await {
+                tracing::warn!(error = %e, "failed to disable confirmation buttons");
+            }
+            return Ok(None);
+        };
+
+        interaction
+            .create_response(self.ctx.http(), CreateInteractionResponse::Acknowledge)
+            .await?;
+        Ok(Some(Confirmation {
+            ctx: self.ctx,
+            confirmed: interaction.data.custom_id == CONFIRM_BUTTON_ID,
+            interaction,
+        }))
+    }
+}
+
+/// An answered [`ConfirmedAction`]
+pub struct Confirmation<'a> {
+    ctx: Context<'a>,
+    interaction: ComponentInteraction,
+    pub confirmed: bool,
+}
+
+impl Confirmation<'_> {
+    /// Replaces the prompt with the outcome of the action
+    ///
+    /// Once the token of the answer has expired the outcome is sent to the channel instead so
+    /// it is never lost, such as after an action that took a long time
+    pub async fn report(&self, message: &str) -> Result<(), Error> {
+        if !token_expired(age_of(self.interaction.id.created_at())) {
+            let edited = edit_interaction(self.ctx, &self.interaction, message, true).await;
+            match edited {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    let expired = e
+                        .downcast_ref::<serenity::all::Error>()
+                        .and_then(RequestFailure::from_error)
+                        .is_some_and(|failure| failure.is_expired_token());
+                    if !expired {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        self.ctx
+            .channel_id()
+            .say(
+                self.ctx.http(),
+                format!("{} {}", self.ctx.author().mention(), message),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod components_test {
+    use super::*;
+
+    #[test]
+    fn edit_substitute_modal_id_round_trip() {
+        for id in [0, 42, KeySize::MAX] {
+            assert_eq!(
+                parse_edit_substitute_modal_id(&edit_substitute_modal_id(id)),
+                Some(id)
+            );
+        }
+        assert_eq!(parse_edit_substitute_modal_id("edit_sub"), None);
+        assert_eq!(parse_edit_substitute_modal_id("edit_sub abc"), None);
+        assert_eq!(parse_edit_substitute_modal_id("add_sub 1 2"), None);
+    }
+
+    #[test]
+    fn modal_input_length_gate() {
+        assert!(fits_in_modal_input(""));
+        assert!(fits_in_modal_input(&"a".repeat(MODAL_INPUT_LIMIT)));
+        assert!(!fits_in_modal_input(&"a".repeat(MODAL_INPUT_LIMIT + 1)));
+        // the limit counts characters so multi-byte text is not rejected early
+        assert!(fits_in_modal_input(&"é".repeat(MODAL_INPUT_LIMIT)));
+    }
+
+    #[test]
+    fn ask_choice_button_id_round_trip() {
+        let prefix = ask_choice_id_prefix(ChannelId::new(1), Uuid::new_v4());
+        assert_eq!(
+            parse_ask_choice_button_id(&prefix, &ask_choice_button_id(&prefix, "3")),
+            Some(ChoiceOutcome::Chosen(3))
+        );
+        assert_eq!(
+            parse_ask_choice_button_id(&prefix, &ask_choice_button_id(&prefix, STOP_CHOICE_ID)),
+            Some(ChoiceOutcome::Stopped)
+        );
+        assert_eq!(parse_ask_choice_button_id(&prefix, &prefix), None);
+        assert_eq!(
+            parse_ask_choice_button_id(&prefix, &ask_choice_button_id(&prefix, "abc")),
+            None
+        );
+    }
+
+    #[test]
+    fn ask_choice_ids_are_namespaced() {
+        let nonce = Uuid::new_v4();
+        let prefix = ask_choice_id_prefix(ChannelId::new(1), nonce);
+        let other_channel = ask_choice_id_prefix(ChannelId::new(12), nonce);
+        let other_call = ask_choice_id_prefix(ChannelId::new(1), Uuid::new_v4());
+
+        for other in [other_channel, other_call] {
+            assert_ne!(prefix, other);
+            assert_eq!(
+                parse_ask_choice_button_id(&prefix, &ask_choice_button_id(&other, "0")),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn confirmation_timeout_is_bounded_by_token_lifetime() {
+        let requested = Duration::from_secs(30);
+        assert_eq!(
+            confirmation_timeout(requested, Duration::ZERO),
+            Some(requested)
+        );
+        assert_eq!(
+            confirmation_timeout(requested, Duration::from_secs(14 * 60)),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            confirmation_timeout(requested, Duration::from_secs(14 * 60 + 20)),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            confirmation_timeout(requested, Duration::from_secs(14 * 60 + 30)),
+            None
+        );
+        assert_eq!(
+            confirmation_timeout(requested, Duration::from_secs(60 * 60)),
+            None
+        );
+    }
+
+    #[test]
+    fn old_tokens_are_not_used() {
+        assert!(!token_expired(Duration::ZERO));
+        assert!(!token_expired(Duration::from_secs(14 * 60)));
+        assert!(token_expired(Duration::from_secs(14 * 60 + 30)));
+        assert!(token_expired(INTERACTION_TOKEN_LIFETIME));
+    }
+
+    #[test]
+    fn expired_token_failures_fall_back() {
+        let expired = [
+            RequestFailure {
+                status: 401,
+                code: INVALID_WEBHOOK_TOKEN,
+            },
+            RequestFailure {
+                status: 404,
+                code: UNKNOWN_WEBHOOK,
+            },
+            RequestFailure {
+                status: 404,
+                code: UNKNOWN_INTERACTION,
+            },
+            RequestFailure {
+                status: 401,
+                code: 0,
+            },
+        ];
+        for failure in expired {
+            assert!(failure.is_expired_token(), "{:?}", failure);
+        }
+
+        let other = [
+            // Invalid form body
+            RequestFailure {
+                status: 400,
+                code: 50035,
+            },
+            // Missing permissions
+            RequestFailure {
+                status: 403,
+                code: 50013,
+            },
+            RequestFailure {
+                status: 429,
+                code: 0,
+            },
+        ];
+        for failure in other {
+            assert!(!failure.is_expired_token(), "{:?}", failure);
+        }
+    }
 }
@@ -0,0 +1,66 @@
+use funboy_core::template_database::KeySize;
+use serenity::all::{Context as SerenityContext, GuildId, UserId};
+
+/// The name a contributor is listed under
+///
+/// Contributors without a display name, such as members who left the guild, are listed by the
+/// id they were recorded with
+pub fn contributor_name(user_id: KeySize, display_name: Option<&str>) -> String {
+    match display_name {
+        Some(name) => name.to_string(),
+        None => format!("`{}`", user_id),
+    }
+}
+
+/// The current display name of a contributor in guild_id, or their global display name outside
+/// of a guild
+///
+/// Members and users are read from the cache before asking the HTTP API
+async fn lookup_display_name(
+    ctx: &SerenityContext,
+    guild_id: Option<GuildId>,
+    user_id: KeySize,
+) -> Option<String> {
+    let user_id = u64::try_from(user_id)
+        .ok()
+        .filter(|id| *id != 0)
+        .map(UserId::new)?;
+
+    match guild_id {
+        Some(guild_id) => guild_id
+            .member(ctx, user_id)
+            .await
+            .ok()
+            .map(|member| member.display_name().to_string()),
+        None => user_id
+            .to_user(ctx)
+            .await
+            .ok()
+            .map(|user| user.display_name().to_string()),
+    }
+}
+
+/// Names of each contributor in user_ids in the same order
+pub async fn contributor_names(
+    ctx: &SerenityContext,
+    guild_id: Option<GuildId>,
+    user_ids: &[KeySize],
+) -> Vec<String> {
+    let mut names = Vec::with_capacity(user_ids.len());
+    for &user_id in user_ids {
+        let display_name = lookup_display_name(ctx, guild_id, user_id).await;
+        names.push(contributor_name(user_id, display_name.as_deref()));
+    }
+    names
+}
+
+#[cfg(test)]
+mod contributors_test {
+    use super::*;
+
+    #[test]
+    fn departed_members_fall_back_to_their_id() {
+        assert_eq!(contributor_name(42, Some("Jane")), "Jane");
+        assert_eq!(contributor_name(42, None), "`42`");
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use serenity::all::{
+    ChannelId, CreateAllowedMentions, CreateMessage, ExecuteWebhook, Http, Webhook,
+};
+
+use crate::io_format::discord_message_format::{
+    DISCORD_CHARACTER_LIMIT, truncate_on_char_boundary,
+};
+
+/// How long a report of one failure keeps identical failures from being reported again
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(60 * 10);
+/// Longest invocation kept in a report
+pub const MAX_ARGUMENTS_LENGTH: usize = 300;
+/// Longest error kept in a report
+pub const MAX_ERROR_LENGTH: usize = 1000;
+
+/// Where reports of failed commands are sent
+#[derive(Debug, Clone)]
+pub enum ReportTarget {
+    Channel(ChannelId),
+    Webhook(String),
+}
+
+impl ReportTarget {
+    /// Reads `ERROR_WEBHOOK_URL`, or `ERROR_CHANNEL_ID` when no webhook is set
+    pub fn from_env() -> Option<Self> {
+        if let Ok(url) = std::env::var("ERROR_WEBHOOK_URL") {
+            return Some(ReportTarget::Webhook(url));
+        }
+        std::env::var("ERROR_CHANNEL_ID").ok().map(|id| {
+            ReportTarget::Channel(ChannelId::new(
+                id.parse().expect("ERROR_CHANNEL_ID must be a channel id"),
+            ))
+        })
+    }
+}
+
+/// A failed command with enough context for operators to reproduce it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub command: String,
+    pub user_id: u64,
+    pub guild_id: Option<u64>,
+    pub channel_id: u64,
+    /// The invocation passed through [`sanitize`]
+    pub arguments: String,
+    pub error: String,
+    pub panicked: bool,
+}
+
+impl ErrorReport {
+    /// Identical failures of a command share a fingerprint even when the numbers in them differ
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.command.hash(&mut hasher);
+        self.panicked.hash(&mut hasher);
+        let mut last_digit = false;
+        for c in self.error.chars() {
+            let digit = c.is_ascii_digit();
+            if !(digit && last_digit) {
+                (if digit { '#' } else { c }).hash(&mut hasher);
+            }
+            last_digit = digit;
+        }
+        hasher.finish()
+    }
+
+    /// The message posted to operators, suppressed is how many identical failures weren't reported
+    pub fn to_message(&self, suppressed: u32) -> String {
+        let guild = match self.guild_id {
+            Some(guild_id) => guild_id.to_string(),
+            None => "direct message".to_string(),
+        };
+        let mut message = format!(
+            "**`{}` {}**\nuser: {} guild: {} channel: {}\ninvocation: `{}`\n```\n{}\n```",
+            self.command,
+            if self.panicked { "panicked" } else { "failed" },
+            self.user_id,
+            guild,
+            self.channel_id,
+            self.arguments,
+            sanitize(&self.error, MAX_ERROR_LENGTH),
+        );
+        if suppressed > 0 {
+            message.push_str(&format!(
+                "\n{} identical failures were not reported",
+                suppressed
+            ));
+        }
+        message
+    }
+}
+
+/// Flattens text onto one line, swaps backticks out and truncates it to at most max bytes
+pub fn sanitize(text: &str, max: usize) -> String {
+    let flattened: String = text
+        .chars()
+        .map(|c| match c {
+            '`' => '\'',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    if flattened.len() > max {
+        format!("{}…", truncate_on_char_boundary(&flattened, max))
+    } else {
+        flattened
+    }
+}
+
+/// When each failure was last reported and how many times it happened since
+#[derive(Debug, Default)]
+pub struct ErrorReportLimiter {
+    reported: HashMap<u64, (Instant, u32)>,
+}
+
+impl ErrorReportLimiter {
+    /// Records fingerprint as reported at now unless it was already reported within
+    /// [`REPORT_INTERVAL`]
+    ///
+    /// Returns how many times it was suppressed since it was last reported, None when it is
+    /// suppressed again
+    pub fn try_report(&mut self, fingerprint: u64, now: Instant) -> Option<u32> {
+        // Failures that were never suppressed have nothing to carry into their next report
+        self.reported.retain(|_, (reported, suppressed)| {
+            *suppressed > 0 || now.saturating_duration_since(*reported) < REPORT_INTERVAL
+        });
+        match self.reported.get_mut(&fingerprint) {
+            Some((reported, suppressed))
+                if now.saturating_duration_since(*reported) < REPORT_INTERVAL =>
+            {
+                *suppressed += 1;
+                None
+            }
+            Some((reported, suppressed)) => {
+                *reported = now;
+                Some(std::mem::take(suppressed))
+            }
+            None => {
+                self.reported.insert(fingerprint, (now, 0));
+                Some(0)
+            }
+        }
+    }
+}
+
+/// Sends reports of failed commands to operators, rate limited per fingerprint
+#[derive(Debug)]
+pub struct ErrorReporter {
+    target: ReportTarget,
+    limiter: Mutex<ErrorReportLimiter>,
+}
+
+impl ErrorReporter {
+    pub fn new(target: ReportTarget) -> Self {
+        Self {
+            target,
+            limiter: Mutex::default(),
+        }
+    }
+
+    pub async fn report(&self, http: &Http, report: &ErrorReport) {
+        let admitted = self
+            .limiter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .try_report(report.fingerprint(), Instant::now());
+        let Some(suppressed) = admitted else {
+            return;
+        };
+
+        self.send(http, report.to_message(suppressed)).await;
+    }
+
+    /// Posts content to operators without rate limiting
+    pub async fn notify(&self, http: &Http, content: &str) {
+        self.send(
+            http,
+            truncate_on_char_boundary(content, DISCORD_CHARACTER_LIMIT).to_string(),
+        )
+        .await;
+    }
+
+    async fn send(&self, http: &Http, content: String) {
+        let result = match &self.target {
+            ReportTarget::Channel(channel_id) => channel_id
+                .send_message(
+                    http,
+                    CreateMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                )
+                .await
+                .map(|_| ()),
+            ReportTarget::Webhook(url) => match Webhook::from_url(http, url).await {
+                Ok(webhook) => webhook
+                    .execute(
+                        http,
+                        false,
+                        ExecuteWebhook::new()
+                            .content(content)
+                            .allowed_mentions(CreateAllowedMentions::new()),
+                    )
+                    .await
+                    .map(|_| ()),
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "failed to report command error");
+        }
+    }
+}
+
+#[cfg(test)]
+mod error_reports_test {
+    use super::*;
+
+    fn report(command: &str, error: &str) -> ErrorReport {
+        ErrorReport {
+            command: command.to_string(),
+            user_id: 1,
+            guild_id: Some(2),
+            channel_id: 3,
+            arguments: format!("/{}", command),
+            error: error.to_string(),
+            panicked: false,
+        }
+    }
+
+    #[test]
+    fn fingerprints_ignore_numbers() {
+        let timeout = report("generate", "pool timed out after 5000ms on connection 12");
+        assert_eq!(
+            timeout.fingerprint(),
+            report("generate", "pool timed out after 30ms on connection 4").fingerprint()
+        );
+        assert_ne!(
+            timeout.fingerprint(),
+            report("add_subs", "pool timed out after 5000ms on connection 12").fingerprint()
+        );
+        assert_ne!(
+            timeout.fingerprint(),
+            report("generate", "pool closed").fingerprint()
+        );
+
+        let mut panic = timeout.clone();
+        panic.panicked = true;
+        assert_ne!(timeout.fingerprint(), panic.fingerprint());
+    }
+
+    #[test]
+    fn one_report_per_fingerprint_per_interval() {
+        let mut limiter = ErrorReportLimiter::default();
+        let start = Instant::now();
+
+        assert_eq!(limiter.try_report(1, start), Some(0));
+        assert_eq!(limiter.try_report(1, start + Duration::from_secs(1)), None);
+        assert_eq!(
+            limiter.try_report(2, start + Duration::from_secs(1)),
+            Some(0)
+        );
+        assert_eq!(
+            limiter.try_report(1, start + REPORT_INTERVAL - Duration::from_secs(1)),
+            None
+        );
+        // Both suppressed reports of 1 are counted in its next report
+        assert_eq!(limiter.try_report(1, start + REPORT_INTERVAL), Some(2));
+        assert_eq!(limiter.try_report(1, start + REPORT_INTERVAL * 2), Some(0));
+    }
+
+    #[test]
+    fn quiet_failures_are_forgotten() {
+        let mut limiter = ErrorReportLimiter::default();
+        let start = Instant::now();
+
+        limiter.try_report(1, start);
+        limiter.try_report(2, start);
+        limiter.try_report(2, start + Duration::from_secs(1));
+        limiter.try_report(3, start + REPORT_INTERVAL);
+        assert!(!limiter.reported.contains_key(&1));
+        assert!(limiter.reported.contains_key(&2));
+        assert!(limiter.reported.contains_key(&3));
+    }
+
+    #[test]
+    fn reports_are_sanitized() {
+        assert_eq!(
+            sanitize("`@everyone`\nhi\tthere", 100),
+            "'@everyone' hi there"
+        );
+        assert_eq!(sanitize("ééé", 3), "é…");
+
+        let mut report = report("generate", "bad ```\ncode");
+        report.guild_id = None;
+        let message = report.to_message(2);
+        assert!(message.contains("guild: direct message"), "{}", message);
+        assert!(message.contains("bad ''' code"), "{}", message);
+        assert!(message.ends_with("2 identical failures were not reported"));
+    }
+}
@@ -0,0 +1,144 @@
+use std::{sync::Arc, time::Duration};
+
+use fsl_interpreter::FslInterpreter;
+use funboy_core::{
+    Funboy,
+    featured::FeaturedStrategy,
+    is_effectively_empty,
+    template_database::{FeaturedCandidate, FeaturedChannel},
+    template_substitutor::TemplateDelimiter,
+    template_usage::day_of,
+};
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage, Http, Timestamp};
+use tokio::sync::Mutex;
+
+use crate::commands::templates::fit_embed_field;
+
+/// How often featured channels are checked for a template of the day that is due
+pub const FEATURED_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 10);
+/// Outputs of the featured template generated for each post
+pub const FEATURED_SAMPLES: usize = 3;
+
+/// Posts the template of the day in every featured channel every [`FEATURED_CHECK_INTERVAL`]
+pub async fn post_featured_templates_periodically(http: Arc<Http>, funboy: Arc<Funboy>) {
+    let mut interval = tokio::time::interval(FEATURED_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        post_featured_templates(&http, &funboy).await;
+    }
+}
+
+/// Posts in every featured channel that has not been posted in today
+async fn post_featured_templates(http: &Http, funboy: &Funboy) {
+    let today = day_of(Timestamp::now().unix_timestamp());
+    let channels = match funboy.get_featured_channels_due(today).await {
+        Ok(channels) => channels,
+        Err(e) => {
+            tracing::warn!(error = %e.to_string(), "failed to read featured channels");
+            return;
+        }
+    };
+
+    for channel in channels {
+        post_featured_template(http, funboy, &channel, today).await;
+    }
+}
+
+/// The day is marked as posted before sending so a channel the bot can't post in is only
+/// tried once a day
+async fn post_featured_template(
+    http: &Http,
+    funboy: &Funboy,
+    channel: &FeaturedChannel,
+    today: i64,
+) {
+    let guild_id = channel.guild_id as u64;
+    let strategy = FeaturedStrategy::parse(&channel.strategy).unwrap_or(FeaturedStrategy::Random);
+    let featured = match funboy.pick_featured_template(guild_id, strategy).await {
+        Ok(featured) => featured,
+        Err(e) => {
+            tracing::warn!(error = %e.to_string(), guild_id, "failed to pick featured template");
+            return;
+        }
+    };
+    if let Err(e) = funboy.mark_featured_posted(guild_id, today).await {
+        tracing::warn!(error = %e.to_string(), guild_id, "failed to mark featured template posted");
+        return;
+    }
+    let Some(featured) = featured else {
+        return;
+    };
+
+    let samples = generate_samples(funboy, &featured.name).await;
+    let message = CreateMessage::new().embed(featured_embed(&featured, &samples));
+    if let Err(e) = ChannelId::new(channel.channel_id as u64)
+        .send_message(http, message)
+        .await
+    {
+        tracing::warn!(error = %e, guild_id, "failed to post featured template");
+    }
+}
+
+/// Generates the template on its own [`FEATURED_SAMPLES`] times skipping failed generations
+async fn generate_samples(funboy: &Funboy, template: &str) -> Vec<String> {
+    let input = format!("{}{}", TemplateDelimiter::CARET.to_char(), template);
+    let mut samples = Vec::with_capacity(FEATURED_SAMPLES);
+    for _ in 0..FEATURED_SAMPLES {
+        let interpreter = Arc::new(Mutex::new(FslInterpreter::new()));
+        match funboy.generate(&input, interpreter).await {
+            Ok(sample) => samples.push(sample.text),
+            Err(e) => {
+                tracing::warn!(error = %e.to_string(), template, "failed to generate featured sample")
+            }
+        }
+    }
+    samples
+}
+
+pub fn featured_embed(featured: &FeaturedCandidate, samples: &[String]) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .title(format!("Template of the day: `{}`", featured.name))
+        .field("Substitutes", featured.substitute_count.to_string(), true);
+    if samples.is_empty() {
+        embed = embed.field("Samples", "No sample could be generated.", false);
+    }
+    for (i, sample) in samples.iter().enumerate() {
+        let sample = if is_effectively_empty(sample) {
+            "*(empty)*".to_string()
+        } else {
+            fit_embed_field(sample)
+        };
+        embed = embed.field(format!("Sample {}", i + 1), sample, false);
+    }
+    embed
+}
+
+#[cfg(test)]
+mod featured_posts_test {
+    use super::*;
+
+    fn embed_json(samples: &[&str]) -> serde_json::Value {
+        let featured = FeaturedCandidate {
+            id: 1,
+            name: "animal".to_string(),
+            substitute_count: 12,
+            last_featured_day: None,
+            uses: 0,
+        };
+        let samples: Vec<String> = samples.iter().map(|sample| sample.to_string()).collect();
+        serde_json::to_value(featured_embed(&featured, &samples)).unwrap()
+    }
+
+    #[test]
+    fn embed_lists_samples() {
+        let embed = embed_json(&["fox", ""]);
+        assert_eq!(embed["title"], "Template of the day: `animal`");
+        assert_eq!(embed["fields"][0]["value"], "12");
+        assert_eq!(embed["fields"][1]["name"], "Sample 1");
+        assert_eq!(embed["fields"][1]["value"], "fox");
+        assert_eq!(embed["fields"][2]["value"], "*(empty)*");
+
+        let embed = embed_json(&[]);
+        assert_eq!(embed["fields"][1]["value"], "No sample could be generated.");
+    }
+}
@@ -0,0 +1,112 @@
+use serenity::all::{ChannelId, ChannelType, Mentionable};
+
+use crate::{Context, Error, io_format::context_extension::ContextExtension};
+
+/// Whether generating in channel is allowed
+///
+/// Every channel is allowed while none are configured, threads are allowed when their parent is
+pub fn is_generate_channel(
+    allowed: &[ChannelId],
+    channel: ChannelId,
+    thread_parent: Option<ChannelId>,
+) -> bool {
+    allowed.is_empty()
+        || allowed.contains(&channel)
+        || thread_parent.is_some_and(|parent| allowed.contains(&parent))
+}
+
+/// Points the user at the channels generating is limited to
+pub fn redirect_message(allowed: &[ChannelId]) -> String {
+    let channels: Vec<String> = allowed
+        .iter()
+        .map(|channel| channel.mention().to_string())
+        .collect();
+    format!(
+        "Generating is limited to {} in this server",
+        channels.join(", ")
+    )
+}
+
+fn is_thread(kind: ChannelType) -> bool {
+    matches!(
+        kind,
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread
+    )
+}
+
+/// Command check limiting generation to the allowed channels of a guild
+///
+/// Replies with where generating is allowed when the check fails
+pub async fn check_generate_channel(ctx: Context<'_>) -> Result<bool, Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(true);
+    };
+
+    let allowed = match ctx
+        .data()
+        .funboy
+        .get_generate_channels(guild_id.get())
+        .await
+    {
+        Ok(channels) => channels.into_iter().map(ChannelId::new).collect::<Vec<_>>(),
+        Err(e) => {
+            tracing::warn!(error = %e.to_string(), "failed to read generate channels");
+            return Ok(true);
+        }
+    };
+    if allowed.is_empty() {
+        return Ok(true);
+    }
+
+    let thread_parent = match ctx.guild_channel().await {
+        Some(channel) if is_thread(channel.kind) => channel.parent_id,
+        _ => None,
+    };
+    if is_generate_channel(&allowed, ctx.channel_id(), thread_parent) {
+        Ok(true)
+    } else {
+        ctx.say_ephemeral(&redirect_message(&allowed)).await?;
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod generate_channels_test {
+    use super::*;
+
+    const BOT_SPAM: ChannelId = ChannelId::new(1);
+    const MEMES: ChannelId = ChannelId::new(2);
+    const GENERAL: ChannelId = ChannelId::new(3);
+    const THREAD: ChannelId = ChannelId::new(4);
+
+    #[test]
+    fn unconfigured_guilds_allow_every_channel() {
+        assert!(is_generate_channel(&[], GENERAL, None));
+        assert!(is_generate_channel(&[], THREAD, Some(GENERAL)));
+    }
+
+    #[test]
+    fn only_allowed_channels_pass() {
+        let allowed = [BOT_SPAM, MEMES];
+        assert!(is_generate_channel(&allowed, BOT_SPAM, None));
+        assert!(is_generate_channel(&allowed, MEMES, None));
+        assert!(!is_generate_channel(&allowed, GENERAL, None));
+    }
+
+    #[test]
+    fn threads_follow_their_parent() {
+        let allowed = [BOT_SPAM];
+        assert!(is_generate_channel(&allowed, THREAD, Some(BOT_SPAM)));
+        assert!(!is_generate_channel(&allowed, THREAD, Some(GENERAL)));
+        assert!(!is_generate_channel(&allowed, THREAD, None));
+        assert!(is_generate_channel(&[THREAD], THREAD, Some(GENERAL)));
+    }
+
+    #[test]
+    fn redirect_names_the_allowed_channels() {
+        assert_eq!(
+            redirect_message(&[BOT_SPAM, MEMES]),
+            "Generating is limited to <#1>, <#2> in this server"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::{sync::Arc, time::Duration};
+use std::{future::Future, sync::Arc, time::Duration};
 
 use fsl_interpreter::{
     FslInterpreter, InterpreterData,
@@ -8,19 +8,47 @@ use fsl_interpreter::{
         value::Value,
     },
 };
-use funboy_core::Funboy;
+use funboy_core::{Funboy, GenerationOutput};
 use serenity::{
-    all::{Cache, ChannelId, GuildId, Http, Member, Mentionable, ShardMessenger, UserId},
+    all::{
+        Cache, ChannelId, ComponentInteraction, ComponentInteractionCollector,
+        CreateInteractionResponse, CreateMessage, EditMessage, GuildId, Http, Member, Mentionable,
+        Message, ShardMessenger, UserId,
+    },
     futures::StreamExt,
 };
-use tokio::{sync::Mutex, time::sleep};
+use tokio::{
+    sync::{Mutex, OnceCell},
+    time::sleep,
+};
+use uuid::Uuid;
+
+use crate::{
+    Context, Data,
+    channel_resolver::{
+        ChannelEntry, check_send_permission, fetch_channel_entries, resolve_channel,
+    },
+    components::{
+        ChoiceOutcome, MAX_CHOICES, ask_choice_id_prefix, create_choice_buttons,
+        parse_ask_choice_button_id,
+    },
+    generate_channels::{is_generate_channel, redirect_message},
+    rate_limiter::RateLimit,
+};
 
-use crate::{Context, rate_limiter::RateLimit};
+mod command_permissions;
+mod member_resolver;
+mod template_sources;
+
+pub use command_permissions::CommandPermissions;
+use command_permissions::create_denied_command;
+use member_resolver::{MemberEntry, resolve_member};
+use template_sources::GuildSources;
+pub use template_sources::VIRTUAL_TEMPLATE_NAMES;
 
 #[derive(Clone)]
 pub struct InterpreterContext {
     pub http: Arc<Http>,
-    #[allow(dead_code)]
     pub cache: Arc<Cache>,
     pub shard: ShardMessenger,
     pub guild_id: Option<GuildId>,
@@ -29,21 +57,84 @@ pub struct InterpreterContext {
     pub funboy: Arc<Funboy>,
     pub rate_limit: Arc<Mutex<RateLimit>>,
     pub command_call_count: Arc<Mutex<u16>>,
+    /// How long delay() has waited so far in this generation
+    pub delay_total: Arc<Mutex<Duration>>,
+    members: Arc<OnceCell<Vec<MemberEntry>>>,
+    channels: Arc<OnceCell<Vec<ChannelEntry>>>,
+    generate_channels: Arc<OnceCell<Vec<ChannelId>>>,
     interpreter: Arc<Mutex<FslInterpreter>>,
 }
 
 impl InterpreterContext {
     pub fn from_poise(ctx: &Context<'_>) -> Self {
+        let http = ctx.serenity_context().http.clone();
+        let members = Arc::new(OnceCell::new());
+        let sources = GuildSources::new(http.clone(), ctx.guild_id(), members.clone());
+
         Self {
-            http: ctx.serenity_context().http.clone(),
+            http,
             cache: ctx.serenity_context().cache.clone(),
             shard: ctx.serenity_context().shard.clone(),
             guild_id: ctx.guild_id(),
             channel_id: ctx.channel_id(),
             author_id: ctx.author().id,
-            funboy: ctx.data().funboy.clone(),
+            funboy: Arc::new(sources.attach(&ctx.data().funboy)),
             rate_limit: ctx.data().interpreter_rate_limit.clone(),
             command_call_count: Arc::new(Mutex::new(0)),
+            delay_total: Arc::new(Mutex::new(Duration::ZERO)),
+            members,
+            channels: Arc::new(OnceCell::new()),
+            generate_channels: Arc::new(OnceCell::new()),
+            interpreter: Arc::new(Mutex::new(FslInterpreter::new())),
+        }
+    }
+
+    /// Creates a context for generation started from a component rather than a command
+    pub fn from_component(
+        ctx: &serenity::all::Context,
+        interaction: &ComponentInteraction,
+        data: &Data,
+    ) -> Self {
+        let members = Arc::new(OnceCell::new());
+        let sources = GuildSources::new(ctx.http.clone(), interaction.guild_id, members.clone());
+
+        Self {
+            http: ctx.http.clone(),
+            cache: ctx.cache.clone(),
+            shard: ctx.shard.clone(),
+            guild_id: interaction.guild_id,
+            channel_id: interaction.channel_id,
+            author_id: interaction.user.id,
+            funboy: Arc::new(sources.attach(&data.funboy)),
+            rate_limit: data.interpreter_rate_limit.clone(),
+            command_call_count: Arc::new(Mutex::new(0)),
+            delay_total: Arc::new(Mutex::new(Duration::ZERO)),
+            members,
+            channels: Arc::new(OnceCell::new()),
+            generate_channels: Arc::new(OnceCell::new()),
+            interpreter: Arc::new(Mutex::new(FslInterpreter::new())),
+        }
+    }
+
+    /// Creates a context for generation started by a message rather than a command
+    pub fn from_message(ctx: &serenity::all::Context, message: &Message, data: &Data) -> Self {
+        let members = Arc::new(OnceCell::new());
+        let sources = GuildSources::new(ctx.http.clone(), message.guild_id, members.clone());
+
+        Self {
+            http: ctx.http.clone(),
+            cache: ctx.cache.clone(),
+            shard: ctx.shard.clone(),
+            guild_id: message.guild_id,
+            channel_id: message.channel_id,
+            author_id: message.author.id,
+            funboy: Arc::new(sources.attach(&data.funboy)),
+            rate_limit: data.interpreter_rate_limit.clone(),
+            command_call_count: Arc::new(Mutex::new(0)),
+            delay_total: Arc::new(Mutex::new(Duration::ZERO)),
+            members,
+            channels: Arc::new(OnceCell::new()),
+            generate_channels: Arc::new(OnceCell::new()),
             interpreter: Arc::new(Mutex::new(FslInterpreter::new())),
         }
     }
@@ -62,61 +153,146 @@ impl InterpreterContext {
         }
     }
 
+    /// Guild members fetched once per generation so repeated lookups don't hit the HTTP API
+    async fn get_member_entries(&self) -> Result<&[MemberEntry], CommandError> {
+        let members = self
+            .members
+            .get_or_try_init(|| async {
+                let members = self.get_guild_members().await?;
+                Ok::<_, CommandError>(members.iter().map(MemberEntry::from).collect())
+            })
+            .await?;
+
+        Ok(members)
+    }
+
     pub async fn say_to_user(&self, user_name: &str, message: &str) -> Result<(), CommandError> {
-        let members = self.get_guild_members().await?;
+        let mention = if user_name == "everyone" {
+            "@everyone".to_string()
+        } else {
+            self.get_user_id(user_name).await?.mention().to_string()
+        };
 
-        let say_message = async |mention: &str| {
-            let mention_message = format!("{} {}", mention, message);
-            if let Err(e) = self.channel_id.say(&self.http, mention_message).await {
-                return Err(CommandError::Custom(e.to_string()));
-            };
+        self.check_generate_channel(self.channel_id).await?;
+        let mention_message = format!("{} {}", mention, message);
+        if let Err(e) = self.channel_id.say(&self.http, mention_message).await {
+            return Err(CommandError::Custom(e.to_string()));
+        };
 
-            Ok(())
+        Ok(())
+    }
+
+    /// Guild channels fetched once per generation so repeated lookups don't hit the HTTP API
+    async fn get_channel_entries(&self) -> Result<&[ChannelEntry], CommandError> {
+        let guild_id = self
+            .guild_id
+            .ok_or_else(|| CommandError::Custom("failed to get guild id".to_string()))?;
+
+        let channels = self
+            .channels
+            .get_or_try_init(|| async {
+                fetch_channel_entries(&self.http, guild_id)
+                    .await
+                    .map_err(|_| CommandError::Custom("failed to fetch guild channels".to_string()))
+            })
+            .await?;
+
+        Ok(channels)
+    }
+
+    /// Fails when the guild limits generation to channels that don't include channel_id
+    async fn check_generate_channel(&self, channel_id: ChannelId) -> Result<(), CommandError> {
+        let Some(guild_id) = self.guild_id else {
+            return Ok(());
         };
 
-        if let Some(member) = members.iter().find(|m| {
-            m.user.name == user_name
-                || m.user.tag() == user_name
-                || m.user.display_name() == user_name
-                || m.nick.as_ref().is_some_and(|nick| nick == &user_name)
-        }) {
-            say_message(&member.mention().to_string()).await?;
-        } else if user_name == "everyone" {
-            say_message("@everyone").await?;
+        let allowed = self
+            .generate_channels
+            .get_or_init(|| async {
+                match self.funboy.get_generate_channels(guild_id.get()).await {
+                    Ok(channels) => channels.into_iter().map(ChannelId::new).collect(),
+                    Err(e) => {
+                        tracing::warn!(error = %e.to_string(), "failed to read generate channels");
+                        Vec::new()
+                    }
+                }
+            })
+            .await;
+        if allowed.is_empty() {
+            return Ok(());
+        }
+
+        let channels = self.get_channel_entries().await?;
+        let thread_parent = channels
+            .iter()
+            .find(|entry| entry.id == channel_id)
+            .and_then(|entry| entry.parent.as_ref())
+            .map(|parent| parent.id);
+        if is_generate_channel(allowed, channel_id, thread_parent) {
+            Ok(())
         } else {
-            return Err(CommandError::Custom(format!(
-                "no user named {} found",
-                user_name
-            )));
+            Err(CommandError::Custom(redirect_message(allowed)))
         }
+    }
+
+    async fn check_bot_can_send(&self, channel: &ChannelEntry) -> Result<(), CommandError> {
+        let guild_id = self
+            .guild_id
+            .ok_or_else(|| CommandError::Custom("failed to get guild id".to_string()))?;
+        let bot_id = self.cache.current_user().id;
+
+        let permissions = async {
+            let guild = guild_id.to_partial_guild(&self.http).await?;
+            let member = guild_id.member(&self.http, bot_id).await?;
+            let permission_channel = channel
+                .permission_channel_id()
+                .to_channel(&self.http)
+                .await?
+                .guild()
+                .ok_or(serenity::Error::Other("channel is not in a guild"))?;
+            Ok::<_, serenity::Error>(guild.user_permissions_in(&permission_channel, &member))
+        }
+        .await
+        .map_err(|_| CommandError::Custom("failed to fetch channel permissions".to_string()))?;
+
+        check_send_permission(permissions, channel).map_err(CommandError::Custom)
+    }
+
+    pub async fn say_in_channel(
+        &self,
+        channel_name: &str,
+        message: &str,
+    ) -> Result<(), CommandError> {
+        let channels = self.get_channel_entries().await?;
+        let channel = resolve_channel(channel_name, channels).map_err(CommandError::Custom)?;
+
+        self.check_generate_channel(channel.id).await?;
+        self.check_bot_can_send(channel).await?;
+
+        if let Err(e) = channel.id.say(&self.http, message).await {
+            return Err(CommandError::Custom(e.to_string()));
+        };
+
         Ok(())
     }
 
     pub async fn get_user_id(&self, user_name: &str) -> Result<UserId, CommandError> {
-        let members = self.get_guild_members().await?;
-
-        if let Some(member) = members.iter().find(|m| {
-            m.user.name == user_name
-                || m.user.tag() == user_name
-                || m.user.display_name() == user_name
-                || m.nick.as_ref().is_some_and(|nick| nick == &user_name)
-        }) {
-            Ok(member.user.id)
-        } else {
-            return Err(CommandError::Custom(format!(
-                "no user named {} found",
-                user_name
-            )));
-        }
+        let members = self.get_member_entries().await?;
+
+        resolve_member(user_name, members).map_err(CommandError::Custom)
     }
 
     pub async fn generate_message(&self, message: &str) -> Result<String, CommandError> {
+        Ok(self.generate_output(message).await?.text)
+    }
+
+    pub async fn generate_output(&self, message: &str) -> Result<GenerationOutput, CommandError> {
         match self
             .funboy
             .generate(&message, self.interpreter.clone())
             .await
         {
-            Ok(gen_msg) => Ok(gen_msg),
+            Ok(output) => Ok(output),
             Err(e) => {
                 return Err(CommandError::Custom(e.to_string()));
             }
@@ -124,16 +300,87 @@ impl InterpreterContext {
     }
 }
 
-const COMMAND_MESSAGE_DELAY_MS: u64 = 500;
-pub fn create_custom_interpreter(ctx: &Context<'_>) -> Arc<tokio::sync::Mutex<FslInterpreter>> {
-    let mut interpreter = FslInterpreter::new();
+/// Sends the text of output unless nothing in it is visible, returns whether it was sent
+///
+/// Generations with every branch of a condition false would otherwise post blank messages
+async fn send_if_visible<F, Fut>(output: GenerationOutput, send: F) -> Result<bool, CommandError>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<(), CommandError>>,
+{
+    if output.is_effectively_empty {
+        return Ok(false);
+    }
+    send(output.text).await?;
+    Ok(true)
+}
 
+/// Commands registered by [`create_custom_generation`] which templates must not shadow
+pub const INTERPRETER_COMMAND_NAMES: &[&str] =
+    &[SAY, SAY_TO, SAY_IN, ASK, ASK_TO, ASK_CHOICE, DELAY];
+
+const COMMAND_MESSAGE_DELAY_MS: u64 = 500;
+/// The funboy and interpreter for a generation started by ctx with the guild templates attached
+///
+/// Commands the author isn't permitted to use fail with a permission error
+pub async fn create_custom_generation(
+    ctx: &Context<'_>,
+) -> (Arc<Funboy>, Arc<tokio::sync::Mutex<FslInterpreter>>) {
+    let member = ctx.author_member().await;
+    let permissions =
+        CommandPermissions::resolve(&ctx.data().funboy, ctx.guild_id(), member.as_deref()).await;
     let ictx = InterpreterContext::from_poise(ctx);
+    (ictx.funboy.clone(), create_interpreter(ictx, permissions))
+}
+
+/// Vars describing where and by whom a generation was started, seeded before any session vars
+///
+/// Names start with [`Funboy::RESERVED_VAR_PREFIX`] so embedded code can read them with
+/// `clone(ctx_author)` but never overwrite them. Channel and guild are empty outside of a guild.
+pub async fn context_vars(ctx: &Context<'_>) -> Vec<(&'static str, String)> {
+    let author = match ctx.author_member().await {
+        Some(member) => member.display_name().to_string(),
+        None => ctx.author().display_name().to_string(),
+    };
+    let guild = ctx.guild().map(|guild| guild.name.clone());
+    let channel = ctx.guild_channel().await.map(|channel| channel.name);
+
+    vec![
+        ("ctx_author", author),
+        ("ctx_author_id", ctx.author().id.to_string()),
+        ("ctx_channel", channel.unwrap_or_default()),
+        ("ctx_guild", guild.unwrap_or_default()),
+        ("ctx_time", ctx.created_at().to_string()),
+    ]
+}
+
+/// Creates an interpreter with the Discord commands acting on ictx
+///
+/// Commands outside of permissions are registered as stubs that fail with a permission error
+pub fn create_interpreter(
+    ictx: InterpreterContext,
+    permissions: CommandPermissions,
+) -> Arc<tokio::sync::Mutex<FslInterpreter>> {
+    let mut interpreter = FslInterpreter::new();
+    let mut add_command = |name: &'static str, rules: &'static [ArgRule], executor: Executor| {
+        if permissions.allows(name) {
+            interpreter.add_command(name, rules, executor);
+        } else {
+            interpreter.add_command(name, rules, create_denied_command(name));
+        }
+    };
 
-    interpreter.add_command(SAY, SAY_RULES, create_say_command(ictx.clone()));
-    interpreter.add_command(SAY_TO, SAY_TO_RULES, create_say_to_command(ictx.clone()));
-    interpreter.add_command(ASK, ASK_RULES, create_ask_command(ictx.clone()));
-    interpreter.add_command(ASK_TO, ASK_TO_RULES, create_ask_to_command(ictx.clone()));
+    add_command(SAY, SAY_RULES, create_say_command(ictx.clone()));
+    add_command(SAY_TO, SAY_TO_RULES, create_say_to_command(ictx.clone()));
+    add_command(SAY_IN, SAY_IN_RULES, create_say_in_command(ictx.clone()));
+    add_command(ASK, ASK_RULES, create_ask_command(ictx.clone()));
+    add_command(ASK_TO, ASK_TO_RULES, create_ask_to_command(ictx.clone()));
+    add_command(
+        ASK_CHOICE,
+        ASK_CHOICE_RULES,
+        create_ask_choice_command(ictx.clone()),
+    );
+    add_command(DELAY, DELAY_RULES, create_delay_command(ictx.clone()));
 
     Arc::new(tokio::sync::Mutex::new(interpreter))
 }
@@ -141,15 +388,7 @@ pub fn create_custom_interpreter(ctx: &Context<'_>) -> Arc<tokio::sync::Mutex<Fs
 const MAX_CALLS: u16 = 200;
 async fn check_limits(ictx: InterpreterContext) -> Result<(), CommandError> {
     let mut rate_limit = ictx.rate_limit.lock().await;
-    let mut call_count = ictx.command_call_count.lock().await;
-    if *call_count >= MAX_CALLS {
-        *call_count = 0;
-        return Err(CommandError::Custom(format!(
-            "cannot use commands that send messages more than {} per generation",
-            MAX_CALLS
-        )));
-    }
-    *call_count = call_count.saturating_add(1);
+    count_call(&ictx.command_call_count).await?;
 
     match rate_limit.check(ictx.author_id) {
         crate::rate_limiter::RateLimitResult::MaxLimitsReached => {
@@ -165,6 +404,20 @@ async fn check_limits(ictx: InterpreterContext) -> Result<(), CommandError> {
     }
 }
 
+/// Counts a call toward [`MAX_CALLS`], failing and starting over once it is reached
+async fn count_call(command_call_count: &Mutex<u16>) -> Result<(), CommandError> {
+    let mut call_count = command_call_count.lock().await;
+    if *call_count >= MAX_CALLS {
+        *call_count = 0;
+        return Err(CommandError::Custom(format!(
+            "cannot use commands that send messages more than {} per generation",
+            MAX_CALLS
+        )));
+    }
+    *call_count = call_count.saturating_add(1);
+    Ok(())
+}
+
 const SAY: &str = "say";
 const SAY_RULES: &'static [ArgRule] = &[ArgRule::new(ArgPos::Index(0), TEXT_TYPES)];
 pub fn create_say_command(ictx: InterpreterContext) -> Executor {
@@ -184,9 +437,14 @@ pub fn create_say_command(ictx: InterpreterContext) -> Executor {
                     .as_text(interpreter_data)
                     .await?;
 
-                let message = ictx.generate_message(&message).await?;
+                let output = ictx.generate_output(&message).await?;
 
-                ictx.channel_id.say(&ictx.http, message).await.ok();
+                send_if_visible(output, |message| async move {
+                    ictx.check_generate_channel(ictx.channel_id).await?;
+                    ictx.channel_id.say(&ictx.http, message).await.ok();
+                    Ok(())
+                })
+                .await?;
 
                 Ok(Value::None)
             }
@@ -222,7 +480,48 @@ pub fn create_say_to_command(ictx: InterpreterContext) -> Executor {
                     .as_text(interpreter_data)
                     .await?;
 
-                ictx.say_to_user(&user_name, &ictx.generate_message(&message).await?)
+                let output = ictx.generate_output(&message).await?;
+
+                send_if_visible(output, |message| async move {
+                    ictx.say_to_user(&user_name, &message).await
+                })
+                .await?;
+
+                Ok(Value::None)
+            }
+        }
+    };
+    Some(Arc::new(say_command))
+}
+
+const SAY_IN: &str = "say_in";
+const SAY_IN_RULES: &'static [ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(1), TEXT_TYPES),
+];
+pub fn create_say_in_command(ictx: InterpreterContext) -> Executor {
+    let say_command = {
+        let ictx = ictx.clone();
+        move |command: Command, interpreter_data: Arc<InterpreterData>| {
+            let ictx = ictx.clone();
+            async move {
+                check_limits(ictx.clone()).await?;
+
+                sleep(Duration::from_millis(COMMAND_MESSAGE_DELAY_MS)).await;
+
+                let mut values = command.take_args();
+                let channel_name = values
+                    .pop_front()
+                    .unwrap()
+                    .as_text(interpreter_data.clone())
+                    .await?;
+                let message = values
+                    .pop_front()
+                    .unwrap()
+                    .as_text(interpreter_data)
+                    .await?;
+
+                ictx.say_in_channel(&channel_name, &ictx.generate_message(&message).await?)
                     .await?;
 
                 Ok(Value::None)
@@ -358,6 +657,234 @@ pub fn create_ask_to_command(ictx: InterpreterContext) -> Executor {
     Some(Arc::new(ask_command))
 }
 
+const ASK_CHOICE: &str = "ask_choice";
+const ASK_CHOICE_RULES: &'static [ArgRule] = &[
+    ArgRule::new(ArgPos::Index(0), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(1), TEXT_TYPES),
+    ArgRule::new(ArgPos::Index(2), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(3), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(4), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(5), TEXT_TYPES),
+    ArgRule::new(ArgPos::OptionalIndex(6), TEXT_TYPES),
+];
+/// Asks the author to pick one of the options with buttons
+///
+/// A number after the options is taken as the timeout in seconds
+pub fn create_ask_choice_command(ictx: InterpreterContext) -> Executor {
+    const DEFAULT_TIMEOUT_SECS: f64 = 60.0 * 2.0;
+    const MAX_TIMEOUT_SECS: f64 = 60.0 * 10.0;
+    let ask_choice_command = {
+        move |command: Command, data: Arc<InterpreterData>| {
+            let ictx = ictx.clone();
+            async move {
+                check_limits(ictx.clone()).await?;
+
+                sleep(Duration::from_millis(COMMAND_MESSAGE_DELAY_MS)).await;
+
+                let mut values: Vec<Value> = command.take_args().into_iter().collect();
+
+                let time_out = if values.len() > 3
+                    && matches!(values.last(), Some(Value::Int(_) | Value::Float(_)))
+                {
+                    values.pop().unwrap().as_float(data.clone()).await?
+                } else {
+                    DEFAULT_TIMEOUT_SECS
+                };
+                validate_time_out(time_out, MAX_TIMEOUT_SECS)?;
+
+                let mut values = values.into_iter();
+                let question = values.next().unwrap().as_text(data.clone()).await?;
+                let question = format!("{}\n{}", ictx.author_id.mention(), question);
+
+                let mut options = Vec::new();
+                for value in values {
+                    let option = value.as_text(data.clone()).await?;
+                    options.push(ictx.generate_message(&option).await?);
+                }
+                validate_choice_options(&options)?;
+
+                let prefix = ask_choice_id_prefix(ictx.channel_id, Uuid::new_v4());
+                let mut message = ictx
+                    .channel_id
+                    .send_message(
+                        &ictx.http,
+                        CreateMessage::new()
+                            .content(ictx.generate_message(&question).await?)
+                            .components(create_choice_buttons(&prefix, &options)),
+                    )
+                    .await
+                    .map_err(|e| CommandError::Custom(e.to_string()))?;
+
+                let waiter = ComponentChoiceWaiter {
+                    shard: ictx.shard.clone(),
+                    http: ictx.http.clone(),
+                };
+                let outcome = wait_for_choice(
+                    &waiter,
+                    &prefix,
+                    ictx.author_id,
+                    Duration::from_secs_f64(time_out),
+                )
+                .await;
+
+                message
+                    .edit(&ictx.http, EditMessage::new().components(Vec::new()))
+                    .await
+                    .ok();
+
+                chosen_option(&options, outcome).map(Value::Text)
+            }
+        }
+    };
+    Some(Arc::new(ask_choice_command))
+}
+
+/// Checks there are between two and [`MAX_CHOICES`] options and none are blank
+fn validate_choice_options(options: &[String]) -> Result<(), CommandError> {
+    if !(2..=MAX_CHOICES).contains(&options.len()) {
+        return Err(CommandError::Custom(format!(
+            "ask_choice needs between 2 and {} options",
+            MAX_CHOICES
+        )));
+    } else if options.iter().any(|option| option.trim().is_empty()) {
+        return Err(CommandError::Custom(format!(
+            "ask_choice options cannot be empty"
+        )));
+    }
+    Ok(())
+}
+
+/// Waits for a click on one of the buttons of an ask_choice question
+pub trait ChoiceWaiter {
+    /// Returns the custom id of the clicked button or None if the timeout ended first
+    fn wait_for_click(
+        &self,
+        prefix: &str,
+        user_id: UserId,
+        time_out: Duration,
+    ) -> impl Future<Output = Option<String>> + Send;
+}
+
+/// Waits for the button through the gateway, acknowledging the click
+struct ComponentChoiceWaiter {
+    shard: ShardMessenger,
+    http: Arc<Http>,
+}
+
+impl ChoiceWaiter for ComponentChoiceWaiter {
+    fn wait_for_click(
+        &self,
+        prefix: &str,
+        user_id: UserId,
+        time_out: Duration,
+    ) -> impl Future<Output = Option<String>> + Send {
+        let prefix = prefix.to_string();
+        let collector = ComponentInteractionCollector::new(&self.shard)
+            .author_id(user_id)
+            .timeout(time_out)
+            .filter(move |interaction| interaction.data.custom_id.starts_with(&prefix));
+        let http = self.http.clone();
+
+        async move {
+            let interaction = collector.next().await?;
+            interaction
+                .create_response(&http, CreateInteractionResponse::Acknowledge)
+                .await
+                .ok();
+            Some(interaction.data.custom_id)
+        }
+    }
+}
+
+async fn wait_for_choice(
+    waiter: &impl ChoiceWaiter,
+    prefix: &str,
+    user_id: UserId,
+    time_out: Duration,
+) -> ChoiceOutcome {
+    match waiter.wait_for_click(prefix, user_id, time_out).await {
+        Some(custom_id) => {
+            parse_ask_choice_button_id(prefix, &custom_id).unwrap_or(ChoiceOutcome::TimedOut)
+        }
+        None => ChoiceOutcome::TimedOut,
+    }
+}
+
+/// Maps the outcome to the text of the chosen option, stopping and timing out are errors
+fn chosen_option(options: &[String], outcome: ChoiceOutcome) -> Result<String, CommandError> {
+    match outcome {
+        ChoiceOutcome::Chosen(index) => options
+            .get(index)
+            .cloned()
+            .ok_or_else(|| CommandError::Custom(format!("{} is not a valid choice", index + 1))),
+        ChoiceOutcome::Stopped => Err(CommandError::Custom("User quit the program".into())),
+        ChoiceOutcome::TimedOut => Err(CommandError::Custom(format!(
+            "Didn't receive a choice before timeout ended"
+        ))),
+    }
+}
+
+const DELAY: &str = "delay";
+const DELAY_RULES: &'static [ArgRule] = &[ArgRule::new(ArgPos::Index(0), NUMERIC_TYPES)];
+/// Longest a single delay() may wait
+const MAX_DELAY_SECS: f64 = 10.0;
+/// Longest every delay() of a generation may wait together
+const MAX_TOTAL_DELAY: Duration = Duration::from_secs(60);
+/// Pauses a script between says, counted like a command that sends a message
+pub fn create_delay_command(ictx: InterpreterContext) -> Executor {
+    let delay_command = {
+        move |command: Command, data: Arc<InterpreterData>| {
+            let ictx = ictx.clone();
+            async move {
+                check_limits(ictx.clone()).await?;
+
+                let mut values = command.take_args();
+                let seconds = values.pop_front().unwrap().as_float(data).await?;
+                delay(seconds, &ictx.delay_total).await?;
+
+                Ok(Value::None)
+            }
+        }
+    };
+    Some(Arc::new(delay_command))
+}
+
+/// Waits for seconds unless it would take the total waited past [`MAX_TOTAL_DELAY`]
+async fn delay(seconds: f64, delay_total: &Mutex<Duration>) -> Result<(), CommandError> {
+    validate_delay(seconds, MAX_DELAY_SECS)?;
+    let delay = Duration::from_secs_f64(seconds);
+    {
+        let mut total = delay_total.lock().await;
+        if *total + delay > MAX_TOTAL_DELAY {
+            return Err(CommandError::Custom(format!(
+                "cannot delay for {} more seconds after {} seconds, delays cannot add up to more than {} seconds per generation",
+                seconds,
+                total.as_secs_f64(),
+                MAX_TOTAL_DELAY.as_secs()
+            )));
+        }
+        *total += delay;
+    }
+    sleep(delay).await;
+    Ok(())
+}
+
+pub fn validate_delay(seconds: f64, max: f64) -> Result<(), CommandError> {
+    if !seconds.is_finite() {
+        return Err(CommandError::NonFiniteValue);
+    } else if seconds.is_sign_negative() {
+        return Err(CommandError::Custom(format!(
+            "delay cannot be a negative number"
+        )));
+    } else if seconds > max {
+        return Err(CommandError::Custom(format!(
+            "delay cannot be greater than {} seconds",
+            max
+        )));
+    }
+    Ok(())
+}
+
 pub fn validate_time_out(time_out: f64, max: f64) -> Result<(), CommandError> {
     if !time_out.is_finite() {
         return Err(CommandError::NonFiniteValue);
@@ -373,3 +900,135 @@ pub fn validate_time_out(time_out: f64, max: f64) -> Result<(), CommandError> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod interpreter_test {
+    use super::*;
+
+    struct FixedClick(Option<String>);
+
+    impl ChoiceWaiter for FixedClick {
+        fn wait_for_click(
+            &self,
+            _prefix: &str,
+            _user_id: UserId,
+            _time_out: Duration,
+        ) -> impl Future<Output = Option<String>> + Send {
+            let click = self.0.clone();
+            async move { click }
+        }
+    }
+
+    fn options(count: usize) -> Vec<String> {
+        (1..=count).map(|n| format!("option {}", n)).collect()
+    }
+
+    async fn answer(click: Option<String>, prefix: &str) -> Result<String, CommandError> {
+        let outcome =
+            wait_for_choice(&FixedClick(click), prefix, UserId::new(1), Duration::ZERO).await;
+        chosen_option(&options(3), outcome)
+    }
+
+    #[test]
+    fn choice_option_count() {
+        assert!(validate_choice_options(&options(1)).is_err());
+        assert!(validate_choice_options(&options(2)).is_ok());
+        assert!(validate_choice_options(&options(MAX_CHOICES)).is_ok());
+        assert!(validate_choice_options(&options(MAX_CHOICES + 1)).is_err());
+        assert!(validate_choice_options(&["yes".into(), " ".into()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn invisible_output_is_not_sent() {
+        let mut sent = Vec::new();
+        for (text, visible) in [
+            ("", false),
+            ("\n\n", false),
+            ("\u{200B}", false),
+            (" \u{200B}hi\n", true),
+        ] {
+            let was_sent = send_if_visible(GenerationOutput::new(text.to_string(), 1), |message| {
+                sent.push(message);
+                async { Ok(()) }
+            })
+            .await
+            .unwrap();
+            assert!(was_sent == visible, "{:?}", text);
+        }
+        assert!(sent == vec![" \u{200B}hi\n"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_are_capped_per_call() {
+        let total = Mutex::new(Duration::ZERO);
+        let start = tokio::time::Instant::now();
+
+        delay(2.5, &total).await.unwrap();
+        assert!(start.elapsed() == Duration::from_secs_f64(2.5));
+
+        for seconds in [MAX_DELAY_SECS + 0.5, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(delay(seconds, &total).await.is_err(), "{}", seconds);
+        }
+        assert!(start.elapsed() == Duration::from_secs_f64(2.5));
+        assert!(*total.lock().await == Duration::from_secs_f64(2.5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_are_capped_per_generation() {
+        let total = Mutex::new(Duration::ZERO);
+        let start = tokio::time::Instant::now();
+
+        for _ in 0..6 {
+            delay(MAX_DELAY_SECS, &total).await.unwrap();
+        }
+        assert!(start.elapsed() == MAX_TOTAL_DELAY);
+
+        let exceeded = delay(0.5, &total).await;
+        assert!(exceeded.is_err_and(|e| matches!(
+            e,
+            CommandError::Custom(m) if m.contains("0.5 more seconds after 60 seconds")
+        )));
+        assert!(start.elapsed() == MAX_TOTAL_DELAY);
+        delay(0.0, &total).await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_count_toward_max_calls() {
+        let calls = Mutex::new(0);
+        let total = Mutex::new(Duration::ZERO);
+
+        for _ in 0..MAX_CALLS {
+            count_call(&calls).await.unwrap();
+            delay(0.0, &total).await.unwrap();
+        }
+        assert!(count_call(&calls).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn choice_outcomes() {
+        let prefix = ask_choice_id_prefix(ChannelId::new(1), Uuid::new_v4());
+
+        assert!(
+            answer(Some(format!("{} 1", prefix)), &prefix)
+                .await
+                .is_ok_and(|option| option == "option 2")
+        );
+        assert!(
+            answer(Some(format!("{} stop", prefix)), &prefix)
+                .await
+                .is_err_and(
+                    |e| matches!(e, CommandError::Custom(m) if m == "User quit the program")
+                )
+        );
+        assert!(
+            answer(None, &prefix).await.is_err_and(
+                |e| matches!(e, CommandError::Custom(m) if m.contains("timeout ended"))
+            )
+        );
+        assert!(
+            answer(Some(format!("{} 7", prefix)), &prefix)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use fsl_interpreter::{
+    InterpreterData,
+    types::{
+        command::{Command, CommandError, Executor},
+        value::Value,
+    },
+};
+use funboy_core::Funboy;
+use serenity::all::{GuildId, Member, RoleId};
+
+use super::{ASK, ASK_CHOICE, ASK_TO, SAY, SAY_IN, SAY_TO};
+
+/// FSL commands that send messages or prompt other users, only registered when permitted
+pub const SIDE_EFFECT_COMMANDS: &[&str] = &[SAY, SAY_TO, SAY_IN, ASK, ASK_TO, ASK_CHOICE];
+
+/// Which FSL commands a generation may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandPermissions {
+    /// Whether [`SIDE_EFFECT_COMMANDS`] may be used
+    pub side_effects: bool,
+}
+
+impl CommandPermissions {
+    pub const ALL: Self = Self { side_effects: true };
+    pub const RESTRICTED: Self = Self {
+        side_effects: false,
+    };
+
+    /// Everyone is permitted while a guild allows no roles, otherwise admins and members with an
+    /// allowed role are
+    pub fn for_member(allowed_roles: &[RoleId], member_roles: &[RoleId], is_admin: bool) -> Self {
+        if allowed_roles.is_empty()
+            || is_admin
+            || member_roles.iter().any(|role| allowed_roles.contains(role))
+        {
+            Self::ALL
+        } else {
+            Self::RESTRICTED
+        }
+    }
+
+    /// Looks up the allowed roles of a guild, generations outside of a guild are unrestricted
+    pub async fn resolve(
+        funboy: &Funboy,
+        guild_id: Option<GuildId>,
+        member: Option<&Member>,
+    ) -> Self {
+        let Some(guild_id) = guild_id else {
+            return Self::ALL;
+        };
+
+        let allowed_roles = match funboy.get_fsl_allowed_roles(guild_id.get()).await {
+            Ok(roles) => roles.into_iter().map(RoleId::new).collect::<Vec<_>>(),
+            Err(e) => {
+                tracing::warn!(error = %e.to_string(), "failed to read fsl permissions");
+                return Self::RESTRICTED;
+            }
+        };
+
+        match member {
+            Some(member) => {
+                let is_admin = member
+                    .permissions
+                    .is_some_and(|permissions| permissions.administrator());
+                Self::for_member(&allowed_roles, &member.roles, is_admin)
+            }
+            None => Self::for_member(&allowed_roles, &[], false),
+        }
+    }
+
+    pub fn allows(&self, command: &str) -> bool {
+        self.side_effects || !SIDE_EFFECT_COMMANDS.contains(&command)
+    }
+}
+
+pub fn denied_message(command: &str) -> String {
+    format!(
+        "you don't have permission to use {}() in this server",
+        command
+    )
+}
+
+/// Stands in for a command that isn't permitted so scripts fail with a clear error
+pub fn create_denied_command(command: &'static str) -> Executor {
+    let denied_command = move |_command: Command, _data: Arc<InterpreterData>| async move {
+        Err::<Value, CommandError>(CommandError::Custom(denied_message(command)))
+    };
+    Some(Arc::new(denied_command))
+}
+
+#[cfg(test)]
+mod command_permissions_test {
+    use super::*;
+
+    const MODERATOR: RoleId = RoleId::new(1);
+    const MEMBER: RoleId = RoleId::new(2);
+
+    #[test]
+    fn empty_allowlist_permits_everyone() {
+        assert_eq!(
+            CommandPermissions::for_member(&[], &[], false),
+            CommandPermissions::ALL
+        );
+        assert_eq!(
+            CommandPermissions::for_member(&[], &[MEMBER], false),
+            CommandPermissions::ALL
+        );
+    }
+
+    #[test]
+    fn allowlist_permits_roles_and_admins() {
+        let allowed = [MODERATOR];
+        assert_eq!(
+            CommandPermissions::for_member(&allowed, &[MEMBER, MODERATOR], false),
+            CommandPermissions::ALL
+        );
+        assert_eq!(
+            CommandPermissions::for_member(&allowed, &[MEMBER], false),
+            CommandPermissions::RESTRICTED
+        );
+        assert_eq!(
+            CommandPermissions::for_member(&allowed, &[], false),
+            CommandPermissions::RESTRICTED
+        );
+        assert_eq!(
+            CommandPermissions::for_member(&allowed, &[MEMBER], true),
+            CommandPermissions::ALL
+        );
+    }
+
+    #[test]
+    fn registration_matrix() {
+        for command in SIDE_EFFECT_COMMANDS {
+            assert!(CommandPermissions::ALL.allows(command));
+            assert!(!CommandPermissions::RESTRICTED.allows(command));
+        }
+        for command in ["print", "repeat", "random_range"] {
+            assert!(CommandPermissions::ALL.allows(command));
+            assert!(CommandPermissions::RESTRICTED.allows(command));
+        }
+    }
+
+    #[test]
+    fn denied_message_names_the_command() {
+        assert_eq!(
+            denied_message(SAY),
+            "you don't have permission to use say() in this server"
+        );
+        assert_eq!(
+            denied_message(ASK_TO),
+            "you don't have permission to use ask_to() in this server"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use funboy_core::edit_distance::{
+    MAX_FUZZY_DISTANCE, MAX_SUGGESTIONS, edit_distance, max_suggestion_distance,
+};
+use serenity::all::{Member, UserId};
+
+/// The names a guild member can be referred to by inside of FSL scripts
+#[derive(Debug, Clone)]
+pub struct MemberEntry {
+    pub id: UserId,
+    pub name: String,
+    pub tag: String,
+    pub display_name: String,
+    pub nick: Option<String>,
+}
+
+impl MemberEntry {
+    /// The name the member goes by in the guild
+    pub fn guild_name(&self) -> &str {
+        self.nick.as_deref().unwrap_or(&self.display_name)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        [
+            Some(self.name.as_str()),
+            Some(self.tag.as_str()),
+            Some(self.display_name.as_str()),
+            self.nick.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl From<&Member> for MemberEntry {
+    fn from(member: &Member) -> Self {
+        Self {
+            id: member.user.id,
+            name: member.user.name.clone(),
+            tag: member.user.tag(),
+            display_name: member.user.display_name().to_string(),
+            nick: member.nick.clone(),
+        }
+    }
+}
+
+/// Parses `<@id>`, `<@!id>`, or a bare numeric id
+///
+/// Returns whether the id was written as a mention alongside the id
+fn parse_user_id(input: &str) -> Option<(UserId, bool)> {
+    let mention = input
+        .strip_prefix("<@")
+        .and_then(|id| id.strip_suffix('>'))
+        .map(|id| id.trim_start_matches('!'));
+
+    let (id, is_mention) = match mention {
+        Some(id) => (id, true),
+        None => (input, false),
+    };
+
+    match id.parse::<u64>() {
+        Ok(id) if id != 0 => Some((UserId::new(id), is_mention)),
+        _ => None,
+    }
+}
+
+/// Resolves user input to a member id
+///
+/// Resolution priority is id or mention, exact name, case-insensitive name, then a single
+/// unambiguous close name. Ids and mentions only resolve to members of the guild. When nothing matches the error suggests up to three close names, names
+/// further than [`max_suggestion_distance`] from input are never suggested.
+pub fn resolve_member(input: &str, members: &[MemberEntry]) -> Result<UserId, String> {
+    let input = input.trim();
+
+    if let Some((id, is_mention)) = parse_user_id(input) {
+        if members.iter().any(|member| member.id == id) {
+            return Ok(id);
+        }
+        // a mention names a user by id alone so there is no name to fall back on
+        if is_mention {
+            return Err(format!("no user {} found in this server", input));
+        }
+    }
+
+    if let Some(member) = members
+        .iter()
+        .find(|member| member.names().any(|name| name == input))
+    {
+        return Ok(member.id);
+    }
+
+    let lowercase_input = input.to_lowercase();
+    if let Some(member) = members.iter().find(|member| {
+        member
+            .names()
+            .any(|name| name.to_lowercase() == lowercase_input)
+    }) {
+        return Ok(member.id);
+    }
+
+    let mut distances: Vec<(usize, &MemberEntry)> = members
+        .iter()
+        .map(|member| {
+            let distance = member
+                .names()
+                .map(|name| edit_distance(&name.to_lowercase(), &lowercase_input))
+                .min()
+                .unwrap_or(usize::MAX);
+            (distance, member)
+        })
+        .collect();
+    distances.sort_by_key(|(distance, _)| *distance);
+
+    match distances.as_slice() {
+        [(closest, member), rest @ ..]
+            if *closest <= MAX_FUZZY_DISTANCE
+                && rest.first().is_none_or(|(next, _)| next > closest) =>
+        {
+            return Ok(member.id);
+        }
+        _ => {}
+    }
+
+    let max_distance = max_suggestion_distance(&lowercase_input);
+    let suggestions: Vec<&str> = distances
+        .iter()
+        .take_while(|(distance, _)| *distance <= max_distance)
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, member)| member.display_name.as_str())
+        .collect();
+
+    if suggestions.is_empty() {
+        Err(format!("no user named {} found", input))
+    } else {
+        Err(format!(
+            "no user named {} found, did you mean {}?",
+            input,
+            suggestions.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod member_resolver_test {
+    use super::*;
+
+    fn member(id: u64, name: &str, display_name: &str, nick: Option<&str>) -> MemberEntry {
+        MemberEntry {
+            id: UserId::new(id),
+            name: name.to_string(),
+            tag: name.to_string(),
+            display_name: display_name.to_string(),
+            nick: nick.map(|nick| nick.to_string()),
+        }
+    }
+
+    fn fixture() -> Vec<MemberEntry> {
+        vec![
+            member(1, "alice", "Alice", None),
+            member(2, "Bob", "Bobby", Some("bob")),
+            member(3, "charlie", "Charlie", Some("chaz")),
+            member(4, "1", "Numbers", None),
+            member(5, "daniel", "Dan", None),
+            member(6, "danielle", "Dani", None),
+        ]
+    }
+
+    #[test]
+    fn resolves_mentions_and_ids() {
+        let members = fixture();
+        assert_eq!(resolve_member("<@3>", &members), Ok(UserId::new(3)));
+        assert_eq!(resolve_member("<@!3>", &members), Ok(UserId::new(3)));
+        assert_eq!(resolve_member("5", &members), Ok(UserId::new(5)));
+    }
+
+    #[test]
+    fn mentions_of_non_members_are_rejected() {
+        assert_eq!(
+            resolve_member("<@999>", &fixture()),
+            Err("no user <@999> found in this server".to_string())
+        );
+        assert_eq!(
+            resolve_member("<@!999>", &[]),
+            Err("no user <@!999> found in this server".to_string())
+        );
+    }
+
+    #[test]
+    fn id_beats_name() {
+        // "1" is both member 1's id and member 4's name
+        assert_eq!(resolve_member("1", &fixture()), Ok(UserId::new(1)));
+    }
+
+    #[test]
+    fn exact_beats_case_insensitive() {
+        // "bob" is member 2's nick exactly while "Bob" is its name
+        let mut members = fixture();
+        members.insert(0, member(7, "BOB", "Robert", None));
+        assert_eq!(resolve_member("bob", &members), Ok(UserId::new(2)));
+        assert_eq!(resolve_member("BOB", &members), Ok(UserId::new(7)));
+    }
+
+    #[test]
+    fn case_insensitive_beats_fuzzy() {
+        assert_eq!(resolve_member("ALICE", &fixture()), Ok(UserId::new(1)));
+        assert_eq!(resolve_member("CHAZ", &fixture()), Ok(UserId::new(3)));
+    }
+
+    #[test]
+    fn fuzzy_resolves_unambiguous_typos() {
+        assert_eq!(resolve_member("charlei", &fixture()), Ok(UserId::new(3)));
+        assert_eq!(resolve_member("alicee", &fixture()), Ok(UserId::new(1)));
+    }
+
+    #[test]
+    fn ambiguous_or_distant_names_suggest() {
+        let error = resolve_member("danie", &fixture()).unwrap_err();
+        assert!(error.contains("did you mean"));
+        assert!(error.contains("Dan"));
+        assert!(error.contains("Dani"));
+
+        let mut members = fixture();
+        members.push(member(8, "danny", "Danny", None));
+        members.push(member(9, "dana", "Dana", None));
+        let error = resolve_member("dan_", &members).unwrap_err();
+        let suggested = error.split("did you mean ").nth(1).unwrap();
+        assert_eq!(suggested.matches(", ").count(), MAX_SUGGESTIONS - 1);
+    }
+
+    #[test]
+    fn distant_names_are_not_suggested() {
+        assert_eq!(
+            resolve_member("zzzzzzzz", &fixture()),
+            Err("no user named zzzzzzzz found".to_string())
+        );
+        assert_eq!(
+            resolve_member("bartholomew", &fixture()),
+            Err("no user named bartholomew found".to_string())
+        );
+    }
+
+    #[test]
+    fn no_members() {
+        assert_eq!(
+            resolve_member("alice", &[]),
+            Err("no user named alice found".to_string())
+        );
+    }
+
+    #[test]
+    fn guild_name_prefers_nick() {
+        let members = fixture();
+        assert_eq!(members[0].guild_name(), "Alice");
+        assert_eq!(members[2].guild_name(), "chaz");
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use funboy_core::Funboy;
+use rand::seq::IndexedRandom;
+use serenity::all::{GuildId, Http};
+use tokio::sync::OnceCell;
+
+use super::member_resolver::MemberEntry;
+
+pub const MEMBER_TEMPLATE: &str = "_member";
+pub const EMOJI_TEMPLATE: &str = "_emoji";
+
+/// Templates filled from the guild instead of the database, reserved so they can't be created
+pub const VIRTUAL_TEMPLATE_NAMES: &[&str] = &[MEMBER_TEMPLATE, EMOJI_TEMPLATE];
+
+/// The guild data virtual templates of a single generation pick from
+///
+/// Members and emojis are fetched the first time they are needed and kept for the generation
+#[derive(Clone)]
+pub struct GuildSources {
+    http: Arc<Http>,
+    guild_id: Option<GuildId>,
+    members: Arc<OnceCell<Vec<MemberEntry>>>,
+    emojis: Arc<OnceCell<Vec<String>>>,
+}
+
+impl GuildSources {
+    pub fn new(
+        http: Arc<Http>,
+        guild_id: Option<GuildId>,
+        members: Arc<OnceCell<Vec<MemberEntry>>>,
+    ) -> Self {
+        Self {
+            http,
+            guild_id,
+            members,
+            emojis: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// A clone of funboy for a generation in this guild
+    ///
+    /// It resolves the virtual templates from the guild and counts template uses toward it
+    pub fn attach(self, funboy: &Funboy) -> Funboy {
+        let mut funboy = funboy.clone();
+        funboy.set_usage_guild(self.guild_id.map(|guild_id| guild_id.get()));
+        funboy.set_fallback_resolver(Arc::new(move |template: &str| {
+            let sources = self.clone();
+            let template = template.to_string();
+            Box::pin(async move { sources.resolve(&template).await })
+        }));
+        funboy
+    }
+
+    async fn resolve(&self, template: &str) -> Option<String> {
+        match template {
+            MEMBER_TEMPLATE => {
+                let members = self.get_members().await?;
+                members
+                    .choose(&mut rand::rng())
+                    .map(|member| member.guild_name().to_string())
+            }
+            EMOJI_TEMPLATE => {
+                let emojis = self.get_emojis().await?;
+                emojis.choose(&mut rand::rng()).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    async fn get_members(&self) -> Option<&[MemberEntry]> {
+        let guild_id = self.guild_id?;
+        let members = self
+            .members
+            .get_or_try_init(|| async {
+                let members = guild_id.members(&self.http, None, None).await?;
+                Ok::<_, serenity::Error>(members.iter().map(MemberEntry::from).collect())
+            })
+            .await
+            .ok()?;
+
+        Some(members)
+    }
+
+    async fn get_emojis(&self) -> Option<&[String]> {
+        let guild_id = self.guild_id?;
+        let emojis = self
+            .emojis
+            .get_or_try_init(|| async {
+                let emojis = guild_id.emojis(&self.http).await?;
+                Ok::<_, serenity::Error>(emojis.iter().map(|emoji| emoji.to_string()).collect())
+            })
+            .await
+            .ok()?;
+
+        Some(emojis)
+    }
+}
@@ -1,4 +1,5 @@
 pub mod context_extension;
-pub mod discord_message_format;
-pub mod quote_filter;
+pub use funboy_core::message_format as discord_message_format;
+pub mod generation_format;
 pub mod str_extension;
+pub mod text_diff;
@@ -32,6 +32,13 @@ pub trait ContextExtension {
         message: &str,
         ephemeral: bool,
     ) -> Result<(), Error>;
+
+    async fn edit_messages<'b>(
+        &self,
+        original_message: ReplyHandle<'b>,
+        messages: &[&str],
+        ephemeral: bool,
+    ) -> Result<(), Error>;
 }
 
 impl<'a> ContextExtension for Context<'a> {
@@ -129,7 +136,31 @@ impl<'a> ContextExtension for Context<'a> {
             return Ok(());
         }
 
-        for (i, m) in split_message(message).iter().enumerate() {
+        self.edit_messages(original_message, &split_message(message), ephemeral)
+            .await
+    }
+
+    /// Replaces original_message with the first message and sends the rest after it
+    ///
+    /// Each message must already fit within Discord's message limit
+    async fn edit_messages<'b>(
+        &self,
+        original_message: ReplyHandle<'b>,
+        messages: &[&str],
+        ephemeral: bool,
+    ) -> Result<(), Error> {
+        let size = messages
+            .iter()
+            .fold(0usize, |size, m| size.saturating_add(m.len()));
+        if !ephemeral && size > MAX_MESSAGE_CHAIN_SIZE {
+            self.say_ephemeral(WARN_MESSAGE_SIZE_EXCEEDED).await?;
+            return Ok(());
+        } else if size == 0 {
+            self.say_ephemeral(WARN_EMPTY_MESSAGE).await?;
+            return Ok(());
+        }
+
+        for (i, m) in messages.iter().enumerate() {
             if i == 0 {
                 original_message
                     .edit(
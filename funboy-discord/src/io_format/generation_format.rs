@@ -0,0 +1,231 @@
+use std::borrow::Cow;
+
+use poise::ChoiceParameter;
+
+use super::discord_message_format::{
+    DISCORD_CHARACTER_LIMIT, split_message, truncate_on_char_boundary,
+};
+
+pub const EMBED_TITLE_LIMIT: usize = 256;
+pub const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+
+const SPOILER: &str = "||";
+const CODE_FENCE_OPEN: &str = "```\n";
+const CODE_FENCE_CLOSE: &str = "\n```";
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum GenerateFormat {
+    Plain,
+    Embed,
+    Spoiler,
+    #[name = "Code Block"]
+    CodeBlock,
+}
+
+/// Generated output prepared to be sent in a given format
+#[derive(Debug, PartialEq, Eq)]
+pub enum RenderedGeneration {
+    /// Messages that each fit inside of a single Discord message
+    Messages(Vec<String>),
+    /// The description of an embed
+    Embed(String),
+    /// Output too large for its format that should be sent as a file
+    Attachment(String),
+}
+
+/// Prepares generated output to be sent in format
+///
+/// Each message fits in Discord's message limit including any markdown the format wraps it in.
+/// Embed output longer than an embed description is returned as an attachment instead.
+pub fn render_generation(output: &str, format: GenerateFormat) -> RenderedGeneration {
+    match format {
+        GenerateFormat::Plain => RenderedGeneration::Messages(
+            split_message(output)
+                .into_iter()
+                .map(|message| message.to_string())
+                .collect(),
+        ),
+        GenerateFormat::Embed => {
+            if output.len() <= EMBED_DESCRIPTION_LIMIT {
+                RenderedGeneration::Embed(output.to_string())
+            } else {
+                RenderedGeneration::Attachment(output.to_string())
+            }
+        }
+        GenerateFormat::Spoiler => {
+            let limit = DISCORD_CHARACTER_LIMIT - SPOILER.len() * 2;
+            RenderedGeneration::Messages(
+                split_preferring_lines(output, limit)
+                    .into_iter()
+                    .map(|chunk| format!("{SPOILER}{chunk}{SPOILER}"))
+                    .collect(),
+            )
+        }
+        GenerateFormat::CodeBlock => {
+            let limit = DISCORD_CHARACTER_LIMIT - CODE_FENCE_OPEN.len() - CODE_FENCE_CLOSE.len();
+            let escaped = escape_code_fences(output);
+            RenderedGeneration::Messages(
+                split_preferring_lines(&escaped, limit)
+                    .into_iter()
+                    .map(|chunk| {
+                        let chunk = chunk.strip_suffix('\n').unwrap_or(chunk);
+                        format!("{CODE_FENCE_OPEN}{chunk}{CODE_FENCE_CLOSE}")
+                    })
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Shortens input to fit in an embed title
+pub fn embed_title(input: &str) -> Cow<'_, str> {
+    if input.len() <= EMBED_TITLE_LIMIT {
+        Cow::Borrowed(input)
+    } else {
+        let ellipsis = "...";
+        Cow::Owned(
+            truncate_on_char_boundary(input, EMBED_TITLE_LIMIT - ellipsis.len()).to_string()
+                + ellipsis,
+        )
+    }
+}
+
+/// Breaks up backtick runs so output cannot close the code block it is wrapped in
+fn escape_code_fences(input: &str) -> Cow<'_, str> {
+    if !input.contains("```") {
+        return Cow::Borrowed(input);
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut run = 0;
+    for ch in input.chars() {
+        if ch == '`' {
+            if run == 2 {
+                output.push(ZERO_WIDTH_SPACE);
+                run = 0;
+            }
+            run += 1;
+        } else {
+            run = 0;
+        }
+        output.push(ch);
+    }
+    Cow::Owned(output)
+}
+
+/// Splits input into chunks of at most limit bytes
+///
+/// Chunks end after the last newline that fits, then after the last whitespace, and only split
+/// inside of a word when a single word is longer than limit
+fn split_preferring_lines(input: &str, limit: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = input;
+
+    while rest.len() > limit {
+        let window = truncate_on_char_boundary(rest, limit);
+        let end = window
+            .rfind('\n')
+            .or_else(|| window.rfind(char::is_whitespace))
+            .map(|i| i + 1)
+            .unwrap_or(window.len());
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod generation_format_test {
+    use super::*;
+
+    fn messages(rendered: RenderedGeneration) -> Vec<String> {
+        match rendered {
+            RenderedGeneration::Messages(messages) => messages,
+            other => panic!("expected messages, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn plain_fits_message_limit() {
+        let output = "word ".repeat(1000);
+        let messages = messages(render_generation(&output, GenerateFormat::Plain));
+        assert!(messages.len() > 1);
+        assert!(messages.iter().all(|m| m.len() <= DISCORD_CHARACTER_LIMIT));
+        assert_eq!(messages.concat(), output);
+    }
+
+    #[test]
+    fn embed_falls_back_to_attachment() {
+        let longest = "a".repeat(EMBED_DESCRIPTION_LIMIT);
+        assert_eq!(
+            render_generation(&longest, GenerateFormat::Embed),
+            RenderedGeneration::Embed(longest.clone())
+        );
+
+        let too_long = "a".repeat(EMBED_DESCRIPTION_LIMIT + 1);
+        assert_eq!(
+            render_generation(&too_long, GenerateFormat::Embed),
+            RenderedGeneration::Attachment(too_long.clone())
+        );
+    }
+
+    #[test]
+    fn embed_titles_are_shortened() {
+        assert_eq!(embed_title("short"), "short");
+
+        let title = embed_title(&"é".repeat(EMBED_TITLE_LIMIT));
+        assert!(title.len() <= EMBED_TITLE_LIMIT);
+        assert!(title.ends_with("..."));
+    }
+
+    #[test]
+    fn spoilers_account_for_markers() {
+        let output = "a".repeat(DISCORD_CHARACTER_LIMIT);
+        let messages = messages(render_generation(&output, GenerateFormat::Spoiler));
+        assert_eq!(messages.len(), 2);
+        for message in &messages {
+            assert!(message.len() <= DISCORD_CHARACTER_LIMIT);
+            assert!(message.starts_with(SPOILER) && message.ends_with(SPOILER));
+        }
+    }
+
+    #[test]
+    fn code_blocks_account_for_fences() {
+        let output = "a".repeat(DISCORD_CHARACTER_LIMIT);
+        let messages = messages(render_generation(&output, GenerateFormat::CodeBlock));
+        assert_eq!(messages.len(), 2);
+        for message in &messages {
+            assert!(message.len() <= DISCORD_CHARACTER_LIMIT);
+            assert!(message.starts_with(CODE_FENCE_OPEN) && message.ends_with(CODE_FENCE_CLOSE));
+        }
+    }
+
+    #[test]
+    fn code_blocks_split_between_lines() {
+        let line = format!("{}\n", "#".repeat(99));
+        let output = line.repeat(40);
+        let messages = messages(render_generation(&output, GenerateFormat::CodeBlock));
+        assert!(messages.len() > 1);
+        for message in &messages {
+            assert!(message.len() <= DISCORD_CHARACTER_LIMIT);
+            let body = &message[CODE_FENCE_OPEN.len()..message.len() - CODE_FENCE_CLOSE.len()];
+            assert!(body.lines().all(|l| l.len() == 99));
+        }
+    }
+
+    #[test]
+    fn code_blocks_cannot_be_closed_by_output() {
+        let messages = messages(render_generation(
+            "before ``` after ````",
+            GenerateFormat::CodeBlock,
+        ));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].matches("```").count(), 2);
+    }
+}
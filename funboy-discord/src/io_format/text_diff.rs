@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+/// How many characters an edit added and removed
+///
+/// Characters shared at the start and end of both texts are treated as unchanged,
+/// everything between them counts as removed from the old text and added from the new one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextDiff {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl TextDiff {
+    pub fn new(old: &str, new: &str) -> Self {
+        let old: Vec<char> = old.chars().collect();
+        let new: Vec<char> = new.chars().collect();
+
+        let prefix = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        Self {
+            added: new.len() - prefix - suffix,
+            removed: old.len() - prefix - suffix,
+        }
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.added == 0 && self.removed == 0
+    }
+}
+
+impl Display for TextDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_unchanged() {
+            write!(f, "no changes")
+        } else {
+            write!(f, "+{} -{} chars", self.added, self.removed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod text_diff_test {
+    use super::*;
+
+    #[test]
+    fn unchanged() {
+        let diff = TextDiff::new("the quick fox", "the quick fox");
+        assert!(diff.is_unchanged());
+        assert_eq!(diff.to_string(), "no changes");
+    }
+
+    #[test]
+    fn insertion_and_deletion() {
+        assert_eq!(
+            TextDiff::new("the fox", "the quick fox"),
+            TextDiff {
+                added: 6,
+                removed: 0
+            }
+        );
+        assert_eq!(
+            TextDiff::new("the quick fox", "the fox"),
+            TextDiff {
+                added: 0,
+                removed: 6
+            }
+        );
+    }
+
+    #[test]
+    fn replacement() {
+        let diff = TextDiff::new("the quick fox", "the slow fox");
+        assert_eq!(
+            diff,
+            TextDiff {
+                added: 4,
+                removed: 5
+            }
+        );
+        assert_eq!(diff.to_string(), "+4 -5 chars");
+    }
+
+    #[test]
+    fn repeated_characters_are_not_counted_twice() {
+        // prefix and suffix overlap on the shared "a"s
+        assert_eq!(
+            TextDiff::new("aaa", "aaaa"),
+            TextDiff {
+                added: 1,
+                removed: 0
+            }
+        );
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        assert_eq!(
+            TextDiff::new("café", "cafés"),
+            TextDiff {
+                added: 1,
+                removed: 0
+            }
+        );
+        assert_eq!(
+            TextDiff::new("é", "e"),
+            TextDiff {
+                added: 1,
+                removed: 1
+            }
+        );
+    }
+}
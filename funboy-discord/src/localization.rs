@@ -0,0 +1,237 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde::Deserialize;
+
+/// Locale every message is written in first, translations missing a message fall back to it
+pub const FALLBACK_LOCALE: &str = "en-US";
+
+/// Every bundled locale by its Discord locale code
+const LOCALE_FILES: &[(&str, &str)] = &[
+    ("en-US", include_str!("../locales/en-US.json")),
+    ("es-ES", include_str!("../locales/es-ES.json")),
+];
+
+static LOCALES: OnceLock<HashMap<String, Locale>> = OnceLock::new();
+
+/// The name and description a command is shown with in a locale
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CommandLocalization {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Translations of a single locale
+///
+/// English command names and descriptions come from the commands themselves so only the
+/// messages are listed for [`FALLBACK_LOCALE`]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Locale {
+    #[serde(default)]
+    pub commands: HashMap<String, CommandLocalization>,
+    #[serde(default)]
+    pub messages: HashMap<String, String>,
+}
+
+/// Every bundled locale by its Discord locale code
+pub fn locales() -> &'static HashMap<String, Locale> {
+    LOCALES.get_or_init(|| {
+        LOCALE_FILES
+            .iter()
+            .map(|(code, file)| {
+                let locale = serde_json::from_str(file)
+                    .unwrap_or_else(|e| panic!("locale {} should be valid json: {}", code, e));
+                (code.to_string(), locale)
+            })
+            .collect()
+    })
+}
+
+/// Adds the name and description each locale has for a command to it and its subcommands
+pub fn localize_commands<U, E>(
+    commands: &mut [poise::Command<U, E>],
+    locales: &HashMap<String, Locale>,
+) {
+    for command in commands {
+        for (code, locale) in locales {
+            let Some(localization) = locale.commands.get(&command.name) else {
+                continue;
+            };
+            if let Some(name) = &localization.name {
+                command
+                    .name_localizations
+                    .insert(code.clone(), name.clone());
+            }
+            if let Some(description) = &localization.description {
+                command
+                    .description_localizations
+                    .insert(code.clone(), description.clone());
+            }
+        }
+        localize_commands(&mut command.subcommands, locales);
+    }
+}
+
+/// The message key translated for locale with each `{name}` in it replaced by its value in args
+///
+/// Messages missing from locale are read from [`FALLBACK_LOCALE`] and keys missing there too are
+/// returned as they are so a missing translation never hides a response
+pub fn tr(locale: Option<&str>, key: &str, args: &[(&str, &str)]) -> String {
+    translate(locales(), locale, key, args)
+}
+
+/// Picks the `_one` or `_other` form of key for count like [`tr`]
+pub fn tr_count(locale: Option<&str>, key: &str, count: u64, args: &[(&str, &str)]) -> String {
+    let form = if count == 1 { "one" } else { "other" };
+    let count = count.to_string();
+    let mut args = args.to_vec();
+    args.push(("count", &count));
+    tr(locale, &format!("{}_{}", key, form), &args)
+}
+
+fn translate(
+    locales: &HashMap<String, Locale>,
+    locale: Option<&str>,
+    key: &str,
+    args: &[(&str, &str)],
+) -> String {
+    let message = locale
+        .and_then(|locale| locales.get(locale))
+        .and_then(|locale| locale.messages.get(key))
+        .or_else(|| {
+            locales
+                .get(FALLBACK_LOCALE)
+                .and_then(|locale| locale.messages.get(key))
+        });
+
+    let Some(message) = message else {
+        tracing::warn!(key, "missing message");
+        return key.to_string();
+    };
+
+    args.iter().fold(message.clone(), |message, (name, value)| {
+        message.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+#[cfg(test)]
+mod localization_test {
+    use super::*;
+
+    fn test_locales() -> HashMap<String, Locale> {
+        let english = Locale {
+            commands: HashMap::new(),
+            messages: HashMap::from([
+                ("greeting".to_string(), "Hello {name}".to_string()),
+                ("farewell".to_string(), "Bye".to_string()),
+            ]),
+        };
+        let spanish = Locale {
+            commands: HashMap::from([(
+                "generate".to_string(),
+                CommandLocalization {
+                    name: Some("generar".to_string()),
+                    description: Some("Genera texto".to_string()),
+                },
+            )]),
+            messages: HashMap::from([("greeting".to_string(), "Hola {name}".to_string())]),
+        };
+        HashMap::from([
+            ("en-US".to_string(), english),
+            ("es-ES".to_string(), spanish),
+        ])
+    }
+
+    #[test]
+    fn commands_are_decorated_with_localizations() {
+        let mut commands = crate::registered_commands();
+        localize_commands(&mut commands, &test_locales());
+
+        let generate = commands
+            .iter()
+            .find(|command| command.name == "generate")
+            .unwrap();
+        assert_eq!(
+            generate.name_localizations.get("es-ES").map(String::as_str),
+            Some("generar")
+        );
+        assert_eq!(
+            generate
+                .description_localizations
+                .get("es-ES")
+                .map(String::as_str),
+            Some("Genera texto")
+        );
+
+        let untranslated = commands
+            .iter()
+            .find(|command| command.name == "random_entry")
+            .unwrap();
+        assert!(untranslated.name_localizations.is_empty());
+        assert!(untranslated.description_localizations.is_empty());
+    }
+
+    #[test]
+    fn missing_messages_fall_back_to_english_then_the_key() {
+        let locales = test_locales();
+        let args = [("name", "Jane")];
+
+        assert_eq!(
+            translate(&locales, Some("es-ES"), "greeting", &args),
+            "Hola Jane"
+        );
+        assert_eq!(translate(&locales, Some("es-ES"), "farewell", &[]), "Bye");
+        assert_eq!(
+            translate(&locales, Some("fr"), "greeting", &args),
+            "Hello Jane"
+        );
+        assert_eq!(translate(&locales, None, "greeting", &args), "Hello Jane");
+        assert_eq!(
+            translate(&locales, Some("es-ES"), "missing", &[]),
+            "missing"
+        );
+    }
+
+    #[test]
+    fn bundled_locales_are_valid() {
+        let locales = locales();
+        let english = &locales[FALLBACK_LOCALE];
+        let commands = crate::registered_commands();
+
+        for (code, locale) in locales {
+            for key in locale.messages.keys() {
+                assert!(english.messages.contains_key(key), "{} {}", code, key);
+            }
+            for (command, localization) in &locale.commands {
+                assert!(
+                    commands
+                        .iter()
+                        .any(|registered| registered.name == *command),
+                    "{} {}",
+                    code,
+                    command
+                );
+                // Discord refuses to register names or descriptions outside of these limits
+                if let Some(name) = &localization.name {
+                    assert!(
+                        (1..=32).contains(&name.chars().count())
+                            && name.chars().all(|c| c.is_lowercase()
+                                || c.is_numeric()
+                                || c == '_'
+                                || c == '-'),
+                        "{} {}",
+                        code,
+                        name
+                    );
+                }
+                if let Some(description) = &localization.description {
+                    assert!(
+                        (1..=100).contains(&description.chars().count()),
+                        "{} {}",
+                        code,
+                        description
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::Instant;
+
+use poise::FrameworkError;
+use serenity::all::{ChannelId, GuildId, UserId};
+use tracing::{Span, field};
+use tracing_subscriber::{EnvFilter, fmt::format::FmtSpan};
+
+use crate::{
+    Context, Data, Error,
+    error_reports::{ErrorReport, MAX_ARGUMENTS_LENGTH, sanitize},
+    io_format::context_extension::ContextExtension,
+    localization::tr,
+};
+
+/// Used when `RUST_LOG` is not set
+pub const DEFAULT_LOG_FILTER: &str = "info,funboy_core=debug,funboy_discord=debug";
+
+/// Set `LOG_FORMAT` to this to write logs as JSON lines
+pub const JSON_LOG_FORMAT: &str = "json";
+
+/// Installs the global subscriber
+///
+/// Filtering follows `RUST_LOG` and `LOG_FORMAT=json` switches to JSON output.
+/// Closed spans are logged so the duration of each Funboy call shows up.
+pub fn init_tracing() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let json = std::env::var("LOG_FORMAT").is_ok_and(|format| format == JSON_LOG_FORMAT);
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+
+    if json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
+/// The span a command invocation is logged in, kept in the invocation data between hooks
+pub struct CommandSpan {
+    pub span: Span,
+    pub started: Instant,
+}
+
+/// Creates the span for a command invocation, guild is left empty in direct messages
+pub fn command_span(
+    command: &str,
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+) -> Span {
+    let span = tracing::info_span!(
+        "command",
+        command = command,
+        user = user_id.get(),
+        guild = field::Empty,
+        channel = channel_id.get(),
+    );
+    if let Some(guild_id) = guild_id {
+        span.record("guild", guild_id.get());
+    }
+    span
+}
+
+pub async fn pre_command(ctx: Context<'_>) {
+    let span = command_span(
+        &ctx.command().qualified_name,
+        ctx.author().id,
+        ctx.guild_id(),
+        ctx.channel_id(),
+    );
+    span.in_scope(|| tracing::info!("command started"));
+
+    ctx.set_invocation_data(CommandSpan {
+        span,
+        started: Instant::now(),
+    })
+    .await;
+}
+
+pub async fn post_command(ctx: Context<'_>) {
+    if let Some(command_span) = ctx.invocation_data::<CommandSpan>().await {
+        command_span.span.in_scope(|| {
+            tracing::info!(
+                elapsed_ms = command_span.started.elapsed().as_millis() as u64,
+                "command finished"
+            )
+        });
+    }
+}
+
+/// Logs framework errors in the span of the failing command
+///
+/// Errors and panics of command bodies get a generic reply and are reported to operators, every
+/// other error is handled by poise
+pub async fn on_error(error: FrameworkError<'_, Data, Error>) {
+    let span = match error.ctx() {
+        Some(ctx) => match ctx.invocation_data::<CommandSpan>().await {
+            Some(command_span) => command_span.span.clone(),
+            None => command_span(
+                &ctx.command().qualified_name,
+                ctx.author().id,
+                ctx.guild_id(),
+                ctx.channel_id(),
+            ),
+        },
+        None => Span::none(),
+    };
+    span.in_scope(|| tracing::error!(error = %error, "command failed"));
+
+    match error {
+        FrameworkError::Command { error, ctx, .. } => {
+            report_failure(ctx, error.to_string(), false).await
+        }
+        FrameworkError::CommandPanic { payload, ctx, .. } => {
+            let payload = payload.unwrap_or_else(|| "panic without a message".to_string());
+            report_failure(ctx, payload, true).await
+        }
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                tracing::error!(error = %e, "failed to report command error");
+            }
+        }
+    }
+}
+
+async fn report_failure(ctx: Context<'_>, error: String, panicked: bool) {
+    if let Err(e) = ctx
+        .say_ephemeral(&tr(ctx.locale(), "command_failed", &[]))
+        .await
+    {
+        tracing::error!(error = %e, "failed to reply to failed command");
+    }
+
+    if let Some(reporter) = &ctx.data().error_reporter {
+        let report = ErrorReport {
+            command: ctx.command().qualified_name.clone(),
+            user_id: ctx.author().id.get(),
+            guild_id: ctx.guild_id().map(|guild_id| guild_id.get()),
+            channel_id: ctx.channel_id().get(),
+            arguments: sanitize(&ctx.invocation_string(), MAX_ARGUMENTS_LENGTH),
+            error,
+            panicked,
+        };
+        reporter.report(&ctx.serenity_context().http, &report).await;
+    }
+}
+
+#[cfg(test)]
+mod logging_test {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn log_in_command_span(guild_id: Option<GuildId>) -> String {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_ansi(false)
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            command_span("generate", UserId::new(1), guild_id, ChannelId::new(3))
+                .in_scope(|| tracing::info!("command started"));
+        });
+
+        String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn command_span_has_invocation_fields() {
+        let output = log_in_command_span(Some(GuildId::new(2)));
+        assert!(output.contains("command=\"generate\""), "{}", output);
+        assert!(output.contains("user=1"), "{}", output);
+        assert!(output.contains("guild=2"), "{}", output);
+        assert!(output.contains("channel=3"), "{}", output);
+    }
+
+    #[test]
+    fn command_span_in_direct_messages() {
+        let output = log_in_command_span(None);
+        assert!(output.contains("user=1"), "{}", output);
+        assert!(!output.contains("guild="), "{}", output);
+    }
+}
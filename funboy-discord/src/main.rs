@@ -1,74 +1,101 @@
-use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
-    time::Duration,
-};
+use std::{sync::Arc, time::Duration};
 
-use ::serenity::all::{FullEvent, Interaction, UserId};
+use ::serenity::all::{FullEvent, Interaction};
 use dotenvy::dotenv;
 use funboy_core::{
     Funboy,
-    ollama::{OllamaGenerator, OllamaSettings},
-    template_database::TemplateDatabase,
+    self_test::SelfTestSpec,
+    template_database::{
+        DB_URL_SCHEMES, DbPool, DbPoolOptions, TemplateDatabase, is_supported_db_url,
+    },
+    triggers::TriggerCooldowns,
 };
 use poise::serenity_prelude as serenity;
 use reqwest::Client as HttpClient;
 use songbird::{SerenityInit, typemap::TypeMapKey};
-use sqlx::{PgPool, postgres::PgPoolOptions};
 use tokio::sync::Mutex;
 
 use crate::{
-    commands::sound::TrackList,
-    components::{CustomComponent, TrackComponent},
+    command_sync::RegistrationMode,
+    commands::sound::{TrackButtonGuard, TrackList},
+    components::{
+        AddSubstituteModal, CustomComponent, CustomModal, EditSubstituteModal,
+        QuickGenerateComponent, TrackComponent,
+    },
+    error_reports::{ErrorReporter, ReportTarget},
+    featured_posts::post_featured_templates_periodically,
+    interpreter::{INTERPRETER_COMMAND_NAMES, VIRTUAL_TEMPLATE_NAMES},
     rate_limiter::RateLimit,
+    session_vars::SessionVars,
 };
 
+mod alerts;
+mod channel_resolver;
+mod command_help;
+mod command_sync;
 mod commands;
 mod components;
+mod contributors;
+mod error_reports;
+mod featured_posts;
+mod generate_channels;
 mod interpreter;
 mod io_format;
+mod localization;
+mod logging;
+mod message_triggers;
+mod public_server;
 mod rate_limiter;
+mod session_vars;
+
+/// Generations with more code than this many bytes are interpreted off the async workers
+const BLOCKING_INTERPRETATION_THRESHOLD: usize = 2000;
+/// How often template uses counted in memory are written to the database
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
 
-pub type OllamaUserSettingsMap = HashMap<UserId, OllamaSettings>;
-
-struct OllamaData {
-    pub users: Mutex<HashSet<UserId>>,
-    pub generator: Mutex<OllamaGenerator>,
-    pub user_settings: Arc<Mutex<OllamaUserSettingsMap>>,
-}
-
-impl Default for OllamaData {
-    fn default() -> Self {
-        Self {
-            users: Default::default(),
-            generator: Default::default(),
-            user_settings: Default::default(),
-        }
-    }
-}
-
 struct Data {
     pub funboy: Arc<Funboy>,
     pub track_list: Arc<Mutex<TrackList>>,
     pub track_player_lock: Arc<Mutex<()>>,
-    pub ollama_data: OllamaData,
+    #[cfg(feature = "ollama")]
+    pub ollama_data: commands::ollama::OllamaData,
     pub interpreter_rate_limit: Arc<Mutex<RateLimit>>,
+    pub track_button_guard: Arc<Mutex<TrackButtonGuard>>,
+    pub session_vars: SessionVars,
+    pub trigger_cooldowns: Arc<Mutex<TriggerCooldowns>>,
+    /// Reports failed commands to operators when a channel or webhook is configured
+    pub error_reporter: Option<ErrorReporter>,
+    /// Cases run by `/self_test` and at startup
+    pub self_test_spec: SelfTestSpec,
     yt_dlp_cookies_path: Option<String>,
 } // User data, which is stored and accessible in all command invocations
 
 impl Data {
-    pub fn new(pool: Arc<PgPool>) -> Self {
+    pub fn new(pool: Arc<DbPool>) -> Self {
         Self {
-            funboy: Arc::new(Funboy::new(TemplateDatabase::new(pool.clone()))),
+            funboy: Arc::new(
+                Funboy::new(TemplateDatabase::new(pool.clone()))
+                    .with_reserved_names(INTERPRETER_COMMAND_NAMES)
+                    .with_reserved_names(VIRTUAL_TEMPLATE_NAMES)
+                    .with_blocking_threshold(BLOCKING_INTERPRETATION_THRESHOLD),
+            ),
             track_list: Mutex::new(TrackList::new()).into(),
             track_player_lock: Default::default(),
-            ollama_data: OllamaData::default(),
+            #[cfg(feature = "ollama")]
+            ollama_data: Default::default(),
             interpreter_rate_limit: Arc::new(Mutex::new(
                 RateLimit::new(15, 20).with_timeout(60, 4),
             )),
+            track_button_guard: Arc::new(Mutex::new(TrackButtonGuard::new(
+                RateLimit::new(5, 10).with_timeout(30, 3),
+            ))),
+            session_vars: SessionVars::default(),
+            trigger_cooldowns: Default::default(),
+            error_reporter: None,
+            self_test_spec: SelfTestSpec::default(),
             yt_dlp_cookies_path: None,
         }
     }
@@ -88,15 +115,104 @@ impl TypeMapKey for HttpKey {
     type Value = HttpClient;
 }
 
-#[poise::command(prefix_command)]
+/// Registers the slash commands of the bot with Discord
+#[poise::command(prefix_command, help_text_fn = "crate::command_help::register")]
 pub async fn register(ctx: Context<'_>) -> Result<(), Error> {
     poise::builtins::register_application_commands_buttons(ctx).await?;
     Ok(())
 }
 
+/// Every command the bot registers
+fn registered_commands() -> Vec<poise::Command<Data, Error>> {
+    #[allow(unused_mut)]
+    let mut commands = vec![
+        register(),
+        commands::templates::generate(),
+        commands::templates::debug_generate(),
+        commands::templates::rename_template(),
+        commands::templates::rewrite_references(),
+        commands::templates::add_subs(),
+        commands::templates::add_message_sub(),
+        commands::templates::upload_sub(),
+        commands::templates::copy_subs(),
+        commands::templates::clone_template(),
+        commands::templates::set_template_cap(),
+        commands::templates::set_template_strategy(),
+        commands::templates::set_fsl_permissions(),
+        commands::templates::alias(),
+        commands::templates::allow_generate_channel(),
+        commands::templates::disallow_generate_channel(),
+        commands::templates::list_generate_channels(),
+        commands::templates::add_trigger(),
+        commands::templates::remove_trigger(),
+        commands::templates::list_triggers(),
+        commands::templates::set_daily_template_channel(),
+        commands::templates::set_template_examples(),
+        commands::templates::preview_template(),
+        commands::templates::set_template_description(),
+        commands::templates::template_info(),
+        commands::templates::audit_template(),
+        commands::templates::test_sub(),
+        commands::templates::replace_sub(),
+        commands::templates::edit_sub(),
+        commands::templates::favorite_template(),
+        commands::templates::unfavorite_template(),
+        commands::templates::quick_generate(),
+        commands::templates::delete_subs(),
+        commands::templates::disable_subs(),
+        commands::templates::enable_subs(),
+        commands::templates::delete_templates(),
+        commands::templates::delete_templates_matching(),
+        commands::templates::list_subs(),
+        commands::templates::find_source(),
+        commands::templates::list_templates(),
+        commands::templates::template_leaderboard(),
+        commands::templates::contributors(),
+        commands::templates::my_contributions(),
+        commands::templates::session_vars(),
+        commands::templates::clear_session(),
+        commands::random::random_number(),
+        commands::random::random_entry(),
+        commands::sound::join_voice(),
+        commands::sound::leave_voice(),
+        commands::sound::play_track(),
+        commands::sound::stop_tracks(),
+        commands::sound::list_tracks(),
+        commands::sound::track_history(),
+        commands::sound::set_track_announcements(),
+        commands::utility::help(),
+        commands::utility::help_command(),
+        commands::utility::move_bot_pins(),
+        commands::utility::age(),
+        commands::utility::cache_debug(),
+        commands::utility::self_test(),
+    ];
+
+    #[cfg(feature = "ollama")]
+    commands.extend([
+        commands::ollama::list_ollama_models(),
+        commands::ollama::set_ollama_model(),
+        commands::ollama::list_ollama_settings(),
+        commands::ollama::set_ollama_word_limit(),
+        commands::ollama::set_ollama_parameters(),
+        commands::ollama::set_ollama_system_prompt(),
+        commands::ollama::reset_ollama_system_prompt(),
+        commands::ollama::set_ollama_template(),
+        commands::ollama::reset_ollama_template(),
+        commands::ollama::reset_ollama_parameters(),
+        commands::ollama::generate_ollama(),
+        commands::ollama::set_ai_label(),
+        commands::ollama::save_prompt_preset(),
+        commands::ollama::list_prompt_presets(),
+    ]);
+
+    commands
+}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    logging::init_tracing();
 
     let intents = serenity::GatewayIntents::non_privileged()
         | serenity::GatewayIntents::MESSAGE_CONTENT
@@ -108,15 +224,23 @@ async fn main() {
         .parse::<bool>()
         .expect("DEBUG_MODE must be of type bool");
     let db_url = if debug_mode == false {
-        println!("Launching in release mode.");
+        tracing::info!("launching in release mode");
         std::env::var("DATABASE_URL").expect("missing DATABASE_URL")
     } else {
-        println!("Launching in debug mode.");
+        tracing::info!("launching in debug mode");
         std::env::var("DEBUG_DATABASE_URL").expect("missing DATABASE_URL")
     };
 
+    // The scheme of the url picks the backend, sqlite: urls need the sqlite feature
+    if !is_supported_db_url(&db_url) {
+        panic!(
+            "database url must start with one of {:?} for this build, enable the sqlite feature to use SQLite",
+            DB_URL_SCHEMES
+        );
+    }
+
     let pool = Arc::new(
-        PgPoolOptions::new()
+        DbPoolOptions::new()
             .max_connections(15)
             .min_connections(2)
             .acquire_timeout(Duration::from_secs(5))
@@ -131,43 +255,38 @@ async fn main() {
         .await
         .expect("sqlx migration failed");
 
+    // Alerts about generations crossing thresholds are only posted when a channel is given
+    let audit_channel_id = std::env::var("AUDIT_CHANNEL_ID").ok().map(|id| {
+        serenity::ChannelId::new(id.parse().expect("AUDIT_CHANNEL_ID must be a channel id"))
+    });
+
+    let registration_mode = RegistrationMode::from_env();
+    let self_test_on_startup = std::env::var("SELF_TEST_ON_STARTUP")
+        .unwrap_or("false".to_string())
+        .parse::<bool>()
+        .expect("SELF_TEST_ON_STARTUP must be of type bool");
+
+    let mut data = Data::new(pool);
+    data.error_reporter = ReportTarget::from_env().map(ErrorReporter::new);
+    // Cases are read from a JSON file so they can change without a rebuild
+    if let Ok(path) = std::env::var("SELF_TEST_SPEC") {
+        let json = std::fs::read_to_string(&path).expect("failed to read SELF_TEST_SPEC");
+        data.self_test_spec =
+            SelfTestSpec::from_json(&json).expect("SELF_TEST_SPEC must be a valid self test spec");
+    }
+    let funboy = data.funboy.clone();
+    tokio::spawn(flush_usage_periodically(funboy.clone()));
+    // Public listings are only served when an address is given
+    if let Some(addr) = public_server::addr_from_env() {
+        tokio::spawn(public_server::serve_public_templates(addr, funboy.clone()));
+    }
+
+    let mut commands = registered_commands();
+    localization::localize_commands(&mut commands, localization::locales());
+
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![
-                register(),
-                commands::templates::generate(),
-                commands::templates::rename_template(),
-                commands::templates::add_subs(),
-                commands::templates::upload_sub(),
-                commands::templates::copy_subs(),
-                commands::templates::replace_sub(),
-                commands::templates::delete_subs(),
-                commands::templates::delete_templates(),
-                commands::templates::list_subs(),
-                commands::templates::list_templates(),
-                commands::random::random_number(),
-                commands::random::random_entry(),
-                commands::sound::join_voice(),
-                commands::sound::leave_voice(),
-                commands::sound::play_track(),
-                commands::sound::stop_tracks(),
-                commands::sound::list_tracks(),
-                commands::utility::help(),
-                commands::utility::help_command(),
-                commands::utility::move_bot_pins(),
-                commands::utility::age(),
-                commands::ollama::list_ollama_models(),
-                commands::ollama::set_ollama_model(),
-                commands::ollama::list_ollama_settings(),
-                commands::ollama::set_ollama_word_limit(),
-                commands::ollama::set_ollama_parameters(),
-                commands::ollama::set_ollama_system_prompt(),
-                commands::ollama::reset_ollama_system_prompt(),
-                commands::ollama::set_ollama_template(),
-                commands::ollama::reset_ollama_template(),
-                commands::ollama::reset_ollama_parameters(),
-                commands::ollama::generate_ollama(),
-            ],
+            commands,
             event_handler: |ctx, event, _framework_ctx, data| {
                 Box::pin(async move {
                     match event {
@@ -182,19 +301,90 @@ async fn main() {
                                 )
                                 .await?;
                             }
+                            CustomComponent::QuickGenerate => {
+                                commands::templates::on_quick_generate_select(
+                                    ctx,
+                                    QuickGenerateComponent::new(component_interaction.clone()),
+                                    data,
+                                )
+                                .await?;
+                            }
                             CustomComponent::None => {}
                         },
+                        FullEvent::InteractionCreate {
+                            interaction: Interaction::Modal(modal_interaction),
+                        } => match CustomModal::from(modal_interaction) {
+                            CustomModal::AddSubstitute => {
+                                commands::templates::on_add_substitute_modal_submit(
+                                    ctx,
+                                    AddSubstituteModal::new(modal_interaction.clone()),
+                                    data,
+                                )
+                                .await?;
+                            }
+                            CustomModal::EditSubstitute => {
+                                commands::templates::on_edit_substitute_modal_submit(
+                                    ctx,
+                                    EditSubstituteModal::new(modal_interaction.clone()),
+                                    data,
+                                )
+                                .await?;
+                            }
+                            CustomModal::None => {}
+                        },
+                        FullEvent::Message { new_message } => {
+                            message_triggers::on_message(ctx, new_message, data).await?;
+                        }
                         _ => {}
                     }
                     Ok(())
                 })
             },
+            pre_command: |ctx| Box::pin(logging::pre_command(ctx)),
+            post_command: |ctx| Box::pin(logging::post_command(ctx)),
+            on_error: |error| Box::pin(logging::on_error(error)),
             ..Default::default()
         })
-        .setup(|_ctx, _ready, _framework| {
+        .setup(|ctx, _ready, framework| {
             Box::pin(async move {
-                // poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data::new(pool))
+                command_sync::sync_commands(
+                    &ctx.http,
+                    &framework.options().commands,
+                    &registration_mode,
+                    data.error_reporter.as_ref(),
+                )
+                .await;
+                tokio::spawn(post_featured_templates_periodically(
+                    ctx.http.clone(),
+                    data.funboy.clone(),
+                ));
+                if let Some(channel_id) = audit_channel_id {
+                    tokio::spawn(alerts::forward_alerts(
+                        ctx.http.clone(),
+                        channel_id,
+                        data.funboy.subscribe_events(),
+                    ));
+                }
+                if self_test_on_startup {
+                    let report = data.funboy.self_test(&data.self_test_spec).await;
+                    if report.is_healthy() {
+                        tracing::info!(passed = report.passed(), "self test passed");
+                    } else {
+                        tracing::error!(summary = %report.summary(), "self test failed");
+                    }
+                    if let Some(reporter) = &data.error_reporter {
+                        reporter
+                            .notify(&ctx.http, &format!("```\n{}\n```", report.summary()))
+                            .await;
+                    }
+                }
+                match data.funboy.prune_playback_history().await {
+                    Ok(deleted) => tracing::info!(deleted, "pruned playback history"),
+                    Err(e) => {
+                        tracing::warn!(error = %e.to_string(), "failed to prune playback history")
+                    }
+                }
+                Ok(data)
             })
         })
         .build();
@@ -204,5 +394,35 @@ async fn main() {
         .register_songbird()
         .type_map_insert::<HttpKey>(HttpClient::new())
         .await;
-    client.unwrap().start().await.unwrap();
+    let mut client = client.unwrap();
+
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            tracing::info!("shutting down");
+            shard_manager.shutdown_all().await;
+        }
+    });
+
+    client.start().await.unwrap();
+    flush_usage(&funboy).await;
+}
+
+async fn flush_usage(funboy: &Funboy) {
+    if let Err(e) = funboy.flush_usage().await {
+        tracing::warn!(error = %e.to_string(), "failed to flush template usage");
+    }
+    if let Err(e) = funboy.flush_selection_state().await {
+        tracing::warn!(error = %e.to_string(), "failed to flush selection state");
+    }
+}
+
+/// Writes template uses and round robin and least recently used picks every
+/// [`USAGE_FLUSH_INTERVAL`], the rest are written on shutdown
+async fn flush_usage_periodically(funboy: Arc<Funboy>) {
+    let mut interval = tokio::time::interval(USAGE_FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        flush_usage(&funboy).await;
+    }
 }
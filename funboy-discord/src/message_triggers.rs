@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+use serenity::all::{Context as SerenityContext, Message};
+
+use crate::{
+    Data, Error,
+    interpreter::{CommandPermissions, InterpreterContext, create_interpreter},
+    io_format::discord_message_format::{DISCORD_CHARACTER_LIMIT, truncate_on_char_boundary},
+    rate_limiter::RateLimitResult,
+};
+
+/// Whether message could set off a trigger
+///
+/// Messages from bots and webhooks never do so replies to triggers, including ones repeating the
+/// trigger phrase, can't set off more replies
+pub fn is_trigger_candidate(message: &Message) -> bool {
+    message.guild_id.is_some()
+        && !message.author.bot
+        && message.webhook_id.is_none()
+        && !message.content.trim().is_empty()
+}
+
+/// Replies to a message matching a trigger of its guild with a generation of the trigger input
+///
+/// Nothing is sent while the trigger is cooling down or the author is rate limited
+pub async fn on_message(
+    ctx: &SerenityContext,
+    message: &Message,
+    data: &Data,
+) -> Result<(), Error> {
+    if !is_trigger_candidate(message) {
+        return Ok(());
+    }
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+
+    let trigger = match data
+        .funboy
+        .match_trigger(guild_id.get(), &message.content)
+        .await
+    {
+        Ok(Some(trigger)) => trigger,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            tracing::warn!(error = %e.to_string(), "failed to match triggers");
+            return Ok(());
+        }
+    };
+
+    if !data
+        .trigger_cooldowns
+        .lock()
+        .await
+        .try_fire(&trigger, Instant::now())
+    {
+        return Ok(());
+    }
+    if !matches!(
+        data.interpreter_rate_limit
+            .lock()
+            .await
+            .check(message.author.id),
+        RateLimitResult::Ok
+    ) {
+        return Ok(());
+    }
+
+    // Anyone can set off a trigger so commands with side effects are left out
+    let ictx = InterpreterContext::from_message(ctx, message, data);
+    let funboy = ictx.funboy.clone();
+    let output = funboy
+        .generate(
+            &trigger.template_input,
+            create_interpreter(ictx, CommandPermissions::RESTRICTED),
+        )
+        .await;
+
+    match output {
+        Ok(output) if output.is_effectively_empty => {}
+        Ok(output) => {
+            message
+                .reply(
+                    ctx,
+                    truncate_on_char_boundary(&output.text, DISCORD_CHARACTER_LIMIT),
+                )
+                .await?;
+        }
+        Err(e) => {
+            tracing::warn!(error = %e.to_string(), trigger_id = trigger.id, "trigger failed to generate");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod message_triggers_test {
+    use serenity::all::{GuildId, WebhookId};
+
+    use super::*;
+
+    fn message(content: &str) -> Message {
+        let mut message = Message::default();
+        message.guild_id = Some(GuildId::new(1));
+        message.content = content.to_string();
+        message
+    }
+
+    #[test]
+    fn bots_and_webhooks_cant_set_off_triggers() {
+        assert!(is_trigger_candidate(&message("who's that pokemon")));
+        assert!(!is_trigger_candidate(&message("  ")));
+
+        let mut direct = message("who's that pokemon");
+        direct.guild_id = None;
+        assert!(!is_trigger_candidate(&direct));
+
+        let mut reply = message("who's that pokemon? it's pikachu");
+        reply.author.bot = true;
+        assert!(!is_trigger_candidate(&reply));
+
+        let mut webhook = message("who's that pokemon");
+        webhook.webhook_id = Some(WebhookId::new(1));
+        assert!(!is_trigger_candidate(&webhook));
+    }
+}
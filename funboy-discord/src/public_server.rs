@@ -0,0 +1,315 @@
+//! Serves the read-only listings of [`funboy_core::public_browse`] over HTTP
+//!
+//! Only started when PUBLIC_BROWSE_ADDR is set. Each address is rate limited and pages are
+//! answered with 304 Not Modified when the ETag the client sends is still current
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use funboy_core::{
+    Funboy, FunboyError,
+    public_browse::{self, PUBLIC_TEMPLATES_PATH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+};
+
+use crate::rate_limiter::{RateLimit, RateLimitResult};
+
+/// Longest request head read before the request is refused
+const MAX_HEAD_LEN: usize = 8 * 1024;
+/// How long a client has to send its request head
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reads the address to serve public listings on from PUBLIC_BROWSE_ADDR, None when unset
+pub fn addr_from_env() -> Option<SocketAddr> {
+    let addr = std::env::var("PUBLIC_BROWSE_ADDR").ok()?;
+    Some(
+        addr.parse()
+            .expect("PUBLIC_BROWSE_ADDR must be an address such as 0.0.0.0:8080"),
+    )
+}
+
+/// Accepts connections on addr until the listener fails
+pub async fn serve_public_templates(addr: SocketAddr, funboy: Arc<Funboy>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!(error = %e, %addr, "failed to bind public browse listener");
+            return;
+        }
+    };
+    tracing::info!(%addr, "serving public template listings");
+
+    let rate_limit = Arc::new(Mutex::new(
+        RateLimit::<IpAddr>::new(30, 10).with_timeout(60, 5),
+    ));
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to accept public browse connection");
+                continue;
+            }
+        };
+        let funboy = funboy.clone();
+        let rate_limit = rate_limit.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, peer.ip(), &funboy, &rate_limit).await {
+                tracing::debug!(error = %e, %peer, "public browse connection failed");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    ip: IpAddr,
+    funboy: &Funboy,
+    rate_limit: &Mutex<RateLimit<IpAddr>>,
+) -> std::io::Result<()> {
+    let head = match tokio::time::timeout(READ_TIMEOUT, read_head(&mut stream)).await {
+        Ok(head) => head?,
+        Err(_) => return Ok(()),
+    };
+
+    let response = match head.as_deref().and_then(Request::parse) {
+        None => Response::status(400, "Bad Request"),
+        Some(request) => match rate_limit.lock().await.check(ip) {
+            RateLimitResult::Ok => respond(funboy, &request).await,
+            _ => Response::status(429, "Too Many Requests"),
+        },
+    };
+    stream.write_all(&response.to_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Reads up to the blank line ending the request head, None when it is too long or cut off
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || head.len() + read > MAX_HEAD_LEN {
+            return Ok(None);
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    Ok(String::from_utf8(head).ok())
+}
+
+/// The parts of a request head the public pages look at
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    query: Option<String>,
+    if_none_match: Option<String>,
+}
+
+impl Request {
+    fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next()?.split(' ');
+        let method = request_line.next()?.to_string();
+        let target = request_line.next()?;
+        if !request_line.next()?.starts_with("HTTP/1.") {
+            return None;
+        }
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (target, None),
+        };
+
+        let if_none_match = lines.find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("if-none-match")
+                .then(|| value.trim().to_string())
+        });
+
+        Some(Self {
+            method,
+            path: path.to_string(),
+            query,
+            if_none_match,
+        })
+    }
+
+    /// The page asked for with ?page=, 1 when none is given and None when it isn't a number
+    fn page(&self) -> Option<usize> {
+        let Some(query) = &self.query else {
+            return Some(1);
+        };
+        match query.split('&').find_map(|pair| pair.strip_prefix("page=")) {
+            Some(page) => page.parse().ok(),
+            None => Some(1),
+        }
+    }
+
+    fn is_current(&self, etag: &str) -> bool {
+        self.if_none_match
+            .as_deref()
+            .is_some_and(|if_none_match| public_browse::etag_matches(if_none_match, etag))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Response {
+    status: u16,
+    reason: &'static str,
+    etag: Option<String>,
+    body: String,
+}
+
+impl Response {
+    fn status(status: u16, reason: &'static str) -> Self {
+        Self {
+            status,
+            reason,
+            etag: None,
+            body: format!("{} {}\n", status, reason),
+        }
+    }
+
+    fn page(etag: String, body: String) -> Self {
+        Self {
+            status: 200,
+            reason: "OK",
+            etag: Some(etag),
+            body,
+        }
+    }
+
+    fn not_modified(etag: String) -> Self {
+        Self {
+            status: 304,
+            reason: "Not Modified",
+            etag: Some(etag),
+            body: String::new(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, self.reason);
+        if let Some(etag) = &self.etag {
+            // Clients revalidate every time, which is cheap as only the version is read
+            head.push_str(&format!("ETag: {}\r\nCache-Control: no-cache\r\n", etag));
+        }
+        if self.status == 405 {
+            head.push_str("Allow: GET\r\n");
+        }
+        if self.status != 304 {
+            head.push_str(&format!(
+                "Content-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n",
+                self.body.len()
+            ));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(self.body.as_bytes());
+        bytes
+    }
+}
+
+async fn respond(funboy: &Funboy, request: &Request) -> Response {
+    if request.method != "GET" {
+        return Response::status(405, "Method Not Allowed");
+    }
+    let result = match request.path.strip_prefix(PUBLIC_TEMPLATES_PATH) {
+        Some("" | "/") => respond_listing(funboy, request).await,
+        Some(rest) => match (rest.strip_prefix('/'), request.page()) {
+            (Some(template), Some(page)) if !template.contains('/') => {
+                respond_template(funboy, request, template, page).await
+            }
+            _ => Ok(Response::status(404, "Not Found")),
+        },
+        None => Ok(Response::status(404, "Not Found")),
+    };
+    result.unwrap_or_else(|e| {
+        tracing::warn!(error = %e.to_string(), path = %request.path, "failed to serve public page");
+        Response::status(500, "Internal Server Error")
+    })
+}
+
+async fn respond_listing(funboy: &Funboy, request: &Request) -> Result<Response, FunboyError> {
+    let etag = funboy.public_listing_etag().await?;
+    if request.is_current(&etag) {
+        return Ok(Response::not_modified(etag));
+    }
+    let templates = funboy.public_templates().await?;
+    Ok(Response::page(
+        etag,
+        public_browse::render_template_list(&templates),
+    ))
+}
+
+async fn respond_template(
+    funboy: &Funboy,
+    request: &Request,
+    template: &str,
+    page: usize,
+) -> Result<Response, FunboyError> {
+    let etag = funboy.public_template_etag(template, page).await?;
+    if request.is_current(&etag) {
+        return Ok(Response::not_modified(etag));
+    }
+    Ok(match funboy.public_template_page(template, page).await? {
+        Some(page) => Response::page(etag, public_browse::render_template_page(&page)),
+        None => Response::status(404, "Not Found"),
+    })
+}
+
+#[cfg(test)]
+mod public_server_test {
+    use super::*;
+
+    #[test]
+    fn parses_request_head() {
+        let request = Request::parse(
+            "GET /public/templates/animal?page=2 HTTP/1.1\r\nHost: funboy\r\nif-none-match: W/\"0-3-2\"\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/public/templates/animal");
+        assert_eq!(request.page(), Some(2));
+        assert!(request.is_current("W/\"0-3-2\""));
+        assert!(!request.is_current("W/\"0-4-2\""));
+
+        let request = Request::parse("GET /public/templates HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.page(), Some(1));
+        assert_eq!(request.if_none_match, None);
+
+        let request = Request::parse("GET /public/templates?page=two HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.page(), None);
+
+        assert_eq!(Request::parse("GET /public/templates\r\n\r\n"), None);
+        assert_eq!(Request::parse("nonsense"), None);
+    }
+
+    #[test]
+    fn writes_responses() {
+        let response = Response::page("W/\"1-2-0\"".to_string(), "<p>hi</p>".to_string());
+        let bytes = String::from_utf8(response.to_bytes()).unwrap();
+        assert!(bytes.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(bytes.contains("ETag: W/\"1-2-0\"\r\n"));
+        assert!(bytes.contains("Content-Length: 9\r\n"));
+        assert!(bytes.ends_with("\r\n\r\n<p>hi</p>"));
+
+        let response = Response::not_modified("W/\"1-2-0\"".to_string());
+        let bytes = String::from_utf8(response.to_bytes()).unwrap();
+        assert!(bytes.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+        assert!(!bytes.contains("Content-Length"));
+        assert!(bytes.ends_with("\r\n\r\n"));
+
+        let response = Response::status(405, "Method Not Allowed");
+        let bytes = String::from_utf8(response.to_bytes()).unwrap();
+        assert!(bytes.contains("Allow: GET\r\n"));
+    }
+}
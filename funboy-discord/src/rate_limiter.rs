@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    hash::Hash,
     time::{Duration, SystemTime},
 };
 
@@ -22,9 +23,10 @@ impl Uses {
     }
 }
 
+/// Limits how often each key may be used, such as a user or a user paired with an action
 #[derive(Debug, Clone)]
-pub struct RateLimit {
-    users: HashMap<UserId, Uses>,
+pub struct RateLimit<K = UserId> {
+    users: HashMap<K, Uses>,
     uses_per_interval: usize,
     interval: u64,
     limits_before_timeout: u16,
@@ -37,7 +39,7 @@ pub enum RateLimitResult {
     Ok,
 }
 
-impl RateLimit {
+impl<K: Eq + Hash> RateLimit<K> {
     pub fn new(uses_per_interval: usize, interval: u64) -> Self {
         Self {
             users: HashMap::new(),
@@ -54,11 +56,11 @@ impl RateLimit {
         self
     }
 
-    pub fn check(&mut self, user_id: UserId) -> RateLimitResult {
+    pub fn check(&mut self, key: K) -> RateLimitResult {
         let now = SystemTime::now();
         let usage_window = now - Duration::from_secs(self.interval);
 
-        let uses = self.users.entry(user_id).or_insert_with(Uses::new);
+        let uses = self.users.entry(key).or_insert_with(Uses::new);
 
         uses.time_stamps.retain(|&t| t > usage_window);
 
@@ -0,0 +1,168 @@
+use std::{collections::HashMap, time::Duration};
+
+use funboy_core::{Funboy, user_facing_error::UserFacingError};
+use moka::future::{Cache, CacheBuilder};
+use serenity::all::UserId;
+
+use crate::io_format::discord_message_format::DISCORD_CHARACTER_LIMIT;
+
+/// How long a session lasts after a variable was last saved
+pub const SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+pub const MAX_SESSIONS: u64 = 10_000;
+pub const MAX_SESSION_VARS: usize = 20;
+pub const MAX_SESSION_VAR_LENGTH: usize = DISCORD_CHARACTER_LIMIT;
+
+/// Generation outputs each user saved with `/generate save_as:` to read back in later code
+#[derive(Clone)]
+pub struct SessionVars {
+    sessions: Cache<UserId, HashMap<String, String>>,
+}
+
+impl Default for SessionVars {
+    fn default() -> Self {
+        Self::new(SESSION_TTL)
+    }
+}
+
+impl SessionVars {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            sessions: CacheBuilder::new(MAX_SESSIONS).time_to_live(ttl).build(),
+        }
+    }
+
+    /// Stores value under name replacing any earlier value with the same name
+    pub async fn save(
+        &self,
+        user_id: UserId,
+        name: &str,
+        value: &str,
+    ) -> Result<(), UserFacingError> {
+        Funboy::validate_user_variable_name(name)?;
+        if value.len() > MAX_SESSION_VAR_LENGTH {
+            return Err(UserFacingError::VariableTooLong {
+                name: name.to_string(),
+                length: value.len(),
+                limit: MAX_SESSION_VAR_LENGTH,
+            });
+        }
+
+        let mut vars = self.sessions.get(&user_id).await.unwrap_or_default();
+        if !vars.contains_key(name) && vars.len() >= MAX_SESSION_VARS {
+            return Err(UserFacingError::TooManyVariables {
+                limit: MAX_SESSION_VARS,
+            });
+        }
+
+        vars.insert(name.to_string(), value.to_string());
+        self.sessions.insert(user_id, vars).await;
+        Ok(())
+    }
+
+    /// The variables of a user sorted by name
+    pub async fn get(&self, user_id: UserId) -> Vec<(String, String)> {
+        let mut vars: Vec<(String, String)> = self
+            .sessions
+            .get(&user_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    /// Removes every variable of a user returning how many there were
+    pub async fn clear(&self, user_id: UserId) -> usize {
+        self.sessions
+            .remove(&user_id)
+            .await
+            .map_or(0, |vars| vars.len())
+    }
+}
+
+#[cfg(test)]
+mod session_vars_test {
+    use super::*;
+
+    const USER: UserId = UserId::new(1);
+
+    #[tokio::test]
+    async fn saved_vars_are_listed_by_name() {
+        let session_vars = SessionVars::default();
+        session_vars.save(USER, "villain", "Morgana").await.unwrap();
+        session_vars.save(USER, "hero", "Arthur").await.unwrap();
+        session_vars.save(USER, "hero", "Lancelot").await.unwrap();
+
+        assert_eq!(
+            session_vars.get(USER).await,
+            [
+                ("hero".to_string(), "Lancelot".to_string()),
+                ("villain".to_string(), "Morgana".to_string()),
+            ]
+        );
+        assert!(session_vars.get(UserId::new(2)).await.is_empty());
+
+        assert_eq!(session_vars.clear(USER).await, 2);
+        assert!(session_vars.get(USER).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sessions_expire() {
+        let session_vars = SessionVars::new(Duration::from_millis(50));
+        session_vars.save(USER, "hero", "Arthur").await.unwrap();
+        assert_eq!(session_vars.get(USER).await.len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(session_vars.get(USER).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn vars_are_capped() {
+        let session_vars = SessionVars::default();
+
+        let too_long = "a".repeat(MAX_SESSION_VAR_LENGTH + 1);
+        assert_eq!(
+            session_vars.save(USER, "story", &too_long).await,
+            Err(UserFacingError::VariableTooLong {
+                name: "story".to_string(),
+                length: MAX_SESSION_VAR_LENGTH + 1,
+                limit: MAX_SESSION_VAR_LENGTH,
+            })
+        );
+        assert!(
+            session_vars
+                .save(USER, "story", &too_long[1..])
+                .await
+                .is_ok()
+        );
+
+        for i in 1..MAX_SESSION_VARS {
+            session_vars
+                .save(USER, &format!("var_{}", i), "value")
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            session_vars.save(USER, "one_more", "value").await,
+            Err(UserFacingError::TooManyVariables {
+                limit: MAX_SESSION_VARS
+            })
+        );
+        // replacing a var doesn't add to the count
+        assert!(session_vars.save(USER, "story", "short").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn names_must_be_identifiers() {
+        let session_vars = SessionVars::default();
+        assert!(matches!(
+            session_vars.save(USER, "Last Character", "value").await,
+            Err(UserFacingError::VariableNameInvalid { .. })
+        ));
+        assert!(matches!(
+            session_vars.save(USER, "ctx_author", "value").await,
+            Err(UserFacingError::VariableReserved { .. })
+        ));
+    }
+}